@@ -11,6 +11,8 @@
   #define GET_LOCAL_ID() threadIdx.x
   #define GET_LOCAL_SIZE() blockDim.x
   #define BARRIER_LOCAL() __syncthreads()
+  #define BARRIER_GLOBAL() __syncthreads()
+  #define ATOMIC_ADD(p, v) atomicAdd(p, v)
 
   typedef unsigned char uchar;
 
@@ -27,12 +29,22 @@
   #define GET_LOCAL_ID() get_local_id(0)
   #define GET_LOCAL_SIZE() get_local_size(0)
   #define BARRIER_LOCAL() barrier(CLK_LOCAL_MEM_FENCE)
+  #define BARRIER_GLOBAL() barrier(CLK_GLOBAL_MEM_FENCE)
+  #define ATOMIC_ADD(p, v) atomic_add(p, v)
 #endif
 
 #ifdef __NV_CL_C_VERSION
 #define OPENCL_NVIDIA
 #endif
 
+// The alignment of the field element types, if it differs from the one of
+// their limbs, see `SourceBuilder::field_alignment`.
+#ifdef ELEMENT_ALIGNMENT
+#define ELEMENT_ALIGN __attribute__((aligned(ELEMENT_ALIGNMENT)))
+#else
+#define ELEMENT_ALIGN
+#endif
+
 #if defined(__WinterPark__) || defined(__BeaverCreek__) || defined(__Turks__) || \
     defined(__Caicos__) || defined(__Tahiti__) || defined(__Pitcairn__) || \
     defined(__Capeverde__) || defined(__Cayman__) || defined(__Barts__) || \
@@ -43,6 +55,15 @@
 #define AMD
 #endif
 
+// 128-bit integers are an extension of some OpenCL compilers. They are only
+// used if requested with `SourceBuilder::opencl_use_u128` and supported by
+// the compiler, otherwise the portable code is used. `OPENCL_NO_U128`
+// simulates a compiler without support in the tests.
+#if !defined(__NVCC__) && defined(OPENCL_USE_U128) && \
+    defined(__SIZEOF_INT128__) && !defined(OPENCL_NO_U128)
+#define OPENCL_U128
+#endif
+
 // Returns a * b + c + d, puts the carry in d
 DEVICE ulong mac_with_carry_64(ulong a, ulong b, ulong c, ulong *d) {
   #if defined(OPENCL_NVIDIA) || defined(CUDA)
@@ -54,6 +75,10 @@ DEVICE ulong mac_with_carry_64(ulong a, ulong b, ulong c, ulong *d) {
         : "=l"(lo), "=l"(hi) : "l"(a), "l"(b), "l"(c), "l"(*d));
     *d = hi;
     return lo;
+  #elif defined(OPENCL_U128)
+    const unsigned __int128 res = (unsigned __int128)a * b + c + *d;
+    *d = (ulong)(res >> 64);
+    return (ulong)res;
   #else
     ulong lo = a * b + c;
     ulong hi = mad_hi(a, b, (ulong)(lo < c));
@@ -104,6 +129,37 @@ DEVICE uint add_with_carry_32(uint a, uint *b) {
   #endif
 }
 
+// Copies the `len` 32-bit words of `src` into `dst`, starting at `dst[offset]`.
+// Buffers can only be written as a whole, hence large uploads go through a
+// smaller staging buffer, which is copied into place with this kernel.
+KERNEL void copy_words_to_offset(GLOBAL uint *src,
+                                 GLOBAL uint *dst,
+                                 uint offset,
+                                 uint len) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= len) return;
+  dst[offset + gid] = src[gid];
+}
+
+// Writes the FNV-1a hash of every chunk of `chunk_len` of the `len` 32-bit
+// words of `data` to `results`. It's used to verify uploads without reading
+// the whole buffer back.
+KERNEL void checksum_words(GLOBAL uint *data,
+                           uint len,
+                           uint chunk_len,
+                           GLOBAL uint *results,
+                           uint num_chunks) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= num_chunks) return;
+  const uint start = gid * chunk_len;
+  const uint end = min(start + chunk_len, len);
+  uint hash = 2166136261u;
+  for(uint i = start; i < end; i++) {
+    hash = (hash ^ data[i]) * 16777619u;
+  }
+  results[gid] = hash;
+}
+
 // Reverse the given bits. It's used by the FFT kernel.
 DEVICE uint bitreverse(uint n, uint bits) {
   uint r = 0;
@@ -118,7 +174,7 @@ DEVICE uint bitreverse(uint n, uint bits) {
 // CUDA doesn't support local buffers ("dynamic shared memory" in CUDA lingo) as function
 // arguments, but only a single globally defined extern value. Use `uchar` so that it is always
 // allocated by the number of bytes.
-extern __shared__ uchar cuda_shared[];
+extern __shared__ uchar ELEMENT_ALIGN cuda_shared[];
 
 typedef uint uint32_t;
 typedef int  int32_t;
@@ -0,0 +1,55 @@
+// Compression of curve points into the encoding of arkworks'
+// `CanonicalSerialize`, for curves over a prime field. A point is encoded as
+// the canonical encoding of its `x` coordinate (see `field-bytes.cl`), with
+// flags in the spare bits of one of the bytes. The byte order and the flags
+// depend on the curve, e.g. the zcash encoding of BLS12-381 is big-endian and
+// always sets the highest bit, hence they are arguments of the kernel.
+
+// Returns whether `y` is larger than `-y` as an integer, which is the sign of a
+// point in the compressed encoding.
+DEVICE bool POINT_y_is_larger(BASE y) {
+  const BASE_repr a = BASE_unmont(y);
+  const BASE_repr b = BASE_unmont(BASE_sub(BASE_ZERO, y));
+  for(uint i = BASE_LIMBS; i-- > 0;) {
+    if(a.val[i] != b.val[i]) return a.val[i] > b.val[i];
+  }
+  return false;
+}
+
+// Writes the compressed encodings of the `n` affine `points` one after another
+// to `bytes`, `BASE_BYTES` each. The point at infinity is (0, 0), it's encoded
+// like `x = 0`. The bytes of `x` are reversed if `big_endian` is set, then the
+// flags are ORed into the byte `flag_byte`: `always` into every encoding,
+// `infinity` into the one of the point at infinity and `sign` into the ones of
+// the other points with `y > -y`.
+KERNEL void POINT_compress(GLOBAL POINT_affine *points,
+                           GLOBAL uchar *bytes,
+                           uint n,
+                           uint big_endian,
+                           uint flag_byte,
+                           uint always,
+                           uint sign,
+                           uint infinity) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= n) return;
+
+  const POINT_affine p = points[gid];
+  GLOBAL uchar *encoding = bytes + gid * BASE_BYTES;
+  BASE_write_bytes(p.x, encoding);
+  if(big_endian) {
+    for(uint i = 0; i < BASE_BYTES / 2; i++) {
+      const uchar byte = encoding[i];
+      encoding[i] = encoding[BASE_BYTES - 1 - i];
+      encoding[BASE_BYTES - 1 - i] = byte;
+    }
+  }
+
+  const BASE local_zero = BASE_ZERO;
+  uint flags = always;
+  if(BASE_eq(p.x, local_zero) && BASE_eq(p.y, local_zero)) {
+    flags |= infinity;
+  } else if(POINT_y_is_larger(p.y)) {
+    flags |= sign;
+  }
+  encoding[flag_byte] |= (uchar)flags;
+}
@@ -1,26 +1,21 @@
 /*
- * FFT algorithm for G1 is inspired from: http://www.bealto.com/gpu-fft_group-1.html
+ * A single round of `POINT_radix_fft`, `u` is the local buffer. If `coset`
+ * is set, the inputs are multiplied by the powers of `shift[0]` when they
+ * are loaded, which is only valid in the first round.
  */
-KERNEL void POINT_radix_fft(GLOBAL POINT_jacobian* x, // Source buffer
-                      GLOBAL POINT_jacobian* y, // Destination buffer
-                      GLOBAL SCALAR* pq, // Precalculated twiddle factors
-                      GLOBAL SCALAR* omegas, // [omega, omega^2, omega^4, ...]
-                      LOCAL POINT_jacobian* u_arg, // Local buffer to store intermediary values
-                      uint n, // Number of elements
-                      uint lgp, // Log2 of `p` (Read more in the link above)
-                      uint deg, // 1=>radix2, 2=>radix4, 3=>radix8, ...
-                      uint vbs, // Virtual block size, the algorithm may require a small block size, which will damage the performance
-                      uint max_deg) // Maximum degree supported, according to `pq` and `omegas`
+DEVICE void POINT_radix_fft_round(GLOBAL POINT_jacobian* x,
+                                  GLOBAL POINT_jacobian* y,
+                                  GLOBAL SCALAR* pq,
+                                  GLOBAL SCALAR* omegas,
+                                  LOCAL POINT_jacobian* u,
+                                  uint n,
+                                  uint lgp,
+                                  uint deg,
+                                  uint vbs,
+                                  uint max_deg,
+                                  bool coset,
+                                  GLOBAL SCALAR* shift)
 {
-// CUDA doesn't support local buffers ("shared memory" in CUDA lingo) as function arguments,
-// ignore that argument and use the globally defined extern memory instead.
-#ifdef CUDA
-  // There can only be a single dynamic shared memory item, hence cast it to the type we need.
-  POINT_jacobian* u = (POINT_jacobian*)cuda_shared;
-#else
-  LOCAL POINT_jacobian* u = u_arg;
-#endif
-
   uint lid = GET_LOCAL_ID();
   uint lsize = GET_LOCAL_SIZE();
   uint index = GET_GROUP_ID();
@@ -46,9 +41,21 @@ KERNEL void POINT_radix_fft(GLOBAL POINT_jacobian* x, // Source buffer
   // Compute powers of twiddle
   const SCALAR twiddle = SCALAR_pow_lookup(omegas, (n >> lgp >> deg) * k);
   SCALAR tmp = SCALAR_pow(twiddle, counts);
-  for(uint i = counts; i < counte; i++) {
-    u[i] = POINT_mul(x[i*t], tmp);
-    tmp = SCALAR_mul(tmp, twiddle);
+  if(coset) {
+    // In the first round the input `i` is the coefficient `index + i * t`.
+    const SCALAR shift_step = SCALAR_pow(shift[0], t);
+    SCALAR shift_pow = SCALAR_mul(SCALAR_pow(shift[0], index),
+                                  SCALAR_pow(shift_step, counts));
+    for(uint i = counts; i < counte; i++) {
+      u[i] = POINT_mul(x[i*t], SCALAR_mul(tmp, shift_pow));
+      tmp = SCALAR_mul(tmp, twiddle);
+      shift_pow = SCALAR_mul(shift_pow, shift_step);
+    }
+  } else {
+    for(uint i = counts; i < counte; i++) {
+      u[i] = POINT_mul(x[i*t], tmp);
+      tmp = SCALAR_mul(tmp, twiddle);
+    }
   }
   BARRIER_LOCAL();
 
@@ -74,3 +81,77 @@ KERNEL void POINT_radix_fft(GLOBAL POINT_jacobian* x, // Source buffer
     y[(i+counth)*p] = u[bitreverse(i + counth, deg)];
   }
 }
+
+/*
+ * FFT algorithm for G1 is inspired from: http://www.bealto.com/gpu-fft_group-1.html
+ */
+KERNEL void POINT_radix_fft(GLOBAL POINT_jacobian* x, // Source buffer
+                      GLOBAL POINT_jacobian* y, // Destination buffer
+                      GLOBAL SCALAR* pq, // Precalculated twiddle factors
+                      GLOBAL SCALAR* omegas, // [omega, omega^2, omega^4, ...]
+                      LOCAL POINT_jacobian* u_arg, // Local buffer to store intermediary values
+                      uint n, // Number of elements
+                      uint lgp, // Log2 of `p` (Read more in the link above)
+                      uint deg, // 1=>radix2, 2=>radix4, 3=>radix8, ...
+                      uint vbs, // Virtual block size, the algorithm may require a small block size, which will damage the performance
+                      uint max_deg) // Maximum degree supported, according to `pq` and `omegas`
+{
+// CUDA doesn't support local buffers ("shared memory" in CUDA lingo) as function arguments,
+// ignore that argument and use the globally defined extern memory instead.
+#ifdef CUDA
+  // There can only be a single dynamic shared memory item, hence cast it to the type we need.
+  POINT_jacobian* u = (POINT_jacobian*)cuda_shared;
+#else
+  LOCAL POINT_jacobian* u = u_arg;
+#endif
+  POINT_radix_fft_round(x, y, pq, omegas, u, n, lgp, deg, vbs, max_deg, false,
+                        pq);
+}
+
+/*
+ * Like `POINT_radix_fft`, but the inputs are multiplied by the powers of
+ * `shift[0]`, i.e. it's the first round of an FFT over the coset
+ * `shift * <omega>`.
+ */
+KERNEL void POINT_radix_fft_coset(GLOBAL POINT_jacobian* x,
+                                  GLOBAL POINT_jacobian* y,
+                                  GLOBAL SCALAR* pq,
+                                  GLOBAL SCALAR* omegas,
+                                  LOCAL POINT_jacobian* u_arg,
+                                  uint n,
+                                  uint lgp,
+                                  uint deg,
+                                  uint vbs,
+                                  uint max_deg,
+                                  GLOBAL SCALAR* shift)
+{
+#ifdef CUDA
+  POINT_jacobian* u = (POINT_jacobian*)cuda_shared;
+#else
+  LOCAL POINT_jacobian* u = u_arg;
+#endif
+  POINT_radix_fft_round(x, y, pq, omegas, u, n, lgp, deg, vbs, max_deg, true,
+                        shift);
+}
+
+/// Multiplies `points[i]` by `params[0] * params[1]^i` for all `i < n`
+///
+/// It moves the results of an inverse FFT back from a coset and scales them
+/// by `n^-1` at the same time.
+KERNEL void POINT_distribute_powers(GLOBAL POINT_jacobian* points,
+                                    GLOBAL SCALAR* params,
+                                    uint n) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= n) return;
+  const SCALAR factor = SCALAR_mul(params[0], SCALAR_pow(params[1], gid));
+  points[gid] = POINT_mul(points[gid], factor);
+}
+
+/// Adds the points of `b` to the ones of `a`, element by element
+KERNEL void POINT_add_many(GLOBAL POINT_jacobian* a,
+                           GLOBAL POINT_jacobian* b,
+                           uint n) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= n) return;
+  a[gid] = POINT_add(a[gid], b[gid]);
+}
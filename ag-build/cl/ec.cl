@@ -145,4 +145,74 @@ DEVICE POINT_jacobian POINT_mul_exponent(POINT_jacobian base, SCALAR_repr exp) {
 
 DEVICE POINT_jacobian POINT_mul(POINT_jacobian base, SCALAR exp) {
   return POINT_mul_exponent(base, SCALAR_unmont(exp));
-}
\ No newline at end of file
+}
+// Converts the points into affine form. Every thread converts `chunk_len`
+// consecutive points with a single inversion (Montgomery's trick), `scratch`
+// holds the partial products and has the same length as `points`. The point
+// at infinity results in (0, 0), the GPU representation of the identity.
+KERNEL void POINT_normalize_many(GLOBAL POINT_jacobian *points,
+                                 GLOBAL POINT_affine *results,
+                                 GLOBAL BASE *scratch,
+                                 uint n,
+                                 uint chunk_len) {
+  const uint gid = GET_GLOBAL_ID();
+  const uint start = gid * chunk_len;
+  if(start >= n) return;
+  const uint end = min(start + chunk_len, n);
+  const BASE local_zero = BASE_ZERO;
+
+  BASE acc = BASE_ONE;
+  for(uint i = start; i < end; i++) {
+    scratch[i] = acc;
+    if(!BASE_eq(points[i].z, local_zero)) acc = BASE_mul(acc, points[i].z);
+  }
+
+  BASE inv = BASE_inverse(acc);
+  for(uint i = end; i-- > start;) {
+    const POINT_jacobian p = points[i];
+    if(BASE_eq(p.z, local_zero)) {
+      results[i].x = local_zero;
+      results[i].y = local_zero;
+      continue;
+    }
+    const BASE z_inv = BASE_mul(inv, scratch[i]);
+    inv = BASE_mul(inv, p.z);
+    const BASE z_inv2 = BASE_sqr(z_inv);
+    results[i].x = BASE_mul(p.x, z_inv2);
+    results[i].y = BASE_mul(p.y, BASE_mul(z_inv2, z_inv));
+  }
+}
+
+// Checks whether the `points` satisfy the curve equation `y^2 = x^3 + b[0]`,
+// the coefficient `a` is zero. The point at infinity is represented as (0, 0)
+// and is on the curve.
+KERNEL void POINT_check_on_curve(GLOBAL POINT_affine *points,
+                                 GLOBAL BASE *b,
+                                 GLOBAL uint *results,
+                                 uint n) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= n) return;
+  const POINT_affine p = points[gid];
+  const BASE local_zero = BASE_ZERO;
+  if(BASE_eq(p.x, local_zero) && BASE_eq(p.y, local_zero)) {
+    results[gid] = 1;
+    return;
+  }
+  const BASE lhs = BASE_sqr(p.y);
+  const BASE rhs = BASE_add(BASE_mul(BASE_sqr(p.x), p.x), b[0]);
+  results[gid] = BASE_eq(lhs, rhs) ? 1 : 0;
+}
+
+// Multiplies `base[0]` by `s^i` for every `i` in `start..start + n`. The
+// powers are calculated independently by every thread from `s_powers`, which
+// holds `s^(2^j)` for all 32 bits of the exponent.
+KERNEL void POINT_powers_of_tau(GLOBAL POINT_jacobian *base,
+                                GLOBAL SCALAR *s_powers,
+                                GLOBAL POINT_jacobian *results,
+                                uint start,
+                                uint n) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= n) return;
+  const SCALAR s_i = SCALAR_pow_lookup(s_powers, start + gid);
+  results[gid] = POINT_mul(base[0], s_i);
+}
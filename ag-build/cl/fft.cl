@@ -1,34 +1,62 @@
+// The maps that can be applied to the outputs of an FFT, they need to match
+// `FftPostMap` on the host.
+#ifndef FFT_POST_MAP_NONE
+#define FFT_POST_MAP_NONE 0
+#define FFT_POST_MAP_SQUARE 1
+#define FFT_POST_MAP_NEGATE 2
+#define FFT_POST_MAP_ADD_CONST 3
+#define FFT_POST_MAP_MUL_CONST 4
+#endif
+
+// Applies the map `post_map` to an output of an FFT, `post_const[0]` is the
+// constant of the maps that have one.
+DEVICE FIELD FIELD_post_map(FIELD a, uint post_map, GLOBAL FIELD* post_const) {
+  switch(post_map) {
+    case FFT_POST_MAP_SQUARE: return FIELD_sqr(a);
+    case FFT_POST_MAP_NEGATE: return FIELD_sub(FIELD_ZERO, a);
+    case FFT_POST_MAP_ADD_CONST: return FIELD_add(a, post_const[0]);
+    case FFT_POST_MAP_MUL_CONST: return FIELD_mul(a, post_const[0]);
+    default: return a;
+  }
+}
+
 /*
  * FFT algorithm is inspired from: http://www.bealto.com/gpu-fft_group-1.html
+ *
+ * A single round of `FIELD_radix_fft`, `u` is the local buffer. If
+ * `cache_twiddles` is set, the twiddle factors of the round are loaded into
+ * the local buffer `tw` of `2^(deg - 1)` elements once, instead of reading
+ * them from global memory in every butterfly. If `coset` is set, the inputs
+ * are multiplied by the powers of `shift[0]` when they are loaded, which is
+ * only valid in the first round. `index` is the work group within the FFT.
  */
-KERNEL void FIELD_radix_fft(GLOBAL FIELD* x, // Source buffer
-                      GLOBAL FIELD* y, // Destination buffer
-                      GLOBAL FIELD* pq, // Precalculated twiddle factors
-                      GLOBAL FIELD* omegas, // [omega, omega^2, omega^4, ...]
-                      LOCAL FIELD* u_arg, // Local buffer to store intermediary values
-                      uint n, // Number of elements
-                      uint lgp, // Log2 of `p` (Read more in the link above)
-                      uint deg, // 1=>radix2, 2=>radix4, 3=>radix8, ...
-                      uint max_deg) // Maximum degree supported, according to `pq` and `omegas`
+DEVICE void FIELD_radix_fft_round(GLOBAL FIELD* x,
+                                  GLOBAL FIELD* y,
+                                  GLOBAL FIELD* pq,
+                                  GLOBAL FIELD* omegas,
+                                  LOCAL FIELD* u,
+                                  LOCAL FIELD* tw,
+                                  bool cache_twiddles,
+                                  uint n,
+                                  uint lgp,
+                                  uint deg,
+                                  uint max_deg,
+                                  uint x_stride,
+                                  uint y_stride,
+                                  uint post_map,
+                                  GLOBAL FIELD* post_const,
+                                  bool coset,
+                                  GLOBAL FIELD* shift,
+                                  uint index)
 {
-// CUDA doesn't support local buffers ("shared memory" in CUDA lingo) as function arguments,
-// ignore that argument and use the globally defined extern memory instead.
-#ifdef CUDA
-  // There can only be a single dynamic shared memory item, hence cast it to the type we need.
-  FIELD* u = (FIELD*)cuda_shared;
-#else
-  LOCAL FIELD* u = u_arg;
-#endif
-
   uint lid = GET_LOCAL_ID();
   uint lsize = GET_LOCAL_SIZE();
-  uint index = GET_GROUP_ID();
   uint t = n >> deg;
   uint p = 1 << lgp;
   uint k = index & (p - 1);
 
-  x += index;
-  y += ((index - k) << deg) + k;
+  x += index * x_stride;
+  y += (((index - k) << deg) + k) * y_stride;
 
   uint count = 1 << deg; // 2^deg
   uint counth = count >> 1; // Half of count
@@ -36,16 +64,36 @@ KERNEL void FIELD_radix_fft(GLOBAL FIELD* x, // Source buffer
   uint counts = count / lsize * lid;
   uint counte = counts + count / lsize;
 
+  const uint pqshift = max_deg - deg;
+  // The butterflies only use `pq[j << pqshift]` for `j < counth`. The barrier
+  // below makes them visible to the whole work group.
+  if(cache_twiddles) {
+    for(uint j = lid; j < counth; j += lsize) {
+      tw[j] = pq[j << pqshift];
+    }
+  }
+
   // Compute powers of twiddle
   const FIELD twiddle = FIELD_pow_lookup(omegas, (n >> lgp >> deg) * k);
   FIELD tmp = FIELD_pow(twiddle, counts);
-  for(uint i = counts; i < counte; i++) {
-    u[i] = FIELD_mul(tmp, x[i*t]);
-    tmp = FIELD_mul(tmp, twiddle);
+  if(coset) {
+    // In the first round the input `i` is the coefficient `index + i * t`.
+    const FIELD shift_step = FIELD_pow(shift[0], t);
+    FIELD shift_pow = FIELD_mul(FIELD_pow(shift[0], index),
+                                FIELD_pow(shift_step, counts));
+    for(uint i = counts; i < counte; i++) {
+      u[i] = FIELD_mul(tmp, FIELD_mul(shift_pow, x[i*t*x_stride]));
+      tmp = FIELD_mul(tmp, twiddle);
+      shift_pow = FIELD_mul(shift_pow, shift_step);
+    }
+  } else {
+    for(uint i = counts; i < counte; i++) {
+      u[i] = FIELD_mul(tmp, x[i*t*x_stride]);
+      tmp = FIELD_mul(tmp, twiddle);
+    }
   }
   BARRIER_LOCAL();
 
-  const uint pqshift = max_deg - deg;
   for(uint rnd = 0; rnd < deg; rnd++) {
     const uint bit = counth >> rnd;
     for(uint i = counts >> 1; i < counte >> 1; i++) {
@@ -55,16 +103,252 @@ KERNEL void FIELD_radix_fft(GLOBAL FIELD* x, // Source buffer
       tmp = u[i0];
       u[i0] = FIELD_add(u[i0], u[i1]);
       u[i1] = FIELD_sub(tmp, u[i1]);
-      if(di != 0) u[i1] = FIELD_mul(pq[di << rnd << pqshift], u[i1]);
+      if(di != 0) {
+        const FIELD w = cache_twiddles ? tw[di << rnd] : pq[di << rnd << pqshift];
+        u[i1] = FIELD_mul(w, u[i1]);
+      }
     }
 
     BARRIER_LOCAL();
   }
 
   for(uint i = counts >> 1; i < counte >> 1; i++) {
-    y[i*p] = u[bitreverse(i, deg)];
-    y[(i+counth)*p] = u[bitreverse(i + counth, deg)];
+    y[i*p*y_stride] = FIELD_post_map(u[bitreverse(i, deg)], post_map, post_const);
+    y[(i+counth)*p*y_stride] = FIELD_post_map(u[bitreverse(i + counth, deg)], post_map, post_const);
+  }
+}
+
+KERNEL void FIELD_radix_fft(GLOBAL FIELD* x, // Source buffer
+                      GLOBAL FIELD* y, // Destination buffer
+                      GLOBAL FIELD* pq, // Precalculated twiddle factors
+                      GLOBAL FIELD* omegas, // [omega, omega^2, omega^4, ...]
+                      LOCAL FIELD* u_arg, // Local buffer to store intermediary values
+                      uint n, // Number of elements
+                      uint lgp, // Log2 of `p` (Read more in the link above)
+                      uint deg, // 1=>radix2, 2=>radix4, 3=>radix8, ...
+                      uint max_deg, // Maximum degree supported, according to `pq` and `omegas`
+                      uint x_stride, // Distance between two consecutive elements of `x`
+                      uint y_stride, // Distance between two consecutive elements of `y`
+                      uint post_map, // Map applied to the outputs, only used in the last round
+                      GLOBAL FIELD* post_const) // The constant of `post_map`, if it has one
+{
+// CUDA doesn't support local buffers ("shared memory" in CUDA lingo) as function arguments,
+// ignore that argument and use the globally defined extern memory instead.
+#ifdef CUDA
+  // There can only be a single dynamic shared memory item, hence cast it to the type we need.
+  FIELD* u = (FIELD*)cuda_shared;
+#else
+  LOCAL FIELD* u = u_arg;
+#endif
+  FIELD_radix_fft_round(x, y, pq, omegas, u, u, false, n, lgp, deg, max_deg,
+                        x_stride, y_stride, post_map, post_const, false,
+                        post_const, GET_GROUP_ID());
+}
+
+/*
+ * Like `FIELD_radix_fft`, but for a batch of FFTs of `n` elements each, which
+ * are stored back to back in `x` and `y`. The FFT `i` is done by the work
+ * groups `i * (n >> deg)` up to excluding `(i + 1) * (n >> deg)`.
+ */
+KERNEL void FIELD_radix_fft_batch(GLOBAL FIELD* x,
+                                  GLOBAL FIELD* y,
+                                  GLOBAL FIELD* pq,
+                                  GLOBAL FIELD* omegas,
+                                  LOCAL FIELD* u_arg,
+                                  uint n,
+                                  uint lgp,
+                                  uint deg,
+                                  uint max_deg,
+                                  uint post_map,
+                                  GLOBAL FIELD* post_const)
+{
+#ifdef CUDA
+  FIELD* u = (FIELD*)cuda_shared;
+#else
+  LOCAL FIELD* u = u_arg;
+#endif
+  const uint groups = n >> deg;
+  const uint fft = GET_GROUP_ID() / groups;
+  FIELD_radix_fft_round(x + fft * n, y + fft * n, pq, omegas, u, u, false, n,
+                        lgp, deg, max_deg, 1, 1, post_map, post_const, false,
+                        post_const, GET_GROUP_ID() - fft * groups);
+}
+
+/*
+ * Like `FIELD_radix_fft`, but the inputs are multiplied by the powers of
+ * `shift[0]`, i.e. it's the first round of an FFT over the coset
+ * `shift * <omega>`.
+ */
+KERNEL void FIELD_radix_fft_coset(GLOBAL FIELD* x,
+                                  GLOBAL FIELD* y,
+                                  GLOBAL FIELD* pq,
+                                  GLOBAL FIELD* omegas,
+                                  LOCAL FIELD* u_arg,
+                                  uint n,
+                                  uint lgp,
+                                  uint deg,
+                                  uint max_deg,
+                                  uint x_stride,
+                                  uint y_stride,
+                                  uint post_map,
+                                  GLOBAL FIELD* post_const,
+                                  GLOBAL FIELD* shift)
+{
+#ifdef CUDA
+  FIELD* u = (FIELD*)cuda_shared;
+#else
+  LOCAL FIELD* u = u_arg;
+#endif
+  FIELD_radix_fft_round(x, y, pq, omegas, u, u, false, n, lgp, deg, max_deg,
+                        x_stride, y_stride, post_map, post_const, true, shift,
+                        GET_GROUP_ID());
+}
+
+#ifdef FFT_SHARED_TWIDDLES
+/*
+ * Like `FIELD_radix_fft`, but the twiddle factors of the round are cached in
+ * local memory. The local buffer needs to hold `2^deg + 2^(deg - 1)` elements.
+ */
+KERNEL void FIELD_radix_fft_shared_twiddles(GLOBAL FIELD* x,
+                                            GLOBAL FIELD* y,
+                                            GLOBAL FIELD* pq,
+                                            GLOBAL FIELD* omegas,
+                                            LOCAL FIELD* u_arg,
+                                            uint n,
+                                            uint lgp,
+                                            uint deg,
+                                            uint max_deg,
+                                            uint x_stride,
+                                            uint y_stride,
+                                            uint post_map,
+                                            GLOBAL FIELD* post_const)
+{
+#ifdef CUDA
+  FIELD* u = (FIELD*)cuda_shared;
+#else
+  LOCAL FIELD* u = u_arg;
+#endif
+  FIELD_radix_fft_round(x, y, pq, omegas, u, u + (1 << deg), true, n, lgp,
+                        deg, max_deg, x_stride, y_stride, post_map,
+                        post_const, false, post_const, GET_GROUP_ID());
+}
+#endif
+
+/// Performs a whole FFT of `2^log_n` elements in place, within a single work
+/// group, see `FIELD_shared_fft`. If `coset` is set, the inputs are
+/// multiplied by the powers of `shift[0]` when they are loaded.
+DEVICE void FIELD_shared_fft_group(GLOBAL FIELD* x,
+                                   GLOBAL FIELD* omegas,
+                                   LOCAL FIELD* u,
+                                   uint log_n,
+                                   uint stride,
+                                   uint post_map,
+                                   GLOBAL FIELD* post_const,
+                                   bool coset,
+                                   GLOBAL FIELD* shift)
+{
+  const uint lid = GET_LOCAL_ID();
+  const uint lsize = GET_LOCAL_SIZE();
+  const uint n = 1 << log_n;
+  const uint half = n >> 1;
+  LOCAL FIELD* w = u + n;
+
+  if(coset) {
+    const FIELD shift_step = FIELD_pow(shift[0], lsize);
+    FIELD shift_pow = FIELD_pow(shift[0], lid);
+    for(uint i = lid; i < n; i += lsize) {
+      u[bitreverse(i, log_n)] = FIELD_mul(shift_pow, x[i * stride]);
+      shift_pow = FIELD_mul(shift_pow, shift_step);
+    }
+  } else {
+    for(uint i = lid; i < n; i += lsize) {
+      u[bitreverse(i, log_n)] = x[i * stride];
+    }
+  }
+  for(uint i = lid; i < half; i += lsize) {
+    w[i] = FIELD_pow_lookup(omegas, i);
   }
+  BARRIER_LOCAL();
+
+  for(uint rnd = 0; rnd < log_n; rnd++) {
+    const uint m = 1 << rnd;
+    // The twiddle of this round is `omega^(n / (2 * m))`
+    const uint w_shift = log_n - 1 - rnd;
+    for(uint i = lid; i < half; i += lsize) {
+      const uint j = i & (m - 1);
+      const uint i0 = ((i >> rnd) << (rnd + 1)) + j;
+      const uint i1 = i0 + m;
+      const FIELD t = FIELD_mul(w[j << w_shift], u[i1]);
+      u[i1] = FIELD_sub(u[i0], t);
+      u[i0] = FIELD_add(u[i0], t);
+    }
+    BARRIER_LOCAL();
+  }
+
+  for(uint i = lid; i < n; i += lsize) {
+    x[i * stride] = FIELD_post_map(u[i], post_map, post_const);
+  }
+}
+
+/// Performs a whole FFT of `2^log_n` elements in place, within a single work group
+///
+/// All rounds are done in local memory, hence there is only one launch and
+/// no round trips through global memory. As all elements are loaded before
+/// any is stored, the elements may be strided within a larger buffer. The local buffer needs to hold
+/// `n + n / 2` elements: the values and the twiddles `omega^0, ..., omega^(n/2-1)`.
+KERNEL void FIELD_shared_fft(GLOBAL FIELD* x, // Source and destination buffer
+                             GLOBAL FIELD* omegas, // [omega, omega^2, omega^4, ...]
+                             LOCAL FIELD* u_arg, // Local buffer for values and twiddles
+                             uint log_n, // Log2 of the number of elements
+                             uint stride, // Distance between two consecutive elements of `x`
+                             uint post_map, // Map applied to the outputs
+                             GLOBAL FIELD* post_const) // The constant of `post_map`, if it has one
+{
+#ifdef CUDA
+  FIELD* u = (FIELD*)cuda_shared;
+#else
+  LOCAL FIELD* u = u_arg;
+#endif
+  FIELD_shared_fft_group(x, omegas, u, log_n, stride, post_map, post_const,
+                         false, post_const);
+}
+
+/// Like `FIELD_shared_fft`, but over the coset `shift[0] * <omega>`, i.e. the
+/// inputs are multiplied by the powers of `shift[0]` first
+KERNEL void FIELD_shared_fft_coset(GLOBAL FIELD* x,
+                                   GLOBAL FIELD* omegas,
+                                   LOCAL FIELD* u_arg,
+                                   uint log_n,
+                                   uint stride,
+                                   uint post_map,
+                                   GLOBAL FIELD* post_const,
+                                   GLOBAL FIELD* shift)
+{
+#ifdef CUDA
+  FIELD* u = (FIELD*)cuda_shared;
+#else
+  LOCAL FIELD* u = u_arg;
+#endif
+  FIELD_shared_fft_group(x, omegas, u, log_n, stride, post_map, post_const,
+                         true, shift);
+}
+
+/// Like `FIELD_shared_fft`, but for a batch of FFTs of `2^log_n` elements
+/// each, which are stored back to back in `x`. Every work group does one FFT.
+KERNEL void FIELD_shared_fft_batch(GLOBAL FIELD* x,
+                                   GLOBAL FIELD* omegas,
+                                   LOCAL FIELD* u_arg,
+                                   uint log_n,
+                                   uint post_map,
+                                   GLOBAL FIELD* post_const)
+{
+#ifdef CUDA
+  FIELD* u = (FIELD*)cuda_shared;
+#else
+  LOCAL FIELD* u = u_arg;
+#endif
+  FIELD_shared_fft_group(x + (GET_GROUP_ID() << log_n), omegas, u, log_n, 1,
+                         post_map, post_const, false, post_const);
 }
 
 /// Multiplies all of the elements by `field`
@@ -74,3 +358,231 @@ KERNEL void FIELD_mul_by_field(GLOBAL FIELD* elements,
   const uint gid = GET_GLOBAL_ID();
   elements[gid] = FIELD_mul(elements[gid], field);
 }
+
+/// Converts all of the elements from normal into Montgomery form with
+/// `FIELD_mont`, a `FIELD_repr` has the same layout as a `FIELD`
+KERNEL void FIELD_to_mont(GLOBAL FIELD* elements,
+                          uint n) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= n) return;
+  elements[gid] = FIELD_mont(((GLOBAL FIELD_repr*)elements)[gid]);
+}
+
+/// Converts all of the elements from Montgomery into normal form with
+/// `FIELD_unmont`
+KERNEL void FIELD_from_mont(GLOBAL FIELD* elements,
+                            uint n) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= n) return;
+  ((GLOBAL FIELD_repr*)elements)[gid] = FIELD_unmont(elements[gid]);
+}
+
+/// Multiplies all of the elements by `factor[0]`
+///
+/// The factor is passed in a buffer, so that no field element needs to be
+/// passed by value. Passing the Montgomery form of `c * R^-1` multiplies by
+/// `c` and converts into normal form at the same time.
+KERNEL void FIELD_scale(GLOBAL FIELD* elements,
+                        GLOBAL FIELD* factor,
+                        uint n) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= n) return;
+  elements[gid] = FIELD_mul(elements[gid], factor[0]);
+}
+
+/// Subtracts `b[i]` from `a[i]` for all `i < n`
+KERNEL void FIELD_sub_elementwise(GLOBAL FIELD* a,
+                                  GLOBAL FIELD* b,
+                                  uint n) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= n) return;
+  a[gid] = FIELD_sub(a[gid], b[gid]);
+}
+
+/// Adds `r[0] * b[i]` to `a[i]` for all `i < n`
+KERNEL void FIELD_fold(GLOBAL FIELD* a,
+                       GLOBAL FIELD* b,
+                       GLOBAL FIELD* r,
+                       uint n) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= n) return;
+  a[gid] = FIELD_add(a[gid], FIELD_mul(r[0], b[gid]));
+}
+
+/// Sets `result[0]` to 1 if any of the `n` elements is not zero
+///
+/// `result[0]` must be 0 before. Threads only ever write a 1, hence no atomic
+/// operations are needed.
+KERNEL void FIELD_any_nonzero(GLOBAL FIELD* elements,
+                              uint n,
+                              GLOBAL uint* result) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= n) return;
+  if(!FIELD_eq(elements[gid], FIELD_ZERO)) result[0] = 1;
+}
+
+/// Writes `params[0] * params[1]^i` to `results[i]` for all `i < n`
+///
+/// Every thread calculates its power with square-and-multiply, so that no
+/// thread depends on the result of another one.
+KERNEL void FIELD_powers(GLOBAL FIELD* results,
+                         GLOBAL FIELD* params,
+                         uint n) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= n) return;
+
+  FIELD res = params[0];
+  FIELD base = params[1];
+  for(uint exp = gid; exp > 0; exp >>= 1) {
+    if(exp & 1) res = FIELD_mul(res, base);
+    base = FIELD_sqr(base);
+  }
+  results[gid] = res;
+}
+
+/// Multiplies `elements[i]` by `params[0] * params[1]^i` for all `i < n`
+///
+/// The powers are calculated like in `FIELD_powers`. It moves the
+/// coefficients of a polynomial onto a coset before an FFT and back after an
+/// inverse FFT.
+KERNEL void FIELD_distribute_powers(GLOBAL FIELD* elements,
+                                    GLOBAL FIELD* params,
+                                    uint n) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= n) return;
+
+  FIELD res = params[0];
+  FIELD base = params[1];
+  for(uint exp = gid; exp > 0; exp >>= 1) {
+    if(exp & 1) res = FIELD_mul(res, base);
+    base = FIELD_sqr(base);
+  }
+  elements[gid] = FIELD_mul(elements[gid], res);
+}
+
+/// Multiplies `elements[i]` by `factors[i % period]` for all `i < n`
+///
+/// `period` must be a power of two.
+KERNEL void FIELD_mul_periodic(GLOBAL FIELD* elements,
+                               GLOBAL FIELD* factors,
+                               uint period,
+                               uint n) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= n) return;
+  elements[gid] = FIELD_mul(elements[gid], factors[gid & (period - 1)]);
+}
+
+/// Copies the `len` elements of `src` into `dst`, starting at `dst[offset]`
+KERNEL void FIELD_copy_to_offset(GLOBAL FIELD* src,
+                                 GLOBAL FIELD* dst,
+                                 uint offset,
+                                 uint len) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= len) return;
+  dst[offset + gid] = src[gid];
+}
+
+/// Copies `len` elements of `src`, starting at `src[offset]`, into `dst`
+KERNEL void FIELD_copy_from_offset(GLOBAL FIELD* src,
+                                   GLOBAL FIELD* dst,
+                                   uint offset,
+                                   uint len) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= len) return;
+  dst[gid] = src[offset + gid];
+}
+
+/// Evaluates chunks of polynomials at the point `z[0]` with Horner's rule
+///
+/// The coefficients of all polynomials are stored back to back in `coeffs`.
+/// Every chunk is described by three numbers in `chunks`: the index of its
+/// first coefficient, the index after its last coefficient and the index `k`
+/// of the power in `powers`, by which the result is shifted to the position
+/// of the chunk within its polynomial.
+KERNEL void FIELD_batch_eval(GLOBAL FIELD* coeffs,
+                             GLOBAL uint* chunks,
+                             GLOBAL FIELD* powers,
+                             GLOBAL FIELD* z,
+                             GLOBAL FIELD* results,
+                             uint num_chunks) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= num_chunks) return;
+
+  const uint start = chunks[3 * gid];
+  const uint end = chunks[3 * gid + 1];
+  const FIELD x = z[0];
+  FIELD acc = FIELD_ZERO;
+  for(uint i = end; i-- > start;) {
+    acc = FIELD_add(FIELD_mul(acc, x), coeffs[i]);
+  }
+  results[gid] = FIELD_mul(acc, powers[chunks[3 * gid + 2]]);
+}
+
+/// Evaluates chunks of a polynomial of `n` coefficients at several points
+/// with Horner's rule
+///
+/// Thread `gid` evaluates the chunk `gid % num_chunks` of `chunk_len`
+/// coefficients at the point `points[gid / num_chunks]`. The result is
+/// multiplied by `shifts[gid]`, the power of that point which shifts it to
+/// the position of the chunk within the polynomial.
+KERNEL void FIELD_eval_points(GLOBAL FIELD* coeffs,
+                              GLOBAL FIELD* points,
+                              GLOBAL FIELD* shifts,
+                              GLOBAL FIELD* results,
+                              uint n,
+                              uint chunk_len,
+                              uint num_chunks,
+                              uint num_points) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= num_chunks * num_points) return;
+
+  const uint start = (gid % num_chunks) * chunk_len;
+  const uint end = min(start + chunk_len, n);
+  const FIELD x = points[gid / num_chunks];
+  FIELD acc = FIELD_ZERO;
+  for(uint i = end; i-- > start;) {
+    acc = FIELD_add(FIELD_mul(acc, x), coeffs[i]);
+  }
+  results[gid] = FIELD_mul(acc, shifts[gid]);
+}
+
+/// Adds changes of coefficients to their evaluations, as the FFT is linear
+///
+/// Every change consists of the index `indices[k]` of a coefficient and the
+/// difference `deltas[k]` between its new and its old value. `steps[k]` is
+/// `omega^indices[k]`. Every thread updates `chunk_len` consecutive
+/// evaluations, so that only the first power of every change needs a lookup.
+KERNEL void FIELD_fft_update(GLOBAL FIELD* evals,
+                             GLOBAL uint* indices,
+                             GLOBAL FIELD* deltas,
+                             GLOBAL FIELD* steps,
+                             GLOBAL FIELD* omegas, // [omega, omega^2, omega^4, ...]
+                             uint num_changes,
+                             uint n,
+                             uint chunk_len) {
+  const uint gid = GET_GLOBAL_ID();
+  const uint start = gid * chunk_len;
+  if(start >= n) return;
+  const uint end = min(start + chunk_len, n);
+
+  for(uint k = 0; k < num_changes; k++) {
+    // `omega^(indices[k] * start)`, the exponent is reduced modulo `n`.
+    const uint exponent = (uint)(((ulong)indices[k] * start) & (n - 1));
+    FIELD term = FIELD_mul(deltas[k], FIELD_pow_lookup(omegas, exponent));
+    for(uint j = start; j < end; j++) {
+      evals[j] = FIELD_add(evals[j], term);
+      term = FIELD_mul(term, steps[k]);
+    }
+  }
+}
+
+/// Sets `result[0]` to 1 if `omegas[log_n - 1]`, which is `omega^(n/2)`, is
+/// `-1`, else to 0
+KERNEL void FIELD_check_twiddles(GLOBAL FIELD* omegas,
+                                 uint log_n,
+                                 GLOBAL uint* result) {
+  if(GET_GLOBAL_ID() != 0) return;
+
+  const FIELD minus_one = FIELD_sub(FIELD_ZERO, FIELD_ONE);
+  result[0] = FIELD_eq(omegas[log_n - 1], minus_one);
+}
@@ -0,0 +1,53 @@
+// Conversion of field elements from and to their canonical encoding, which is
+// the integer they represent (not in Montgomery form) as `FIELD_BYTES`
+// little-endian bytes, like arkworks' `CanonicalSerialize`.
+
+#define FIELD_LIMB_BYTES (FIELD_LIMB_BITS / 8)
+
+/// Writes the canonical encoding of `value` to the first `FIELD_BYTES` of
+/// `bytes`
+DEVICE void FIELD_write_bytes(FIELD value, GLOBAL uchar* bytes) {
+  const FIELD_repr repr = FIELD_unmont(value);
+  for(uint i = 0; i < FIELD_BYTES; i++) {
+    const FIELD_limb limb = repr.val[i / FIELD_LIMB_BYTES];
+    bytes[i] = (uchar)(limb >> (8 * (i % FIELD_LIMB_BYTES)));
+  }
+}
+
+/// Writes the canonical encodings of the `n` `values` one after another to
+/// `bytes`
+KERNEL void FIELD_to_bytes(GLOBAL FIELD* values,
+                           GLOBAL uchar* bytes,
+                           uint n) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= n) return;
+
+  FIELD_write_bytes(values[gid], bytes + gid * FIELD_BYTES);
+}
+
+/// Reads `n` canonical encodings from `bytes` into `values`
+///
+/// `valid` is set to 1 if an encoding is smaller than the modulus, else to 0,
+/// the value is undefined then.
+KERNEL void FIELD_from_bytes(GLOBAL uchar* bytes,
+                             GLOBAL FIELD* values,
+                             GLOBAL uint* valid,
+                             uint n) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= n) return;
+
+  FIELD_repr repr;
+  for(uint i = 0; i < FIELD_LIMBS; i++) repr.val[i] = 0;
+  for(uint i = 0; i < FIELD_BYTES; i++) {
+    const FIELD_limb byte = bytes[gid * FIELD_BYTES + i];
+    repr.val[i / FIELD_LIMB_BYTES] |= byte << (8 * (i % FIELD_LIMB_BYTES));
+  }
+
+  #ifdef CUDA
+    const FIELD integer = reinterpret_cast<FIELD&>(repr);
+  #else
+    const FIELD integer = * (FIELD *) &repr;
+  #endif
+  valid[gid] = !FIELD_gte(integer, FIELD_P);
+  values[gid] = FIELD_mont(repr);
+}
@@ -0,0 +1,89 @@
+// Prime-field operations that only build on the basic arithmetic, hence they
+// are shared by all field implementations.
+
+// Modular exponentiation (Exponentiation by Squaring)
+// https://en.wikipedia.org/wiki/Exponentiation_by_squaring
+DEVICE FIELD FIELD_pow(FIELD base, uint exponent) {
+  FIELD res = FIELD_ONE;
+  while(exponent > 0) {
+    if (exponent & 1)
+      res = FIELD_mul(res, base);
+    exponent = exponent >> 1;
+    base = FIELD_sqr(base);
+  }
+  return res;
+}
+
+
+// Store squares of the base in a lookup table for faster evaluation.
+DEVICE FIELD FIELD_pow_lookup(GLOBAL FIELD *bases, uint exponent) {
+  FIELD res = FIELD_ONE;
+  uint i = 0;
+  while(exponent > 0) {
+    if (exponent & 1)
+      res = FIELD_mul(res, bases[i]);
+    exponent = exponent >> 1;
+    i++;
+  }
+  return res;
+}
+
+// Modular inverse (Fermat's little theorem), i.e. `a^(p-2)`.
+// The inverse of zero is zero.
+DEVICE FIELD FIELD_inverse(FIELD a) {
+  // Calculate `p - 2`, the borrow is propagated in case the lowest limbs are
+  // smaller than two.
+  FIELD exponent = FIELD_P;
+  FIELD_limb borrow = 2;
+  for(uchar i = 0; i < FIELD_LIMBS && borrow; i++) {
+    const FIELD_limb old = exponent.val[i];
+    exponent.val[i] -= borrow;
+    borrow = old < borrow;
+  }
+
+  FIELD res = FIELD_ONE;
+  for(int i = FIELD_LIMBS - 1; i >= 0; i--) {
+    for(int j = FIELD_LIMB_BITS - 1; j >= 0; j--) {
+      res = FIELD_sqr(res);
+      if((exponent.val[i] >> j) & 1) res = FIELD_mul(res, a);
+    }
+  }
+  return res;
+}
+
+DEVICE FIELD FIELD_mont(FIELD_repr a) {
+  #ifdef CUDA
+    FIELD input = reinterpret_cast<FIELD&>(a);  
+  #else
+    FIELD input = * (FIELD *) &a;
+  #endif
+
+  return FIELD_mul(input, FIELD_R2);
+}
+
+DEVICE FIELD_repr FIELD_unmont(FIELD a) {
+  FIELD unmont = FIELD_mul(a, FIELD_ONE_NORMAL);
+
+  
+  #ifdef CUDA
+    FIELD_repr answer = reinterpret_cast<FIELD_repr&>(unmont);  
+  #else
+    FIELD_repr answer = * (FIELD_repr *) &unmont;
+  #endif
+  return answer;
+}
+
+// Get `i`th bit (From most significant digit) of the field.
+DEVICE bool FIELD_get_bit(FIELD_repr l, uint i) {
+  return (l.val[FIELD_LIMBS - 1 - i / FIELD_LIMB_BITS] >> (FIELD_LIMB_BITS - 1 - (i % FIELD_LIMB_BITS))) & 1;
+}
+
+// Get `window` consecutive bits, (Starting from `skip`th bit) from the field.
+DEVICE uint FIELD_get_bits(FIELD_repr l, uint skip, uint window) {
+  uint ret = 0;
+  for(uint i = 0; i < window; i++) {
+    ret <<= 1;
+    ret |= FIELD_get_bit(l, skip + i);
+  }
+  return ret;
+}
@@ -0,0 +1,164 @@
+// Element-wise operations on batches of field elements.
+
+/// Raises all `bases` to the power of `exponent[0]` (square-and-multiply)
+KERNEL void FIELD_pow_many(GLOBAL FIELD* bases,
+                           GLOBAL FIELD_repr* exponent,
+                           GLOBAL FIELD* results,
+                           uint n) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= n) return;
+
+  const FIELD_repr exp = exponent[0];
+  const FIELD base = bases[gid];
+  FIELD res = FIELD_ONE;
+  for(uint i = 0; i < FIELD_BITS; i++) {
+    res = FIELD_sqr(res);
+    if(FIELD_get_bit(exp, i)) res = FIELD_mul(res, base);
+  }
+  results[gid] = res;
+}
+
+/// Writes the constants of the field to `result`, in the order `FIELD_ZERO`,
+/// `FIELD_ONE`, `FIELD_ONE_NORMAL` and `FIELD_R2`
+KERNEL void FIELD_constants(GLOBAL FIELD* result) {
+  if(GET_GLOBAL_ID() != 0) return;
+  result[0] = FIELD_ZERO;
+  result[1] = FIELD_ONE;
+  result[2] = FIELD_ONE_NORMAL;
+  result[3] = FIELD_R2;
+}
+
+/// Sums up chunks of `values`, in the order given by `order`
+///
+/// Every chunk is described by two numbers in `chunks`: the index of its first
+/// entry in `order` and the index after its last one. All values of a chunk
+/// belong to the same key, the partial sums of the chunks of a key are added
+/// up on the host.
+KERNEL void FIELD_segmented_sum(GLOBAL FIELD* values,
+                                GLOBAL uint* order,
+                                GLOBAL uint* chunks,
+                                GLOBAL FIELD* results,
+                                uint num_chunks) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= num_chunks) return;
+
+  const uint start = chunks[2 * gid];
+  const uint end = chunks[2 * gid + 1];
+  FIELD sum = FIELD_ZERO;
+  for(uint i = start; i < end; i++) {
+    sum = FIELD_add(sum, values[order[i]]);
+  }
+  results[gid] = sum;
+}
+
+/// Writes one plus the index of the last nonzero element of every chunk of
+/// `chunk_len` `elements` to `results`, or 0 if the whole chunk is zero
+///
+/// The maximum over all chunks is taken on the host.
+KERNEL void FIELD_last_nonzero(GLOBAL FIELD* elements,
+                               uint n,
+                               uint chunk_len,
+                               GLOBAL uint* results,
+                               uint num_chunks) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= num_chunks) return;
+
+  const uint start = gid * chunk_len;
+  const uint end = min(start + chunk_len, n);
+  uint last = 0;
+  for(uint i = end; i > start; i--) {
+    if(!FIELD_eq(elements[i - 1], FIELD_ZERO)) {
+      last = i;
+      break;
+    }
+  }
+  results[gid] = last;
+}
+
+/// Inverts every chunk of `chunk_len` `elements` in place, zeros stay zero
+///
+/// Every thread inverts its chunk with Montgomery's trick: the prefix products
+/// of the nonzero elements are stored in `prefix`, the product of the whole
+/// chunk is inverted once, and the inverses are recovered by going backwards
+/// through the chunk.
+KERNEL void FIELD_batch_invert(GLOBAL FIELD* elements,
+                               GLOBAL FIELD* prefix,
+                               uint n,
+                               uint chunk_len,
+                               uint num_chunks) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= num_chunks) return;
+
+  const uint start = gid * chunk_len;
+  const uint end = min(start + chunk_len, n);
+  FIELD acc = FIELD_ONE;
+  for(uint i = start; i < end; i++) {
+    prefix[i] = acc;
+    if(!FIELD_eq(elements[i], FIELD_ZERO)) {
+      acc = FIELD_mul(acc, elements[i]);
+    }
+  }
+  acc = FIELD_inverse(acc);
+  for(uint i = end; i > start; i--) {
+    const FIELD x = elements[i - 1];
+    if(!FIELD_eq(x, FIELD_ZERO)) {
+      elements[i - 1] = FIELD_mul(acc, prefix[i - 1]);
+      acc = FIELD_mul(acc, x);
+    }
+  }
+}
+
+// The maximum depth of the stack of `FIELD_eval_gate`, it needs to match
+// `GATE_MAX_STACK` on the host.
+#ifndef GATE_MAX_STACK
+#define GATE_MAX_STACK 16
+#define GATE_OP_COLUMN 0
+#define GATE_OP_COEFF 1
+#define GATE_OP_ADD 2
+#define GATE_OP_SUB 3
+#define GATE_OP_MUL 4
+#endif
+
+/// Evaluates an expression over the `columns` and `coeffs` for every row
+///
+/// The expression is given as `code_len` instructions of a stack machine in
+/// postfix order. Every instruction consists of an opcode and an operand,
+/// which is the index of a column or a coefficient for the opcodes that push a
+/// value. The `columns` are stored one after another, each has `n` rows.
+KERNEL void FIELD_eval_gate(GLOBAL FIELD* columns,
+                            GLOBAL FIELD* coeffs,
+                            GLOBAL uint* code,
+                            uint code_len,
+                            GLOBAL FIELD* results,
+                            uint n) {
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= n) return;
+
+  FIELD stack[GATE_MAX_STACK];
+  uint top = 0;
+  for(uint i = 0; i < code_len; i++) {
+    const uint op = code[2 * i];
+    const uint arg = code[2 * i + 1];
+    switch(op) {
+      case GATE_OP_COLUMN:
+        stack[top++] = columns[arg * n + gid];
+        break;
+      case GATE_OP_COEFF:
+        stack[top++] = coeffs[arg];
+        break;
+      case GATE_OP_ADD:
+        top--;
+        stack[top - 1] = FIELD_add(stack[top - 1], stack[top]);
+        break;
+      case GATE_OP_SUB:
+        top--;
+        stack[top - 1] = FIELD_sub(stack[top - 1], stack[top]);
+        break;
+      case GATE_OP_MUL:
+        top--;
+        stack[top - 1] = FIELD_mul(stack[top - 1], stack[top]);
+        break;
+    }
+  }
+  results[gid] = stack[0];
+}
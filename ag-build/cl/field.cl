@@ -323,70 +323,3 @@ DEVICE FIELD FIELD_double(FIELD a) {
   if(FIELD_gte(a, FIELD_P)) a = FIELD_sub_(a, FIELD_P);
   return a;
 }
-
-// Modular exponentiation (Exponentiation by Squaring)
-// https://en.wikipedia.org/wiki/Exponentiation_by_squaring
-DEVICE FIELD FIELD_pow(FIELD base, uint exponent) {
-  FIELD res = FIELD_ONE;
-  while(exponent > 0) {
-    if (exponent & 1)
-      res = FIELD_mul(res, base);
-    exponent = exponent >> 1;
-    base = FIELD_sqr(base);
-  }
-  return res;
-}
-
-
-// Store squares of the base in a lookup table for faster evaluation.
-DEVICE FIELD FIELD_pow_lookup(GLOBAL FIELD *bases, uint exponent) {
-  FIELD res = FIELD_ONE;
-  uint i = 0;
-  while(exponent > 0) {
-    if (exponent & 1)
-      res = FIELD_mul(res, bases[i]);
-    exponent = exponent >> 1;
-    i++;
-  }
-  return res;
-}
-
-
-DEVICE FIELD FIELD_mont(FIELD_repr a) {
-  #ifdef CUDA
-    FIELD input = reinterpret_cast<FIELD&>(a);  
-  #else
-    FIELD input = * (FIELD *) &a;
-  #endif
-
-  return FIELD_mul(input, FIELD_R2);
-}
-
-DEVICE FIELD_repr FIELD_unmont(FIELD a) {
-  FIELD one = FIELD_ZERO;
-  one.val[0] = 1;
-  FIELD unmont = FIELD_mul(a, one);
-
-  
-  #ifdef CUDA
-    FIELD_repr answer = reinterpret_cast<FIELD_repr&>(unmont);  
-  #else
-    FIELD_repr answer = * (FIELD_repr *) &unmont;
-  #endif
-  return answer;
-}
-
-// Get `i`th bit (From most significant digit) of the field.
-DEVICE bool FIELD_get_bit(FIELD_repr l, uint i) {
-  return (l.val[FIELD_LIMBS - 1 - i / FIELD_LIMB_BITS] >> (FIELD_LIMB_BITS - 1 - (i % FIELD_LIMB_BITS))) & 1;
-}
-
-// Get `window` consecutive bits, (Starting from `skip`th bit) from the field.
-DEVICE uint FIELD_get_bits(FIELD_repr l, uint skip, uint window) {
-  uint ret = 0;
-  for(uint i = 0; i < window; i++) {
-    ret <<= 1;
-    ret |= FIELD_get_bit(l, skip + i);
-  }
-  return ret;
-}
@@ -1,4 +1,7 @@
-// Fp2 Extension Field where u^2 + 1 = 0
+// Fp2 Extension Field where u^2 = FIELD2_NON_RESIDUE
+//
+// FIELD2_NON_RESIDUE is defined by the generated source, so is
+// FIELD2_NON_RESIDUE_IS_MINUS_ONE if it's -1.
 
 #define FIELD2_LIMB_BITS FIELD_LIMB_BITS
 #define FIELD2_ZERO ((FIELD2){FIELD_ZERO, FIELD_ZERO})
@@ -28,10 +31,19 @@ DEVICE FIELD2 FIELD2_double(FIELD2 a) {
   return a;
 }
 
+// Multiplies an element of the sub-field with the non-residue.
+DEVICE FIELD FIELD2_mul_by_non_residue(FIELD a) {
+#ifdef FIELD2_NON_RESIDUE_IS_MINUS_ONE
+  return FIELD_sub(FIELD_ZERO, a);
+#else
+  return FIELD_mul(a, FIELD2_NON_RESIDUE);
+#endif
+}
+
 /*
- * (a_0 + u * a_1)(b_0 + u * b_1) = a_0 * b_0 - a_1 * b_1 + u * (a_0 * b_1 + a_1 * b_0)
- * Therefore:
- * c_0 = a_0 * b_0 - a_1 * b_1
+ * (a_0 + u * a_1)(b_0 + u * b_1) = a_0 * b_0 + b * a_1 * b_1 + u * (a_0 * b_1 + a_1 * b_0)
+ * Therefore (Karatsuba):
+ * c_0 = a_0 * b_0 + b * a_1 * b_1
  * c_1 = (a_0 * b_1 + a_1 * b_0) = (a_0 + a_1) * (b_0 + b_1) - a_0 * b_0 - a_1 * b_1
  */
 DEVICE FIELD2 FIELD2_mul(FIELD2 a, FIELD2 b) {
@@ -42,20 +54,41 @@ DEVICE FIELD2 FIELD2_mul(FIELD2 a, FIELD2 b) {
   a.c1 = FIELD_mul(a.c1, o);
   a.c1 = FIELD_sub(a.c1, aa);
   a.c1 = FIELD_sub(a.c1, bb);
-  a.c0 = FIELD_sub(aa, bb);
+  a.c0 = FIELD_add(aa, FIELD2_mul_by_non_residue(bb));
   return a;
 }
 
 /*
- * (a_0 + u * a_1)(a_0 + u * a_1) = a_0 ^ 2 - a_1 ^ 2 + u * 2 * a_0 * a_1
+ * (a_0 + u * a_1)(a_0 + u * a_1) = a_0 ^ 2 + b * a_1 ^ 2 + u * 2 * a_0 * a_1
  * Therefore:
- * c_0 = (a_0 * a_0 - a_1 * a_1) = (a_0 + a_1)(a_0 - a_1)
+ * c_0 = (a_0 - a_1)(a_0 - b * a_1) + (b + 1) * a_0 * a_1
  * c_1 = 2 * a_0 * a_1
+ * For b = -1 the first term is (a_0 - a_1)(a_0 + a_1) and the second is zero.
  */
 DEVICE FIELD2 FIELD2_sqr(FIELD2 a) {
   const FIELD ab = FIELD_mul(a.c0, a.c1);
+#ifdef FIELD2_NON_RESIDUE_IS_MINUS_ONE
   const FIELD c0c1 = FIELD_add(a.c0, a.c1);
   a.c0 = FIELD_mul(FIELD_sub(a.c0, a.c1), c0c1);
+#else
+  const FIELD v = FIELD_sub(a.c0, FIELD2_mul_by_non_residue(a.c1));
+  a.c0 = FIELD_mul(FIELD_sub(a.c0, a.c1), v);
+  a.c0 = FIELD_add(a.c0, ab);
+  a.c0 = FIELD_add(a.c0, FIELD2_mul_by_non_residue(ab));
+#endif
   a.c1 = FIELD_double(ab);
   return a;
 }
+
+/*
+ * (a_0 + u * a_1)^-1 = (a_0 - u * a_1) / (a_0 ^ 2 - b * a_1 ^ 2)
+ * The inverse of zero is zero.
+ */
+DEVICE FIELD2 FIELD2_inverse(FIELD2 a) {
+  const FIELD norm =
+      FIELD_sub(FIELD_sqr(a.c0), FIELD2_mul_by_non_residue(FIELD_sqr(a.c1)));
+  const FIELD norm_inv = FIELD_inverse(norm);
+  a.c0 = FIELD_mul(a.c0, norm_inv);
+  a.c1 = FIELD_mul(FIELD_sub(FIELD_ZERO, a.c1), norm_inv);
+  return a;
+}
@@ -0,0 +1,86 @@
+// Prime-field arithmetic for moduli that fit into a single 64-bit limb
+//
+// The elements are in Montgomery form with `R = 2^64`, just like with the
+// multi-limb implementation, so that the memory layout is the same. For the
+// Goldilocks field (`p = 2^64 - 2^32 + 1`), `FIELD_GOLDILOCKS` is defined and
+// the Montgomery reduction is done with shifts only.
+
+#define FIELD_BITS FIELD_LIMB_BITS
+
+// Greater than or equal
+DEVICE bool FIELD_gte(FIELD a, FIELD b) {
+  return a.val[0] >= b.val[0];
+}
+
+// Equals
+DEVICE bool FIELD_eq(FIELD a, FIELD b) {
+  return a.val[0] == b.val[0];
+}
+
+// Modular subtraction
+DEVICE FIELD FIELD_sub(FIELD a, FIELD b) {
+  const ulong res = a.val[0] - b.val[0];
+  a.val[0] = a.val[0] < b.val[0] ? res + FIELD_P.val[0] : res;
+  return a;
+}
+
+// Modular addition
+DEVICE FIELD FIELD_add(FIELD a, FIELD b) {
+  const ulong res = a.val[0] + b.val[0];
+  // The sum may overflow 64 bits if the modulus is larger than 2^63.
+  a.val[0] = (res < a.val[0] || res >= FIELD_P.val[0]) ? res - FIELD_P.val[0] : res;
+  return a;
+}
+
+// The upper 64 bits of the product
+DEVICE ulong FIELD_mul_hi(ulong a, ulong b) {
+#ifdef CUDA
+  return __umul64hi(a, b);
+#else
+  return mul_hi(a, b);
+#endif
+}
+
+#ifdef FIELD_GOLDILOCKS
+// Montgomery reduction of `hi * 2^64 + lo`, which needs to be smaller than `p^2`
+//
+// As `p = 2^64 - 2^32 + 1`, the multiplications by `INV` and by `p` turn into
+// shifts and subtractions.
+DEVICE ulong FIELD_mont_reduce(ulong lo, ulong hi) {
+  const ulong a = lo + (lo << 32);
+  const ulong overflow = a < lo;
+  const ulong b = a - (a >> 32) - overflow;
+  ulong res = hi - b;
+  // A borrow means `2^64` too much, which is `2^32 - 1` modulo `p`.
+  if(hi < b) res -= 0xffffffff;
+  if(res >= FIELD_P.val[0]) res -= FIELD_P.val[0];
+  return res;
+}
+#else
+// Montgomery reduction of `hi * 2^64 + lo`, which needs to be smaller than `p^2`
+DEVICE ulong FIELD_mont_reduce(ulong lo, ulong hi) {
+  const ulong m = lo * FIELD_INV;
+  // The lower half of `lo + m * p` is zero by construction, only its carry is
+  // needed.
+  const ulong carry = lo + m * FIELD_P.val[0] < lo;
+  const ulong sum = hi + FIELD_mul_hi(m, FIELD_P.val[0]);
+  const ulong res = sum + carry;
+  // The result is smaller than `2p`, but may overflow 64 bits.
+  const bool overflow = sum < hi || res < sum;
+  return (overflow || res >= FIELD_P.val[0]) ? res - FIELD_P.val[0] : res;
+}
+#endif
+
+// Modular multiplication
+DEVICE FIELD FIELD_mul(FIELD a, FIELD b) {
+  a.val[0] = FIELD_mont_reduce(a.val[0] * b.val[0], FIELD_mul_hi(a.val[0], b.val[0]));
+  return a;
+}
+
+DEVICE FIELD FIELD_sqr(FIELD a) {
+  return FIELD_mul(a, a);
+}
+
+DEVICE FIELD FIELD_double(FIELD a) {
+  return FIELD_add(a, a);
+}
@@ -44,6 +44,8 @@
  *
  * @param bases The vector of elliptic curve points.
  * @param exps The vector of large integer scalars.
+ * @param mask One byte per scalar, a scalar whose byte is zero is skipped as if it was zero. A null pointer selects
+ *             all scalars.
  * @param buckets The buckets allocated for the current MSM task.
  * @param tid The thread ID within the current MSM task.
  * @param chunk_len The length of each vector in the MSM task.
@@ -52,6 +54,8 @@
  * @param window_bits The number of bits in each bucket window.
  * @param signed_window Indicates whether each window is treated as a signed or unsigned integer,
  *                      related to the WNAF optimization.
+ * @param op_counts The counters of the mixed additions, additions and doublings, which are atomically incremented by
+ *                  the operations the thread performs. A null pointer disables the counting.
  *
  * 
  * The function accumulates the elliptic curve points in the respective buckets based on the corresponding scalar values.
@@ -62,13 +66,15 @@
 DEVICE void POINT_multiexp_chunk(
   GLOBAL POINT_affine *bases,
   GLOBAL SCALAR_repr *exps,
+  GLOBAL uchar *mask,
   GLOBAL POINT_jacobian *buckets,
   uint tid,
   uint chunk_len,
   uint n_chunk_threads,
   uint n_thread_buckets,
   uint window_bits,
-  bool signed_window
+  bool signed_window,
+  GLOBAL uint *op_counts
 )
 {
   // When the large integer bits number is not divisible by window_bits, some threads may have an actual 
@@ -90,9 +96,13 @@ DEVICE void POINT_multiexp_chunk(
 
   uint half_bucket = 1 << (window_bits - 1);
   uint full_bucket = 1 << window_bits;
+  uint mixed_additions = 0;
+  uint additions = 0;
   
   // Process each input element  
   for(uint i = 0; i < chunk_len; i++) {
+    if (mask != 0 && !mask[i]) continue;
+
     // Scalar for the thread's window
     uint ind = SCALAR_get_bits(exps[i], tid * window_bits, w);
 
@@ -112,9 +122,11 @@ DEVICE void POINT_multiexp_chunk(
     if (ind > 0 && !compute_neg) {
       POINT_jacobian* bucket = &t_buckets[ind - 1];
       *bucket = POINT_add_mixed(*bucket, bases[i]);
+      mixed_additions++;
     } else if (full_bucket > ind && compute_neg) {
       POINT_jacobian* bucket = &t_buckets[full_bucket - ind - 1];
       *bucket = POINT_add_mixed(*bucket, POINT_affine_neg(bases[i]));
+      mixed_additions++;
     }
   }
 
@@ -127,10 +139,17 @@ DEVICE void POINT_multiexp_chunk(
   for(int j = n_thread_buckets - 1; j >= 1; j--) {
     acc = POINT_add(acc, t_buckets[j - 1]);
     res = POINT_add(res, acc);
+    additions += 2;
   }
   t_buckets[0] = res;
+
+  if (op_counts != 0) {
+    ATOMIC_ADD(&op_counts[0], mixed_additions);
+    ATOMIC_ADD(&op_counts[1], additions);
+  }
   
-  BARRIER_LOCAL();
+  // The buckets are in global memory, they are read by the other threads of the task.
+  BARRIER_GLOBAL();
 }
 
 // A utility function for `POINT_aggregate_chunk`
@@ -151,9 +170,12 @@ DEVICE uint POINT_bucket_scalar_exp(uint index, uint window_bits, uint height) {
  * @param n_chunk_threads The number of threads assigned to the current MSM task.
  * @param n_thread_buckets The number of buckets owned by each thread, i.e., 2^window_bits.
  * @param window_bits The number of bits in each bucket window.
+ * @param op_counts The counters like in `POINT_multiexp_chunk`, a null pointer disables the counting.
  * 
  * Note: After each thread finishes its computation, each bucket corresponds to a scalar (a power of 2) to be multiplied. 
  * And scalar_exp in the code represents the exponent of this power.
+ *
+ * All threads of the task run every height, as they all need to reach the barrier, even if they have no work left.
  */
 
 DEVICE void POINT_aggregate_chunk(
@@ -161,38 +183,44 @@ DEVICE void POINT_aggregate_chunk(
   uint tid,
   uint n_chunk_threads,
   uint n_thread_buckets,
-  uint window_bits
+  uint window_bits,
+  GLOBAL uint *op_counts
 )
 {
+  uint additions = 0;
+  uint doublings = 0;
+
   // The current processing height.
   uint h = 0;
   while(n_chunk_threads > (1 << h)) {
     // Only the thread whose id divides 2^(h + 1) applies the following work.
     uint lead_id = tid >> (h + 1) << (h + 1);
-    if (tid != lead_id) {
-      return;
-    }
 
     // The sibling node id to be added for the current thread.
     uint sib_id = tid + (1 << h);
-    if (sib_id >= n_chunk_threads) {
-      return;
-    }
-    
-    // The bucket_scalar_exp keeps changes in each height.
-    uint my_scalar_exp = POINT_bucket_scalar_exp(tid, window_bits, h);
-    uint sib_scalar_exp = POINT_bucket_scalar_exp(sib_id, window_bits, h);
 
+    if (tid == lead_id && sib_id < n_chunk_threads) {
+      // The bucket_scalar_exp keeps changes in each height.
+      uint my_scalar_exp = POINT_bucket_scalar_exp(tid, window_bits, h);
+      uint sib_scalar_exp = POINT_bucket_scalar_exp(sib_id, window_bits, h);
 
-    POINT_jacobian res = buckets[tid * n_thread_buckets];
-    for(uint i = 0; i < my_scalar_exp - sib_scalar_exp; i++) {
-      res = POINT_double(res);
+      POINT_jacobian res = buckets[tid * n_thread_buckets];
+      for(uint i = 0; i < my_scalar_exp - sib_scalar_exp; i++) {
+        res = POINT_double(res);
+        doublings++;
+      }
+      buckets[tid * n_thread_buckets] = POINT_add(res, buckets[sib_id * n_thread_buckets]); // 8
+      additions++;
     }
-    buckets[tid * n_thread_buckets] = POINT_add(res, buckets[sib_id * n_thread_buckets]); // 8
 
     h += 1;
   
-    BARRIER_LOCAL();
+    BARRIER_GLOBAL();
+  }
+
+  if (op_counts != 0) {
+    ATOMIC_ADD(&op_counts[1], additions);
+    ATOMIC_ADD(&op_counts[2], doublings);
   }
 }
 
@@ -202,29 +230,42 @@ DEVICE void POINT_aggregate_chunk(
  * @param bases Multiple lines of elliptic curve points used for computation, with a size of line_len * n_lines.
  * @param results The computation results, with a size of n_lines * n_chunks. The results are stored sequentially for each line.
  * @param exps A row of large integer scalars, with a size of line_len.
+ * @param mask One byte per scalar, with a size of line_len. A scalar whose byte is zero is skipped, hence the bases and
+ *             scalars can stay on the device, while the selection changes between launches. It's only read if
+ *             use_mask is set.
  * @param buckets Uninitialized memory allocated for the bucket computations.
- * @param line_len The length of each line of elliptic curve points and the row of large integer scalars, which must be a power of two.
+ * @param line_len The length of each line of elliptic curve points and the row of large integer scalars.
  * @param n_lines The number of lines of elliptic curve points.
- * @param n_chunks The number of chunks each line is divided into for parallel computation.
- * @param n_chunk_threads The number of threads assigned to each chunk, representing the number of windows.
+ * @param n_chunks The number of chunks each line is divided into for parallel computation. All chunks but the last one
+ *                 have ceil(line_len / n_chunks) scalars, the last one has the rest, which may be none.
+ * @param n_chunk_threads The number of threads assigned to each chunk, representing the number of windows. The threads
+ *                        of a chunk must be in the same thread group, i.e. it must be the local work size.
  * @param window_bits The number of bits in each bucket window.
  * @param neg_is_cheap Indicates whether the affine negation operation is relatively cheap, controlling the WNAF optimization.
+ * @param use_mask Whether only the scalars selected by mask are added up.
+ * @param op_counts The counters of the mixed additions, additions and doublings, they must be initialized to zero.
+ *                  They are only incremented if count_ops is set, as the atomics are not for free.
+ * @param count_ops Whether the operations are counted.
  *
  * This function receives a row of large integer scalars and multiple rows of elliptic curve points. The length of each line of elliptic curve points
- * equals to the length of the large integer row and must be a power of two. The code divides each line into several chunks based on the input parameters
+ * equals to the length of the large integer row. The code divides each line into several chunks based on the input parameters
  * and computes the MSM for each chunk separately.
  */
 KERNEL void POINT_multiexp(
     GLOBAL POINT_affine *bases,
     GLOBAL POINT_jacobian *results,
     GLOBAL SCALAR_repr *exps,
+    GLOBAL uchar *mask,
     GLOBAL POINT_jacobian *buckets,
     uint line_len,
     uint n_lines,
     uint n_chunks,
     uint n_chunk_threads,
     uint window_bits,
-    bool neg_is_cheap
+    uint neg_is_cheap,
+    uint use_mask,
+    GLOBAL uint *op_counts,
+    uint count_ops
 ) 
 {
   const uint gid = GET_GLOBAL_ID();
@@ -232,7 +273,8 @@ KERNEL void POINT_multiexp(
 
   // POINT_jacobian* buckets = (POINT_jacobian*)cuda_shared;
 
-  const uint chunk_len = line_len / n_chunks;
+  // The last chunks are shorter if the line isn't divisible into chunks of the same length.
+  const uint max_chunk_len = (line_len + n_chunks - 1) / n_chunks;
   
   // task_id ∈ [0, n_lines * n_chunks)
   const uint task_id = gid / n_chunk_threads;
@@ -240,6 +282,8 @@ KERNEL void POINT_multiexp(
 
   const uint chunk_id = task_id / n_lines;
   const uint line_id = task_id % n_lines;
+  const uint chunk_start = min(chunk_id * max_chunk_len, line_len);
+  const uint chunk_len = min(max_chunk_len, line_len - chunk_start);
 
   const bool signed_window = neg_is_cheap && window_bits > 1;
   uint n_thread_buckets;
@@ -250,15 +294,180 @@ KERNEL void POINT_multiexp(
   }
   
   POINT_affine *bases_line = &bases[line_id * line_len];
-  POINT_affine *bases_chunk = &bases_line[chunk_id * chunk_len];
-  SCALAR_repr *exps_chunk = &exps[chunk_id * chunk_len];
+  POINT_affine *bases_chunk = &bases_line[chunk_start];
+  SCALAR_repr *exps_chunk = &exps[chunk_start];
+  uchar *mask_chunk = use_mask ? &mask[chunk_start] : 0;
+  uint *op_counts_chunk = count_ops ? op_counts : 0;
   POINT_jacobian *buckets_chunk = &buckets[task_id * n_chunk_threads * n_thread_buckets];
 
-  POINT_multiexp_chunk(bases_chunk, exps_chunk, buckets_chunk, local_thread_id, chunk_len, n_chunk_threads, n_thread_buckets, window_bits, signed_window);
+  POINT_multiexp_chunk(bases_chunk, exps_chunk, mask_chunk, buckets_chunk, local_thread_id, chunk_len, n_chunk_threads, n_thread_buckets, window_bits, signed_window, op_counts_chunk);
 
-  POINT_aggregate_chunk(buckets_chunk, local_thread_id, n_chunk_threads, n_thread_buckets, window_bits);
+  POINT_aggregate_chunk(buckets_chunk, local_thread_id, n_chunk_threads, n_thread_buckets, window_bits, op_counts_chunk);
 
   if (local_thread_id == 0) {
     results[line_id * n_chunks + chunk_id] = buckets_chunk[0];
   }
-}
\ No newline at end of file
+}
+/**
+ * @brief Counts how many bases `POINT_multiexp` adds into each bucket, without adding them.
+ *
+ * @param exps The vector of large integer scalars.
+ * @param occupancy The counters, `1 << window_size` per window, they must be initialized to zero.
+ * @param n The number of scalars.
+ * @param num_groups The number of groups the scalars are split into.
+ * @param num_windows The number of windows of each scalar.
+ * @param window_size The number of bits in each window.
+ * @param neg_is_cheap Whether the digits are recoded into signed ones, like in `POINT_multiexp_chunk`.
+ *
+ * Each thread counts one window of one group of scalars, the groups are the chunks of `POINT_multiexp`. Every group has its own
+ * buckets, the counters of a window are shared by all groups, hence they hold the number of scalars per digit. The counter of digit zero is
+ * never incremented, as such digits don't touch any bucket. With signed digits, a digit and its negation share a
+ * bucket, it's counted at the absolute value, hence only the counters up to `1 << (window_size - 1)` are used.
+ */
+KERNEL void POINT_multiexp_occupancy(
+    GLOBAL SCALAR_repr *exps,
+    GLOBAL uint *occupancy,
+    uint n,
+    uint num_groups,
+    uint num_windows,
+    uint window_size,
+    uint neg_is_cheap)
+{
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= num_windows * num_groups) return;
+
+  const uint len = (n + num_groups - 1) / num_groups;
+  const uint nstart = len * (gid / num_windows);
+  const uint nend = min(nstart + len, n);
+  const uint window = gid % num_windows;
+  const uint bits = window * window_size;
+  const ushort w = min((ushort)window_size, (ushort)(SCALAR_BITS - bits));
+
+  // The same recoding as in `POINT_multiexp_chunk`.
+  const bool signed_window = neg_is_cheap && window_size > 1;
+  ushort w_next = 0;
+  if (SCALAR_BITS >= bits + window_size) {
+    w_next = min((ushort)window_size, (ushort)(SCALAR_BITS - bits - window_size));
+  }
+  const uint half_bucket = 1 << (window_size - 1);
+  const uint full_bucket = 1 << window_size;
+
+  GLOBAL uint *counters = occupancy + (window << window_size);
+  for(uint i = nstart; i < nend; i++) {
+    uint ind = SCALAR_get_bits(exps[i], bits, w);
+    const bool carry = (ind >= half_bucket);
+    if (signed_window && w_next == window_size) {
+      const uint ind_next = SCALAR_get_bits(exps[i], bits + window_size, w_next);
+      if (ind_next >= half_bucket) {
+        ind += 1;
+      }
+    }
+    const bool compute_neg = carry && signed_window;
+
+    if (ind > 0 && !compute_neg) {
+      ATOMIC_ADD(&counters[ind], 1u);
+    } else if (full_bucket > ind && compute_neg) {
+      ATOMIC_ADD(&counters[full_bucket - ind], 1u);
+    }
+  }
+}
+
+/**
+ * @brief Computes the MSM from precomputed multiples of the bases, instead of adding the bases into buckets.
+ *
+ * @param table The multiples `1 * base` up to `table_len * base` of every base, base by base.
+ * @param results The sum of every thread, the one of group `g` and window `w` is at `g * num_windows + w`.
+ * @param exps The vector of large integer scalars.
+ * @param n The number of scalars.
+ * @param num_groups The number of groups the scalars are split into.
+ * @param num_windows The number of windows of each scalar.
+ * @param window_size The number of bits in each window.
+ * @param table_len The number of multiples of each base, `(1 << window_size) - 1`, or `1 << (window_size - 1)` with
+ *                  signed digits.
+ * @param signed_digits Whether the digits are recoded into signed ones, like in `POINT_multiexp_chunk`. A negative
+ *                      digit adds the negation of the multiple of its absolute value.
+ *
+ * Each thread is assigned the same group and window as in `POINT_multiexp_occupancy`. The multiple of a digit is
+ * added directly, hence there are no buckets to sum up. The windows are combined on the host.
+ */
+KERNEL void POINT_multiexp_table(
+    GLOBAL POINT_affine *table,
+    GLOBAL POINT_jacobian *results,
+    GLOBAL SCALAR_repr *exps,
+    uint n,
+    uint num_groups,
+    uint num_windows,
+    uint window_size,
+    uint table_len,
+    uint signed_digits)
+{
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= num_windows * num_groups) return;
+
+  const uint len = (n + num_groups - 1) / num_groups;
+  const uint nstart = len * (gid / num_windows);
+  const uint nend = min(nstart + len, n);
+  const uint window = gid % num_windows;
+  const uint bits = window * window_size;
+  const ushort w = min((ushort)window_size, (ushort)(SCALAR_BITS - bits));
+
+  // The same recoding as in `POINT_multiexp_chunk`, the index of a multiple is the one of the bucket there.
+  const bool signed_window = signed_digits && window_size > 1;
+  ushort w_next = 0;
+  if (SCALAR_BITS >= bits + window_size) {
+    w_next = min((ushort)window_size, (ushort)(SCALAR_BITS - bits - window_size));
+  }
+  const uint half_bucket = 1 << (window_size - 1);
+  const uint full_bucket = 1 << window_size;
+
+  POINT_jacobian res = POINT_ZERO;
+  for(uint i = nstart; i < nend; i++) {
+    uint ind = SCALAR_get_bits(exps[i], bits, w);
+    const bool carry = (ind >= half_bucket);
+    if (signed_window && w_next == window_size) {
+      const uint ind_next = SCALAR_get_bits(exps[i], bits + window_size, w_next);
+      if (ind_next >= half_bucket) {
+        ind += 1;
+      }
+    }
+    const bool compute_neg = carry && signed_window;
+
+    GLOBAL POINT_affine *multiples = &table[i * table_len];
+    if (ind > 0 && !compute_neg) {
+      res = POINT_add_mixed(res, multiples[ind - 1]);
+    } else if (full_bucket > ind && compute_neg) {
+      res = POINT_add_mixed(res, POINT_affine_neg(multiples[full_bucket - ind - 1]));
+    }
+  }
+  results[gid] = res;
+}
+
+/*
+ * Reduces wide integers modulo the scalar field order, so that they can be
+ * used as exponents.
+ *
+ * Each integer consists of `words` little-endian 32-bit words. It is
+ * evaluated with Horner's rule in Montgomery form, where `radix[0]` is 2^32
+ * in Montgomery form. Every word is smaller than the field order, hence it can
+ * be converted into Montgomery form with a single multiplication by R^2.
+ */
+KERNEL void POINT_multiexp_reduce_wide(
+    GLOBAL uint *wide,
+    GLOBAL SCALAR_repr *exps,
+    GLOBAL SCALAR *radix,
+    uint n,
+    uint words) {
+
+  const uint gid = GET_GLOBAL_ID();
+  if(gid >= n) return;
+
+  wide += gid * words;
+  const SCALAR r = radix[0];
+  SCALAR acc = SCALAR_ZERO;
+  for(uint i = words; i-- > 0;) {
+    SCALAR digit = SCALAR_ZERO;
+    digit.val[0] = wide[i];
+    acc = SCALAR_add(SCALAR_mul(acc, r), SCALAR_mul(digit, SCALAR_R2));
+  }
+  exps[gid] = SCALAR_unmont(acc);
+}
@@ -0,0 +1,42 @@
+/// Writes the transpose of the `rows` x `cols` matrix `src` to `dst`
+///
+/// Both matrices are stored row by row. Every work group transposes one tile
+/// of `tile` x `tile` elements, it needs `tile * tile` threads and a local
+/// buffer of `tile * (tile + 1)` elements. The tile is read row by row and
+/// written column by column through local memory, so that both the reads and
+/// the writes of global memory are coalesced. The extra column of the local
+/// buffer shifts every row to another bank, hence reading its columns doesn't
+/// result in bank conflicts. Tiles at the border of the matrix may be partial.
+KERNEL void FIELD_transpose_tiled(GLOBAL FIELD* src,
+                                  GLOBAL FIELD* dst,
+                                  LOCAL FIELD* tile_arg,
+                                  uint rows,
+                                  uint cols,
+                                  uint tile) {
+// CUDA doesn't support local buffers ("shared memory" in CUDA lingo) as function arguments,
+// ignore that argument and use the globally defined extern memory instead.
+#ifdef CUDA
+  FIELD* buf = (FIELD*)cuda_shared;
+#else
+  LOCAL FIELD* buf = tile_arg;
+#endif
+  const uint tiles_per_row = (cols + tile - 1) / tile;
+  const uint tile_row = GET_GROUP_ID() / tiles_per_row;
+  const uint tile_col = GET_GROUP_ID() % tiles_per_row;
+  const uint y = GET_LOCAL_ID() / tile;
+  const uint x = GET_LOCAL_ID() % tile;
+
+  uint row = tile_row * tile + y;
+  uint col = tile_col * tile + x;
+  if(row < rows && col < cols) {
+    buf[y * (tile + 1) + x] = src[row * cols + col];
+  }
+  BARRIER_LOCAL();
+
+  // In the transpose the tile is at the swapped position.
+  row = tile_col * tile + y;
+  col = tile_row * tile + x;
+  if(row < cols && col < rows) {
+    dst[row * rows + col] = buf[x * (tile + 1) + y];
+  }
+}
@@ -11,25 +11,77 @@
 /// `_EC_GPU_OPENCL_KERNEL_SOURCE` environment variable, that will
 /// automatically be used by the `ec-gpu-gen` functionality that needs a
 /// kernel. OpenCL compiles the source at run time).
+///
+/// The files are named after a hash of everything that affects them, see
+/// `kernel_digest`, so that a kernel that was generated before is reused,
+/// e.g. nvcc only runs if the source or the toolchain changed.
 pub use super::source::SourceBuilder;
 
 pub use std::{env, fs, path::PathBuf};
+use std::{io, sync::Mutex};
 
 fn in_build_script() -> bool { std::env::var("OUT_DIR").is_ok() }
 
-fn working_dir() -> String {
-    if let Ok(dir) = env::var("ARK_GPU_BUILD_DIR") {
-        dir
+/// Returns the directory the kernels are cached in.
+///
+/// It's `ARK_GPU_BUILD_DIR` if it's set, e.g. to share the kernels between
+/// several crates or to keep them across `cargo clean`, else `OUT_DIR` in a
+/// build script. Otherwise a new temporary directory is used, which is only
+/// shared by the kernels of the current process. A fixed directory within the
+/// world-writable temporary directory of the system could be prepared by
+/// another user, who would then control the reused kernels.
+fn working_dir() -> io::Result<String> {
+    static TEMP_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+    let dir = if let Ok(dir) = env::var("ARK_GPU_BUILD_DIR") {
+        PathBuf::from(dir)
     } else if let Ok(dir) = env::var("OUT_DIR") {
-        dir
+        PathBuf::from(dir)
     } else {
-        tempfile::tempdir()
-            .unwrap()
-            .path()
-            .to_str()
-            .unwrap()
-            .to_owned()
-    }
+        let mut temp_dir = TEMP_DIR.lock().unwrap();
+        match &*temp_dir {
+            Some(dir) => dir.clone(),
+            None => temp_dir.insert(tempfile::tempdir()?.keep()).clone(),
+        }
+    };
+    fs::create_dir_all(&dir)?;
+    Ok(dir.to_str().unwrap().to_owned())
+}
+
+/// Returns the hex encoded hash the generated files are named after.
+///
+/// The `source` is the fully rendered kernel source, hence it covers the
+/// selected fields and curves as well as the limb size. The version of this
+/// crate and the `toolchain`, i.e. the compiler, its version and its
+/// arguments, are hashed as well, so that a file is never reused after any
+/// input of it changed.
+pub(crate) fn kernel_digest(source: &str, toolchain: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+    hasher.update([0]);
+    hasher.update(source.as_bytes());
+    hasher.update([0]);
+    hasher.update(toolchain.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+/// Moves the file at `from` to `to`, which is atomic within a file system.
+///
+/// Files are written or compiled to a temporary path first, so that an
+/// interrupted build never leaves a partial file that would be reused.
+fn persist(from: &std::path::Path, to: &std::path::Path) {
+    fs::rename(from, to).unwrap_or_else(|_| {
+        panic!("Cannot move {} to {}.", from.display(), to.display())
+    });
+}
+
+/// Returns a temporary path next to `path` that is unique to this process.
+fn temporary_path(path: &std::path::Path) -> PathBuf {
+    let mut name = path.file_name().unwrap().to_owned();
+    name.push(format!(".{}.tmp", std::process::id()));
+    path.with_file_name(name)
 }
 
 macro_rules! bprintln {
@@ -42,8 +94,6 @@ macro_rules! bprintln {
 
 #[cfg(feature = "cuda")]
 pub fn generate_cuda(source_builder: &SourceBuilder) -> PathBuf {
-    use sha2::{Digest, Sha256};
-
     // This is a hack when no properly compiled kernel is needed. That's the
     // case when the documentation is built on docs.rs and when Clippy is
     // run. We can use arbitrary bytes as input then.
@@ -53,7 +103,9 @@ pub fn generate_cuda(source_builder: &SourceBuilder) -> PathBuf {
     }
 
     let kernel_source = source_builder.build_32_bit_limbs();
-    let out_dir = working_dir();
+    let out_dir = working_dir().unwrap_or_else(|e| {
+        panic!("Cannot create the kernel directory: {}", e)
+    });
 
     // Make it possible to override the default options. Though the source and
     // output file is always set automatically.
@@ -74,12 +126,10 @@ pub fn generate_cuda(source_builder: &SourceBuilder) -> PathBuf {
         }
     };
 
-    // Hash the source and the compile flags. Use that as the filename, so that
-    // the kernel is only rebuilt if any of them change.
-    let mut hasher = Sha256::new();
-    hasher.update(kernel_source.as_bytes());
-    hasher.update(&format!("{:?}", &nvcc));
-    let kernel_digest = hex::encode(hasher.finalize());
+    // Hash the source, the compile flags and the version of nvcc. Use that as
+    // the filename, so that the kernel is only rebuilt if any of them change.
+    let toolchain = format!("{:?}\n{}", &nvcc, nvcc_version());
+    let kernel_digest = kernel_digest(&kernel_source, &toolchain);
 
     let source_path: PathBuf = [&out_dir, &format!("{}.cu", &kernel_digest)]
         .iter()
@@ -97,20 +147,36 @@ pub fn generate_cuda(source_builder: &SourceBuilder) -> PathBuf {
     });
 
     // Only compile if the output doesn't exist yet.
-    if !fatbin_path.as_path().exists() {
-        let status = nvcc
-            .arg("--output-file")
-            .arg(&fatbin_path)
-            .arg(&source_path)
-            .status()
-            .expect("Cannot run nvcc. Install the NVIDIA toolkit or disable the `cuda` feature.");
-
-        if !status.success() {
-            panic!(
-                "nvcc failed. See the kernel source at {}",
-                source_path.to_str().unwrap()
-            );
+    if fatbin_path.as_path().exists() {
+        log::info!("Reusing the CUDA kernel at {}", fatbin_path.display());
+    } else {
+        let compiled_path = temporary_path(&fatbin_path);
+        nvcc.arg("--output-file")
+            .arg(&compiled_path)
+            .arg(&source_path);
+        let expect_msg = "Cannot run nvcc. Install the NVIDIA toolkit or disable the `cuda` feature.";
+        if source_builder.should_validate_compile() {
+            // Capture the output, so that the errors are part of the message
+            // and not hidden in the build script output.
+            let output = nvcc.output().expect(expect_msg);
+            if !output.status.success() {
+                panic!(
+                    "nvcc failed. See the kernel source at {}\n{}",
+                    source_path.to_str().unwrap(),
+                    String::from_utf8_lossy(&output.stderr)
+                );
+            }
+        } else {
+            let status = nvcc.status().expect(expect_msg);
+
+            if !status.success() {
+                panic!(
+                    "nvcc failed. See the kernel source at {}",
+                    source_path.to_str().unwrap()
+                );
+            }
         }
+        persist(&compiled_path, &fatbin_path);
     }
 
     // The idea to put the path to the farbin into a compile-time env variable
@@ -129,21 +195,45 @@ pub fn generate_cuda(source_builder: &SourceBuilder) -> PathBuf {
     fatbin_path
 }
 
+/// Returns the output of `nvcc --version`, or an empty string if it cannot be
+/// run.
+#[cfg(feature = "cuda")]
+fn nvcc_version() -> String {
+    std::process::Command::new("nvcc")
+        .arg("--version")
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+        .unwrap_or_default()
+}
+
 #[cfg(feature = "opencl")]
 pub fn generate_opencl(source_builder: &SourceBuilder) -> PathBuf {
     let kernel_source = source_builder.build_64_bit_limbs();
-    let out_dir = working_dir();
+    let out_dir = working_dir().unwrap_or_else(|e| {
+        panic!("Cannot create the kernel directory: {}", e)
+    });
 
-    // Generating the kernel source is cheap, hence use a fixed name and
-    // override it on every build.
-    let source_path: PathBuf = [&out_dir, "kernel.cl"].iter().collect();
+    // The source is compiled at run time, hence only the source itself needs
+    // to be hashed.
+    let kernel_digest = kernel_digest(&kernel_source, "opencl");
+    let source_path: PathBuf = [&out_dir, &format!("{}.cl", &kernel_digest)]
+        .iter()
+        .collect();
 
-    fs::write(&source_path, &kernel_source).unwrap_or_else(|_| {
-        panic!(
-            "Cannot write kernel source at {}.",
-            source_path.to_str().unwrap()
-        )
-    });
+    if !source_path.as_path().exists() {
+        let written_path = temporary_path(&source_path);
+        fs::write(&written_path, &kernel_source).unwrap_or_else(|_| {
+            panic!(
+                "Cannot write kernel source at {}.",
+                written_path.to_str().unwrap()
+            )
+        });
+        persist(&written_path, &source_path);
+    }
+
+    if source_builder.should_validate_compile() {
+        validate_opencl(&source_path);
+    }
 
     // For OpenCL we only need the kernel source, it is compiled at runtime.
     bprintln!(
@@ -159,3 +249,51 @@ pub fn generate_opencl(source_builder: &SourceBuilder) -> PathBuf {
 
     source_path
 }
+
+/// Compiles the OpenCL source with an offline compiler, in order to catch
+/// errors at build time instead of at run time.
+///
+/// The compiler is `clang` unless `EC_GPU_OPENCL_OFFLINE_COMPILER` is set. The
+/// arguments can be overridden with `EC_GPU_OPENCL_OFFLINE_COMPILER_ARGS`. If
+/// the compiler cannot be found, the validation is skipped with a warning.
+#[cfg(feature = "opencl")]
+fn validate_opencl(source_path: &std::path::Path) {
+    let compiler = env::var("EC_GPU_OPENCL_OFFLINE_COMPILER")
+        .unwrap_or_else(|_| "clang".to_string());
+    let mut command = match env::var("EC_GPU_OPENCL_OFFLINE_COMPILER_ARGS") {
+        Ok(args) => execute::command(format!("{} {}", compiler, args)),
+        Err(_) => {
+            let mut command = std::process::Command::new(&compiler);
+            command
+                .arg("-x")
+                .arg("cl")
+                .arg("-cl-std=CL1.2")
+                .arg("-Xclang")
+                .arg("-finclude-default-header")
+                .arg("-fsyntax-only");
+            command
+        }
+    };
+
+    let output = match command.arg(source_path).output() {
+        Ok(output) => output,
+        Err(_) => {
+            let warning = format!(
+                "Cannot run the OpenCL offline compiler `{}`, skipping the \
+                 validation of the OpenCL source.",
+                compiler
+            );
+            bprintln!("cargo:warning={}", warning);
+            log::warn!("{}", warning);
+            return;
+        }
+    };
+
+    if !output.status.success() {
+        panic!(
+            "OpenCL offline compilation failed. See the kernel source at {}\n{}",
+            source_path.to_str().unwrap(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
@@ -34,7 +34,7 @@
 //! [fatbin]: https://en.wikipedia.org/wiki/Fat_binary#Heterogeneous_computing
 //! [feature flags]: https://doc.rust-lang.org/cargo/reference/manifest.html#the-features-section
 
-pub use source::SourceBuilder;
+pub use source::{modulus_field_name, SourceBuilder};
 
 mod source;
 
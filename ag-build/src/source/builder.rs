@@ -1,13 +1,24 @@
 //! Builder to create the source code of a GPU kernel.
 
-use std::{collections::BTreeSet, fmt::Write};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    fs, io,
+    path::Path,
+};
 
 use super::{
+    header,
     limb::Limb32Or64,
-    synthesis::{Ec, EcFft, Fft, Field, Multiexp, NameAndSource},
+    synthesis::{
+        Ec, EcCompress, EcFft, Fft, Field, FieldBytes, FieldOps, ModulusField,
+        Multiexp, NameAndSource, Transpose,
+    },
     template::*,
 };
 use ag_types::{GpuCurveAffine, GpuField};
+use ec_gpu_program::{EcError, EcResult};
+use num_bigint::BigUint;
 
 // In the `HashSet`s the concrete types cannot be used, as each item of the set
 // should be able to have its own (different) generic type.
@@ -22,7 +33,15 @@ pub struct SourceBuilder {
     extension_fields: BTreeSet<Box<dyn NameAndSource>>,
     /// The [`Fft`]s that are used in this kernel.
     ffts: BTreeSet<Box<dyn NameAndSource>>,
+    /// The [`FieldOps`] that are used in this kernel.
+    field_ops: BTreeSet<Box<dyn NameAndSource>>,
+    /// The [`Transpose`]s of the [`Fft`]s and [`FieldOps`].
+    transposes: BTreeSet<Box<dyn NameAndSource>>,
+    /// The [`FieldBytes`] that are used in this kernel.
+    field_bytes: BTreeSet<Box<dyn NameAndSource>>,
     ec: BTreeSet<Box<dyn NameAndSource>>,
+    /// The [`EcCompress`]s that are used in this kernel.
+    ec_compressions: BTreeSet<Box<dyn NameAndSource>>,
     /// The [`Fftg`]s that are used in this kernel.
     ec_ffts: BTreeSet<Box<dyn NameAndSource>>,
     /// The [`Multiexp`]s that are used in this kernel.
@@ -30,6 +49,22 @@ pub struct SourceBuilder {
     others: BTreeSet<Box<dyn NameAndSource>>,
     /// Additional source that is appended at the end of the generated source.
     extra_sources: Vec<String>,
+    /// Whether [`crate::generate`] compiles the source for all enabled
+    /// backends on the build host.
+    validate_compile: bool,
+    /// Whether the OpenCL code multiplies 64-bit limbs with 128-bit integers.
+    opencl_use_u128: bool,
+    /// Whether the OpenCL code acts as if the compiler didn't support 128-bit
+    /// integers, see [`SourceBuilder::force_u128_fallback`].
+    force_u128_fallback: bool,
+    /// Whether the FFT kernels include the variant that caches the twiddle
+    /// factors in local memory.
+    fft_shared_twiddles: bool,
+    /// The alignment of the field element types in bytes, if it differs from
+    /// the one of their limbs.
+    field_alignment: Option<usize>,
+    /// The size in bytes of an element of every field, by name.
+    element_sizes: BTreeMap<String, usize>,
 }
 
 impl SourceBuilder {
@@ -43,6 +78,7 @@ impl SourceBuilder {
     pub fn add_field<F>(mut self) -> Self
     where F: GpuField + 'static {
         let field = Field::<F>::new();
+        self.element_sizes.insert(F::name(), element_bytes::<F>());
         // If it's an extension field, also add the corresponding sub-field.
         if let Some(sub_field_name) = F::sub_field_name() {
             self.extension_fields.insert(Box::new(field));
@@ -54,48 +90,191 @@ impl SourceBuilder {
         self
     }
 
+    /// Add a quadratic extension field and its sub-field to the
+    /// configuration.
+    ///
+    /// It's [`SourceBuilder::add_field`] for fields that must be extension
+    /// fields. Besides `add`, `sub`, `double`, `mul`, `sqr` and `inverse` of
+    /// the extension field, all operations of the sub-field are generated,
+    /// the former are built on the latter. The non-residue is taken from the
+    /// `GpuField` implementation, for arkworks fields from their `Fp2Config`,
+    /// so that the results match the ones of the CPU.
+    ///
+    /// Panics if `F` is not an extension field.
+    pub fn add_extension_field<F>(self) -> Self
+    where F: GpuField + 'static {
+        assert!(
+            F::sub_field_name().is_some(),
+            "{} is not an extension field",
+            F::name()
+        );
+        self.add_field::<F>()
+    }
+
+    /// Add the prime field with the given modulus to the configuration.
+    ///
+    /// Unlike [`SourceBuilder::add_field`] it doesn't need a `GpuField`
+    /// implementation, the Montgomery constants are computed from the modulus.
+    /// The field is named [`modulus_field_name`] in the generated source,
+    /// which also defines its `two_adicity` as `<NAME>_TWO_ADICITY`. Its
+    /// elements consist of 32-bit limbs, the number of them is the size of
+    /// `<NAME>` divided by 4.
+    ///
+    /// Panics if the modulus is not an odd number greater than two, or if
+    /// `two_adicity` is not the largest `s` such that `2^s` divides
+    /// `modulus - 1`.
+    pub fn add_field_from_modulus(
+        mut self, modulus: BigUint, two_adicity: u32,
+    ) -> Self {
+        assert!(
+            modulus.bit(0) && modulus.bits() > 1,
+            "The modulus {} is not an odd number greater than two",
+            modulus
+        );
+        let actual = (&modulus - 1u32).trailing_zeros();
+        assert_eq!(
+            actual,
+            Some(two_adicity as u64),
+            "The two-adicity of the modulus {} is not {}",
+            modulus,
+            two_adicity
+        );
+        let name = modulus_field_name(&modulus);
+        let constants = FieldConstants::from_modulus(&modulus);
+        self.element_sizes
+            .insert(name.clone(), constants.element_bytes());
+        let field = ModulusField::new(name, constants, two_adicity);
+        self.fields.insert(Box::new(field));
+        self
+    }
+
     /// Add an FFT kernel function to the configuration.
     pub fn add_fft<F>(self) -> Self
     where F: GpuField + 'static {
         let mut config = self.add_field::<F>();
         let fft = Fft::<F>::new();
         config.ffts.insert(Box::new(fft));
+        config.transposes.insert(Box::new(Transpose::<F>::new()));
+        config
+    }
+
+    /// Add the kernel functions of the inverse FFT to the configuration.
+    ///
+    /// The inverse FFT is the forward FFT with the inverse root of unity,
+    /// whose outputs are scaled by `n^-1` when they are written. Hence it uses
+    /// the same kernel functions as [`SourceBuilder::add_fft`], adding both
+    /// emits the field and FFT code only once.
+    pub fn add_ifft<F>(self) -> Self
+    where F: GpuField + 'static {
+        self.add_fft::<F>()
+    }
+
+    /// Add the element-wise field operations kernel functions to the
+    /// configuration.
+    pub fn add_field_ops<F>(self) -> Self
+    where F: GpuField + 'static {
+        let mut config = self.add_field::<F>();
+        let field_ops = FieldOps::<F>::new();
+        config.field_ops.insert(Box::new(field_ops));
+        config.transposes.insert(Box::new(Transpose::<F>::new()));
         config
     }
 
+    /// Add the kernel functions that convert elements of a prime field from
+    /// and to their canonical byte encoding to the configuration.
+    ///
+    /// The encoding is the same as the one of arkworks' `CanonicalSerialize`,
+    /// the little-endian bytes of the integer an element represents.
+    pub fn add_field_bytes<F>(self) -> Self
+    where F: GpuField + 'static {
+        let mut config = self.add_field::<F>();
+        let field_bytes = FieldBytes::<F>::new();
+        config.field_bytes.insert(Box::new(field_bytes));
+        config
+    }
+
+    /// Add the elliptic curve operations to the configuration.
+    ///
+    /// Panics if the curve is not supported, see [`SourceBuilder::try_add_ec`].
     pub fn add_ec<C>(self) -> Self
     where C: GpuCurveAffine + 'static {
+        self.try_add_ec::<C>().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Add the elliptic curve operations to the configuration.
+    ///
+    /// Returns an [`EcError::UnsupportedCurve`] if the curve violates the
+    /// assumptions of the GPU code, which would otherwise compute wrong
+    /// results.
+    pub fn try_add_ec<C>(self) -> EcResult<Self>
+    where C: GpuCurveAffine + 'static {
+        if let Some(reason) = C::unsupported_reason() {
+            return Err(EcError::unsupported_curve::<C>(reason));
+        }
         let mut config = self.add_field::<C::Base>().add_field::<C::Scalar>();
         let ec = Ec::<C>::new();
         config.ec.insert(Box::new(ec));
-        config
+        Ok(config)
     }
 
     /// Add an FFTg kernel function to the configuration.
     ///
     /// The field must be given explicitly as currently it cannot derived from
     /// the curve point directly.
+    ///
+    /// Panics if the curve is not supported, see
+    /// [`SourceBuilder::try_add_ec_fft`].
     pub fn add_ec_fft<C>(self) -> Self
     where C: GpuCurveAffine + 'static {
-        let mut config = self.add_ec::<C>();
+        self.try_add_ec_fft::<C>()
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Add an FFTg kernel function to the configuration.
+    ///
+    /// Returns an [`EcError::UnsupportedCurve`] if the curve is not supported.
+    pub fn try_add_ec_fft<C>(self) -> EcResult<Self>
+    where C: GpuCurveAffine + 'static {
+        let mut config = self.try_add_ec::<C>()?;
         let ec_fft = EcFft::<C>::new();
         config.ec_ffts.insert(Box::new(ec_fft));
-        config
+        Ok(config)
     }
 
     /// Add an Multiexp kernel function to the configuration.
     ///
     /// The field must be given explicitly as currently it cannot derived from
     /// the curve point directly.
+    ///
+    /// Panics if the curve is not supported, see
+    /// [`SourceBuilder::try_add_multiexp`].
     pub fn add_multiexp<C>(self) -> Self
+    where C: GpuCurveAffine + 'static {
+        self.try_add_multiexp::<C>()
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Add an Multiexp kernel function to the configuration.
+    ///
+    /// For curves over a prime field, the kernel that compresses the points
+    /// into their canonical encoding is added as well, together with the
+    /// [`SourceBuilder::add_field_bytes`] of the base field.
+    ///
+    /// Returns an [`EcError::UnsupportedCurve`] if the curve is not supported.
+    pub fn try_add_multiexp<C>(self) -> EcResult<Self>
     where C: GpuCurveAffine + 'static {
         if cfg!(feature = "opencl") {
             panic!("The source code has not been tested on opencl");
         }
-        let mut config = self.add_ec::<C>();
+        let mut config = self.try_add_ec::<C>()?;
         let multiexp = Multiexp::<C>::new();
         config.multiexps.insert(Box::new(multiexp));
-        config
+        if C::Base::sub_field_name().is_none() {
+            config = config.add_field_bytes::<C::Base>();
+            let compress = EcCompress::<C>::new();
+            config.ec_compressions.insert(Box::new(compress));
+        }
+        Ok(config)
     }
 
     #[cfg(test)]
@@ -120,6 +299,74 @@ impl SourceBuilder {
         self
     }
 
+    /// Makes [`crate::generate`] compile the source for all enabled backends on
+    /// the build host.
+    ///
+    /// A source that compiles for one backend might not compile for the other
+    /// one. With validation enabled, such errors are reported with the full
+    /// compiler output at build time. OpenCL is then compiled with an offline
+    /// compiler, which is `clang` unless `EC_GPU_OPENCL_OFFLINE_COMPILER` is
+    /// set. If there is no such compiler, the validation is skipped with a
+    /// warning.
+    pub fn validate_compile(mut self, validate: bool) -> Self {
+        self.validate_compile = validate;
+        self
+    }
+
+    /// Makes the OpenCL code use 128-bit integers for the multiplication of
+    /// 64-bit limbs.
+    ///
+    /// Some OpenCL compilers (e.g. the ones based on Clang) support
+    /// `unsigned __int128` as an extension, which results in a simpler
+    /// schoolbook multiplication than the portable one. Whether the compiler
+    /// supports it is checked when the kernel is compiled, if it doesn't, the
+    /// portable code is used. It has no effect on CUDA, on Nvidia devices and
+    /// with 32-bit limbs, which use their own multiplications.
+    pub fn opencl_use_u128(mut self, use_u128: bool) -> Self {
+        self.opencl_use_u128 = use_u128;
+        self
+    }
+
+    /// Makes the OpenCL code use the portable multiplication, even if
+    /// 128-bit integers were requested and the compiler supports them.
+    #[cfg(all(test, feature = "opencl"))]
+    pub(crate) fn force_u128_fallback(mut self) -> Self {
+        self.force_u128_fallback = true;
+        self
+    }
+
+    /// Adds the `*_radix_fft_shared_twiddles` variant to the FFT kernels.
+    ///
+    /// The variant loads the twiddle factors of a block into local memory
+    /// once, instead of reading them from global memory in every butterfly.
+    /// Whether it's faster depends on the device, it's used once it's enabled
+    /// on the kernel, see `SingleFftKernel::set_shared_twiddles` of the proxy.
+    pub fn fft_shared_twiddles(mut self, enable: bool) -> Self {
+        self.fft_shared_twiddles = enable;
+        self
+    }
+
+    /// Aligns the field element types to `bytes`, e.g. to 16 bytes for
+    /// vectorized loads.
+    ///
+    /// By default an element is aligned like its limbs. Elements are
+    /// transferred between the host and the GPU as they are, hence they must
+    /// have the same size on both sides. That's the case if the alignment is
+    /// a power of two that divides the size of the elements of every field
+    /// of the configuration, otherwise generating the source panics. E.g.
+    /// with BLS12-381 it can be at most 16 bytes, as an element of the base
+    /// field has 48 bytes. Curve points and extension field elements consist
+    /// of field elements and are aligned accordingly.
+    pub fn field_alignment(mut self, bytes: usize) -> Self {
+        self.field_alignment = Some(bytes);
+        self
+    }
+
+    /// Whether the source should be compiled on the build host.
+    pub(crate) fn should_validate_compile(&self) -> bool {
+        self.validate_compile
+    }
+
     /// Generate the GPU kernel source code based on the current configuration
     /// with 32-bit limbs.
     ///
@@ -136,13 +383,127 @@ impl SourceBuilder {
         self.build(Limb32Or64::Limb64)
     }
 
+    /// Generate a C header that declares the field and multiexp kernels of
+    /// the current configuration.
+    ///
+    /// It contains the type definitions of the fields and curve points and
+    /// `extern "C"` prototypes of the kernel entry points, with the address
+    /// space qualifiers removed and the OpenCL integer types mapped to the
+    /// ones of `stdint.h`. It describes the ABI of the kernels compiled from
+    /// the 32-bit limbs source, so that frameworks that launch kernels by name
+    /// (e.g. Triton or other JIT compilers) can call into them.
+    pub fn c_header(&self) -> String {
+        let mut types = String::new();
+        write_field(&mut types, Limb32Or64::Limb32, &self.fields);
+        write_field(&mut types, Limb32Or64::Limb32, &self.extension_fields);
+        write_field(&mut types, Limb32Or64::Limb32, &self.ec);
+
+        let mut kernels = String::new();
+        write_field(&mut kernels, Limb32Or64::Limb32, &self.ffts);
+        write_field(&mut kernels, Limb32Or64::Limb32, &self.transposes);
+        write_field(&mut kernels, Limb32Or64::Limb32, &self.field_ops);
+        write_field(&mut kernels, Limb32Or64::Limb32, &self.field_bytes);
+        write_field(&mut kernels, Limb32Or64::Limb32, &self.ec_compressions);
+        write_field(&mut kernels, Limb32Or64::Limb32, &self.multiexps);
+
+        let mut result = String::from(
+            "#ifndef EC_GPU_KERNELS_H\n#define EC_GPU_KERNELS_H\n\n\
+             #include <stdbool.h>\n#include <stdint.h>\n\n\
+             #ifdef __cplusplus\nextern \"C\" {\n#endif\n\n",
+        );
+        let align = match self.checked_field_alignment() {
+            Some(alignment) => {
+                format!("__attribute__((aligned({}))) ", alignment)
+            }
+            None => String::new(),
+        };
+        for line in header::type_definitions(&types) {
+            writeln!(result, "{}", line.replace("ELEMENT_ALIGN ", &align))
+                .unwrap();
+        }
+        result.push('\n');
+        for line in header::kernel_prototypes(&kernels) {
+            // The variant is only compiled if `FFT_SHARED_TWIDDLES` is defined.
+            if !self.fft_shared_twiddles
+                && header::prototype_name(&line)
+                    .ends_with("_radix_fft_shared_twiddles")
+            {
+                continue;
+            }
+            writeln!(result, "{}", line).unwrap();
+        }
+        result.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n#endif\n");
+        result
+    }
+
+    /// Returns the names of the kernels that are declared in
+    /// [`SourceBuilder::c_header`].
+    pub fn c_header_kernel_names(&self) -> Vec<String> {
+        self.c_header()
+            .lines()
+            .filter(|line| line.starts_with("void "))
+            .map(|line| header::prototype_name(line).to_string())
+            .collect()
+    }
+
+    /// Writes the header of [`SourceBuilder::c_header`] to `path`.
+    ///
+    /// Only the header is generated, the kernels still need to be compiled and
+    /// loaded by the calling framework.
+    pub fn emit_c_header<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.c_header())
+    }
+
+    /// Returns the alignment of [`SourceBuilder::field_alignment`], if it's
+    /// set.
+    ///
+    /// Panics if it's not a power of two or doesn't divide the size of the
+    /// elements of a field.
+    fn checked_field_alignment(&self) -> Option<usize> {
+        let alignment = self.field_alignment?;
+        assert!(
+            alignment.is_power_of_two(),
+            "The field alignment {} is not a power of two",
+            alignment
+        );
+        for (name, size) in &self.element_sizes {
+            assert!(
+                size % alignment == 0,
+                "The field alignment {} doesn't divide the {} bytes of an \
+                 element of {}",
+                alignment,
+                size,
+                name
+            );
+        }
+        Some(alignment)
+    }
+
     /// Generate the GPU kernel source code based on the current configuration.
     fn build(&self, limb_size: Limb32Or64) -> String {
-        let mut answer = COMMON_SRC.into();
+        let mut answer = String::new();
+        if let Some(alignment) = self.checked_field_alignment() {
+            writeln!(answer, "#define ELEMENT_ALIGNMENT {}", alignment)
+                .unwrap();
+        }
+        if self.opencl_use_u128 {
+            answer.push_str("#define OPENCL_USE_U128\n");
+        }
+        if self.force_u128_fallback {
+            answer.push_str("#define OPENCL_NO_U128\n");
+        }
+        if self.fft_shared_twiddles {
+            answer.push_str("#define FFT_SHARED_TWIDDLES\n");
+        }
+        answer.push_str(COMMON_SRC);
         write_field(&mut answer, limb_size, &self.fields);
         write_field(&mut answer, limb_size, &self.extension_fields);
         write_field(&mut answer, limb_size, &self.ec);
         write_field(&mut answer, limb_size, &self.ffts);
+        write_field(&mut answer, limb_size, &self.transposes);
+        write_field(&mut answer, limb_size, &self.field_ops);
+        write_field(&mut answer, limb_size, &self.field_bytes);
+        write_field(&mut answer, limb_size, &self.ec_compressions);
         write_field(&mut answer, limb_size, &self.ec_ffts);
         write_field(&mut answer, limb_size, &self.multiexps);
         write_field(&mut answer, limb_size, &self.others);
@@ -151,6 +512,12 @@ impl SourceBuilder {
     }
 }
 
+/// Returns the name of the field added with
+/// [`SourceBuilder::add_field_from_modulus`] in the generated source.
+pub fn modulus_field_name(modulus: &BigUint) -> String {
+    format!("modulus_field_{:x}", modulus)
+}
+
 fn write_field(
     result: &mut String, limb_size: Limb32Or64,
     field: &BTreeSet<Box<dyn NameAndSource>>,
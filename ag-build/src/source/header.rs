@@ -0,0 +1,160 @@
+//! Generation of a C header that declares the kernel entry points.
+//!
+//! The declarations are extracted from the generated GPU source, so that they
+//! always match the kernels that are actually compiled.
+
+/// Removes `/* */` and `//` comments from the source.
+fn strip_comments(source: &str) -> String {
+    let mut result = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(start) = rest.find("/*") {
+        result.push_str(&rest[..start]);
+        rest = match rest[start..].find("*/") {
+            Some(end) => &rest[start + end + 2..],
+            None => "",
+        };
+    }
+    result.push_str(rest);
+    result
+        .lines()
+        .map(|line| match line.find("//") {
+            Some(start) => &line[..start],
+            None => line,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Translates a declaration from the GPU dialect into plain C, by dropping the
+/// address space qualifiers and mapping the OpenCL integer types to the ones
+/// of `stdint.h`. Whitespace is collapsed to a single space.
+fn to_c(declaration: &str) -> String {
+    let mut result = String::with_capacity(declaration.len());
+    let mut word = String::new();
+    let flush = |word: &mut String, result: &mut String| {
+        let replacement = match word.as_str() {
+            "GLOBAL" | "LOCAL" | "CONSTANT" => "",
+            "uchar" => "uint8_t",
+            "uint" => "uint32_t",
+            "ulong" => "uint64_t",
+            other => other,
+        };
+        result.push_str(replacement);
+        word.clear();
+    };
+    for c in declaration.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            word.push(c);
+        } else {
+            flush(&mut word, &mut result);
+            result.push(c);
+        }
+    }
+    flush(&mut word, &mut result);
+    result
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("( ", "(")
+        .replace(" )", ")")
+}
+
+/// Returns the type definitions of the given source, i.e. the limb type and
+/// the number of limbs of fields and all `typedef struct`s.
+///
+/// The alignment of the field types is kept as `ELEMENT_ALIGN`, which is
+/// defined by the common source.
+pub(crate) fn type_definitions(source: &str) -> Vec<String> {
+    let source = strip_comments(source);
+    let mut definitions = Vec::new();
+    let mut lines = source.lines();
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+        let mut tokens = line.split_whitespace();
+        if tokens.next() == Some("#define") {
+            if let Some(name) = tokens.next() {
+                if name.ends_with("_limb") || name.ends_with("_LIMBS") {
+                    definitions.push(to_c(line));
+                }
+            }
+        } else if line.starts_with("typedef struct") {
+            // The struct ends with the line that closes its body.
+            let mut typedef = line.to_string();
+            while !typedef.contains('}') {
+                match lines.next() {
+                    Some(next) => {
+                        typedef.push(' ');
+                        typedef.push_str(next.trim());
+                    }
+                    None => break,
+                }
+            }
+            definitions.push(to_c(&typedef));
+        }
+    }
+    definitions
+}
+
+/// Returns the prototypes of all kernels of the given source, e.g.
+/// `void Fr_to_mont(Fr* elements, uint32_t n);`.
+pub(crate) fn kernel_prototypes(source: &str) -> Vec<String> {
+    const KERNEL: &str = "KERNEL void ";
+    let source = strip_comments(source);
+    let mut prototypes = Vec::new();
+    let mut rest = source.as_str();
+    while let Some(start) = rest.find(KERNEL) {
+        rest = &rest[start + KERNEL.len()..];
+        let end = match rest.find(')') {
+            Some(end) => end,
+            None => break,
+        };
+        prototypes.push(format!("void {};", to_c(&rest[..=end])));
+        rest = &rest[end..];
+    }
+    prototypes
+}
+
+/// Returns the name of the kernel a prototype declares.
+pub(crate) fn prototype_name(prototype: &str) -> &str {
+    let start = prototype.find(' ').map_or(0, |index| index + 1);
+    let end = prototype.find('(').unwrap_or(prototype.len());
+    &prototype[start..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kernel_prototypes() {
+        let source = "/** Computes (something). */\n\
+            KERNEL void Fr_scale(GLOBAL Fr* elements, // Values (in place)\n\
+                                 GLOBAL uchar* factor,\n\
+                                 uint n) {\n\
+              // KERNEL void not_a_kernel(uint n)\n\
+            }";
+        let prototypes = kernel_prototypes(source);
+        assert_eq!(
+            prototypes,
+            vec!["void Fr_scale(Fr* elements, uint8_t* factor, uint32_t n);"]
+        );
+        assert_eq!(prototype_name(&prototypes[0]), "Fr_scale");
+    }
+
+    #[test]
+    fn test_type_definitions() {
+        let source = "#define Fr_limb uint\n#define Fr_LIMBS 8\n\
+            #define Fr_LIMB_BITS 32\n\
+            typedef struct { Fr_limb val[Fr_LIMBS]; } Fr;\n\
+            typedef struct {\n  Fq x;\n  Fq y;\n} G1_affine; // A point";
+        assert_eq!(
+            type_definitions(source),
+            vec![
+                "#define Fr_limb uint32_t",
+                "#define Fr_LIMBS 8",
+                "typedef struct { Fr_limb val[Fr_LIMBS]; } Fr;",
+                "typedef struct { Fq x; Fq y; } G1_affine;",
+            ]
+        );
+    }
+}
@@ -1,4 +1,3 @@
-use ag_types::GpuField;
 use std::mem;
 
 #[derive(Clone, Copy)]
@@ -24,18 +23,14 @@ pub trait Limb: Sized + Clone + Copy {
     fn ptx_info() -> (&'static str, &'static str);
     /// Returns the type that OpenCL is using to represent the limb.
     fn opencl_type() -> &'static str;
-    /// Returns the limbs that represent the multiplicative identity of the
-    /// given field.
-    fn one_limbs<F: GpuField>() -> Vec<Self>;
-    /// Returns the field modulus in non-Montgomery form as a vector of
-    /// `Self::LimbType` (least significant limb first).
-    fn modulus_limbs<F: GpuField>() -> Vec<Self>;
+    /// Converts a number given as 32-bit limbs (least significant limb first)
+    /// into limbs of this size. The number of 32-bit limbs must be a multiple
+    /// of the ones per limb.
+    fn from_u32_limbs(limbs: &[u32]) -> Vec<Self>;
     /// Calculate the `INV` parameter of Montgomery reduction algorithm for
     /// 32/64bit limbs
     /// * `a` - Is the first limb of modulus.
     fn calc_inv(a: Self) -> Self;
-    /// Returns the limbs that represent `R ^ 2 mod P`.
-    fn calculate_r2<F: GpuField>() -> Vec<Self>;
 }
 
 /// A 32-bit limb.
@@ -54,12 +49,8 @@ impl Limb for Limb32 {
 
     fn opencl_type() -> &'static str { "uint" }
 
-    fn one_limbs<F: GpuField>() -> Vec<Self> {
-        F::one().into_iter().map(Self::new).collect()
-    }
-
-    fn modulus_limbs<F: GpuField>() -> Vec<Self> {
-        F::modulus().into_iter().map(Self::new).collect()
+    fn from_u32_limbs(limbs: &[u32]) -> Vec<Self> {
+        limbs.iter().copied().map(Self::new).collect()
     }
 
     fn calc_inv(a: Self) -> Self {
@@ -70,10 +61,6 @@ impl Limb for Limb32 {
         }
         Self(inv.wrapping_neg())
     }
-
-    fn calculate_r2<F: GpuField>() -> Vec<Self> {
-        F::r2().into_iter().map(Self::new).collect()
-    }
 }
 
 /// A 64-bit limb.
@@ -92,17 +79,8 @@ impl Limb for Limb64 {
 
     fn opencl_type() -> &'static str { "ulong" }
 
-    fn one_limbs<F: GpuField>() -> Vec<Self> {
-        F::one()
-            .chunks(2)
-            .map(|chunk| {
-                Self::new(((chunk[1] as u64) << 32) + (chunk[0] as u64))
-            })
-            .collect()
-    }
-
-    fn modulus_limbs<F: GpuField>() -> Vec<Self> {
-        F::modulus()
+    fn from_u32_limbs(limbs: &[u32]) -> Vec<Self> {
+        limbs
             .chunks(2)
             .map(|chunk| {
                 Self::new(((chunk[1] as u64) << 32) + (chunk[0] as u64))
@@ -118,13 +96,4 @@ impl Limb for Limb64 {
         }
         Self(inv.wrapping_neg())
     }
-
-    fn calculate_r2<F: GpuField>() -> Vec<Self> {
-        F::r2()
-            .chunks(2)
-            .map(|chunk| {
-                Self::new(((chunk[1] as u64) << 32) + (chunk[0] as u64))
-            })
-            .collect()
-    }
 }
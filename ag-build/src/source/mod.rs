@@ -1,6 +1,7 @@
 mod builder;
+mod header;
 mod limb;
 mod synthesis;
 mod template;
 
-pub use builder::SourceBuilder;
+pub use builder::{modulus_field_name, SourceBuilder};
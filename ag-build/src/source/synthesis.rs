@@ -102,9 +102,19 @@ impl<F: GpuField> NameAndSource for Field<F> {
             Self::Field(_) => {
                 // If it's an extension field.
                 if let Some(sub_field_name) = F::sub_field_name() {
-                    String::from(FIELD2_SRC)
-                        .replace("FIELD2", &F::name())
-                        .replace("FIELD", &sub_field_name)
+                    let non_residue = F::non_residue()
+                        .expect("An extension field has a non-residue");
+                    [
+                        non_residue_params(
+                            &FieldConstants::of::<F>(),
+                            &non_residue,
+                            limb,
+                        ),
+                        String::from(FIELD2_SRC),
+                    ]
+                    .join("\n")
+                    .replace("FIELD2", &F::name())
+                    .replace("FIELD", &sub_field_name)
                 } else {
                     field_source::<F>(limb).replace("FIELD", &F::name())
                 }
@@ -122,6 +132,42 @@ impl<F: GpuField> NameAndSource for Field<F> {
     }
 }
 
+/// A prime field that is only given by its modulus, without a `GpuField`
+/// implementation.
+pub struct ModulusField {
+    /// The name of the field in the GPU source.
+    name: String,
+    /// The Montgomery constants of the field.
+    constants: FieldConstants,
+    /// The largest `s` such that `2^s` divides `P - 1`.
+    two_adicity: u32,
+}
+
+impl ModulusField {
+    pub fn new(
+        name: String, constants: FieldConstants, two_adicity: u32,
+    ) -> Self {
+        Self {
+            name,
+            constants,
+            two_adicity,
+        }
+    }
+}
+
+impl NameAndSource for ModulusField {
+    fn name(&self) -> String { self.name.clone() }
+
+    fn source(&self, limb: Limb32Or64) -> String {
+        format!(
+            "#define FIELD_TWO_ADICITY {}\n{}",
+            self.two_adicity,
+            field_source_of(&self.constants, limb)
+        )
+        .replace("FIELD", &self.name)
+    }
+}
+
 /// Struct that generates FFT GPU source code.
 pub struct Fft<F: GpuName>(PhantomData<F>);
 
@@ -137,6 +183,59 @@ impl<F: GpuName> NameAndSource for Fft<F> {
     }
 }
 
+/// Struct that generates the GPU source code of element-wise field operations.
+pub struct FieldOps<F: GpuName>(PhantomData<F>);
+
+impl<F: GpuName> FieldOps<F> {
+    pub fn new() -> Self { Self(PhantomData) }
+}
+
+impl<F: GpuName> NameAndSource for FieldOps<F> {
+    fn name(&self) -> String { F::name() }
+
+    fn source(&self, _limb: Limb32Or64) -> String {
+        String::from(FIELD_OPS_SRC).replace("FIELD", &F::name())
+    }
+}
+
+/// Struct that generates the GPU source code of the tiled matrix transpose.
+///
+/// It's used by both the FFT and the element-wise field operations.
+pub struct Transpose<F: GpuName>(PhantomData<F>);
+
+impl<F: GpuName> Transpose<F> {
+    pub fn new() -> Self { Self(PhantomData) }
+}
+
+impl<F: GpuName> NameAndSource for Transpose<F> {
+    fn name(&self) -> String { F::name() }
+
+    fn source(&self, _limb: Limb32Or64) -> String {
+        String::from(TRANSPOSE_SRC).replace("FIELD", &F::name())
+    }
+}
+
+/// Struct that generates the GPU source code of the conversions from and to
+/// the canonical byte encoding.
+pub struct FieldBytes<F: GpuField>(PhantomData<F>);
+
+impl<F: GpuField> FieldBytes<F> {
+    pub fn new() -> Self { Self(PhantomData) }
+}
+
+impl<F: GpuField> NameAndSource for FieldBytes<F> {
+    fn name(&self) -> String { F::name() }
+
+    fn source(&self, _limb: Limb32Or64) -> String {
+        format!(
+            "#define FIELD_BYTES {}\n{}",
+            canonical_bytes::<F>(),
+            FIELD_BYTES_SRC
+        )
+        .replace("FIELD", &F::name())
+    }
+}
+
 /// Struct that generates FFT for G1 GPU source code.
 pub struct Ec<C: GpuCurveName>(PhantomData<C>);
 
@@ -155,6 +254,27 @@ impl<C: GpuCurveName> NameAndSource for Ec<C> {
     }
 }
 
+/// Struct that generates the GPU source code of the compression of curve
+/// points into their canonical encoding.
+///
+/// The base field must be a prime field whose [`FieldBytes`] are generated as
+/// well.
+pub struct EcCompress<C: GpuCurveName>(PhantomData<C>);
+
+impl<C: GpuCurveName> EcCompress<C> {
+    pub fn new() -> Self { Self(PhantomData) }
+}
+
+impl<C: GpuCurveName> NameAndSource for EcCompress<C> {
+    fn name(&self) -> String { C::Affine::name() }
+
+    fn source(&self, _limb: Limb32Or64) -> String {
+        String::from(EC_COMPRESS_SRC)
+            .replace("BASE", &C::Base::name())
+            .replace("POINT", &C::Affine::name())
+    }
+}
+
 /// Struct that generates FFT for G1 GPU source code.
 pub struct EcFft<C: GpuCurveName>(PhantomData<C>);
 
@@ -1,5 +1,6 @@
 use super::limb::{Limb, Limb32, Limb32Or64, Limb64};
 use ag_types::GpuField;
+use num_bigint::BigUint;
 use std::fmt::Write;
 
 macro_rules! include_cl {
@@ -10,10 +11,16 @@ macro_rules! include_cl {
 
 pub static COMMON_SRC: &str = include_cl!("common.cl");
 pub static FIELD_SRC: &str = include_cl!("field.cl");
+pub static FIELD64_SRC: &str = include_cl!("field64.cl");
+pub static FIELD_COMMON_SRC: &str = include_cl!("field-common.cl");
 pub static FIELD2_SRC: &str = include_cl!("field2.cl");
 pub static EC_SRC: &str = include_cl!("ec.cl");
 pub static FFT_SRC: &str = include_cl!("fft.cl");
+pub static FIELD_OPS_SRC: &str = include_cl!("field-ops.cl");
+pub static TRANSPOSE_SRC: &str = include_cl!("transpose.cl");
+pub static FIELD_BYTES_SRC: &str = include_cl!("field-bytes.cl");
 pub static EC_FFT_SRC: &str = include_cl!("ec-fft.cl");
+pub static EC_COMPRESS_SRC: &str = include_cl!("ec-compress.cl");
 pub static MULTIEXP_SRC: &str = include_cl!("multiexp.cl");
 
 #[cfg(test)]
@@ -31,15 +38,65 @@ pub fn const_field<L: Limb>(name: &str, limbs: Vec<L>) -> String {
     )
 }
 
-/// Generates CUDA/OpenCL constants and type definitions of prime-field `F`
-pub fn params<F, L>() -> String
-where
-    F: GpuField,
-    L: Limb,
-{
-    let one = L::one_limbs::<F>(); // Get Montgomery form of F::one()
-    let p = L::modulus_limbs::<F>(); // Get field modulus in non-Montgomery form
-    let r2 = L::calculate_r2::<F>();
+/// The Montgomery constants of a prime field as 32-bit limbs (least
+/// significant limb first).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldConstants {
+    /// `R mod P`, the Montgomery form of one.
+    pub one: Vec<u32>,
+    /// `R ^ 2 mod P`.
+    pub r2: Vec<u32>,
+    /// The field modulus in non-Montgomery form.
+    pub modulus: Vec<u32>,
+}
+
+impl FieldConstants {
+    /// Returns the constants of the prime field `F`.
+    pub fn of<F: GpuField>() -> Self {
+        Self {
+            one: F::one(),
+            r2: F::r2(),
+            modulus: F::modulus(),
+        }
+    }
+
+    /// Computes the constants of the prime field with the given modulus.
+    ///
+    /// Moduli of up to 64 bits use a single 64-bit limb. Larger ones use a
+    /// whole number of 64-bit limbs with at least one spare bit, as the
+    /// multi-limb addition drops the final carry.
+    pub fn from_modulus(modulus: &BigUint) -> Self {
+        let bits = modulus.bits() as usize;
+        let limbs = if bits <= 64 { 2 } else { 2 * (bits / 64 + 1) };
+        let r = BigUint::from(1u32) << (32 * limbs);
+        let to_limbs = |n: BigUint| {
+            let mut digits = n.to_u32_digits();
+            digits.resize(limbs, 0);
+            digits
+        };
+        Self {
+            one: to_limbs(&r % modulus),
+            r2: to_limbs((&r * &r) % modulus),
+            modulus: to_limbs(modulus.clone()),
+        }
+    }
+
+    /// Returns the number of bytes of an element in memory, on the host as
+    /// well as on the GPU.
+    pub fn element_bytes(&self) -> usize { self.modulus.len() * 4 }
+}
+
+/// Generates CUDA/OpenCL constants and type definitions of a prime field
+///
+/// The field arithmetic works on elements in Montgomery form, hence
+/// `FIELD_ONE` is `R mod P`, the identity of `FIELD_mul`. `FIELD_ONE_NORMAL`
+/// is the literal one in normal form, it's only meant for converting out of
+/// Montgomery form by multiplying with it. Using it as one in the arithmetic
+/// multiplies by `R^-1` instead.
+pub fn params<L: Limb>(constants: &FieldConstants) -> String {
+    let one = L::from_u32_limbs(&constants.one); // Montgomery form of one
+    let p = L::from_u32_limbs(&constants.modulus); // Non-Montgomery modulus
+    let r2 = L::from_u32_limbs(&constants.r2);
     let limbs = one.len(); // Number of limbs
     let inv = L::calc_inv(p[0]);
     let limb_def = format!("#define FIELD_limb {}", L::opencl_type());
@@ -47,14 +104,19 @@ where
     let limb_bits_def = format!("#define FIELD_LIMB_BITS {}", L::bits());
     let p_def = const_field("FIELD_P", p);
     let r2_def = const_field("FIELD_R2", r2);
+    let mut one_normal = vec![0u32; constants.one.len()];
+    one_normal[0] = 1;
+    let one_normal = L::from_u32_limbs(&one_normal);
     let one_def = const_field("FIELD_ONE", one);
+    let one_normal_def = const_field("FIELD_ONE_NORMAL", one_normal);
     let zero_def = const_field("FIELD_ZERO", vec![L::zero(); limbs]);
     let inv_def = format!("#define FIELD_INV {}", inv.value());
     let type_def =
-        "typedef struct { FIELD_limb val[FIELD_LIMBS]; } FIELD;".to_string();
-    let type_repr_def =
-        "typedef struct { FIELD_limb val[FIELD_LIMBS]; } FIELD_repr;"
+        "typedef struct ELEMENT_ALIGN { FIELD_limb val[FIELD_LIMBS]; } FIELD;"
             .to_string();
+    let type_repr_def = "typedef struct ELEMENT_ALIGN { FIELD_limb \
+                         val[FIELD_LIMBS]; } FIELD_repr;"
+        .to_string();
     [
         limb_def,
         limbs_def,
@@ -63,6 +125,7 @@ where
         type_def,
         type_repr_def,
         one_def,
+        one_normal_def,
         p_def,
         r2_def,
         zero_def,
@@ -70,36 +133,121 @@ where
     .join("\n")
 }
 
+/// Returns the number of bytes of an element of `F` in memory, on the host as
+/// well as on the GPU.
+pub fn element_bytes<F: GpuField>() -> usize {
+    FieldConstants::of::<F>().element_bytes()
+}
+
+/// Returns the number of bytes of the canonical encoding of an element of `F`,
+/// which is the number of bits of the modulus rounded up to whole bytes.
+pub fn canonical_bytes<F: GpuField>() -> usize {
+    let modulus = F::modulus();
+    let top = modulus.iter().rposition(|&limb| limb != 0).unwrap_or(0);
+    let bits = 32 * top + (32 - modulus[top].leading_zeros() as usize);
+    (bits + 7) / 8
+}
+
+/// The modulus of the Goldilocks field `2^64 - 2^32 + 1` as 32-bit limbs.
+const GOLDILOCKS_MODULUS: [u32; 2] = [1, u32::MAX];
+
+/// Returns whether the modulus fits into a single 64-bit limb.
+pub fn is_field64(constants: &FieldConstants) -> bool {
+    constants.modulus.len() <= 2
+}
+
+/// Returns whether the modulus is the one of the Goldilocks field
+/// `2^64 - 2^32 + 1`.
+pub fn is_goldilocks(constants: &FieldConstants) -> bool {
+    constants.modulus == GOLDILOCKS_MODULUS
+}
+
+/// Generates the definitions of the non-residue `b` of a quadratic extension
+/// field `F[u] / (u^2 - b)`, whose sub-field has the given constants.
+///
+/// `non_residue` is in Montgomery form. The limbs are the ones of the
+/// sub-field, see [`field_source_of`].
+pub fn non_residue_params(
+    constants: &FieldConstants, non_residue: &[u32], limb: Limb32Or64,
+) -> String {
+    let non_residue_def = if is_field64(constants) {
+        const_field("FIELD2_NON_RESIDUE", Limb64::from_u32_limbs(non_residue))
+    } else {
+        match limb {
+            Limb32Or64::Limb32 => const_field(
+                "FIELD2_NON_RESIDUE",
+                Limb32::from_u32_limbs(non_residue),
+            ),
+            Limb32Or64::Limb64 => const_field(
+                "FIELD2_NON_RESIDUE",
+                Limb64::from_u32_limbs(non_residue),
+            ),
+        }
+    };
+    // A non-residue of `-1` is multiplied with a negation.
+    let minus_one = BigUint::from_slice(&constants.modulus)
+        - BigUint::from_slice(&constants.one);
+    if BigUint::from_slice(non_residue) == minus_one {
+        [
+            non_residue_def,
+            "#define FIELD2_NON_RESIDUE_IS_MINUS_ONE".to_string(),
+        ]
+        .join("\n")
+    } else {
+        non_residue_def
+    }
+}
+
 pub fn field_source<F: GpuField>(limb: Limb32Or64) -> String {
+    field_source_of(&FieldConstants::of::<F>(), limb)
+}
+
+/// Generates the field source for the prime field with the given constants.
+pub fn field_source_of(constants: &FieldConstants, limb: Limb32Or64) -> String {
+    // Fields with a single 64-bit limb use their own implementation,
+    // independent of the limb size of the other fields. It has the same
+    // memory layout as the multi-limb one.
+    if is_field64(constants) {
+        let mut source = vec![params::<Limb64>(constants)];
+        if is_goldilocks(constants) {
+            source.push("#define FIELD_GOLDILOCKS".to_string());
+        }
+        source.extend([
+            String::from(FIELD64_SRC),
+            String::from(FIELD_COMMON_SRC),
+        ]);
+        return source.join("\n");
+    }
+
     match limb {
         Limb32Or64::Limb32 => [
-            params::<F, Limb32>(),
-            field_add_sub_nvidia::<F, Limb32>().expect("preallocated"),
+            params::<Limb32>(constants),
+            field_add_sub_nvidia::<Limb32>(constants.modulus.len())
+                .expect("preallocated"),
             String::from(FIELD_SRC),
+            String::from(FIELD_COMMON_SRC),
         ]
         .join("\n"),
         Limb32Or64::Limb64 => [
-            params::<F, Limb64>(),
-            field_add_sub_nvidia::<F, Limb64>().expect("preallocated"),
+            params::<Limb64>(constants),
+            field_add_sub_nvidia::<Limb64>(constants.modulus.len() / 2)
+                .expect("preallocated"),
             String::from(FIELD_SRC),
+            String::from(FIELD_COMMON_SRC),
         ]
         .join("\n"),
     }
 }
 
 /// Generates PTX-Assembly implementation of FIELD_add_/FIELD_sub_
-fn field_add_sub_nvidia<F, L>() -> Result<String, std::fmt::Error>
-where
-    F: GpuField,
-    L: Limb,
-{
+fn field_add_sub_nvidia<L: Limb>(
+    len: usize,
+) -> Result<String, std::fmt::Error> {
     let mut result = String::new();
     let (ptx_type, ptx_reg) = L::ptx_info();
 
     writeln!(result, "#if defined(OPENCL_NVIDIA) || defined(CUDA)\n")?;
     for op in &["sub", "add"] {
-        let len = L::one_limbs::<F>().len();
-
         writeln!(
             result,
             "DEVICE FIELD FIELD_{}_nvidia(FIELD a, FIELD b) {{",
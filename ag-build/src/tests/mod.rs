@@ -1,5 +1,12 @@
 mod program;
+mod test_alignment;
+mod test_cache;
 #[cfg(feature = "cuda")]
 mod test_ec;
+mod test_extension_field;
 mod test_fields;
+#[cfg(feature = "cuda")]
+mod test_header;
+mod test_modulus_field;
+mod test_unsupported;
 mod types;
@@ -60,6 +60,84 @@ lazy_static! {
     };
 }
 
+/// A kernel that returns whether the multiplication uses 128-bit integers.
+#[cfg(feature = "opencl")]
+const U128_USED_KERNEL: &str =
+    "KERNEL void test_u128_used(GLOBAL uint *result) {
+#ifdef OPENCL_U128
+  result[0] = 1;
+#else
+  result[0] = 0;
+#endif
+}";
+
+/// The test programs with 64-bit limbs that are multiplied with 128-bit
+/// integers, if the OpenCL compiler supports them. The second one is forced
+/// to use the portable fallback.
+#[cfg(feature = "opencl")]
+lazy_static! {
+    pub static ref OPENCL_U128_PROGRAMS: Mutex<(Program, Program)> = {
+        let device =
+            *Device::all().first().expect("Cannot get a default device");
+        let opencl_device = device.opencl_device().unwrap();
+        let build = |source: SourceBuilder| {
+            let source = source
+                .opencl_use_u128(true)
+                .append_source(U128_USED_KERNEL.into())
+                .build_64_bit_limbs();
+            let program =
+                opencl::Program::from_opencl(opencl_device, &source).unwrap();
+            Program::Opencl(program)
+        };
+        Mutex::new((
+            build(test_source()),
+            build(test_source().force_u128_fallback()),
+        ))
+    };
+}
+
+fn broken_source() -> SourceBuilder {
+    test_source()
+        .append_source("KERNEL void broken() { undefined_call(); }".into())
+}
+
+/// nvcc always compiles the source, the validation adds the errors of the
+/// compiler to the message.
+#[cfg(feature = "cuda")]
+#[test]
+#[should_panic(expected = "undefined_call")]
+fn test_validate_compile_broken_source_cuda() {
+    generate_cuda(&broken_source().validate_compile(true));
+}
+
+/// The OpenCL source is compiled at run time, hence a broken one only fails
+/// the build if the validation is enabled.
+#[cfg(feature = "opencl")]
+#[test]
+fn test_validate_compile_broken_source_opencl() {
+    generate_opencl(&broken_source());
+
+    let validated = std::panic::catch_unwind(|| {
+        generate_opencl(&broken_source().validate_compile(true))
+    });
+    // The validation is skipped if there is no offline compiler.
+    let compiler = std::env::var("EC_GPU_OPENCL_OFFLINE_COMPILER")
+        .unwrap_or_else(|_| "clang".to_string());
+    if std::process::Command::new(compiler)
+        .arg("--version")
+        .output()
+        .is_ok()
+    {
+        let message = validated.expect_err("The broken source was accepted");
+        let message = message
+            .downcast_ref::<String>()
+            .expect("The panic has a formatted message");
+        assert!(message.contains("OpenCL offline compilation failed"));
+    } else {
+        assert!(validated.is_ok());
+    }
+}
+
 use rust_gpu_tools::program_closures;
 
 pub fn call_kernel(name: &str, scalars: &[GpuScalar], uints: &[u32]) -> Scalar {
@@ -119,3 +197,52 @@ pub fn call_kernel(name: &str, scalars: &[GpuScalar], uints: &[u32]) -> Scalar {
         cuda_result
     }
 }
+
+/// Calls the kernel like [`call_kernel`], but only on the OpenCL program that
+/// uses 128-bit integers for the multiplication, or on the one that is
+/// forced to use the portable `fallback`.
+#[cfg(feature = "opencl")]
+pub fn call_kernel_opencl_u128(
+    fallback: bool, name: &str, scalars: &[GpuScalar], uints: &[u32],
+) -> Scalar {
+    let closures =
+        program_closures!(|program, _args| -> Result<Scalar, NoError> {
+            let mut cpu_buffer = vec![GpuScalar::default()];
+            let buffer = program.create_buffer_from_slice(&cpu_buffer).unwrap();
+
+            let mut kernel = program.create_kernel(name, 1, 64).unwrap();
+            for scalar in scalars {
+                kernel = kernel.arg(scalar);
+            }
+            for uint in uints {
+                kernel = kernel.arg(uint);
+            }
+            kernel.arg(&buffer).run().unwrap();
+
+            program.read_into_buffer(&buffer, &mut cpu_buffer).unwrap();
+            Ok(cpu_buffer[0].0)
+        });
+
+    let programs = OPENCL_U128_PROGRAMS.lock().unwrap();
+    let program = if fallback { &programs.1 } else { &programs.0 };
+    program.run(closures, ()).unwrap()
+}
+
+/// Returns whether the OpenCL program of [`call_kernel_opencl_u128`] uses
+/// 128-bit integers for the multiplication.
+#[cfg(feature = "opencl")]
+pub fn opencl_u128_used(fallback: bool) -> bool {
+    let closures =
+        program_closures!(|program, _args| -> Result<bool, NoError> {
+            let mut cpu_buffer = vec![u32::MAX];
+            let buffer = program.create_buffer_from_slice(&cpu_buffer).unwrap();
+            let kernel = program.create_kernel("test_u128_used", 1, 1).unwrap();
+            kernel.arg(&buffer).run().unwrap();
+            program.read_into_buffer(&buffer, &mut cpu_buffer).unwrap();
+            Ok(cpu_buffer[0] == 1)
+        });
+
+    let programs = OPENCL_U128_PROGRAMS.lock().unwrap();
+    let program = if fallback { &programs.1 } else { &programs.0 };
+    program.run(closures, ()).unwrap()
+}
@@ -0,0 +1,42 @@
+use super::types::{G1Affine, Scalar};
+use crate::SourceBuilder;
+
+#[test]
+fn test_field_alignment() {
+    let source = SourceBuilder::new()
+        .add_fft::<Scalar>()
+        .add_ec::<G1Affine>()
+        .field_alignment(16);
+    assert!(source
+        .build_32_bit_limbs()
+        .contains("#define ELEMENT_ALIGNMENT 16\n"));
+    assert!(source
+        .c_header()
+        .contains("typedef struct __attribute__((aligned(16))) {"));
+
+    // By default the types are aligned like their limbs.
+    let source = SourceBuilder::new().add_fft::<Scalar>();
+    assert!(!source
+        .build_32_bit_limbs()
+        .contains("#define ELEMENT_ALIGNMENT"));
+    assert!(!source.c_header().contains("ELEMENT_ALIGN"));
+}
+
+#[test]
+#[should_panic(expected = "doesn't divide the 48 bytes")]
+fn test_field_alignment_padding() {
+    // An element of the base field would be padded from 48 to 64 bytes.
+    SourceBuilder::new()
+        .add_ec::<G1Affine>()
+        .field_alignment(32)
+        .build_32_bit_limbs();
+}
+
+#[test]
+#[should_panic(expected = "is not a power of two")]
+fn test_field_alignment_power_of_two() {
+    SourceBuilder::new()
+        .add_fft::<Scalar>()
+        .field_alignment(24)
+        .build_64_bit_limbs();
+}
@@ -0,0 +1,30 @@
+use crate::compile::kernel_digest;
+
+#[test]
+fn test_kernel_digest() {
+    let digest = kernel_digest("source", "nvcc");
+    assert_eq!(digest, kernel_digest("source", "nvcc"));
+    assert_eq!(digest.len(), 64);
+    assert_ne!(digest, kernel_digest("other source", "nvcc"));
+    assert_ne!(digest, kernel_digest("source", "nvcc --fatbin"));
+    // The inputs are separated, so they cannot be shifted into each other.
+    assert_ne!(kernel_digest("ab", "c"), kernel_digest("a", "bc"));
+}
+
+#[cfg(feature = "opencl")]
+#[test]
+fn test_opencl_source_cache() {
+    use super::types::{Base, G1Affine};
+    use crate::{compile::generate_opencl, SourceBuilder};
+
+    let fields = SourceBuilder::new().add_field::<Base>();
+    let path = generate_opencl(&fields);
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        fields.build_64_bit_limbs()
+    );
+    // The same source is reused, another one gets another file.
+    assert_eq!(path, generate_opencl(&fields));
+    let curve = SourceBuilder::new().add_test::<G1Affine, Base>();
+    assert_ne!(path, generate_opencl(&curve));
+}
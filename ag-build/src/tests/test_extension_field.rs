@@ -0,0 +1,145 @@
+use ag_types::GpuName;
+use ark_ff::{Field, Fp2, Fp2Config, MontFp, UniformRand};
+use chosen_ark_suite::{Fq, Fq2};
+use rand::thread_rng;
+use rust_gpu_tools::{program_closures, Device, GPUError, Program};
+
+use crate::SourceBuilder;
+
+/// An extension of the base field with the non-residue `2`, instead of the
+/// `-1` of BLS12-381.
+struct Fq2TwoConfig;
+
+impl Fp2Config for Fq2TwoConfig {
+    type Fp = Fq;
+
+    const FROBENIUS_COEFF_FP2_C1: &'static [Fq] = &[
+        MontFp!("1"),
+        MontFp!("4002409555221667393417789825735904156556882819939007885332058136124031650490837864442687629129015664037894272559786"),
+    ];
+    const NONRESIDUE: Fq = MontFp!("2");
+}
+
+type Fq2Two = Fp2<Fq2TwoConfig>;
+
+/// The products and squares, as pairs of coordinates.
+type Results = Result<(Vec<[Fq; 2]>, Vec<[Fq; 2]>), GPUError>;
+
+fn test_source<P: Fp2Config<Fp = Fq>>() -> SourceBuilder {
+    let kernel =
+        "KERNEL void test_fp2_ops(GLOBAL FIELD2 *a, GLOBAL FIELD2 *b, \
+                  GLOBAL FIELD2 *mul, GLOBAL FIELD2 *sqr, uint n) {
+  uint i = GET_GLOBAL_ID();
+  if (i < n) {
+    mul[i] = FIELD2_mul(a[i], b[i]);
+    sqr[i] = FIELD2_sqr(a[i]);
+  }
+}";
+    SourceBuilder::new()
+        .add_extension_field::<Fp2<P>>()
+        .append_source(kernel.replace("FIELD2", &Fp2::<P>::name()))
+}
+
+fn program(source: &SourceBuilder) -> Program {
+    let device = *Device::all().first().expect("Cannot get a default device");
+
+    #[cfg(feature = "cuda")]
+    {
+        use rust_gpu_tools::cuda;
+        use std::ffi::CString;
+
+        let fatbin_path = crate::compile::generate_cuda(source);
+        let fatbin_path_cstring = CString::new(
+            fatbin_path.to_str().expect("path is not valid UTF-8."),
+        )
+        .expect("path contains NULL byte.");
+        let program = cuda::Program::from_binary(
+            device.cuda_device().unwrap(),
+            fatbin_path_cstring.as_c_str(),
+        )
+        .unwrap();
+        Program::Cuda(program)
+    }
+
+    #[cfg(not(feature = "cuda"))]
+    {
+        use rust_gpu_tools::opencl;
+
+        let source = source.build_32_bit_limbs();
+        let program = opencl::Program::from_opencl(
+            device.opencl_device().unwrap(),
+            &source,
+        )
+        .unwrap();
+        Program::Opencl(program)
+    }
+}
+
+/// Compares the multiplication and squaring on the GPU with arkworks.
+fn check_mul_sqr<P: Fp2Config<Fp = Fq>>() {
+    let mut rng = thread_rng();
+    let n = 64;
+    let a = (0..n).map(|_| Fp2::<P>::rand(&mut rng)).collect::<Vec<_>>();
+    let b = (0..n).map(|_| Fp2::<P>::rand(&mut rng)).collect::<Vec<_>>();
+    // The coordinates are uploaded explicitly, as the layout of the arkworks
+    // type isn't guaranteed.
+    let a_gpu = a.iter().map(|x| [x.c0, x.c1]).collect::<Vec<_>>();
+    let b_gpu = b.iter().map(|x| [x.c0, x.c1]).collect::<Vec<_>>();
+
+    let closures = program_closures!(|program, _args| -> Results {
+        let a_buffer = program.create_buffer_from_slice(&a_gpu)?;
+        let b_buffer = program.create_buffer_from_slice(&b_gpu)?;
+        let mul_buffer = unsafe { program.create_buffer::<[Fq; 2]>(n)? };
+        let sqr_buffer = unsafe { program.create_buffer::<[Fq; 2]>(n)? };
+
+        let kernel = program.create_kernel("test_fp2_ops", 1, 64)?;
+        kernel
+            .arg(&a_buffer)
+            .arg(&b_buffer)
+            .arg(&mul_buffer)
+            .arg(&sqr_buffer)
+            .arg(&(n as u32))
+            .run()?;
+
+        let mut mul = vec![[Fq::ZERO; 2]; n];
+        let mut sqr = vec![[Fq::ZERO; 2]; n];
+        program.read_into_buffer(&mul_buffer, &mut mul)?;
+        program.read_into_buffer(&sqr_buffer, &mut sqr)?;
+        Ok((mul, sqr))
+    });
+    let (mul, sqr) = program(&test_source::<P>()).run(closures, ()).unwrap();
+
+    for i in 0..n {
+        let product = a[i] * b[i];
+        assert_eq!(mul[i], [product.c0, product.c1], "mul {}", i);
+        let square = a[i].square();
+        assert_eq!(sqr[i], [square.c0, square.c1], "sqr {}", i);
+    }
+}
+
+#[test]
+fn test_extension_field_minus_one() {
+    check_mul_sqr::<chosen_ark_suite::Fq2Config>();
+}
+
+#[test]
+fn test_extension_field_non_residue() { check_mul_sqr::<Fq2TwoConfig>(); }
+
+#[test]
+fn test_extension_field_source() {
+    let minus_one = format!("#define {}_NON_RESIDUE_IS_MINUS_ONE", Fq2::name());
+    let source =
+        test_source::<chosen_ark_suite::Fq2Config>().build_64_bit_limbs();
+    assert!(source.contains(&minus_one));
+
+    let name = Fq2Two::name();
+    let source = test_source::<Fq2TwoConfig>().build_64_bit_limbs();
+    assert!(source.contains(&format!("{}_NON_RESIDUE =", name)));
+    assert!(!source.contains(&format!("{}_NON_RESIDUE_IS_MINUS_ONE", name)));
+}
+
+#[test]
+#[should_panic(expected = "is not an extension field")]
+fn test_add_extension_field_prime_field() {
+    SourceBuilder::new().add_extension_field::<Fq>();
+}
@@ -105,3 +105,60 @@ fn test_mont() {
         assert_eq!(call_kernel("test_mont", &[GpuScalar(a)], &[]), b);
     }
 }
+
+#[cfg(feature = "opencl")]
+#[test]
+fn test_opencl_u128_mul() {
+    use super::program::call_kernel_opencl_u128;
+
+    let mut rng = thread_rng();
+    for _ in 0..10 {
+        let a = Scalar::rand(&mut rng);
+        let b = Scalar::rand(&mut rng);
+        let portable =
+            call_kernel("test_mul", &[GpuScalar(a), GpuScalar(b)], &[]);
+        let u128 = call_kernel_opencl_u128(
+            false,
+            "test_mul",
+            &[GpuScalar(a), GpuScalar(b)],
+            &[],
+        );
+        assert_eq!(portable, u128);
+        assert_eq!(u128, a * b);
+
+        let c = rng.gen::<u32>();
+        assert_eq!(
+            call_kernel_opencl_u128(false, "test_pow", &[GpuScalar(a)], &[c]),
+            a.pow(&[c as u64])
+        );
+        assert_eq!(
+            call_kernel_opencl_u128(false, "test_sqr", &[GpuScalar(a)], &[]),
+            a.square()
+        );
+    }
+}
+
+#[cfg(feature = "opencl")]
+#[test]
+fn test_opencl_u128_fallback() {
+    use super::program::{call_kernel_opencl_u128, opencl_u128_used};
+
+    // 128-bit integers were requested, but the portable code is used.
+    assert!(!opencl_u128_used(true));
+    let mut rng = thread_rng();
+    for _ in 0..10 {
+        let a = Scalar::rand(&mut rng);
+        let b = Scalar::rand(&mut rng);
+        let args = [GpuScalar(a), GpuScalar(b)];
+        let fallback = call_kernel_opencl_u128(true, "test_mul", &args, &[]);
+        assert_eq!(fallback, a * b);
+        assert_eq!(
+            fallback,
+            call_kernel_opencl_u128(false, "test_mul", &args, &[])
+        );
+        assert_eq!(
+            call_kernel_opencl_u128(true, "test_sqr", &[GpuScalar(a)], &[]),
+            a.square()
+        );
+    }
+}
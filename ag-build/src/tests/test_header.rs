@@ -0,0 +1,71 @@
+use std::{ffi::CString, process::Command};
+
+use ag_types::GpuName;
+use rust_gpu_tools::{cuda, Device, GPUError, Program};
+
+use super::types::{G1Affine, Scalar};
+use crate::{compile::generate_cuda, SourceBuilder};
+
+#[test]
+fn test_c_header() {
+    let source = SourceBuilder::new()
+        .add_fft::<Scalar>()
+        .add_field_ops::<Scalar>()
+        .add_field_bytes::<Scalar>()
+        .add_multiexp::<G1Affine>();
+
+    // The header must be valid C.
+    let dir = tempfile::tempdir().unwrap();
+    let header_path = dir.path().join("kernels.h");
+    source.emit_c_header(&header_path).unwrap();
+    let c_path = dir.path().join("main.c");
+    std::fs::write(
+        &c_path,
+        "#include \"kernels.h\"\nint main(void) { return 0; }\n",
+    )
+    .unwrap();
+    match Command::new("cc")
+        .arg("-fsyntax-only")
+        .arg("-Werror")
+        .arg(&c_path)
+        .output()
+    {
+        Ok(output) => assert!(
+            output.status.success(),
+            "header doesn't compile:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        ),
+        Err(_) => log::warn!("no C compiler found, skipping the header check"),
+    }
+
+    // All declared kernels must resolve in the compiled program.
+    let names = source.c_header_kernel_names();
+    assert!(names.contains(&format!("{}_radix_fft", Scalar::name())));
+    assert!(names.contains(&format!("{}_multiexp", G1Affine::name())));
+    // The base field of G1 is a prime field, its points can be compressed.
+    assert!(names.contains(&format!("{}_compress", G1Affine::name())));
+    // The FFT and the field operations share the tiled transpose.
+    let transpose = format!("{}_transpose_tiled", Scalar::name());
+    assert_eq!(names.iter().filter(|name| **name == transpose).count(), 1);
+    let fatbin_path = generate_cuda(&source);
+    let device = *Device::all().first().expect("Cannot get a default device.");
+    let fatbin_path_cstring =
+        CString::new(fatbin_path.to_str().unwrap()).unwrap();
+    let program = Program::Cuda(
+        cuda::Program::from_binary(
+            device.cuda_device().unwrap(),
+            fatbin_path_cstring.as_c_str(),
+        )
+        .unwrap(),
+    );
+    let closures =
+        rust_gpu_tools::program_closures!(|program,
+                                           _args|
+         -> Result<(), GPUError> {
+            for name in &names {
+                program.create_kernel(name, 1, 1)?;
+            }
+            Ok(())
+        });
+    program.run(closures, ()).unwrap();
+}
@@ -0,0 +1,122 @@
+use num_bigint::BigUint;
+use rand::{thread_rng, Rng};
+use rust_gpu_tools::{program_closures, Device, GPUError, Program};
+
+use crate::{modulus_field_name, SourceBuilder};
+
+/// The number of 32-bit limbs of an element of the test field.
+const LIMBS: usize = 4;
+
+type Limbs = Result<Vec<u32>, GPUError>;
+
+/// `12 * 2^64 + 1`, a 68-bit prime with a two-adicity of 66.
+fn modulus() -> BigUint { (BigUint::from(12u32) << 64) + 1u32 }
+
+fn test_source() -> SourceBuilder {
+    let name = modulus_field_name(&modulus());
+    let kernel = "KERNEL void test_modulus_mul(GLOBAL FIELD_repr *a, GLOBAL \
+                  FIELD_repr *b, GLOBAL FIELD_repr *result, uint n) {
+  uint i = GET_GLOBAL_ID();
+  if (i < n) {
+    result[i] = FIELD_unmont(FIELD_mul(FIELD_mont(a[i]), FIELD_mont(b[i])));
+  }
+}";
+    SourceBuilder::new()
+        .add_field_from_modulus(modulus(), 66)
+        .append_source(kernel.replace("FIELD", &name))
+}
+
+fn program() -> Program {
+    let device = *Device::all().first().expect("Cannot get a default device");
+
+    #[cfg(feature = "cuda")]
+    {
+        use rust_gpu_tools::cuda;
+        use std::ffi::CString;
+
+        let fatbin_path = crate::compile::generate_cuda(&test_source());
+        let fatbin_path_cstring = CString::new(
+            fatbin_path.to_str().expect("path is not valid UTF-8."),
+        )
+        .expect("path contains NULL byte.");
+        let program = cuda::Program::from_binary(
+            device.cuda_device().unwrap(),
+            fatbin_path_cstring.as_c_str(),
+        )
+        .unwrap();
+        Program::Cuda(program)
+    }
+
+    #[cfg(not(feature = "cuda"))]
+    {
+        use rust_gpu_tools::opencl;
+
+        let source = test_source().build_32_bit_limbs();
+        let program = opencl::Program::from_opencl(
+            device.opencl_device().unwrap(),
+            &source,
+        )
+        .unwrap();
+        Program::Opencl(program)
+    }
+}
+
+fn to_limbs(n: &BigUint) -> Vec<u32> {
+    let mut limbs = n.to_u32_digits();
+    limbs.resize(LIMBS, 0);
+    limbs
+}
+
+#[test]
+fn test_modulus_field_mul() {
+    let p = modulus();
+    let mut rng = thread_rng();
+    let n = 64;
+    let a = (0..n)
+        .map(|_| BigUint::from(rng.gen::<u128>()) % &p)
+        .collect::<Vec<_>>();
+    let b = (0..n)
+        .map(|_| BigUint::from(rng.gen::<u128>()) % &p)
+        .collect::<Vec<_>>();
+    let a_limbs = a.iter().flat_map(to_limbs).collect::<Vec<_>>();
+    let b_limbs = b.iter().flat_map(to_limbs).collect::<Vec<_>>();
+
+    let closures = program_closures!(|program, _args| -> Limbs {
+        let a_buffer = program.create_buffer_from_slice(&a_limbs)?;
+        let b_buffer = program.create_buffer_from_slice(&b_limbs)?;
+        let len = n * LIMBS;
+        let result_buffer = unsafe { program.create_buffer::<u32>(len)? };
+
+        let kernel = program.create_kernel("test_modulus_mul", 1, 64)?;
+        kernel
+            .arg(&a_buffer)
+            .arg(&b_buffer)
+            .arg(&result_buffer)
+            .arg(&(n as u32))
+            .run()?;
+
+        let mut result = vec![0u32; n * LIMBS];
+        program.read_into_buffer(&result_buffer, &mut result)?;
+        Ok(result)
+    });
+    let result = program().run(closures, ()).unwrap();
+
+    for (i, (a, b)) in a.iter().zip(&b).enumerate() {
+        let expected = to_limbs(&((a * b) % &p));
+        assert_eq!(&result[i * LIMBS..(i + 1) * LIMBS], &expected[..]);
+    }
+}
+
+#[test]
+fn test_modulus_field_source() {
+    let name = modulus_field_name(&modulus());
+    let source = test_source().build_64_bit_limbs();
+    assert!(source.contains(&format!("#define {}_TWO_ADICITY 66\n", name)));
+    assert!(source.contains(&format!("#define {}_LIMBS 2\n", name)));
+}
+
+#[test]
+#[should_panic(expected = "is not 65")]
+fn test_modulus_field_wrong_two_adicity() {
+    SourceBuilder::new().add_field_from_modulus(modulus(), 65);
+}
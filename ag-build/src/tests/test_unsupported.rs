@@ -0,0 +1,41 @@
+use ark_ec::{
+    short_weierstrass::{Affine, SWCurveConfig},
+    CurveConfig,
+};
+use ark_ff::Field;
+use ec_gpu_program::EcError;
+
+use super::types::{Base, G1Affine, Scalar};
+use crate::SourceBuilder;
+
+/// A curve with `a = 1`, which the GPU code doesn't support.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+struct NonZeroA;
+
+impl CurveConfig for NonZeroA {
+    type BaseField = Base;
+    type ScalarField = Scalar;
+
+    const COFACTOR: &'static [u64] = &[1];
+    const COFACTOR_INV: Scalar = Scalar::ONE;
+}
+
+impl SWCurveConfig for NonZeroA {
+    const COEFF_A: Base = Base::ONE;
+    const COEFF_B: Base = Base::ONE;
+    const GENERATOR: Affine<Self> = Affine::new_unchecked(Base::ONE, Base::ONE);
+}
+
+#[test]
+fn test_unsupported_curve() {
+    let result = SourceBuilder::new().try_add_ec_fft::<Affine<NonZeroA>>();
+    match result {
+        Err(EcError::UnsupportedCurve { curve, reason }) => {
+            assert!(curve.contains("NonZeroA"));
+            assert!(reason.contains("`a`"));
+        }
+        _ => panic!("expected an `UnsupportedCurve` error"),
+    }
+
+    assert!(SourceBuilder::new().try_add_ec_fft::<G1Affine>().is_ok());
+}
@@ -54,13 +54,17 @@ pub fn multiple_multiexp(
         .dev_data(&base_gpu)?
         .out_slice(&mut output)?
         .in_ref_slice(&exponents)?
+        .empty()?
         .dev_data(&buckets)?
         .val(input_len as u32)?
         .val(num_lines as u32)?
         .val(num_chunks as u32)?
         .val(num_windows as u32)?
         .val(window_size as u32)?
-        .val(neg_is_cheap)?
+        .val(neg_is_cheap as u32)?
+        .val(0u32)?
+        .empty()?
+        .val(0u32)?
         .launch(config)?
         .complete()?;
 
@@ -145,9 +149,12 @@ pub fn multiexp_gpu(
     const MAX_WINDOW_SIZE: usize = 10;
     let work_units = 128 * 256; // TODO device.work_units
     let num_terms = bases.len();
+    // `floor(log2(x))` with integers, floats may be off by one close to
+    // powers of two.
+    let terms_per_unit =
+        std::cmp::max((num_terms + work_units - 1) / work_units, 1);
     let window_size = std::cmp::min(
-        ((((num_terms + work_units - 1) / work_units) as f64).log2() as usize)
-            + 2,
+        (usize::BITS - 1 - terms_per_unit.leading_zeros()) as usize + 2,
         MAX_WINDOW_SIZE,
     );
     // windows_size * num_windows needs to be >= 256 in order for the kernel to
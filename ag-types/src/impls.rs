@@ -17,6 +17,28 @@ where Self: PrimeField
     }
 }
 
+impl<T: MontConfig<N>, const N: usize> CanonicalBytes
+    for ark_ff::Fp<MontBackend<T, N>, N>
+where Self: PrimeField
+{
+    fn modulus_le_bytes() -> Vec<u8> { Self::MODULUS.to_bytes_le() }
+
+    fn from_canonical_le_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() > N * 8 {
+            return None;
+        }
+        let mut limbs = [0u64; N];
+        for (i, byte) in bytes.iter().enumerate() {
+            limbs[i / 8] |= u64::from(*byte) << (8 * (i % 8));
+        }
+        Self::from_bigint(BigInt(limbs))
+    }
+
+    fn to_canonical_le_bytes(&self) -> Vec<u8> {
+        self.into_bigint().to_bytes_le()
+    }
+}
+
 fn u64_to_u32(limbs: &[u64]) -> Vec<u32> {
     let split_u64 =
         |limb: &u64| [(limb & u32::MAX as u64) as u32, (limb >> 32) as u32];
@@ -43,6 +65,27 @@ where P::Fp: GpuField
     fn modulus() -> Vec<u32> { <P::Fp as GpuField>::modulus() }
 
     fn sub_field_name() -> Option<String> { Some(<P::Fp as GpuName>::name()) }
+
+    fn non_residue() -> Option<Vec<u32>> {
+        // The Montgomery form of `b` is `b * R`, where `R` is the canonical
+        // value of the Montgomery form of one.
+        let r_bytes = <P::Fp as GpuField>::one()
+            .iter()
+            .flat_map(|limb| limb.to_le_bytes())
+            .collect::<Vec<_>>();
+        let r = P::Fp::from_le_bytes_mod_order(&r_bytes);
+        let bytes = (P::NONRESIDUE * r).into_bigint().to_bytes_le();
+        Some(
+            bytes
+                .chunks(4)
+                .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+                .collect(),
+        )
+    }
+
+    fn unsupported_reason() -> Option<String> {
+        <P::Fp as GpuField>::unsupported_reason()
+    }
 }
 
 impl<P: SWCurveConfig> GpuRepr for Affine<P> {
@@ -55,6 +98,16 @@ impl<P: SWCurveConfig> GpuRepr for Affine<P> {
             [self.x, self.y]
         }
     }
+
+    fn from_gpu_repr(repr: &Self::Repr) -> Self {
+        let [x, y] = *repr;
+        // The identity is represented as (0, 0), see `to_gpu_repr`.
+        if x.is_zero() && y.is_zero() {
+            Affine::identity()
+        } else {
+            Affine::new_unchecked(x, y)
+        }
+    }
 }
 
 impl<P: SWCurveConfig> GpuCurveAffine for Affine<P>
@@ -67,6 +120,26 @@ where
     type Scalar = <Affine<P> as ark_ec::AffineRepr>::ScalarField;
 
     fn is_identity(&self) -> bool { Affine::is_zero(&self) }
+
+    fn from_xy_unchecked(x: Self::Base, y: Self::Base) -> Self {
+        Self::from_gpu_repr(&[x, y])
+    }
+
+    fn to_xy(&self) -> (Self::Base, Self::Base) {
+        let [x, y] = self.to_gpu_repr();
+        (x, y)
+    }
+
+    fn unsupported_reason() -> Option<String> {
+        // The point doubling formula is specialized for `a = 0`.
+        if !P::COEFF_A.is_zero() {
+            return Some(
+                "the coefficient `a` is non-zero, only `a = 0` is supported"
+                    .to_string(),
+            );
+        }
+        <Self as GpuCurveAffine>::Base::unsupported_reason()
+    }
 }
 
 impl<T: GpuCurveAffine> GpuCurveName for T {
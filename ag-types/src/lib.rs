@@ -47,6 +47,14 @@ pub trait GpuField: GpuName {
     /// If the field is an extension field, then the name of the sub-field is
     /// returned.
     fn sub_field_name() -> Option<String> { None }
+
+    /// If the field is a quadratic extension field `F[u] / (u^2 - b)`, then
+    /// the non-residue `b` is returned as a vector of 32-bit limbs in
+    /// Montgomery form of the sub-field (least significant limb first).
+    fn non_residue() -> Option<Vec<u32>> { None }
+
+    /// Returns why the GPU code cannot handle this field, if it cannot.
+    fn unsupported_reason() -> Option<String> { None }
 }
 
 pub trait GpuCurveAffine:
@@ -57,6 +65,21 @@ pub trait GpuCurveAffine:
     type Curve: CurveGroup<Affine = Self> + MulAssign<Self::ScalarField>;
 
     fn is_identity(&self) -> bool;
+
+    /// Creates a point from its affine coordinates, without checking that it
+    /// is on the curve. Like in the GPU representation, `(0, 0)` is the point
+    /// at infinity.
+    fn from_xy_unchecked(x: Self::Base, y: Self::Base) -> Self;
+
+    /// Returns the affine coordinates of the point, `(0, 0)` for the point at
+    /// infinity.
+    fn to_xy(&self) -> (Self::Base, Self::Base);
+
+    /// Returns why the GPU code cannot handle this curve, if it cannot.
+    ///
+    /// The GPU code makes assumptions about the curve parameters, e.g. the
+    /// point doubling is only correct for curves with `a = 0`.
+    fn unsupported_reason() -> Option<String> { None }
 }
 
 pub trait PrimeFieldRepr: ark_ff::PrimeField {
@@ -65,10 +88,31 @@ pub trait PrimeFieldRepr: ark_ff::PrimeField {
     fn from_repr(repr: Self::Repr) -> Option<Self>;
 }
 
+/// A prime field whose elements can be encoded as the little-endian bytes of
+/// their canonical (non-Montgomery) value.
+///
+/// It decouples byte based interfaces from the library that implements the
+/// field.
+pub trait CanonicalBytes: Sized {
+    /// Returns the little-endian bytes of the modulus.
+    fn modulus_le_bytes() -> Vec<u8>;
+
+    /// Decodes the little-endian bytes of a value, `None` if the value is not
+    /// smaller than the modulus.
+    fn from_canonical_le_bytes(bytes: &[u8]) -> Option<Self>;
+
+    /// Returns the little-endian bytes of the value, there are as many as
+    /// [`CanonicalBytes::modulus_le_bytes`] returns.
+    fn to_canonical_le_bytes(&self) -> Vec<u8>;
+}
+
 pub trait GpuRepr {
-    type Repr;
+    type Repr: Copy;
 
     fn to_gpu_repr(&self) -> Self::Repr;
+
+    /// The inverse of [`GpuRepr::to_gpu_repr`].
+    fn from_gpu_repr(repr: &Self::Repr) -> Self;
 }
 
 /// Macro to get a unique name of an item.
@@ -93,7 +137,9 @@ macro_rules! name {
 use std::{any::Any, ops::MulAssign};
 
 use ark_ec::{AffineRepr, CurveGroup};
-use ark_ff::{BigInt, Fp2Config, MontBackend, MontConfig, PrimeField, Zero};
+use ark_ff::{
+    BigInt, BigInteger, Fp2Config, MontBackend, MontConfig, PrimeField, Zero,
+};
 
 #[test]
 fn mr_demo() -> () {
@@ -109,3 +155,20 @@ fn mr_demo() -> () {
     println!("G2 modulus: {:?}", Fq2::modulus());
     println!("G2 sub field name: {:?}", Fq2::sub_field_name());
 }
+
+#[test]
+fn test_non_residue() {
+    use ark_bls12_381::{Fq, Fq2};
+    use ark_ff::Field;
+
+    // The non-residue of BLS12-381 is `-1`, its Montgomery form is the one
+    // the field element stores.
+    let expected = (-Fq::ONE)
+        .0
+         .0
+        .iter()
+        .flat_map(|limb| [*limb as u32, (limb >> 32) as u32])
+        .collect::<Vec<_>>();
+    assert_eq!(Fq2::non_residue(), Some(expected));
+    assert_eq!(Fq::non_residue(), None);
+}
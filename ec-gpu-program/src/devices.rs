@@ -0,0 +1,209 @@
+use rust_gpu_tools::{Device, PciId, Vendor};
+
+use crate::{EcError, EcResult};
+
+/// The environment variable that restricts the devices of
+/// [`DeviceSelection::select`] to a comma separated list of indices into
+/// [`Device::all`], e.g. `EC_GPU_DEVICES=0,2`.
+pub const DEVICES_ENV: &str = "EC_GPU_DEVICES";
+
+/// A description of a GPU and of the capabilities that matter for this crate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceInfo {
+    /// The name of the device.
+    pub name: String,
+    /// The vendor of the device, e.g. "NVIDIA".
+    pub vendor: String,
+    /// The global memory of the device in bytes.
+    pub memory: u64,
+    /// The number of compute units (CUDA: streaming multiprocessors).
+    pub compute_units: u32,
+    /// The CUDA compute capability, `None` for non-CUDA devices.
+    pub compute_capability: Option<(u32, u32)>,
+    /// Whether a CUDA program can be built for the device.
+    pub cuda: bool,
+    /// Whether an OpenCL program can be built for the device.
+    pub opencl: bool,
+    /// Whether the device is known to support 64-bit atomic operations on
+    /// global memory. This is always the case for CUDA devices. For OpenCL
+    /// only devices it is derived from the vendor, as the extensions of a
+    /// device are not exposed by rust-gpu-tools.
+    pub atomics_64: bool,
+}
+
+impl DeviceInfo {
+    /// Collects the information of a single device.
+    pub fn new(device: &Device) -> Self {
+        #[cfg(feature = "cuda")]
+        let cuda = device.cuda_device().is_some();
+        #[cfg(not(feature = "cuda"))]
+        let cuda = false;
+        #[cfg(feature = "opencl")]
+        let opencl = device.opencl_device().is_some();
+        #[cfg(not(feature = "opencl"))]
+        let opencl = false;
+
+        let vendor = device.vendor();
+        // AMD and NVIDIA OpenCL drivers ship `cl_khr_int64_base_atomics`.
+        let atomics_64 = cuda || matches!(vendor, Vendor::Amd | Vendor::Nvidia);
+
+        Self {
+            name: device.name(),
+            vendor: vendor.to_string(),
+            memory: device.memory(),
+            compute_units: device.compute_units(),
+            compute_capability: device.compute_capability(),
+            cuda,
+            opencl,
+            atomics_64,
+        }
+    }
+}
+
+/// Returns the information of all GPUs that are usable with the enabled
+/// frameworks.
+pub fn list_devices() -> Vec<DeviceInfo> {
+    Device::all().into_iter().map(DeviceInfo::new).collect()
+}
+
+/// Selects the GPUs that match all of the given criteria, e.g. to leave the
+/// other GPUs of a shared server alone.
+///
+/// Without any criteria, the `EC_GPU_DEVICES` environment variable (see
+/// [`DEVICES_ENV`]) restricts the devices to the given indices, otherwise all
+/// devices are selected. As soon as a criterion is set, the environment
+/// variable is ignored, i.e. the selection in code takes precedence.
+#[derive(Clone, Debug, Default)]
+pub struct DeviceSelection {
+    indices: Option<Vec<usize>>,
+    name: Option<String>,
+    pci_ids: Option<Vec<PciId>>,
+    min_memory: Option<u64>,
+}
+
+impl DeviceSelection {
+    /// Creates a selection without any criteria.
+    pub fn new() -> Self { Self::default() }
+
+    /// Only selects the devices with the given indices into [`Device::all`].
+    pub fn indices(mut self, indices: &[usize]) -> Self {
+        self.indices = Some(indices.to_vec());
+        self
+    }
+
+    /// Only selects the devices whose name contains `name`.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Only selects the devices with the given PCI ids.
+    pub fn pci_ids(mut self, pci_ids: &[PciId]) -> Self {
+        self.pci_ids = Some(pci_ids.to_vec());
+        self
+    }
+
+    /// Only selects the devices with at least `bytes` of global memory.
+    ///
+    /// This is the total memory of a device, the memory that other processes
+    /// use isn't known.
+    pub fn min_memory(mut self, bytes: u64) -> Self {
+        self.min_memory = Some(bytes);
+        self
+    }
+
+    /// Returns the selected devices, in the order of [`Device::all`].
+    ///
+    /// Fails if an index is out of range, if the environment variable can't
+    /// be parsed or if no device matches, so that the work doesn't silently
+    /// end up on other devices.
+    pub fn select(&self) -> EcResult<Vec<&'static Device>> {
+        let devices = Device::all();
+        let indices = match (&self.indices, self.has_criteria()) {
+            (Some(indices), _) => Some(indices.clone()),
+            (None, false) => match std::env::var(DEVICES_ENV) {
+                Ok(env) => Some(parse_indices(&env)?),
+                Err(_) => None,
+            },
+            (None, true) => None,
+        };
+        if let Some(indices) = &indices {
+            if indices.iter().any(|&index| index >= devices.len()) {
+                return Err(EcError::Simple("A device index is out of range"));
+            }
+        }
+
+        let selected = devices
+            .into_iter()
+            .enumerate()
+            .filter(|(index, device)| {
+                indices
+                    .as_ref()
+                    .map_or(true, |indices| indices.contains(index))
+                    && self.name.as_ref().map_or(true, |name| {
+                        device.name().contains(name.as_str())
+                    })
+                    && self.pci_ids.as_ref().map_or(true, |pci_ids| {
+                        pci_ids.contains(&device.pci_id())
+                    })
+                    && self
+                        .min_memory
+                        .map_or(true, |bytes| device.memory() >= bytes)
+            })
+            .map(|(_, device)| device)
+            .collect::<Vec<_>>();
+        if selected.is_empty() {
+            return Err(EcError::Simple("No device matches the selection"));
+        }
+        Ok(selected)
+    }
+
+    /// Returns whether any criterion is set in code.
+    fn has_criteria(&self) -> bool {
+        self.indices.is_some()
+            || self.name.is_some()
+            || self.pci_ids.is_some()
+            || self.min_memory.is_some()
+    }
+}
+
+/// Parses a comma separated list of device indices.
+fn parse_indices(list: &str) -> EcResult<Vec<usize>> {
+    list.split(',')
+        .map(|index| {
+            index.trim().parse().map_err(|_| {
+                EcError::Simple(
+                    "EC_GPU_DEVICES is not a list of device indices",
+                )
+            })
+        })
+        .collect()
+}
+
+/// Returns the devices that are selected by the `EC_GPU_DEVICES` environment
+/// variable, or all devices if it isn't set. See [`DeviceSelection`].
+pub fn select_devices() -> EcResult<Vec<&'static Device>> {
+    DeviceSelection::new().select()
+}
+
+/// Returns the devices with the given indices into [`Device::all`]. See
+/// [`DeviceSelection::indices`].
+pub fn select_devices_by_index(
+    indices: &[usize],
+) -> EcResult<Vec<&'static Device>> {
+    DeviceSelection::new().indices(indices).select()
+}
+
+/// Returns the devices whose name contains `name`. See
+/// [`DeviceSelection::name`].
+pub fn select_devices_by_name(name: &str) -> EcResult<Vec<&'static Device>> {
+    DeviceSelection::new().name(name).select()
+}
+
+/// Returns the devices with at least `bytes` of global memory. See
+/// [`DeviceSelection::min_memory`].
+pub fn select_devices_with_min_memory(
+    bytes: u64,
+) -> EcResult<Vec<&'static Device>> {
+    DeviceSelection::new().min_memory(bytes).select()
+}
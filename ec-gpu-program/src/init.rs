@@ -0,0 +1,108 @@
+use rust_gpu_tools::{program_closures, Device, Framework, Program};
+
+use crate::{DeviceInfo, EcError, EcResult};
+
+/// The outcome of initializing a device with one of its frameworks.
+#[derive(Debug)]
+pub struct InitStatus {
+    /// The device the program was built for.
+    pub device: DeviceInfo,
+    /// The framework the program was built with.
+    pub framework: Framework,
+    /// Whether building the program and its self-test succeeded.
+    pub result: EcResult<()>,
+}
+
+/// The result of [`initialize_devices`], one status for every combination of
+/// a device and a framework it supports.
+#[derive(Debug, Default)]
+pub struct InitReport {
+    /// The statuses in the order of the devices, CUDA before OpenCL.
+    pub statuses: Vec<InitStatus>,
+}
+
+impl InitReport {
+    /// Returns whether there was at least one combination and all of them
+    /// succeeded.
+    pub fn is_ok(&self) -> bool {
+        !self.statuses.is_empty()
+            && self.statuses.iter().all(|status| status.result.is_ok())
+    }
+
+    /// Returns the combinations that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &InitStatus> {
+        self.statuses.iter().filter(|status| status.result.is_err())
+    }
+}
+
+/// Returns the enabled frameworks the device supports.
+fn frameworks(device: &Device) -> Vec<Framework> {
+    let mut frameworks = Vec::new();
+    #[cfg(feature = "cuda")]
+    if device.cuda_device().is_some() {
+        frameworks.push(Framework::Cuda);
+    }
+    #[cfg(feature = "opencl")]
+    if device.opencl_device().is_some() {
+        frameworks.push(Framework::Opencl);
+    }
+    frameworks
+}
+
+/// Builds a program for every device with every enabled framework it
+/// supports and runs [`self_test`] on it.
+///
+/// `load` builds the program, usually with [`program!`](crate::program),
+/// which is what [`initialize_all!`](crate::initialize_all) does. Failures
+/// don't stop the initialization of the other combinations, they are
+/// reported instead. This surfaces broken drivers or kernels at startup,
+/// instead of on the first heavy use.
+pub fn initialize_devices<L>(mut load: L) -> InitReport
+where L: FnMut(&Device, Framework) -> EcResult<Program> {
+    let mut statuses = Vec::new();
+    for device in Device::all() {
+        for framework in frameworks(device) {
+            let result =
+                load(device, framework).and_then(|program| self_test(&program));
+            statuses.push(InitStatus {
+                device: DeviceInfo::new(device),
+                framework,
+                result,
+            });
+        }
+    }
+    InitReport { statuses }
+}
+
+/// Checks that kernels of the program run and compute correct results.
+///
+/// It copies a few words with the `copy_words_to_offset` kernel, which every
+/// generated source contains, and reads them back.
+pub fn self_test(program: &Program) -> EcResult<()> {
+    const WORDS: [u32; 4] = [1, 2, 3, 4];
+    const OFFSET: usize = 2;
+
+    let closures = program_closures!(|program, _arg| -> EcResult<Vec<u32>> {
+        let src_buffer = program.create_buffer_from_slice(&WORDS)?;
+        let dst_buffer =
+            program.create_buffer_from_slice(&[0u32; OFFSET + WORDS.len()])?;
+        let kernel =
+            program.create_kernel("copy_words_to_offset", 1, WORDS.len())?;
+        kernel
+            .arg(&src_buffer)
+            .arg(&dst_buffer)
+            .arg(&(OFFSET as u32))
+            .arg(&(WORDS.len() as u32))
+            .run()?;
+
+        let mut result = vec![0u32; OFFSET + WORDS.len()];
+        program.read_into_buffer(&dst_buffer, &mut result)?;
+        Ok(result)
+    });
+
+    let result = program.run(closures, ())?;
+    if result[..OFFSET] != [0; OFFSET] || result[OFFSET..] != WORDS {
+        return Err(EcError::Simple("The self-test computed a wrong result"));
+    }
+    Ok(())
+}
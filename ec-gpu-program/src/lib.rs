@@ -2,6 +2,14 @@
 mod program;
 #[cfg(any(feature = "cuda", feature = "opencl"))]
 pub use program::*;
+#[cfg(any(feature = "cuda", feature = "opencl"))]
+mod devices;
+#[cfg(any(feature = "cuda", feature = "opencl"))]
+pub use devices::*;
+#[cfg(any(feature = "cuda", feature = "opencl"))]
+mod init;
+#[cfg(any(feature = "cuda", feature = "opencl"))]
+pub use init::*;
 
 #[cfg(not(any(feature = "cuda", feature = "opencl")))]
 mod place_holder;
@@ -13,11 +21,89 @@ pub enum EcError {
     #[error("EcError: {0}")]
     Simple(&'static str),
 
+    /// The length of an input doesn't match the one that is implied by the
+    /// other arguments, e.g. a buffer that doesn't hold `2^log_n` elements or
+    /// fewer roots of unity than inputs.
+    #[error("Dimension mismatch: expected {expected}, got {actual}")]
+    DimensionMismatch {
+        /// The length that was expected.
+        expected: usize,
+        /// The length that was given.
+        actual: usize,
+    },
+
+    /// An input has an invalid length, the message says which one and why.
+    #[error("Invalid length: {0}")]
+    InvalidLength(String),
+
+    /// GPU devices were found, but none of them could be used. Each entry
+    /// contains the name of a device and the reason it was rejected.
+    #[error("No usable GPU found: {}", format_rejected(.0))]
+    NoUsableDevice(Vec<(String, String)>),
+
+    /// The GPU code cannot handle the given curve.
+    #[error("Unsupported curve {curve}: {reason}")]
+    UnsupportedCurve {
+        /// The name of the curve.
+        curve: String,
+        /// Which assumption of the GPU code the curve violates.
+        reason: String,
+    },
+
+    /// A GPU memory reservation is larger than the whole memory budget.
+    #[error(
+        "Requested {requested} bytes of GPU memory, but the budget is only \
+         {limit} bytes"
+    )]
+    MemoryBudgetExceeded {
+        /// The number of bytes that were requested.
+        requested: usize,
+        /// The limit of the budget in bytes.
+        limit: usize,
+    },
+
+    /// The driver of the device doesn't allow changing its clocks or power
+    /// limits, see [`set_performance_mode`].
+    #[cfg(any(feature = "cuda", feature = "opencl"))]
+    #[error("Performance mode is not supported on device '{0}'")]
+    PerformanceModeUnsupported(String),
+
+    /// Changing the clocks of the device failed, e.g. due to missing
+    /// privileges, see [`set_performance_mode`].
+    #[cfg(any(feature = "cuda", feature = "opencl"))]
+    #[error(
+        "Setting the performance mode of device '{device}' failed: {reason}"
+    )]
+    PerformanceModeFailed {
+        /// The name of the device.
+        device: String,
+        /// The message of the tool of the driver.
+        reason: String,
+    },
+
     /// Error in case a GPU kernel execution was aborted.
     #[cfg(any(feature = "cuda", feature = "opencl"))]
     #[error("GPU call was aborted!")]
     Aborted,
 
+    /// The device failed fatally, e.g. a kernel crashed or was killed by the
+    /// watchdog of the driver. All further calls on the device fail until it
+    /// is reset, see `SharedProgram::reset_device` of the proxy.
+    #[cfg(any(feature = "cuda", feature = "opencl"))]
+    #[error("The device '{device}' was lost and needs to be reset")]
+    DeviceLost {
+        /// The name of the device.
+        device: String,
+    },
+
+    /// The data in GPU memory differs from the data that was uploaded.
+    #[cfg(any(feature = "cuda", feature = "opencl"))]
+    #[error("Upload to the GPU is corrupted at byte offset {offset}")]
+    TransferCorrupted {
+        /// The offset of the first chunk whose checksum differs.
+        offset: usize,
+    },
+
     /// An error that is bubbled up from the rust-gpu-tools library.
     #[cfg(any(feature = "cuda", feature = "opencl"))]
     #[error("GPU tools error: {0}")]
@@ -28,5 +114,23 @@ pub enum EcError {
     Io(#[from] std::io::Error),
 }
 
+impl EcError {
+    /// Creates an [`EcError::UnsupportedCurve`] error for the curve `C`.
+    pub fn unsupported_curve<C>(reason: String) -> Self {
+        Self::UnsupportedCurve {
+            curve: std::any::type_name::<C>().to_string(),
+            reason,
+        }
+    }
+}
+
+fn format_rejected(rejected: &[(String, String)]) -> String {
+    rejected
+        .iter()
+        .map(|(device, reason)| format!("'{}' ({})", device, reason))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 /// Result wrapper that is always using [`EcError`] as error.
 pub type EcResult<T> = std::result::Result<T, EcError>;
@@ -1,6 +1,6 @@
 #[macro_export]
 macro_rules! program {
-    ($device:ident) => {
+    ($device:ident $(, $framework:expr)?) => {
         compile_error!(
             "At least one of the features `cuda` or `opencl` must be enabled."
         );
@@ -10,7 +10,7 @@ macro_rules! program {
 #[cfg(feature = "test-tools")]
 #[macro_export]
 macro_rules! load_program {
-    ($device:ident) => {
+    ($device:ident $(, $framework:expr)?) => {
         compile_error!(
             "At least one of the features `cuda` or `opencl` must be enabled."
         );
@@ -7,12 +7,26 @@ use super::*;
 /// source needs to be generated via [`crate::source::generate`] in your
 /// `build.rs`.
 ///
+/// The framework is selected by [`check_framework`]. It can also be pinned by
+/// passing a [`Framework`] as second argument, see [`select_framework`].
+///
 /// It returns a `[crate::rust_gpu_tools::Program`] instance.
 macro_rules! program {
     ($device:ident) => {{
         use ec_gpu_program::*;
 
-        match check_framework($device) {
+        $crate::program!(@build $device, check_framework($device))
+    }};
+    ($device:ident, $framework:expr) => {{
+        use ec_gpu_program::*;
+
+        $crate::program!(
+            @build $device,
+            select_framework($device, $framework)
+        )
+    }};
+    (@build $device:ident, $framework:expr) => {{
+        match $framework {
             #[cfg(feature = "cuda")]
             Ok(Framework::Cuda) => build_cuda_program(
                 $device,
@@ -28,14 +42,41 @@ macro_rules! program {
     }};
 }
 
+#[macro_export]
+/// Builds the embedded program for every device with every enabled framework
+/// it supports and runs a small self-test on each of them.
+///
+/// It returns an [`InitReport`] with the status of every combination, see
+/// [`initialize_devices`]. Like [`program!`], it needs the source generated
+/// in your `build.rs`.
+macro_rules! initialize_all {
+    () => {{
+        ec_gpu_program::initialize_devices(|device, framework| {
+            $crate::program!(device, framework)
+        })
+    }};
+}
+
 #[cfg(feature = "test-tools")]
 #[macro_export]
 macro_rules! load_program {
     ($device:ident) => {{
         use ec_gpu_program::*;
+
+        $crate::load_program!(@build $device, check_framework($device))
+    }};
+    ($device:ident, $framework:expr) => {{
+        use ec_gpu_program::*;
+
+        $crate::load_program!(
+            @build $device,
+            select_framework($device, $framework)
+        )
+    }};
+    (@build $device:ident, $framework:expr) => {{
         use std::io::Read;
 
-        match check_framework($device) {
+        match $framework {
             #[cfg(feature = "cuda")]
             Ok(Framework::Cuda) => {
                 let mut file = std::fs::File::open(
@@ -94,6 +135,28 @@ pub fn check_framework(device: &Device) -> EcResult<Framework> {
     Ok(framework)
 }
 
+/// Returns the given framework if the device supports it.
+///
+/// Unlike [`check_framework`], this ignores the `EC_GPU_FRAMEWORK` environment
+/// variable, so that the framework can be pinned even if the device supports
+/// both of them.
+pub fn select_framework(
+    device: &Device, framework: Framework,
+) -> EcResult<Framework> {
+    let supported = match framework {
+        #[cfg(feature = "cuda")]
+        Framework::Cuda => device.cuda_device().is_some(),
+        #[cfg(feature = "opencl")]
+        Framework::Opencl => device.opencl_device().is_some(),
+    };
+    if !supported {
+        return Err(EcError::Simple(
+            "The device doesn't support the requested framework.",
+        ));
+    }
+    Ok(framework)
+}
+
 #[cfg(feature = "cuda")]
 pub fn build_cuda_program(
     device: &Device, kernel: &[u8],
@@ -116,3 +179,89 @@ pub fn build_opencl_program(
     let program = Program::from_opencl(opencl_device, source)?;
     Ok(rust_gpu_tools::Program::Opencl(program))
 }
+
+/// The command line tool of the NVIDIA driver, which locks and resets the
+/// clocks of a GPU.
+const NVIDIA_SMI: &str = "nvidia-smi";
+
+/// The exit code of `nvidia-smi` if the device doesn't support an operation.
+const NVIDIA_SMI_NOT_SUPPORTED: i32 = 3;
+
+/// Requests high performance clocks for the `device` (`on == true`), or
+/// releases such a request (`on == false`).
+///
+/// This is meant to be called before and after a big batch of work.
+/// rust-gpu-tools doesn't expose any clock controls, hence the tool of the
+/// driver is used. For NVIDIA GPUs the graphics clock is locked to its
+/// maximum with `nvidia-smi --lock-gpu-clocks` and reset with
+/// `nvidia-smi --reset-gpu-clocks`, which usually requires root privileges.
+///
+/// Other vendors, devices whose driver doesn't support locking the clocks and
+/// systems without `nvidia-smi` result in
+/// [`EcError::PerformanceModeUnsupported`], which callers should treat as a
+/// hint that the default clocks are used. If the tool fails otherwise, e.g.
+/// due to missing privileges, [`EcError::PerformanceModeFailed`] contains
+/// its message.
+pub fn set_performance_mode(device: &Device, on: bool) -> EcResult<()> {
+    let name = device.name();
+    if !matches!(device.vendor(), rust_gpu_tools::Vendor::Nvidia) {
+        return Err(EcError::PerformanceModeUnsupported(name));
+    }
+
+    // The PCI id consists of the bus and the device number, the domain and
+    // function are assumed to be zero.
+    let pci_id: u16 = device.pci_id().into();
+    let id =
+        format!("--id=00000000:{:02X}:{:02X}.0", pci_id >> 8, pci_id & 0xff);
+    if on {
+        let max_clock = run_nvidia_smi(
+            &name,
+            &[
+                &id,
+                "--query-gpu=clocks.max.graphics",
+                "--format=csv,noheader,nounits",
+            ],
+        )?;
+        let max_clock = max_clock.trim();
+        run_nvidia_smi(
+            &name,
+            &[&id, &format!("--lock-gpu-clocks={0},{0}", max_clock)],
+        )?;
+    } else {
+        run_nvidia_smi(&name, &[&id, "--reset-gpu-clocks"])?;
+    }
+    Ok(())
+}
+
+/// Runs `nvidia-smi` with `args` for the device called `name` and returns
+/// its output.
+fn run_nvidia_smi(name: &str, args: &[&str]) -> EcResult<String> {
+    let output = match std::process::Command::new(NVIDIA_SMI)
+        .args(args)
+        .output()
+    {
+        Ok(output) => output,
+        // Without the tool of the driver, the clocks cannot be changed.
+        Err(_) => {
+            return Err(EcError::PerformanceModeUnsupported(name.to_string()))
+        }
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    match output.status.code() {
+        Some(0) => Ok(stdout),
+        Some(NVIDIA_SMI_NOT_SUPPORTED) => {
+            Err(EcError::PerformanceModeUnsupported(name.to_string()))
+        }
+        // nvidia-smi prints most of its errors to stdout.
+        _ => Err(EcError::PerformanceModeFailed {
+            device: name.to_string(),
+            reason: format!(
+                "{} {}",
+                stdout.trim(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .trim()
+            .to_string(),
+        }),
+    }
+}
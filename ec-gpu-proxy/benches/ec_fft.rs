@@ -0,0 +1,76 @@
+use ag_build::generate;
+use ark_bls12_381::{Fr, G1Affine};
+use ark_ec::AffineRepr;
+use ark_ff::FftField;
+use ark_std::UniformRand;
+use criterion::{
+    black_box, criterion_group, criterion_main, BenchmarkId, Criterion,
+};
+use ec_gpu_proxy::ec_fft::EcFftKernel;
+use rust_gpu_tools::Device;
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+/// The largest FFT size (log2 of the number of elements) that is benchmarked.
+const MAX_LOG_N: u32 = 22;
+
+fn build_ec_fft() {
+    generate(&ag_build::SourceBuilder::new().add_ec_fft::<G1Affine>())
+}
+
+/// Compares a single large FFT on the first GPU with the same FFT distributed
+/// among all GPUs.
+fn bench_ec_fft_distributed(crit: &mut Criterion) {
+    let mut group = crit.benchmark_group("ec_fft");
+    // The difference between runs is so little, hence a low sample size is OK.
+    group.sample_size(10);
+
+    build_ec_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = EcFftKernel::<G1Affine>::create(programs)
+        .expect("Cannot initialize kernel!");
+    let max_points: Vec<_> = (0..1 << MAX_LOG_N)
+        .into_par_iter()
+        .map(|_| G1Affine::rand(&mut rand::thread_rng()).into_group())
+        .collect();
+
+    for log_n in 16..=MAX_LOG_N {
+        let omega = Fr::get_root_of_unity(1 << log_n).unwrap();
+        let points = &max_points[..1 << log_n];
+        group.bench_with_input(
+            BenchmarkId::new("single", log_n),
+            &log_n,
+            |bencher, &log_n| {
+                let mut input = points.to_vec();
+                bencher.iter(|| {
+                    kern.radix_ec_fft(black_box(&mut input), &omega, log_n)
+                        .unwrap();
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("distributed", log_n),
+            &log_n,
+            |bencher, &log_n| {
+                let mut input = points.to_vec();
+                bencher.iter(|| {
+                    kern.radix_ec_fft_distributed(
+                        black_box(&mut input),
+                        &omega,
+                        log_n,
+                    )
+                    .unwrap();
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_ec_fft_distributed);
+criterion_main!(benches);
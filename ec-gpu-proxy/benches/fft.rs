@@ -0,0 +1,143 @@
+use ag_build::generate;
+use ark_bls12_381::Fr;
+use ark_ff::FftField;
+use ark_std::UniformRand;
+use criterion::{
+    black_box, criterion_group, criterion_main, BenchmarkId, Criterion,
+};
+use ec_gpu_proxy::fft::FftKernel;
+use rust_gpu_tools::Device;
+
+/// The largest FFT size (log2 of the number of elements) that is benchmarked.
+const MAX_LOG_N: u32 = 10;
+
+fn build_fft() {
+    generate(
+        &ag_build::SourceBuilder::new()
+            .add_fft::<Fr>()
+            .fft_shared_twiddles(true),
+    )
+}
+
+fn bench_small_fft(crit: &mut Criterion) {
+    let mut group = crit.benchmark_group("small_fft");
+
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+
+    for log_n in 1..=MAX_LOG_N {
+        let omega = Fr::get_root_of_unity(1 << log_n).unwrap();
+        let coeffs = (0..1 << log_n)
+            .map(|_| Fr::rand(&mut rand::thread_rng()))
+            .collect::<Vec<_>>();
+        for (name, threshold) in
+            [("iterative", None), ("shared-mem", Some(MAX_LOG_N))]
+        {
+            kern.set_shared_mem_threshold(threshold);
+            group.bench_with_input(
+                BenchmarkId::new(name, log_n),
+                &log_n,
+                |bencher, &log_n| {
+                    let mut input = coeffs.clone();
+                    bencher.iter(|| {
+                        kern.radix_fft(black_box(&mut input), &omega, log_n)
+                            .unwrap();
+                    })
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+/// Compares large FFTs that read the twiddle factors from global memory with
+/// the ones that cache them in local memory.
+fn bench_shared_twiddles(crit: &mut Criterion) {
+    let mut group = crit.benchmark_group("shared_twiddles");
+
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+
+    for log_n in [16, 20] {
+        let omega = Fr::get_root_of_unity(1 << log_n).unwrap();
+        let coeffs = (0..1 << log_n)
+            .map(|_| Fr::rand(&mut rand::thread_rng()))
+            .collect::<Vec<_>>();
+        for (name, shared_twiddles) in [("global", false), ("shared", true)] {
+            kern.set_shared_twiddles(shared_twiddles);
+            group.bench_with_input(
+                BenchmarkId::new(name, log_n),
+                &log_n,
+                |bencher, &log_n| {
+                    let mut input = coeffs.clone();
+                    bencher.iter(|| {
+                        kern.radix_fft(black_box(&mut input), &omega, log_n)
+                            .unwrap();
+                    })
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+/// Compares the FFT with the portable multiplication of 64-bit limbs with the
+/// one that uses 128-bit integers, on a device whose OpenCL compiler supports
+/// them.
+#[cfg(feature = "opencl")]
+fn bench_opencl_u128(crit: &mut Criterion) {
+    use rust_gpu_tools::{opencl, Program};
+
+    const LOG_N: u32 = 16;
+    let mut group = crit.benchmark_group("opencl_u128");
+
+    let device = *Device::all().first().expect("Cannot get a default device");
+    let opencl_device = device.opencl_device().expect("Not an OpenCL device");
+    let omega = Fr::get_root_of_unity(1 << LOG_N).unwrap();
+    let coeffs = (0..1 << LOG_N)
+        .map(|_| Fr::rand(&mut rand::thread_rng()))
+        .collect::<Vec<_>>();
+    for (name, use_u128) in [("portable", false), ("u128", true)] {
+        let source = ag_build::SourceBuilder::new()
+            .add_fft::<Fr>()
+            .opencl_use_u128(use_u128)
+            .build_64_bit_limbs();
+        let program = opencl::Program::from_opencl(opencl_device, &source)
+            .expect("Cannot create program!");
+        let mut kern = FftKernel::<Fr>::create(vec![Program::Opencl(program)])
+            .expect("Cannot initialize kernel!");
+        group.bench_function(name, |bencher| {
+            let mut input = coeffs.clone();
+            bencher.iter(|| {
+                kern.radix_fft(black_box(&mut input), &omega, LOG_N)
+                    .unwrap();
+            })
+        });
+    }
+    group.finish();
+}
+
+#[cfg(not(feature = "opencl"))]
+criterion_group!(benches, bench_small_fft, bench_shared_twiddles);
+#[cfg(feature = "opencl")]
+criterion_group!(
+    benches,
+    bench_small_fft,
+    bench_shared_twiddles,
+    bench_opencl_u128
+);
+criterion_main!(benches);
@@ -50,33 +50,98 @@ fn bench_multiexp(crit: &mut Criterion) {
 
     let num_elements: Vec<_> =
         (16..MAX_ELEMENTS_POWER).map(|shift| 1 << shift).collect();
-    for num in num_elements {
-        group.bench_with_input(
-            BenchmarkId::from_parameter(num),
-            &num,
-            |bencher, &num| {
-                let (bases, skip) = SourceBuilder::get((
-                    Arc::new(max_bases[0..num].to_vec()),
-                    0,
-                ));
-                let exponents = Arc::new(max_exponents[0..num].to_vec());
+    for (num, numa_aware) in num_elements
+        .into_iter()
+        .flat_map(|num| [(num, false), (num, true)])
+    {
+        kern.set_numa_aware(numa_aware);
+        let id = if numa_aware {
+            BenchmarkId::new("numa-aware", num)
+        } else {
+            BenchmarkId::from_parameter(num)
+        };
+        group.bench_with_input(id, &num, |bencher, &num| {
+            let (bases, skip) =
+                SourceBuilder::get((Arc::new(max_bases[0..num].to_vec()), 0));
+            let exponents = Arc::new(max_exponents[0..num].to_vec());
 
-                bencher.iter(|| {
-                    let _ = black_box(
-                        kern.multiexp(
-                            &pool,
-                            bases.clone(),
-                            exponents.clone(),
-                            skip,
-                        )
-                        .unwrap(),
-                    );
-                })
-            },
-        );
+            bencher.iter(|| {
+                let _ = black_box(
+                    kern.multiexp(
+                        &pool,
+                        bases.clone(),
+                        exponents.clone(),
+                        skip,
+                    )
+                    .unwrap(),
+                );
+            })
+        });
     }
     group.finish();
 }
 
-criterion_group!(benches, bench_multiexp);
+/// Compares several independent multiexps of medium size, run one after
+/// another, with running them pipelined.
+fn bench_multiexp_pipeline(crit: &mut Criterion) {
+    const NUM_JOBS: usize = 8;
+    const JOB_SIZE: usize = 1 << 18;
+    let mut group = crit.benchmark_group("multiexp_pipeline");
+    group.sample_size(10);
+
+    build_multiexp();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let jobs: Vec<_> = (0..NUM_JOBS)
+        .map(|_| {
+            let bases: Vec<_> = (0..JOB_SIZE)
+                .into_par_iter()
+                .map(|_| G1Affine::rand(&mut rand::thread_rng()))
+                .collect();
+            let exponents: Vec<_> = (0..JOB_SIZE)
+                .into_par_iter()
+                .map(|_| Scalar::rand(&mut rand::thread_rng()).to_repr())
+                .collect();
+            (Arc::new(bases), Arc::new(exponents))
+        })
+        .collect();
+
+    group.bench_function("sequential", |bencher| {
+        bencher.iter(|| {
+            for (bases, exponents) in jobs.iter() {
+                let _ = black_box(
+                    kern.multiexp(&pool, bases.clone(), exponents.clone(), 0)
+                        .unwrap(),
+                );
+            }
+        })
+    });
+    group.bench_function("pipeline", |bencher| {
+        bencher.iter(|| {
+            let _ = black_box(kern.multiexp_pipeline(&pool, &jobs).unwrap());
+        })
+    });
+    // One further stream per device, so that two chunks are in flight.
+    let streams = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device).map(|p| vec![p]))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    kern.set_pipeline_streams(streams).unwrap();
+    group.bench_function("pipeline_streams", |bencher| {
+        bencher.iter(|| {
+            let _ = black_box(kern.multiexp_pipeline(&pool, &jobs).unwrap());
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_multiexp, bench_multiexp_pipeline);
 criterion_main!(benches);
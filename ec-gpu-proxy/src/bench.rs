@@ -0,0 +1,146 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use ag_types::{GpuCurveAffine, GpuName, PrimeFieldRepr};
+use ark_ec::{CurveGroup, Group};
+use ark_ff::{FftField, PrimeField};
+use ec_gpu_program::{DeviceInfo, EcError, EcResult};
+
+use crate::{
+    fft::FftKernel,
+    fft_cpu::parallel_fft,
+    multiexp::MultiexpKernel,
+    multiexp_cpu::{multiexp_cpu, FullDensity},
+    threadpool::Worker,
+};
+
+/// The CPU and GPU timings of a single size.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SizeTimings {
+    /// Log2 of the number of elements (FFT) or terms (multiexp).
+    pub log_n: u32,
+    /// The time the CPU implementation took.
+    pub cpu: Duration,
+    /// The time the GPU implementation took.
+    pub gpu: Duration,
+}
+
+impl SizeTimings {
+    /// How many times faster the GPU was, values below 1 mean that the CPU
+    /// was faster.
+    pub fn speedup(&self) -> f64 {
+        self.cpu.as_secs_f64() / self.gpu.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+}
+
+/// The result of [`run_comparison`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ComparisonReport {
+    /// The device the FFTs ran on, see [`FftKernel::radix_fft`].
+    pub fft_device: DeviceInfo,
+    /// The devices the multiexps were split among.
+    pub multiexp_devices: Vec<DeviceInfo>,
+    /// The FFT timings, one per size, in the order of the sizes.
+    pub fft: Vec<SizeTimings>,
+    /// The multiexp timings, one per size, in the order of the sizes.
+    pub multiexp: Vec<SizeTimings>,
+}
+
+/// A SplitMix64 generator, the inputs only need to look random and be the
+/// same on every run.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn field<F: PrimeField>(&mut self) -> F {
+        let bytes = (0..8)
+            .flat_map(|_| self.next().to_le_bytes())
+            .collect::<Vec<_>>();
+        F::from_le_bytes_mod_order(&bytes)
+    }
+}
+
+/// Times the FFT and the multiexp on the CPU and the GPU for every size.
+///
+/// The sizes are given as log2 of the number of elements (FFT) or terms
+/// (multiexp). The inputs are pseudo-random, but the same on every run. The
+/// CPU implementations are [`parallel_fft`] and [`multiexp_cpu`], which use
+/// the threads of `pool`. Every operation is run once, hence the first sizes
+/// also contain the warm-up of the GPU.
+pub fn run_comparison<G>(
+    fft_kern: &mut FftKernel<G::Scalar>, multiexp_kern: &mut MultiexpKernel<G>,
+    pool: &Worker, log_sizes: &[u32],
+) -> EcResult<ComparisonReport>
+where
+    G: GpuCurveAffine + GpuName,
+{
+    let mut rng = SplitMix64(0);
+    let mut fft = Vec::with_capacity(log_sizes.len());
+    let mut multiexp = Vec::with_capacity(log_sizes.len());
+    for &log_n in log_sizes {
+        let n = 1 << log_n;
+        let omega =
+            G::Scalar::get_root_of_unity(n as u64).ok_or(EcError::Simple(
+                "The FFT size exceeds the two-adicity of the field",
+            ))?;
+        let scalars =
+            (0..n).map(|_| rng.field::<G::Scalar>()).collect::<Vec<_>>();
+
+        let mut input = scalars.clone();
+        let now = Instant::now();
+        parallel_fft(
+            &mut input,
+            pool,
+            &omega,
+            log_n,
+            pool.log_num_threads().min(log_n),
+        );
+        let cpu = now.elapsed();
+        let mut input = scalars.clone();
+        let now = Instant::now();
+        fft_kern.radix_fft(&mut input, &omega, log_n)?;
+        let gpu = now.elapsed();
+        fft.push(SizeTimings { log_n, cpu, gpu });
+
+        // Consecutive multiples of the generator are cheap to compute.
+        let mut point = G::Curve::generator();
+        let bases = (0..n)
+            .map(|_| {
+                point += G::Curve::generator();
+                point
+            })
+            .collect::<Vec<_>>();
+        let bases = Arc::new(G::Curve::normalize_batch(&bases));
+        let exps = Arc::new(
+            scalars
+                .iter()
+                .map(PrimeFieldRepr::to_repr)
+                .collect::<Vec<_>>(),
+        );
+
+        let now = Instant::now();
+        multiexp_cpu(pool, (bases.clone(), 0), FullDensity, exps.clone())
+            .wait()?;
+        let cpu = now.elapsed();
+        let now = Instant::now();
+        multiexp_kern.multiexp(pool, bases, exps, 0)?;
+        let gpu = now.elapsed();
+        multiexp.push(SizeTimings { log_n, cpu, gpu });
+    }
+
+    Ok(ComparisonReport {
+        fft_device: fft_kern.device_info()[0].clone(),
+        multiexp_devices: multiexp_kern.device_info(),
+        fft,
+        multiexp,
+    })
+}
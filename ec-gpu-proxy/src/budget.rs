@@ -0,0 +1,210 @@
+use std::sync::{Arc, Condvar, Mutex};
+
+use ec_gpu_program::{EcError, EcResult};
+
+/// A limit on the GPU memory, which is shared by several kernels.
+///
+/// Kernels that were given a budget (see e.g.
+/// `MultiexpKernel::with_budget`) reserve the memory of their device buffers
+/// before allocating them, and release it once they are done. The limit is
+/// in bytes and applies to all devices together.
+///
+/// If a reservation doesn't fit at the moment, it waits until other
+/// reservations are released. Requests that are larger than the whole limit
+/// fail with [`EcError::MemoryBudgetExceeded`], kernels that can split their
+/// work (like the multiexp) use smaller chunks instead.
+#[derive(Debug)]
+pub struct MemoryBudget {
+    limit: usize,
+    usage: Mutex<Usage>,
+    released: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct Usage {
+    /// The bytes that are currently reserved.
+    used: usize,
+    /// The maximum of `used` so far.
+    peak: usize,
+}
+
+impl MemoryBudget {
+    /// Creates a new budget of `limit` bytes.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            usage: Mutex::new(Usage::default()),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Returns the limit in bytes.
+    pub fn limit(&self) -> usize { self.limit }
+
+    /// Returns the bytes that are currently reserved.
+    pub fn used(&self) -> usize { self.usage.lock().unwrap().used }
+
+    /// Returns the maximum number of bytes that were reserved at the same
+    /// time.
+    pub fn peak(&self) -> usize { self.usage.lock().unwrap().peak }
+
+    /// Reserves `bytes` of the budget, until the returned [`Reservation`] is
+    /// dropped.
+    ///
+    /// Blocks while other reservations don't leave enough space. Fails
+    /// immediately if `bytes` exceeds the limit.
+    pub fn reserve(self: &Arc<Self>, bytes: usize) -> EcResult<Reservation> {
+        if bytes > self.limit {
+            return Err(EcError::MemoryBudgetExceeded {
+                requested: bytes,
+                limit: self.limit,
+            });
+        }
+        let mut usage = self.usage.lock().unwrap();
+        while usage.used + bytes > self.limit {
+            usage = self.released.wait(usage).unwrap();
+        }
+        usage.used += bytes;
+        usage.peak = usage.peak.max(usage.used);
+        Ok(Reservation {
+            budget: self.clone(),
+            bytes,
+        })
+    }
+}
+
+/// Memory that is reserved from a [`MemoryBudget`]. It is released on drop.
+#[derive(Debug)]
+pub struct Reservation {
+    budget: Arc<MemoryBudget>,
+    bytes: usize,
+}
+
+impl Reservation {
+    /// Changes the reservation to `bytes`.
+    ///
+    /// Growing waits like [`MemoryBudget::reserve`], but the memory that is
+    /// already reserved counts towards `bytes`. Hence it doesn't wait for
+    /// itself, it fails if `bytes` exceeds the limit instead.
+    pub(crate) fn resize(&mut self, bytes: usize) -> EcResult<()> {
+        let budget = &self.budget;
+        if bytes > budget.limit {
+            return Err(EcError::MemoryBudgetExceeded {
+                requested: bytes,
+                limit: budget.limit,
+            });
+        }
+        let mut usage = budget.usage.lock().unwrap();
+        while usage.used - self.bytes + bytes > budget.limit {
+            usage = budget.released.wait(usage).unwrap();
+        }
+        usage.used = usage.used - self.bytes + bytes;
+        usage.peak = usage.peak.max(usage.used);
+        drop(usage);
+        if bytes < self.bytes {
+            budget.released.notify_all();
+        }
+        self.bytes = bytes;
+        Ok(())
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.budget.usage.lock().unwrap().used -= self.bytes;
+        self.budget.released.notify_all();
+    }
+}
+
+/// Reserves `bytes` of `budget`, if there is one.
+pub(crate) fn reserve(
+    budget: &Option<Arc<MemoryBudget>>, bytes: usize,
+) -> EcResult<Option<Reservation>> {
+    budget
+        .as_ref()
+        .map(|budget| budget.reserve(bytes))
+        .transpose()
+}
+
+/// Grows `held`, the reservation of a buffer that stays on the device, to
+/// `bytes` of `budget`. If nothing is held yet, `bytes` are reserved.
+///
+/// The held memory is counted once, hence an operation on a resident buffer
+/// never waits for the reservation of its own input.
+pub(crate) fn reserve_with(
+    budget: &Option<Arc<MemoryBudget>>, held: Option<Reservation>, bytes: usize,
+) -> EcResult<Option<Reservation>> {
+    match held {
+        Some(mut reservation) => {
+            reservation.resize(bytes)?;
+            Ok(Some(reservation))
+        }
+        None => reserve(budget, bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_budget() {
+        let budget = Arc::new(MemoryBudget::new(100));
+        let first = budget.reserve(60).unwrap();
+        let second = budget.reserve(40).unwrap();
+        assert_eq!(budget.used(), 100);
+        drop(first);
+        assert_eq!(budget.used(), 40);
+        drop(second);
+        assert_eq!(budget.used(), 0);
+        assert_eq!(budget.peak(), 100);
+
+        assert!(matches!(
+            budget.reserve(101),
+            Err(EcError::MemoryBudgetExceeded {
+                requested: 101,
+                limit: 100
+            })
+        ));
+        assert!(reserve(&None, usize::MAX).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_memory_budget_waits_for_release() {
+        let budget = Arc::new(MemoryBudget::new(100));
+        let first = budget.reserve(80).unwrap();
+        let waiting = {
+            let budget = budget.clone();
+            std::thread::spawn(move || budget.reserve(50).map(|_| ()))
+        };
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        drop(first);
+        waiting.join().unwrap().unwrap();
+        assert_eq!(budget.used(), 0);
+        assert!(budget.peak() <= 100);
+    }
+
+    #[test]
+    fn test_reservation_resize() {
+        let budget = Arc::new(MemoryBudget::new(100));
+        let mut held = budget.reserve(60).unwrap();
+        // The held memory counts towards the new size, it doesn't block.
+        held.resize(100).unwrap();
+        assert_eq!(budget.used(), 100);
+        assert!(matches!(
+            held.resize(101),
+            Err(EcError::MemoryBudgetExceeded {
+                requested: 101,
+                limit: 100
+            })
+        ));
+        held.resize(30).unwrap();
+        assert_eq!(budget.used(), 30);
+        let grown =
+            reserve_with(&Some(budget.clone()), Some(held), 90).unwrap();
+        assert_eq!(budget.used(), 90);
+        drop(grown);
+        assert_eq!(budget.used(), 0);
+        assert_eq!(budget.peak(), 100);
+    }
+}
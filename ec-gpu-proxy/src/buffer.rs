@@ -0,0 +1,212 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ec_gpu_program::{EcError, EcResult};
+#[cfg(feature = "cuda")]
+use rust_gpu_tools::cuda;
+#[cfg(feature = "opencl")]
+use rust_gpu_tools::opencl;
+
+use crate::{budget::Reservation, device::SharedProgram};
+
+/// A buffer of `T`s that stays in GPU memory.
+///
+/// It is returned by operations like
+/// [`SingleMultiexpKernel::ifft_to_device`] and can be passed on to other
+/// operations of the same kernel, e.g.
+/// [`SingleMultiexpKernel::multiexp_from_device`], without transferring the
+/// data to the host and back in between. A buffer can't be passed to another
+/// kernel, as the programs of different kernels may use different GPU
+/// contexts. For the same reason it can't be used anymore after the device
+/// was reset, see [`SharedProgram::reset_device`].
+///
+/// [`SingleMultiexpKernel::ifft_to_device`]: crate::multiexp::SingleMultiexpKernel::ifft_to_device
+/// [`SingleMultiexpKernel::multiexp_from_device`]: crate::multiexp::SingleMultiexpKernel::multiexp_from_device
+pub struct DeviceBuffer<T> {
+    inner: Inner<T>,
+    len: usize,
+    /// The kernel and the context that created the buffer.
+    owner: BufferOwner,
+    /// The memory of the buffer, if the kernel has a budget.
+    reservation: Option<Reservation>,
+}
+
+enum Inner<T> {
+    #[cfg(feature = "cuda")]
+    Cuda(cuda::Buffer<T>),
+    #[cfg(feature = "opencl")]
+    Opencl(opencl::Buffer<T>),
+}
+
+impl<T> DeviceBuffer<T> {
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize { self.len }
+
+    /// Returns whether the buffer has no elements.
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    /// Keeps `reservation` until the buffer is dropped.
+    pub(crate) fn with_reservation(
+        mut self, reservation: Option<Reservation>,
+    ) -> Self {
+        self.reservation = reservation;
+        self
+    }
+
+    /// Takes the reservation of the buffer, so that an operation that
+    /// consumes the buffer can include it in its own.
+    pub(crate) fn take_reservation(&mut self) -> Option<Reservation> {
+        self.reservation.take()
+    }
+}
+
+/// Identifies the kernel and the GPU context a [`DeviceBuffer`] was created
+/// in.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct BufferOwner {
+    /// The id of the kernel, see [`next_owner_id`].
+    kernel: usize,
+    /// The generation of the program of the kernel, a reset of the device
+    /// creates a new context.
+    generation: usize,
+}
+
+impl BufferOwner {
+    /// Returns the current owner for the kernel with the id `kernel`, which
+    /// runs on `program`.
+    pub(crate) fn new(kernel: usize, program: &SharedProgram) -> Self {
+        BufferOwner {
+            kernel,
+            generation: program.generation(),
+        }
+    }
+}
+
+/// Returns a new id for a kernel that creates [`DeviceBuffer`]s.
+pub(crate) fn next_owner_id() -> usize {
+    static NEXT_OWNER_ID: AtomicUsize = AtomicUsize::new(0);
+    NEXT_OWNER_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Converts between [`DeviceBuffer`]s and the buffers of the backend of a
+/// program.
+///
+/// It's implemented for the program types of all backends, so that it can be
+/// used within `program_closures!`.
+pub(crate) trait BackendBuffer<T> {
+    /// The buffer type of the backend.
+    type Buffer;
+
+    /// Wraps the `len` elements long `buffer` that was created by `owner`.
+    fn wrap_buffer(
+        &self, buffer: Self::Buffer, len: usize, owner: BufferOwner,
+    ) -> DeviceBuffer<T>;
+
+    /// Returns the buffer of the backend, if `buffer` was created by `owner`.
+    fn unwrap_buffer(
+        &self, buffer: DeviceBuffer<T>, owner: BufferOwner,
+    ) -> EcResult<Self::Buffer>;
+
+    /// Like [`BackendBuffer::unwrap_buffer`], but `buffer` stays with the
+    /// caller, so that it can be used again.
+    fn borrow_buffer<'b>(
+        &self, buffer: &'b DeviceBuffer<T>, owner: BufferOwner,
+    ) -> EcResult<&'b Self::Buffer>;
+}
+
+/// Checks that `buffer` was created by the kernel of `owner`, in its current
+/// context.
+pub(crate) fn check_owner<T>(
+    buffer: &DeviceBuffer<T>, owner: BufferOwner,
+) -> EcResult<()> {
+    if buffer.owner.kernel != owner.kernel {
+        return Err(EcError::Simple(
+            "The device buffer was created by another kernel",
+        ));
+    }
+    if buffer.owner.generation != owner.generation {
+        return Err(EcError::Simple(
+            "The device buffer was created before the device was reset",
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "cuda")]
+impl<T> BackendBuffer<T> for cuda::Program {
+    type Buffer = cuda::Buffer<T>;
+
+    fn wrap_buffer(
+        &self, buffer: Self::Buffer, len: usize, owner: BufferOwner,
+    ) -> DeviceBuffer<T> {
+        DeviceBuffer {
+            inner: Inner::Cuda(buffer),
+            len,
+            owner,
+            reservation: None,
+        }
+    }
+
+    fn unwrap_buffer(
+        &self, buffer: DeviceBuffer<T>, owner: BufferOwner,
+    ) -> EcResult<Self::Buffer> {
+        check_owner(&buffer, owner)?;
+        #[allow(unreachable_patterns)]
+        match buffer.inner {
+            Inner::Cuda(buffer) => Ok(buffer),
+            _ => Err(EcError::Simple("The device buffer is not a CUDA buffer")),
+        }
+    }
+
+    fn borrow_buffer<'b>(
+        &self, buffer: &'b DeviceBuffer<T>, owner: BufferOwner,
+    ) -> EcResult<&'b Self::Buffer> {
+        check_owner(buffer, owner)?;
+        #[allow(unreachable_patterns)]
+        match &buffer.inner {
+            Inner::Cuda(buffer) => Ok(buffer),
+            _ => Err(EcError::Simple("The device buffer is not a CUDA buffer")),
+        }
+    }
+}
+
+#[cfg(feature = "opencl")]
+impl<T> BackendBuffer<T> for opencl::Program {
+    type Buffer = opencl::Buffer<T>;
+
+    fn wrap_buffer(
+        &self, buffer: Self::Buffer, len: usize, owner: BufferOwner,
+    ) -> DeviceBuffer<T> {
+        DeviceBuffer {
+            inner: Inner::Opencl(buffer),
+            len,
+            owner,
+            reservation: None,
+        }
+    }
+
+    fn unwrap_buffer(
+        &self, buffer: DeviceBuffer<T>, owner: BufferOwner,
+    ) -> EcResult<Self::Buffer> {
+        check_owner(&buffer, owner)?;
+        #[allow(unreachable_patterns)]
+        match buffer.inner {
+            Inner::Opencl(buffer) => Ok(buffer),
+            _ => Err(EcError::Simple(
+                "The device buffer is not an OpenCL buffer",
+            )),
+        }
+    }
+
+    fn borrow_buffer<'b>(
+        &self, buffer: &'b DeviceBuffer<T>, owner: BufferOwner,
+    ) -> EcResult<&'b Self::Buffer> {
+        check_owner(buffer, owner)?;
+        #[allow(unreachable_patterns)]
+        match &buffer.inner {
+            Inner::Opencl(buffer) => Ok(buffer),
+            _ => Err(EcError::Simple(
+                "The device buffer is not an OpenCL buffer",
+            )),
+        }
+    }
+}
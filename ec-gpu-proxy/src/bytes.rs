@@ -0,0 +1,273 @@
+use std::cmp::Ordering;
+
+use ag_types::{CanonicalBytes, GpuCurveAffine};
+use ark_ec::AffineRepr;
+use ark_serialize::CanonicalSerialize;
+use ec_gpu_program::{EcError, EcResult};
+
+/// A prime field that is only known by its modulus at runtime.
+///
+/// It's used by the entry points that take field elements as bytes instead of
+/// arkworks types, e.g. [`crate::fft::FftKernel::radix_fft_bytes`]. An
+/// element is encoded as the integer it represents (not in Montgomery form)
+/// in [`FieldSpec::bytes_per_element`] little-endian bytes. The field types
+/// are only accessed through [`CanonicalBytes`]. The kernel still needs to be
+/// generated for a matching field type, which also fixes the limb size, the
+/// modulus is checked against it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldSpec {
+    /// The little-endian bytes of the modulus, without trailing zeros.
+    modulus: Vec<u8>,
+}
+
+impl FieldSpec {
+    /// Creates the description of the field with the given little-endian
+    /// modulus.
+    pub fn new(modulus: &[u8]) -> Self {
+        let len = modulus
+            .iter()
+            .rposition(|&byte| byte != 0)
+            .map_or(0, |i| i + 1);
+        Self {
+            modulus: modulus[..len].to_vec(),
+        }
+    }
+
+    /// Returns the description of the field `F`.
+    pub fn of<F: CanonicalBytes>() -> Self { Self::new(&F::modulus_le_bytes()) }
+
+    /// Returns the little-endian bytes of the modulus.
+    pub fn modulus(&self) -> &[u8] { &self.modulus }
+
+    /// Returns the number of bytes of the encoding of a single element.
+    ///
+    /// It's the number of bits of the modulus, rounded up to whole bytes.
+    pub fn bytes_per_element(&self) -> usize { self.modulus.len() }
+
+    /// Returns an error if `F` isn't the field this describes.
+    pub(crate) fn check<F: CanonicalBytes>(&self) -> EcResult<()> {
+        if *self != Self::of::<F>() {
+            return Err(EcError::Simple(
+                "The modulus doesn't match the field of the kernel",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Decodes the elements of `bytes`, which must be a multiple of
+    /// [`FieldSpec::bytes_per_element`] long.
+    ///
+    /// Returns an error if the modulus is zero or if an encoding is not
+    /// smaller than the modulus.
+    pub(crate) fn decode<F: CanonicalBytes>(
+        &self, bytes: &[u8],
+    ) -> EcResult<Vec<F>> {
+        // A zero modulus has no bytes, the encodings would be empty.
+        if self.modulus.is_empty() {
+            return Err(EcError::Simple("The modulus is zero"));
+        }
+        if bytes.len() % self.bytes_per_element() != 0 {
+            return Err(EcError::Simple(
+                "The bytes are not a multiple of the encoding size",
+            ));
+        }
+        bytes
+            .chunks(self.bytes_per_element())
+            .map(|encoding| {
+                F::from_canonical_le_bytes(encoding).ok_or(EcError::Simple(
+                    "A field element is not smaller than the modulus",
+                ))
+            })
+            .collect()
+    }
+
+    /// Appends the encodings of the `values` to `bytes`.
+    pub(crate) fn encode<F: CanonicalBytes>(
+        &self, values: &[F], bytes: &mut Vec<u8>,
+    ) {
+        for value in values {
+            let encoding = value.to_canonical_le_bytes();
+            bytes.extend_from_slice(&encoding[..self.bytes_per_element()]);
+        }
+    }
+}
+
+/// The layout of the compressed encoding of the points of a curve over a
+/// prime field, the arguments of the `POINT_compress` kernel.
+///
+/// arkworks encodes a point as the canonical encoding of its `x` coordinate
+/// with flags in the spare bits of one byte, but the byte order and the flags
+/// are up to the curve, e.g. BLS12-381 uses the big-endian zcash encoding.
+/// Hence the layout is derived from the encodings of the generator, its
+/// negation and the point at infinity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct CompressedLayout {
+    /// The number of bytes of an encoding.
+    pub bytes_per_point: usize,
+    /// Whether the bytes of `x` are in big-endian order.
+    pub big_endian: bool,
+    /// The index of the byte that holds the flags.
+    pub flag_byte: usize,
+    /// The flags that are set in every encoding.
+    pub always: u8,
+    /// The flag that is set if `y > -y`, except for the point at infinity.
+    pub sign: u8,
+    /// The flag of the point at infinity, whose `x` is encoded as zero.
+    pub infinity: u8,
+}
+
+impl CompressedLayout {
+    /// Returns the layout of the curve of `G`.
+    ///
+    /// Returns an error if its compressed encoding doesn't have such a
+    /// layout.
+    pub(crate) fn of<G>() -> EcResult<Self>
+    where
+        G: GpuCurveAffine,
+        G::Base: CanonicalBytes,
+    {
+        const UNSUPPORTED: EcError = EcError::Simple(
+            "The compressed encoding of the curve is not supported",
+        );
+        let encode = |point: G| {
+            let mut bytes = Vec::new();
+            point
+                .serialize_compressed(&mut bytes)
+                .expect("serializing into a Vec doesn't fail");
+            bytes
+        };
+        let bytes_per_point = FieldSpec::of::<G::Base>().bytes_per_element();
+        let generator = G::generator();
+        let (x, y) = generator.to_xy();
+        let (_, neg_y) = (-generator).to_xy();
+        let mut x = x.to_canonical_le_bytes();
+        x.truncate(bytes_per_point);
+
+        // The encodings of the generator and its negation only differ in the
+        // sign, which is set for the one with the larger `y`.
+        let (larger, smaller) = match compare_le(&y, &neg_y) {
+            Ordering::Greater => (encode(generator), encode(-generator)),
+            _ => (encode(-generator), encode(generator)),
+        };
+        if larger.len() != bytes_per_point || smaller.len() != bytes_per_point {
+            return Err(UNSUPPORTED);
+        }
+        let differences = larger
+            .iter()
+            .zip(&smaller)
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(i, (a, b))| (i, a ^ b))
+            .collect::<Vec<_>>();
+        let (flag_byte, sign) = match differences[..] {
+            [(i, sign)] if sign.is_power_of_two() && larger[i] & sign != 0 => {
+                (i, sign)
+            }
+            _ => return Err(UNSUPPORTED),
+        };
+
+        let infinity = encode(G::zero());
+        for big_endian in [false, true] {
+            let mut expected = x.clone();
+            if big_endian {
+                expected.reverse();
+            }
+            // The flags must be in bits that are zero in every encoding of
+            // `x`, which has at least one spare bit.
+            let always = smaller[flag_byte] ^ expected[flag_byte];
+            if expected[flag_byte] & always != 0 {
+                continue;
+            }
+            expected[flag_byte] |= always;
+            if expected != smaller {
+                continue;
+            }
+
+            let mut expected = vec![0; bytes_per_point];
+            expected[flag_byte] = infinity[flag_byte] & !always;
+            let infinity_flag = expected[flag_byte];
+            expected[flag_byte] |= always;
+            if infinity_flag == 0 || expected != infinity {
+                return Err(UNSUPPORTED);
+            }
+            return Ok(Self {
+                bytes_per_point,
+                big_endian,
+                flag_byte,
+                always,
+                sign,
+                infinity: infinity_flag,
+            });
+        }
+        Err(UNSUPPORTED)
+    }
+}
+
+/// Compares two field elements by the integers they represent.
+fn compare_le<F: CanonicalBytes>(a: &F, b: &F) -> Ordering {
+    let a = a.to_canonical_le_bytes();
+    let b = b.to_canonical_le_bytes();
+    a.iter().rev().cmp(b.iter().rev())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chosen_ark_suite::{Fq, Fr};
+
+    #[test]
+    fn test_field_spec() {
+        let spec = FieldSpec::of::<Fr>();
+        assert_eq!(spec.bytes_per_element(), 32);
+        assert!(spec.check::<Fr>().is_ok());
+        assert!(spec.check::<Fq>().is_err());
+
+        let values = [Fr::from(0u64), Fr::from(1u64), -Fr::from(1u64)];
+        let mut bytes = Vec::new();
+        spec.encode(&values, &mut bytes);
+        assert_eq!(bytes.len(), 3 * 32);
+        assert_eq!(spec.decode::<Fr>(&bytes).unwrap(), values);
+
+        // The modulus itself is not a canonical encoding.
+        assert!(spec.decode::<Fr>(spec.modulus()).is_err());
+        assert!(spec.decode::<Fr>(&bytes[1..]).is_err());
+
+        // A zero modulus is rejected instead of dividing by zero.
+        for modulus in [vec![], vec![0u8, 0]] {
+            let zero = FieldSpec::new(&modulus);
+            assert_eq!(zero.bytes_per_element(), 0);
+            assert!(zero.decode::<Fr>(&bytes).is_err());
+            assert!(zero.decode::<Fr>(&[]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_compressed_layout() {
+        // The zcash encoding, see the serialization of `ark_bls12_381`.
+        assert_eq!(
+            CompressedLayout::of::<chosen_ark_suite::G1Affine>().unwrap(),
+            CompressedLayout {
+                bytes_per_point: 48,
+                big_endian: true,
+                flag_byte: 0,
+                always: 1 << 7,
+                sign: 1 << 5,
+                infinity: 1 << 6,
+            }
+        );
+        // The default encoding of arkworks, with the `SWFlags` in the last
+        // byte.
+        assert_eq!(
+            CompressedLayout::of::<ark_bn254::G1Affine>().unwrap(),
+            CompressedLayout {
+                bytes_per_point: 32,
+                big_endian: false,
+                flag_byte: 31,
+                always: 0,
+                sign: 1 << 7,
+                infinity: 1 << 6,
+            }
+        );
+    }
+}
@@ -0,0 +1,361 @@
+use std::{
+    ops::Deref,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+};
+
+use ec_gpu_program::{DeviceInfo, EcError, EcResult};
+use log::error;
+use rust_gpu_tools::{Device, Program};
+
+use crate::{estimate::DEFAULT_COMPUTE_UNITS, launch::LaunchPermit};
+
+/// A program that can be shared by several kernels of the same device.
+///
+/// If the source contains the kernels of several operations, e.g. it was
+/// built with `add_fft`, `add_ec_fft` and `add_multiexp`, a single program
+/// per device is enough for the [`FftKernel`], the [`EcFftKernel`] and the
+/// [`MultiexpKernel`]. This saves compiling the program and setting up a GPU
+/// context for each of them. Cloning it is cheap, all clones refer to the
+/// same program. The kernels take turns in using it.
+///
+/// [`FftKernel`]: crate::fft::FftKernel
+/// [`EcFftKernel`]: crate::ec_fft::EcFftKernel
+/// [`MultiexpKernel`]: crate::multiexp::MultiexpKernel
+///
+/// After a fatal error of the device, the kernels return an
+/// [`EcError::DeviceLost`] until the device is reset with
+/// [`SharedProgram::reset_device`]. As the kernels that are created from
+/// plain programs don't expose them, create them from shared programs in
+/// order to recover in-process.
+#[derive(Clone)]
+pub struct SharedProgram {
+    program: Arc<Mutex<Program>>,
+    device_name: String,
+    backend: &'static str,
+    /// Whether the device failed fatally since the program was set.
+    lost: Arc<AtomicBool>,
+    /// The number of times the program was replaced, see
+    /// [`SharedProgram::generation`].
+    generation: Arc<AtomicUsize>,
+}
+
+impl SharedProgram {
+    /// Wraps `program`, so that it can be shared.
+    pub fn new(program: Program) -> Self {
+        SharedProgram {
+            device_name: program.device_name().to_string(),
+            backend: backend_name(&program),
+            program: Arc::new(Mutex::new(program)),
+            lost: Arc::new(AtomicBool::new(false)),
+            generation: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Returns the name of the device the program runs on.
+    pub fn device_name(&self) -> &str { &self.device_name }
+
+    /// Returns the name of the backend, either `"cuda"` or `"opencl"`.
+    pub fn backend(&self) -> &'static str { self.backend }
+
+    /// Returns whether the device failed fatally and needs to be reset, see
+    /// [`SharedProgram::reset_device`].
+    pub fn is_device_lost(&self) -> bool { self.lost.load(Ordering::SeqCst) }
+
+    /// Replaces the program of a lost device with `program`, which must have
+    /// been built for the same device again, e.g. with `program!`.
+    ///
+    /// Building a program creates a new GPU context, the old one is released
+    /// once the old program is dropped. All kernels that use this shared
+    /// program, or a clone of it, use the new one afterwards. A program for
+    /// another device results in an error. Device buffers of the old context
+    /// can't be used anymore, they are rejected with an error.
+    pub fn reset_device(&self, program: Program) -> EcResult<()> {
+        if program.device_name() != self.device_name
+            || backend_name(&program) != self.backend
+        {
+            return Err(EcError::Simple(
+                "The program was built for another device",
+            ));
+        }
+        let mut current = self.program.lock().unwrap();
+        *current = program;
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.lost.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Returns the number of times the program was replaced with
+    /// [`SharedProgram::reset_device`].
+    ///
+    /// Device buffers remember the generation of the context they were
+    /// created in.
+    pub(crate) fn generation(&self) -> usize {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Returns an [`EcError::DeviceLost`] if the device needs to be reset.
+    pub(crate) fn check_lost(&self) -> EcResult<()> {
+        if self.is_device_lost() {
+            return Err(self.device_lost());
+        }
+        Ok(())
+    }
+
+    /// Turns a fatal error of the device into an [`EcError::DeviceLost`] and
+    /// remembers that the device needs to be reset.
+    pub(crate) fn check_result<R>(&self, result: EcResult<R>) -> EcResult<R> {
+        match result {
+            Err(e) if is_device_lost(&e) => {
+                error!("Device '{}' was lost: {}", self.device_name, e);
+                self.lost.store(true, Ordering::SeqCst);
+                Err(self.device_lost())
+            }
+            result => result,
+        }
+    }
+
+    fn device_lost(&self) -> EcError {
+        EcError::DeviceLost {
+            device: self.device_name.clone(),
+        }
+    }
+
+    /// Returns the program, other kernels wait until the guard is dropped.
+    ///
+    /// It also takes a slot of the process-wide launch limit, see
+    /// [`set_max_concurrent_launches`](crate::set_max_concurrent_launches).
+    /// The slot is taken first, so that a kernel that holds a program never
+    /// waits for a slot.
+    pub(crate) fn lock(&self) -> ProgramGuard<'_> {
+        let permit = LaunchPermit::acquire();
+        ProgramGuard {
+            program: self.program.lock().unwrap(),
+            _permit: permit,
+        }
+    }
+}
+
+/// The exclusive use of a [`SharedProgram`], see [`SharedProgram::lock`].
+pub(crate) struct ProgramGuard<'a> {
+    program: MutexGuard<'a, Program>,
+    _permit: LaunchPermit,
+}
+
+impl Deref for ProgramGuard<'_> {
+    type Target = Program;
+
+    fn deref(&self) -> &Program { &self.program }
+}
+
+impl From<Program> for SharedProgram {
+    fn from(program: Program) -> Self { SharedProgram::new(program) }
+}
+
+/// Runs the `closures` on the program of a [`SharedProgram`], like
+/// `program.lock().run(closures, arg)`.
+///
+/// Fails right away if the device was lost, a fatal error of the device is
+/// turned into an [`EcError::DeviceLost`].
+macro_rules! run_checked {
+    ($program:expr, $closures:expr, $arg:expr) => {{
+        let program: &$crate::device::SharedProgram = &$program;
+        match program.check_lost() {
+            Ok(()) => program.check_result(program.lock().run($closures, $arg)),
+            Err(e) => Err(e),
+        }
+    }};
+}
+
+pub(crate) use run_checked;
+
+/// The errors after which a device is unusable until its context is
+/// recreated, as they appear in the error messages of the CUDA and OpenCL
+/// backends.
+const DEVICE_LOST_ERRORS: &[&str] = &[
+    // CUDA
+    "LaunchFailed",
+    "LaunchTimeout",
+    "IllegalAddress",
+    "IllegalInstruction",
+    "MisalignedAddress",
+    "InvalidAddressSpace",
+    "InvalidProgramCounter",
+    "HardwareStackError",
+    "EccUncorrectable",
+    "AssertError",
+    // OpenCL, `CL_OUT_OF_RESOURCES` is not among them, as it's also reported
+    // if a launch merely exceeds the resources of the device.
+    "CL_DEVICE_NOT_AVAILABLE",
+    "CL_INVALID_COMMAND_QUEUE",
+    "CL_EXEC_STATUS_ERROR_FOR_EVENTS_IN_WAIT_LIST",
+];
+
+/// Returns whether `error` is a fatal error of the device.
+///
+/// The backend errors are classified by their name, as rust-gpu-tools
+/// wraps the ones of the drivers in different ways.
+pub(crate) fn is_device_lost(error: &EcError) -> bool {
+    match error {
+        EcError::DeviceLost { .. } => true,
+        EcError::GpuTools(e) => {
+            is_device_lost_message(&format!("{:?}", e))
+                || is_device_lost_message(&e.to_string())
+        }
+        _ => false,
+    }
+}
+
+/// Returns whether the `message` contains one of the
+/// [`DEVICE_LOST_ERRORS`] as a whole word.
+fn is_device_lost_message(message: &str) -> bool {
+    message
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .any(|word| DEVICE_LOST_ERRORS.contains(&word))
+}
+
+/// Returns the information of the device the `program` runs on.
+///
+/// Programs only know the name of their device. Devices of the same model
+/// have the same name, hence any of them will do. If no such device is
+/// found, the capabilities are guessed.
+pub(crate) fn device_info_of(program: &SharedProgram) -> DeviceInfo {
+    Device::all()
+        .into_iter()
+        .find(|device| device.name() == program.device_name())
+        .map(DeviceInfo::new)
+        .unwrap_or_else(|| DeviceInfo {
+            name: program.device_name().to_string(),
+            vendor: String::new(),
+            memory: 0,
+            compute_units: DEFAULT_COMPUTE_UNITS,
+            compute_capability: None,
+            cuda: program.backend() == "cuda",
+            opencl: program.backend() == "opencl",
+            atomics_64: program.backend() == "cuda",
+        })
+}
+
+/// Wraps every program into its own [`SharedProgram`].
+pub(crate) fn share(programs: Vec<Program>) -> Vec<SharedProgram> {
+    programs.into_iter().map(SharedProgram::new).collect()
+}
+
+/// Returns the name of the backend the `program` runs on.
+fn backend_name(program: &Program) -> &'static str {
+    match program {
+        #[cfg(feature = "cuda")]
+        Program::Cuda(_) => "cuda",
+        #[cfg(feature = "opencl")]
+        Program::Opencl(_) => "opencl",
+    }
+}
+
+/// Collects the kernels that could be initialized.
+///
+/// Each item is the name of a device together with the result of creating a
+/// kernel for it. Failures are logged and skipped. If no kernel is left, the
+/// error distinguishes between "no devices at all" and "devices present, but
+/// none usable". The latter lists every device with the reason it was
+/// rejected, e.g. when all GPUs are held by other jobs in exclusive-process
+/// mode.
+pub(crate) fn working_kernels<K>(
+    attempts: impl IntoIterator<Item = (String, EcResult<K>)>,
+) -> EcResult<Vec<K>> {
+    let mut kernels = Vec::new();
+    let mut rejected = Vec::new();
+    for (device_name, kernel) in attempts {
+        match kernel {
+            Ok(kernel) => kernels.push(kernel),
+            Err(e) => {
+                error!(
+                    "Cannot initialize kernel for device '{}'! Error: {}",
+                    device_name, e
+                );
+                rejected.push((device_name, e.to_string()));
+            }
+        }
+    }
+
+    if kernels.is_empty() {
+        if rejected.is_empty() {
+            return Err(EcError::Simple("No GPU devices found!"));
+        }
+        return Err(EcError::NoUsableDevice(rejected));
+    }
+
+    Ok(kernels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_working_kernels_errors() {
+        let none: Vec<(String, EcResult<()>)> = Vec::new();
+        assert!(matches!(
+            working_kernels(none),
+            Err(EcError::Simple("No GPU devices found!"))
+        ));
+
+        let busy = vec![
+            ("GPU 0".to_string(), Err::<(), _>(EcError::Simple("busy"))),
+            (
+                "GPU 1".to_string(),
+                Err(EcError::Simple("exclusive-process mode")),
+            ),
+        ];
+        let error = working_kernels(busy).unwrap_err();
+        match &error {
+            EcError::NoUsableDevice(rejected) => {
+                let names: Vec<&str> =
+                    rejected.iter().map(|(name, _)| name.as_str()).collect();
+                assert_eq!(names, ["GPU 0", "GPU 1"]);
+            }
+            other => panic!("expected NoUsableDevice, got {:?}", other),
+        }
+        let message = error.to_string();
+        assert!(message.contains("'GPU 0' (EcError: busy)"));
+        assert!(message.contains("'GPU 1' (EcError: exclusive-process mode)"));
+
+        let mixed = vec![
+            ("GPU 0".to_string(), Err(EcError::Simple("busy"))),
+            ("GPU 1".to_string(), Ok(1)),
+        ];
+        assert_eq!(working_kernels(mixed).unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn test_device_lost_classification() {
+        for message in [
+            "Cuda(LaunchFailed)",
+            "Cuda Error: LaunchTimeout",
+            "Opencl3(ClError(-36), Some(\"CL_INVALID_COMMAND_QUEUE\"))",
+        ] {
+            assert!(is_device_lost_message(message), "{}", message);
+        }
+        for message in [
+            "Cuda(OutOfMemory)",
+            "Cuda Error: InvalidValue",
+            "Opencl3 Error: CL_BUILD_PROGRAM_FAILURE",
+            "Opencl3 Error: CL_OUT_OF_RESOURCES",
+            "Kernel with name FailedLaunchFailed not found",
+        ] {
+            assert!(!is_device_lost_message(message), "{}", message);
+        }
+
+        let lost = EcError::DeviceLost {
+            device: "GPU 0".to_string(),
+        };
+        assert!(is_device_lost(&lost));
+        assert_eq!(
+            lost.to_string(),
+            "The device 'GPU 0' was lost and needs to be reset"
+        );
+        assert!(!is_device_lost(&EcError::Aborted));
+        assert!(!is_device_lost(&EcError::Simple("LaunchFailed")));
+    }
+}
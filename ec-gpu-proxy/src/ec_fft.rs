@@ -1,30 +1,168 @@
 use std::{
     cmp,
+    ops::Range,
     sync::{Arc, RwLock},
 };
 
 use ag_types::{GpuCurveAffine, GpuName};
-use ark_ff::Field;
-use log::{error, info};
+use ark_ff::{Field, Zero};
+use log::info;
 use rust_gpu_tools::{program_closures, LocalBuffer, Program};
 
-use crate::{pow_vartime, threadpool::THREAD_POOL};
-use ec_gpu_program::{EcError, EcResult};
+use crate::{
+    buffer::{next_owner_id, BackendBuffer, BufferOwner, DeviceBuffer},
+    device::{
+        device_info_of, run_checked, share, working_kernels, SharedProgram,
+    },
+    fft::{
+        check_batch, check_len, elementwise_work_size, inverse_omega,
+        size_inverses,
+    },
+    pow_vartime,
+    split::{split_ranges, EvenSplit, WorkSplitter},
+    threadpool::THREAD_POOL,
+};
+use ec_gpu_program::{DeviceInfo, EcError, EcResult};
 
 const LOG2_MAX_ELEMENTS: usize = 32; // At most 2^32 elements is supported.
 const MAX_LOG2_RADIX: u32 = 8; // Radix256
 
+/// Runs the rounds of a radix FFT of the `2^log_n` points in `src_buffer`.
+///
+/// The buffers are swapped after each round, the result ends up in
+/// `src_buffer`. `maybe_abort` is checked before each round. If a `shift` is
+/// given, the FFT is over the coset `shift * <omega>`.
+macro_rules! ec_fft_rounds {
+    (
+        $program:expr,
+        $src_buffer:ident,
+        $dst_buffer:ident,
+        $omega:expr,
+        $log_n:expr,
+        $maybe_abort:expr,
+        $shift:expr
+    ) => {{
+        let program = $program;
+        let omega: &G::Scalar = $omega;
+        let log_n: u32 = $log_n;
+        let shift: Option<&G::Scalar> = $shift;
+        let n: usize = 1 << log_n;
+        // The precalculated values pq` and `omegas` are valid for radix
+        // degrees up to `max_deg`
+        let max_deg = cmp::min(MAX_LOG2_RADIX, log_n);
+
+        // Precalculate:
+        // [omega^(0/(2^(deg-1))), omega^(1/(2^(deg-1))), ...,
+        // omega^((2^(deg-1)-1)/(2^(deg-1)))] let mut pq =
+        // vec![G::Scalar::ZERO; 1 << max_deg >> 1];
+        let twiddle = pow_vartime(omega, [(n >> max_deg) as u64]);
+        let pq = vec![twiddle];
+        // pq[0] = G::Scalar::ONE;
+        // if max_deg > 1 {
+        //     pq[1] = twiddle;
+        //     for i in 2..(1 << max_deg >> 1) {
+        //         pq[i] = pq[i - 1];
+        //         pq[i].mul_assign(&twiddle);
+        //     }
+        // }
+        let pq_buffer = program.create_buffer_from_slice(&pq)?;
+
+        // Precalculate [omega, omega^2, omega^4, omega^8, ...,
+        // omega^(2^31)]
+        let mut omegas = vec![G::Scalar::ZERO; 32];
+        omegas[0] = *omega;
+        for i in 1..LOG2_MAX_ELEMENTS {
+            omegas[i] = pow_vartime(&omegas[i - 1], [2u64]);
+        }
+        let omegas_buffer = program.create_buffer_from_slice(&omegas)?;
+        let shift_buffer = match shift {
+            Some(shift) => Some(program.create_buffer_from_slice(&[*shift])?),
+            None => None,
+        };
+
+        // Specifies log2 of `p`, (http://www.bealto.com/gpu-fft_group-1.html)
+        let mut log_p = 0u32;
+        // Each iteration performs a FFT round
+        while log_p < log_n {
+            if let Some(maybe_abort) = $maybe_abort {
+                if maybe_abort() {
+                    return Err(EcError::Aborted);
+                }
+            }
+
+            // 1=>radix2, 2=>radix4, 3=>radix8, ...
+            let deg = cmp::min(max_deg, log_n - log_p);
+
+            let n = 1u32 << log_n;
+
+            let virtual_local_work_size = 1 << (deg - 1);
+
+            // The algorithm may require a small local_network_size.
+            // However, too small local_network_size will undermine the
+            // performance. So we allocate a larger local_network_size, but
+            // translate the global parameter before execution.
+            let physical_local_work_size = if virtual_local_work_size >= 32 {
+                virtual_local_work_size
+            } else if n <= 64 {
+                virtual_local_work_size
+            } else {
+                32
+            };
+            let global_work_size = n / 2 / physical_local_work_size;
+
+            // The coset shift is applied when the inputs are loaded in the
+            // first round.
+            let round_shift = shift_buffer.as_ref().filter(|_| log_p == 0);
+            let kernel_name = match round_shift {
+                Some(_) => format!("{}_radix_fft_coset", G::name()),
+                None => format!("{}_radix_fft", G::name()),
+            };
+            let kernel = program.create_kernel(
+                &kernel_name,
+                global_work_size as usize,
+                physical_local_work_size as usize,
+            )?;
+            // dbg!(n, deg, max_deg, log_p, global_work_size,
+            // physical_local_work_size, virtual_local_work_size);
+            let kernel = kernel
+                .arg(&$src_buffer)
+                .arg(&$dst_buffer)
+                .arg(&pq_buffer)
+                .arg(&omegas_buffer)
+                .arg(&LocalBuffer::<G::Curve>::new(
+                    2 * physical_local_work_size as usize,
+                ))
+                .arg(&n)
+                .arg(&log_p)
+                .arg(&deg)
+                .arg(&virtual_local_work_size)
+                .arg(&max_deg);
+            match round_shift {
+                Some(shift_buffer) => kernel.arg(shift_buffer).run()?,
+                None => kernel.run()?,
+            }
+
+            log_p += deg;
+            std::mem::swap(&mut $src_buffer, &mut $dst_buffer);
+        }
+    }};
+}
+
 /// FFT kernel for a single GPU.
 pub struct SingleEcFftKernel<'a, G>
 where
     G: GpuCurveAffine,
     G::Scalar: Field + GpuName,
 {
-    program: Program,
+    program: SharedProgram,
     /// An optional function which will be called at places where it is
     /// possible to abort the FFT calculations. If it returns true, the
     /// calculation will be aborted with an [`EcError::Aborted`].
     maybe_abort: Option<&'a (dyn Fn() -> bool + Send + Sync)>,
+    /// Identifies the [`DeviceBuffer`]s this kernel created.
+    id: usize,
+    /// The information of the device the kernel runs on.
+    device_info: DeviceInfo,
     _phantom: std::marker::PhantomData<G::Scalar>,
 }
 
@@ -37,12 +175,18 @@ where G::Scalar: Field + GpuName
     /// computation, without leaving the GPU in a weird state. If that
     /// function returns `true`, execution is aborted.
     pub fn create(
-        program: Program,
+        program: impl Into<SharedProgram>,
         maybe_abort: Option<&'a (dyn Fn() -> bool + Send + Sync)>,
     ) -> EcResult<Self> {
+        if let Some(reason) = G::unsupported_reason() {
+            return Err(EcError::unsupported_curve::<G>(reason));
+        }
+        let program = program.into();
         Ok(SingleEcFftKernel {
+            device_info: device_info_of(&program),
             program,
             maybe_abort,
+            id: next_owner_id(),
             _phantom: Default::default(),
         })
     }
@@ -50,9 +194,13 @@ where G::Scalar: Field + GpuName
     /// Performs FFT on `input`
     /// * `omega` - Special value `omega` is used for FFT over finite-fields
     /// * `log_n` - Specifies log2 of number of elements
+    ///
+    /// The result is in projective (Jacobian) coordinates, no affine
+    /// conversion is done.
     pub fn radix_ec_fft(
         &mut self, input: &mut [G::Curve], omega: &G::Scalar, log_n: u32,
     ) -> EcResult<()> {
+        check_len(1 << log_n, input.len())?;
         let closures = program_closures!(|program,
                                           input: &mut [G::Curve]|
          -> EcResult<()> {
@@ -63,92 +211,134 @@ where G::Scalar: Field + GpuName
                 unsafe { program.create_buffer::<G::Curve>(n)? };
             let mut dst_buffer =
                 unsafe { program.create_buffer::<G::Curve>(n)? };
-            // The precalculated values pq` and `omegas` are valid for radix
-            // degrees up to `max_deg`
-            let max_deg = cmp::min(MAX_LOG2_RADIX, log_n);
-
-            // Precalculate:
-            // [omega^(0/(2^(deg-1))), omega^(1/(2^(deg-1))), ...,
-            // omega^((2^(deg-1)-1)/(2^(deg-1)))] let mut pq =
-            // vec![G::Scalar::ZERO; 1 << max_deg >> 1];
-            let twiddle = pow_vartime(omega, [(n >> max_deg) as u64]);
-            let pq = vec![twiddle];
-            // pq[0] = G::Scalar::ONE;
-            // if max_deg > 1 {
-            //     pq[1] = twiddle;
-            //     for i in 2..(1 << max_deg >> 1) {
-            //         pq[i] = pq[i - 1];
-            //         pq[i].mul_assign(&twiddle);
-            //     }
-            // }
-            let pq_buffer = program.create_buffer_from_slice(&pq)?;
-
-            // Precalculate [omega, omega^2, omega^4, omega^8, ...,
-            // omega^(2^31)]
-            let mut omegas = vec![G::Scalar::ZERO; 32];
-            omegas[0] = *omega;
-            for i in 1..LOG2_MAX_ELEMENTS {
-                omegas[i] = pow_vartime(&omegas[i - 1], [2u64]);
-            }
-            let omegas_buffer = program.create_buffer_from_slice(&omegas)?;
-
             program.write_from_buffer(&mut src_buffer, &*input)?;
-            // Specifies log2 of `p`, (http://www.bealto.com/gpu-fft_group-1.html)
-            let mut log_p = 0u32;
-            // Each iteration performs a FFT round
-            while log_p < log_n {
-                if let Some(maybe_abort) = &self.maybe_abort {
-                    if maybe_abort() {
-                        return Err(EcError::Aborted);
-                    }
-                }
 
-                // 1=>radix2, 2=>radix4, 3=>radix8, ...
-                let deg = cmp::min(max_deg, log_n - log_p);
+            ec_fft_rounds!(
+                program,
+                src_buffer,
+                dst_buffer,
+                omega,
+                log_n,
+                &self.maybe_abort,
+                None
+            );
+
+            program.read_into_buffer(&src_buffer, input)?;
 
-                let n = 1u32 << log_n;
+            Ok(())
+        });
+
+        run_checked!(self.program, closures, input)
+    }
+
+    /// Performs the inverse FFT on `input`, `omega` is the root of unity of
+    /// the forward FFT.
+    ///
+    /// It's the FFT with `omega^-1`, whose results are multiplied by `n^-1`
+    /// on the device before they are read back.
+    pub fn radix_ec_ifft(
+        &mut self, input: &mut [G::Curve], omega: &G::Scalar, log_n: u32,
+    ) -> EcResult<()> {
+        let omega_inv = inverse_omega(omega)?;
+        let n_inv = G::Scalar::from(1u64 << log_n)
+            .inverse()
+            .expect("n is non-zero");
+        self.radix_ec_fft_on_coset(
+            input,
+            &omega_inv,
+            log_n,
+            Some([n_inv, G::Scalar::ONE]),
+            None,
+        )
+    }
+
+    /// Performs FFT on `input` over the coset `shift * <omega>`, i.e. it
+    /// evaluates at `shift * omega^i`.
+    ///
+    /// The points are multiplied by the powers of `shift` in the first round
+    /// of the FFT, not in a separate pass. A shift of one is the same as
+    /// [`SingleEcFftKernel::radix_ec_fft`].
+    pub fn radix_ec_fft_coset(
+        &mut self, input: &mut [G::Curve], omega: &G::Scalar, log_n: u32,
+        shift: &G::Scalar,
+    ) -> EcResult<()> {
+        if *shift == G::Scalar::ONE {
+            return self.radix_ec_fft(input, omega, log_n);
+        }
+        self.radix_ec_fft_on_coset(input, omega, log_n, None, Some(shift))
+    }
+
+    /// Performs the inverse FFT on `input` over the coset `shift * <omega>`,
+    /// `omega` is the root of unity of the forward FFT.
+    ///
+    /// It's the inverse of [`SingleEcFftKernel::radix_ec_fft_coset`], the
+    /// results are multiplied by `n^-1 * shift^-i` after the FFT with
+    /// `omega^-1`.
+    pub fn radix_ec_ifft_coset(
+        &mut self, input: &mut [G::Curve], omega: &G::Scalar, log_n: u32,
+        shift: &G::Scalar,
+    ) -> EcResult<()> {
+        let shift_inv = shift
+            .inverse()
+            .ok_or(EcError::Simple("The coset shift must be non-zero"))?;
+        let omega_inv = inverse_omega(omega)?;
+        let n_inv = G::Scalar::from(1u64 << log_n)
+            .inverse()
+            .expect("n is non-zero");
+        self.radix_ec_fft_on_coset(
+            input,
+            &omega_inv,
+            log_n,
+            Some([n_inv, shift_inv]),
+            None,
+        )
+    }
 
-                let virtual_local_work_size = 1 << (deg - 1);
+    /// Performs FFT on `input`, whose inputs are multiplied by the powers of
+    /// `shift`, if it's given. The outputs are multiplied by
+    /// `scale[0] * scale[1]^i` afterwards, if it's given.
+    fn radix_ec_fft_on_coset(
+        &mut self, input: &mut [G::Curve], omega: &G::Scalar, log_n: u32,
+        scale: Option<[G::Scalar; 2]>, shift: Option<&G::Scalar>,
+    ) -> EcResult<()> {
+        check_len(1 << log_n, input.len())?;
+        let closures = program_closures!(|program,
+                                          input: &mut [G::Curve]|
+         -> EcResult<()> {
+            let n = 1 << log_n;
+            // All usages are safe as the buffers are initialized from either
+            // the host or the GPU before they are read.
+            let mut src_buffer =
+                unsafe { program.create_buffer::<G::Curve>(n)? };
+            let mut dst_buffer =
+                unsafe { program.create_buffer::<G::Curve>(n)? };
+            program.write_from_buffer(&mut src_buffer, &*input)?;
 
-                // The algorithm may require a small local_network_size.
-                // However, too small local_network_size will undermine the
-                // performance. So we allocate a larger local_network_size, but
-                // translate the global parameter before execution.
-                let physical_local_work_size = if virtual_local_work_size >= 32
-                {
-                    virtual_local_work_size
-                } else if n <= 64 {
-                    virtual_local_work_size
-                } else {
-                    32
-                };
-                let global_work_size = n / 2 / physical_local_work_size;
+            ec_fft_rounds!(
+                program,
+                src_buffer,
+                dst_buffer,
+                omega,
+                log_n,
+                &self.maybe_abort,
+                shift
+            );
 
-                let kernel_name = format!("{}_radix_fft", G::name());
+            if let Some(params) = scale {
+                let params_buffer =
+                    program.create_buffer_from_slice(&params)?;
+                let (global_work_size, local_work_size) =
+                    elementwise_work_size(n);
                 let kernel = program.create_kernel(
-                    &kernel_name,
-                    global_work_size as usize,
-                    physical_local_work_size as usize,
+                    &format!("{}_distribute_powers", G::name()),
+                    global_work_size,
+                    local_work_size,
                 )?;
-                // dbg!(n, deg, max_deg, log_p, global_work_size,
-                // physical_local_work_size, virtual_local_work_size);
                 kernel
                     .arg(&src_buffer)
-                    .arg(&dst_buffer)
-                    .arg(&pq_buffer)
-                    .arg(&omegas_buffer)
-                    .arg(&LocalBuffer::<G::Curve>::new(
-                        2 * physical_local_work_size as usize,
-                    ))
-                    .arg(&n)
-                    .arg(&log_p)
-                    .arg(&deg)
-                    .arg(&virtual_local_work_size)
-                    .arg(&max_deg)
+                    .arg(&params_buffer)
+                    .arg(&(n as u32))
                     .run()?;
-
-                log_p += deg;
-                std::mem::swap(&mut src_buffer, &mut dst_buffer);
             }
 
             program.read_into_buffer(&src_buffer, input)?;
@@ -156,17 +346,142 @@ where G::Scalar: Field + GpuName
             Ok(())
         });
 
-        self.program.run(closures, input)
+        run_checked!(self.program, closures, input)
+    }
+
+    /// Copies `points` into GPU memory, so that several FFTs can be done on
+    /// them without transferring them back and forth, see
+    /// [`SingleEcFftKernel::radix_ec_fft_on_device`].
+    pub fn upload(
+        &mut self, points: &[G::Curve],
+    ) -> EcResult<DeviceBuffer<G::Curve>> {
+        let owner = BufferOwner::new(self.id, &self.program);
+        let closures =
+            program_closures!(
+                |program, _arg| -> EcResult<DeviceBuffer<G::Curve>> {
+                    let buffer = program.create_buffer_from_slice(points)?;
+                    Ok(program.wrap_buffer(buffer, points.len(), owner))
+                }
+            );
+
+        run_checked!(self.program, closures, ())
+    }
+
+    /// Copies the points of `buffer` from GPU memory to the host.
+    ///
+    /// The buffer must have been created by this kernel.
+    pub fn download(
+        &mut self, buffer: DeviceBuffer<G::Curve>,
+    ) -> EcResult<Vec<G::Curve>> {
+        let owner = BufferOwner::new(self.id, &self.program);
+        let len = buffer.len();
+        let closures =
+            program_closures!(|program, buffer| -> EcResult<Vec<G::Curve>> {
+                let buffer = program.unwrap_buffer(buffer, owner)?;
+                let mut points = vec![G::Curve::zero(); len];
+                program.read_into_buffer(&buffer, &mut points)?;
+                Ok(points)
+            });
+
+        run_checked!(self.program, closures, buffer)
+    }
+
+    /// Performs FFT on the points of `buffer`, which stay in GPU memory.
+    ///
+    /// The buffer must have been created by this kernel, e.g. with
+    /// [`SingleEcFftKernel::upload`], and hold `2^log_n` points. The returned
+    /// buffer holds the result, it can be passed on to the next FFT.
+    pub fn radix_ec_fft_on_device(
+        &mut self, buffer: DeviceBuffer<G::Curve>, omega: &G::Scalar,
+        log_n: u32,
+    ) -> EcResult<DeviceBuffer<G::Curve>> {
+        let n = 1 << log_n;
+        check_len(n, buffer.len())?;
+        let owner = BufferOwner::new(self.id, &self.program);
+        let closures = program_closures!(|program,
+                                          buffer|
+         -> EcResult<
+            DeviceBuffer<G::Curve>,
+        > {
+            let mut src_buffer = program.unwrap_buffer(buffer, owner)?;
+            // It is safe as the GPU will initialize that buffer
+            let mut dst_buffer =
+                unsafe { program.create_buffer::<G::Curve>(n)? };
+
+            ec_fft_rounds!(
+                program,
+                src_buffer,
+                dst_buffer,
+                omega,
+                log_n,
+                &self.maybe_abort,
+                None
+            );
+
+            Ok(program.wrap_buffer(src_buffer, n, owner))
+        });
+
+        run_checked!(self.program, closures, buffer)
+    }
+
+    /// Adds the points of `b` to the ones of `a`, element by element.
+    ///
+    /// The additions are done in projective (Jacobian) coordinates with the
+    /// same routine the FFT uses, the point at infinity is supported in both
+    /// inputs. Inputs of different lengths result in an error.
+    pub fn point_add_many(
+        &mut self, a: &mut [G::Curve], b: &[G::Curve],
+    ) -> EcResult<()> {
+        check_len(a.len(), b.len())?;
+        if a.is_empty() {
+            return Ok(());
+        }
+        if let Some(maybe_abort) = &self.maybe_abort {
+            if maybe_abort() {
+                return Err(EcError::Aborted);
+            }
+        }
+
+        let n = a.len();
+        let closures = program_closures!(|program,
+                                          a: &mut [G::Curve]|
+         -> EcResult<()> {
+            let a_buffer = program.create_buffer_from_slice(&*a)?;
+            let b_buffer = program.create_buffer_from_slice(b)?;
+
+            let (global_work_size, local_work_size) = elementwise_work_size(n);
+            let kernel = program.create_kernel(
+                &format!("{}_add_many", G::name()),
+                global_work_size,
+                local_work_size,
+            )?;
+            kernel
+                .arg(&a_buffer)
+                .arg(&b_buffer)
+                .arg(&(n as u32))
+                .run()?;
+
+            program.read_into_buffer(&a_buffer, a)?;
+            Ok(())
+        });
+
+        run_checked!(self.program, closures, a)
     }
 }
 
 /// One FFT kernel for each GPU available.
+#[doc(alias = "FftGKernel")]
 pub struct EcFftKernel<'a, G>
 where
     G: GpuCurveAffine,
     G::Scalar: Field + GpuName,
 {
     kernels: Vec<SingleEcFftKernel<'a, G>>,
+    /// How the FFTs of a batch are split among the devices.
+    splitter: Arc<dyn WorkSplitter>,
+    /// Whether the work is split independently of the device properties, see
+    /// [`EcFftKernel::set_reproducible`].
+    reproducible: bool,
 }
 
 impl<'a, G> EcFftKernel<'a, G>
@@ -176,7 +491,7 @@ where
 {
     /// Create new kernels, one for each given device.
     pub fn create(programs: Vec<Program>) -> EcResult<Self> {
-        Self::create_optional_abort(programs, None)
+        Self::create_optional_abort(share(programs), None)
     }
 
     /// Create new kernels, one for each given device, with early abort hook.
@@ -188,84 +503,358 @@ where
         programs: Vec<Program>,
         maybe_abort: &'a (dyn Fn() -> bool + Send + Sync),
     ) -> EcResult<Self> {
-        Self::create_optional_abort(programs, Some(maybe_abort))
+        Self::create_optional_abort(share(programs), Some(maybe_abort))
+    }
+
+    /// Create new kernels from programs that may be shared with other
+    /// kernels, one for each given device.
+    ///
+    /// See [`SharedProgram`] for how to use a single program for several
+    /// kinds of kernels.
+    pub fn create_shared(programs: Vec<SharedProgram>) -> EcResult<Self> {
+        Self::create_optional_abort(programs, None)
     }
 
     fn create_optional_abort(
-        programs: Vec<Program>,
+        programs: Vec<SharedProgram>,
         maybe_abort: Option<&'a (dyn Fn() -> bool + Send + Sync)>,
     ) -> EcResult<Self> {
-        let kernels: Vec<_> = programs
-            .into_iter()
-            .filter_map(|program| {
-                let device_name = program.device_name().to_string();
-                let kernel =
-                    SingleEcFftKernel::<G>::create(program, maybe_abort);
-                if let Err(ref e) = kernel {
-                    error!(
-                        "Cannot initialize kernel for device '{}'! Error: {}",
-                        device_name, e
-                    );
-                }
-                kernel.ok()
-            })
-            .collect();
-
-        if kernels.is_empty() {
-            return Err(EcError::Simple("No working GPUs found!"));
+        // Check it upfront, as it is not a problem of a specific device.
+        if let Some(reason) = G::unsupported_reason() {
+            return Err(EcError::unsupported_curve::<G>(reason));
         }
+        let kernels = working_kernels(programs.into_iter().map(|program| {
+            let device_name = program.device_name().to_string();
+            let kernel = SingleEcFftKernel::<G>::create(program, maybe_abort);
+            (device_name, kernel)
+        }))?;
+
         info!("FFTg: {} working device(s) selected. ", kernels.len());
         for (i, k) in kernels.iter().enumerate() {
             info!("FFTg: Device {}: {}", i, k.program.device_name(),);
         }
 
-        Ok(Self { kernels })
+        Ok(Self {
+            kernels,
+            splitter: Arc::new(EvenSplit),
+            reproducible: false,
+        })
+    }
+
+    /// Sets how the FFTs of a batch, e.g. of
+    /// [`EcFftKernel::radix_ec_fft_many`], are split among the devices. By
+    /// default they are split evenly, [`MemorySplit`] gives devices with less
+    /// memory a smaller part.
+    ///
+    /// [`MemorySplit`]: crate::split::MemorySplit
+    pub fn set_work_splitter(&mut self, splitter: impl WorkSplitter + 'static) {
+        self.splitter = Arc::new(splitter);
+    }
+
+    /// Enables or disables the reproducible mode.
+    ///
+    /// In that mode the FFTs of a batch are always split evenly, no matter
+    /// which [`WorkSplitter`] is set, so that the split only depends on the
+    /// number of devices and not on the properties the drivers report. Every
+    /// FFT is done by a single device, hence the projective coordinates of
+    /// the results are the same on every run with the same devices. The price
+    /// is that devices with more memory don't get a larger share.
+    pub fn set_reproducible(&mut self, reproducible: bool) {
+        self.reproducible = reproducible;
+    }
+
+    /// Returns the information of the devices, in the order they get their
+    /// share of the work.
+    pub fn device_info(&self) -> Vec<DeviceInfo> {
+        self.kernels
+            .iter()
+            .map(|kern| kern.device_info.clone())
+            .collect()
+    }
+
+    /// Returns the range of the `n` items each device processes.
+    fn device_ranges(&self, n: usize) -> EcResult<Vec<Range<usize>>> {
+        // An even split only depends on the number of devices.
+        let splitter: &dyn WorkSplitter = if self.reproducible {
+            &EvenSplit
+        } else {
+            &*self.splitter
+        };
+        split_ranges(splitter, n, &self.device_info())
     }
 
     /// Performs FFT on `input`
     /// * `omega` - Special value `omega` is used for FFT over finite-fields
     /// * `log_n` - Specifies log2 of number of elements
     ///
-    /// Uses the first available GPU.
+    /// Uses the first available GPU. The result is in projective (Jacobian)
+    /// coordinates, no affine conversion is done.
     pub fn radix_ec_fft(
         &mut self, input: &mut [G::Curve], omega: &G::Scalar, log_n: u32,
     ) -> EcResult<()> {
         self.kernels[0].radix_ec_fft(input, omega, log_n)
     }
 
+    /// Copies `points` into GPU memory, see [`SingleEcFftKernel::upload`].
+    ///
+    /// Uses the first available GPU.
+    pub fn upload(
+        &mut self, points: &[G::Curve],
+    ) -> EcResult<DeviceBuffer<G::Curve>> {
+        self.kernels[0].upload(points)
+    }
+
+    /// Copies the points of `buffer` from GPU memory to the host, see
+    /// [`SingleEcFftKernel::download`].
+    ///
+    /// Uses the first available GPU.
+    pub fn download(
+        &mut self, buffer: DeviceBuffer<G::Curve>,
+    ) -> EcResult<Vec<G::Curve>> {
+        self.kernels[0].download(buffer)
+    }
+
+    /// Performs FFT on the points of `buffer`, which stay in GPU memory, see
+    /// [`SingleEcFftKernel::radix_ec_fft_on_device`].
+    ///
+    /// Uses the first available GPU.
+    pub fn radix_ec_fft_on_device(
+        &mut self, buffer: DeviceBuffer<G::Curve>, omega: &G::Scalar,
+        log_n: u32,
+    ) -> EcResult<DeviceBuffer<G::Curve>> {
+        self.kernels[0].radix_ec_fft_on_device(buffer, omega, log_n)
+    }
+
+    /// Adds the points of `b` to the ones of `a`, element by element, see
+    /// [`SingleEcFftKernel::point_add_many`].
+    ///
+    /// Uses the first available GPU.
+    pub fn point_add_many(
+        &mut self, a: &mut [G::Curve], b: &[G::Curve],
+    ) -> EcResult<()> {
+        self.kernels[0].point_add_many(a, b)
+    }
+
     /// Performs FFT on `inputs`
     /// * `omega` - Special value `omega` is used for FFT over finite-fields
     /// * `log_n` - Specifies log2 of number of elements
     ///
-    /// Uses all available GPUs to distribute the work.
+    /// Uses all available GPUs to distribute the work. The results are in
+    /// projective (Jacobian) coordinates, no affine conversion is done. If
+    /// affine points are needed, convert them in a batch, e.g. with
+    /// [`ark_ec::CurveGroup::normalize_batch`].
     pub fn radix_ec_fft_many(
         &mut self, inputs: &mut [&mut [G::Curve]], omegas: &[G::Scalar],
         log_ns: &[u32],
     ) -> EcResult<()> {
-        let n = inputs.len();
-        let num_devices = self.kernels.len();
-        let chunk_size = ((n as f64) / (num_devices as f64)).ceil() as usize;
+        self.radix_ec_fft_many_by(
+            inputs,
+            omegas,
+            log_ns,
+            |kern, input, omega, log_n, _| {
+                kern.radix_ec_fft(input, omega, log_n)
+            },
+        )
+    }
+
+    /// Performs the inverse FFT on `inputs`, `omegas` are the roots of unity
+    /// of the forward FFTs.
+    ///
+    /// Uses all available GPUs to distribute the work. The results are
+    /// multiplied by `n^-1` on the device, see
+    /// [`SingleEcFftKernel::radix_ec_ifft`], it's computed once for every
+    /// distinct size.
+    pub fn radix_ec_ifft_many(
+        &mut self, inputs: &mut [&mut [G::Curve]], omegas: &[G::Scalar],
+        log_ns: &[u32],
+    ) -> EcResult<()> {
+        let n_invs = size_inverses::<G::Scalar>(log_ns);
+        let omega_invs = omegas
+            .iter()
+            .map(inverse_omega)
+            .collect::<EcResult<Vec<_>>>()?;
+        self.radix_ec_fft_many_by(
+            inputs,
+            &omega_invs,
+            log_ns,
+            |kern, input, omega_inv, log_n, _| {
+                let scale = [n_invs[&log_n], G::Scalar::ONE];
+                kern.radix_ec_fft_on_coset(
+                    input,
+                    omega_inv,
+                    log_n,
+                    Some(scale),
+                    None,
+                )
+            },
+        )
+    }
+
+    /// Performs FFT on `inputs` over the cosets `shifts[i] * <omegas[i]>`.
+    ///
+    /// Uses all available GPUs to distribute the work, see
+    /// [`SingleEcFftKernel::radix_ec_fft_coset`]. There must be a shift for
+    /// every input, a shift of one is an FFT over the subgroup.
+    #[doc(alias = "radix_fftg_coset_many")]
+    pub fn radix_ec_fft_coset_many(
+        &mut self, inputs: &mut [&mut [G::Curve]], omegas: &[G::Scalar],
+        log_ns: &[u32], shifts: &[G::Scalar],
+    ) -> EcResult<()> {
+        check_len(inputs.len(), shifts.len())?;
+        self.radix_ec_fft_many_by(
+            inputs,
+            omegas,
+            log_ns,
+            |kern, input, omega, log_n, i| {
+                kern.radix_ec_fft_coset(input, omega, log_n, &shifts[i])
+            },
+        )
+    }
+
+    /// Performs the inverse FFT on `inputs` over the cosets
+    /// `shifts[i] * <omegas[i]>`, `omegas` are the roots of unity of the
+    /// forward FFTs.
+    ///
+    /// It's the inverse of [`EcFftKernel::radix_ec_fft_coset_many`]. Uses all
+    /// available GPUs to distribute the work, see
+    /// [`SingleEcFftKernel::radix_ec_ifft_coset`].
+    #[doc(alias = "radix_ifftg_coset_many")]
+    pub fn radix_ec_ifft_coset_many(
+        &mut self, inputs: &mut [&mut [G::Curve]], omegas: &[G::Scalar],
+        log_ns: &[u32], shifts: &[G::Scalar],
+    ) -> EcResult<()> {
+        check_len(inputs.len(), shifts.len())?;
+        self.radix_ec_fft_many_by(
+            inputs,
+            omegas,
+            log_ns,
+            |kern, input, omega, log_n, i| {
+                kern.radix_ec_ifft_coset(input, omega, log_n, &shifts[i])
+            },
+        )
+    }
+
+    /// Performs FFT on `input`, distributed among all available GPUs.
+    ///
+    /// The FFT is split into smaller ones with the four-step algorithm, like
+    /// [`FftKernel::radix_fft_distributed`] does. Instead of multiplying the
+    /// points with the twiddle factors on the host, row `k1` is transformed
+    /// over the coset `omega^k1 * <omega^rows>`, so that all scalar
+    /// multiplications happen on the devices. With a single device this is
+    /// [`EcFftKernel::radix_ec_fft`]. The result is in projective (Jacobian)
+    /// coordinates, no affine conversion is done.
+    ///
+    /// [`FftKernel::radix_fft_distributed`]: crate::fft::FftKernel::radix_fft_distributed
+    pub fn radix_ec_fft_distributed(
+        &mut self, input: &mut [G::Curve], omega: &G::Scalar, log_n: u32,
+    ) -> EcResult<()> {
+        check_len(1 << log_n, input.len())?;
+        if self.kernels.len() == 1 {
+            return self.radix_ec_fft(input, omega, log_n);
+        }
+        if log_n == 0 {
+            return Ok(());
+        }
+        let log_rows = log_n / 2;
+        let log_cols = log_n - log_rows;
+        let (rows, cols) = (1 << log_rows, 1 << log_cols);
+
+        // Element `(j1, j2)` is `input[j1 * cols + j2]`, column `j2` is stored
+        // contiguously.
+        let mut columns = Vec::with_capacity(input.len());
+        for j2 in 0..cols {
+            columns.extend((0..rows).map(|j1| input[j1 * cols + j2]));
+        }
+        // Columns of a single element are already transformed.
+        if log_rows > 0 {
+            let omega_col = pow_vartime(omega, [cols as u64]);
+            self.radix_ec_fft_many(
+                &mut columns.chunks_mut(rows).collect::<Vec<_>>(),
+                &vec![omega_col; cols],
+                &vec![log_rows; cols],
+            )?;
+        }
+
+        // Transpose, so that row `k1` is stored contiguously.
+        let mut rows_data = Vec::with_capacity(input.len());
+        for k1 in 0..rows {
+            rows_data.extend((0..cols).map(|j2| columns[j2 * rows + k1]));
+        }
+        drop(columns);
+        // Entry `j2` of row `k1` needs the twiddle factor `omega^(j2 * k1)`,
+        // which is the coset shift `omega^k1`.
+        let mut shifts = Vec::with_capacity(rows);
+        let mut shift = G::Scalar::ONE;
+        for _ in 0..rows {
+            shifts.push(shift);
+            shift *= omega;
+        }
+        let omega_row = pow_vartime(omega, [rows as u64]);
+        self.radix_ec_fft_coset_many(
+            &mut rows_data.chunks_mut(cols).collect::<Vec<_>>(),
+            &vec![omega_row; rows],
+            &vec![log_cols; rows],
+            &shifts,
+        )?;
+
+        // Output `k1 + k2 * rows` is entry `k2` of row `k1`.
+        for (k1, row) in rows_data.chunks(cols).enumerate() {
+            for (k2, value) in row.iter().enumerate() {
+                input[k1 + k2 * rows] = *value;
+            }
+        }
+        Ok(())
+    }
+
+    /// Distributes the FFTs of `inputs` among the devices, `fft` performs
+    /// the FFT of the input with the given index on a kernel.
+    fn radix_ec_fft_many_by<T>(
+        &mut self, inputs: &mut [&mut [G::Curve]], omegas: &[G::Scalar],
+        log_ns: &[u32], fft: T,
+    ) -> EcResult<()>
+    where
+        T: Fn(
+                &mut SingleEcFftKernel<'a, G>,
+                &mut [G::Curve],
+                &G::Scalar,
+                u32,
+                usize,
+            ) -> EcResult<()>
+            + Sync,
+    {
+        check_batch(inputs, omegas, log_ns)?;
+        let ranges = self.device_ranges(inputs.len())?;
+        let fft = &fft;
 
         let result = Arc::new(RwLock::new(Ok(())));
 
         THREAD_POOL.scoped(|s| {
-            for (((inputs, omegas), log_ns), kern) in inputs
-                .chunks_mut(chunk_size)
-                .zip(omegas.chunks(chunk_size))
-                .zip(log_ns.chunks(chunk_size))
-                .zip(self.kernels.iter_mut())
+            let mut rest = &mut inputs[..];
+            for (range, kern) in ranges.into_iter().zip(self.kernels.iter_mut())
             {
+                let (inputs, tail) =
+                    std::mem::take(&mut rest).split_at_mut(range.len());
+                rest = tail;
+                if inputs.is_empty() {
+                    continue;
+                }
+                let start = range.start;
+                let omegas = &omegas[range.clone()];
+                let log_ns = &log_ns[range];
                 let result = result.clone();
                 s.execute(move || {
-                    for ((input, omega), log_n) in
-                        inputs.iter_mut().zip(omegas.iter()).zip(log_ns.iter())
+                    for (j, ((input, omega), log_n)) in inputs
+                        .iter_mut()
+                        .zip(omegas.iter())
+                        .zip(log_ns.iter())
+                        .enumerate()
                     {
                         if result.read().unwrap().is_err() {
                             break;
                         }
 
                         if let Err(err) =
-                            kern.radix_ec_fft(input, omega, *log_n)
+                            fft(kern, input, omega, *log_n, start + j)
                         {
                             *result.write().unwrap() = Err(err);
                             break;
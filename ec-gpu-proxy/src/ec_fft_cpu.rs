@@ -55,6 +55,44 @@ pub fn serial_ec_fft<G: GpuCurveAffine>(
     }
 }
 
+/// Calculate the coset Fast Fourier Transform on the CPU (single-threaded).
+///
+/// The points in `a` are multiplied by the powers of the coset generator `g`
+/// before the transform, so the result is the evaluation over `g * <omega>`.
+pub fn serial_ec_coset_fft<G: GpuCurveAffine>(
+    a: &mut [G::Curve], omega: &G::Scalar, g: &G::Scalar, log_n: u32,
+) where G::Scalar: PrimeField {
+    distribute_powers::<G>(a, G::Scalar::ONE, g);
+    serial_ec_fft::<G>(a, omega, log_n);
+}
+
+/// Calculate the inverse coset Fast Fourier Transform on the CPU
+/// (single-threaded).
+///
+/// `omega` and `g` are the same values that were passed to
+/// [`serial_ec_coset_fft`], the inverses and the `1/n` scaling are applied
+/// here.
+pub fn serial_ec_coset_ifft<G: GpuCurveAffine>(
+    a: &mut [G::Curve], omega: &G::Scalar, g: &G::Scalar, log_n: u32,
+) where G::Scalar: PrimeField {
+    let omega_inv = omega.inverse().expect("omega must be non-zero");
+    let g_inv = g.inverse().expect("coset generator must be non-zero");
+    let n_inv = G::Scalar::from(a.len() as u64).inverse().unwrap();
+    serial_ec_fft::<G>(a, &omega_inv, log_n);
+    distribute_powers::<G>(a, n_inv, &g_inv);
+}
+
+/// Multiplies `a[i]` by `c * g^i`.
+fn distribute_powers<G: GpuCurveAffine>(
+    a: &mut [G::Curve], c: G::Scalar, g: &G::Scalar,
+) {
+    let mut power = c;
+    for point in a {
+        point.mul_assign(power);
+        power *= g;
+    }
+}
+
 /// Calculate the Fast Fourier Transform on the CPU (multithreaded).
 ///
 /// The result is is written to the input `a`.
@@ -123,7 +161,7 @@ mod tests {
 
     fn omega<F: FftField>(num_coeffs: usize) -> F {
         // Compute omega, the 2^exp primitive root of unity
-        let exp = (num_coeffs as f32).log2().floor() as u32;
+        let exp = crate::log2_strict(num_coeffs);
         let mut omega = F::TWO_ADIC_ROOT_OF_UNITY;
         for _ in exp..F::TWO_ADICITY {
             omega = omega.square();
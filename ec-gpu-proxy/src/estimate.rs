@@ -0,0 +1,37 @@
+//! A simple cost model to estimate the runtime of GPU operations.
+//!
+//! The throughput values are rough assumptions for recent GPUs. The
+//! estimates they lead to are only meant for scheduling, they can be refined
+//! by timing a small operation on the actual device.
+
+/// The assumed number of multiplications of a 256-bit field a compute unit
+/// does per second.
+const FIELD_MULS_PER_UNIT_PER_SEC: f64 = 4e8;
+/// The assumed bandwidth between host and device (PCIe 3.0 x16) in bytes per
+/// second.
+const TRANSFER_BYTES_PER_SEC: f64 = 12e9;
+/// The assumed bandwidth of the device memory in bytes per second.
+const DEVICE_BYTES_PER_SEC: f64 = 400e9;
+/// The number of compute units that is assumed if the device is unknown.
+pub(crate) const DEFAULT_COMPUTE_UNITS: u32 = 32;
+
+/// Returns the seconds `muls` multiplications of the field `F` take on a
+/// device with `compute_units` compute units.
+///
+/// The cost of a multiplication grows quadratically with the size of the
+/// field elements.
+pub(crate) fn compute_secs<F>(muls: f64, compute_units: u32) -> f64 {
+    let relative_size = std::mem::size_of::<F>() as f64 / 32.0;
+    muls * relative_size * relative_size
+        / (FIELD_MULS_PER_UNIT_PER_SEC * compute_units as f64)
+}
+
+/// Returns the seconds transferring `bytes` between host and device takes.
+pub(crate) fn transfer_secs(bytes: usize) -> f64 {
+    bytes as f64 / TRANSFER_BYTES_PER_SEC
+}
+
+/// Returns the seconds reading or writing `bytes` of device memory takes.
+pub(crate) fn device_memory_secs(bytes: usize) -> f64 {
+    bytes as f64 / DEVICE_BYTES_PER_SEC
+}
@@ -1,30 +1,760 @@
 use std::{
     cmp,
-    sync::{Arc, RwLock},
+    collections::BTreeMap,
+    ops::Range,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    time::{Duration, Instant},
 };
 
-use ag_types::GpuName;
-use ark_ff::Field;
-use log::{error, info};
+use ag_types::{CanonicalBytes, GpuName};
+use ark_ff::{FftField, Field};
+use ark_poly::{
+    univariate::DensePolynomial, DenseUVPolynomial, EvaluationDomain,
+    Evaluations, Radix2EvaluationDomain,
+};
+use log::info;
 use rust_gpu_tools::{program_closures, LocalBuffer, Program};
 
-use crate::{pow_vartime, threadpool::THREAD_POOL};
-use ec_gpu_program::{EcError, EcResult};
+#[cfg(feature = "metrics")]
+use crate::metrics::{self, Operation};
+use crate::{
+    budget::{reserve, MemoryBudget},
+    bytes::FieldSpec,
+    device::{
+        device_info_of, run_checked, share, working_kernels, SharedProgram,
+    },
+    estimate,
+    fft_cpu::{serial_coset_fft, serial_coset_ifft, serial_fft},
+    pow_vartime,
+    scratch::{HostAllocator, ScratchVec},
+    split::{split_ranges, EvenSplit, WorkSplitter},
+    threadpool::THREAD_POOL,
+};
+use ec_gpu_program::{DeviceInfo, EcError, EcResult};
 
 const LOG2_MAX_ELEMENTS: usize = 32; // At most 2^32 elements is supported.
-const MAX_LOG2_RADIX: u32 = 8; // Radix256
-const MAX_LOG2_LOCAL_WORK_SIZE: u32 = 7; // 128
+pub(crate) const MAX_LOG2_RADIX: u32 = 8; // Radix256
+pub(crate) const MAX_LOG2_LOCAL_WORK_SIZE: u32 = 7; // 128
+/// The local work size of the kernels that process every element separately.
+const ELEMENTWISE_LOCAL_WORK_SIZE: usize = 64;
+
+/// The size (log2 of the number of elements) of the FFT that is timed for
+/// calibration.
+const CALIBRATION_LOG_N: u32 = 16;
+
+/// The number of coefficients a single thread evaluates in
+/// [`SingleFftKernel::batch_evaluate_at`].
+const EVAL_CHUNK_LEN: usize = 256;
+
+/// The maximum number of elements that are transferred at once by
+/// [`SingleFftKernel::radix_fft_segmented`].
+const SEGMENT_TRANSFER_LEN: usize = 1 << 16;
+
+/// The code of [`FftPostMap::None`] in the GPU code. The constant buffer that
+/// is passed along with it is never read.
+pub(crate) const NO_POST_MAP: u32 = 0;
+
+/// The number of consecutive evaluations a single thread updates in
+/// [`SingleFftKernel::update_fft`].
+const UPDATE_CHUNK_LEN: usize = 64;
+
+/// The width and height of the tiles of [`transpose_tiled`]. A work group of
+/// `TRANSPOSE_TILE^2` threads transposes one tile.
+pub(crate) const TRANSPOSE_TILE: usize = 16;
+
+/// Checks on the GPU that `omega^(n/2)`, the entry `log_n - 1` of the
+/// `omegas` buffer, is `-1`, if `verify` is set.
+///
+/// Otherwise `omega` isn't a primitive `n`-th root of unity, or the twiddle
+/// factors are broken, and the FFT would silently return wrong results.
+macro_rules! verify_twiddles {
+    ($program:expr, $omegas_buffer:expr, $log_n:expr, $verify:expr) => {{
+        let log_n: u32 = $log_n;
+        if $verify && log_n > 0 {
+            let program = $program;
+            let result_buffer = program.create_buffer_from_slice(&[0u32])?;
+            let kernel = program.create_kernel(
+                &format!("{}_check_twiddles", F::name()),
+                1,
+                1,
+            )?;
+            kernel
+                .arg($omegas_buffer)
+                .arg(&log_n)
+                .arg(&result_buffer)
+                .run()?;
+            let mut result = [0u32];
+            program.read_into_buffer(&result_buffer, &mut result)?;
+            if result[0] == 0 {
+                return Err(EcError::Simple(
+                    "The twiddle factors are invalid, omega is not a primitive \
+                     root of unity of the FFT size",
+                ));
+            }
+        }
+    }};
+}
+
+/// Runs an FFT on each of the `count` inputs of `2^log_n` elements, which are
+/// stored back to back in the buffer `src` refers to, with the given
+/// `twiddles`.
+///
+/// Every round is a single kernel launch for all inputs. The buffer `dst`
+/// refers to is of the same size and used as scratch space, the references
+/// are swapped after each round, so that `src` refers to the results at the
+/// end. The `post_map` is applied to the outputs of the last round.
+macro_rules! batch_fft {
+    (
+        $kern:expr,
+        $program:expr,
+        $src:ident,
+        $dst:ident,
+        $count:expr,
+        $log_n:expr,
+        $twiddles:expr,
+        $omegas_buffer:expr,
+        $post_map:expr,
+        $post_const_buffer:expr
+    ) => {{
+        let kern = &*$kern;
+        let program = $program;
+        let count: usize = $count;
+        let log_n: u32 = $log_n;
+        let omegas_buffer = $omegas_buffer;
+        let post_map: u32 = $post_map;
+        let post_const_buffer = $post_const_buffer;
+        let n = 1usize << log_n;
+
+        if kern.in_shared_mem(log_n) {
+            // Every work group does one of the small FFTs in local memory.
+            let local_work_size = 1
+                << cmp::min(log_n.saturating_sub(1), MAX_LOG2_LOCAL_WORK_SIZE);
+            let kernel = program.create_kernel(
+                &format!("{}_shared_fft_batch", F::name()),
+                count,
+                local_work_size,
+            )?;
+            kernel
+                .arg($src)
+                .arg(omegas_buffer)
+                .arg(&LocalBuffer::<F>::new(n + n / 2))
+                .arg(&log_n)
+                .arg(&post_map)
+                .arg(post_const_buffer)
+                .run()?;
+        } else {
+            let max_deg = cmp::min(MAX_LOG2_RADIX, log_n);
+            let pq_buffer = program.create_buffer_from_slice(&$twiddles.pq)?;
+
+            let mut log_p = 0u32;
+            while log_p < log_n {
+                if let Some(maybe_abort) = &kern.maybe_abort {
+                    if maybe_abort() {
+                        return Err(EcError::Aborted);
+                    }
+                }
+
+                let deg = cmp::min(max_deg, log_n - log_p);
+                let round_post_map = if log_p + deg == log_n {
+                    post_map
+                } else {
+                    NO_POST_MAP
+                };
+                let local_work_size =
+                    1 << cmp::min(deg - 1, MAX_LOG2_LOCAL_WORK_SIZE);
+                // The work groups of all FFTs of the batch run in the same
+                // launch.
+                let global_work_size = count * (n >> deg);
+                let kernel = program.create_kernel(
+                    &format!("{}_radix_fft_batch", F::name()),
+                    global_work_size,
+                    local_work_size as usize,
+                )?;
+                kernel
+                    .arg($src)
+                    .arg($dst)
+                    .arg(&pq_buffer)
+                    .arg(omegas_buffer)
+                    .arg(&LocalBuffer::<F>::new(1 << deg))
+                    .arg(&(n as u32))
+                    .arg(&log_p)
+                    .arg(&deg)
+                    .arg(&max_deg)
+                    .arg(&round_post_map)
+                    .arg(post_const_buffer)
+                    .run()?;
+
+                log_p += deg;
+                std::mem::swap(&mut $src, &mut $dst);
+            }
+        }
+    }};
+}
+
+/// Runs an FFT on each of the `num_rows` rows of `2^log_len` elements, which
+/// are stored one after another in `matrix`, with the given `twiddles`.
+///
+/// All rows are transformed together with [`batch_fft`], the results end up
+/// in `matrix` again.
+macro_rules! row_ffts {
+    (
+        $kern:expr,
+        $program:expr,
+        $matrix:expr,
+        $num_rows:expr,
+        $log_len:expr,
+        $twiddles:expr
+    ) => {{
+        let kern = $kern;
+        let program = $program;
+        let matrix = $matrix;
+        let num_rows: usize = $num_rows;
+        let log_len: u32 = $log_len;
+        let twiddles = $twiddles;
+        let total = num_rows << log_len;
+        // It is safe as the GPU will initialize that buffer before it is read.
+        let scratch_buffer = unsafe { program.create_buffer::<F>(total)? };
+        let omegas_buffer =
+            program.create_buffer_from_slice(&twiddles.omegas)?;
+        verify_twiddles!(
+            program,
+            &omegas_buffer,
+            log_len,
+            kern.verify_twiddles
+        );
+
+        let mut src = matrix;
+        let mut dst = &scratch_buffer;
+        batch_fft!(
+            kern,
+            program,
+            src,
+            dst,
+            num_rows,
+            log_len,
+            twiddles,
+            &omegas_buffer,
+            NO_POST_MAP,
+            &omegas_buffer
+        );
+        // After an odd number of rounds the results are in the scratch buffer.
+        if !std::ptr::eq(src, matrix) {
+            let (copy_global, copy_local) = elementwise_work_size(total);
+            program
+                .create_kernel(
+                    &format!("{}_copy_to_offset", F::name()),
+                    copy_global,
+                    copy_local,
+                )?
+                .arg(src)
+                .arg(matrix)
+                .arg(&0u32)
+                .arg(&(total as u32))
+                .run()?;
+        }
+    }};
+}
+
+/// Writes the transpose of the `rows` x `cols` matrix in `src` to `dst`, both
+/// are stored row by row.
+///
+/// The matrix is split into tiles of `TRANSPOSE_TILE` x `TRANSPOSE_TILE`
+/// elements, which are transposed in local memory, so that both the reads
+/// and the writes of global memory are coalesced. The dimensions don't need
+/// to be multiples of the tile size.
+macro_rules! transpose_tiled {
+    ($program:expr, $src:expr, $dst:expr, $rows:expr, $cols:expr) => {{
+        let rows: usize = $rows;
+        let cols: usize = $cols;
+        let tile = $crate::fft::TRANSPOSE_TILE;
+        let num_tiles = $crate::fft::div_ceil(rows, tile)
+            * $crate::fft::div_ceil(cols, tile);
+        $program
+            .create_kernel(
+                &format!("{}_transpose_tiled", F::name()),
+                num_tiles,
+                tile * tile,
+            )?
+            .arg($src)
+            .arg($dst)
+            .arg(&LocalBuffer::<F>::new(tile * (tile + 1)))
+            .arg(&(rows as u32))
+            .arg(&(cols as u32))
+            .arg(&(tile as u32))
+            .run()?;
+    }};
+}
+
+pub(crate) use transpose_tiled;
+
+/// Returns an error if the `domain` is a coset of the subgroup, the FFT
+/// kernels only evaluate over the subgroup itself.
+fn check_subgroup_domain<F: FftField>(
+    domain: &Radix2EvaluationDomain<F>,
+) -> EcResult<()> {
+    if domain.offset != F::ONE {
+        return Err(EcError::Simple("Coset domains are not supported"));
+    }
+    Ok(())
+}
+
+/// Fails with an [`EcError::DimensionMismatch`] if the `actual` length isn't
+/// the `expected` one.
+pub(crate) fn check_len(expected: usize, actual: usize) -> EcResult<()> {
+    if actual != expected {
+        return Err(EcError::DimensionMismatch { expected, actual });
+    }
+    Ok(())
+}
+
+/// Checks that there are as many `omegas` and `log_ns` as `inputs` and that
+/// every input has `2^log_n` elements.
+pub(crate) fn check_batch<T, S>(
+    inputs: &[&mut [T]], omegas: &[S], log_ns: &[u32],
+) -> EcResult<()> {
+    check_len(inputs.len(), omegas.len())?;
+    check_len(inputs.len(), log_ns.len())?;
+    for (i, (input, log_n)) in inputs.iter().zip(log_ns).enumerate() {
+        if input.len() != 1 << log_n {
+            return Err(EcError::InvalidLength(format!(
+                "input {} has {} elements, but log_n is {}",
+                i,
+                input.len(),
+                log_n
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the inverse of the root of unity `omega`, which fails with an
+/// error if it's zero.
+pub(crate) fn inverse_omega<F: Field>(omega: &F) -> EcResult<F> {
+    omega
+        .inverse()
+        .ok_or(EcError::Simple("The root of unity must be non-zero"))
+}
+
+/// Divide and ceil to the next value.
+pub(crate) const fn div_ceil(a: usize, b: usize) -> usize { (a + b - 1) / b }
+
+/// Returns the global and the local work size for a kernel that uses one
+/// thread per element.
+///
+/// The global work size follows CUDA's definition and is the number of
+/// `ELEMENTWISE_LOCAL_WORK_SIZE` sized thread groups.
+pub(crate) fn elementwise_work_size(n: usize) -> (usize, usize) {
+    let global_work_size = div_ceil(n, ELEMENTWISE_LOCAL_WORK_SIZE);
+    (global_work_size, ELEMENTWISE_LOCAL_WORK_SIZE)
+}
+
+/// Returns the inverse of `2^log_n` for every distinct size in `log_ns`, which
+/// scales the results of an inverse FFT.
+pub(crate) fn size_inverses<F: Field>(log_ns: &[u32]) -> BTreeMap<u32, F> {
+    let mut n_invs = BTreeMap::new();
+    for &log_n in log_ns {
+        n_invs.entry(log_n).or_insert_with(|| {
+            F::from(1u64 << log_n).inverse().expect("n is non-zero")
+        });
+    }
+    n_invs
+}
+
+/// Copies the elements of the logical array that is formed by `segments` into
+/// `chunk`, starting at the index `offset` of that array.
+fn gather_segments<F: Copy>(
+    segments: &[&mut [F]], offset: usize, chunk: &mut [F],
+) {
+    let mut start = 0;
+    let mut filled = 0;
+    for segment in segments {
+        let end = start + segment.len();
+        if filled < chunk.len() && offset + filled < end {
+            let from = offset + filled - start;
+            let len = cmp::min(segment.len() - from, chunk.len() - filled);
+            chunk[filled..filled + len]
+                .copy_from_slice(&segment[from..from + len]);
+            filled += len;
+        }
+        start = end;
+    }
+}
+
+/// Copies `chunk` into the logical array that is formed by `segments`,
+/// starting at the index `offset` of that array.
+fn scatter_segments<F: Copy>(
+    segments: &mut [&mut [F]], offset: usize, chunk: &[F],
+) {
+    let mut start = 0;
+    let mut copied = 0;
+    for segment in segments.iter_mut() {
+        let end = start + segment.len();
+        if copied < chunk.len() && offset + copied < end {
+            let from = offset + copied - start;
+            let len = cmp::min(segment.len() - from, chunk.len() - copied);
+            segment[from..from + len]
+                .copy_from_slice(&chunk[copied..copied + len]);
+            copied += len;
+        }
+        start = end;
+    }
+}
+
+/// Precalculates the twiddle factors the `radix_fft` kernel needs.
+///
+/// Returns `pq`, which is
+/// `[omega^(0/(2^(deg-1))), omega^(1/(2^(deg-1))), ...,
+/// omega^((2^(deg-1)-1)/(2^(deg-1)))]` and valid for radix degrees up to
+/// `min(MAX_LOG2_RADIX, log_n)`, and `omegas`, which is
+/// `[omega, omega^2, omega^4, omega^8, ..., omega^(2^31)]`.
+pub(crate) fn precalculate_twiddles<F: Field>(
+    omega: &F, log_n: u32,
+) -> (Vec<F>, Vec<F>) {
+    let n = 1 << log_n;
+    let max_deg = cmp::min(MAX_LOG2_RADIX, log_n);
+
+    let mut pq = vec![F::ZERO; 1 << max_deg >> 1];
+    let twiddle = pow_vartime(omega, [(n >> max_deg) as u64]);
+    pq[0] = F::ONE;
+    if max_deg > 1 {
+        pq[1] = twiddle;
+        for i in 2..(1 << max_deg >> 1) {
+            pq[i] = pq[i - 1];
+            pq[i].mul_assign(&twiddle);
+        }
+    }
+
+    let mut omegas = vec![F::ZERO; 32];
+    omegas[0] = *omega;
+    for i in 1..LOG2_MAX_ELEMENTS {
+        omegas[i] = pow_vartime(&omegas[i - 1], [2u64]);
+    }
+
+    (pq, omegas)
+}
+
+/// The maximum number of unpinned twiddle sets that are cached per device.
+const MAX_CACHED_TWIDDLES: usize = 8;
+
+/// Precalculated twiddle factors, see [`precalculate_twiddles`].
+struct Twiddles<F> {
+    pq: Vec<F>,
+    omegas: Vec<F>,
+}
+
+/// An entry of the [`TwiddleCache`].
+struct CachedTwiddles<F> {
+    log_n: u32,
+    omega: F,
+    twiddles: Arc<Twiddles<F>>,
+    /// Pinned twiddles are never evicted.
+    pinned: bool,
+}
+
+/// Cache of the twiddle factors of the most recently used FFT sizes.
+struct TwiddleCache<F> {
+    /// The entries, from the least to the most recently used one.
+    entries: Vec<CachedTwiddles<F>>,
+    /// The number of times twiddles were calculated.
+    generations: usize,
+}
+
+impl<F: Field> TwiddleCache<F> {
+    fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            generations: 0,
+        }
+    }
+
+    /// Returns the twiddles for the given size and `omega`. They are
+    /// calculated if they are not cached yet.
+    fn get(&mut self, omega: &F, log_n: u32) -> Arc<Twiddles<F>> {
+        self.get_or_insert(omega, log_n, false)
+    }
+
+    /// Calculates the twiddles for the given size and `omega` if needed and
+    /// makes sure they are never evicted.
+    fn pin(&mut self, omega: &F, log_n: u32) {
+        self.get_or_insert(omega, log_n, true);
+    }
+
+    fn get_or_insert(
+        &mut self, omega: &F, log_n: u32, pin: bool,
+    ) -> Arc<Twiddles<F>> {
+        let position = self
+            .entries
+            .iter()
+            .position(|entry| entry.log_n == log_n && entry.omega == *omega);
+        let mut entry = match position {
+            Some(index) => self.entries.remove(index),
+            None => {
+                self.generations += 1;
+                let (pq, omegas) = precalculate_twiddles(omega, log_n);
+                CachedTwiddles {
+                    log_n,
+                    omega: *omega,
+                    twiddles: Arc::new(Twiddles { pq, omegas }),
+                    pinned: false,
+                }
+            }
+        };
+        entry.pinned |= pin;
+        let twiddles = entry.twiddles.clone();
+        self.entries.push(entry);
+
+        // Evict the least recently used unpinned entry.
+        if self.entries.iter().filter(|entry| !entry.pinned).count()
+            > MAX_CACHED_TWIDDLES
+        {
+            let index = self
+                .entries
+                .iter()
+                .position(|entry| !entry.pinned)
+                .expect("there are unpinned entries");
+            self.entries.remove(index);
+        }
+
+        twiddles
+    }
+}
+
+/// The representation of the field elements that are passed into an FFT.
+///
+/// Arkworks keeps field elements in Montgomery form, which is also what the
+/// kernels operate on. Data that comes from an external source might be in
+/// normal form instead. Such input is converted on the device right after the
+/// upload, the result is converted back right before the download. This costs
+/// two extra passes over the data with one field multiplication per element
+/// each, which is small compared to the `log_n` rounds of the FFT itself.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InputForm {
+    /// The elements are in Montgomery form (the arkworks representation).
+    #[default]
+    Montgomery,
+    /// The elements are in normal form, i.e. the limbs of the canonical
+    /// integer, least significant limb first.
+    Normal,
+}
+
+/// An elementwise map that is applied to the outputs of an FFT, see
+/// [`SingleFftKernel::radix_fft_with_map`].
+///
+/// The map is applied when the outputs are written in the last round of the
+/// FFT, which saves a separate pass over them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FftPostMap<F> {
+    /// The outputs are left as they are.
+    #[default]
+    None,
+    /// Every output is squared.
+    Square,
+    /// Every output is negated.
+    Negate,
+    /// The constant is added to every output.
+    AddConst(F),
+    /// Every output is multiplied by the constant.
+    MulConst(F),
+}
+
+impl<F: Field> FftPostMap<F> {
+    /// Applies the map to a single element on the host.
+    pub fn apply(&self, x: F) -> F {
+        match self {
+            FftPostMap::None => x,
+            FftPostMap::Square => x.square(),
+            FftPostMap::Negate => -x,
+            FftPostMap::AddConst(c) => x + c,
+            FftPostMap::MulConst(c) => x * c,
+        }
+    }
+
+    /// Returns the code of the map in the GPU code and its constant. Maps
+    /// without a constant return zero.
+    fn encode(&self) -> (u32, F) {
+        match self {
+            FftPostMap::None => (NO_POST_MAP, F::ZERO),
+            FftPostMap::Square => (1, F::ZERO),
+            FftPostMap::Negate => (2, F::ZERO),
+            FftPostMap::AddConst(c) => (3, *c),
+            FftPostMap::MulConst(c) => (4, *c),
+        }
+    }
+}
+
+/// The FFTs of a device with the same size and `omega`, which are done at
+/// once, see [`FftKernel::radix_fft_many_by`].
+struct FftGroup<'b, F> {
+    log_n: u32,
+    omega: F,
+    inputs: Vec<&'b mut [F]>,
+    /// The indices of the inputs within all inputs of the call.
+    indices: Vec<usize>,
+}
+
+/// How an FFT is moved onto a coset `shift * <omega>` of the subgroup.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Coset<F> {
+    /// The FFT is over the subgroup.
+    None,
+    /// The inputs are multiplied by the powers of the shift in the first
+    /// round of the FFT.
+    Shift(F),
+    /// The outputs are multiplied by the powers of the inverse shift after
+    /// the FFT, which is given here.
+    Unshift(F),
+}
+
+/// The domains of a quotient, see [`SingleFftKernel::compute_quotient`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuotientDomain<F> {
+    /// The root of unity of the evaluation domain.
+    pub omega: F,
+    /// The log2 of the size of the evaluation domain.
+    pub log_n: u32,
+    /// The log2 of the size of the subgroup `H`, whose vanishing polynomial
+    /// `Z_H(X) = X^|H| - 1` divides the numerator. It must not exceed
+    /// `log_n`.
+    pub log_h: u32,
+}
+
+/// The primitive roots of unity of all subgroup sizes up to a maximum, see
+/// [`FftKernel::precompute_roots`].
+///
+/// The table is computed on the host once and can be passed to the FFTs of
+/// any of those sizes, e.g. [`FftKernel::radix_fft_with_roots`], instead of
+/// computing the root of unity of every FFT again. The roots are the same
+/// as the ones of [`FftField::get_root_of_unity`]. Unlike the twiddle
+/// factors, nothing of it is kept on the GPU.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RootTable<F> {
+    /// The root of unity of order `2^i` at index `i`.
+    omegas: Vec<F>,
+    /// The inverses of the `omegas`.
+    omega_invs: Vec<F>,
+    /// The inverse of `2^i` at index `i`, which scales the inverse FFT.
+    size_invs: Vec<F>,
+}
+
+impl<F: FftField> RootTable<F> {
+    /// Computes the roots of unity of the sizes `2^i` and their inverses, for
+    /// all `i` in `0..=max_log_n`.
+    ///
+    /// Only the largest root is inverted, all other roots and inverses are
+    /// obtained by squaring.
+    pub fn new(max_log_n: u32) -> EcResult<Self> {
+        if max_log_n > F::TWO_ADICITY {
+            return Err(EcError::Simple(
+                "The field has no subgroup of that size",
+            ));
+        }
+        let omega = F::get_root_of_unity(1 << max_log_n)
+            .ok_or(EcError::Simple("The field has no subgroup of that size"))?;
+        let omega_inv = inverse_omega(&omega)?;
+        let two_inv = F::from(2u64).inverse().expect("two is non-zero");
+
+        let len = max_log_n as usize + 1;
+        let mut omegas = vec![omega; len];
+        let mut omega_invs = vec![omega_inv; len];
+        for i in (0..len - 1).rev() {
+            omegas[i] = omegas[i + 1].square();
+            omega_invs[i] = omega_invs[i + 1].square();
+        }
+        let mut size_invs = vec![F::ONE; len];
+        for i in 1..len {
+            size_invs[i] = size_invs[i - 1] * two_inv;
+        }
+
+        Ok(Self {
+            omegas,
+            omega_invs,
+            size_invs,
+        })
+    }
+}
+
+impl<F: Field> RootTable<F> {
+    /// Returns the log2 of the largest size the table covers.
+    pub fn max_log_n(&self) -> u32 { (self.omegas.len() - 1) as u32 }
+
+    /// Returns the primitive root of unity of order `2^log_n`, if the table
+    /// covers that size.
+    pub fn omega(&self, log_n: u32) -> Option<F> {
+        self.omegas.get(log_n as usize).copied()
+    }
+
+    /// Returns the inverse of [`RootTable::omega`].
+    pub fn omega_inv(&self, log_n: u32) -> Option<F> {
+        self.omega_invs.get(log_n as usize).copied()
+    }
+
+    /// Returns the inverse of `2^log_n`.
+    pub fn size_inv(&self, log_n: u32) -> Option<F> {
+        self.size_invs.get(log_n as usize).copied()
+    }
+
+    /// Returns the root of unity, its inverse and the inverse of the size of
+    /// an FFT of `2^log_n` elements.
+    fn get(&self, log_n: u32) -> EcResult<(F, F, F)> {
+        let i = log_n as usize;
+        if i >= self.omegas.len() {
+            return Err(EcError::Simple(
+                "The root table doesn't cover the size",
+            ));
+        }
+        Ok((self.omegas[i], self.omega_invs[i], self.size_invs[i]))
+    }
+}
+
+/// Performs the FFT of `input` on the CPU if `log_n` is below the
+/// `threshold` and counts it in `counter`. Returns whether it did.
+///
+/// The FFTs are small, hence they are done single-threaded.
+fn cpu_fft_below<F: Field>(
+    threshold: u32, counter: &AtomicUsize, input: &mut [F], omega: &F,
+    log_n: u32,
+) -> bool {
+    if log_n >= threshold {
+        return false;
+    }
+    serial_fft(input, omega, log_n);
+    counter.fetch_add(1, Ordering::Relaxed);
+    true
+}
 
 /// FFT kernel for a single GPU.
 pub struct SingleFftKernel<'a, F>
 where F: Field + GpuName
 {
-    program: Program,
+    program: SharedProgram,
     /// An optional function which will be called at places where it is
     /// possible to abort the FFT calculations. If it returns true, the
     /// calculation will be aborted with an [`EcError::Aborted`].
     maybe_abort: Option<&'a (dyn Fn() -> bool + Send + Sync)>,
-    _phantom: std::marker::PhantomData<F>,
+    /// The twiddle factors of the recently used FFT sizes.
+    twiddle_cache: TwiddleCache<F>,
+    /// FFTs of at most `2^shared_mem_threshold` elements are done entirely in
+    /// local memory, if it's set.
+    shared_mem_threshold: Option<u32>,
+    /// Whether the twiddle factors are checked on the GPU before an FFT.
+    verify_twiddles: bool,
+    /// Whether the radix kernel caches the twiddle factors in local memory.
+    shared_twiddles: bool,
+    /// The GPU memory limit this kernel shares with other kernels.
+    budget: Option<Arc<MemoryBudget>>,
+    /// The number of compute units of the device.
+    compute_units: u32,
+    /// The device the kernel runs on.
+    device_info: DeviceInfo,
+    /// The factor the modelled time is multiplied with, see
+    /// [`SingleFftKernel::calibrate`].
+    time_scale: f64,
+    /// The allocator of the temporary host buffers, the global allocator is
+    /// used if it's `None`.
+    host_allocator: Option<HostAllocator>,
 }
 
 impl<'a, F: Field + GpuName> SingleFftKernel<'a, F> {
@@ -34,22 +764,263 @@ impl<'a, F: Field + GpuName> SingleFftKernel<'a, F> {
     /// computation, without leaving the GPU in a weird state. If that
     /// function returns `true`, execution is aborted.
     pub fn create(
-        program: Program,
+        program: impl Into<SharedProgram>,
         maybe_abort: Option<&'a (dyn Fn() -> bool + Send + Sync)>,
     ) -> EcResult<Self> {
+        let program = program.into();
+        let device_info = device_info_of(&program);
         Ok(SingleFftKernel {
+            compute_units: device_info.compute_units,
+            device_info,
             program,
             maybe_abort,
-            _phantom: Default::default(),
+            twiddle_cache: TwiddleCache::new(),
+            shared_mem_threshold: None,
+            verify_twiddles: false,
+            shared_twiddles: false,
+            budget: None,
+            time_scale: 1.0,
+            host_allocator: None,
         })
     }
 
+    /// Sets the size (as log2 of the number of elements) up to which FFTs are
+    /// done entirely in local memory with a single kernel launch, `None`
+    /// disables it.
+    ///
+    /// Larger FFTs use the iterative radix kernel, which goes through global
+    /// memory after each round. The local memory needs to hold `1.5 * 2^log_n`
+    /// field elements, so the threshold shouldn't exceed what the device
+    /// supports. It's off by default, i.e. all FFTs use the radix kernel.
+    pub fn set_shared_mem_threshold(&mut self, log_n: Option<u32>) {
+        self.shared_mem_threshold = log_n;
+    }
+
+    /// Returns whether an FFT of `2^log_n` elements is done in local memory,
+    /// see [`SingleFftKernel::set_shared_mem_threshold`].
+    fn in_shared_mem(&self, log_n: u32) -> bool {
+        self.shared_mem_threshold
+            .map_or(false, |threshold| log_n <= threshold)
+    }
+
+    /// Enables or disables the check of the twiddle factors.
+    ///
+    /// When enabled, every FFT first checks on the GPU that `omega^(n/2)` is
+    /// `-1`, i.e. that `omega` is a primitive `n`-th root of unity, and fails
+    /// with an [`EcError::Simple`] otherwise. This catches wrong `omega`s as
+    /// well as broken twiddle factors, at the cost of an extra kernel launch.
+    /// It is off by default.
+    pub fn set_verify_twiddles(&mut self, verify: bool) {
+        self.verify_twiddles = verify;
+    }
+
+    /// Makes the rounds of large FFTs cache their twiddle factors in local
+    /// memory, instead of reading them from global memory in every butterfly.
+    ///
+    /// It needs the kernel variant that is generated with
+    /// `SourceBuilder::fft_shared_twiddles(true)`, otherwise the FFTs fail.
+    /// The local memory of a round then needs to hold `1.5 * 2^8` instead of
+    /// `2^8` field elements. It's off by default and doesn't affect FFTs that
+    /// are done entirely in local memory, see
+    /// [`SingleFftKernel::set_shared_mem_threshold`].
+    pub fn set_shared_twiddles(&mut self, shared_twiddles: bool) {
+        self.shared_twiddles = shared_twiddles;
+    }
+
+    /// Returns the information of the device the kernel runs on.
+    pub fn device_info(&self) -> &DeviceInfo { &self.device_info }
+
+    /// Sets the allocator of the temporary host buffers, like the staging
+    /// buffer of [`SingleFftKernel::radix_fft_segmented`].
+    pub fn set_host_allocator(&mut self, allocator: HostAllocator) {
+        self.host_allocator = Some(allocator);
+    }
+
     /// Performs FFT on `input`
     /// * `omega` - Special value `omega` is used for FFT over finite-fields
     /// * `log_n` - Specifies log2 of number of elements
     pub fn radix_fft(
         &mut self, input: &mut [F], omega: &F, log_n: u32,
     ) -> EcResult<()> {
+        self.radix_fft_with_form(input, omega, log_n, InputForm::Montgomery)
+    }
+
+    /// Performs FFT on `input`, whose elements are in the given `form`.
+    ///
+    /// The result is returned in the same form. See [`InputForm`] for the
+    /// cost of non-Montgomery input.
+    pub fn radix_fft_with_form(
+        &mut self, input: &mut [F], omega: &F, log_n: u32, form: InputForm,
+    ) -> EcResult<()> {
+        self.radix_fft_with_form_and_map(
+            input,
+            omega,
+            log_n,
+            form,
+            FftPostMap::None,
+            Coset::None,
+        )
+    }
+
+    /// Performs FFT on `input` and applies `map` to every output.
+    ///
+    /// The result is the same as applying the map to the outputs of
+    /// [`SingleFftKernel::radix_fft`], but without an extra pass over them.
+    pub fn radix_fft_with_map(
+        &mut self, input: &mut [F], omega: &F, log_n: u32, map: FftPostMap<F>,
+    ) -> EcResult<()> {
+        self.radix_fft_with_form_and_map(
+            input,
+            omega,
+            log_n,
+            InputForm::Montgomery,
+            map,
+            Coset::None,
+        )
+    }
+
+    /// Performs the inverse FFT on `input`
+    /// * `omega` - The root of unity of the forward FFT
+    /// * `log_n` - Specifies log2 of number of elements
+    ///
+    /// It's the forward FFT with `omega^-1`, whose outputs are multiplied by
+    /// `n^-1` when they are written, hence it uses the same kernels.
+    pub fn radix_ifft(
+        &mut self, input: &mut [F], omega: &F, log_n: u32,
+    ) -> EcResult<()> {
+        let omega_inv = inverse_omega(omega)?;
+        let n_inv = F::from(1u64 << log_n).inverse().expect("n is non-zero");
+        self.radix_fft_with_map(
+            input,
+            &omega_inv,
+            log_n,
+            FftPostMap::MulConst(n_inv),
+        )
+    }
+
+    /// Performs FFT on `input`, with the root of unity of its size from the
+    /// precomputed `roots`.
+    ///
+    /// A size that isn't covered by the table results in an error.
+    pub fn radix_fft_with_roots(
+        &mut self, input: &mut [F], roots: &RootTable<F>, log_n: u32,
+    ) -> EcResult<()> {
+        let (omega, _, _) = roots.get(log_n)?;
+        self.radix_fft(input, &omega, log_n)
+    }
+
+    /// Performs the inverse FFT on `input`, with the inverse root of unity and
+    /// size from the precomputed `roots`, hence nothing is inverted.
+    ///
+    /// A size that isn't covered by the table results in an error.
+    pub fn radix_ifft_with_roots(
+        &mut self, input: &mut [F], roots: &RootTable<F>, log_n: u32,
+    ) -> EcResult<()> {
+        let (_, omega_inv, n_inv) = roots.get(log_n)?;
+        self.radix_fft_with_map(
+            input,
+            &omega_inv,
+            log_n,
+            FftPostMap::MulConst(n_inv),
+        )
+    }
+
+    /// Performs FFT on `input` over the coset `shift * <omega>`, i.e. it
+    /// evaluates the polynomial at `shift * omega^i`.
+    ///
+    /// The coefficients are multiplied by the powers of `shift` in the first
+    /// round of the FFT, not in a separate pass. A shift of one is the same
+    /// as [`SingleFftKernel::radix_fft`].
+    pub fn radix_fft_coset(
+        &mut self, input: &mut [F], omega: &F, log_n: u32, shift: &F,
+    ) -> EcResult<()> {
+        if *shift == F::ONE {
+            return self.radix_fft(input, omega, log_n);
+        }
+        self.radix_fft_with_form_and_map(
+            input,
+            omega,
+            log_n,
+            InputForm::Montgomery,
+            FftPostMap::None,
+            Coset::Shift(*shift),
+        )
+    }
+
+    /// Performs the inverse FFT on `input` over the coset `shift * <omega>`,
+    /// `omega` is the root of unity of the forward FFT.
+    ///
+    /// It's the inverse of [`SingleFftKernel::radix_fft_coset`], the
+    /// coefficients are multiplied by the powers of `shift^-1` after they
+    /// are scaled by `n^-1`. A shift of one is the same as
+    /// [`SingleFftKernel::radix_ifft`].
+    pub fn radix_ifft_coset(
+        &mut self, input: &mut [F], omega: &F, log_n: u32, shift: &F,
+    ) -> EcResult<()> {
+        if *shift == F::ONE {
+            return self.radix_ifft(input, omega, log_n);
+        }
+        let shift_inv = shift
+            .inverse()
+            .ok_or(EcError::Simple("The coset shift must be non-zero"))?;
+        let omega_inv = inverse_omega(omega)?;
+        let n_inv = F::from(1u64 << log_n).inverse().expect("n is non-zero");
+        self.radix_fft_with_form_and_map(
+            input,
+            &omega_inv,
+            log_n,
+            InputForm::Montgomery,
+            FftPostMap::MulConst(n_inv),
+            Coset::Unshift(shift_inv),
+        )
+    }
+
+    /// Performs FFT on `input`, whose elements are given as bytes.
+    ///
+    /// `field` must describe the field of this kernel, see [`FieldSpec`] for
+    /// the encoding. `input` holds `2^log_n` encodings one after another and
+    /// is overwritten with the encodings of the result, `omega` is a single
+    /// encoding.
+    pub fn radix_fft_bytes(
+        &mut self, field: &FieldSpec, input: &mut [u8], omega: &[u8],
+        log_n: u32,
+    ) -> EcResult<()>
+    where
+        F: CanonicalBytes,
+    {
+        field.check::<F>()?;
+        let omega = match field.decode::<F>(omega)?[..] {
+            [omega] => omega,
+            _ => return Err(EcError::Simple("omega must be a single element")),
+        };
+        let mut values = field.decode::<F>(input)?;
+        check_len(1 << log_n, values.len())?;
+        self.radix_fft(&mut values, &omega, log_n)?;
+        let mut output = Vec::with_capacity(input.len());
+        field.encode(&values, &mut output);
+        input.copy_from_slice(&output);
+        Ok(())
+    }
+
+    /// Performs FFT on `input`, whose elements are in the given `form`, and
+    /// applies `map` to every output before it is converted back. The FFT is
+    /// moved onto a coset as given by `coset`.
+    fn radix_fft_with_form_and_map(
+        &mut self, input: &mut [F], omega: &F, log_n: u32, form: InputForm,
+        map: FftPostMap<F>, coset: Coset<F>,
+    ) -> EcResult<()> {
+        check_len(1 << log_n, input.len())?;
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        let twiddles = self.twiddle_cache.get(omega, log_n);
+        let _reservation = reserve(
+            &self.budget,
+            ((2 << log_n) + twiddles.pq.len() + twiddles.omegas.len() + 1)
+                * std::mem::size_of::<F>(),
+        )?;
+        let in_shared_mem = self.in_shared_mem(log_n);
+        let shared_twiddles = self.shared_twiddles;
+        let (post_map, post_const) = map.encode();
         let closures = program_closures!(|program,
                                           input: &mut [F]|
          -> EcResult<()> {
@@ -58,88 +1029,1310 @@ impl<'a, F: Field + GpuName> SingleFftKernel<'a, F> {
             // the host or the GPU before they are read.
             let mut src_buffer = unsafe { program.create_buffer::<F>(n)? };
             let mut dst_buffer = unsafe { program.create_buffer::<F>(n)? };
-            // The precalculated values pq` and `omegas` are valid for radix
-            // degrees up to `max_deg`
+            program.write_from_buffer(&mut src_buffer, &*input)?;
+            let (elementwise_global, elementwise_local) =
+                elementwise_work_size(n);
+            if form == InputForm::Normal {
+                let kernel = program.create_kernel(
+                    &format!("{}_to_mont", F::name()),
+                    elementwise_global,
+                    elementwise_local,
+                )?;
+                kernel.arg(&src_buffer).arg(&(n as u32)).run()?;
+            }
+            let post_const_buffer =
+                program.create_buffer_from_slice(&[post_const])?;
+            let omegas_buffer =
+                program.create_buffer_from_slice(&twiddles.omegas)?;
+            verify_twiddles!(
+                program,
+                &omegas_buffer,
+                log_n,
+                self.verify_twiddles
+            );
+            let shift_buffer = match coset {
+                Coset::Shift(shift) => {
+                    Some(program.create_buffer_from_slice(&[shift])?)
+                }
+                _ => None,
+            };
+
+            if in_shared_mem {
+                // Small FFTs are done in a single launch in local memory.
+                let local_work_size = 1
+                    << cmp::min(
+                        log_n.saturating_sub(1),
+                        MAX_LOG2_LOCAL_WORK_SIZE,
+                    );
+                let kernel_name = match shift_buffer {
+                    Some(_) => format!("{}_shared_fft_coset", F::name()),
+                    None => format!("{}_shared_fft", F::name()),
+                };
+                let kernel =
+                    program.create_kernel(&kernel_name, 1, local_work_size)?;
+                let kernel = kernel
+                    .arg(&src_buffer)
+                    .arg(&omegas_buffer)
+                    .arg(&LocalBuffer::<F>::new(n + n / 2))
+                    .arg(&log_n)
+                    .arg(&1u32)
+                    .arg(&post_map)
+                    .arg(&post_const_buffer);
+                match &shift_buffer {
+                    Some(shift_buffer) => kernel.arg(shift_buffer).run()?,
+                    None => kernel.run()?,
+                }
+            } else {
+                // The precalculated values pq` and `omegas` are valid for radix
+                // degrees up to `max_deg`
+                let max_deg = cmp::min(MAX_LOG2_RADIX, log_n);
+
+                let pq_buffer =
+                    program.create_buffer_from_slice(&twiddles.pq)?;
+
+                // Specifies log2 of `p`, (http://www.bealto.com/gpu-fft_group-1.html)
+                let mut log_p = 0u32;
+                // Each iteration performs a FFT round
+                while log_p < log_n {
+                    if let Some(maybe_abort) = &self.maybe_abort {
+                        if maybe_abort() {
+                            return Err(EcError::Aborted);
+                        }
+                    }
+
+                    // 1=>radix2, 2=>radix4, 3=>radix8, ...
+                    let deg = cmp::min(max_deg, log_n - log_p);
+
+                    let n = 1u32 << log_n;
+                    // The map is applied when the outputs are written.
+                    let round_post_map = if log_p + deg == log_n {
+                        post_map
+                    } else {
+                        NO_POST_MAP
+                    };
+                    let local_work_size =
+                        1 << cmp::min(deg - 1, MAX_LOG2_LOCAL_WORK_SIZE);
+                    let global_work_size = n >> deg;
+                    // The coset shift is applied when the inputs are loaded
+                    // in the first round.
+                    let round_shift =
+                        shift_buffer.as_ref().filter(|_| log_p == 0);
+                    // The cached twiddle factors are stored behind the
+                    // values in local memory.
+                    let (kernel_name, local_len) = if round_shift.is_some() {
+                        (format!("{}_radix_fft_coset", F::name()), 1 << deg)
+                    } else if shared_twiddles {
+                        (
+                            format!("{}_radix_fft_shared_twiddles", F::name()),
+                            (1 << deg) + (1 << deg >> 1),
+                        )
+                    } else {
+                        (format!("{}_radix_fft", F::name()), 1 << deg)
+                    };
+                    let kernel = program.create_kernel(
+                        &kernel_name,
+                        global_work_size as usize,
+                        local_work_size as usize,
+                    )?;
+                    let kernel = kernel
+                        .arg(&src_buffer)
+                        .arg(&dst_buffer)
+                        .arg(&pq_buffer)
+                        .arg(&omegas_buffer)
+                        .arg(&LocalBuffer::<F>::new(local_len))
+                        .arg(&n)
+                        .arg(&log_p)
+                        .arg(&deg)
+                        .arg(&max_deg)
+                        .arg(&1u32)
+                        .arg(&1u32)
+                        .arg(&round_post_map)
+                        .arg(&post_const_buffer);
+                    match round_shift {
+                        Some(shift_buffer) => kernel.arg(shift_buffer).run()?,
+                        None => kernel.run()?,
+                    }
+
+                    log_p += deg;
+                    std::mem::swap(&mut src_buffer, &mut dst_buffer);
+                }
+            }
+
+            if let Coset::Unshift(shift_inv) = coset {
+                let params_buffer =
+                    program.create_buffer_from_slice(&[F::ONE, shift_inv])?;
+                let kernel = program.create_kernel(
+                    &format!("{}_distribute_powers", F::name()),
+                    elementwise_global,
+                    elementwise_local,
+                )?;
+                kernel
+                    .arg(&src_buffer)
+                    .arg(&params_buffer)
+                    .arg(&(n as u32))
+                    .run()?;
+            }
+
+            if form == InputForm::Normal {
+                let kernel = program.create_kernel(
+                    &format!("{}_from_mont", F::name()),
+                    elementwise_global,
+                    elementwise_local,
+                )?;
+                kernel.arg(&src_buffer).arg(&(n as u32)).run()?;
+            }
+            program.read_into_buffer(&src_buffer, input)?;
+
+            Ok(())
+        });
+
+        run_checked!(self.program, closures, input)?;
+        #[cfg(feature = "metrics")]
+        metrics::record(
+            Operation::Fft,
+            self.program.device_name(),
+            1 << log_n,
+            start.elapsed(),
+        );
+        Ok(())
+    }
+
+    /// Performs FFT on all of the `inputs`, which have `2^log_n` elements
+    /// each and share the same `omega`.
+    ///
+    /// The inputs are packed back to back into a single buffer, hence every
+    /// round is one kernel launch for the whole batch, instead of one launch
+    /// per input. It needs device memory for twice the total size of the
+    /// inputs, a batch that exceeds the memory of the device or the budget is
+    /// split into smaller ones.
+    pub fn radix_fft_batch(
+        &mut self, inputs: &mut [&mut [F]], omega: &F, log_n: u32,
+    ) -> EcResult<()> {
+        self.radix_fft_batch_with_form_and_map(
+            inputs,
+            omega,
+            log_n,
+            InputForm::Montgomery,
+            FftPostMap::None,
+        )
+    }
+
+    /// Performs FFT on all of the `inputs`, see
+    /// [`SingleFftKernel::radix_fft_batch`], whose elements are in the given
+    /// `form`, and applies `map` to every output.
+    fn radix_fft_batch_with_form_and_map(
+        &mut self, inputs: &mut [&mut [F]], omega: &F, log_n: u32,
+        form: InputForm, map: FftPostMap<F>,
+    ) -> EcResult<()> {
+        let n = 1 << log_n;
+        for input in inputs.iter() {
+            check_len(n, input.len())?;
+        }
+        if inputs.is_empty() {
+            return Ok(());
+        }
+        let twiddles = self.twiddle_cache.get(omega, log_n);
+        let twiddles_len = twiddles.pq.len() + twiddles.omegas.len() + 1;
+        let max_count = self.max_batch_len(n, twiddles_len)?;
+        if inputs.len() > max_count {
+            for batch in inputs.chunks_mut(max_count) {
+                self.radix_fft_batch_with_form_and_map(
+                    batch, omega, log_n, form, map,
+                )?;
+            }
+            return Ok(());
+        }
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        let total = inputs.len() * n;
+        let _reservation = reserve(
+            &self.budget,
+            (2 * total + twiddles_len) * std::mem::size_of::<F>(),
+        )?;
+        let (post_map, post_const) = map.encode();
+        let closures = program_closures!(|program,
+                                          inputs: &mut [&mut [F]]|
+         -> EcResult<()> {
+            let count = inputs.len();
+            let mut packed = ScratchVec::from_elem(
+                self.host_allocator.as_ref(),
+                F::ZERO,
+                total,
+            );
+            for (chunk, input) in packed.chunks_mut(n).zip(inputs.iter()) {
+                chunk.copy_from_slice(input);
+            }
+            // All usages are safe as the buffers are initialized from either
+            // the host or the GPU before they are read.
+            let mut src_buffer = unsafe { program.create_buffer::<F>(total)? };
+            let dst_buffer = unsafe { program.create_buffer::<F>(total)? };
+            program.write_from_buffer(&mut src_buffer, &packed)?;
+            let (elementwise_global, elementwise_local) =
+                elementwise_work_size(total);
+            if form == InputForm::Normal {
+                let kernel = program.create_kernel(
+                    &format!("{}_to_mont", F::name()),
+                    elementwise_global,
+                    elementwise_local,
+                )?;
+                kernel.arg(&src_buffer).arg(&(total as u32)).run()?;
+            }
+            let post_const_buffer =
+                program.create_buffer_from_slice(&[post_const])?;
+            let omegas_buffer =
+                program.create_buffer_from_slice(&twiddles.omegas)?;
+            verify_twiddles!(
+                program,
+                &omegas_buffer,
+                log_n,
+                self.verify_twiddles
+            );
+
+            let mut src = &src_buffer;
+            let mut dst = &dst_buffer;
+            batch_fft!(
+                self,
+                program,
+                src,
+                dst,
+                count,
+                log_n,
+                twiddles,
+                &omegas_buffer,
+                post_map,
+                &post_const_buffer
+            );
+
+            if form == InputForm::Normal {
+                let kernel = program.create_kernel(
+                    &format!("{}_from_mont", F::name()),
+                    elementwise_global,
+                    elementwise_local,
+                )?;
+                kernel.arg(src).arg(&(total as u32)).run()?;
+            }
+            program.read_into_buffer(src, &mut packed)?;
+            for (input, chunk) in inputs.iter_mut().zip(packed.chunks(n)) {
+                input.copy_from_slice(chunk);
+            }
+
+            Ok(())
+        });
+
+        run_checked!(self.program, closures, inputs)?;
+        #[cfg(feature = "metrics")]
+        {
+            // Every FFT of the batch is recorded with its share of the time.
+            let elapsed = start.elapsed() / inputs.len() as u32;
+            for _ in 0..inputs.len() {
+                metrics::record(
+                    Operation::Fft,
+                    self.program.device_name(),
+                    n,
+                    elapsed,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns how many FFTs of `n` elements fit into a single batch, next to
+    /// `twiddles_len` twiddle factors.
+    ///
+    /// The batch must fit into the memory of the device and into the budget,
+    /// if there is one. A device that doesn't report its memory is treated
+    /// as unlimited.
+    fn max_batch_len(&self, n: usize, twiddles_len: usize) -> EcResult<usize> {
+        let elem_size = std::mem::size_of::<F>();
+        let mut limit = match usize::try_from(self.device_info.memory) {
+            Ok(0) | Err(_) => usize::MAX,
+            Ok(memory) => memory,
+        };
+        if let Some(budget) = &self.budget {
+            limit = cmp::min(limit, budget.limit());
+        }
+        let max_count = limit.saturating_sub(twiddles_len * elem_size)
+            / (2 * n * elem_size);
+        if max_count == 0 {
+            return Err(EcError::MemoryBudgetExceeded {
+                requested: (2 * n + twiddles_len) * elem_size,
+                limit,
+            });
+        }
+        Ok(max_count)
+    }
+
+    /// Updates the evaluations `prev_evals` of a polynomial, after some of its
+    /// coefficients changed.
+    ///
+    /// `prev_evals` is the FFT of the old coefficients with `omega`. Every
+    /// change consists of the index of a coefficient, its old and its new
+    /// value. As the FFT is linear, the difference of every change times the
+    /// corresponding powers of `omega` is added to the evaluations, which
+    /// takes `O(changes * n)` operations. With more than `log_n` changes, the
+    /// coefficients are recovered with an inverse FFT instead, the changes are
+    /// applied and the FFT is done again.
+    pub fn update_fft(
+        &mut self, prev_evals: &mut [F], changes: &[(usize, F, F)], omega: &F,
+        log_n: u32,
+    ) -> EcResult<()> {
+        let n = 1 << log_n;
+        check_len(n, prev_evals.len())?;
+        if changes.iter().any(|(index, _, _)| *index >= n) {
+            return Err(EcError::Simple("A change is out of range"));
+        }
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        if changes.len() > log_n as usize {
+            self.radix_ifft(prev_evals, omega, log_n)?;
+            for (index, old, new) in changes {
+                prev_evals[*index] += *new - old;
+            }
+            return self.radix_fft(prev_evals, omega, log_n);
+        }
+
+        if let Some(maybe_abort) = &self.maybe_abort {
+            if maybe_abort() {
+                return Err(EcError::Aborted);
+            }
+        }
+        let twiddles = self.twiddle_cache.get(omega, log_n);
+        let _reservation = reserve(
+            &self.budget,
+            (n + 2 * changes.len() + twiddles.omegas.len())
+                * std::mem::size_of::<F>(),
+        )?;
+        let indices: Vec<u32> =
+            changes.iter().map(|(index, _, _)| *index as u32).collect();
+        let deltas: Vec<F> =
+            changes.iter().map(|(_, old, new)| *new - old).collect();
+        let steps: Vec<F> = changes
+            .iter()
+            .map(|(index, _, _)| pow_vartime(omega, [*index as u64]))
+            .collect();
+        let num_threads = div_ceil(n, UPDATE_CHUNK_LEN);
+
+        let closures = program_closures!(|program,
+                                          evals: &mut [F]|
+         -> EcResult<()> {
+            let evals_buffer = program.create_buffer_from_slice(evals)?;
+            let indices_buffer = program.create_buffer_from_slice(&indices)?;
+            let deltas_buffer = program.create_buffer_from_slice(&deltas)?;
+            let steps_buffer = program.create_buffer_from_slice(&steps)?;
+            let omegas_buffer =
+                program.create_buffer_from_slice(&twiddles.omegas)?;
+            verify_twiddles!(
+                program,
+                &omegas_buffer,
+                log_n,
+                self.verify_twiddles
+            );
+
+            let (global_work_size, local_work_size) =
+                elementwise_work_size(num_threads);
+            let kernel = program.create_kernel(
+                &format!("{}_fft_update", F::name()),
+                global_work_size,
+                local_work_size,
+            )?;
+            kernel
+                .arg(&evals_buffer)
+                .arg(&indices_buffer)
+                .arg(&deltas_buffer)
+                .arg(&steps_buffer)
+                .arg(&omegas_buffer)
+                .arg(&(changes.len() as u32))
+                .arg(&(n as u32))
+                .arg(&(UPDATE_CHUNK_LEN as u32))
+                .run()?;
+
+            program.read_into_buffer(&evals_buffer, evals)?;
+
+            Ok(())
+        });
+
+        run_checked!(self.program, closures, prev_evals)
+    }
+
+    /// Performs FFT on the logical array that is formed by concatenating the
+    /// `segments`, without concatenating them on the host.
+    ///
+    /// The segments may have any length, but together they need to have
+    /// `2^log_n` elements. They are transferred in chunks of a fixed size
+    /// through a small staging buffer, which is the only additional host
+    /// memory that is needed.
+    pub fn radix_fft_segmented(
+        &mut self, segments: &mut [&mut [F]], omega: &F, log_n: u32,
+    ) -> EcResult<()> {
+        let n = 1 << log_n;
+        check_len(
+            n,
+            segments.iter().map(|segment| segment.len()).sum::<usize>(),
+        )?;
+        let transfer_len = cmp::min(n, SEGMENT_TRANSFER_LEN);
+        let twiddles = self.twiddle_cache.get(omega, log_n);
+        let _reservation = reserve(
+            &self.budget,
+            (2 * n + transfer_len + twiddles.pq.len() + twiddles.omegas.len())
+                * std::mem::size_of::<F>(),
+        )?;
+        let closures = program_closures!(|program,
+                                          segments: &mut [&mut [F]]|
+         -> EcResult<()> {
+            let mut staging = ScratchVec::from_elem(
+                self.host_allocator.as_ref(),
+                F::ZERO,
+                transfer_len,
+            );
+            // All usages are safe as the buffers are initialized from either
+            // the host or the GPU before they are read.
+            let mut staging_buffer =
+                unsafe { program.create_buffer::<F>(transfer_len)? };
+            let mut src_buffer = unsafe { program.create_buffer::<F>(n)? };
+            let mut dst_buffer = unsafe { program.create_buffer::<F>(n)? };
+
+            // The chunks are copied into place on the GPU, as buffers can
+            // only be written as a whole.
+            let mut offset = 0;
+            while offset < n {
+                let len = cmp::min(transfer_len, n - offset);
+                gather_segments(segments, offset, &mut staging[..len]);
+                program.write_from_buffer(&mut staging_buffer, &staging)?;
+                let (global_work_size, local_work_size) =
+                    elementwise_work_size(len);
+                let kernel = program.create_kernel(
+                    &format!("{}_copy_to_offset", F::name()),
+                    global_work_size,
+                    local_work_size,
+                )?;
+                kernel
+                    .arg(&staging_buffer)
+                    .arg(&src_buffer)
+                    .arg(&(offset as u32))
+                    .arg(&(len as u32))
+                    .run()?;
+                offset += len;
+            }
+
             let max_deg = cmp::min(MAX_LOG2_RADIX, log_n);
+            let pq_buffer = program.create_buffer_from_slice(&twiddles.pq)?;
+            let omegas_buffer =
+                program.create_buffer_from_slice(&twiddles.omegas)?;
+            verify_twiddles!(
+                program,
+                &omegas_buffer,
+                log_n,
+                self.verify_twiddles
+            );
+
+            let mut log_p = 0u32;
+            while log_p < log_n {
+                if let Some(maybe_abort) = &self.maybe_abort {
+                    if maybe_abort() {
+                        return Err(EcError::Aborted);
+                    }
+                }
+
+                let deg = cmp::min(max_deg, log_n - log_p);
+                let local_work_size =
+                    1 << cmp::min(deg - 1, MAX_LOG2_LOCAL_WORK_SIZE);
+                let global_work_size = n >> deg;
+                let kernel = program.create_kernel(
+                    &format!("{}_radix_fft", F::name()),
+                    global_work_size,
+                    local_work_size,
+                )?;
+                kernel
+                    .arg(&src_buffer)
+                    .arg(&dst_buffer)
+                    .arg(&pq_buffer)
+                    .arg(&omegas_buffer)
+                    .arg(&LocalBuffer::<F>::new(1 << deg))
+                    .arg(&(n as u32))
+                    .arg(&log_p)
+                    .arg(&deg)
+                    .arg(&max_deg)
+                    .arg(&1u32)
+                    .arg(&1u32)
+                    .arg(&NO_POST_MAP)
+                    .arg(&omegas_buffer)
+                    .run()?;
+
+                log_p += deg;
+                std::mem::swap(&mut src_buffer, &mut dst_buffer);
+            }
+
+            let mut offset = 0;
+            while offset < n {
+                let len = cmp::min(transfer_len, n - offset);
+                let (global_work_size, local_work_size) =
+                    elementwise_work_size(len);
+                let kernel = program.create_kernel(
+                    &format!("{}_copy_from_offset", F::name()),
+                    global_work_size,
+                    local_work_size,
+                )?;
+                kernel
+                    .arg(&src_buffer)
+                    .arg(&staging_buffer)
+                    .arg(&(offset as u32))
+                    .arg(&(len as u32))
+                    .run()?;
+                program.read_into_buffer(&staging_buffer, &mut staging)?;
+                scatter_segments(segments, offset, &staging[..len]);
+                offset += len;
+            }
+
+            Ok(())
+        });
+
+        run_checked!(self.program, closures, segments)
+    }
+
+    /// Performs FFT on the elements `buffer[offset + i * stride]`, for `i` in
+    /// `0..2^log_n`.
+    ///
+    /// The elements are gathered and scattered by the kernels, hence
+    /// interleaved data doesn't need to be copied into a packed buffer first.
+    /// The other elements of `buffer` are left untouched. Only the part of
+    /// `buffer` that is covered by the view is transferred to the GPU.
+    pub fn radix_fft_strided(
+        &mut self, buffer: &mut [F], offset: usize, stride: usize, omega: &F,
+        log_n: u32,
+    ) -> EcResult<()> {
+        if stride == 0 {
+            return Err(EcError::Simple("The stride must not be zero"));
+        }
+        let n = 1 << log_n;
+        let end = offset + (n - 1) * stride + 1;
+        if end > buffer.len() {
+            return Err(EcError::InvalidLength(format!(
+                "the view needs {} elements, but the buffer has {}",
+                end,
+                buffer.len()
+            )));
+        }
+        let view = &mut buffer[offset..offset + (n - 1) * stride + 1];
+        let twiddles = self.twiddle_cache.get(omega, log_n);
+        let _reservation = reserve(
+            &self.budget,
+            (view.len()
+                + if log_n > MAX_LOG2_RADIX { 2 * n } else { 0 }
+                + twiddles.pq.len()
+                + twiddles.omegas.len())
+                * std::mem::size_of::<F>(),
+        )?;
+        let stride = stride as u32;
+        let closures = program_closures!(|program,
+                                          view: &mut [F]|
+         -> EcResult<()> {
+            let data_buffer = program.create_buffer_from_slice(view)?;
+            let omegas_buffer =
+                program.create_buffer_from_slice(&twiddles.omegas)?;
+            verify_twiddles!(
+                program,
+                &omegas_buffer,
+                log_n,
+                self.verify_twiddles
+            );
+
+            if log_n <= MAX_LOG2_RADIX {
+                // A single round would read and write the same buffer from
+                // different work groups. In local memory all elements are
+                // loaded before any is stored, hence it can be done in place.
+                let local_work_size = 1
+                    << cmp::min(
+                        log_n.saturating_sub(1),
+                        MAX_LOG2_LOCAL_WORK_SIZE,
+                    );
+                let kernel = program.create_kernel(
+                    &format!("{}_shared_fft", F::name()),
+                    1,
+                    local_work_size,
+                )?;
+                kernel
+                    .arg(&data_buffer)
+                    .arg(&omegas_buffer)
+                    .arg(&LocalBuffer::<F>::new(n + n / 2))
+                    .arg(&log_n)
+                    .arg(&stride)
+                    .arg(&NO_POST_MAP)
+                    .arg(&omegas_buffer)
+                    .run()?;
+            } else {
+                // The first round gathers from the strided data, the last one
+                // scatters into it. The rounds in between use packed buffers.
+                // All usages are safe as the buffers are initialized by the
+                // GPU before they are read.
+                let mut src_buffer = unsafe { program.create_buffer::<F>(n)? };
+                let mut dst_buffer = unsafe { program.create_buffer::<F>(n)? };
+                let max_deg = MAX_LOG2_RADIX;
+                let pq_buffer =
+                    program.create_buffer_from_slice(&twiddles.pq)?;
+
+                let mut log_p = 0u32;
+                while log_p < log_n {
+                    if let Some(maybe_abort) = &self.maybe_abort {
+                        if maybe_abort() {
+                            return Err(EcError::Aborted);
+                        }
+                    }
+
+                    let deg = cmp::min(max_deg, log_n - log_p);
+                    let (x, x_stride) = if log_p == 0 {
+                        (&data_buffer, stride)
+                    } else {
+                        (&src_buffer, 1)
+                    };
+                    let (y, y_stride) = if log_p + deg == log_n {
+                        (&data_buffer, stride)
+                    } else {
+                        (&dst_buffer, 1)
+                    };
+
+                    let local_work_size =
+                        1 << cmp::min(deg - 1, MAX_LOG2_LOCAL_WORK_SIZE);
+                    let global_work_size = n >> deg;
+                    let kernel = program.create_kernel(
+                        &format!("{}_radix_fft", F::name()),
+                        global_work_size,
+                        local_work_size,
+                    )?;
+                    kernel
+                        .arg(x)
+                        .arg(y)
+                        .arg(&pq_buffer)
+                        .arg(&omegas_buffer)
+                        .arg(&LocalBuffer::<F>::new(1 << deg))
+                        .arg(&(n as u32))
+                        .arg(&log_p)
+                        .arg(&deg)
+                        .arg(&max_deg)
+                        .arg(&x_stride)
+                        .arg(&y_stride)
+                        .arg(&NO_POST_MAP)
+                        .arg(&omegas_buffer)
+                        .run()?;
+
+                    log_p += deg;
+                    std::mem::swap(&mut src_buffer, &mut dst_buffer);
+                }
+            }
+
+            program.read_into_buffer(&data_buffer, view)?;
+
+            Ok(())
+        });
+
+        run_checked!(self.program, closures, view)
+    }
+
+    /// Performs a 2D FFT of the `rows` x `cols` `matrix`, which is stored row
+    /// by row.
+    ///
+    /// First every row is transformed with `omega_row`, a primitive `cols`-th
+    /// root of unity, then every column with `omega_col`, a primitive
+    /// `rows`-th root of unity. The matrix is transposed on the GPU in
+    /// between, so that the columns are contiguous as well. Both dimensions
+    /// must be powers of two.
+    pub fn fft_2d(
+        &mut self, matrix: &mut [F], rows: usize, cols: usize, omega_row: &F,
+        omega_col: &F,
+    ) -> EcResult<()> {
+        check_len(rows * cols, matrix.len())?;
+        if !rows.is_power_of_two() || !cols.is_power_of_two() {
+            return Err(EcError::Simple(
+                "The dimensions of the matrix must be powers of two",
+            ));
+        }
+        let n = rows * cols;
+        let log_rows = rows.trailing_zeros();
+        let log_cols = cols.trailing_zeros();
+        let row_twiddles = self.twiddle_cache.get(omega_row, log_cols);
+        let col_twiddles = self.twiddle_cache.get(omega_col, log_rows);
+        let _reservation = reserve(
+            &self.budget,
+            (3 * n
+                + row_twiddles.pq.len()
+                + row_twiddles.omegas.len()
+                + col_twiddles.pq.len()
+                + col_twiddles.omegas.len())
+                * std::mem::size_of::<F>(),
+        )?;
+
+        let closures = program_closures!(|program,
+                                          matrix: &mut [F]|
+         -> EcResult<()> {
+            let matrix_buffer = program.create_buffer_from_slice(matrix)?;
+            // It is safe as the GPU will initialize that buffer
+            let transposed_buffer = unsafe { program.create_buffer::<F>(n)? };
+
+            row_ffts!(
+                self,
+                program,
+                &matrix_buffer,
+                rows,
+                log_cols,
+                &row_twiddles
+            );
+            transpose_tiled!(
+                program,
+                &matrix_buffer,
+                &transposed_buffer,
+                rows,
+                cols
+            );
+
+            // The columns are the rows of the transposed matrix.
+            row_ffts!(
+                self,
+                program,
+                &transposed_buffer,
+                cols,
+                log_rows,
+                &col_twiddles
+            );
+            transpose_tiled!(
+                program,
+                &transposed_buffer,
+                &matrix_buffer,
+                cols,
+                rows
+            );
+
+            program.read_into_buffer(&matrix_buffer, matrix)?;
+
+            Ok(())
+        });
+
+        run_checked!(self.program, closures, matrix)
+    }
+
+    /// Returns `FFT(a) - FFT(b)`, with `omega` as root of unity.
+    ///
+    /// As the FFT is linear, this is the FFT of `a - b`. The subtraction is
+    /// done on the GPU, so that only a single FFT is needed and neither
+    /// `FFT(a)` nor `FFT(b)` is ever materialized. Both inputs must have
+    /// `2^log_n` elements.
+    pub fn fft_difference(
+        &mut self, a: &[F], b: &[F], omega: &F, log_n: u32,
+    ) -> EcResult<Vec<F>> {
+        let (difference, _) =
+            self.fft_difference_or_equal(a, b, omega, log_n, true)?;
+        Ok(difference)
+    }
+
+    /// Returns whether the FFTs of `a` and `b` with `omega` are equal, i.e.
+    /// whether the polynomials with the coefficients `a` and `b` agree on the
+    /// domain.
+    ///
+    /// It computes [`SingleFftKernel::fft_difference`] and checks on the GPU
+    /// whether all of its elements are zero, only a single flag is read back.
+    pub fn fft_equal_on_domain(
+        &mut self, a: &[F], b: &[F], omega: &F, log_n: u32,
+    ) -> EcResult<bool> {
+        let (_, equal) =
+            self.fft_difference_or_equal(a, b, omega, log_n, false)?;
+        Ok(equal)
+    }
+
+    /// Computes the FFT of `a - b` on the GPU. Returns it if `read_values`
+    /// is set, else an empty vector, and whether all of its elements are zero.
+    fn fft_difference_or_equal(
+        &mut self, a: &[F], b: &[F], omega: &F, log_n: u32, read_values: bool,
+    ) -> EcResult<(Vec<F>, bool)> {
+        let n = 1 << log_n;
+        check_len(n, a.len())?;
+        check_len(n, b.len())?;
+        let twiddles = self.twiddle_cache.get(omega, log_n);
+        let _reservation = reserve(
+            &self.budget,
+            (4 * n + twiddles.pq.len() + twiddles.omegas.len())
+                * std::mem::size_of::<F>(),
+        )?;
+        let (elementwise_global, elementwise_local) = elementwise_work_size(n);
+
+        let closures = program_closures!(|program,
+                                          _arg|
+         -> EcResult<(Vec<F>, bool)> {
+            let a_buffer = program.create_buffer_from_slice(a)?;
+            let b_buffer = program.create_buffer_from_slice(b)?;
+            program
+                .create_kernel(
+                    &format!("{}_sub_elementwise", F::name()),
+                    elementwise_global,
+                    elementwise_local,
+                )?
+                .arg(&a_buffer)
+                .arg(&b_buffer)
+                .arg(&(n as u32))
+                .run()?;
+            drop(b_buffer);
+
+            row_ffts!(self, program, &a_buffer, 1, log_n, &twiddles);
+
+            let nonzero_buffer = program.create_buffer_from_slice(&[0u32])?;
+            program
+                .create_kernel(
+                    &format!("{}_any_nonzero", F::name()),
+                    elementwise_global,
+                    elementwise_local,
+                )?
+                .arg(&a_buffer)
+                .arg(&(n as u32))
+                .arg(&nonzero_buffer)
+                .run()?;
+            let mut nonzero = [0u32];
+            program.read_into_buffer(&nonzero_buffer, &mut nonzero)?;
+
+            let mut difference = Vec::new();
+            if read_values {
+                difference = vec![F::ZERO; n];
+                program.read_into_buffer(&a_buffer, &mut difference)?;
+            }
+
+            Ok((difference, nonzero[0] == 0))
+        });
+
+        run_checked!(self.program, closures, ())
+    }
+
+    /// Computes `a + r * b` elementwise into `a`, the folding step of
+    /// folding schemes.
+    ///
+    /// Both vectors are uploaded, combined in a single launch and only `a` is
+    /// read back. Inputs of different lengths result in an error.
+    pub fn fold(&mut self, a: &mut [F], b: &[F], r: F) -> EcResult<()> {
+        check_len(a.len(), b.len())?;
+        if a.is_empty() {
+            return Ok(());
+        }
+        let n = a.len();
+        let _reservation =
+            reserve(&self.budget, (2 * n + 1) * std::mem::size_of::<F>())?;
+        let (elementwise_global, elementwise_local) = elementwise_work_size(n);
+
+        let closures =
+            program_closures!(|program, a: &mut [F]| -> EcResult<()> {
+                let a_buffer = program.create_buffer_from_slice(&*a)?;
+                let b_buffer = program.create_buffer_from_slice(b)?;
+                let r_buffer = program.create_buffer_from_slice(&[r])?;
+                program
+                    .create_kernel(
+                        &format!("{}_fold", F::name()),
+                        elementwise_global,
+                        elementwise_local,
+                    )?
+                    .arg(&a_buffer)
+                    .arg(&b_buffer)
+                    .arg(&r_buffer)
+                    .arg(&(n as u32))
+                    .run()?;
+                program.read_into_buffer(&a_buffer, a)?;
+                Ok(())
+            });
+
+        run_checked!(self.program, closures, a)
+    }
+
+    /// Computes the coefficients of the quotient of the numerator and the
+    /// vanishing polynomial `Z_H` of the `domain`.
+    ///
+    /// The numerator is evaluated on the coset `coset_gen * <omega>` with a
+    /// coset FFT, divided by `Z_H` on that coset and interpolated again with
+    /// a coset inverse FFT. The values stay on the GPU in between, only the
+    /// quotient is read back. The coset must be disjoint from `H`, i.e.
+    /// `Z_H` must not vanish on it. The `numerator_coeffs` must not exceed
+    /// `2^log_n` elements, missing ones are treated as zero. If the
+    /// numerator is divisible by `Z_H`, the degree of the result is its
+    /// degree minus `|H|`, else the result is not meaningful.
+    pub fn compute_quotient(
+        &mut self, numerator_coeffs: &[F], domain: &QuotientDomain<F>,
+        coset_gen: &F,
+    ) -> EcResult<Vec<F>> {
+        let QuotientDomain {
+            omega,
+            log_n,
+            log_h,
+        } = *domain;
+        let n = 1 << log_n;
+        if log_h > log_n {
+            return Err(EcError::Simple(
+                "H must not be larger than the domain",
+            ));
+        }
+        if numerator_coeffs.len() > n {
+            return Err(EcError::InvalidLength(format!(
+                "the numerator has {} coefficients, but the domain only {}",
+                numerator_coeffs.len(),
+                n
+            )));
+        }
+
+        // `Z_H(g * omega^i) = g^|H| * omega^(i * |H|) - 1` only depends on `i`
+        // modulo the order of `omega^|H|`.
+        let period = 1 << (log_n - log_h);
+        let coset_pow = coset_gen.pow([1u64 << log_h]);
+        let omega_pow = omega.pow([1u64 << log_h]);
+        let mut vanishing_inv = Vec::with_capacity(period);
+        let mut power = coset_pow;
+        for _ in 0..period {
+            match (power - F::ONE).inverse() {
+                Some(inv) => vanishing_inv.push(inv),
+                None => {
+                    return Err(EcError::Simple(
+                        "The vanishing polynomial is zero on the coset",
+                    ))
+                }
+            }
+            power *= omega_pow;
+        }
+
+        let omega_inv = inverse_omega(&omega)?;
+        let coset_gen_inv = coset_gen
+            .inverse()
+            .ok_or(EcError::Simple("The coset generator must be non-zero"))?;
+        let n_inv = F::from(n as u64).inverse().expect("n is non-zero");
+        let twiddles = self.twiddle_cache.get(&omega, log_n);
+        let inv_twiddles = self.twiddle_cache.get(&omega_inv, log_n);
+        let _reservation = reserve(
+            &self.budget,
+            (3 * n
+                + 2 * twiddles.pq.len()
+                + 2 * twiddles.omegas.len()
+                + period)
+                * std::mem::size_of::<F>(),
+        )?;
+        let (elementwise_global, elementwise_local) = elementwise_work_size(n);
+
+        let closures = program_closures!(|program, _arg| -> EcResult<Vec<F>> {
+            // The buffer is safe as it's fully written before it is read.
+            let mut buffer = unsafe { program.create_buffer::<F>(n)? };
+            let mut coeffs = vec![F::ZERO; n];
+            coeffs[..numerator_coeffs.len()].copy_from_slice(numerator_coeffs);
+            program.write_from_buffer(&mut buffer, &coeffs)?;
+
+            let distribute_powers = |c: F, g: F| -> EcResult<()> {
+                let params_buffer =
+                    program.create_buffer_from_slice(&[c, g])?;
+                program
+                    .create_kernel(
+                        &format!("{}_distribute_powers", F::name()),
+                        elementwise_global,
+                        elementwise_local,
+                    )?
+                    .arg(&buffer)
+                    .arg(&params_buffer)
+                    .arg(&(n as u32))
+                    .run()?;
+                Ok(())
+            };
+
+            // Coset FFT.
+            distribute_powers(F::ONE, *coset_gen)?;
+            row_ffts!(self, program, &buffer, 1, log_n, &twiddles);
+
+            let vanishing_buffer =
+                program.create_buffer_from_slice(&vanishing_inv)?;
+            program
+                .create_kernel(
+                    &format!("{}_mul_periodic", F::name()),
+                    elementwise_global,
+                    elementwise_local,
+                )?
+                .arg(&buffer)
+                .arg(&vanishing_buffer)
+                .arg(&(period as u32))
+                .arg(&(n as u32))
+                .run()?;
+
+            // Coset inverse FFT.
+            row_ffts!(self, program, &buffer, 1, log_n, &inv_twiddles);
+            distribute_powers(n_inv, coset_gen_inv)?;
+
+            program.read_into_buffer(&buffer, &mut coeffs)?;
+            Ok(coeffs)
+        });
+
+        run_checked!(self.program, closures, ())
+    }
+
+    /// Sets the GPU memory limit this kernel shares with other kernels.
+    pub fn set_budget(&mut self, budget: Arc<MemoryBudget>) {
+        self.budget = Some(budget);
+    }
 
-            // Precalculate:
-            // [omega^(0/(2^(deg-1))), omega^(1/(2^(deg-1))), ...,
-            // omega^((2^(deg-1)-1)/(2^(deg-1)))]
-            let mut pq = vec![F::ZERO; 1 << max_deg >> 1];
-            let twiddle = pow_vartime(omega, [(n >> max_deg) as u64]);
-            pq[0] = F::ONE;
-            if max_deg > 1 {
-                pq[1] = twiddle;
-                for i in 2..(1 << max_deg >> 1) {
-                    pq[i] = pq[i - 1];
-                    pq[i].mul_assign(&twiddle);
-                }
+    /// Returns a rough estimate of the time an FFT of `2^log_n` elements
+    /// takes on this device.
+    ///
+    /// It is based on a simple cost model of the butterflies, the memory
+    /// accesses of each round and the data transfers, it's meant for
+    /// scheduling and not for exact predictions. Call
+    /// [`SingleFftKernel::calibrate`] to adjust it to the actual device.
+    pub fn estimate_time(&self, log_n: u32) -> Duration {
+        Duration::from_secs_f64(self.model_secs(log_n) * self.time_scale)
+    }
+
+    /// Returns the modelled seconds of an FFT of `2^log_n` elements.
+    fn model_secs(&self, log_n: u32) -> f64 {
+        let n = 1usize << log_n;
+        let elem_size = std::mem::size_of::<F>();
+        // Every round reads and writes all elements from global memory, except
+        // for small FFTs, which are done in local memory at once.
+        let rounds = if self.in_shared_mem(log_n) {
+            1
+        } else {
+            div_ceil(log_n as usize, MAX_LOG2_RADIX as usize)
+        };
+        // One multiplication per butterfly and one per element for the
+        // twiddle factors.
+        let muls = (n / 2) as f64 * log_n as f64 + n as f64;
+        estimate::compute_secs::<F>(muls, self.compute_units)
+            + estimate::device_memory_secs(rounds * 2 * n * elem_size)
+            + estimate::transfer_secs(2 * n * elem_size)
+    }
+
+    /// Calibrates [`SingleFftKernel::estimate_time`] by timing a small FFT on
+    /// the device.
+    pub fn calibrate(&mut self) -> EcResult<()>
+    where F: FftField {
+        let n = 1 << CALIBRATION_LOG_N;
+        let omega = F::get_root_of_unity(n).expect("field supports the size");
+        let mut input = vec![F::one(); n as usize];
+        // The first run includes one-time costs, like the twiddle factors,
+        // hence it's not timed.
+        self.radix_fft(&mut input, &omega, CALIBRATION_LOG_N)?;
+        let start = Instant::now();
+        self.radix_fft(&mut input, &omega, CALIBRATION_LOG_N)?;
+        let measured = start.elapsed().as_secs_f64();
+        self.time_scale = measured / self.model_secs(CALIBRATION_LOG_N);
+        Ok(())
+    }
+
+    /// Precalculates the twiddle factors for the given size and `omega` and
+    /// pins them in the cache, so that they are never evicted.
+    pub fn prepare_domain(&mut self, omega: &F, log_n: u32) {
+        self.twiddle_cache.pin(omega, log_n);
+    }
+
+    /// Returns how many times twiddle factors were calculated, because they
+    /// were not cached.
+    pub fn twiddle_generations(&self) -> usize {
+        self.twiddle_cache.generations
+    }
+
+    /// Evaluates all `polys` at the point `z`.
+    ///
+    /// The polynomials are given by their coefficients, lowest degree first.
+    /// They are split into chunks of `EVAL_CHUNK_LEN` coefficients, which are
+    /// all evaluated within a single kernel launch. The powers of `z` that
+    /// shift the chunks into place are shared between all polynomials.
+    pub fn batch_evaluate_at(
+        &mut self, polys: &[&[F]], z: F,
+    ) -> EcResult<Vec<F>> {
+        if let Some(maybe_abort) = &self.maybe_abort {
+            if maybe_abort() {
+                return Err(EcError::Aborted);
             }
-            let pq_buffer = program.create_buffer_from_slice(&pq)?;
+        }
+
+        let coeffs: Vec<F> =
+            polys.iter().flat_map(|p| p.iter()).copied().collect();
+        if coeffs.is_empty() {
+            return Ok(vec![F::ZERO; polys.len()]);
+        }
 
-            // Precalculate [omega, omega^2, omega^4, omega^8, ...,
-            // omega^(2^31)]
-            let mut omegas = vec![F::ZERO; 32];
-            omegas[0] = *omega;
-            for i in 1..LOG2_MAX_ELEMENTS {
-                omegas[i] = pow_vartime(&omegas[i - 1], [2u64]);
+        // Every chunk is described by its start, its end and its power.
+        let mut chunks = Vec::new();
+        // The polynomial every chunk belongs to.
+        let mut chunk_polys = Vec::new();
+        let mut max_chunks = 0;
+        let mut offset = 0;
+        for (i, poly) in polys.iter().enumerate() {
+            let num_chunks = div_ceil(poly.len(), EVAL_CHUNK_LEN);
+            for k in 0..num_chunks {
+                let start = offset + k * EVAL_CHUNK_LEN;
+                let end = cmp::min(start + EVAL_CHUNK_LEN, offset + poly.len());
+                chunks.extend([start as u32, end as u32, k as u32]);
+                chunk_polys.push(i);
             }
-            let omegas_buffer = program.create_buffer_from_slice(&omegas)?;
+            max_chunks = cmp::max(max_chunks, num_chunks);
+            offset += poly.len();
+        }
+        let num_chunks = chunk_polys.len();
+        let _reservation = reserve(
+            &self.budget,
+            (coeffs.len() + max_chunks + 1 + num_chunks)
+                * std::mem::size_of::<F>()
+                + chunks.len() * std::mem::size_of::<u32>(),
+        )?;
 
-            program.write_from_buffer(&mut src_buffer, &*input)?;
-            // Specifies log2 of `p`, (http://www.bealto.com/gpu-fft_group-1.html)
-            let mut log_p = 0u32;
-            // Each iteration performs a FFT round
-            while log_p < log_n {
-                if let Some(maybe_abort) = &self.maybe_abort {
-                    if maybe_abort() {
-                        return Err(EcError::Aborted);
-                    }
-                }
+        // [1, z^EVAL_CHUNK_LEN, z^(2 * EVAL_CHUNK_LEN), ...]
+        let step = pow_vartime(&z, [EVAL_CHUNK_LEN as u64]);
+        let mut powers = Vec::with_capacity(max_chunks);
+        let mut power = F::ONE;
+        for _ in 0..max_chunks {
+            powers.push(power);
+            power *= step;
+        }
 
-                // 1=>radix2, 2=>radix4, 3=>radix8, ...
-                let deg = cmp::min(max_deg, log_n - log_p);
+        let closures = program_closures!(|program, _arg| -> EcResult<Vec<F>> {
+            let coeffs_buffer = program.create_buffer_from_slice(&coeffs)?;
+            let chunks_buffer = program.create_buffer_from_slice(&chunks)?;
+            let powers_buffer = program.create_buffer_from_slice(&powers)?;
+            let z_buffer = program.create_buffer_from_slice(&[z])?;
+            // It is safe as the GPU will initialize that buffer
+            let results_buffer =
+                unsafe { program.create_buffer::<F>(num_chunks)? };
 
-                let n = 1u32 << log_n;
-                let local_work_size =
-                    1 << cmp::min(deg - 1, MAX_LOG2_LOCAL_WORK_SIZE);
-                let global_work_size = n >> deg;
-                let kernel_name = format!("{}_radix_fft", F::name());
-                let kernel = program.create_kernel(
-                    &kernel_name,
-                    global_work_size as usize,
-                    local_work_size as usize,
-                )?;
-                kernel
-                    .arg(&src_buffer)
-                    .arg(&dst_buffer)
-                    .arg(&pq_buffer)
-                    .arg(&omegas_buffer)
-                    .arg(&LocalBuffer::<F>::new(1 << deg))
-                    .arg(&n)
-                    .arg(&log_p)
-                    .arg(&deg)
-                    .arg(&max_deg)
-                    .run()?;
+            let (global_work_size, local_work_size) =
+                elementwise_work_size(num_chunks);
+            let kernel = program.create_kernel(
+                &format!("{}_batch_eval", F::name()),
+                global_work_size,
+                local_work_size,
+            )?;
+            kernel
+                .arg(&coeffs_buffer)
+                .arg(&chunks_buffer)
+                .arg(&powers_buffer)
+                .arg(&z_buffer)
+                .arg(&results_buffer)
+                .arg(&(num_chunks as u32))
+                .run()?;
 
-                log_p += deg;
-                std::mem::swap(&mut src_buffer, &mut dst_buffer);
+            let mut results = vec![F::ZERO; num_chunks];
+            program.read_into_buffer(&results_buffer, &mut results)?;
+
+            Ok(results)
+        });
+
+        let partials = run_checked!(self.program, closures, ())?;
+        let mut evaluations = vec![F::ZERO; polys.len()];
+        for (partial, poly) in partials.into_iter().zip(chunk_polys) {
+            evaluations[poly] += partial;
+        }
+        Ok(evaluations)
+    }
+
+    /// Computes only the outputs at the `output_indices` of the FFT of
+    /// `coeffs`.
+    ///
+    /// The result is the same as gathering the outputs of
+    /// [`SingleFftKernel::radix_fft`] at the given indices, in the same
+    /// order, duplicates are allowed. With at most `log_n` indices, the
+    /// polynomial is evaluated at `omega^index` directly, which takes
+    /// `O(indices * n)` operations instead of a full FFT. The coefficients
+    /// are split into chunks of `EVAL_CHUNK_LEN`, so that all evaluations are
+    /// done in a single kernel launch. With more indices, the full FFT is
+    /// calculated and gathered.
+    pub fn partial_fft(
+        &mut self, coeffs: &[F], omega: &F, log_n: u32,
+        output_indices: &[usize],
+    ) -> EcResult<Vec<F>> {
+        let n = 1 << log_n;
+        check_len(n, coeffs.len())?;
+        if output_indices.iter().any(|index| *index >= n) {
+            return Err(EcError::Simple("An output index is out of range"));
+        }
+        if output_indices.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        if output_indices.len() > log_n as usize {
+            let mut values = coeffs.to_vec();
+            self.radix_fft(&mut values, omega, log_n)?;
+            return Ok(output_indices.iter().map(|i| values[*i]).collect());
+        }
+
+        if let Some(maybe_abort) = &self.maybe_abort {
+            if maybe_abort() {
+                return Err(EcError::Aborted);
             }
+        }
+        let num_points = output_indices.len();
+        let num_chunks = div_ceil(n, EVAL_CHUNK_LEN);
+        let _reservation = reserve(
+            &self.budget,
+            (n + num_points + 2 * num_points * num_chunks)
+                * std::mem::size_of::<F>(),
+        )?;
 
-            program.read_into_buffer(&src_buffer, input)?;
+        // Every point `x = omega^index` with its powers `x^(k *
+        // EVAL_CHUNK_LEN)`.
+        let points = output_indices
+            .iter()
+            .map(|index| pow_vartime(omega, [*index as u64]))
+            .collect::<Vec<_>>();
+        let mut shifts = Vec::with_capacity(num_points * num_chunks);
+        for point in &points {
+            let step = pow_vartime(point, [EVAL_CHUNK_LEN as u64]);
+            let mut shift = F::ONE;
+            for _ in 0..num_chunks {
+                shifts.push(shift);
+                shift *= step;
+            }
+        }
 
-            Ok(())
+        let closures = program_closures!(|program, _arg| -> EcResult<Vec<F>> {
+            let coeffs_buffer = program.create_buffer_from_slice(coeffs)?;
+            let points_buffer = program.create_buffer_from_slice(&points)?;
+            let shifts_buffer = program.create_buffer_from_slice(&shifts)?;
+            // It is safe as the GPU will initialize that buffer
+            let results_buffer =
+                unsafe { program.create_buffer::<F>(shifts.len())? };
+
+            let (global_work_size, local_work_size) =
+                elementwise_work_size(shifts.len());
+            program
+                .create_kernel(
+                    &format!("{}_eval_points", F::name()),
+                    global_work_size,
+                    local_work_size,
+                )?
+                .arg(&coeffs_buffer)
+                .arg(&points_buffer)
+                .arg(&shifts_buffer)
+                .arg(&results_buffer)
+                .arg(&(n as u32))
+                .arg(&(EVAL_CHUNK_LEN as u32))
+                .arg(&(num_chunks as u32))
+                .arg(&(num_points as u32))
+                .run()?;
+
+            let mut results = vec![F::ZERO; shifts.len()];
+            program.read_into_buffer(&results_buffer, &mut results)?;
+            Ok(results)
         });
 
-        self.program.run(closures, input)
+        let partials = run_checked!(self.program, closures, ())?;
+        Ok(partials
+            .chunks(num_chunks)
+            .map(|chunks| chunks.iter().sum())
+            .collect())
     }
 }
 
 /// One FFT kernel for each GPU available.
+///
+/// The kernels own the programs they are created from, they stay valid no
+/// matter what happens to the caller's bindings of them.
 pub struct FftKernel<'a, F>
 where F: Field + GpuName
 {
     kernels: Vec<SingleFftKernel<'a, F>>,
+    /// How the FFTs of a batch are split among the devices.
+    splitter: Arc<dyn WorkSplitter>,
+    /// FFTs of less than `2^cpu_threshold` elements are done on the CPU.
+    cpu_threshold: u32,
+    /// The number of FFTs that were done on the CPU because of the
+    /// `cpu_threshold`.
+    cpu_ffts: AtomicUsize,
+    /// Whether the work is split independently of the device properties, see
+    /// [`FftKernel::set_reproducible`].
+    reproducible: bool,
 }
 
 impl<'a, F> FftKernel<'a, F>
@@ -147,7 +2340,7 @@ where F: Field + GpuName
 {
     /// Create new kernels, one for each given device.
     pub fn create(programs: Vec<Program>) -> EcResult<Self> {
-        Self::create_optional_abort(programs, None)
+        Self::create_optional_abort(share(programs), None)
     }
 
     /// Create new kernels, one for each given device, with early abort hook.
@@ -159,37 +2352,56 @@ where F: Field + GpuName
         programs: Vec<Program>,
         maybe_abort: &'a (dyn Fn() -> bool + Send + Sync),
     ) -> EcResult<Self> {
-        Self::create_optional_abort(programs, Some(maybe_abort))
+        Self::create_optional_abort(share(programs), Some(maybe_abort))
+    }
+
+    /// Create new kernels from programs that may be shared with other
+    /// kernels, one for each given device.
+    ///
+    /// See [`SharedProgram`] for how to use a single program for several
+    /// kinds of kernels.
+    pub fn create_shared(programs: Vec<SharedProgram>) -> EcResult<Self> {
+        Self::create_optional_abort(programs, None)
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "fft_create",
+            skip_all,
+            fields(num_programs = programs.len())
+        )
+    )]
     fn create_optional_abort(
-        programs: Vec<Program>,
+        programs: Vec<SharedProgram>,
         maybe_abort: Option<&'a (dyn Fn() -> bool + Send + Sync)>,
     ) -> EcResult<Self> {
-        let kernels: Vec<_> = programs
-            .into_iter()
-            .filter_map(|program| {
-                let device_name = program.device_name().to_string();
-                let kernel = SingleFftKernel::<F>::create(program, maybe_abort);
-                if let Err(ref e) = kernel {
-                    error!(
-                        "Cannot initialize kernel for device '{}'! Error: {}",
-                        device_name, e
-                    );
-                }
-                kernel.ok()
-            })
-            .collect();
+        let kernels = working_kernels(programs.into_iter().map(|program| {
+            let device_name = program.device_name().to_string();
+            (
+                device_name,
+                SingleFftKernel::<F>::create(program, maybe_abort),
+            )
+        }))?;
 
-        if kernels.is_empty() {
-            return Err(EcError::Simple("No working GPUs found!"));
-        }
         info!("FFT: {} working device(s) selected. ", kernels.len());
         for (i, k) in kernels.iter().enumerate() {
             info!("FFT: Device {}: {}", i, k.program.device_name(),);
+            #[cfg(feature = "tracing")]
+            tracing::info!(
+                device = k.program.device_name(),
+                backend = k.program.backend(),
+                "device selected"
+            );
         }
 
-        Ok(Self { kernels })
+        Ok(Self {
+            kernels,
+            splitter: Arc::new(EvenSplit),
+            cpu_threshold: 0,
+            cpu_ffts: AtomicUsize::new(0),
+            reproducible: false,
+        })
     }
 
     /// Performs FFT on `input`
@@ -200,43 +2412,628 @@ where F: Field + GpuName
     pub fn radix_fft(
         &mut self, input: &mut [F], omega: &F, log_n: u32,
     ) -> EcResult<()> {
+        if self.cpu_fft(input, omega, log_n)? {
+            return Ok(());
+        }
         self.kernels[0].radix_fft(input, omega, log_n)
     }
 
+    /// Performs FFT on `input`, whose elements are in the given `form`.
+    ///
+    /// Uses the first available GPU. See [`InputForm`] for the cost of
+    /// non-Montgomery input.
+    pub fn radix_fft_with_form(
+        &mut self, input: &mut [F], omega: &F, log_n: u32, form: InputForm,
+    ) -> EcResult<()> {
+        self.kernels[0].radix_fft_with_form(input, omega, log_n, form)
+    }
+
+    /// Performs FFT on `input` and applies `map` to every output, without an
+    /// extra pass over them.
+    ///
+    /// Uses the first available GPU.
+    pub fn radix_fft_with_map(
+        &mut self, input: &mut [F], omega: &F, log_n: u32, map: FftPostMap<F>,
+    ) -> EcResult<()> {
+        self.kernels[0].radix_fft_with_map(input, omega, log_n, map)
+    }
+
+    /// Performs the inverse FFT on `input`, `omega` is the root of unity of
+    /// the forward FFT.
+    ///
+    /// Uses the first available GPU. See [`SingleFftKernel::radix_ifft`].
+    pub fn radix_ifft(
+        &mut self, input: &mut [F], omega: &F, log_n: u32,
+    ) -> EcResult<()> {
+        if log_n < self.cpu_threshold {
+            let omega_inv = inverse_omega(omega)?;
+            let n_inv =
+                F::from(1u64 << log_n).inverse().expect("n is non-zero");
+            self.cpu_fft(input, &omega_inv, log_n)?;
+            input.iter_mut().for_each(|x| *x *= n_inv);
+            return Ok(());
+        }
+        self.kernels[0].radix_ifft(input, omega, log_n)
+    }
+
+    /// Precomputes the roots of unity of all sizes up to `2^max_log_n` and
+    /// their inverses on the host.
+    ///
+    /// The table can be passed to [`FftKernel::radix_fft_with_roots`] and
+    /// [`FftKernel::radix_ifft_with_roots`] for any of those sizes, so that
+    /// the roots aren't computed for every FFT. See [`RootTable`].
+    pub fn precompute_roots(&self, max_log_n: u32) -> EcResult<RootTable<F>>
+    where F: FftField {
+        RootTable::new(max_log_n)
+    }
+
+    /// Performs FFT on `input`, with the root of unity from `roots`.
+    ///
+    /// Uses the first available GPU. See
+    /// [`SingleFftKernel::radix_fft_with_roots`].
+    pub fn radix_fft_with_roots(
+        &mut self, input: &mut [F], roots: &RootTable<F>, log_n: u32,
+    ) -> EcResult<()> {
+        self.kernels[0].radix_fft_with_roots(input, roots, log_n)
+    }
+
+    /// Performs the inverse FFT on `input`, with the inverse root of unity
+    /// from `roots`.
+    ///
+    /// Uses the first available GPU. See
+    /// [`SingleFftKernel::radix_ifft_with_roots`].
+    pub fn radix_ifft_with_roots(
+        &mut self, input: &mut [F], roots: &RootTable<F>, log_n: u32,
+    ) -> EcResult<()> {
+        self.kernels[0].radix_ifft_with_roots(input, roots, log_n)
+    }
+
+    /// Performs FFT on `input`, whose elements are given as bytes.
+    ///
+    /// Uses the first available GPU. See [`SingleFftKernel::radix_fft_bytes`].
+    pub fn radix_fft_bytes(
+        &mut self, field: &FieldSpec, input: &mut [u8], omega: &[u8],
+        log_n: u32,
+    ) -> EcResult<()>
+    where
+        F: CanonicalBytes,
+    {
+        self.kernels[0].radix_fft_bytes(field, input, omega, log_n)
+    }
+
+    /// Performs FFT on the elements `buffer[offset + i * stride]`, for `i` in
+    /// `0..2^log_n`, without de-interleaving them first.
+    ///
+    /// Uses the first available GPU. See
+    /// [`SingleFftKernel::radix_fft_strided`].
+    pub fn radix_fft_strided(
+        &mut self, buffer: &mut [F], offset: usize, stride: usize, omega: &F,
+        log_n: u32,
+    ) -> EcResult<()> {
+        self.kernels[0].radix_fft_strided(buffer, offset, stride, omega, log_n)
+    }
+
+    /// Performs a 2D FFT of the `rows` x `cols` `matrix`, which is stored row
+    /// by row.
+    ///
+    /// Uses the first available GPU. See [`SingleFftKernel::fft_2d`].
+    pub fn fft_2d(
+        &mut self, matrix: &mut [F], rows: usize, cols: usize, omega_row: &F,
+        omega_col: &F,
+    ) -> EcResult<()> {
+        self.kernels[0].fft_2d(matrix, rows, cols, omega_row, omega_col)
+    }
+
+    /// Updates the evaluations `prev_evals` of a polynomial, after some of its
+    /// coefficients changed from their old to their new value.
+    ///
+    /// Uses the first available GPU. See [`SingleFftKernel::update_fft`].
+    pub fn update_fft(
+        &mut self, prev_evals: &mut [F], changes: &[(usize, F, F)], omega: &F,
+        log_n: u32,
+    ) -> EcResult<()> {
+        self.kernels[0].update_fft(prev_evals, changes, omega, log_n)
+    }
+
+    /// Performs FFT on the logical array that is formed by concatenating the
+    /// `segments`.
+    ///
+    /// Uses the first available GPU. See
+    /// [`SingleFftKernel::radix_fft_segmented`].
+    pub fn radix_fft_segmented(
+        &mut self, segments: &mut [&mut [F]], omega: &F, log_n: u32,
+    ) -> EcResult<()> {
+        self.kernels[0].radix_fft_segmented(segments, omega, log_n)
+    }
+
+    /// Precalculates the twiddle factors of the given FFT sizes on all GPUs.
+    ///
+    /// The twiddles are pinned in the cache, so that they are never evicted
+    /// and the first FFT of each of those sizes doesn't need to calculate
+    /// them. The sizes are given as log2 of the number of elements, the
+    /// roots of unity are the ones of [`FftField::get_root_of_unity`].
+    pub fn prepare_domains(&mut self, log_ns: &[u32]) -> EcResult<()>
+    where F: FftField {
+        for log_n in log_ns {
+            let omega = F::get_root_of_unity(1 << log_n).ok_or(
+                EcError::Simple("The field has no subgroup of that size"),
+            )?;
+            for kern in self.kernels.iter_mut() {
+                kern.prepare_domain(&omega, *log_n);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns how many times twiddle factors were calculated on all GPUs,
+    /// because they were not cached.
+    pub fn twiddle_generations(&self) -> usize {
+        self.kernels.iter().map(|k| k.twiddle_generations()).sum()
+    }
+
+    /// Shares the given GPU memory `budget` with the other kernels that use
+    /// it.
+    ///
+    /// Every FFT reserves the memory of its buffers from the budget first and
+    /// waits while other kernels hold too much of it. A single FFT cannot be
+    /// split, if it's larger than the whole budget it fails with
+    /// [`EcError::MemoryBudgetExceeded`].
+    pub fn with_budget(mut self, budget: Arc<MemoryBudget>) -> Self {
+        for kern in self.kernels.iter_mut() {
+            kern.set_budget(budget.clone());
+        }
+        self
+    }
+
+    /// Sets the size (as log2 of the number of elements) below which FFTs are
+    /// done on the CPU instead of on a GPU.
+    ///
+    /// For tiny FFTs the overhead of the transfers and kernel launches is
+    /// larger than the FFT itself. This applies to
+    /// [`FftKernel::radix_fft`], [`FftKernel::radix_ifft`] and the FFTs of a
+    /// batch, e.g. [`FftKernel::radix_fft_many`]. The results are the same
+    /// either way. A threshold of zero, the default, disables it.
+    pub fn set_cpu_threshold(&mut self, log_n: u32) {
+        self.cpu_threshold = log_n;
+    }
+
+    /// Returns how many FFTs were done on the CPU, because they were smaller
+    /// than the threshold of [`FftKernel::set_cpu_threshold`].
+    pub fn cpu_ffts(&self) -> usize { self.cpu_ffts.load(Ordering::Relaxed) }
+
+    /// Performs the FFT on the CPU, if it's smaller than the CPU threshold.
+    /// Returns whether it did, or an error if `input` doesn't have `2^log_n`
+    /// elements.
+    fn cpu_fft(
+        &self, input: &mut [F], omega: &F, log_n: u32,
+    ) -> EcResult<bool> {
+        check_len(1 << log_n, input.len())?;
+        Ok(cpu_fft_below(
+            self.cpu_threshold,
+            &self.cpu_ffts,
+            input,
+            omega,
+            log_n,
+        ))
+    }
+
+    /// Sets the size (as log2 of the number of elements) up to which FFTs are
+    /// done entirely in local memory on all GPUs, `None` disables it.
+    ///
+    /// See [`SingleFftKernel::set_shared_mem_threshold`].
+    pub fn set_shared_mem_threshold(&mut self, log_n: Option<u32>) {
+        for kern in self.kernels.iter_mut() {
+            kern.set_shared_mem_threshold(log_n);
+        }
+    }
+
+    /// Enables or disables the check of the twiddle factors on all GPUs.
+    ///
+    /// See [`SingleFftKernel::set_verify_twiddles`].
+    pub fn set_verify_twiddles(&mut self, verify: bool) {
+        for kern in self.kernels.iter_mut() {
+            kern.set_verify_twiddles(verify);
+        }
+    }
+
+    /// Enables or disables the caching of twiddle factors on all GPUs.
+    ///
+    /// See [`SingleFftKernel::set_shared_twiddles`].
+    pub fn set_shared_twiddles(&mut self, shared_twiddles: bool) {
+        for kern in self.kernels.iter_mut() {
+            kern.set_shared_twiddles(shared_twiddles);
+        }
+    }
+
+    /// Sets the allocator of the temporary host buffers of all GPUs.
+    ///
+    /// See [`SingleFftKernel::set_host_allocator`].
+    pub fn set_host_allocator(&mut self, allocator: HostAllocator) {
+        for kern in self.kernels.iter_mut() {
+            kern.set_host_allocator(allocator.clone());
+        }
+    }
+
+    /// Sets how the FFTs of a batch, e.g. of [`FftKernel::radix_fft_many`],
+    /// are split among the devices. By default they are split evenly.
+    pub fn set_work_splitter(&mut self, splitter: impl WorkSplitter + 'static) {
+        self.splitter = Arc::new(splitter);
+    }
+
+    /// Enables or disables the reproducible mode.
+    ///
+    /// In that mode the work is always split evenly, no matter which
+    /// [`WorkSplitter`] is set, so that the split only depends on the number
+    /// of devices and not on the properties the drivers report. The time
+    /// estimates aren't calibrated, [`FftKernel::calibrate`] does nothing.
+    /// The field elements of the results are the same in both modes, the
+    /// price is that faster or larger devices don't get a larger share of
+    /// the work.
+    pub fn set_reproducible(&mut self, reproducible: bool) {
+        self.reproducible = reproducible;
+        if reproducible {
+            for kern in self.kernels.iter_mut() {
+                kern.time_scale = 1.0;
+            }
+        }
+    }
+
+    /// Returns the information of the devices, in the order they get their
+    /// share of the work.
+    pub fn device_info(&self) -> Vec<DeviceInfo> {
+        self.kernels
+            .iter()
+            .map(|kern| kern.device_info.clone())
+            .collect()
+    }
+
+    /// Returns the range of the `n` items each device processes.
+    fn device_ranges(&self, n: usize) -> EcResult<Vec<Range<usize>>> {
+        // An even split only depends on the number of devices.
+        let splitter: &dyn WorkSplitter = if self.reproducible {
+            &EvenSplit
+        } else {
+            &*self.splitter
+        };
+        split_ranges(splitter, n, &self.device_info())
+    }
+
+    /// Returns a rough estimate of the time an FFT of `2^log_n` elements
+    /// takes, see [`SingleFftKernel::estimate_time`].
+    ///
+    /// Uses the first available GPU, like [`FftKernel::radix_fft`].
+    pub fn estimate_time(&self, log_n: u32) -> Duration {
+        self.kernels[0].estimate_time(log_n)
+    }
+
+    /// Calibrates the time estimates of all GPUs, see
+    /// [`SingleFftKernel::calibrate`].
+    ///
+    /// It does nothing in the reproducible mode, see
+    /// [`FftKernel::set_reproducible`].
+    pub fn calibrate(&mut self) -> EcResult<()>
+    where F: FftField {
+        if self.reproducible {
+            return Ok(());
+        }
+        self.kernels
+            .iter_mut()
+            .try_for_each(SingleFftKernel::calibrate)
+    }
+
+    /// Computes `a + r * b` elementwise into `a` on the GPU.
+    ///
+    /// Uses the first available GPU. See [`SingleFftKernel::fold`].
+    pub fn fold(&mut self, a: &mut [F], b: &[F], r: F) -> EcResult<()> {
+        self.kernels[0].fold(a, b, r)
+    }
+
+    /// Returns `FFT(a) - FFT(b)`, with the subtraction done on the GPU.
+    ///
+    /// Uses the first available GPU. See
+    /// [`SingleFftKernel::fft_difference`].
+    pub fn fft_difference(
+        &mut self, a: &[F], b: &[F], omega: &F, log_n: u32,
+    ) -> EcResult<Vec<F>> {
+        self.kernels[0].fft_difference(a, b, omega, log_n)
+    }
+
+    /// Computes the coefficients of the quotient of the numerator and the
+    /// vanishing polynomial of the `domain`.
+    ///
+    /// Uses the first available GPU. See
+    /// [`SingleFftKernel::compute_quotient`].
+    pub fn compute_quotient(
+        &mut self, numerator_coeffs: &[F], domain: &QuotientDomain<F>,
+        coset_gen: &F,
+    ) -> EcResult<Vec<F>> {
+        self.kernels[0].compute_quotient(numerator_coeffs, domain, coset_gen)
+    }
+
+    /// Returns whether the FFTs of `a` and `b` are equal.
+    ///
+    /// Uses the first available GPU. See
+    /// [`SingleFftKernel::fft_equal_on_domain`].
+    pub fn fft_equal_on_domain(
+        &mut self, a: &[F], b: &[F], omega: &F, log_n: u32,
+    ) -> EcResult<bool> {
+        self.kernels[0].fft_equal_on_domain(a, b, omega, log_n)
+    }
+
+    /// Evaluates all `polys` at the point `z`, in a single kernel launch.
+    ///
+    /// The polynomials are given by their coefficients, lowest degree first.
+    ///
+    /// Uses the first available GPU.
+    pub fn batch_evaluate_at(
+        &mut self, polys: &[&[F]], z: F,
+    ) -> EcResult<Vec<F>> {
+        self.kernels[0].batch_evaluate_at(polys, z)
+    }
+
+    /// Computes only the outputs at the `output_indices` of the FFT of
+    /// `coeffs`.
+    ///
+    /// Uses the first available GPU. See [`SingleFftKernel::partial_fft`].
+    pub fn partial_fft(
+        &mut self, coeffs: &[F], omega: &F, log_n: u32,
+        output_indices: &[usize],
+    ) -> EcResult<Vec<F>> {
+        self.kernels[0].partial_fft(coeffs, omega, log_n, output_indices)
+    }
+
     /// Performs FFT on `inputs`
     /// * `omega` - Special value `omega` is used for FFT over finite-fields
     /// * `log_n` - Specifies log2 of number of elements
     ///
-    /// Uses all available GPUs to distribute the work.
+    /// Uses all available GPUs to distribute the work. The inputs may have
+    /// different sizes, the ones of a device with the same size and `omega`
+    /// are transformed together, see [`SingleFftKernel::radix_fft_batch`].
     pub fn radix_fft_many(
         &mut self, inputs: &mut [&mut [F]], omegas: &[F], log_ns: &[u32],
     ) -> EcResult<()> {
-        let n = inputs.len();
-        let num_devices = self.kernels.len();
-        let chunk_size = ((n as f64) / (num_devices as f64)).ceil() as usize;
+        self.radix_fft_many_with_form(
+            inputs,
+            omegas,
+            log_ns,
+            InputForm::Montgomery,
+        )
+    }
+
+    /// Performs FFT on `inputs`, whose elements are in the given `form`.
+    ///
+    /// Uses all available GPUs to distribute the work. See [`InputForm`] for
+    /// the cost of non-Montgomery input.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "radix_fft_many",
+            skip_all,
+            fields(num_ffts = inputs.len(), num_devices = self.kernels.len())
+        )
+    )]
+    pub fn radix_fft_many_with_form(
+        &mut self, inputs: &mut [&mut [F]], omegas: &[F], log_ns: &[u32],
+        form: InputForm,
+    ) -> EcResult<()> {
+        self.radix_fft_many_by(
+            inputs,
+            omegas,
+            log_ns,
+            // The FFT is linear, hence the form doesn't matter on the CPU.
+            |input, omega, log_n, _| serial_fft(input, omega, log_n),
+            |kern, inputs, omega, log_n, _| {
+                kern.radix_fft_batch_with_form_and_map(
+                    inputs,
+                    omega,
+                    log_n,
+                    form,
+                    FftPostMap::None,
+                )
+            },
+        )
+    }
+
+    /// Performs the inverse FFT on `inputs`, `omegas` are the roots of unity
+    /// of the forward FFTs.
+    ///
+    /// Uses all available GPUs to distribute the work. The outputs are
+    /// multiplied by `n^-1` on the device when they are written, see
+    /// [`SingleFftKernel::radix_ifft`], it's computed once for every distinct
+    /// size.
+    pub fn radix_ifft_many(
+        &mut self, inputs: &mut [&mut [F]], omegas: &[F], log_ns: &[u32],
+    ) -> EcResult<()> {
+        let n_invs = size_inverses::<F>(log_ns);
+        let omega_invs = omegas
+            .iter()
+            .map(inverse_omega)
+            .collect::<EcResult<Vec<_>>>()?;
+        self.radix_fft_many_by(
+            inputs,
+            &omega_invs,
+            log_ns,
+            |input, omega_inv, log_n, _| {
+                serial_fft(input, omega_inv, log_n);
+                let n_inv = n_invs[&log_n];
+                input.iter_mut().for_each(|x| *x *= n_inv);
+            },
+            |kern, inputs, omega_inv, log_n, _| {
+                kern.radix_fft_batch_with_form_and_map(
+                    inputs,
+                    omega_inv,
+                    log_n,
+                    InputForm::Montgomery,
+                    FftPostMap::MulConst(n_invs[&log_n]),
+                )
+            },
+        )
+    }
+
+    /// Performs FFT on `inputs` over the cosets `shifts[i] * <omegas[i]>`,
+    /// i.e. it evaluates the polynomials at `shifts[i] * omegas[i]^j`.
+    ///
+    /// Uses all available GPUs to distribute the work, see
+    /// [`SingleFftKernel::radix_fft_coset`]. There must be a shift for every
+    /// input, a shift of one is an FFT over the subgroup.
+    pub fn radix_fft_coset_many(
+        &mut self, inputs: &mut [&mut [F]], omegas: &[F], log_ns: &[u32],
+        shifts: &[F],
+    ) -> EcResult<()> {
+        check_len(inputs.len(), shifts.len())?;
+        self.radix_fft_many_by(
+            inputs,
+            omegas,
+            log_ns,
+            |input, omega, log_n, i| {
+                serial_coset_fft(input, omega, &shifts[i], log_n)
+            },
+            |kern, inputs, omega, log_n, indices| {
+                for (input, &i) in inputs.iter_mut().zip(indices) {
+                    kern.radix_fft_coset(input, omega, log_n, &shifts[i])?;
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Performs the inverse FFT on `inputs` over the cosets
+    /// `shifts[i] * <omegas[i]>`, `omegas` are the roots of unity of the
+    /// forward FFTs.
+    ///
+    /// It's the inverse of [`FftKernel::radix_fft_coset_many`]. Uses all
+    /// available GPUs to distribute the work, see
+    /// [`SingleFftKernel::radix_ifft_coset`].
+    pub fn radix_ifft_coset_many(
+        &mut self, inputs: &mut [&mut [F]], omegas: &[F], log_ns: &[u32],
+        shifts: &[F],
+    ) -> EcResult<()> {
+        check_len(inputs.len(), shifts.len())?;
+        if shifts.iter().any(|shift| *shift == F::ZERO) {
+            return Err(EcError::Simple("The coset shift must be non-zero"));
+        }
+        for omega in omegas {
+            inverse_omega(omega)?;
+        }
+        self.radix_fft_many_by(
+            inputs,
+            omegas,
+            log_ns,
+            |input, omega, log_n, i| {
+                serial_coset_ifft(input, omega, &shifts[i], log_n)
+            },
+            |kern, inputs, omega, log_n, indices| {
+                for (input, &i) in inputs.iter_mut().zip(indices) {
+                    kern.radix_ifft_coset(input, omega, log_n, &shifts[i])?;
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Distributes the FFTs of `inputs` among the devices.
+    ///
+    /// The inputs of a device are grouped by their size and `omega`, `gpu`
+    /// performs all FFTs of a group at once, it gets the indices of the
+    /// inputs of the group as well. Inputs that are smaller than the CPU
+    /// threshold are done by `cpu` on the host instead.
+    fn radix_fft_many_by<C, G>(
+        &mut self, inputs: &mut [&mut [F]], omegas: &[F], log_ns: &[u32],
+        cpu: C, gpu: G,
+    ) -> EcResult<()>
+    where
+        C: Fn(&mut [F], &F, u32, usize) + Sync,
+        G: Fn(
+                &mut SingleFftKernel<'a, F>,
+                &mut [&mut [F]],
+                &F,
+                u32,
+                &[usize],
+            ) -> EcResult<()>
+            + Sync,
+    {
+        check_batch(inputs, omegas, log_ns)?;
+        let ranges = self.device_ranges(inputs.len())?;
+        let cpu_threshold = self.cpu_threshold;
+        let cpu_ffts = &self.cpu_ffts;
+        let (cpu, gpu) = (&cpu, &gpu);
 
         let result = Arc::new(RwLock::new(Ok(())));
 
         THREAD_POOL.scoped(|s| {
-            for (((inputs, omegas), log_ns), kern) in inputs
-                .chunks_mut(chunk_size)
-                .zip(omegas.chunks(chunk_size))
-                .zip(log_ns.chunks(chunk_size))
-                .zip(self.kernels.iter_mut())
+            let mut rest = &mut inputs[..];
+            for (range, kern) in ranges.into_iter().zip(self.kernels.iter_mut())
             {
+                let (inputs, tail) =
+                    std::mem::take(&mut rest).split_at_mut(range.len());
+                rest = tail;
+                if inputs.is_empty() {
+                    continue;
+                }
+                let start = range.start;
+                let omegas = &omegas[range.clone()];
+                let log_ns = &log_ns[range];
                 let result = result.clone();
+                #[cfg(feature = "tracing")]
+                let span = tracing::info_span!(
+                    "fft_device",
+                    device = kern.program.device_name(),
+                    backend = kern.program.backend(),
+                    num_ffts = inputs.len(),
+                );
                 s.execute(move || {
-                    for ((input, omega), log_n) in
-                        inputs.iter_mut().zip(omegas.iter()).zip(log_ns.iter())
+                    #[cfg(feature = "tracing")]
+                    let _entered = span.enter();
+                    let mut batches: Vec<FftGroup<'_, F>> = Vec::new();
+                    for (j, ((input, omega), log_n)) in inputs
+                        .iter_mut()
+                        .zip(omegas.iter())
+                        .zip(log_ns.iter())
+                        .enumerate()
                     {
+                        if *log_n < cpu_threshold {
+                            cpu(input, omega, *log_n, start + j);
+                            cpu_ffts.fetch_add(1, Ordering::Relaxed);
+                            continue;
+                        }
+                        let batch = match batches.iter().position(|batch| {
+                            batch.log_n == *log_n && batch.omega == *omega
+                        }) {
+                            Some(k) => &mut batches[k],
+                            None => {
+                                batches.push(FftGroup {
+                                    log_n: *log_n,
+                                    omega: *omega,
+                                    inputs: Vec::new(),
+                                    indices: Vec::new(),
+                                });
+                                batches.last_mut().unwrap()
+                            }
+                        };
+                        batch.inputs.push(&mut **input);
+                        batch.indices.push(start + j);
+                    }
+
+                    for mut batch in batches {
                         if result.read().unwrap().is_err() {
                             break;
                         }
-
-                        if let Err(err) = kern.radix_fft(input, omega, *log_n) {
+                        if let Err(err) = gpu(
+                            kern,
+                            &mut batch.inputs,
+                            &batch.omega,
+                            batch.log_n,
+                            &batch.indices,
+                        ) {
                             *result.write().unwrap() = Err(err);
                             break;
                         }
+                        #[cfg(feature = "tracing")]
+                        tracing::debug!(
+                            log_n = batch.log_n,
+                            num_ffts = batch.inputs.len(),
+                            "fft batch done"
+                        );
                     }
                 });
             }
@@ -244,4 +3041,160 @@ where F: Field + GpuName
 
         Arc::try_unwrap(result).unwrap().into_inner().unwrap()
     }
+
+    /// Evaluates the `polys` over the `domain`, like `domain.fft()` of
+    /// `ark_poly` does.
+    ///
+    /// The coefficients are padded with zeros to the size of the domain, the
+    /// evaluations are in the order of the domain elements, i.e. the `i`-th
+    /// one is the evaluation at `group_gen^i`. Coset domains are not
+    /// supported. Uses all available GPUs to distribute the work, see
+    /// [`FftKernel::radix_fft_many`].
+    pub fn fft_polynomials(
+        &mut self, polys: &[DensePolynomial<F>],
+        domain: Radix2EvaluationDomain<F>,
+    ) -> EcResult<Vec<Evaluations<F, Radix2EvaluationDomain<F>>>>
+    where
+        F: FftField,
+    {
+        check_subgroup_domain(&domain)?;
+        if let Some((i, poly)) = polys
+            .iter()
+            .enumerate()
+            .find(|(_, poly)| poly.coeffs.len() > domain.size())
+        {
+            return Err(EcError::InvalidLength(format!(
+                "polynomial {} has {} coefficients, but the domain only {} \
+                 elements",
+                i,
+                poly.coeffs.len(),
+                domain.size()
+            )));
+        }
+        let mut values = polys
+            .iter()
+            .map(|poly| {
+                let mut coeffs = poly.coeffs.clone();
+                coeffs.resize(domain.size(), F::ZERO);
+                coeffs
+            })
+            .collect::<Vec<_>>();
+        let mut inputs =
+            values.iter_mut().map(|v| &mut v[..]).collect::<Vec<_>>();
+        let omegas = vec![domain.group_gen; inputs.len()];
+        let log_ns = vec![domain.log_size_of_group; inputs.len()];
+        self.radix_fft_many(&mut inputs, &omegas, &log_ns)?;
+        Ok(values
+            .into_iter()
+            .map(|evals| Evaluations::from_vec_and_domain(evals, domain))
+            .collect())
+    }
+
+    /// Interpolates the `evaluations`, like `Evaluations::interpolate()` of
+    /// `ark_poly` does.
+    ///
+    /// Every evaluation is interpolated over its own domain, which must not
+    /// be a coset domain. It is the inverse of
+    /// [`FftKernel::fft_polynomials`], the trailing zero coefficients of the
+    /// results are removed. Uses all available GPUs to distribute the work,
+    /// see [`FftKernel::radix_fft_many`].
+    pub fn ifft_evaluations(
+        &mut self, evaluations: &[Evaluations<F, Radix2EvaluationDomain<F>>],
+    ) -> EcResult<Vec<DensePolynomial<F>>>
+    where F: FftField {
+        let mut values = Vec::with_capacity(evaluations.len());
+        let mut omegas = Vec::with_capacity(evaluations.len());
+        let mut log_ns = Vec::with_capacity(evaluations.len());
+        for evaluation in evaluations {
+            let domain = evaluation.domain();
+            check_subgroup_domain(&domain)?;
+            check_len(domain.size(), evaluation.evals.len())?;
+            values.push(evaluation.evals.clone());
+            omegas.push(domain.group_gen_inv);
+            log_ns.push(domain.log_size_of_group);
+        }
+        let mut inputs =
+            values.iter_mut().map(|v| &mut v[..]).collect::<Vec<_>>();
+        self.radix_fft_many(&mut inputs, &omegas, &log_ns)?;
+        Ok(values
+            .into_iter()
+            .zip(evaluations)
+            .map(|(mut coeffs, evaluation)| {
+                let size_inv = evaluation.domain().size_inv;
+                coeffs.iter_mut().for_each(|coeff| *coeff *= size_inv);
+                DensePolynomial::from_coefficients_vec(coeffs)
+            })
+            .collect())
+    }
+
+    /// Performs FFT on `input`, distributed among all available GPUs.
+    ///
+    /// The FFT is split into smaller ones with the four-step algorithm: the
+    /// input is viewed as a `2^(log_n / 2)` x `2^(log_n - log_n / 2)` matrix,
+    /// whose columns are transformed, multiplied by twiddle factors and whose
+    /// rows are transformed then. The columns and rows are split among the
+    /// devices like [`FftKernel::radix_fft_many`] does. The decomposition only
+    /// depends on `log_n`, not on the number of devices or on the
+    /// [`WorkSplitter`], and the twiddle factors are applied on the host. As
+    /// field elements have a unique representation, the output is
+    /// bit-identical to the one of [`FftKernel::radix_fft`], no matter how
+    /// many devices participated.
+    pub fn radix_fft_distributed(
+        &mut self, input: &mut [F], omega: &F, log_n: u32,
+    ) -> EcResult<()> {
+        check_len(1 << log_n, input.len())?;
+        if log_n == 0 {
+            return Ok(());
+        }
+        let log_rows = log_n / 2;
+        let log_cols = log_n - log_rows;
+        let (rows, cols) = (1 << log_rows, 1 << log_cols);
+
+        // Element `(j1, j2)` is `input[j1 * cols + j2]`, column `j2` is stored
+        // contiguously.
+        let mut columns = Vec::with_capacity(input.len());
+        for j2 in 0..cols {
+            columns.extend((0..rows).map(|j1| input[j1 * cols + j2]));
+        }
+        // Columns of a single element are already transformed.
+        if log_rows > 0 {
+            let omega_col = pow_vartime(omega, [cols as u64]);
+            self.radix_fft_many(
+                &mut columns.chunks_mut(rows).collect::<Vec<_>>(),
+                &vec![omega_col; cols],
+                &vec![log_rows; cols],
+            )?;
+        }
+
+        // Multiply entry `k1` of column `j2` with `omega^(j2 * k1)`.
+        for (j2, column) in columns.chunks_mut(rows).enumerate() {
+            let step = pow_vartime(omega, [j2 as u64]);
+            let mut twiddle = F::ONE;
+            for value in column.iter_mut() {
+                *value *= twiddle;
+                twiddle *= step;
+            }
+        }
+
+        // Transpose, so that row `k1` is stored contiguously.
+        let mut rows_data = Vec::with_capacity(input.len());
+        for k1 in 0..rows {
+            rows_data.extend((0..cols).map(|j2| columns[j2 * rows + k1]));
+        }
+        drop(columns);
+        let omega_row = pow_vartime(omega, [rows as u64]);
+        self.radix_fft_many(
+            &mut rows_data.chunks_mut(cols).collect::<Vec<_>>(),
+            &vec![omega_row; rows],
+            &vec![log_cols; rows],
+        )?;
+
+        // Output `k1 + k2 * rows` is entry `k2` of row `k1`.
+        for (k1, row) in rows_data.chunks(cols).enumerate() {
+            for (k2, value) in row.iter().enumerate() {
+                input[k1 + k2 * rows] = *value;
+            }
+        }
+        Ok(())
+    }
 }
@@ -1,13 +1,19 @@
-use ark_ff::PrimeField;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use ark_ff::{Field, PrimeField};
 
 use crate::{pow_vartime, threadpool::Worker};
 
+/// Arrays of at least `2^LOG_PARALLEL_PERMUTE` elements are permuted with
+/// multiple threads.
+const LOG_PARALLEL_PERMUTE: u32 = 16;
+
 /// Calculate the Fast Fourier Transform on the CPU (single-threaded).
 ///
 /// The input `a` is mutated and contains the result when this function returns.
 /// The length of the input vector must be `2^log_n`.
 #[allow(clippy::many_single_char_names)]
-pub fn serial_fft<F: PrimeField>(a: &mut [F], omega: &F, log_n: u32) {
+pub fn serial_fft<F: Field>(a: &mut [F], omega: &F, log_n: u32) {
     fn bitreverse(mut n: u32, l: u32) -> u32 {
         let mut r = 0;
         for _ in 0..l {
@@ -110,13 +116,183 @@ pub fn parallel_fft<F: PrimeField>(
     });
 }
 
+/// Calculate the coset Fast Fourier Transform on the CPU (single-threaded).
+///
+/// The coefficients in `a` are multiplied by the powers of the coset generator
+/// `g` before the transform, so the result is the evaluation over `g *
+/// <omega>`.
+pub fn serial_coset_fft<F: Field>(a: &mut [F], omega: &F, g: &F, log_n: u32) {
+    distribute_powers(a, F::ONE, g);
+    serial_fft(a, omega, log_n);
+}
+
+/// Calculate the inverse coset Fast Fourier Transform on the CPU
+/// (single-threaded).
+///
+/// `omega` and `g` are the same values that were passed to
+/// [`serial_coset_fft`], the inverses and the `1/n` scaling are applied here.
+pub fn serial_coset_ifft<F: Field>(a: &mut [F], omega: &F, g: &F, log_n: u32) {
+    let omega_inv = omega.inverse().expect("omega must be non-zero");
+    let g_inv = g.inverse().expect("coset generator must be non-zero");
+    let n_inv = F::from(a.len() as u64).inverse().unwrap();
+    serial_fft(a, &omega_inv, log_n);
+    distribute_powers(a, n_inv, &g_inv);
+}
+
+/// Calculate the coset Fast Fourier Transform on the CPU (multithreaded).
+///
+/// See [`serial_coset_fft`] and [`parallel_fft`] for the meaning of the
+/// arguments.
+pub fn parallel_coset_fft<F: PrimeField>(
+    a: &mut [F], worker: &Worker, omega: &F, g: &F, log_n: u32,
+    log_threads: u32,
+) {
+    parallel_distribute_powers(a, worker, F::ONE, g);
+    parallel_fft(a, worker, omega, log_n, log_threads);
+}
+
+/// Calculate the inverse coset Fast Fourier Transform on the CPU
+/// (multithreaded).
+///
+/// See [`serial_coset_ifft`] and [`parallel_fft`] for the meaning of the
+/// arguments.
+pub fn parallel_coset_ifft<F: PrimeField>(
+    a: &mut [F], worker: &Worker, omega: &F, g: &F, log_n: u32,
+    log_threads: u32,
+) {
+    let omega_inv = omega.inverse().expect("omega must be non-zero");
+    let g_inv = g.inverse().expect("coset generator must be non-zero");
+    let n_inv = F::from(a.len() as u64).inverse().unwrap();
+    parallel_fft(a, worker, &omega_inv, log_n, log_threads);
+    parallel_distribute_powers(a, worker, n_inv, &g_inv);
+}
+
+/// Multiplies `a[i]` by `c * g^i`.
+fn distribute_powers<F: Field>(a: &mut [F], c: F, g: &F) {
+    let mut power = c;
+    for a in a {
+        *a *= power;
+        power *= g;
+    }
+}
+
+fn parallel_distribute_powers<F: PrimeField>(
+    a: &mut [F], worker: &Worker, c: F, g: &F,
+) {
+    worker.scope(a.len(), |scope, chunk| {
+        for (idx, a) in a.chunks_mut(chunk).enumerate() {
+            scope.execute(move || {
+                let start = pow_vartime(g, &[(idx * chunk) as u64]) * c;
+                distribute_powers(a, start, g);
+            });
+        }
+    });
+}
+
+/// Reverses the lowest `log_n` bits of `i`.
+pub(crate) fn bitreverse(i: usize, log_n: u32) -> usize {
+    if log_n == 0 {
+        return 0;
+    }
+    i.reverse_bits() >> (usize::BITS - log_n)
+}
+
+/// Permutes `a` from natural into bit-reversed order, or vice versa.
+///
+/// The element at index `i` is swapped with the one at the index with the
+/// lowest `log_n` bits of `i` reversed, hence applying it twice restores the
+/// original order. It's the host counterpart of the permutation the GPU FFT
+/// does, for data that is already on the host. Large arrays are permuted with
+/// the threads of a [`Worker`]. The length of `a` must be `2^log_n`.
+pub fn bit_reverse_permute<T: Send>(a: &mut [T], log_n: u32) {
+    assert_eq!(a.len(), 1 << log_n);
+    let n = a.len();
+    if log_n < LOG_PARALLEL_PERMUTE {
+        for i in 0..n {
+            let rev = bitreverse(i, log_n);
+            if i < rev {
+                a.swap(i, rev);
+            }
+        }
+        return;
+    }
+
+    /// The elements that are shared between the threads.
+    struct Elements<T>(*mut T);
+    // Every pair of elements is only swapped by a single thread.
+    unsafe impl<T: Send> Send for Elements<T> {}
+    unsafe impl<T: Send> Sync for Elements<T> {}
+    impl<T> Elements<T> {
+        /// # Safety
+        ///
+        /// Both indices must be in bounds and no other thread may access the
+        /// elements at the same time.
+        unsafe fn swap(&self, i: usize, j: usize) {
+            std::ptr::swap(self.0.add(i), self.0.add(j));
+        }
+    }
+
+    let elements = Elements(a.as_mut_ptr());
+    Worker::new().scope(n, |scope, chunk| {
+        let elements = &elements;
+        for start in (0..n).step_by(chunk) {
+            scope.execute(move || {
+                for i in start..(start + chunk).min(n) {
+                    let rev = bitreverse(i, log_n);
+                    if i < rev {
+                        // SAFETY: Both indices are below `n` and the pair is
+                        // only swapped by the thread whose range contains the
+                        // smaller index.
+                        unsafe { elements.swap(i, rev) };
+                    }
+                }
+            });
+        }
+    });
+}
+
+/// Returns whether `reversed` is `natural` in bit-reversed order, see
+/// [`bit_reverse_permute`].
+///
+/// Large arrays are compared with the threads of a [`Worker`]. Both slices
+/// must have a length of `2^log_n`.
+pub fn is_bit_reversed<T: PartialEq + Sync>(
+    natural: &[T], reversed: &[T], log_n: u32,
+) -> bool {
+    assert_eq!(natural.len(), 1 << log_n);
+    assert_eq!(reversed.len(), 1 << log_n);
+    let matches_from = |start: usize, natural: &[T]| {
+        natural
+            .iter()
+            .enumerate()
+            .all(|(i, value)| *value == reversed[bitreverse(start + i, log_n)])
+    };
+    if log_n < LOG_PARALLEL_PERMUTE {
+        return matches_from(0, natural);
+    }
+
+    let matches = AtomicBool::new(true);
+    Worker::new().scope(natural.len(), |scope, chunk| {
+        let matches = &matches;
+        let matches_from = &matches_from;
+        for (idx, natural) in natural.chunks(chunk).enumerate() {
+            scope.execute(move || {
+                if !matches_from(idx * chunk, natural) {
+                    matches.store(false, Ordering::Relaxed);
+                }
+            });
+        }
+    });
+    matches.into_inner()
+}
+
 #[cfg(test)]
 mod tests {
     use ark_ff::FftField;
 
     fn omega<F: FftField>(num_coeffs: usize) -> F {
         // Compute omega, the 2^exp primitive root of unity
-        let exp = (num_coeffs as f32).log2().floor() as u32;
+        let exp = crate::log2_strict(num_coeffs);
         let mut omega = F::TWO_ADIC_ROOT_OF_UNITY;
         for _ in exp..F::TWO_ADICITY {
             omega = omega.square();
@@ -124,6 +300,48 @@ mod tests {
         omega
     }
 
+    #[test]
+    fn log2_floor_boundaries() {
+        use crate::log2_floor;
+        use ark_ff::Field;
+        use chosen_ark_suite::Fr;
+
+        for log_n in [1, 23, 24, 25, 31] {
+            let n = 1usize << log_n;
+            assert_eq!(log2_floor(n - 1), log_n - 1);
+            assert_eq!(log2_floor(n), log_n);
+            assert_eq!(log2_floor(n + 1), log_n);
+        }
+        // `(n as f32).log2().floor()` rounds this up to 25.
+        assert_eq!(log2_floor((1 << 25) - 1), 24);
+
+        let omega = omega::<Fr>(1 << 24);
+        assert_eq!(omega.pow([1u64 << 24]), Fr::ONE);
+        assert_ne!(omega.pow([1u64 << 23]), Fr::ONE);
+    }
+
+    #[test]
+    fn log2_strict_large_sizes() {
+        use crate::log2_strict;
+        use ark_ff::Field;
+        use chosen_ark_suite::Fr;
+
+        for log_n in [24, 25, 31] {
+            assert_eq!(log2_strict(1 << log_n), log_n);
+        }
+
+        // The sizes where the float based exponent used to be wrong.
+        for log_n in [24, 25, 26] {
+            let omega = omega::<Fr>(1 << log_n);
+            assert_eq!(omega.pow([1u64 << log_n]), Fr::ONE);
+            assert_ne!(omega.pow([1u64 << (log_n - 1)]), Fr::ONE);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "is not a power of two")]
+    fn log2_strict_non_power_of_two() { crate::log2_strict((1 << 24) + 1); }
+
     #[test]
     fn parallel_fft_consistency() {
         use super::*;
@@ -165,4 +383,90 @@ mod tests {
 
         test_consistency::<Fr, _>(rng);
     }
+
+    #[test]
+    fn bit_reverse_permute_involution() {
+        use super::*;
+
+        // Covers the single-threaded as well as the multithreaded path.
+        for log_n in [0, 1, 3, 10, LOG_PARALLEL_PERMUTE + 1] {
+            let natural = (0..1u32 << log_n).collect::<Vec<_>>();
+            let mut permuted = natural.clone();
+            bit_reverse_permute(&mut permuted, log_n);
+            for (i, value) in permuted.iter().enumerate() {
+                assert_eq!(*value as usize, bitreverse(i, log_n));
+            }
+            assert!(is_bit_reversed(&natural, &permuted, log_n));
+            assert!(is_bit_reversed(&permuted, &natural, log_n));
+            if log_n > 1 {
+                assert!(!is_bit_reversed(&natural, &natural, log_n));
+            }
+
+            bit_reverse_permute(&mut permuted, log_n);
+            assert_eq!(permuted, natural);
+        }
+    }
+
+    #[test]
+    fn test_bitreverse() {
+        use super::bitreverse;
+
+        assert_eq!(bitreverse(0, 0), 0);
+        assert_eq!(bitreverse(1, 1), 1);
+        assert_eq!(bitreverse(1, 3), 4);
+        assert_eq!(bitreverse(6, 3), 3);
+        for i in 0..1024 {
+            assert_eq!(bitreverse(bitreverse(i, 10), 10), i);
+        }
+    }
+
+    #[test]
+    fn coset_fft_consistency() {
+        use super::*;
+
+        use ark_ff::{FftField, UniformRand};
+        use ark_poly::{EvaluationDomain, Radix2EvaluationDomain};
+        use chosen_ark_suite::Fr;
+
+        let worker = Worker::new();
+        let rng = &mut rand::thread_rng();
+        let g = Fr::GENERATOR;
+
+        for log_d in 1..10 {
+            let d = 1 << log_d;
+            let coeffs =
+                (0..d).map(|_| Fr::rand(&mut *rng)).collect::<Vec<_>>();
+            let domain = Radix2EvaluationDomain::<Fr>::new(d).unwrap();
+            let coset = domain.get_coset(g).unwrap();
+            let expected = coset.fft(&coeffs);
+
+            let mut serial = coeffs.clone();
+            serial_coset_fft(&mut serial, &domain.group_gen, &g, log_d);
+            assert_eq!(serial, expected);
+
+            let log_threads = std::cmp::min(log_d, 2);
+            let mut parallel = coeffs.clone();
+            parallel_coset_fft(
+                &mut parallel,
+                &worker,
+                &domain.group_gen,
+                &g,
+                log_d,
+                log_threads,
+            );
+            assert_eq!(parallel, expected);
+
+            serial_coset_ifft(&mut serial, &domain.group_gen, &g, log_d);
+            assert_eq!(serial, coeffs);
+            parallel_coset_ifft(
+                &mut parallel,
+                &worker,
+                &domain.group_gen,
+                &g,
+                log_d,
+                log_threads,
+            );
+            assert_eq!(parallel, coeffs);
+        }
+    }
 }
@@ -0,0 +1,105 @@
+use ark_ff::Field;
+
+/// Returns `base^exp`, with the exponent given as little-endian 64-bit limbs.
+///
+/// It's the square-and-multiply algorithm, which only multiplies for the set
+/// bits of the exponent. The running time therefore depends on the exponent,
+/// use it for public exponents only, e.g. when computing the twiddle factors
+/// of an FFT from a root of unity. For secret exponents use
+/// [`pow_const_time`].
+///
+/// ```
+/// use ark_bls12_381::Fr;
+/// use ark_ff::Field;
+/// use ec_gpu_proxy::field::pow_vartime;
+///
+/// let base = Fr::from(3u64);
+/// assert_eq!(pow_vartime(&base, [5u64]), Fr::from(243u64));
+/// assert_eq!(pow_vartime(&base, [5u64]), base.pow([5u64]));
+/// ```
+pub fn pow_vartime<F: Field, S: AsRef<[u64]>>(base: &F, exp: S) -> F {
+    let mut res = F::ONE;
+    for e in exp.as_ref().iter().rev() {
+        for i in (0..64).rev() {
+            res = res.square();
+
+            if ((*e >> i) & 1) == 1 {
+                res.mul_assign(base);
+            }
+        }
+    }
+
+    res
+}
+
+/// Returns `base^exp`, with the exponent given as little-endian 64-bit limbs.
+///
+/// Unlike [`pow_vartime`] it's a Montgomery ladder, which does one
+/// multiplication and one squaring for every bit of `exp`, including the
+/// leading zeros of the limbs. The bits only select which of the two ladder
+/// values are swapped, which is done arithmetically, without branching on
+/// them. Hence the sequence of field operations only depends on the number of
+/// limbs, not on their values. Whether the operations themselves run in
+/// constant time is up to the field implementation.
+pub fn pow_const_time<F: Field, S: AsRef<[u64]>>(base: &F, exp: S) -> F {
+    // Invariant: `r1 = r0 * base`, up to the pending swap.
+    let mut r0 = F::ONE;
+    let mut r1 = *base;
+    let mut swapped = 0;
+    for e in exp.as_ref().iter().rev() {
+        for i in (0..64).rev() {
+            let bit = (*e >> i) & 1;
+            // Consecutive swaps cancel out, only a change of the bit swaps.
+            conditional_swap(&mut r0, &mut r1, swapped ^ bit);
+            swapped = bit;
+            r1.mul_assign(&r0);
+            r0.square_in_place();
+        }
+    }
+    conditional_swap(&mut r0, &mut r1, swapped);
+
+    r0
+}
+
+/// Swaps `a` and `b` if `choice` is one and keeps them if it's zero.
+///
+/// The choice is multiplied into the difference of the values, so that there
+/// is no branch on it.
+fn conditional_swap<F: Field>(a: &mut F, b: &mut F, choice: u64) {
+    let t = (*a - *b) * F::from(choice);
+    *a -= t;
+    *b += t;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chosen_ark_suite::{Fq, Fr};
+    use rand_core::RngCore;
+
+    fn test_pow<F: Field>() {
+        let mut rng = rand::thread_rng();
+        for limbs in 0..5 {
+            let base = F::rand(&mut rng);
+            let exp = (0..limbs).map(|_| rng.next_u64()).collect::<Vec<_>>();
+            let expected = base.pow(&exp);
+            assert_eq!(pow_vartime(&base, &exp), expected);
+            assert_eq!(pow_const_time(&base, &exp), expected);
+        }
+
+        let base = F::rand(&mut rng);
+        for exp in [0u64, 1, 2, u64::MAX] {
+            assert_eq!(pow_vartime(&base, [exp]), base.pow([exp]));
+            assert_eq!(pow_const_time(&base, [exp]), base.pow([exp]));
+        }
+        assert_eq!(pow_const_time(&F::ZERO, [0u64]), F::ONE);
+        assert_eq!(pow_const_time(&F::ZERO, [3u64]), F::ZERO);
+    }
+
+    #[test]
+    fn test_pow_fr() { test_pow::<Fr>() }
+
+    #[test]
+    fn test_pow_fq() { test_pow::<Fq>() }
+}
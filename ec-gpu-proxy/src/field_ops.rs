@@ -0,0 +1,926 @@
+use ag_types::GpuName;
+use ark_ff::PrimeField;
+use log::info;
+use rust_gpu_tools::{program_closures, LocalBuffer, Program};
+
+use crate::{
+    buffer::{next_owner_id, BackendBuffer, BufferOwner, DeviceBuffer},
+    device::{run_checked, share, working_kernels, SharedProgram},
+    fft::{div_ceil, elementwise_work_size, transpose_tiled},
+};
+use ec_gpu_program::{EcError, EcResult};
+
+/// The maximum number of values a single thread sums up in
+/// [`SingleFieldOpsKernel::segmented_sum`].
+const SUM_CHUNK_LEN: usize = 256;
+
+/// The number of coefficients a single thread scans in
+/// [`SingleFieldOpsKernel::degree`].
+const DEGREE_CHUNK_LEN: usize = 256;
+
+/// The number of elements a single thread inverts in
+/// [`SingleFieldOpsKernel::batch_invert`].
+const INVERT_CHUNK_LEN: usize = 256;
+
+/// The maximum stack depth an expression of
+/// [`SingleFieldOpsKernel::eval_gate`] may need. It must match the value in
+/// the GPU code.
+const GATE_MAX_STACK: usize = 16;
+
+/// The opcodes of the stack machine that evaluates a [`GateExpr`] on the GPU.
+const GATE_OP_COLUMN: u32 = 0;
+const GATE_OP_COEFF: u32 = 1;
+const GATE_OP_ADD: u32 = 2;
+const GATE_OP_SUB: u32 = 3;
+const GATE_OP_MUL: u32 = 4;
+
+/// An expression over columns and coefficients, e.g. a custom gate of a
+/// PLONKish constraint system.
+///
+/// It is evaluated for every row of the columns by
+/// [`FieldOps::eval_gate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GateExpr {
+    /// The value of the column with the given index in the current row.
+    Column(usize),
+    /// The coefficient with the given index.
+    Coeff(usize),
+    /// The sum of two expressions.
+    Add(Box<GateExpr>, Box<GateExpr>),
+    /// The difference of two expressions.
+    Sub(Box<GateExpr>, Box<GateExpr>),
+    /// The product of two expressions.
+    Mul(Box<GateExpr>, Box<GateExpr>),
+}
+
+impl GateExpr {
+    /// Evaluates the expression for the given `row` of the `columns` on the
+    /// host.
+    pub fn evaluate<F: PrimeField>(
+        &self, columns: &[&[F]], coeffs: &[F], row: usize,
+    ) -> F {
+        match self {
+            GateExpr::Column(index) => columns[*index][row],
+            GateExpr::Coeff(index) => coeffs[*index],
+            GateExpr::Add(lhs, rhs) => {
+                lhs.evaluate(columns, coeffs, row)
+                    + rhs.evaluate(columns, coeffs, row)
+            }
+            GateExpr::Sub(lhs, rhs) => {
+                lhs.evaluate(columns, coeffs, row)
+                    - rhs.evaluate(columns, coeffs, row)
+            }
+            GateExpr::Mul(lhs, rhs) => {
+                lhs.evaluate(columns, coeffs, row)
+                    * rhs.evaluate(columns, coeffs, row)
+            }
+        }
+    }
+
+    /// Appends the instructions of the stack machine, which are pairs of an
+    /// opcode and an operand, in postfix order to `code`.
+    ///
+    /// `depth` is the stack depth before the expression is evaluated. Returns
+    /// the maximum depth during its evaluation.
+    fn compile(
+        &self, num_columns: usize, num_coeffs: usize, depth: usize,
+        code: &mut Vec<u32>,
+    ) -> EcResult<usize> {
+        let (op, lhs, rhs) = match self {
+            GateExpr::Column(index) => {
+                if *index >= num_columns {
+                    return Err(EcError::Simple("A column is out of range"));
+                }
+                code.extend([GATE_OP_COLUMN, *index as u32]);
+                return Ok(depth + 1);
+            }
+            GateExpr::Coeff(index) => {
+                if *index >= num_coeffs {
+                    return Err(EcError::Simple(
+                        "A coefficient is out of range",
+                    ));
+                }
+                code.extend([GATE_OP_COEFF, *index as u32]);
+                return Ok(depth + 1);
+            }
+            GateExpr::Add(lhs, rhs) => (GATE_OP_ADD, lhs, rhs),
+            GateExpr::Sub(lhs, rhs) => (GATE_OP_SUB, lhs, rhs),
+            GateExpr::Mul(lhs, rhs) => (GATE_OP_MUL, lhs, rhs),
+        };
+        let lhs_depth = lhs.compile(num_columns, num_coeffs, depth, code)?;
+        let rhs_depth =
+            rhs.compile(num_columns, num_coeffs, depth + 1, code)?;
+        code.extend([op, 0]);
+        Ok(std::cmp::max(lhs_depth, rhs_depth))
+    }
+}
+
+/// The constants of a field as they are defined in the GPU code, see
+/// [`FieldOps::constants`].
+///
+/// The GPU stores elements in Montgomery form, just like arkworks does on the
+/// host. Every constant is the element whose host representation has the
+/// same bits as the constant on the GPU, hence it can be passed to kernels
+/// as it is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeviceConstants<F> {
+    /// `FIELD_ZERO`, which is zero in both forms.
+    pub zero: F,
+    /// `FIELD_ONE`, the Montgomery form of one, i.e. `R mod p`. It's the
+    /// identity of the multiplication on the GPU and equals `F::ONE`.
+    pub one: F,
+    /// `FIELD_ONE_NORMAL`, the literal one in normal form. Multiplying with
+    /// it converts an element from Montgomery into normal form, as an element
+    /// it's `R^-1`.
+    pub one_normal: F,
+    /// `FIELD_R2`, i.e. `R^2 mod p`. Multiplying with it converts an element
+    /// from normal into Montgomery form, as an element it's `R`.
+    pub r2: F,
+}
+
+/// Element-wise field operations kernel for a single GPU.
+pub struct SingleFieldOpsKernel<'a, F>
+where F: PrimeField + GpuName
+{
+    program: SharedProgram,
+    /// An optional function which will be called at places where it is
+    /// possible to abort the calculations. If it returns true, the
+    /// calculation will be aborted with an [`EcError::Aborted`].
+    maybe_abort: Option<&'a (dyn Fn() -> bool + Send + Sync)>,
+    /// Identifies the [`DeviceBuffer`]s this kernel created.
+    id: usize,
+    _phantom: std::marker::PhantomData<F>,
+}
+
+impl<'a, F: PrimeField + GpuName> SingleFieldOpsKernel<'a, F> {
+    /// Create a new field operations instance for the given device.
+    ///
+    /// The `maybe_abort` function is called when it is possible to abort the
+    /// computation, without leaving the GPU in a weird state. If that
+    /// function returns `true`, execution is aborted.
+    pub fn create(
+        program: impl Into<SharedProgram>,
+        maybe_abort: Option<&'a (dyn Fn() -> bool + Send + Sync)>,
+    ) -> EcResult<Self> {
+        Ok(SingleFieldOpsKernel {
+            program: program.into(),
+            maybe_abort,
+            id: next_owner_id(),
+            _phantom: Default::default(),
+        })
+    }
+
+    /// Copies `data` into GPU memory.
+    pub fn upload(&mut self, data: &[F]) -> EcResult<DeviceBuffer<F>> {
+        let owner = BufferOwner::new(self.id, &self.program);
+        let closures =
+            program_closures!(|program, _arg| -> EcResult<DeviceBuffer<F>> {
+                let buffer = program.create_buffer_from_slice(data)?;
+                Ok(program.wrap_buffer(buffer, data.len(), owner))
+            });
+
+        run_checked!(self.program, closures, ())
+    }
+
+    /// Copies `buffer` from GPU memory to the host.
+    ///
+    /// The buffer must have been created by this kernel.
+    pub fn download(&mut self, buffer: DeviceBuffer<F>) -> EcResult<Vec<F>> {
+        let owner = BufferOwner::new(self.id, &self.program);
+        let len = buffer.len();
+        let closures =
+            program_closures!(|program, buffer| -> EcResult<Vec<F>> {
+                let buffer = program.unwrap_buffer(buffer, owner)?;
+                let mut data = vec![F::ZERO; len];
+                program.read_into_buffer(&buffer, &mut data)?;
+                Ok(data)
+            });
+
+        run_checked!(self.program, closures, buffer)
+    }
+
+    /// Transposes the `rows` x `cols` matrix in `values`, which is stored row
+    /// by row. The result is the `cols` x `rows` matrix, also stored row by
+    /// row.
+    ///
+    /// The matrix is split into tiles of 16 x 16 elements, which are
+    /// transposed in local memory, the same kernel transposes the matrix of
+    /// `SingleFftKernel::fft_2d`. The dimensions don't need to be multiples
+    /// of the tile size. The buffer must have been created by this kernel and
+    /// must have `rows * cols` elements.
+    pub fn transpose(
+        &mut self, values: DeviceBuffer<F>, rows: usize, cols: usize,
+    ) -> EcResult<DeviceBuffer<F>> {
+        if values.len() != rows * cols {
+            return Err(EcError::Simple(
+                "The buffer doesn't have rows * cols elements",
+            ));
+        }
+        if values.is_empty() {
+            return Ok(values);
+        }
+        if let Some(maybe_abort) = &self.maybe_abort {
+            if maybe_abort() {
+                return Err(EcError::Aborted);
+            }
+        }
+
+        let n = rows * cols;
+        let owner = BufferOwner::new(self.id, &self.program);
+        let closures =
+            program_closures!(|program, values| -> EcResult<DeviceBuffer<F>> {
+                let src_buffer = program.unwrap_buffer(values, owner)?;
+                // It is safe as the GPU will initialize that buffer
+                let dst_buffer = unsafe { program.create_buffer::<F>(n)? };
+
+                transpose_tiled!(program, &src_buffer, &dst_buffer, rows, cols);
+
+                Ok(program.wrap_buffer(dst_buffer, n, owner))
+            });
+
+        run_checked!(self.program, closures, values)
+    }
+
+    /// Reads the constants of the field from the GPU code.
+    pub fn constants(&mut self) -> EcResult<DeviceConstants<F>> {
+        let closures = program_closures!(|program, _arg| -> EcResult<Vec<F>> {
+            // It is safe as the GPU will initialize that buffer
+            let buffer = unsafe { program.create_buffer::<F>(4)? };
+            let kernel = program.create_kernel(
+                &format!("{}_constants", F::name()),
+                1,
+                1,
+            )?;
+            kernel.arg(&buffer).run()?;
+
+            let mut constants = vec![F::ZERO; 4];
+            program.read_into_buffer(&buffer, &mut constants)?;
+            Ok(constants)
+        });
+
+        let constants = run_checked!(self.program, closures, ())?;
+        Ok(DeviceConstants {
+            zero: constants[0],
+            one: constants[1],
+            one_normal: constants[2],
+            r2: constants[3],
+        })
+    }
+
+    /// Raises all `bases` to the power of `exp`.
+    ///
+    /// The exponent is shared by all bases. An exponent of zero results in
+    /// ones.
+    pub fn pow_many(
+        &mut self, bases: &[F], exp: F::BigInt,
+    ) -> EcResult<Vec<F>> {
+        if bases.is_empty() {
+            return Ok(Vec::new());
+        }
+        if let Some(maybe_abort) = &self.maybe_abort {
+            if maybe_abort() {
+                return Err(EcError::Aborted);
+            }
+        }
+
+        let n = bases.len();
+        let closures = program_closures!(|program, _arg| -> EcResult<Vec<F>> {
+            let bases_buffer = program.create_buffer_from_slice(bases)?;
+            let exp_buffer = program.create_buffer_from_slice(&[exp])?;
+            // It is safe as the GPU will initialize that buffer
+            let results_buffer = unsafe { program.create_buffer::<F>(n)? };
+
+            let (global_work_size, local_work_size) = elementwise_work_size(n);
+            let kernel = program.create_kernel(
+                &format!("{}_pow_many", F::name()),
+                global_work_size,
+                local_work_size,
+            )?;
+            kernel
+                .arg(&bases_buffer)
+                .arg(&exp_buffer)
+                .arg(&results_buffer)
+                .arg(&(n as u32))
+                .run()?;
+
+            let mut results = vec![F::ZERO; n];
+            program.read_into_buffer(&results_buffer, &mut results)?;
+
+            Ok(results)
+        });
+
+        run_checked!(self.program, closures, ())
+    }
+
+    /// Sums up the `values` grouped by their `keys`.
+    ///
+    /// The result has `num_keys` entries, the sum of the values with key `i`
+    /// is at index `i`. The values are sorted by key on the host, then the
+    /// keys are split into chunks of at most `SUM_CHUNK_LEN` values, which
+    /// are summed up by one thread each.
+    pub fn segmented_sum(
+        &mut self, values: &[F], keys: &[u32], num_keys: usize,
+    ) -> EcResult<Vec<F>> {
+        assert_eq!(values.len(), keys.len());
+        if keys.iter().any(|&key| key as usize >= num_keys) {
+            return Err(EcError::Simple("A key is out of range"));
+        }
+        let mut sums = vec![F::ZERO; num_keys];
+        if values.is_empty() {
+            return Ok(sums);
+        }
+        if let Some(maybe_abort) = &self.maybe_abort {
+            if maybe_abort() {
+                return Err(EcError::Aborted);
+            }
+        }
+
+        // Counting sort of the indices of the values by their key.
+        let mut offsets = vec![0u32; num_keys + 1];
+        for &key in keys {
+            offsets[key as usize + 1] += 1;
+        }
+        for i in 0..num_keys {
+            offsets[i + 1] += offsets[i];
+        }
+        let mut next = offsets.clone();
+        let mut order = vec![0u32; values.len()];
+        for (i, &key) in keys.iter().enumerate() {
+            order[next[key as usize] as usize] = i as u32;
+            next[key as usize] += 1;
+        }
+
+        // Every chunk is described by its start and its end within `order`.
+        let mut chunks = Vec::new();
+        // The key every chunk belongs to.
+        let mut chunk_keys = Vec::new();
+        for key in 0..num_keys {
+            let (start, end) = (offsets[key], offsets[key + 1]);
+            for chunk_start in (start..end).step_by(SUM_CHUNK_LEN) {
+                let chunk_end =
+                    std::cmp::min(chunk_start + SUM_CHUNK_LEN as u32, end);
+                chunks.extend([chunk_start, chunk_end]);
+                chunk_keys.push(key);
+            }
+        }
+        let num_chunks = chunk_keys.len();
+
+        let closures = program_closures!(|program, _arg| -> EcResult<Vec<F>> {
+            let values_buffer = program.create_buffer_from_slice(values)?;
+            let order_buffer = program.create_buffer_from_slice(&order)?;
+            let chunks_buffer = program.create_buffer_from_slice(&chunks)?;
+            // It is safe as the GPU will initialize that buffer
+            let results_buffer =
+                unsafe { program.create_buffer::<F>(num_chunks)? };
+
+            let (global_work_size, local_work_size) =
+                elementwise_work_size(num_chunks);
+            let kernel = program.create_kernel(
+                &format!("{}_segmented_sum", F::name()),
+                global_work_size,
+                local_work_size,
+            )?;
+            kernel
+                .arg(&values_buffer)
+                .arg(&order_buffer)
+                .arg(&chunks_buffer)
+                .arg(&results_buffer)
+                .arg(&(num_chunks as u32))
+                .run()?;
+
+            let mut results = vec![F::ZERO; num_chunks];
+            program.read_into_buffer(&results_buffer, &mut results)?;
+
+            Ok(results)
+        });
+
+        let partials = run_checked!(self.program, closures, ())?;
+        for (partial, key) in partials.iter().zip(chunk_keys) {
+            sums[key] += partial;
+        }
+        Ok(sums)
+    }
+
+    /// Returns the index of the last nonzero coefficient, or `None` if all
+    /// of them are zero.
+    ///
+    /// The coefficients are split into chunks of `DEGREE_CHUNK_LEN`, every
+    /// thread finds the last nonzero coefficient of its chunk. Only these
+    /// per-chunk results are read back, their maximum is taken on the host.
+    pub fn degree(&mut self, coeffs: &[F]) -> EcResult<Option<usize>> {
+        if coeffs.is_empty() {
+            return Ok(None);
+        }
+        if let Some(maybe_abort) = &self.maybe_abort {
+            if maybe_abort() {
+                return Err(EcError::Aborted);
+            }
+        }
+
+        let n = coeffs.len();
+        let num_chunks = div_ceil(n, DEGREE_CHUNK_LEN);
+        let closures =
+            program_closures!(|program, _arg| -> EcResult<Vec<u32>> {
+                let coeffs_buffer = program.create_buffer_from_slice(coeffs)?;
+                // It is safe as the GPU will initialize that buffer
+                let results_buffer =
+                    unsafe { program.create_buffer::<u32>(num_chunks)? };
+
+                let (global_work_size, local_work_size) =
+                    elementwise_work_size(num_chunks);
+                let kernel = program.create_kernel(
+                    &format!("{}_last_nonzero", F::name()),
+                    global_work_size,
+                    local_work_size,
+                )?;
+                kernel
+                    .arg(&coeffs_buffer)
+                    .arg(&(n as u32))
+                    .arg(&(DEGREE_CHUNK_LEN as u32))
+                    .arg(&results_buffer)
+                    .arg(&(num_chunks as u32))
+                    .run()?;
+
+                let mut results = vec![0u32; num_chunks];
+                program.read_into_buffer(&results_buffer, &mut results)?;
+
+                Ok(results)
+            });
+
+        let ends = run_checked!(self.program, closures, ())?;
+        // The results are one past the last nonzero index, zero means none.
+        Ok(ends
+            .into_iter()
+            .max()
+            .and_then(|end| (end as usize).checked_sub(1)))
+    }
+
+    /// Replaces all `values` with their inverses, zeros stay zero.
+    ///
+    /// The values are split into chunks of `INVERT_CHUNK_LEN`, every thread
+    /// inverts its chunk with Montgomery's trick, i.e. with a single
+    /// inversion and three multiplications per element.
+    pub fn batch_invert(&mut self, values: &mut [F]) -> EcResult<()> {
+        if values.is_empty() {
+            return Ok(());
+        }
+        if let Some(maybe_abort) = &self.maybe_abort {
+            if maybe_abort() {
+                return Err(EcError::Aborted);
+            }
+        }
+
+        let n = values.len();
+        let num_chunks = div_ceil(n, INVERT_CHUNK_LEN);
+        let closures =
+            program_closures!(|program, values: &mut [F]| -> EcResult<()> {
+                let values_buffer = program.create_buffer_from_slice(values)?;
+                // It is safe as the GPU will initialize that buffer
+                let prefix_buffer = unsafe { program.create_buffer::<F>(n)? };
+
+                let (global_work_size, local_work_size) =
+                    elementwise_work_size(num_chunks);
+                let kernel = program.create_kernel(
+                    &format!("{}_batch_invert", F::name()),
+                    global_work_size,
+                    local_work_size,
+                )?;
+                kernel
+                    .arg(&values_buffer)
+                    .arg(&prefix_buffer)
+                    .arg(&(n as u32))
+                    .arg(&(INVERT_CHUNK_LEN as u32))
+                    .arg(&(num_chunks as u32))
+                    .run()?;
+
+                program.read_into_buffer(&values_buffer, values)?;
+
+                Ok(())
+            });
+
+        run_checked!(self.program, closures, values)
+    }
+
+    /// Evaluates the `gate` expression for every row of the `columns`.
+    ///
+    /// All columns need to have the same number of rows. The expression is
+    /// compiled into instructions of a stack machine, which are interpreted
+    /// on the GPU, so that all its operations are done in a single pass. Its
+    /// evaluation must not need a stack that is deeper than
+    /// `GATE_MAX_STACK`.
+    pub fn eval_gate(
+        &mut self, gate: &GateExpr, columns: &[&[F]], coeffs: &[F],
+    ) -> EcResult<Vec<F>> {
+        let mut code = Vec::new();
+        let max_depth =
+            gate.compile(columns.len(), coeffs.len(), 0, &mut code)?;
+        if max_depth > GATE_MAX_STACK {
+            return Err(EcError::Simple("The gate expression is too deep"));
+        }
+        let n = match columns.first() {
+            Some(column) => column.len(),
+            None => return Err(EcError::Simple("There are no columns")),
+        };
+        if columns.iter().any(|column| column.len() != n) {
+            return Err(EcError::Simple("The columns differ in length"));
+        }
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+        if let Some(maybe_abort) = &self.maybe_abort {
+            if maybe_abort() {
+                return Err(EcError::Aborted);
+            }
+        }
+
+        // The columns are uploaded as a single buffer, one after another.
+        let columns = columns.concat();
+        // The buffer of the coefficients must not be empty.
+        let coeffs = if coeffs.is_empty() {
+            &[F::ZERO][..]
+        } else {
+            coeffs
+        };
+        let code_len = code.len() / 2;
+
+        let closures = program_closures!(|program, _arg| -> EcResult<Vec<F>> {
+            let columns_buffer = program.create_buffer_from_slice(&columns)?;
+            let coeffs_buffer = program.create_buffer_from_slice(coeffs)?;
+            let code_buffer = program.create_buffer_from_slice(&code)?;
+            // It is safe as the GPU will initialize that buffer
+            let results_buffer = unsafe { program.create_buffer::<F>(n)? };
+
+            let (global_work_size, local_work_size) = elementwise_work_size(n);
+            let kernel = program.create_kernel(
+                &format!("{}_eval_gate", F::name()),
+                global_work_size,
+                local_work_size,
+            )?;
+            kernel
+                .arg(&columns_buffer)
+                .arg(&coeffs_buffer)
+                .arg(&code_buffer)
+                .arg(&(code_len as u32))
+                .arg(&results_buffer)
+                .arg(&(n as u32))
+                .run()?;
+
+            let mut results = vec![F::ZERO; n];
+            program.read_into_buffer(&results_buffer, &mut results)?;
+
+            Ok(results)
+        });
+
+        run_checked!(self.program, closures, ())
+    }
+}
+
+/// One field operations kernel for each GPU available.
+pub struct FieldOps<'a, F>
+where F: PrimeField + GpuName
+{
+    kernels: Vec<SingleFieldOpsKernel<'a, F>>,
+}
+
+impl<'a, F> FieldOps<'a, F>
+where F: PrimeField + GpuName
+{
+    /// Create new kernels, one for each given device.
+    ///
+    /// The programs need to contain the kernels generated by
+    /// `SourceBuilder::add_field_ops`.
+    pub fn create(programs: Vec<Program>) -> EcResult<Self> {
+        Self::create_optional_abort(share(programs), None)
+    }
+
+    /// Create new kernels, one for each given device, with early abort hook.
+    ///
+    /// The `maybe_abort` function is called when it is possible to abort the
+    /// computation, without leaving the GPU in a weird state. If that
+    /// function returns `true`, execution is aborted.
+    pub fn create_with_abort(
+        programs: Vec<Program>,
+        maybe_abort: &'a (dyn Fn() -> bool + Send + Sync),
+    ) -> EcResult<Self> {
+        Self::create_optional_abort(share(programs), Some(maybe_abort))
+    }
+
+    /// Create new kernels from programs that may be shared with other
+    /// kernels, one for each given device.
+    ///
+    /// See [`SharedProgram`] for how to use a single program for several
+    /// kinds of kernels.
+    pub fn create_shared(programs: Vec<SharedProgram>) -> EcResult<Self> {
+        Self::create_optional_abort(programs, None)
+    }
+
+    fn create_optional_abort(
+        programs: Vec<SharedProgram>,
+        maybe_abort: Option<&'a (dyn Fn() -> bool + Send + Sync)>,
+    ) -> EcResult<Self> {
+        let kernels = working_kernels(programs.into_iter().map(|program| {
+            let device_name = program.device_name().to_string();
+            let kernel =
+                SingleFieldOpsKernel::<F>::create(program, maybe_abort);
+            (device_name, kernel)
+        }))?;
+
+        info!("FieldOps: {} working device(s) selected. ", kernels.len());
+        for (i, k) in kernels.iter().enumerate() {
+            info!("FieldOps: Device {}: {}", i, k.program.device_name(),);
+        }
+
+        Ok(Self { kernels })
+    }
+
+    /// Reads the constants of the field from the GPU code.
+    ///
+    /// All kernels do their arithmetic in Montgomery form, where
+    /// [`DeviceConstants::one`] is one. [`DeviceConstants::one_normal`] is
+    /// the literal one in normal form, which is only used to convert out of
+    /// Montgomery form. Mixing them up silently scales the results by `R` or
+    /// `R^-1`.
+    ///
+    /// Uses the first available GPU.
+    pub fn constants(&mut self) -> EcResult<DeviceConstants<F>> {
+        self.kernels[0].constants()
+    }
+
+    /// Raises all `bases` to the power of `exp`.
+    ///
+    /// This is the GPU counterpart of a square-and-multiply exponentiation
+    /// with a runtime exponent. An exponent of zero results in ones.
+    ///
+    /// Uses the first available GPU.
+    pub fn pow_many(
+        &mut self, bases: &[F], exp: F::BigInt,
+    ) -> EcResult<Vec<F>> {
+        self.kernels[0].pow_many(bases, exp)
+    }
+
+    /// Sums up the `values` grouped by their `keys`, which need to be smaller
+    /// than `num_keys`.
+    ///
+    /// Returns one sum per key, keys without values sum up to zero. A key
+    /// that is out of range results in an error.
+    ///
+    /// Uses the first available GPU.
+    pub fn segmented_sum(
+        &mut self, values: &[F], keys: &[u32], num_keys: usize,
+    ) -> EcResult<Vec<F>> {
+        self.kernels[0].segmented_sum(values, keys, num_keys)
+    }
+
+    /// Returns the degree of the polynomial with the given `coeffs`, i.e. the
+    /// index of its highest nonzero coefficient, ignoring trailing zeros.
+    ///
+    /// The zero polynomial, including an empty one, has no degree and results
+    /// in `None`. The search runs on the GPU, only a small number of partial
+    /// results are read back instead of all coefficients.
+    ///
+    /// Uses the first available GPU.
+    pub fn degree(&mut self, coeffs: &[F]) -> EcResult<Option<usize>> {
+        self.kernels[0].degree(coeffs)
+    }
+
+    /// Replaces all `values` with their inverses on the GPU, e.g. to
+    /// normalize many elements after an FFT without a round trip through the
+    /// host.
+    ///
+    /// Zeros have no inverse, they are left in place instead of resulting in
+    /// an error.
+    ///
+    /// Uses the first available GPU.
+    pub fn batch_invert(&mut self, values: &mut [F]) -> EcResult<()> {
+        self.kernels[0].batch_invert(values)
+    }
+
+    /// Evaluates the `gate` expression, e.g. a custom gate of a PLONKish
+    /// constraint system, for every row of the `columns`.
+    ///
+    /// This replaces a separate elementwise pass for every operation of the
+    /// expression with a single one. The result has one entry per row. An
+    /// expression that refers to a column or coefficient that doesn't exist,
+    /// that is too deep, missing columns or columns of different length
+    /// result in an error.
+    ///
+    /// Uses the first available GPU.
+    pub fn eval_gate(
+        &mut self, gate: &GateExpr, columns: &[&[F]], coeffs: &[F],
+    ) -> EcResult<Vec<F>> {
+        self.kernels[0].eval_gate(gate, columns, coeffs)
+    }
+
+    /// Copies `data` into GPU memory, to be used with
+    /// [`FieldOps::transpose`].
+    ///
+    /// Uses the first available GPU.
+    pub fn upload(&mut self, data: &[F]) -> EcResult<DeviceBuffer<F>> {
+        self.kernels[0].upload(data)
+    }
+
+    /// Copies `buffer` from GPU memory to the host.
+    ///
+    /// The buffer must have been created by these kernels. Uses the first
+    /// available GPU.
+    pub fn download(&mut self, buffer: DeviceBuffer<F>) -> EcResult<Vec<F>> {
+        self.kernels[0].download(buffer)
+    }
+
+    /// Transposes the `rows` x `cols` matrix in `values`, which is stored row
+    /// by row, without transferring it to the host.
+    ///
+    /// Returns the `cols` x `rows` transpose, also stored row by row. Any
+    /// dimensions are supported, the matrix doesn't need to be square. A
+    /// buffer that doesn't have `rows * cols` elements or was created by
+    /// other kernels results in an error.
+    ///
+    /// Uses the first available GPU. See [`SingleFieldOpsKernel::transpose`].
+    pub fn transpose(
+        &mut self, values: DeviceBuffer<F>, rows: usize, cols: usize,
+    ) -> EcResult<DeviceBuffer<F>> {
+        self.kernels[0].transpose(values, rows, cols)
+    }
+}
+
+/// Converts field elements that are in GPU memory from and to their
+/// canonical byte encoding, without transferring them to the host.
+///
+/// The encoding is the same as the one of arkworks' `CanonicalSerialize`,
+/// the little-endian bytes of the integer an element represents, see
+/// [`FieldCodec::bytes_per_element`]. The program must contain the kernels of
+/// the field, i.e. its source needs to be built with
+/// `SourceBuilder::add_field_bytes::<F>()`. As the [`DeviceBuffer`]s are tied
+/// to a device, a codec uses a single one.
+pub struct FieldCodec<'a, F>
+where F: PrimeField + GpuName
+{
+    program: SharedProgram,
+    /// An optional function which will be called at places where it is
+    /// possible to abort the calculations. If it returns true, the
+    /// calculation will be aborted with an [`EcError::Aborted`].
+    maybe_abort: Option<&'a (dyn Fn() -> bool + Send + Sync)>,
+    /// Identifies the [`DeviceBuffer`]s this codec created.
+    id: usize,
+    _phantom: std::marker::PhantomData<F>,
+}
+
+impl<'a, F: PrimeField + GpuName> FieldCodec<'a, F> {
+    /// Create a new codec for the given device.
+    ///
+    /// The `maybe_abort` function is called when it is possible to abort the
+    /// computation, without leaving the GPU in a weird state. If that
+    /// function returns `true`, execution is aborted.
+    pub fn create(
+        program: impl Into<SharedProgram>,
+        maybe_abort: Option<&'a (dyn Fn() -> bool + Send + Sync)>,
+    ) -> EcResult<Self> {
+        Ok(FieldCodec {
+            program: program.into(),
+            maybe_abort,
+            id: next_owner_id(),
+            _phantom: Default::default(),
+        })
+    }
+
+    /// Returns the number of bytes of the encoding of a single element.
+    ///
+    /// It's the number of bits of the modulus, rounded up to whole bytes.
+    pub fn bytes_per_element() -> usize {
+        (F::MODULUS_BIT_SIZE as usize + 7) / 8
+    }
+
+    /// Copies `data` into GPU memory.
+    pub fn upload<T>(&mut self, data: &[T]) -> EcResult<DeviceBuffer<T>> {
+        let owner = BufferOwner::new(self.id, &self.program);
+        let closures =
+            program_closures!(|program, _arg| -> EcResult<DeviceBuffer<T>> {
+                let buffer = program.create_buffer_from_slice(data)?;
+                Ok(program.wrap_buffer(buffer, data.len(), owner))
+            });
+
+        run_checked!(self.program, closures, ())
+    }
+
+    /// Copies `buffer` from GPU memory to the host.
+    ///
+    /// The buffer must have been created by this codec.
+    pub fn download<T>(&mut self, buffer: DeviceBuffer<T>) -> EcResult<Vec<T>>
+    where T: Clone + Default {
+        let owner = BufferOwner::new(self.id, &self.program);
+        let len = buffer.len();
+        let closures =
+            program_closures!(|program, buffer| -> EcResult<Vec<T>> {
+                let buffer = program.unwrap_buffer(buffer, owner)?;
+                let mut data = vec![T::default(); len];
+                program.read_into_buffer(&buffer, &mut data)?;
+                Ok(data)
+            });
+
+        run_checked!(self.program, closures, buffer)
+    }
+
+    /// Encodes the `values`, the encodings are stored one after another.
+    ///
+    /// The buffer must have been created by this codec.
+    pub fn to_bytes(
+        &mut self, values: DeviceBuffer<F>,
+    ) -> EcResult<DeviceBuffer<u8>> {
+        if let Some(maybe_abort) = &self.maybe_abort {
+            if maybe_abort() {
+                return Err(EcError::Aborted);
+            }
+        }
+
+        let n = values.len();
+        let num_bytes = n * Self::bytes_per_element();
+        let owner = BufferOwner::new(self.id, &self.program);
+        let closures =
+            program_closures!(|program,
+                               values|
+             -> EcResult<DeviceBuffer<u8>> {
+                let values_buffer = program.unwrap_buffer(values, owner)?;
+                // It is safe as the GPU will initialize that buffer
+                let bytes_buffer =
+                    unsafe { program.create_buffer::<u8>(num_bytes)? };
+
+                if n > 0 {
+                    let (global_work_size, local_work_size) =
+                        elementwise_work_size(n);
+                    let kernel = program.create_kernel(
+                        &format!("{}_to_bytes", F::name()),
+                        global_work_size,
+                        local_work_size,
+                    )?;
+                    kernel
+                        .arg(&values_buffer)
+                        .arg(&bytes_buffer)
+                        .arg(&(n as u32))
+                        .run()?;
+                }
+
+                Ok(program.wrap_buffer(bytes_buffer, num_bytes, owner))
+            });
+
+        run_checked!(self.program, closures, values)
+    }
+
+    /// Decodes the elements from their encodings, which are stored one after
+    /// another.
+    ///
+    /// The buffer must have been created by this codec. It's an error if its
+    /// length is not a multiple of [`FieldCodec::bytes_per_element`] or if an
+    /// encoding is not smaller than the modulus.
+    pub fn from_bytes(
+        &mut self, bytes: DeviceBuffer<u8>,
+    ) -> EcResult<DeviceBuffer<F>> {
+        if bytes.len() % Self::bytes_per_element() != 0 {
+            return Err(EcError::Simple(
+                "The bytes are not a multiple of the encoding size",
+            ));
+        }
+        if let Some(maybe_abort) = &self.maybe_abort {
+            if maybe_abort() {
+                return Err(EcError::Aborted);
+            }
+        }
+
+        let n = bytes.len() / Self::bytes_per_element();
+        let owner = BufferOwner::new(self.id, &self.program);
+        let closures =
+            program_closures!(|program, bytes| -> EcResult<DeviceBuffer<F>> {
+                let bytes_buffer = program.unwrap_buffer(bytes, owner)?;
+                // It is safe as the GPU will initialize these buffers
+                let values_buffer = unsafe { program.create_buffer::<F>(n)? };
+                let valid_buffer = unsafe { program.create_buffer::<u32>(n)? };
+
+                if n > 0 {
+                    let (global_work_size, local_work_size) =
+                        elementwise_work_size(n);
+                    let kernel = program.create_kernel(
+                        &format!("{}_from_bytes", F::name()),
+                        global_work_size,
+                        local_work_size,
+                    )?;
+                    kernel
+                        .arg(&bytes_buffer)
+                        .arg(&values_buffer)
+                        .arg(&valid_buffer)
+                        .arg(&(n as u32))
+                        .run()?;
+
+                    let mut valid = vec![0u32; n];
+                    program.read_into_buffer(&valid_buffer, &mut valid)?;
+                    if valid.contains(&0) {
+                        return Err(EcError::Simple(
+                            "An encoding is not smaller than the modulus",
+                        ));
+                    }
+                }
+
+                Ok(program.wrap_buffer(values_buffer, n, owner))
+            });
+
+        run_checked!(self.program, closures, bytes)
+    }
+}
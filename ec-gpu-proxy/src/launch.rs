@@ -0,0 +1,92 @@
+//! A process-wide limit on the number of kernel launches that run at the
+//! same time.
+
+use std::sync::{Condvar, Mutex};
+
+use once_cell::sync::Lazy;
+
+/// The limiter shared by all kernels of the process.
+static LIMITER: Lazy<LaunchLimiter> = Lazy::new(LaunchLimiter::new);
+
+struct LaunchLimiter {
+    state: Mutex<LimiterState>,
+    released: Condvar,
+}
+
+struct LimiterState {
+    /// The maximum number of concurrent launches.
+    max: usize,
+    /// The number of launches that are currently running.
+    running: usize,
+    /// The maximum of `running` since the limit was set.
+    peak: usize,
+}
+
+impl LaunchLimiter {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(LimiterState {
+                max: usize::MAX,
+                running: 0,
+                peak: 0,
+            }),
+            released: Condvar::new(),
+        }
+    }
+}
+
+/// Limits the number of kernel launches that run at the same time in this
+/// process to `n`.
+///
+/// Every kernel acquires a slot before it uses its program and releases it
+/// once it is done, no matter which device the program runs on. Hence
+/// kernels that are created independently by different parts of an
+/// application don't oversubscribe the GPUs. Launches beyond the limit wait
+/// until another one finished. By default the number is unlimited, which is
+/// the same as a limit of `usize::MAX`. Lowering the limit doesn't interrupt
+/// running launches. Setting the limit resets
+/// [`peak_concurrent_launches`].
+///
+/// # Panics
+///
+/// Panics if `n` is zero, as no launch could run at all.
+pub fn set_max_concurrent_launches(n: usize) {
+    assert!(n > 0, "At least one launch must be allowed");
+    let mut state = LIMITER.state.lock().unwrap();
+    state.max = n;
+    state.peak = state.running;
+    LIMITER.released.notify_all();
+}
+
+/// Returns the maximum number of concurrent kernel launches, see
+/// [`set_max_concurrent_launches`].
+pub fn max_concurrent_launches() -> usize { LIMITER.state.lock().unwrap().max }
+
+/// Returns the maximum number of kernel launches that ran at the same time,
+/// since the limit was last set.
+pub fn peak_concurrent_launches() -> usize {
+    LIMITER.state.lock().unwrap().peak
+}
+
+/// A slot of the process-wide launch limit, it is released on drop.
+pub(crate) struct LaunchPermit(());
+
+impl LaunchPermit {
+    /// Waits until a slot is free and takes it.
+    pub(crate) fn acquire() -> Self {
+        let mut state = LIMITER.state.lock().unwrap();
+        while state.running >= state.max {
+            state = LIMITER.released.wait(state).unwrap();
+        }
+        state.running += 1;
+        state.peak = state.peak.max(state.running);
+        LaunchPermit(())
+    }
+}
+
+impl Drop for LaunchPermit {
+    fn drop(&mut self) {
+        LIMITER.state.lock().unwrap().running -= 1;
+        LIMITER.released.notify_all();
+    }
+}
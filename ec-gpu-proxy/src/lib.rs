@@ -2,6 +2,29 @@
 extern crate ark_bls12_381 as chosen_ark_suite;
 //extern crate ark_bls12_381 as chosen_ark_suite;
 
+#[cfg(any(feature = "cuda", feature = "opencl"))]
+mod device;
+#[cfg(any(feature = "cuda", feature = "opencl"))]
+mod estimate;
+#[cfg(any(feature = "cuda", feature = "opencl"))]
+mod launch;
+#[cfg(any(feature = "cuda", feature = "opencl"))]
+mod numa;
+#[cfg(any(feature = "cuda", feature = "opencl"))]
+mod scratch;
+
+/// Timings of the CPU and GPU implementations for comparison.
+#[cfg(any(feature = "cuda", feature = "opencl"))]
+pub mod bench;
+/// A GPU memory limit that is shared between kernels.
+pub mod budget;
+/// Buffers that stay in GPU memory between operations.
+#[cfg(any(feature = "cuda", feature = "opencl"))]
+pub mod buffer;
+/// Field elements given as bytes instead of arkworks types.
+#[cfg(any(feature = "cuda", feature = "opencl"))]
+pub mod bytes;
+
 /// Fast Fourier Transform on the GPU.
 #[cfg(any(feature = "cuda", feature = "opencl"))]
 pub mod fft;
@@ -14,26 +37,62 @@ pub mod ec_fft;
 /// Fast Fourier Transform for G1 on the CPU.
 pub mod ec_fft_cpu;
 
+/// Generic helpers for field arithmetic on the CPU.
+pub mod field;
+
+/// Element-wise field operations on the GPU.
+#[cfg(any(feature = "cuda", feature = "opencl"))]
+pub mod field_ops;
+
+/// Prometheus metrics of the GPU operations.
+#[cfg(all(feature = "metrics", any(feature = "cuda", feature = "opencl")))]
+pub mod metrics;
+
 /// Multiexponentiation on the GPU.
 #[cfg(any(feature = "cuda", feature = "opencl"))]
 pub mod multiexp;
 /// Multiexponentiation on the CPU.
 pub mod multiexp_cpu;
 
+/// Strategies for splitting work among several devices.
+#[cfg(any(feature = "cuda", feature = "opencl"))]
+pub mod split;
+
 /// Helpers for multithreaded code.
 pub mod threadpool;
 
-fn pow_vartime<F: ark_ff::Field, S: AsRef<[u64]>>(base: &F, exp: S) -> F {
-    let mut res = F::ONE;
-    for e in exp.as_ref().iter().rev() {
-        for i in (0..64).rev() {
-            res = res.square();
+#[cfg(any(feature = "cuda", feature = "opencl"))]
+mod transfer;
+
+#[cfg(any(feature = "cuda", feature = "opencl"))]
+pub use device::SharedProgram;
+#[cfg(any(feature = "cuda", feature = "opencl"))]
+pub use launch::{
+    max_concurrent_launches, peak_concurrent_launches,
+    set_max_concurrent_launches,
+};
+#[cfg(all(
+    feature = "metrics",
+    any(feature = "cuda", feature = "opencl")
+))]
+pub use metrics::gather_metrics;
+#[cfg(any(feature = "cuda", feature = "opencl"))]
+pub use scratch::HostAllocator;
+
+use field::pow_vartime;
 
-            if ((*e >> i) & 1) == 1 {
-                res.mul_assign(base);
-            }
-        }
-    }
+/// Returns `floor(log2(n))`, `n` must not be zero.
+///
+/// Unlike `(n as f32).log2().floor()`, this is exact for all `n`, floats may
+/// be off by one close to powers of two.
+pub const fn log2_floor(n: usize) -> u32 { usize::BITS - 1 - n.leading_zeros() }
 
-    res
+/// Returns `log2(n)` for an `n` that is a power of two.
+///
+/// # Panics
+///
+/// Panics if `n` is not a power of two.
+pub fn log2_strict(n: usize) -> u32 {
+    assert!(n.is_power_of_two(), "{} is not a power of two", n);
+    n.trailing_zeros()
 }
@@ -0,0 +1,218 @@
+//! Durations and sizes of the GPU operations in the Prometheus text format.
+//!
+//! Every multiexp and FFT that runs on a GPU is recorded, labelled with the
+//! operation and the device. A multiexp that is split into chunks is recorded
+//! once per chunk, its size is the number of terms. The size of an FFT is its
+//! number of elements.
+
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex, time::Duration};
+
+use once_cell::sync::Lazy;
+
+/// The upper bounds of the buckets of the duration histogram, in seconds.
+const DURATION_BUCKETS: [f64; 12] = [
+    0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 60.0,
+];
+
+/// The upper bounds of the buckets of the size histogram.
+const SIZE_BUCKETS: [f64; 8] = [
+    16.0,
+    256.0,
+    4096.0,
+    65536.0,
+    1048576.0,
+    16777216.0,
+    268435456.0,
+    4294967296.0,
+];
+
+/// The operations that are recorded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Operation {
+    Multiexp,
+    Fft,
+}
+
+impl Operation {
+    fn name(&self) -> &'static str {
+        match self {
+            Operation::Multiexp => "multiexp",
+            Operation::Fft => "fft",
+        }
+    }
+}
+
+/// A Prometheus histogram with fixed buckets.
+struct Histogram<const N: usize> {
+    /// The number of observations per bucket, not cumulative.
+    buckets: [u64; N],
+    count: u64,
+    sum: f64,
+}
+
+impl<const N: usize> Histogram<N> {
+    fn new() -> Self {
+        Self {
+            buckets: [0; N],
+            count: 0,
+            sum: 0.0,
+        }
+    }
+
+    fn observe(&mut self, bounds: &[f64; N], value: f64) {
+        if let Some(i) = bounds.iter().position(|&bound| value <= bound) {
+            self.buckets[i] += 1;
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+
+    /// Writes the samples of the histogram with the given `labels`.
+    fn write(
+        &self, out: &mut String, name: &str, labels: &str, bounds: &[f64; N],
+    ) -> std::fmt::Result {
+        let mut cumulative = 0;
+        for (bound, count) in bounds.iter().zip(self.buckets) {
+            cumulative += count;
+            writeln!(
+                out,
+                "{}_bucket{{{},le=\"{}\"}} {}",
+                name, labels, bound, cumulative
+            )?;
+        }
+        writeln!(
+            out,
+            "{}_bucket{{{},le=\"+Inf\"}} {}",
+            name, labels, self.count
+        )?;
+        writeln!(out, "{}_sum{{{}}} {}", name, labels, self.sum)?;
+        writeln!(out, "{}_count{{{}}} {}", name, labels, self.count)
+    }
+}
+
+/// The metrics of one operation on one device.
+struct Series {
+    durations: Histogram<{ DURATION_BUCKETS.len() }>,
+    sizes: Histogram<{ SIZE_BUCKETS.len() }>,
+}
+
+/// The metrics of all operations, by operation and device name.
+static METRICS: Lazy<Mutex<BTreeMap<(Operation, String), Series>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+/// Records that `operation` of the given `size` took `duration` on `device`.
+pub(crate) fn record(
+    operation: Operation, device: &str, size: usize, duration: Duration,
+) {
+    let mut metrics = METRICS.lock().unwrap();
+    let series = metrics
+        .entry((operation, device.to_string()))
+        .or_insert_with(|| Series {
+            durations: Histogram::new(),
+            sizes: Histogram::new(),
+        });
+    series
+        .durations
+        .observe(&DURATION_BUCKETS, duration.as_secs_f64());
+    series.sizes.observe(&SIZE_BUCKETS, size as f64);
+}
+
+/// Escapes a label value, see the Prometheus exposition format.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Returns the recorded metrics in the Prometheus text exposition format.
+///
+/// There is a histogram of the durations in seconds,
+/// `ec_gpu_operation_duration_seconds`, and one of the sizes,
+/// `ec_gpu_operation_size`, both with an `operation` and a `device` label.
+/// The number of operations is the `_count` of the histograms. The metrics
+/// are collected since the start of the process.
+pub fn gather_metrics() -> String {
+    let metrics = METRICS.lock().unwrap();
+    let mut out = String::new();
+    let write = |out: &mut String| -> std::fmt::Result {
+        writeln!(
+            out,
+            "# HELP ec_gpu_operation_duration_seconds The duration of the \
+             GPU operations."
+        )?;
+        writeln!(out, "# TYPE ec_gpu_operation_duration_seconds histogram")?;
+        for ((operation, device), series) in metrics.iter() {
+            let labels = format!(
+                "operation=\"{}\",device=\"{}\"",
+                operation.name(),
+                escape(device)
+            );
+            series.durations.write(
+                out,
+                "ec_gpu_operation_duration_seconds",
+                &labels,
+                &DURATION_BUCKETS,
+            )?;
+        }
+        writeln!(
+            out,
+            "# HELP ec_gpu_operation_size The number of terms or elements of \
+             the GPU operations."
+        )?;
+        writeln!(out, "# TYPE ec_gpu_operation_size histogram")?;
+        for ((operation, device), series) in metrics.iter() {
+            let labels = format!(
+                "operation=\"{}\",device=\"{}\"",
+                operation.name(),
+                escape(device)
+            );
+            series.sizes.write(
+                out,
+                "ec_gpu_operation_size",
+                &labels,
+                &SIZE_BUCKETS,
+            )?;
+        }
+        Ok(())
+    };
+    write(&mut out).expect("writing to a String doesn't fail");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gather_metrics() {
+        let device = "test \"device\"";
+        record(Operation::Fft, device, 1000, Duration::from_millis(2));
+        record(Operation::Fft, device, 10, Duration::from_millis(20));
+
+        let metrics = gather_metrics();
+        let labels = "operation=\"fft\",device=\"test \\\"device\\\"\"";
+        for line in [
+            format!(
+                "ec_gpu_operation_duration_seconds_bucket{{{},le=\"0.001\"}} 0",
+                labels
+            ),
+            format!(
+                "ec_gpu_operation_duration_seconds_bucket{{{},le=\"0.005\"}} 1",
+                labels
+            ),
+            format!(
+                "ec_gpu_operation_duration_seconds_bucket{{{},le=\"+Inf\"}} 2",
+                labels
+            ),
+            format!("ec_gpu_operation_duration_seconds_count{{{}}} 2", labels),
+            format!("ec_gpu_operation_size_bucket{{{},le=\"16\"}} 1", labels),
+            format!("ec_gpu_operation_size_bucket{{{},le=\"4096\"}} 2", labels),
+            format!("ec_gpu_operation_size_sum{{{}}} 1010", labels),
+        ] {
+            assert!(metrics.lines().any(|l| l == line), "missing {}", line);
+        }
+        assert!(metrics
+            .contains("# TYPE ec_gpu_operation_duration_seconds histogram\n"));
+    }
+}
@@ -1,38 +1,71 @@
 use std::{
-    ops::AddAssign,
+    borrow::Cow,
+    cmp, iter,
+    ops::{AddAssign, Range},
     sync::{Arc, RwLock},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
 use ag_types::{
-    GpuCurveAffine, GpuName, GpuRepr, PrimeFieldRepr as PrimeField,
+    CanonicalBytes, GpuCurveAffine, GpuName, GpuRepr,
+    PrimeFieldRepr as PrimeField,
 };
-use ark_ec::Group;
-use ark_ff::Zero;
-use ec_gpu_program::{EcError, EcResult};
-use log::{error, info};
-use rust_gpu_tools::{program_closures, Device, Program};
+use ark_ec::{CurveGroup, Group};
+use ark_ff::{FftField, Field, Zero};
+use ark_serialize::{
+    CanonicalDeserialize, CanonicalSerialize, Compress, Read,
+    SerializationError, Valid, Validate, Write,
+};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use ec_gpu_program::{DeviceInfo, EcError, EcResult};
+use log::info;
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator,
+};
+use rust_gpu_tools::{program_closures, Device, LocalBuffer, Program};
 use yastl::Scope;
 
-use crate::threadpool::Worker;
+#[cfg(feature = "metrics")]
+use crate::metrics::{self, Operation};
+use crate::{
+    budget::{reserve, reserve_with, MemoryBudget},
+    buffer::{
+        check_owner, next_owner_id, BackendBuffer, BufferOwner, DeviceBuffer,
+    },
+    bytes::{CompressedLayout, FieldSpec},
+    device::{run_checked, share, working_kernels, SharedProgram},
+    estimate,
+    fft::{
+        check_len, div_ceil, elementwise_work_size, precalculate_twiddles,
+        MAX_LOG2_LOCAL_WORK_SIZE, MAX_LOG2_RADIX, NO_POST_MAP,
+    },
+    fft_cpu::bitreverse,
+    multiexp_cpu::{window_size, MAX_WINDOW_SIZE},
+    numa::{device_numa_node, NodeAffinity},
+    pow_vartime,
+    scratch::{HostAllocator, ScratchVec},
+    split::{split_ranges, EvenSplit, WorkSplitter},
+    threadpool::Worker,
+    transfer::{create_buffer_checked, UploadCheck},
+};
 
-/// On the GPU, the exponents are split into windows, this is the maximum number
-/// of such windows.
-const MAX_WINDOW_SIZE: usize = 10;
 /// In CUDA this is the number of blocks per grid (grid size).
 const LOCAL_WORK_SIZE: usize = 128;
 /// Let 20% of GPU memory be free, this is an arbitrary value.
-const MEMORY_PADDING: f64 = 0.2f64;
+///
+/// It's a percentage and not a float factor, so that the chunk size is the
+/// same on all platforms.
+const MEMORY_PADDING_PERCENT: u128 = 20;
 /// The Nvidia Ampere architecture is compute capability major version 8.
 const AMPERE: u32 = 8;
-
-/// Divide and ceil to the next value.
-const fn div_ceil(a: usize, b: usize) -> usize {
-    if a % b == 0 {
-        a / b
-    } else {
-        (a / b) + 1
-    }
-}
+/// The number of field multiplications of adding an affine point to a
+/// projective one.
+const MIXED_ADDITION_MULS: f64 = 11.0;
+/// The number of field multiplications of adding two projective points.
+const ADDITION_MULS: f64 = 16.0;
+/// The number of terms of the multiexp that is timed for calibration.
+const CALIBRATION_TERMS: usize = 1 << 16;
 
 /// The number of units the work is split into. One unit will result in one CUDA
 /// thread.
@@ -48,11 +81,317 @@ const fn work_units(
     }
 }
 
+/// What [`MultiexpKernel`] does with bases that are the point at infinity.
+///
+/// The GPU code represents the point at infinity as `(0, 0)`, which the
+/// affine additions would treat like a regular point, hence such bases must
+/// not reach the GPU.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IdentityHandling {
+    /// Drops the terms with such a base before the work is distributed, as
+    /// they don't contribute to the result. This is the default.
+    #[default]
+    Skip,
+    /// Returns an error if any base is the point at infinity.
+    Reject,
+}
+
+impl IdentityHandling {
+    /// Returns whether any of the `bases` is the point at infinity, which
+    /// means that their terms must be skipped, or an error if such bases are
+    /// rejected.
+    fn has_identity<G>(self, bases: &[G]) -> EcResult<bool>
+    where G: GpuCurveAffine {
+        if !bases.iter().any(GpuCurveAffine::is_identity) {
+            return Ok(false);
+        }
+        match self {
+            IdentityHandling::Skip => Ok(true),
+            IdentityHandling::Reject => Err(EcError::Simple(
+                "A base of the multiexp is the point at infinity",
+            )),
+        }
+    }
+
+    /// Drops the terms whose base is the point at infinity, or returns an
+    /// error if such bases are rejected.
+    ///
+    /// The terms are only copied if there is something to drop.
+    fn finite_terms<'t, G, E>(
+        self, bases: &'t [G], exps: &'t [E],
+    ) -> EcResult<(Cow<'t, [G]>, Cow<'t, [E]>)>
+    where
+        G: GpuCurveAffine,
+        E: Clone,
+    {
+        if !self.has_identity(bases)? {
+            return Ok((Cow::Borrowed(bases), Cow::Borrowed(exps)));
+        }
+        let (bases, exps): (Vec<G>, Vec<E>) = bases
+            .iter()
+            .zip(exps.iter())
+            .filter(|(base, _)| !base.is_identity())
+            .map(|(base, exp)| (*base, exp.clone()))
+            .unzip();
+        Ok((Cow::Owned(bases), Cow::Owned(exps)))
+    }
+}
+
+/// The number of elliptic curve operations a multiexp performed.
+///
+/// It counts the calls of the point addition and doubling routines, no matter
+/// whether one of the operands is the point at infinity. The multiexp kernel
+/// increments atomic counters while it does the work, the accumulation of the
+/// per-chunk results on the host isn't included.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OpCount {
+    /// Additions of an affine base into a projective bucket.
+    pub mixed_additions: u64,
+    /// Additions of two projective points.
+    pub additions: u64,
+    /// Doublings of a projective point.
+    pub doublings: u64,
+}
+
+impl AddAssign for OpCount {
+    fn add_assign(&mut self, other: Self) {
+        self.mixed_additions += other.mixed_additions;
+        self.additions += other.additions;
+        self.doublings += other.doublings;
+    }
+}
+
+/// How many bases were added into the buckets of one window of a multiexp.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WindowOccupancy {
+    /// The number of bases in the fullest bucket.
+    pub max: u32,
+    /// The average number of bases per bucket.
+    pub mean: f64,
+}
+
+/// How full the buckets of a multiexp got, see
+/// [`SingleMultiexpKernel::multiexp_with_occupancy`].
+///
+/// Every group of GPU threads has its own buckets, one per digit of a window.
+/// The counts are summed up over the groups, i.e. they are the number of
+/// exponents with a certain digit. If the fullest bucket is much fuller than
+/// the average one, the exponents are far from uniformly distributed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BucketOccupancy {
+    /// The number of bits of each window.
+    pub window_size: usize,
+    /// The occupancy of each window, starting with the least significant
+    /// one. The most significant window may be smaller and hence have fewer
+    /// buckets.
+    pub windows: Vec<WindowOccupancy>,
+}
+
+impl BucketOccupancy {
+    /// Calculates the statistics from the counters of the GPU, which hold
+    /// `2^window_size` counts per window of the `exp_bits` bits exponents.
+    ///
+    /// If the digits are `signed`, a full window only has `2^(window_size -
+    /// 1)` buckets.
+    fn from_counts(
+        counts: &[u32], window_size: usize, exp_bits: usize, signed: bool,
+    ) -> Self {
+        let signed = signed && window_size > 1;
+        let windows = counts
+            .chunks(1 << window_size)
+            .enumerate()
+            .map(|(i, counts)| {
+                let bits = cmp::min(window_size, exp_bits - i * window_size);
+                // Digit zero has no bucket.
+                let buckets = if signed && bits == window_size {
+                    &counts[1..=1 << (window_size - 1)]
+                } else {
+                    &counts[1..1 << bits]
+                };
+                let total: u64 = buckets.iter().map(|&c| u64::from(c)).sum();
+                WindowOccupancy {
+                    max: buckets.iter().copied().max().unwrap_or(0),
+                    mean: total as f64 / buckets.len() as f64,
+                }
+            })
+            .collect();
+        BucketOccupancy {
+            window_size,
+            windows,
+        }
+    }
+}
+
+/// The bases and exponents of one of the multiexps of
+/// [`MultiexpKernel::multiexp_pipeline`].
+pub type MultiexpJob<G> = (
+    Arc<Vec<G>>,
+    Arc<Vec<<<G as GpuCurveAffine>::Scalar as PrimeField>::Repr>>,
+);
+
+/// Bases of multiexps that stay in GPU memory, see
+/// [`MultiexpKernel::upload_bases`].
+///
+/// The bases are split among the devices and into chunks like the terms of a
+/// multiexp. A handle can only be used with the kernel that created it, the
+/// GPU memory is released when it's dropped.
+pub struct BaseHandle<G>
+where G: GpuCurveAffine
+{
+    /// The number of bases.
+    len: usize,
+    /// The chunks of every device, each with the range of its bases.
+    chunks: Vec<Vec<(Range<usize>, DeviceBuffer<<G as GpuRepr>::Repr>)>>,
+    /// Whether a base is the point at infinity, if any of them is.
+    identities: Option<Vec<bool>>,
+}
+
+impl<G> BaseHandle<G>
+where G: GpuCurveAffine
+{
+    /// Returns the number of bases.
+    pub fn len(&self) -> usize { self.len }
+
+    /// Returns whether there are no bases.
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+}
+
+/// Precomputed multiples of the bases of a multiexp, see
+/// [`SingleMultiexpKernel::multiexp_with_table`].
+///
+/// Every window digit of an exponent adds the multiple of its base directly,
+/// instead of adding the base into a bucket, which is weighted by the digit
+/// in the end. The table is computed on the host and uploaded with every
+/// multiexp, it holds `2^c - 1` multiples of every base for windows of `c`
+/// bits. A signed table, see [`BaseTable::precompute_signed`], only holds
+/// the `2^(c - 1)` positive multiples, a negative digit adds the negation of
+/// the multiple of its absolute value, which takes about half the memory.
+///
+/// Bases at infinity have no multiples, their terms are skipped.
+/// [`MultiexpKernel::multiexp_with_table`] rejects such a table if the
+/// [`IdentityHandling`] says so.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BaseTable<G>
+where G: GpuCurveAffine
+{
+    /// The multiples of every base that isn't the point at infinity, base by
+    /// base, the multiple `d * base` is at index `d - 1`.
+    multiples: Vec<G>,
+    /// The number of bases.
+    len: usize,
+    /// Whether a base is the point at infinity, if any of them is.
+    identities: Option<Vec<bool>>,
+    /// The number of bits of a window.
+    window_size: usize,
+    /// Whether the digits are signed.
+    signed: bool,
+}
+
+impl<G> BaseTable<G>
+where G: GpuCurveAffine
+{
+    /// Computes the multiples `1..2^window_size` of every base.
+    ///
+    /// The window size must be between one and
+    /// [`MAX_WINDOW_SIZE`](crate::multiexp_cpu::MAX_WINDOW_SIZE).
+    pub fn precompute(bases: &[G], window_size: usize) -> EcResult<Self> {
+        Self::new(bases, window_size, false)
+    }
+
+    /// Computes the multiples `1..=2^(window_size - 1)` of every base, for
+    /// signed digits.
+    ///
+    /// A table of one bit windows is the same as the unsigned one, there are
+    /// no negative digits. The most significant bit of the exponents must be
+    /// spare, otherwise the carry of a negative digit of the most significant
+    /// window would be lost.
+    pub fn precompute_signed(
+        bases: &[G], window_size: usize,
+    ) -> EcResult<Self> {
+        if G::Scalar::MODULUS_BIT_SIZE as usize >= exp_size::<G::Scalar>() * 8 {
+            return Err(EcError::Simple(
+                "The signed digits need a spare bit of the exponents",
+            ));
+        }
+        Self::new(bases, window_size, true)
+    }
+
+    fn new(bases: &[G], window_size: usize, signed: bool) -> EcResult<Self> {
+        let max_window_size =
+            cmp::min(MAX_WINDOW_SIZE, G::Scalar::MODULUS_BIT_SIZE as usize);
+        if window_size == 0 || window_size > max_window_size {
+            return Err(EcError::Simple("The window size is out of range"));
+        }
+        let signed = signed && window_size > 1;
+        let multiples_per_base = if signed {
+            1 << (window_size - 1)
+        } else {
+            (1 << window_size) - 1
+        };
+        let identities = bases
+            .iter()
+            .any(GpuCurveAffine::is_identity)
+            .then(|| bases.iter().map(GpuCurveAffine::is_identity).collect());
+        let multiples = bases
+            .par_iter()
+            .filter(|base| !base.is_identity())
+            .map(|base| {
+                let mut multiple = G::Curve::zero();
+                let multiples = (0..multiples_per_base)
+                    .map(|_| {
+                        multiple += base;
+                        multiple
+                    })
+                    .collect::<Vec<_>>();
+                G::Curve::normalize_batch(&multiples)
+            })
+            .collect::<Vec<_>>()
+            .concat();
+        Ok(Self {
+            multiples,
+            len: bases.len(),
+            identities,
+            window_size,
+            signed,
+        })
+    }
+
+    /// Returns the number of bases.
+    pub fn len(&self) -> usize { self.len }
+
+    /// Returns whether there are no bases.
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    /// Returns the number of bits of a window.
+    pub fn window_size(&self) -> usize { self.window_size }
+
+    /// Returns whether the digits are signed.
+    pub fn is_signed(&self) -> bool { self.signed }
+
+    /// Returns the number of multiples of every base.
+    pub fn multiples_per_base(&self) -> usize {
+        if self.signed {
+            1 << (self.window_size - 1)
+        } else {
+            (1 << self.window_size) - 1
+        }
+    }
+
+    /// Returns the memory (in bytes) of the multiples, which are uploaded to
+    /// the GPU.
+    pub fn memory_size(&self) -> usize {
+        self.multiples.len() * std::mem::size_of::<G>()
+    }
+}
+
 /// Multiexp kernel for a single GPU.
 pub struct SingleMultiexpKernel<'a, G>
 where G: GpuCurveAffine
 {
-    program: Program,
+    program: SharedProgram,
+    /// Further programs of the same device, each with its own command queue
+    /// or stream, see [`SingleMultiexpKernel::set_pipeline_streams`].
+    streams: Vec<SharedProgram>,
     /// The number of exponentiations the GPU can handle in a single execution
     /// of the kernel.
     n: usize,
@@ -63,6 +402,33 @@ where G: GpuCurveAffine
     /// possible to abort the multiexp calculations. If it returns true,
     /// the calculation will be aborted with an [`EcError::Aborted`].
     maybe_abort: Option<&'a (dyn Fn() -> bool + Send + Sync)>,
+    /// Whether the elliptic curve operations are counted.
+    count_ops: bool,
+    /// The operations counted since the last reset.
+    op_count: OpCount,
+    /// The NUMA node the device is attached to, if known.
+    numa_node: Option<usize>,
+    /// Whether the host staging buffers are allocated on `numa_node`.
+    numa_aware: bool,
+    /// The GPU memory limit this kernel shares with other kernels.
+    budget: Option<Arc<MemoryBudget>>,
+    /// Identifies the [`DeviceBuffer`]s this kernel created.
+    id: usize,
+    /// The number of compute units of the device.
+    compute_units: u32,
+    /// The device the kernel runs on.
+    device_info: DeviceInfo,
+    /// The factor the modelled time is multiplied with, see
+    /// [`SingleMultiexpKernel::calibrate`].
+    time_scale: f64,
+    /// The allocator of the temporary host buffers, the global allocator is
+    /// used if it's `None`.
+    host_allocator: Option<HostAllocator>,
+    /// How the uploads of bases and exponents are checked.
+    upload_check: UploadCheck,
+    /// The window size that is used instead of the heuristic, see
+    /// [`SingleMultiexpKernel::set_window_size`].
+    window_size_override: Option<usize>,
 
     _phantom: std::marker::PhantomData<G::Scalar>,
 }
@@ -77,8 +443,9 @@ where
     let exp_size = exp_size::<G::Scalar>();
     let proj_size = std::mem::size_of::<G::Curve>();
 
-    // Leave `MEMORY_PADDING` percent of the memory free.
-    let max_memory = ((mem as f64) * (1f64 - MEMORY_PADDING)) as usize;
+    // Leave `MEMORY_PADDING_PERCENT` percent of the memory free.
+    let max_memory =
+        (u128::from(mem) * (100 - MEMORY_PADDING_PERCENT) / 100) as usize;
     // The amount of memory (in bytes) of a single term.
     let term_size = aff_size + exp_size;
     // The number of buckets needed for one work unit
@@ -98,6 +465,250 @@ where
 /// size.
 fn exp_size<F: PrimeField>() -> usize { std::mem::size_of::<F::Repr>() }
 
+/// Calculates the final result from the results of the `num_groups` *
+/// `num_windows` threads of the multiexp kernel.
+fn accumulate<G>(
+    results: &[G::Curve], window_size: usize, num_windows: usize,
+    num_groups: usize,
+) -> G::Curve
+where
+    G: GpuCurveAffine,
+{
+    let windows = window_sums::<G>(results, num_windows, num_groups);
+    reduce_windows::<G>(&windows, window_size)
+}
+
+/// Sums up the results of the chunks of the multiexp kernel, each one is the
+/// multiexp of the terms of its chunk.
+fn sum_chunks<G>(results: &[G::Curve]) -> G::Curve
+where G: GpuCurveAffine {
+    let mut acc = G::Curve::zero();
+    for result in results {
+        acc.add_assign(result);
+    }
+    acc
+}
+
+/// Sums up the results of the `num_groups` threads of every window.
+fn window_sums<G>(
+    results: &[G::Curve], num_windows: usize, num_groups: usize,
+) -> Vec<G::Curve>
+where G: GpuCurveAffine {
+    (0..num_windows)
+        .map(|i| {
+            let mut sum = G::Curve::zero();
+            for g in 0..num_groups {
+                sum.add_assign(&results[g * num_windows + i]);
+            }
+            sum
+        })
+        .collect()
+}
+
+/// Combines the sums of the windows of `window_size` bits into the result of
+/// the multiexp.
+fn reduce_windows<G>(windows: &[G::Curve], window_size: usize) -> G::Curve
+where G: GpuCurveAffine {
+    let mut acc = G::Curve::zero();
+    let mut bits = 0;
+    let exp_bits = exp_size::<G::Scalar>() * 8;
+    for window in windows {
+        let w = std::cmp::min(window_size, exp_bits - bits);
+        for _ in 0..w {
+            acc = acc.double();
+        }
+        acc.add_assign(window);
+        bits += w; // Process the next window
+    }
+    acc
+}
+
+/// The unreduced result of a multiexp, see
+/// [`MultiexpKernel::multiexp_partial`].
+///
+/// It holds the sum of every window, the doublings that combine the windows
+/// into the result are deferred to [`MultiexpPartial::reduce`]. Partials of
+/// multiexps with different terms, e.g. computed by different nodes of an
+/// aggregation tree, are merged window by window with
+/// [`MultiexpPartial::merge`], hence only the root of the tree does the
+/// doublings. The chunks of a multiexp may use different window sizes, so
+/// there is one set of window sums per window size. The multiexp kernel
+/// combines the windows of its chunks already, their sum is added to the
+/// last window, the one that isn't doubled.
+///
+/// It implements arkworks' `CanonicalSerialize` and `CanonicalDeserialize`,
+/// so that it can be passed to another node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MultiexpPartial<G>
+where G: GpuCurveAffine
+{
+    /// The window sums per window size, the first window is added first.
+    windows: Vec<(usize, Vec<G::Curve>)>,
+}
+
+impl<G> Default for MultiexpPartial<G>
+where G: GpuCurveAffine
+{
+    fn default() -> Self {
+        Self {
+            windows: Vec::new(),
+        }
+    }
+}
+
+impl<G> MultiexpPartial<G>
+where G: GpuCurveAffine
+{
+    /// Adds the window sums of `window_size` bits to the ones of the same
+    /// size.
+    fn add_windows(&mut self, window_size: usize, windows: &[G::Curve]) {
+        match self.windows.iter_mut().find(|(size, sums)| {
+            *size == window_size && sums.len() == windows.len()
+        }) {
+            Some((_, sums)) => {
+                for (sum, window) in sums.iter_mut().zip(windows) {
+                    sum.add_assign(window);
+                }
+            }
+            None => self.windows.push((window_size, windows.to_vec())),
+        }
+    }
+
+    /// Adds the window sums of `other`, the result is the partial of the
+    /// multiexp of the terms of both.
+    pub fn merge(&mut self, other: &Self) {
+        for (window_size, windows) in &other.windows {
+            self.add_windows(*window_size, windows);
+        }
+    }
+
+    /// Calculates the result of the multiexp.
+    pub fn reduce(&self) -> G::Curve {
+        let mut acc = G::Curve::zero();
+        for (window_size, windows) in &self.windows {
+            acc.add_assign(&reduce_windows::<G>(windows, *window_size));
+        }
+        acc
+    }
+}
+
+impl<G> CanonicalSerialize for MultiexpPartial<G>
+where G: GpuCurveAffine
+{
+    fn serialize_with_mode<W: Write>(
+        &self, mut writer: W, compress: Compress,
+    ) -> Result<(), SerializationError> {
+        (self.windows.len() as u64)
+            .serialize_with_mode(&mut writer, compress)?;
+        for (window_size, windows) in &self.windows {
+            (*window_size as u64).serialize_with_mode(&mut writer, compress)?;
+            windows.serialize_with_mode(&mut writer, compress)?;
+        }
+        Ok(())
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        8 + self
+            .windows
+            .iter()
+            .map(|(_, windows)| 8 + windows.serialized_size(compress))
+            .sum::<usize>()
+    }
+}
+
+impl<G> Valid for MultiexpPartial<G>
+where G: GpuCurveAffine
+{
+    fn check(&self) -> Result<(), SerializationError> {
+        for (window_size, windows) in &self.windows {
+            if *window_size == 0 || *window_size > MAX_WINDOW_SIZE {
+                return Err(SerializationError::InvalidData);
+            }
+            windows.check()?;
+        }
+        Ok(())
+    }
+}
+
+impl<G> CanonicalDeserialize for MultiexpPartial<G>
+where G: GpuCurveAffine
+{
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R, compress: Compress, validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let len = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+        let mut partial = Self::default();
+        for _ in 0..len {
+            let window_size =
+                u64::deserialize_with_mode(&mut reader, compress, validate)?;
+            let windows = Vec::<G::Curve>::deserialize_with_mode(
+                &mut reader,
+                compress,
+                validate,
+            )?;
+            partial.windows.push((window_size as usize, windows));
+        }
+        if validate == Validate::Yes {
+            partial.check()?;
+        }
+        Ok(partial)
+    }
+}
+
+/// The bases of a multiexp that is run on the GPU.
+#[derive(Clone, Copy)]
+enum GpuBases<'b, G>
+where G: GpuCurveAffine
+{
+    /// Affine bases, already converted into their GPU representation.
+    Affine(&'b [<G as GpuRepr>::Repr]),
+    /// Projective bases, which are converted into affine form on the GPU.
+    /// None of them may be the point at infinity.
+    Projective(&'b [G::Curve]),
+    /// Affine bases that are already in GPU memory, see
+    /// [`SingleMultiexpKernel::upload_bases`].
+    Resident(&'b DeviceBuffer<<G as GpuRepr>::Repr>),
+}
+
+impl<'b, G> GpuBases<'b, G>
+where G: GpuCurveAffine
+{
+    fn len(&self) -> usize {
+        match self {
+            GpuBases::Affine(bases) => bases.len(),
+            GpuBases::Projective(bases) => bases.len(),
+            GpuBases::Resident(bases) => bases.len(),
+        }
+    }
+}
+
+/// The results of the chunks of the multiexp kernel, which still need to be
+/// accumulated.
+struct PartialResults<G>
+where G: GpuCurveAffine
+{
+    results: Vec<G::Curve>,
+    window_size: usize,
+    num_windows: usize,
+    /// The bucket occupancy, if it was requested.
+    occupancy: Option<BucketOccupancy>,
+}
+
+impl<G> PartialResults<G>
+where G: GpuCurveAffine
+{
+    /// Calculates the final result, see [`sum_chunks`].
+    fn accumulate(&self) -> G::Curve { sum_chunks::<G>(&self.results) }
+
+    /// Adds the result to the last window of the `partial`, see
+    /// [`MultiexpPartial`].
+    fn add_to(&self, partial: &mut MultiexpPartial<G>) {
+        let mut windows = vec![G::Curve::zero(); self.num_windows];
+        windows[self.num_windows - 1] = self.accumulate();
+        partial.add_windows(self.window_size, &windows);
+    }
+}
+
 impl<'a, G> SingleMultiexpKernel<'a, G>
 where G: GpuCurveAffine + GpuName
 {
@@ -107,9 +718,12 @@ where G: GpuCurveAffine + GpuName
     /// computation, without leaving the GPU in a weird state. If that
     /// function returns `true`, execution is aborted.
     pub fn create(
-        program: Program, device: &Device,
+        program: impl Into<SharedProgram>, device: &Device,
         maybe_abort: Option<&'a (dyn Fn() -> bool + Send + Sync)>,
     ) -> EcResult<Self> {
+        if let Some(reason) = G::unsupported_reason() {
+            return Err(EcError::unsupported_curve::<G>(reason));
+        }
         let mem = device.memory();
         let compute_units = device.compute_units();
         let compute_capability = device.compute_capability();
@@ -118,10 +732,23 @@ where G: GpuCurveAffine + GpuName
         dbg!(chunk_size);
 
         Ok(SingleMultiexpKernel {
-            program,
+            program: program.into(),
+            streams: Vec::new(),
             n: chunk_size,
             work_units,
             maybe_abort,
+            count_ops: false,
+            op_count: OpCount::default(),
+            numa_node: device_numa_node(device),
+            numa_aware: false,
+            budget: None,
+            id: next_owner_id(),
+            compute_units,
+            device_info: DeviceInfo::new(device),
+            time_scale: 1.0,
+            host_allocator: None,
+            upload_check: UploadCheck::default(),
+            window_size_override: None,
             _phantom: std::marker::PhantomData,
         })
     }
@@ -132,36 +759,389 @@ where G: GpuCurveAffine + GpuName
     /// [`SingleMultiexpKernel`]`::n`, this means that it is guaranteed that
     /// this amount of calculations fit on the GPU this kernel is
     /// running on.
+    ///
+    /// If operation counting is enabled, the operations are added to
+    /// [`SingleMultiexpKernel::op_count`].
     pub fn multiexp(
-        &self, bases: &[G], exponents: &[<G::Scalar as PrimeField>::Repr],
+        &mut self, bases: &[G], exponents: &[<G::Scalar as PrimeField>::Repr],
     ) -> EcResult<G::Curve> {
         assert_eq!(bases.len(), exponents.len());
 
+        // Allocate the staging buffer close to the GPU, the binding is undone
+        // at the end of this function.
+        let _affinity = self.bind_numa_node();
+        let bases_gpu = ScratchVec::from_iter(
+            self.host_allocator.as_ref(),
+            bases.iter().map(GpuRepr::to_gpu_repr),
+        );
+        let partial = self.multiexp_gpu(
+            GpuBases::Affine(&bases_gpu),
+            exponents,
+            None,
+            None,
+        )?;
+        Ok(partial.accumulate())
+    }
+
+    /// Converts `bases` into their GPU representation and copies them into
+    /// GPU memory, so that several multiexps can use them without uploading
+    /// them again, see [`SingleMultiexpKernel::multiexp_resident`].
+    ///
+    /// The number of `bases` must not exceed [`SingleMultiexpKernel`]`::n`.
+    /// If there is a memory budget, the memory of the bases is reserved until
+    /// the buffer is dropped. It fails with
+    /// [`EcError::MemoryBudgetExceeded`] if the bases and the working memory
+    /// of a multiexp with them don't fit within the budget.
+    pub fn upload_bases(
+        &mut self, bases: &[G],
+    ) -> EcResult<DeviceBuffer<<G as GpuRepr>::Repr>> {
+        if bases.len() > self.n {
+            return Err(EcError::Simple("The bases don't fit on the GPU"));
+        }
+        let bases_memory = bases.len() * std::mem::size_of::<G>();
+        self.check_budget(bases_memory + self.working_memory(bases.len()))?;
+        let reservation = reserve(&self.budget, bases_memory)?;
+
+        let _affinity = self.bind_numa_node();
+        let bases_gpu = ScratchVec::from_iter(
+            self.host_allocator.as_ref(),
+            bases.iter().map(GpuRepr::to_gpu_repr),
+        );
+        let len = bases_gpu.len();
+        let owner = BufferOwner::new(self.id, &self.program);
+        let closures = program_closures!(|program,
+                                          _arg|
+         -> EcResult<
+            DeviceBuffer<<G as GpuRepr>::Repr>,
+        > {
+            let buffer = create_buffer_checked!(
+                program,
+                &bases_gpu[..],
+                <G as GpuRepr>::Repr,
+                &self.maybe_abort,
+                self.upload_check
+            );
+            Ok(program.wrap_buffer(buffer, len, owner))
+        });
+
+        let buffer = run_checked!(self.program, closures, ())?;
+        Ok(buffer.with_reservation(reservation))
+    }
+
+    /// Like [`SingleMultiexpKernel::multiexp`], but with bases that are
+    /// already in GPU memory, see [`SingleMultiexpKernel::upload_bases`].
+    ///
+    /// The buffer must have been created by this kernel, there must be an
+    /// exponent for each of its bases.
+    pub fn multiexp_resident(
+        &mut self, bases: &DeviceBuffer<<G as GpuRepr>::Repr>,
+        exponents: &[<G::Scalar as PrimeField>::Repr],
+    ) -> EcResult<G::Curve> {
+        check_len(bases.len(), exponents.len())?;
+        let partial = self.multiexp_gpu(
+            GpuBases::Resident(bases),
+            exponents,
+            None,
+            None,
+        )?;
+        Ok(partial.accumulate())
+    }
+
+    /// Like [`SingleMultiexpKernel::multiexp`], but the sums of the windows
+    /// are added to `partial` instead of being combined into the result, see
+    /// [`MultiexpPartial`].
+    pub fn multiexp_partial(
+        &mut self, bases: &[G], exponents: &[<G::Scalar as PrimeField>::Repr],
+        partial: &mut MultiexpPartial<G>,
+    ) -> EcResult<()> {
+        check_len(bases.len(), exponents.len())?;
+
+        let _affinity = self.bind_numa_node();
+        let bases_gpu = ScratchVec::from_iter(
+            self.host_allocator.as_ref(),
+            bases.iter().map(GpuRepr::to_gpu_repr),
+        );
+        self.multiexp_gpu(GpuBases::Affine(&bases_gpu), exponents, None, None)?
+            .add_to(partial);
+        Ok(())
+    }
+
+    /// Like [`SingleMultiexpKernel::multiexp`], but with projective bases.
+    ///
+    /// The bases are uploaded as they are and converted into affine form on
+    /// the GPU, like [`SingleMultiexpKernel::normalize_many`] does, before
+    /// they are added up. This saves the batched inversion on the host and
+    /// the transfer of the affine points back to it. Bases at infinity are
+    /// skipped. As a projective base takes more GPU memory than an affine
+    /// one, there must be fewer terms, see
+    /// [`SingleMultiexpKernel::projective_chunk_len`].
+    pub fn multiexp_projective(
+        &mut self, bases: &[G::Curve],
+        exponents: &[<G::Scalar as PrimeField>::Repr],
+    ) -> EcResult<G::Curve> {
+        check_len(bases.len(), exponents.len())?;
+        if bases.is_empty() {
+            return Ok(G::Curve::zero());
+        }
+
+        // The GPU representation of the point at infinity would be added like
+        // a regular point, hence such bases are masked out.
+        let mask = bases.iter().any(Zero::is_zero).then(|| {
+            bases.iter().map(|base| !base.is_zero()).collect::<Vec<_>>()
+        });
+        let partial = self.multiexp_gpu(
+            GpuBases::Projective(bases),
+            exponents,
+            mask.as_deref(),
+            None,
+        )?;
+        Ok(partial.accumulate())
+    }
+
+    /// Returns the number of terms of the next chunk of a multiexp with
+    /// projective bases, if there are `num_terms` left.
+    ///
+    /// It's smaller than the one of a multiexp with affine bases, as the
+    /// projective bases and the scratch space of their conversion take GPU
+    /// memory in addition to the affine ones.
+    pub fn projective_chunk_len(&self, num_terms: usize) -> EcResult<usize> {
+        let len = self.chunk_len(num_terms)?;
+        let term_size = std::mem::size_of::<G>() + exp_size::<G::Scalar>();
+        let extra_size = std::mem::size_of::<G::Curve>()
+            + std::mem::size_of::<G::BaseField>();
+        Ok(cmp::max(1, len * term_size / (term_size + extra_size)))
+    }
+
+    /// Like [`SingleMultiexpKernel::multiexp`], but additionally returns how
+    /// full the buckets got.
+    ///
+    /// This helps to tune the window size and to detect pathological
+    /// exponent distributions. Only small counters are read back, but they
+    /// are filled by an extra kernel with atomics, hence it is slower than a
+    /// plain multiexp. If `neg_is_cheap` is set, the digits are counted after
+    /// the signed recoding of the multiexp kernel, where a digit and its
+    /// negation share a bucket.
+    pub fn multiexp_with_occupancy(
+        &mut self, bases: &[G], exponents: &[<G::Scalar as PrimeField>::Repr],
+        neg_is_cheap: bool,
+    ) -> EcResult<(G::Curve, BucketOccupancy)> {
+        check_len(bases.len(), exponents.len())?;
+
+        let _affinity = self.bind_numa_node();
+        let bases_gpu = ScratchVec::from_iter(
+            self.host_allocator.as_ref(),
+            bases.iter().map(GpuRepr::to_gpu_repr),
+        );
+        let mut partial = self.multiexp_gpu(
+            GpuBases::Affine(&bases_gpu),
+            exponents,
+            None,
+            Some(neg_is_cheap),
+        )?;
+        let occupancy = partial
+            .occupancy
+            .take()
+            .expect("The occupancy was requested");
+        Ok((partial.accumulate(), occupancy))
+    }
+
+    /// Like [`SingleMultiexpKernel::multiexp`], but only the terms whose
+    /// `mask` entry is `true` are added up.
+    ///
+    /// The masked-out terms are skipped on the GPU, as if their exponent was
+    /// zero, hence the inputs don't need to be filtered on the host. If
+    /// operation counting is enabled, the masked-out terms aren't counted, as
+    /// they aren't added.
+    pub fn multiexp_masked(
+        &mut self, bases: &[G], exponents: &[<G::Scalar as PrimeField>::Repr],
+        mask: &[bool],
+    ) -> EcResult<G::Curve> {
+        check_len(bases.len(), exponents.len())?;
+        check_len(exponents.len(), mask.len())?;
+
+        let _affinity = self.bind_numa_node();
+        let bases_gpu = ScratchVec::from_iter(
+            self.host_allocator.as_ref(),
+            bases.iter().map(GpuRepr::to_gpu_repr),
+        );
+        let partial = self.multiexp_gpu(
+            GpuBases::Affine(&bases_gpu),
+            exponents,
+            Some(mask),
+            None,
+        )?;
+        Ok(partial.accumulate())
+    }
+
+    /// Like [`SingleMultiexpKernel::multiexp_masked`], but with bases that
+    /// are already in GPU memory, see [`SingleMultiexpKernel::upload_bases`].
+    ///
+    /// Only the exponents and the mask are uploaded. The buffer must have
+    /// been created by this kernel, there must be an exponent and a mask
+    /// entry for each of its bases.
+    pub fn multiexp_masked_resident(
+        &mut self, bases: &DeviceBuffer<<G as GpuRepr>::Repr>,
+        exponents: &[<G::Scalar as PrimeField>::Repr], mask: &[bool],
+    ) -> EcResult<G::Curve> {
+        check_len(bases.len(), exponents.len())?;
+        check_len(exponents.len(), mask.len())?;
+        let partial = self.multiexp_gpu(
+            GpuBases::Resident(bases),
+            exponents,
+            Some(mask),
+            None,
+        )?;
+        Ok(partial.accumulate())
+    }
+
+    /// Runs the GPU part of a multiexp.
+    ///
+    /// The returned per-chunk results still need to be accumulated on the
+    /// host, which allows to overlap it with the next GPU computation. If a
+    /// `mask` is given, only the selected terms are added up. If
+    /// `occupancy` is set, the bucket occupancy is counted as well, its value
+    /// tells whether the digits are recoded into signed ones.
+    fn multiexp_gpu(
+        &mut self, bases: GpuBases<'_, G>,
+        exponents: &[<G::Scalar as PrimeField>::Repr], mask: Option<&[bool]>,
+        occupancy: Option<bool>,
+    ) -> EcResult<PartialResults<G>> {
+        let (partial, op_count) = self.launch_multiexp(
+            &self.program,
+            bases,
+            exponents,
+            mask,
+            occupancy,
+        )?;
+        self.count_multiexp_ops(op_count);
+        Ok(partial)
+    }
+
+    /// Runs the GPU part of a multiexp on `program`, which is either the
+    /// program of the kernel or one of its streams.
+    ///
+    /// Besides the per-chunk results, it returns the operations the GPU
+    /// counted, which are all zero if counting is disabled.
+    fn launch_multiexp(
+        &self, program: &SharedProgram, bases: GpuBases<'_, G>,
+        exponents: &[<G::Scalar as PrimeField>::Repr], mask: Option<&[bool]>,
+        occupancy: Option<bool>,
+    ) -> EcResult<(PartialResults<G>, OpCount)> {
+        check_len(bases.len(), exponents.len())?;
+        if let Some(mask) = mask {
+            check_len(exponents.len(), mask.len())?;
+        }
+
         if let Some(maybe_abort) = &self.maybe_abort {
             if maybe_abort() {
                 return Err(EcError::Aborted);
             }
         }
-        let window_size = self.calc_window_size(bases.len());
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        let num_terms = exponents.len();
+        // Resident bases are already reserved by the buffer they are in.
+        let memory = match bases {
+            GpuBases::Affine(_) => self.chunk_memory(num_terms),
+            GpuBases::Projective(_) => {
+                self.chunk_memory(num_terms)
+                    + num_terms
+                        * (std::mem::size_of::<G::Curve>()
+                            + std::mem::size_of::<G::BaseField>())
+            }
+            GpuBases::Resident(_) => self.working_memory(num_terms),
+        };
+        let _reservation = reserve(&self.budget, memory)?;
+        let window_size = self.calc_window_size(num_terms);
         // windows_size * num_windows needs to be >= 256 in order for the kernel
         // to work correctly.
         let num_windows = div_ceil(256, window_size);
         let num_groups = self.work_units / num_windows;
         let bucket_len = 1 << window_size;
 
-        let bases_gpu: Vec<_> =
-            bases.iter().map(GpuRepr::to_gpu_repr).collect();
-
         // Each group will have `num_windows` threads and as there are
         // `num_groups` groups, there will be `num_groups` *
         // `num_windows` threads in total. Each thread will use
-        // `num_groups` * `num_windows` * `bucket_len` buckets.
+        // `num_groups` * `num_windows` * `bucket_len` buckets. A group adds
+        // up one chunk of the terms.
 
+        let count_ops = self.count_ops;
+        let owner = BufferOwner::new(self.id, program);
         let closures = program_closures!(|program,
                                           _arg|
-         -> EcResult<Vec<G::Curve>> {
-            let base_buffer = program.create_buffer_from_slice(&bases_gpu)?;
-            let exp_buffer = program.create_buffer_from_slice(exponents)?;
+         -> EcResult<(
+            Vec<G::Curve>,
+            [u32; 3],
+            Vec<u32>
+        )> {
+            // Large uploads are done in chunks, so that they can be aborted.
+            let uploaded;
+            let base_buffer = match bases {
+                GpuBases::Affine(bases_gpu) => {
+                    uploaded = create_buffer_checked!(
+                        program,
+                        bases_gpu,
+                        <G as GpuRepr>::Repr,
+                        &self.maybe_abort,
+                        self.upload_check
+                    );
+                    &uploaded
+                }
+                GpuBases::Projective(points) => {
+                    let point_buffer = create_buffer_checked!(
+                        program,
+                        points,
+                        G::Curve,
+                        &self.maybe_abort,
+                        self.upload_check
+                    );
+                    // It is safe as the GPU will initialize these buffers
+                    let base_buffer = unsafe {
+                        program
+                            .create_buffer::<<G as GpuRepr>::Repr>(num_terms)?
+                    };
+                    let scratch_buffer = unsafe {
+                        program.create_buffer::<G::BaseField>(num_terms)?
+                    };
+                    let chunk_len = div_ceil(num_terms, self.work_units);
+                    let num_threads = div_ceil(num_terms, chunk_len);
+                    program
+                        .create_kernel(
+                            &format!("{}_normalize_many", G::name()),
+                            div_ceil(num_threads, LOCAL_WORK_SIZE),
+                            LOCAL_WORK_SIZE,
+                        )?
+                        .arg(&point_buffer)
+                        .arg(&base_buffer)
+                        .arg(&scratch_buffer)
+                        .arg(&(num_terms as u32))
+                        .arg(&(chunk_len as u32))
+                        .run()?;
+                    uploaded = base_buffer;
+                    &uploaded
+                }
+                GpuBases::Resident(bases) => {
+                    program.borrow_buffer(bases, owner)?
+                }
+            };
+            let exp_buffer = create_buffer_checked!(
+                program,
+                exponents,
+                <G::Scalar as PrimeField>::Repr,
+                &self.maybe_abort,
+                self.upload_check
+            );
+
+            // The mask is small compared to the bases, it's uploaded at once.
+            // Without a mask the kernel doesn't read the buffer.
+            let mask_buffer = match mask {
+                Some(mask) => {
+                    let bytes =
+                        mask.iter().map(|&m| m as u8).collect::<Vec<_>>();
+                    program.create_buffer_from_slice(&bytes)?
+                }
+                None => program.create_buffer_from_slice(&[0u8])?,
+            };
 
             // It is safe as the GPU will initialize that buffer
             let bucket_buffer = unsafe {
@@ -170,23 +1150,23 @@ where G: GpuCurveAffine + GpuName
             };
             // It is safe as the GPU will initialize that buffer
             let result_buffer =
-                unsafe { program.create_buffer::<G::Curve>(self.work_units)? };
+                unsafe { program.create_buffer::<G::Curve>(num_groups)? };
+            // The mixed additions, additions and doublings, the kernel only
+            // increments them if the operations are counted.
+            let mut op_counts = [0u32; 3];
+            let op_count_buffer =
+                program.create_buffer_from_slice(&op_counts)?;
 
             // The global work size follows CUDA's definition and is the number
-            // of `LOCAL_WORK_SIZE` sized thread groups.
-            let global_work_size =
-                div_ceil(num_windows * num_groups, LOCAL_WORK_SIZE);
-
+            // of thread groups. The threads of a chunk synchronize, they must
+            // be in the same group.
             let kernel_name = format!("{}_multiexp", G::name());
-            let kernel = program.create_kernel(
-                &kernel_name,
-                global_work_size,
-                LOCAL_WORK_SIZE,
-            )?;
+            let kernel =
+                program.create_kernel(&kernel_name, num_groups, num_windows)?;
 
             dbg!(window_size, num_groups * num_windows);
             dbg!(
-                bases.len(),
+                num_terms,
                 self.work_units * bucket_len,
                 self.work_units,
                 exponents.len(),
@@ -195,210 +1175,3450 @@ where G: GpuCurveAffine + GpuName
                 window_size
             );
 
+            // A single line of bases, the digits are unsigned.
             kernel
-                .arg(&base_buffer)
-                .arg(&bucket_buffer)
+                .arg(base_buffer)
                 .arg(&result_buffer)
                 .arg(&exp_buffer)
-                .arg(&(bases.len() as u32))
+                .arg(&mask_buffer)
+                .arg(&bucket_buffer)
+                .arg(&(num_terms as u32))
+                .arg(&1u32)
                 .arg(&(num_groups as u32))
                 .arg(&(num_windows as u32))
                 .arg(&(window_size as u32))
+                .arg(&0u32)
+                .arg(&(mask.is_some() as u32))
+                .arg(&op_count_buffer)
+                .arg(&(count_ops as u32))
                 .run()?;
 
-            let mut results = vec![G::Curve::zero(); self.work_units];
+            let mut results = vec![G::Curve::zero(); num_groups];
 
             program.read_into_buffer(&result_buffer, &mut results)?;
+            if count_ops {
+                program.read_into_buffer(&op_count_buffer, &mut op_counts)?;
+            }
 
-            Ok(results)
+            // The occupancy is counted by a separate kernel, so that the
+            // multiexp itself doesn't pay for the atomics.
+            let mut occupancy_counts = Vec::new();
+            if let Some(neg_is_cheap) = occupancy {
+                occupancy_counts = vec![0u32; num_windows * bucket_len];
+                let occupancy_buffer =
+                    program.create_buffer_from_slice(&occupancy_counts)?;
+                let kernel = program.create_kernel(
+                    &format!("{}_multiexp_occupancy", G::name()),
+                    div_ceil(num_windows * num_groups, LOCAL_WORK_SIZE),
+                    LOCAL_WORK_SIZE,
+                )?;
+                kernel
+                    .arg(&exp_buffer)
+                    .arg(&occupancy_buffer)
+                    .arg(&(num_terms as u32))
+                    .arg(&(num_groups as u32))
+                    .arg(&(num_windows as u32))
+                    .arg(&(window_size as u32))
+                    .arg(&(neg_is_cheap as u32))
+                    .run()?;
+                program.read_into_buffer(
+                    &occupancy_buffer,
+                    &mut occupancy_counts,
+                )?;
+            }
+
+            Ok((results, op_counts, occupancy_counts))
         });
 
-        let results: Vec<G::Curve> = self.program.run(closures, ())?;
+        let (results, op_counts, occupancy_counts) =
+            run_checked!(*program, closures, ())?;
+        #[cfg(feature = "metrics")]
+        metrics::record(
+            Operation::Multiexp,
+            program.device_name(),
+            num_terms,
+            start.elapsed(),
+        );
+        #[cfg(feature = "tracing")]
+        tracing::debug!(window_size, num_windows, num_groups, "gpu part done");
 
-        // Using the algorithm below, we can calculate the final result by
-        // accumulating the results of those `NUM_GROUPS` *
-        // `NUM_WINDOWS` threads.
-        let mut acc = G::Curve::zero();
-        let mut bits = 0;
-        let exp_bits = exp_size::<G::Scalar>() * 8;
-        for i in 0..num_windows {
-            let w = std::cmp::min(window_size, exp_bits - bits);
-            for _ in 0..w {
-                acc = acc.double();
-            }
-            for g in 0..num_groups {
-                acc.add_assign(&results[g * num_windows + i]);
-            }
-            bits += w; // Process the next window
-        }
+        let partial = PartialResults {
+            results,
+            window_size,
+            num_windows,
+            occupancy: occupancy.map(|neg_is_cheap| {
+                BucketOccupancy::from_counts(
+                    &occupancy_counts,
+                    window_size,
+                    exp_size::<G::Scalar>() * 8,
+                    neg_is_cheap,
+                )
+            }),
+        };
+        let op_count = OpCount {
+            mixed_additions: op_counts[0] as u64,
+            additions: op_counts[1] as u64,
+            doublings: op_counts[2] as u64,
+        };
+        Ok((partial, op_count))
+    }
 
-        Ok(acc)
+    /// Adds the operations the GPU counted to the [`OpCount`], if counting
+    /// is enabled.
+    fn count_multiexp_ops(&mut self, op_count: OpCount) {
+        if self.count_ops {
+            self.op_count += op_count;
+        }
     }
 
-    /// Calculates the window size, based on the given number of terms.
+    /// Calculates the multiexps of the `jobs` chunk by chunk and adds them to
+    /// the corresponding `results`.
     ///
-    /// For best performance, the window size is reduced, so that maximum
+    /// None of the bases may be the point at infinity,
+    /// [`MultiexpKernel::multiexp_pipeline`] drops such terms beforehand.
+    /// The chunks are processed in waves of one chunk per program, i.e. the
+    /// program of the kernel and its streams, see
+    /// [`SingleMultiexpKernel::set_pipeline_streams`]. The chunks of a wave
+    /// are launched from separate threads, so that the upload of one chunk
+    /// overlaps with the computation and download of the others. While the
+    /// GPU works on a wave, the bases of the next wave are converted and the
+    /// results of the previous one are accumulated on the host. It stops
+    /// early once `error` is set by another device.
+    fn multiexp_pipeline(
+        &mut self, jobs: &[MultiexpJob<G>], results: &mut [G::Curve],
+        error: &RwLock<EcResult<()>>,
+    ) -> EcResult<()>
+    where
+        <G as GpuRepr>::Repr: Send + Sync,
+    {
+        // The chunks of all jobs, as index of the job and range of its terms.
+        let mut chunks: Vec<(usize, Range<usize>)> = Vec::new();
+        for (job, (_, exps)) in jobs.iter().enumerate() {
+            let mut offset = 0;
+            while offset < exps.len() {
+                let len = self.chunk_len(exps.len() - offset)?;
+                chunks.push((job, offset..offset + len));
+                offset += len;
+            }
+        }
+        let programs: Vec<SharedProgram> = iter::once(&self.program)
+            .chain(&self.streams)
+            .cloned()
+            .collect();
+        let waves: Vec<&[(usize, Range<usize>)]> =
+            chunks.chunks(programs.len()).collect();
+        let host_allocator = self.host_allocator.clone();
+        let convert = |wave: &[(usize, Range<usize>)]| {
+            wave.iter()
+                .map(|(job, range)| {
+                    ScratchVec::from_iter(
+                        host_allocator.as_ref(),
+                        jobs[*job].0[range.clone()]
+                            .iter()
+                            .map(GpuRepr::to_gpu_repr),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut next = waves.first().map(|wave| convert(wave));
+        let mut pending: Vec<(usize, PartialResults<G>)> = Vec::new();
+        for (i, wave) in waves.iter().enumerate() {
+            if error.read().unwrap().is_err() {
+                return Ok(());
+            }
+            let bases_gpu = next.take().expect("The wave was converted");
+            let this = &*self;
+            let (launched, (converted, accumulated)) = rayon::join(
+                || {
+                    wave.par_iter()
+                        .zip(bases_gpu.par_iter())
+                        .zip(programs.par_iter())
+                        .map(|(((job, range), bases), program)| {
+                            // The staging buffers of the launch are allocated
+                            // on the worker thread.
+                            let _affinity = this.bind_numa_node();
+                            let launched = this.launch_multiexp(
+                                program,
+                                GpuBases::Affine(bases),
+                                &jobs[*job].1[range.clone()],
+                                None,
+                                None,
+                            )?;
+                            Ok((*job, launched))
+                        })
+                        .collect::<EcResult<Vec<_>>>()
+                },
+                || {
+                    let accumulated: Vec<_> = pending
+                        .drain(..)
+                        .map(|(job, partial)| (job, partial.accumulate()))
+                        .collect();
+                    (waves.get(i + 1).map(|wave| convert(wave)), accumulated)
+                },
+            );
+            for (job, acc) in accumulated {
+                results[job].add_assign(&acc);
+            }
+            next = converted;
+            for (job, (partial, op_count)) in launched? {
+                self.count_multiexp_ops(op_count);
+                pending.push((job, partial));
+            }
+        }
+        for (job, partial) in pending {
+            results[job].add_assign(&partial.accumulate());
+        }
+        Ok(())
+    }
+
+    /// Calculates a multiexp of every set of bases with the same
+    /// `exponents`.
+    ///
+    /// The exponents are uploaded once and stay on the GPU, while the bases
+    /// of one set after another are uploaded and multiplied with them. All
+    /// sets use the same window size, hence the exponents are also split into
+    /// windows the same way. Every set must have as many bases as there are
+    /// exponents, which must not exceed [`SingleMultiexpKernel`]`::n`. The
+    /// results are in the order of the `base_sets`. The operations are not
+    /// counted.
+    pub fn multiexp_shared_scalars(
+        &mut self, base_sets: &[&[G]],
+        exponents: &[<G::Scalar as PrimeField>::Repr],
+    ) -> EcResult<Vec<G::Curve>> {
+        let num_terms = exponents.len();
+        for bases in base_sets {
+            check_len(num_terms, bases.len())?;
+        }
+        if num_terms > self.n {
+            return Err(EcError::InvalidLength(format!(
+                "there are {} exponents, but at most {} fit on the GPU",
+                num_terms, self.n
+            )));
+        }
+        if base_sets.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        if let Some(maybe_abort) = &self.maybe_abort {
+            if maybe_abort() {
+                return Err(EcError::Aborted);
+            }
+        }
+        // Only the bases of a single set are on the GPU at the same time.
+        let _reservation = reserve(&self.budget, self.chunk_memory(num_terms))?;
+        let window_size = self.calc_window_size(num_terms);
+        // windows_size * num_windows needs to be >= 256 in order for the kernel
+        // to work correctly.
+        let num_windows = div_ceil(256, window_size);
+        let num_groups = self.work_units / num_windows;
+        let bucket_len = 1 << window_size;
+
+        let _affinity = self.bind_numa_node();
+        let closures =
+            program_closures!(|program,
+                               _arg|
+             -> EcResult<Vec<Vec<G::Curve>>> {
+                let exp_buffer = create_buffer_checked!(
+                    program,
+                    exponents,
+                    <G::Scalar as PrimeField>::Repr,
+                    &self.maybe_abort,
+                    self.upload_check
+                );
+                // It is safe as the GPU will initialize that buffer
+                let bucket_buffer = unsafe {
+                    program.create_buffer::<G::Curve>(
+                        self.work_units * bucket_len,
+                    )?
+                };
+                // It is safe as the GPU will initialize that buffer
+                let result_buffer =
+                    unsafe { program.create_buffer::<G::Curve>(num_groups)? };
+
+                let kernel_name = format!("{}_multiexp", G::name());
+                // Without a mask and counting, the kernel doesn't read these
+                // buffers.
+                let mask_buffer = program.create_buffer_from_slice(&[0u8])?;
+                let op_count_buffer =
+                    program.create_buffer_from_slice(&[0u32; 3])?;
+                let mut results = Vec::with_capacity(base_sets.len());
+                for bases in base_sets {
+                    // The buffer of the previous set is freed at this point.
+                    let bases_gpu = ScratchVec::from_iter(
+                        self.host_allocator.as_ref(),
+                        bases.iter().map(GpuRepr::to_gpu_repr),
+                    );
+                    let base_buffer = create_buffer_checked!(
+                        program,
+                        &bases_gpu[..],
+                        <G as GpuRepr>::Repr,
+                        &self.maybe_abort,
+                        self.upload_check
+                    );
+                    let kernel = program.create_kernel(
+                        &kernel_name,
+                        num_groups,
+                        num_windows,
+                    )?;
+                    kernel
+                        .arg(&base_buffer)
+                        .arg(&result_buffer)
+                        .arg(&exp_buffer)
+                        .arg(&mask_buffer)
+                        .arg(&bucket_buffer)
+                        .arg(&(num_terms as u32))
+                        .arg(&1u32)
+                        .arg(&(num_groups as u32))
+                        .arg(&(num_windows as u32))
+                        .arg(&(window_size as u32))
+                        .arg(&0u32)
+                        .arg(&0u32)
+                        .arg(&op_count_buffer)
+                        .arg(&0u32)
+                        .run()?;
+
+                    let mut set_results = vec![G::Curve::zero(); num_groups];
+                    program
+                        .read_into_buffer(&result_buffer, &mut set_results)?;
+                    results.push(set_results);
+                }
+                Ok(results)
+            });
+
+        let results = run_checked!(self.program, closures, ())?;
+        Ok(results
+            .iter()
+            .map(|results| sum_chunks::<G>(results))
+            .collect())
+    }
+
+    /// Calculates a multiexp of the same `bases` with every set of
+    /// exponents.
+    ///
+    /// The bases are uploaded once and stay on the GPU, while the exponents
+    /// of one set after another are uploaded and multiplied with them. A set
+    /// may have fewer exponents than there are bases, it's multiplied with
+    /// the first bases only. There must not be more bases than
+    /// [`SingleMultiexpKernel`]`::n`. The results are in the order of the
+    /// `exponent_sets`. The operations are not counted.
+    pub fn multiexp_shared_bases(
+        &mut self, bases: &[G],
+        exponent_sets: &[&[<G::Scalar as PrimeField>::Repr]],
+    ) -> EcResult<Vec<G::Curve>> {
+        let num_bases = bases.len();
+        for exponents in exponent_sets {
+            if exponents.len() > num_bases {
+                return Err(EcError::InvalidLength(format!(
+                    "a set has {} exponents, but there are {} bases",
+                    exponents.len(),
+                    num_bases
+                )));
+            }
+        }
+        if num_bases > self.n {
+            return Err(EcError::InvalidLength(format!(
+                "there are {} bases, but at most {} fit on the GPU",
+                num_bases, self.n
+            )));
+        }
+        let max_len = exponent_sets.iter().map(|exps| exps.len()).max();
+        let max_len = match max_len {
+            Some(max_len) if max_len > 0 => max_len,
+            _ => return Ok(vec![G::Curve::zero(); exponent_sets.len()]),
+        };
+
+        if let Some(maybe_abort) = &self.maybe_abort {
+            if maybe_abort() {
+                return Err(EcError::Aborted);
+            }
+        }
+        // Only the exponents of a single set are on the GPU at the same time.
+        let _reservation = reserve(&self.budget, self.chunk_memory(max_len))?;
+        // Every set has its own window size, the buckets are allocated for the
+        // largest one.
+        let max_bucket_len = 1 << self.calc_window_size(max_len);
+
+        let _affinity = self.bind_numa_node();
+        let bases_gpu = ScratchVec::from_iter(
+            self.host_allocator.as_ref(),
+            bases[..max_len].iter().map(GpuRepr::to_gpu_repr),
+        );
+        let closures = program_closures!(|program,
+                                          _arg|
+         -> EcResult<Vec<G::Curve>> {
+            let base_buffer = create_buffer_checked!(
+                program,
+                &bases_gpu[..],
+                <G as GpuRepr>::Repr,
+                &self.maybe_abort,
+                self.upload_check
+            );
+            // It is safe as the GPU will initialize that buffer
+            let bucket_buffer = unsafe {
+                program.create_buffer::<G::Curve>(
+                    self.work_units * max_bucket_len,
+                )?
+            };
+            // It is safe as the GPU will initialize that buffer, every set
+            // has at most one chunk per work unit.
+            let result_buffer =
+                unsafe { program.create_buffer::<G::Curve>(self.work_units)? };
+
+            let kernel_name = format!("{}_multiexp", G::name());
+            // Without a mask and counting, the kernel doesn't read these
+            // buffers.
+            let mask_buffer = program.create_buffer_from_slice(&[0u8])?;
+            let op_count_buffer =
+                program.create_buffer_from_slice(&[0u32; 3])?;
+            let mut results = Vec::with_capacity(exponent_sets.len());
+            for exponents in exponent_sets {
+                let num_terms = exponents.len();
+                if num_terms == 0 {
+                    results.push(G::Curve::zero());
+                    continue;
+                }
+                let window_size = self.calc_window_size(num_terms);
+                // windows_size * num_windows needs to be >= 256 in order
+                // for the kernel to work correctly.
+                let num_windows = div_ceil(256, window_size);
+                let num_groups = self.work_units / num_windows;
+
+                // The buffer of the previous set is freed at this point.
+                let exp_buffer = create_buffer_checked!(
+                    program,
+                    exponents,
+                    <G::Scalar as PrimeField>::Repr,
+                    &self.maybe_abort,
+                    self.upload_check
+                );
+                let kernel = program.create_kernel(
+                    &kernel_name,
+                    num_groups,
+                    num_windows,
+                )?;
+                // Only the first `num_terms` bases are read.
+                kernel
+                    .arg(&base_buffer)
+                    .arg(&result_buffer)
+                    .arg(&exp_buffer)
+                    .arg(&mask_buffer)
+                    .arg(&bucket_buffer)
+                    .arg(&(num_terms as u32))
+                    .arg(&1u32)
+                    .arg(&(num_groups as u32))
+                    .arg(&(num_windows as u32))
+                    .arg(&(window_size as u32))
+                    .arg(&0u32)
+                    .arg(&0u32)
+                    .arg(&op_count_buffer)
+                    .arg(&0u32)
+                    .run()?;
+
+                // Only the first `num_groups` results are written.
+                let mut set_results = vec![G::Curve::zero(); self.work_units];
+                program.read_into_buffer(&result_buffer, &mut set_results)?;
+                results.push(sum_chunks::<G>(&set_results[..num_groups]));
+            }
+            Ok(results)
+        });
+
+        run_checked!(self.program, closures, ())
+    }
+
+    /// Calculates a multiexp with the precomputed multiples of the bases from
+    /// `table`, see [`BaseTable`].
+    ///
+    /// The windows have the size of the table. There must be as many
+    /// `exponents` as the table has bases, its multiples must not exceed
+    /// [`SingleMultiexpKernel`]`::n`. The terms of bases at infinity are
+    /// skipped. The operations are not counted.
+    pub fn multiexp_with_table(
+        &mut self, table: &BaseTable<G>,
+        exponents: &[<G::Scalar as PrimeField>::Repr],
+    ) -> EcResult<G::Curve> {
+        let num_terms = exponents.len();
+        check_len(table.len(), num_terms)?;
+        if table.multiples.len() > self.n {
+            return Err(EcError::InvalidLength(format!(
+                "the table has {} multiples, but at most {} fit on the GPU",
+                table.multiples.len(),
+                self.n
+            )));
+        }
+        // The bases at infinity have no multiples, their terms are dropped.
+        let finite_exponents: Vec<_>;
+        let exponents = match &table.identities {
+            Some(identities) => {
+                finite_exponents = exponents
+                    .iter()
+                    .zip(identities)
+                    .filter(|(_, &is_identity)| !is_identity)
+                    .map(|(exp, _)| exp.clone())
+                    .collect();
+                &finite_exponents[..]
+            }
+            None => exponents,
+        };
+        let num_terms = exponents.len();
+        if num_terms == 0 {
+            return Ok(G::Curve::zero());
+        }
+
+        if let Some(maybe_abort) = &self.maybe_abort {
+            if maybe_abort() {
+                return Err(EcError::Aborted);
+            }
+        }
+        let window_size = table.window_size();
+        let num_windows = div_ceil(256, window_size);
+        let num_groups = self.work_units / num_windows;
+        let _reservation = reserve(
+            &self.budget,
+            table.memory_size()
+                + num_terms * exp_size::<G::Scalar>()
+                + self.work_units * std::mem::size_of::<G::Curve>(),
+        )?;
+
+        let _affinity = self.bind_numa_node();
+        let table_gpu = ScratchVec::from_iter(
+            self.host_allocator.as_ref(),
+            table.multiples.iter().map(GpuRepr::to_gpu_repr),
+        );
+        let closures = program_closures!(|program,
+                                          _arg|
+         -> EcResult<Vec<G::Curve>> {
+            let table_buffer = create_buffer_checked!(
+                program,
+                &table_gpu[..],
+                <G as GpuRepr>::Repr,
+                &self.maybe_abort,
+                self.upload_check
+            );
+            let exp_buffer = create_buffer_checked!(
+                program,
+                exponents,
+                <G::Scalar as PrimeField>::Repr,
+                &self.maybe_abort,
+                self.upload_check
+            );
+            // It is safe as the GPU will initialize that buffer
+            let result_buffer =
+                unsafe { program.create_buffer::<G::Curve>(self.work_units)? };
+
+            let kernel = program.create_kernel(
+                &format!("{}_multiexp_table", G::name()),
+                div_ceil(num_windows * num_groups, LOCAL_WORK_SIZE),
+                LOCAL_WORK_SIZE,
+            )?;
+            kernel
+                .arg(&table_buffer)
+                .arg(&result_buffer)
+                .arg(&exp_buffer)
+                .arg(&(num_terms as u32))
+                .arg(&(num_groups as u32))
+                .arg(&(num_windows as u32))
+                .arg(&(window_size as u32))
+                .arg(&(table.multiples_per_base() as u32))
+                .arg(&(table.is_signed() as u32))
+                .run()?;
+
+            let mut results = vec![G::Curve::zero(); self.work_units];
+            program.read_into_buffer(&result_buffer, &mut results)?;
+            Ok(results)
+        });
+
+        let results = run_checked!(self.program, closures, ())?;
+        Ok(accumulate::<G>(
+            &results,
+            window_size,
+            num_windows,
+            num_groups,
+        ))
+    }
+
+    /// Commits to a polynomial, given by its `evaluations` over the subgroup
+    /// of the same size.
+    ///
+    /// It runs an inverse FFT followed by a multiexp of the resulting
+    /// coefficients with `bases`. The coefficients stay on the GPU, they are
+    /// neither downloaded nor converted on the host. Hence the program must
+    /// also contain the FFT kernels of the scalar field, i.e. its source
+    /// needs to be built with `SourceBuilder::add_fft::<G::Scalar>()`.
+    ///
+    /// The number of `evaluations` must be a power of two and must not exceed
+    /// [`SingleMultiexpKernel`]`::n`.
+    pub fn commit_polynomial(
+        &mut self, bases: &[G], evaluations: &[G::Scalar],
+    ) -> EcResult<G::Curve>
+    where G::Scalar: GpuName {
+        check_len(evaluations.len(), bases.len())?;
+        if evaluations.len() > self.n {
+            return Err(EcError::InvalidLength(format!(
+                "the polynomial has {} evaluations, but at most {} fit on the \
+                 GPU",
+                evaluations.len(),
+                self.n
+            )));
+        }
+        // The whole commit is reserved at once, the coefficients keep the
+        // reservation for the multiexp.
+        let n = evaluations.len();
+        let coefficients = self.fft_to_device(
+            evaluations,
+            true,
+            self.resident_chunk_memory(n),
+        )?;
+        self.multiexp_from_device(bases, coefficients)
+    }
+
+    /// Commits to the evaluations of a polynomial, given by its
+    /// `coefficients`, over the subgroup of the same size, in bit-reversed
+    /// order.
+    ///
+    /// It runs an FFT followed by a multiexp of the resulting evaluations
+    /// with `bases`, where the evaluation at index `i` is multiplied with
+    /// the base at the bit-reversed index of `i`. This matches an SRS in
+    /// Lagrange form that is stored in bit-reversed order. The evaluations
+    /// stay on the GPU and are never permuted, instead the bases are
+    /// reordered while they are converted into their GPU representation.
+    ///
+    /// The requirements are the same as for
+    /// [`SingleMultiexpKernel::commit_polynomial`].
+    pub fn commit_evaluations_bit_reversed(
+        &mut self, bases: &[G], coefficients: &[G::Scalar],
+    ) -> EcResult<G::Curve>
+    where G::Scalar: GpuName {
+        check_len(coefficients.len(), bases.len())?;
+        if coefficients.len() > self.n {
+            return Err(EcError::InvalidLength(format!(
+                "the polynomial has {} coefficients, but at most {} fit on \
+                 the GPU",
+                coefficients.len(),
+                self.n
+            )));
+        }
+        let n = coefficients.len();
+        let evaluations = self.fft_to_device(
+            coefficients,
+            false,
+            self.resident_chunk_memory(n),
+        )?;
+        self.multiexp_from_device_ordered(bases, evaluations, true)
+    }
+
+    /// Runs an inverse FFT of the `evaluations` over the subgroup of the same
+    /// size and keeps the resulting coefficients on the GPU.
+    ///
+    /// The coefficients are in Montgomery form. The program must contain the
+    /// FFT kernels of the scalar field, see
+    /// [`SingleMultiexpKernel::commit_polynomial`]. The number of
+    /// `evaluations` must be a power of two.
+    pub fn ifft_to_device(
+        &mut self, evaluations: &[G::Scalar],
+    ) -> EcResult<DeviceBuffer<G::Scalar>>
+    where G::Scalar: GpuName {
+        let n = evaluations.len();
+        self.fft_to_device(
+            evaluations,
+            true,
+            n * std::mem::size_of::<G::Scalar>(),
+        )
+    }
+
+    /// Runs an FFT, or an inverse FFT if `inverse` is set, of the `values`
+    /// over the subgroup of the same size and keeps the result on the GPU,
+    /// see [`SingleMultiexpKernel::ifft_to_device`].
+    ///
+    /// The returned buffer keeps a reservation of `keep` bytes, which covers
+    /// at least the buffer itself.
+    fn fft_to_device(
+        &mut self, values: &[G::Scalar], inverse: bool, keep: usize,
+    ) -> EcResult<DeviceBuffer<G::Scalar>>
+    where G::Scalar: GpuName {
+        let n = values.len();
+        if !n.is_power_of_two() {
+            return Err(EcError::InvalidLength(format!(
+                "the FFT size {} is not a power of two",
+                n
+            )));
+        }
+
+        if let Some(maybe_abort) = &self.maybe_abort {
+            if maybe_abort() {
+                return Err(EcError::Aborted);
+            }
+        }
+        let log_n = n.trailing_zeros();
+        let omega = G::Scalar::get_root_of_unity(n as u64)
+            .and_then(|omega| {
+                if inverse {
+                    omega.inverse()
+                } else {
+                    Some(omega)
+                }
+            })
+            .ok_or(EcError::Simple("The field has no subgroup of that size"))?;
+        let (pq, omegas) = precalculate_twiddles(&omega, log_n);
+        let elem_size = std::mem::size_of::<G::Scalar>();
+        // The returned buffer keeps its memory, the second buffer and the
+        // twiddles are only needed during the FFT. Everything is reserved at
+        // once, as several reservations could wait for each other.
+        let keep = cmp::max(keep, n * elem_size);
+        let mut reservation = reserve(
+            &self.budget,
+            cmp::max(keep, (2 * n + pq.len() + omegas.len()) * elem_size),
+        )?;
+        let max_deg = cmp::min(MAX_LOG2_RADIX, log_n);
+        // The inverse FFT needs a scaling by `1/n`.
+        let factor = inverse.then(|| {
+            G::Scalar::from(n as u64).inverse().expect("n is non-zero")
+        });
+        let owner = BufferOwner::new(self.id, &self.program);
+
+        let closures = program_closures!(|program,
+                                          _arg|
+         -> EcResult<
+            DeviceBuffer<G::Scalar>,
+        > {
+            let mut src_buffer = program.create_buffer_from_slice(values)?;
+            // It is safe as the GPU will initialize that buffer
+            let mut dst_buffer =
+                unsafe { program.create_buffer::<G::Scalar>(n)? };
+            let pq_buffer = program.create_buffer_from_slice(&pq)?;
+            let omegas_buffer = program.create_buffer_from_slice(&omegas)?;
+
+            let mut log_p = 0u32;
+            while log_p < log_n {
+                if let Some(maybe_abort) = &self.maybe_abort {
+                    if maybe_abort() {
+                        return Err(EcError::Aborted);
+                    }
+                }
+
+                let deg = cmp::min(max_deg, log_n - log_p);
+                let local_work_size =
+                    1 << cmp::min(deg - 1, MAX_LOG2_LOCAL_WORK_SIZE);
+                let global_work_size = n >> deg;
+                let kernel = program.create_kernel(
+                    &format!("{}_radix_fft", G::Scalar::name()),
+                    global_work_size,
+                    local_work_size,
+                )?;
+                kernel
+                    .arg(&src_buffer)
+                    .arg(&dst_buffer)
+                    .arg(&pq_buffer)
+                    .arg(&omegas_buffer)
+                    .arg(&LocalBuffer::<G::Scalar>::new(1 << deg))
+                    .arg(&(n as u32))
+                    .arg(&log_p)
+                    .arg(&deg)
+                    .arg(&max_deg)
+                    .arg(&1u32)
+                    .arg(&1u32)
+                    .arg(&NO_POST_MAP)
+                    .arg(&omegas_buffer)
+                    .run()?;
+
+                log_p += deg;
+                std::mem::swap(&mut src_buffer, &mut dst_buffer);
+            }
+
+            if let Some(factor) = factor {
+                let factor_buffer =
+                    program.create_buffer_from_slice(&[factor])?;
+                let (elementwise_global, elementwise_local) =
+                    elementwise_work_size(n);
+                let kernel = program.create_kernel(
+                    &format!("{}_scale", G::Scalar::name()),
+                    elementwise_global,
+                    elementwise_local,
+                )?;
+                kernel
+                    .arg(&src_buffer)
+                    .arg(&factor_buffer)
+                    .arg(&(n as u32))
+                    .run()?;
+            }
+
+            Ok(program.wrap_buffer(src_buffer, n, owner))
+        });
+
+        let buffer = run_checked!(self.program, closures, ())?;
+        if let Some(reservation) = &mut reservation {
+            reservation.resize(keep)?;
+        }
+        Ok(buffer.with_reservation(reservation))
+    }
+
+    /// Calculates `sum_i x^i * bases[i]`.
+    ///
+    /// The powers of `x` are generated on the GPU, they are never
+    /// materialized on the host. The program must contain the FFT kernels of
+    /// the scalar field, see [`SingleMultiexpKernel::commit_polynomial`].
+    /// Bases at infinity are skipped, like in
+    /// [`SingleMultiexpKernel::multiexp_from_device`].
+    pub fn multiexp_powers(
+        &mut self, bases: &[G], x: G::Scalar,
+    ) -> EcResult<G::Curve>
+    where G::Scalar: GpuName {
+        self.multiexp_powers_from(bases, x, 0)
+    }
+
+    /// Calculates `sum_i x^(first + i) * bases[i]`, see
+    /// [`SingleMultiexpKernel::multiexp_powers`].
+    fn multiexp_powers_from(
+        &mut self, bases: &[G], x: G::Scalar, first: usize,
+    ) -> EcResult<G::Curve>
+    where G::Scalar: GpuName {
+        let mut acc = G::Curve::zero();
+        let mut offset = 0;
+        while offset < bases.len() {
+            // The powers stay on the device, they take the place of the
+            // exponents.
+            let len = self.chunk_len_by(bases.len() - offset, |len| {
+                self.resident_chunk_memory(len)
+            })?;
+            let start = pow_vartime(&x, [(first + offset) as u64]);
+            let powers = self.powers_to_device(len, start, x)?;
+            let chunk = &bases[offset..offset + len];
+            acc.add_assign(&self.multiexp_from_device(chunk, powers)?);
+            offset += len;
+        }
+        Ok(acc)
+    }
+
+    /// Generates `start * x^i` for all `i < n` on the GPU, in Montgomery
+    /// form.
+    ///
+    /// The returned buffer keeps the reservation of a whole multiexp of `n`
+    /// terms.
+    fn powers_to_device(
+        &mut self, n: usize, start: G::Scalar, x: G::Scalar,
+    ) -> EcResult<DeviceBuffer<G::Scalar>>
+    where G::Scalar: GpuName {
+        if let Some(maybe_abort) = &self.maybe_abort {
+            if maybe_abort() {
+                return Err(EcError::Aborted);
+            }
+        }
+        // The powers are only used by the multiexp, hence the memory of the
+        // multiexp is reserved right away. Growing the reservation of the
+        // buffer later could wait for the reservations of other devices.
+        let reservation = reserve(&self.budget, self.resident_chunk_memory(n))?;
+        let owner = BufferOwner::new(self.id, &self.program);
+
+        let closures = program_closures!(|program,
+                                          _arg|
+         -> EcResult<
+            DeviceBuffer<G::Scalar>,
+        > {
+            // It is safe as the GPU will initialize that buffer
+            let powers_buffer =
+                unsafe { program.create_buffer::<G::Scalar>(n)? };
+            let params_buffer =
+                program.create_buffer_from_slice(&[start, x])?;
+            let (global_work_size, local_work_size) = elementwise_work_size(n);
+            let kernel = program.create_kernel(
+                &format!("{}_powers", G::Scalar::name()),
+                global_work_size,
+                local_work_size,
+            )?;
+            kernel
+                .arg(&powers_buffer)
+                .arg(&params_buffer)
+                .arg(&(n as u32))
+                .run()?;
+
+            Ok(program.wrap_buffer(powers_buffer, n, owner))
+        });
+
+        let buffer = run_checked!(self.program, closures, ())?;
+        Ok(buffer.with_reservation(reservation))
+    }
+
+    /// Calculates the multiexp of `bases` with the `coefficients` that are
+    /// already on the GPU, e.g. the result of
+    /// [`SingleMultiexpKernel::ifft_to_device`].
+    ///
+    /// The buffer must have been created by this kernel. The coefficients are
+    /// in Montgomery form, they are converted into exponents on the GPU. The
+    /// program must contain the FFT kernels of the scalar field, see
+    /// [`SingleMultiexpKernel::commit_polynomial`]. The number of
+    /// `coefficients` must not exceed [`SingleMultiexpKernel`]`::n`. Bases at
+    /// infinity are skipped.
+    pub fn multiexp_from_device(
+        &mut self, bases: &[G], coefficients: DeviceBuffer<G::Scalar>,
+    ) -> EcResult<G::Curve>
+    where G::Scalar: GpuName {
+        self.multiexp_from_device_ordered(bases, coefficients, false)
+    }
+
+    /// Like [`SingleMultiexpKernel::multiexp_from_device`], but if
+    /// `bit_reversed` is set, the coefficient `i` is multiplied with the base
+    /// at the bit-reversed index of `i`.
+    ///
+    /// This is the same as a multiexp of the bit-reversed coefficients with
+    /// the `bases`. The bases are reordered while they are converted into
+    /// their GPU representation anyway, hence it is for free.
+    fn multiexp_from_device_ordered(
+        &mut self, bases: &[G], mut coefficients: DeviceBuffer<G::Scalar>,
+        bit_reversed: bool,
+    ) -> EcResult<G::Curve>
+    where
+        G::Scalar: GpuName,
+    {
+        let n = coefficients.len();
+        check_len(n, bases.len())?;
+        if n > self.n {
+            return Err(EcError::InvalidLength(format!(
+                "there are {} coefficients, but at most {} fit on the GPU",
+                n, self.n
+            )));
+        }
+        if bit_reversed && !n.is_power_of_two() {
+            return Err(EcError::InvalidLength(format!(
+                "the {} coefficients are bit-reversed, but that's not a power \
+                 of two",
+                n
+            )));
+        }
+
+        if let Some(maybe_abort) = &self.maybe_abort {
+            if maybe_abort() {
+                return Err(EcError::Aborted);
+            }
+        }
+        // The coefficients are already reserved, they are used as exponents.
+        // If their reservation covers the whole multiexp, it doesn't grow.
+        let _reservation = reserve_with(
+            &self.budget,
+            coefficients.take_reservation(),
+            self.resident_chunk_memory(n),
+        )?;
+        // A scaling by `R^-1` converts the coefficients into normal form,
+        // which is what the multiexp kernel expects as exponents.
+        let r = pow_vartime(
+            &G::Scalar::from(2u64),
+            [(exp_size::<G::Scalar>() * 8) as u64],
+        );
+        let r_inv = r.inverse().expect("R is non-zero");
+
+        let window_size = self.calc_window_size(n);
+        // windows_size * num_windows needs to be >= 256 in order for the kernel
+        // to work correctly.
+        let num_windows = div_ceil(256, window_size);
+        let num_groups = self.work_units / num_windows;
+        let bucket_len = 1 << window_size;
+
+        let _affinity = self.bind_numa_node();
+        let log_n = n.trailing_zeros();
+        let index = |i: usize| {
+            if bit_reversed {
+                bitreverse(i, log_n)
+            } else {
+                i
+            }
+        };
+        let bases_gpu = ScratchVec::from_iter(
+            self.host_allocator.as_ref(),
+            (0..n).map(|i| bases[index(i)].to_gpu_repr()),
+        );
+        // The GPU representation of the point at infinity would be added like
+        // a regular point, hence such bases are masked out.
+        let mask = bases.iter().any(GpuCurveAffine::is_identity).then(|| {
+            (0..n)
+                .map(|i| !bases[index(i)].is_identity() as u8)
+                .collect::<Vec<_>>()
+        });
+        let owner = BufferOwner::new(self.id, &self.program);
+
+        // The buffer is passed as argument, so that it is freed while the
+        // context of the program is active.
+        let closures = program_closures!(|program,
+                                          coefficients|
+         -> EcResult<Vec<G::Curve>> {
+            let coeff_buffer = program.unwrap_buffer(coefficients, owner)?;
+            let base_buffer = program.create_buffer_from_slice(&bases_gpu)?;
+
+            let factor_buffer = program.create_buffer_from_slice(&[r_inv])?;
+            let (elementwise_global, elementwise_local) =
+                elementwise_work_size(n);
+            let kernel = program.create_kernel(
+                &format!("{}_scale", G::Scalar::name()),
+                elementwise_global,
+                elementwise_local,
+            )?;
+            kernel
+                .arg(&coeff_buffer)
+                .arg(&factor_buffer)
+                .arg(&(n as u32))
+                .run()?;
+
+            // It is safe as the GPU will initialize that buffer
+            let bucket_buffer = unsafe {
+                program
+                    .create_buffer::<G::Curve>(self.work_units * bucket_len)?
+            };
+            // It is safe as the GPU will initialize that buffer
+            let result_buffer =
+                unsafe { program.create_buffer::<G::Curve>(num_groups)? };
+
+            // Without a mask and counting, the kernel doesn't read these
+            // buffers.
+            let mask_buffer = match &mask {
+                Some(mask) => program.create_buffer_from_slice(mask)?,
+                None => program.create_buffer_from_slice(&[0u8])?,
+            };
+            let op_count_buffer =
+                program.create_buffer_from_slice(&[0u32; 3])?;
+            let kernel = program.create_kernel(
+                &format!("{}_multiexp", G::name()),
+                num_groups,
+                num_windows,
+            )?;
+            // The coefficients are used as exponents directly.
+            kernel
+                .arg(&base_buffer)
+                .arg(&result_buffer)
+                .arg(&coeff_buffer)
+                .arg(&mask_buffer)
+                .arg(&bucket_buffer)
+                .arg(&(n as u32))
+                .arg(&1u32)
+                .arg(&(num_groups as u32))
+                .arg(&(num_windows as u32))
+                .arg(&(window_size as u32))
+                .arg(&0u32)
+                .arg(&(mask.is_some() as u32))
+                .arg(&op_count_buffer)
+                .arg(&0u32)
+                .run()?;
+
+            let mut results = vec![G::Curve::zero(); num_groups];
+            program.read_into_buffer(&result_buffer, &mut results)?;
+
+            Ok(results)
+        });
+
+        let results = run_checked!(self.program, closures, coefficients)?;
+        Ok(sum_chunks::<G>(&results))
+    }
+
+    /// Reduces the `wide` integers modulo the scalar field order.
+    ///
+    /// The integers consist of `K` little-endian 64-bit limbs. The result can
+    /// directly be used as exponents.
+    pub fn reduce_wide<const K: usize>(
+        &mut self, wide: &[[u64; K]],
+    ) -> EcResult<Vec<<G::Scalar as PrimeField>::Repr>> {
+        if wide.is_empty() {
+            return Ok(Vec::new());
+        }
+        if let Some(maybe_abort) = &self.maybe_abort {
+            if maybe_abort() {
+                return Err(EcError::Aborted);
+            }
+        }
+
+        let n = wide.len();
+        let _reservation = reserve(
+            &self.budget,
+            n * (std::mem::size_of::<[u64; K]>() + exp_size::<G::Scalar>()),
+        )?;
+        // The integers are processed as 32-bit words on the GPU.
+        let words = K * 2;
+        let radix = G::Scalar::from(1u64 << 32);
+
+        let closures = program_closures!(|program,
+                                          _arg|
+         -> EcResult<
+            Vec<<G::Scalar as PrimeField>::Repr>,
+        > {
+            let wide_buffer = program.create_buffer_from_slice(wide)?;
+            let radix_buffer = program.create_buffer_from_slice(&[radix])?;
+            // It is safe as the GPU will initialize that buffer
+            let exp_buffer = unsafe {
+                program.create_buffer::<<G::Scalar as PrimeField>::Repr>(n)?
+            };
+
+            let (global_work_size, local_work_size) = elementwise_work_size(n);
+            let kernel = program.create_kernel(
+                &format!("{}_multiexp_reduce_wide", G::name()),
+                global_work_size,
+                local_work_size,
+            )?;
+            kernel
+                .arg(&wide_buffer)
+                .arg(&exp_buffer)
+                .arg(&radix_buffer)
+                .arg(&(n as u32))
+                .arg(&(words as u32))
+                .run()?;
+
+            let mut exps = vec![Default::default(); n];
+            program.read_into_buffer(&exp_buffer, &mut exps)?;
+
+            Ok(exps)
+        });
+
+        run_checked!(self.program, closures, ())
+    }
+
+    /// Converts the given projective `points` into affine form.
+    ///
+    /// Each GPU thread converts a chunk of the points with a single field
+    /// inversion. The point at infinity is supported.
+    pub fn normalize_many(&mut self, points: &[G::Curve]) -> EcResult<Vec<G>> {
+        if points.is_empty() {
+            return Ok(Vec::new());
+        }
+        if let Some(maybe_abort) = &self.maybe_abort {
+            if maybe_abort() {
+                return Err(EcError::Aborted);
+            }
+        }
+
+        let n = points.len();
+        let _reservation = reserve(
+            &self.budget,
+            n * (std::mem::size_of::<G::Curve>()
+                + std::mem::size_of::<<G as GpuRepr>::Repr>()
+                + std::mem::size_of::<G::BaseField>()),
+        )?;
+        let chunk_len = div_ceil(n, self.work_units);
+        let num_threads = div_ceil(n, chunk_len);
+
+        let closures = program_closures!(|program,
+                                          _arg|
+         -> EcResult<
+            Vec<<G as GpuRepr>::Repr>,
+        > {
+            let point_buffer = program.create_buffer_from_slice(points)?;
+            // It is safe as the GPU will initialize that buffer
+            let result_buffer =
+                unsafe { program.create_buffer::<<G as GpuRepr>::Repr>(n)? };
+            // It is safe as the GPU will initialize that buffer
+            let scratch_buffer =
+                unsafe { program.create_buffer::<G::BaseField>(n)? };
+
+            let kernel = program.create_kernel(
+                &format!("{}_normalize_many", G::name()),
+                div_ceil(num_threads, LOCAL_WORK_SIZE),
+                LOCAL_WORK_SIZE,
+            )?;
+            kernel
+                .arg(&point_buffer)
+                .arg(&result_buffer)
+                .arg(&scratch_buffer)
+                .arg(&(n as u32))
+                .arg(&(chunk_len as u32))
+                .run()?;
+
+            let mut results = vec![G::zero().to_gpu_repr(); n];
+            program.read_into_buffer(&result_buffer, &mut results)?;
+
+            Ok(results)
+        });
+
+        let results = run_checked!(self.program, closures, ())?;
+        Ok(results.iter().map(G::from_gpu_repr).collect())
+    }
+
+    /// Converts the given projective `points` into their canonical compressed
+    /// encodings, which are returned one after another.
+    ///
+    /// The encoding is the one of arkworks' [`CanonicalSerialize`]. The
+    /// points are normalized like in [`SingleMultiexpKernel::normalize_many`]
+    /// and compressed on the GPU, only the encodings are read back. The
+    /// compression kernel is generated by `SourceBuilder::add_multiexp` for
+    /// curves over a prime field. Returns an error if the encoding of the
+    /// curve isn't the `x` coordinate with flags in one of its bytes.
+    pub fn compress_many(&mut self, points: &[G::Curve]) -> EcResult<Vec<u8>>
+    where G::Base: CanonicalBytes {
+        let layout = CompressedLayout::of::<G>()?;
+        if points.is_empty() {
+            return Ok(Vec::new());
+        }
+        if let Some(maybe_abort) = &self.maybe_abort {
+            if maybe_abort() {
+                return Err(EcError::Aborted);
+            }
+        }
+
+        let n = points.len();
+        let num_bytes = n * layout.bytes_per_point;
+        let _reservation = reserve(
+            &self.budget,
+            n * (std::mem::size_of::<G::Curve>()
+                + std::mem::size_of::<<G as GpuRepr>::Repr>()
+                + std::mem::size_of::<G::BaseField>())
+                + num_bytes,
+        )?;
+        let chunk_len = div_ceil(n, self.work_units);
+        let num_threads = div_ceil(n, chunk_len);
+
+        let closures = program_closures!(|program,
+                                          _arg|
+         -> EcResult<Vec<u8>> {
+            let point_buffer = program.create_buffer_from_slice(points)?;
+            // It is safe as the GPU will initialize that buffer
+            let affine_buffer =
+                unsafe { program.create_buffer::<<G as GpuRepr>::Repr>(n)? };
+            // It is safe as the GPU will initialize that buffer
+            let scratch_buffer =
+                unsafe { program.create_buffer::<G::BaseField>(n)? };
+            // It is safe as the GPU will initialize that buffer
+            let bytes_buffer =
+                unsafe { program.create_buffer::<u8>(num_bytes)? };
+
+            let kernel = program.create_kernel(
+                &format!("{}_normalize_many", G::name()),
+                div_ceil(num_threads, LOCAL_WORK_SIZE),
+                LOCAL_WORK_SIZE,
+            )?;
+            kernel
+                .arg(&point_buffer)
+                .arg(&affine_buffer)
+                .arg(&scratch_buffer)
+                .arg(&(n as u32))
+                .arg(&(chunk_len as u32))
+                .run()?;
+
+            let (global_work_size, local_work_size) = elementwise_work_size(n);
+            let kernel = program.create_kernel(
+                &format!("{}_compress", G::name()),
+                global_work_size,
+                local_work_size,
+            )?;
+            kernel
+                .arg(&affine_buffer)
+                .arg(&bytes_buffer)
+                .arg(&(n as u32))
+                .arg(&(layout.big_endian as u32))
+                .arg(&(layout.flag_byte as u32))
+                .arg(&(layout.always as u32))
+                .arg(&(layout.sign as u32))
+                .arg(&(layout.infinity as u32))
+                .run()?;
+
+            let mut bytes = vec![0u8; num_bytes];
+            program.read_into_buffer(&bytes_buffer, &mut bytes)?;
+
+            Ok(bytes)
+        });
+
+        run_checked!(self.program, closures, ())
+    }
+
+    /// Checks for each of the `points` whether it satisfies the curve
+    /// equation.
+    ///
+    /// The coefficient `b` is derived from the generator, as the GPU code only
+    /// supports curves with `a = 0`. The point at infinity is on the curve.
+    /// This doesn't check whether the points are in the prime order subgroup.
+    pub fn check_on_curve(&mut self, points: &[G]) -> EcResult<Vec<bool>> {
+        if points.is_empty() {
+            return Ok(Vec::new());
+        }
+        if let Some(maybe_abort) = &self.maybe_abort {
+            if maybe_abort() {
+                return Err(EcError::Aborted);
+            }
+        }
+
+        let n = points.len();
+        let _reservation = reserve(
+            &self.budget,
+            n * (std::mem::size_of::<<G as GpuRepr>::Repr>()
+                + std::mem::size_of::<u32>()),
+        )?;
+        let (x, y) = G::generator()
+            .xy()
+            .expect("The generator is not the point at infinity");
+        let b = y.square() - x.square() * x;
+        let points_gpu = ScratchVec::from_iter(
+            self.host_allocator.as_ref(),
+            points.iter().map(GpuRepr::to_gpu_repr),
+        );
+
+        let closures = program_closures!(|program,
+                                          _arg|
+         -> EcResult<Vec<u32>> {
+            let points_buffer =
+                program.create_buffer_from_slice(&points_gpu)?;
+            let b_buffer = program.create_buffer_from_slice(&[b])?;
+            // It is safe as the GPU will initialize that buffer
+            let results_buffer = unsafe { program.create_buffer::<u32>(n)? };
+
+            let (global_work_size, local_work_size) = elementwise_work_size(n);
+            let kernel = program.create_kernel(
+                &format!("{}_check_on_curve", G::name()),
+                global_work_size,
+                local_work_size,
+            )?;
+            kernel
+                .arg(&points_buffer)
+                .arg(&b_buffer)
+                .arg(&results_buffer)
+                .arg(&(n as u32))
+                .run()?;
+
+            let mut results = vec![0u32; n];
+            program.read_into_buffer(&results_buffer, &mut results)?;
+
+            Ok(results)
+        });
+
+        let results = run_checked!(self.program, closures, ())?;
+        Ok(results.into_iter().map(|result| result != 0).collect())
+    }
+
+    /// Calculates `g * s^i` for all `i` in `0..n`, e.g. to generate an SRS
+    /// from a generator and a secret `s`.
+    ///
+    /// The powers of `s` are calculated on the GPU, every one independently
+    /// from a table of `s^(2^j)`. Hence `n` must not exceed `2^32`, larger
+    /// values are an [`EcError::InvalidLength`].
+    pub fn powers_of_tau(
+        &mut self, g: G, s: G::Scalar, n: usize,
+    ) -> EcResult<Vec<G::Curve>> {
+        if n > 1 << 32 {
+            return Err(EcError::InvalidLength(format!(
+                "there are {} powers, but at most 2^32 are supported",
+                n
+            )));
+        }
+        let mut s_powers = Vec::with_capacity(32);
+        let mut power = s;
+        for _ in 0..32 {
+            s_powers.push(power);
+            power.square_in_place();
+        }
+        let base = [g.into_group()];
+        // A projective point needs less memory than two terms of a multiexp.
+        let chunk_len = cmp::max(self.n / 2, 1);
+
+        let mut results = Vec::with_capacity(n);
+        for start in (0..n).step_by(chunk_len) {
+            if let Some(maybe_abort) = &self.maybe_abort {
+                if maybe_abort() {
+                    return Err(EcError::Aborted);
+                }
+            }
+            let len = cmp::min(chunk_len, n - start);
+            let _reservation = reserve(
+                &self.budget,
+                (len + 1) * std::mem::size_of::<G::Curve>()
+                    + s_powers.len() * std::mem::size_of::<G::Scalar>(),
+            )?;
+
+            let closures =
+                program_closures!(|program, _arg| -> EcResult<Vec<G::Curve>> {
+                    let base_buffer =
+                        program.create_buffer_from_slice(&base)?;
+                    let powers_buffer =
+                        program.create_buffer_from_slice(&s_powers)?;
+                    // It is safe as the GPU will initialize that buffer
+                    let result_buffer =
+                        unsafe { program.create_buffer::<G::Curve>(len)? };
+
+                    let kernel = program.create_kernel(
+                        &format!("{}_powers_of_tau", G::name()),
+                        div_ceil(len, LOCAL_WORK_SIZE),
+                        LOCAL_WORK_SIZE,
+                    )?;
+                    kernel
+                        .arg(&base_buffer)
+                        .arg(&powers_buffer)
+                        .arg(&result_buffer)
+                        .arg(&(start as u32))
+                        .arg(&(len as u32))
+                        .run()?;
+
+                    let mut results = vec![G::Curve::zero(); len];
+                    program.read_into_buffer(&result_buffer, &mut results)?;
+
+                    Ok(results)
+                });
+
+            results.extend(run_checked!(self.program, closures, ())?);
+        }
+        Ok(results)
+    }
+
+    /// Sets the GPU memory limit this kernel shares with other kernels.
+    pub fn set_budget(&mut self, budget: Arc<MemoryBudget>) {
+        self.budget = Some(budget);
+    }
+
+    /// Returns the GPU memory (in bytes) a multiexp of `num_terms` terms
+    /// needs.
+    fn chunk_memory(&self, num_terms: usize) -> usize {
+        let term_size = std::mem::size_of::<G>() + exp_size::<G::Scalar>();
+        let bucket_len = 1 << self.calc_window_size(num_terms);
+        num_terms * term_size
+            + self.work_units
+                * (bucket_len + 1)
+                * std::mem::size_of::<G::Curve>()
+    }
+
+    /// Returns the GPU memory (in bytes) a multiexp of `num_terms` terms
+    /// needs besides its bases, i.e. if the bases are already on the device.
+    fn working_memory(&self, num_terms: usize) -> usize {
+        self.chunk_memory(num_terms) - num_terms * std::mem::size_of::<G>()
+    }
+
+    /// Fails with [`EcError::MemoryBudgetExceeded`] if there is a budget and
+    /// `requested` bytes exceed its limit.
+    ///
+    /// Memory that stays reserved, like resident bases, must be checked this
+    /// way up front, a later reservation would wait for it forever.
+    fn check_budget(&self, requested: usize) -> EcResult<()> {
+        match &self.budget {
+            Some(budget) if requested > budget.limit() => {
+                Err(EcError::MemoryBudgetExceeded {
+                    requested,
+                    limit: budget.limit(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the GPU memory (in bytes) a multiexp of `num_terms` terms
+    /// needs, whose scalars are already on the device in Montgomery form.
+    ///
+    /// It includes the resident scalars, they replace the exponents.
+    fn resident_chunk_memory(&self, num_terms: usize) -> usize {
+        self.chunk_memory(num_terms) - num_terms * exp_size::<G::Scalar>()
+            + num_terms * std::mem::size_of::<G::Scalar>()
+    }
+
+    /// Returns the number of terms of the next chunk, if there are
+    /// `num_terms` left.
+    ///
+    /// It's at most [`SingleMultiexpKernel`]`::n`. If there is a memory
+    /// budget, the chunk is reduced until it fits within the whole budget.
+    fn chunk_len(&self, num_terms: usize) -> EcResult<usize> {
+        self.chunk_len_by(num_terms, |len| self.chunk_memory(len))
+    }
+
+    /// Like [`SingleMultiexpKernel::chunk_len`], but a chunk of `len` terms
+    /// needs `memory(len)` bytes.
+    fn chunk_len_by(
+        &self, num_terms: usize, memory: impl Fn(usize) -> usize,
+    ) -> EcResult<usize> {
+        let max_len = cmp::min(num_terms, self.n);
+        let limit = match &self.budget {
+            Some(budget) => budget.limit(),
+            None => return Ok(max_len),
+        };
+        if memory(max_len) <= limit {
+            return Ok(max_len);
+        }
+        if memory(1) > limit {
+            return Err(EcError::MemoryBudgetExceeded {
+                requested: memory(1),
+                limit,
+            });
+        }
+        // The memory grows with the number of terms, hence search for the
+        // largest chunk that still fits.
+        let (mut fits, mut exceeds) = (1, max_len);
+        while exceeds - fits > 1 {
+            let mid = fits + (exceeds - fits) / 2;
+            if memory(mid) <= limit {
+                fits = mid;
+            } else {
+                exceeds = mid;
+            }
+        }
+        Ok(fits)
+    }
+
+    /// Enables or disables the allocation of the host staging buffers on the
+    /// NUMA node the device is attached to.
+    pub fn set_numa_aware(&mut self, numa_aware: bool) {
+        self.numa_aware = numa_aware;
+    }
+
+    /// Returns the information of the device the kernel runs on.
+    pub fn device_info(&self) -> &DeviceInfo { &self.device_info }
+
+    /// Sets the allocator of the temporary host buffers, like the GPU
+    /// representations of the bases.
+    pub fn set_host_allocator(&mut self, allocator: HostAllocator) {
+        self.host_allocator = Some(allocator);
+    }
+
+    /// Sets further programs of the same device, which are used by
+    /// [`MultiexpKernel::multiexp_pipeline`] in addition to the program of
+    /// the kernel.
+    ///
+    /// Every program has its own command queue or stream, the pipeline keeps
+    /// one chunk in flight per program, so that uploads, computations and
+    /// downloads of different chunks overlap. The programs must have been
+    /// built for the device of the kernel, e.g. with `program!`, otherwise an
+    /// error is returned. Each of them needs the GPU memory of a chunk. An
+    /// empty vector restores the single queue.
+    pub fn set_pipeline_streams(
+        &mut self, programs: Vec<Program>,
+    ) -> EcResult<()> {
+        let streams: Vec<SharedProgram> =
+            programs.into_iter().map(SharedProgram::new).collect();
+        if streams.iter().any(|stream| {
+            stream.device_name() != self.program.device_name()
+                || stream.backend() != self.program.backend()
+        }) {
+            return Err(EcError::Simple(
+                "The program was built for another device",
+            ));
+        }
+        self.streams = streams;
+        Ok(())
+    }
+
+    /// Sets the window size of the multiexps, `None` restores the heuristic
+    /// based on the number of terms.
+    ///
+    /// The window size must be between one and [`MAX_WINDOW_SIZE`], and not
+    /// larger than the bit length of the scalar field, otherwise an error is
+    /// returned. A window of `c` bits needs `2^c` buckets per work unit. The
+    /// chunk size is calculated for the largest window, hence every valid
+    /// window fits into GPU memory, but with a memory budget the chunks get
+    /// smaller the larger the window is, see
+    /// [`SingleMultiexpKernel::set_budget`].
+    pub fn set_window_size(
+        &mut self, window_size: Option<usize>,
+    ) -> EcResult<()> {
+        if let Some(window_size) = window_size {
+            let max_window_size =
+                cmp::min(MAX_WINDOW_SIZE, G::Scalar::MODULUS_BIT_SIZE as usize);
+            if window_size == 0 || window_size > max_window_size {
+                return Err(EcError::Simple("The window size is out of range"));
+            }
+        }
+        self.window_size_override = window_size;
+        Ok(())
+    }
+
+    /// Binds the current thread to the NUMA node of the device, if NUMA
+    /// awareness is enabled and the node is known.
+    fn bind_numa_node(&self) -> Option<NodeAffinity> {
+        self.numa_node
+            .filter(|_| self.numa_aware)
+            .and_then(NodeAffinity::bind)
+    }
+
+    /// Enables or disables counting of the elliptic curve operations.
+    ///
+    /// Counting is off by default, as the kernel increments atomic counters
+    /// for every operation it performs.
+    pub fn set_count_ops(&mut self, count_ops: bool) {
+        self.count_ops = count_ops;
+    }
+
+    /// Enables or disables the verification of the bases and exponents that
+    /// [`SingleMultiexpKernel::multiexp`] and
+    /// [`SingleMultiexpKernel::multiexp_shared_scalars`] upload.
+    ///
+    /// After every upload, a checksum of every chunk of the data is
+    /// calculated on the GPU and compared with the one of the host data. Only
+    /// the checksums are read back, but the data is read once more on both
+    /// sides. A mismatch results in an [`EcError::TransferCorrupted`]. It is
+    /// off by default.
+    pub fn set_verify_transfers(&mut self, verify: bool) {
+        self.upload_check.verify = verify;
+    }
+
+    /// Sets the number of bytes of bases or exponents that are uploaded at
+    /// once, the abort hook is called before every chunk.
+    ///
+    /// Smaller chunks abort a large upload sooner, but every chunk is an
+    /// additional copy on the GPU. The default is 64 MiB.
+    pub fn set_transfer_chunk_bytes(&mut self, bytes: usize) {
+        self.upload_check.chunk_bytes = bytes;
+    }
+
+    /// Simulates a transfer error by flipping the first word of every upload
+    /// of bases and exponents on the GPU. It's only meant for testing.
+    #[doc(hidden)]
+    pub fn inject_upload_corruption(&mut self, corrupt: bool) {
+        self.upload_check.corrupt = corrupt;
+    }
+
+    /// Returns the operations counted since the last reset.
+    pub fn op_count(&self) -> OpCount { self.op_count }
+
+    /// Resets the operation counter to zero.
+    pub fn reset_op_count(&mut self) { self.op_count = OpCount::default(); }
+
+    /// Returns the maximum number of terms of a single run of the kernel.
+    pub fn chunk_size(&self) -> usize { self.n }
+
+    /// Returns the number of units the work is split into.
+    pub fn work_units(&self) -> usize { self.work_units }
+
+    /// Returns the window size a multiexp of `num_terms` terms uses for its
+    /// first chunk.
+    pub fn window_size(&self, num_terms: usize) -> usize {
+        self.calc_window_size(cmp::min(num_terms, self.n))
+    }
+
+    /// Returns the name of the backend, either `"cuda"` or `"opencl"`.
+    pub fn backend(&self) -> &'static str { self.program.backend() }
+
+    /// Returns a rough estimate of the time a multiexp of `num_terms` terms
+    /// takes on this device.
+    ///
+    /// It is based on a simple cost model of the number of field
+    /// multiplications and the data transfers, it's meant for scheduling and
+    /// not for exact predictions. Call [`SingleMultiexpKernel::calibrate`] to
+    /// adjust it to the actual device.
+    pub fn estimate_time(&self, num_terms: usize) -> Duration {
+        let full_chunks = num_terms / self.n;
+        let rest = num_terms % self.n;
+        let mut secs = full_chunks as f64 * self.model_chunk_secs(self.n);
+        if rest > 0 {
+            secs += self.model_chunk_secs(rest);
+        }
+        Duration::from_secs_f64(secs * self.time_scale)
+    }
+
+    /// Returns the modelled seconds of a single kernel run with `num_terms`
+    /// terms.
+    ///
+    /// The window size is modelled as a continuous value, so that the time
+    /// grows monotonically with the number of terms.
+    fn model_chunk_secs(&self, num_terms: usize) -> f64 {
+        let terms_per_unit =
+            (num_terms as f64 / self.work_units as f64).max(1.0);
+        let window_size = match self.window_size_override {
+            Some(window_size) => window_size as f64,
+            None => {
+                (terms_per_unit.log2() + 2.0).clamp(2.0, MAX_WINDOW_SIZE as f64)
+            }
+        };
+        let exp_bits = (exp_size::<G::Scalar>() * 8) as f64;
+        // Every term is added into one bucket per window, afterwards every
+        // thread sums up its buckets with two additions per bucket.
+        let mixed_additions = num_terms as f64 * exp_bits / window_size;
+        let additions = self.work_units as f64 * 2.0 * window_size.exp2();
+        let muls =
+            mixed_additions * MIXED_ADDITION_MULS + additions * ADDITION_MULS;
+
+        let term_size = std::mem::size_of::<G>() + exp_size::<G::Scalar>();
+        let transfer = num_terms * term_size
+            + self.work_units * std::mem::size_of::<G::Curve>();
+        estimate::compute_secs::<G::Base>(muls, self.compute_units)
+            + estimate::transfer_secs(transfer)
+    }
+
+    /// Calibrates [`SingleMultiexpKernel::estimate_time`] by timing a small
+    /// multiexp on the device.
+    pub fn calibrate(&mut self) -> EcResult<()> {
+        let num_terms = cmp::min(CALIBRATION_TERMS, self.n);
+        let bases = vec![G::generator(); num_terms];
+        let exponents: Vec<_> = (0..num_terms)
+            .map(|i| G::Scalar::from(i as u64 + 1).to_repr())
+            .collect();
+        // The first run includes one-time costs, like the compilation of the
+        // kernel, hence it's not timed.
+        self.multiexp(&bases, &exponents)?;
+        let start = Instant::now();
+        self.multiexp(&bases, &exponents)?;
+        let measured = start.elapsed().as_secs_f64();
+        self.time_scale = measured / self.model_chunk_secs(num_terms);
+        Ok(())
+    }
+
+    /// Calculates the window size, based on the given number of terms.
+    ///
+    /// For best performance, the window size is reduced, so that maximum
     /// parallelism is possible. If you e.g. have put only a subset of the
     /// terms into the GPU memory, then a smaller window size leads to more
     /// windows, hence more units to work on, as we split the work into
     /// `num_windows * num_groups`.
     fn calc_window_size(&self, num_terms: usize) -> usize {
-        // The window size was determined by running the
-        // `gpu_multiexp_consistency` test and looking at the resulting
-        // numbers.
-        let window_size =
-            ((div_ceil(num_terms, self.work_units) as f64).log2() as usize) + 2;
-        std::cmp::min(window_size, MAX_WINDOW_SIZE)
+        self.window_size_override
+            .unwrap_or_else(|| window_size(num_terms, self.work_units))
+    }
+}
+
+/// A struct that containts several multiexp kernels for different devices.
+///
+/// The kernels own the programs they are created from, they stay valid no
+/// matter what happens to the caller's bindings of them.
+pub struct MultiexpKernel<'a, G>
+where G: GpuCurveAffine
+{
+    kernels: Vec<SingleMultiexpKernel<'a, G>>,
+    /// The operations of the last multiexp, if counting is enabled.
+    op_count: Option<OpCount>,
+    /// How the terms of a multiexp are split among the devices.
+    splitter: Arc<dyn WorkSplitter>,
+    /// What happens to bases that are the point at infinity.
+    identity_handling: IdentityHandling,
+    /// Whether the results are independent of timing and device properties,
+    /// see [`MultiexpKernel::set_reproducible`].
+    reproducible: bool,
+}
+
+impl<'a, G> MultiexpKernel<'a, G>
+where G: GpuCurveAffine + GpuName
+{
+    /// Create new kernels, one for each given device.
+    pub fn create(
+        programs: Vec<Program>, devices: &[&Device],
+    ) -> EcResult<Self> {
+        Self::create_optional_abort(share(programs), devices, None)
+    }
+
+    /// Create new kernels, one for each given device, with early abort hook.
+    ///
+    /// The `maybe_abort` function is called when it is possible to abort the
+    /// computation, without leaving the GPU in a weird state. If that
+    /// function returns `true`, execution is aborted.
+    pub fn create_with_abort(
+        programs: Vec<Program>, devices: &[&Device],
+        maybe_abort: &'a (dyn Fn() -> bool + Send + Sync),
+    ) -> EcResult<Self> {
+        Self::create_optional_abort(share(programs), devices, Some(maybe_abort))
+    }
+
+    /// Create new kernels from programs that may be shared with other
+    /// kernels, one for each given device.
+    ///
+    /// See [`SharedProgram`] for how to use a single program for several
+    /// kinds of kernels.
+    pub fn create_shared(
+        programs: Vec<SharedProgram>, devices: &[&Device],
+    ) -> EcResult<Self> {
+        Self::create_optional_abort(programs, devices, None)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "multiexp_create",
+            skip_all,
+            fields(num_programs = programs.len())
+        )
+    )]
+    fn create_optional_abort(
+        programs: Vec<SharedProgram>, devices: &[&Device],
+        maybe_abort: Option<&'a (dyn Fn() -> bool + Send + Sync)>,
+    ) -> EcResult<Self> {
+        // Check it upfront, as it is not a problem of a specific device.
+        if let Some(reason) = G::unsupported_reason() {
+            return Err(EcError::unsupported_curve::<G>(reason));
+        }
+        let kernels = working_kernels(programs.into_iter().zip(devices).map(
+            |(program, device)| {
+                let device_name = program.device_name().to_string();
+                let kernel =
+                    SingleMultiexpKernel::create(program, device, maybe_abort);
+                (device_name, kernel)
+            },
+        ))?;
+
+        info!("Multiexp: {} working device(s) selected.", kernels.len());
+        for (i, k) in kernels.iter().enumerate() {
+            info!(
+                "Multiexp: Device {}: {} (Chunk-size: {})",
+                i,
+                k.program.device_name(),
+                k.n
+            );
+            #[cfg(feature = "tracing")]
+            tracing::info!(
+                device = k.program.device_name(),
+                backend = k.program.backend(),
+                chunk_size = k.n,
+                "device selected"
+            );
+        }
+        Ok(MultiexpKernel {
+            kernels,
+            op_count: None,
+            splitter: Arc::new(EvenSplit),
+            identity_handling: IdentityHandling::default(),
+            reproducible: false,
+        })
+    }
+
+    /// Calculate multiexp on all available GPUs.
+    ///
+    /// It needs to run within a [`yastl::Scope`]. This method usually isn't
+    /// called directly, use [`MultiexpKernel::multiexp`] instead.
+    pub fn parallel_multiexp<'s>(
+        &'s mut self, scope: &Scope<'s>, bases: &'s [G],
+        exps: &'s [<G::Scalar as PrimeField>::Repr],
+        results: &'s mut [G::Curve], error: Arc<RwLock<EcResult<()>>>,
+    ) {
+        self.parallel_multiexp_notify(scope, bases, exps, results, error, None)
+    }
+
+    /// Like [`MultiexpKernel::parallel_multiexp`], but additionally sends the
+    /// index of each device together with its result to `finished`, as soon
+    /// as the device is done.
+    fn parallel_multiexp_notify<'s>(
+        &'s mut self, scope: &Scope<'s>, bases: &'s [G],
+        exps: &'s [<G::Scalar as PrimeField>::Repr],
+        results: &'s mut [G::Curve], error: Arc<RwLock<EcResult<()>>>,
+        finished: Option<Sender<(usize, G::Curve)>>,
+    ) {
+        let chunks = match self.device_chunks(exps.len(), |kern, num_terms| {
+            kern.chunk_len(num_terms)
+        }) {
+            Ok(chunks) => chunks,
+            Err(e) => {
+                *error.write().unwrap() = Err(e);
+                return;
+            }
+        };
+
+        Self::scoped_chunks(
+            &mut self.kernels,
+            scope,
+            chunks,
+            results,
+            &error,
+            move |kern, chunk, acc| {
+                let result =
+                    kern.multiexp(&bases[chunk.clone()], &exps[chunk])?;
+                acc.add_assign(&result);
+                Ok(())
+            },
+            move |device, acc| {
+                if let Some(finished) = &finished {
+                    // The receiver only goes away if the caller isn't
+                    // interested in the result anymore.
+                    let _ = finished.send((device, *acc));
+                }
+            },
+        );
+    }
+
+    /// Calculate multiexp.
+    ///
+    /// This is the main entry point.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "multiexp",
+            skip_all,
+            fields(num_terms = exps.len(), num_devices = self.kernels.len())
+        )
+    )]
+    pub fn multiexp(
+        &mut self, pool: &Worker, bases_arc: Arc<Vec<G>>,
+        exps: Arc<Vec<<G::Scalar as PrimeField>::Repr>>, skip: usize,
+    ) -> EcResult<G::Curve> {
+        let mut acc = G::Curve::zero();
+        self.multiexp_with_progress(
+            pool,
+            bases_arc,
+            exps,
+            skip,
+            |_, partial| acc.add_assign(&partial),
+        )?;
+        Ok(acc)
+    }
+
+    /// Calculates a multiexp and calls `on_partial` with the index of each
+    /// device and its partial result, as soon as that device is done.
+    ///
+    /// `on_partial` is called on the calling thread, while the other devices
+    /// are still running. The sum of the partial results is the result of
+    /// [`MultiexpKernel::multiexp`]. If a device fails, the error is returned
+    /// after all devices stopped, `on_partial` may have been called for other
+    /// devices then. In the reproducible mode, see
+    /// [`MultiexpKernel::set_reproducible`], it's called in the order of the
+    /// devices once all of them are done.
+    pub fn multiexp_with_progress<P>(
+        &mut self, pool: &Worker, bases_arc: Arc<Vec<G>>,
+        exps: Arc<Vec<<G::Scalar as PrimeField>::Repr>>, skip: usize,
+        mut on_partial: P,
+    ) -> EcResult<()>
+    where
+        P: FnMut(usize, G::Curve),
+    {
+        // Bases are skipped by `self.1` elements, when converted from
+        // (Arc<Vec<G>>, usize) to Source https://github.com/zkcrypto/bellman/blob/10c5010fd9c2ca69442dc9775ea271e286e776d8/src/multiexp.rs#L38
+        let bases = &bases_arc[skip..(skip + exps.len())];
+        let exps = &exps[..];
+
+        let (bases, exps) = self.identity_handling.finite_terms(bases, exps)?;
+        let (bases, exps) = (&bases[..], &exps[..]);
+
+        let mut results = Vec::new();
+        let error = Arc::new(RwLock::new(Ok(())));
+        for kern in self.kernels.iter_mut() {
+            kern.reset_op_count();
+        }
+
+        let num_kernels = self.kernels.len();
+        let reproducible = self.reproducible;
+        let (sender, receiver) = unbounded();
+        pool.scoped(|s| {
+            results = vec![G::Curve::zero(); num_kernels];
+            self.parallel_multiexp_notify(
+                s,
+                bases,
+                exps,
+                &mut results,
+                error.clone(),
+                Some(sender),
+            );
+            // The channel is closed once all devices dropped their sender.
+            if reproducible {
+                let mut partials = receiver.into_iter().collect::<Vec<_>>();
+                partials.sort_by_key(|(device, _)| *device);
+                for (device, partial) in partials {
+                    on_partial(device, partial);
+                }
+            } else {
+                for (device, partial) in receiver {
+                    on_partial(device, partial);
+                }
+            }
+        });
+
+        Arc::try_unwrap(error)
+            .expect("only one ref left")
+            .into_inner()
+            .unwrap()?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!("all devices done");
+
+        if self.op_count.is_some() {
+            let mut op_count = OpCount::default();
+            for kern in self.kernels.iter() {
+                op_count += kern.op_count();
+            }
+            self.op_count = Some(op_count);
+        }
+
+        Ok(())
+    }
+
+    /// Copies `bases` into the memory of the GPUs, so that repeated multiexps
+    /// with them don't upload them every time, see
+    /// [`MultiexpKernel::multiexp_with_handle`].
+    ///
+    /// The bases are split among the devices by the [`WorkSplitter`] and
+    /// stay in GPU memory until the returned handle is dropped, hence they
+    /// must fit into the memory of the devices. Bases that are the point at
+    /// infinity are handled according to
+    /// [`MultiexpKernel::set_identity_handling`] at the time of the upload.
+    ///
+    /// If there is a memory budget, the bases must fit within it next to the
+    /// working memory of a multiexp on each device. Otherwise it fails with
+    /// [`EcError::MemoryBudgetExceeded`] before anything is uploaded.
+    pub fn upload_bases(
+        &mut self, bases: Arc<Vec<G>>,
+    ) -> EcResult<BaseHandle<G>> {
+        let identities = self
+            .identity_handling
+            .has_identity(&bases)?
+            .then(|| bases.iter().map(GpuCurveAffine::is_identity).collect());
+
+        // All chunks stay in GPU memory, next to the working memory of the
+        // largest chunk of each device, as the devices run concurrently.
+        let ranges = self.device_ranges(bases.len())?;
+        let mut plan = Vec::with_capacity(self.kernels.len());
+        let mut requested = 0;
+        for (range, kern) in ranges.into_iter().zip(self.kernels.iter()) {
+            let mut device_chunks = Vec::new();
+            let mut offset = range.start;
+            while offset < range.end {
+                let len = kern.chunk_len(range.end - offset)?;
+                device_chunks.push(offset..offset + len);
+                offset += len;
+            }
+            requested += range.len() * std::mem::size_of::<G>()
+                + device_chunks
+                    .iter()
+                    .map(|chunk| kern.working_memory(chunk.len()))
+                    .max()
+                    .unwrap_or(0);
+            plan.push(device_chunks);
+        }
+        if let Some(kern) = self.kernels.first() {
+            kern.check_budget(requested)?;
+        }
+
+        let mut chunks = Vec::with_capacity(self.kernels.len());
+        for (device_chunks, kern) in
+            plan.into_iter().zip(self.kernels.iter_mut())
+        {
+            let device_chunks = device_chunks
+                .into_iter()
+                .map(|chunk| {
+                    let buffer = kern.upload_bases(&bases[chunk.clone()])?;
+                    Ok((chunk, buffer))
+                })
+                .collect::<EcResult<Vec<_>>>()?;
+            chunks.push(device_chunks);
+        }
+
+        Ok(BaseHandle {
+            len: bases.len(),
+            chunks,
+            identities,
+        })
+    }
+
+    /// Like [`MultiexpKernel::multiexp`], but with bases that are already in
+    /// GPU memory, see [`MultiexpKernel::upload_bases`].
+    ///
+    /// Only the exponents are uploaded. The handle must have been created by
+    /// this kernel, there must be at least `skip + exps.len()` bases. As the
+    /// bases were split among the devices at the time of the upload, the
+    /// terms before `skip` and after the last exponent are computed with
+    /// zero exponents.
+    pub fn multiexp_with_handle(
+        &mut self, pool: &Worker, handle: &BaseHandle<G>,
+        exps: Arc<Vec<<G::Scalar as PrimeField>::Repr>>, skip: usize,
+    ) -> EcResult<G::Curve> {
+        self.multiexp_resident(pool, handle, &exps, skip, None)
+    }
+
+    /// Like [`MultiexpKernel::multiexp_masked`], but with bases that are
+    /// already in GPU memory, see [`MultiexpKernel::upload_bases`].
+    ///
+    /// Only the exponents and the mask are uploaded, see
+    /// [`SingleMultiexpKernel::multiexp_masked_resident`]. Like for
+    /// [`MultiexpKernel::multiexp_with_handle`], the handle must have been
+    /// created by this kernel and there must be at least `skip + exps.len()`
+    /// bases, there must be a mask entry for each exponent.
+    pub fn multiexp_masked_with_handle(
+        &mut self, pool: &Worker, handle: &BaseHandle<G>,
+        exps: Arc<Vec<<G::Scalar as PrimeField>::Repr>>, skip: usize,
+        mask: &[bool],
+    ) -> EcResult<G::Curve> {
+        check_len(exps.len(), mask.len())?;
+        self.multiexp_resident(pool, handle, &exps, skip, Some(mask))
+    }
+
+    /// Calculates the multiexp of [`MultiexpKernel::multiexp_with_handle`],
+    /// if a `mask` is given, only the selected terms are added up.
+    fn multiexp_resident(
+        &mut self, pool: &Worker, handle: &BaseHandle<G>,
+        exps: &[<G::Scalar as PrimeField>::Repr], skip: usize,
+        mask: Option<&[bool]>,
+    ) -> EcResult<G::Curve> {
+        if skip + exps.len() > handle.len() {
+            return Err(EcError::Simple(
+                "There are fewer bases than exponents",
+            ));
+        }
+        if handle.chunks.len() != self.kernels.len() {
+            return Err(EcError::Simple(
+                "The base handle was created by another kernel",
+            ));
+        }
+        for (device_chunks, kern) in handle.chunks.iter().zip(&self.kernels) {
+            for (_, buffer) in device_chunks {
+                check_owner(buffer, BufferOwner::new(kern.id, &kern.program))?;
+            }
+        }
+
+        let zero = G::Scalar::zero().to_repr();
+        let terms = skip..skip + exps.len();
+        let exponent = |i: usize| {
+            let is_identity = handle
+                .identities
+                .as_ref()
+                .map_or(false, |identities| identities[i]);
+            if terms.contains(&i) && !is_identity {
+                exps[i - skip]
+            } else {
+                zero
+            }
+        };
+
+        for kern in self.kernels.iter_mut() {
+            kern.reset_op_count();
+        }
+        let chunks = handle
+            .chunks
+            .iter()
+            .map(|device_chunks| device_chunks.iter().collect::<Vec<_>>())
+            .collect();
+        let terms = &terms;
+        let exponent = &exponent;
+        let results = self.run_chunks(
+            pool,
+            chunks,
+            G::Curve::zero(),
+            move |kern, (range, buffer), acc| {
+                // Chunks without any of the terms contribute nothing.
+                if range.end <= terms.start || range.start >= terms.end {
+                    return Ok(());
+                }
+                let chunk_exps =
+                    range.clone().map(exponent).collect::<Vec<_>>();
+                let partial = match mask {
+                    Some(mask) => {
+                        // Terms outside of the exponents are zero anyway,
+                        // they don't need to be selected.
+                        let chunk_mask = range
+                            .clone()
+                            .map(|i| terms.contains(&i) && mask[i - skip])
+                            .collect::<Vec<_>>();
+                        kern.multiexp_masked_resident(
+                            buffer,
+                            &chunk_exps,
+                            &chunk_mask,
+                        )?
+                    }
+                    None => kern.multiexp_resident(buffer, &chunk_exps)?,
+                };
+                acc.add_assign(&partial);
+                Ok(())
+            },
+        )?;
+
+        if self.op_count.is_some() {
+            let mut op_count = OpCount::default();
+            for kern in self.kernels.iter() {
+                op_count += kern.op_count();
+            }
+            self.op_count = Some(op_count);
+        }
+
+        Ok(results
+            .into_iter()
+            .fold(G::Curve::zero(), |mut acc, result| {
+                acc.add_assign(&result);
+                acc
+            }))
+    }
+
+    /// Calculates a multiexp of bases and exponents that are given as bytes.
+    ///
+    /// `base_field` and `scalar_field` must describe the fields of the curve
+    /// of this kernel, see [`FieldSpec`] for the encoding. Every base is
+    /// encoded as its affine coordinates `x` and `y`, `(0, 0)` is the point at
+    /// infinity, the bases are not checked to be on the curve. Every exponent
+    /// is a single encoding. The result is returned encoded like a base.
+    pub fn multiexp_bytes(
+        &mut self, pool: &Worker, base_field: &FieldSpec,
+        scalar_field: &FieldSpec, bases: &[u8], exponents: &[u8],
+    ) -> EcResult<Vec<u8>>
+    where
+        G::Base: CanonicalBytes,
+        G::Scalar: CanonicalBytes,
+    {
+        base_field.check::<G::Base>()?;
+        scalar_field.check::<G::Scalar>()?;
+        let coordinates = base_field.decode::<G::Base>(bases)?;
+        if coordinates.len() % 2 != 0 {
+            return Err(EcError::Simple(
+                "Every base must consist of two coordinates",
+            ));
+        }
+        let bases = coordinates
+            .chunks(2)
+            .map(|xy| G::from_xy_unchecked(xy[0], xy[1]))
+            .collect::<Vec<_>>();
+        let exps = scalar_field
+            .decode::<G::Scalar>(exponents)?
+            .iter()
+            .map(PrimeField::to_repr)
+            .collect::<Vec<_>>();
+        if bases.len() != exps.len() {
+            return Err(EcError::Simple(
+                "There must be as many bases as exponents",
+            ));
+        }
+
+        let result = self
+            .multiexp(pool, Arc::new(bases), Arc::new(exps), 0)?
+            .into_affine();
+        let (x, y) = result.to_xy();
+        let mut output = Vec::with_capacity(2 * base_field.bytes_per_element());
+        base_field.encode(&[x, y], &mut output);
+        Ok(output)
+    }
+
+    /// Calculates the multiexp of `bases` with sparse exponents.
+    ///
+    /// Only the `nonzero` exponents are given, each together with the index
+    /// of its base. The indices must be strictly increasing and in range.
+    /// Just the bases of these terms are gathered and uploaded, hence for
+    /// highly sparse exponents this is much faster than
+    /// [`MultiexpKernel::multiexp`] with all the zeros.
+    pub fn multiexp_sparse(
+        &mut self, pool: &Worker, bases: Arc<Vec<G>>,
+        nonzero: &[(usize, <G::Scalar as PrimeField>::Repr)],
+    ) -> EcResult<G::Curve> {
+        if nonzero.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err(EcError::Simple(
+                "The indices of the exponents are not strictly increasing",
+            ));
+        }
+        if let Some((index, _)) = nonzero.last() {
+            if *index >= bases.len() {
+                return Err(EcError::Simple(
+                    "The index of an exponent is out of range",
+                ));
+            }
+        }
+        let (sparse_bases, exps): (Vec<_>, Vec<_>) = nonzero
+            .iter()
+            .map(|(index, exp)| (bases[*index], *exp))
+            .unzip();
+        self.multiexp(pool, Arc::new(sparse_bases), Arc::new(exps), 0)
+    }
+
+    /// Calculates several independent multiexps, one for each of the `jobs`.
+    ///
+    /// A job consists of bases and exponents, like the arguments of
+    /// [`MultiexpKernel::multiexp`], the bases may be longer than the
+    /// exponents. Instead of running the multiexps one after another, the
+    /// host side work is overlapped with the GPU: while a device computes a
+    /// chunk, the bases of the next chunk are converted and the results of
+    /// the previous one are accumulated. With additional streams, see
+    /// [`MultiexpKernel::set_pipeline_streams`], a device also uploads and
+    /// computes several chunks at the same time. The jobs are split among
+    /// the devices, each device is driven from threads that are bound to its
+    /// NUMA node if NUMA awareness is enabled. Bases at infinity are handled
+    /// according to the [`IdentityHandling`]. The results are in the order
+    /// of the `jobs`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "multiexp_pipeline",
+            skip_all,
+            fields(num_jobs = jobs.len(), num_devices = self.kernels.len())
+        )
+    )]
+    pub fn multiexp_pipeline(
+        &mut self, pool: &Worker, jobs: &[MultiexpJob<G>],
+    ) -> EcResult<Vec<G::Curve>>
+    where <G as GpuRepr>::Repr: Send + Sync {
+        // Only the jobs with bases at infinity are copied, without them.
+        let mut finite_jobs = Vec::with_capacity(jobs.len());
+        for (bases, exps) in jobs {
+            if bases.len() < exps.len() {
+                return Err(EcError::InvalidLength(format!(
+                    "a job has {} bases, but {} exponents",
+                    bases.len(),
+                    exps.len()
+                )));
+            }
+            let finite = self
+                .identity_handling
+                .finite_terms(&bases[..exps.len()], &exps[..])?;
+            finite_jobs.push(match finite {
+                (Cow::Owned(bases), Cow::Owned(exps)) => {
+                    (Arc::new(bases), Arc::new(exps))
+                }
+                _ => (bases.clone(), exps.clone()),
+            });
+        }
+        let jobs = &finite_jobs[..];
+        let mut results = vec![G::Curve::zero(); jobs.len()];
+        if jobs.is_empty() {
+            return Ok(results);
+        }
+        let error = Arc::new(RwLock::new(Ok(())));
+
+        // Every device gets a consecutive range of the jobs.
+        let jobs_per_device = div_ceil(jobs.len(), self.kernels.len());
+        pool.scoped(|s| {
+            for ((jobs, results), kern) in jobs
+                .chunks(jobs_per_device)
+                .zip(results.chunks_mut(jobs_per_device))
+                .zip(self.kernels.iter_mut())
+            {
+                let error = error.clone();
+                s.execute(move || {
+                    if let Err(e) =
+                        kern.multiexp_pipeline(jobs, results, &error)
+                    {
+                        *error.write().unwrap() = Err(e);
+                    }
+                });
+            }
+        });
+
+        Arc::try_unwrap(error)
+            .expect("only one ref left")
+            .into_inner()
+            .unwrap()?;
+        Ok(results)
+    }
+
+    /// Calculates a multiexp of every set of bases with the same
+    /// `exponents`, e.g. the same linear combination over different
+    /// commitment keys.
+    ///
+    /// The terms are split among the devices like for
+    /// [`MultiexpKernel::multiexp`]. Every device uploads each chunk of its
+    /// share of the exponents once and reuses it for all sets, see
+    /// [`SingleMultiexpKernel::multiexp_shared_scalars`]. The base sets may
+    /// be longer than the exponents, the remaining bases are ignored. A set
+    /// that contains the point at infinity can't share the exponents with the
+    /// others, it's calculated with [`MultiexpKernel::multiexp`] instead,
+    /// which also applies the [`IdentityHandling`]. The results are in the
+    /// order of the `base_sets`.
+    pub fn multiexp_shared_scalars(
+        &mut self, pool: &Worker, base_sets: &[Arc<Vec<G>>],
+        exponents: Arc<Vec<<G::Scalar as PrimeField>::Repr>>,
+    ) -> EcResult<Vec<G::Curve>> {
+        let num_terms = exponents.len();
+        for bases in base_sets {
+            if bases.len() < num_terms {
+                return Err(EcError::InvalidLength(format!(
+                    "a set has {} bases, but there are {} exponents",
+                    bases.len(),
+                    num_terms
+                )));
+            }
+        }
+
+        let mut results = vec![G::Curve::zero(); base_sets.len()];
+        // The indices of the sets that share the exponents on the GPU.
+        let mut shared = Vec::with_capacity(base_sets.len());
+        for (i, bases) in base_sets.iter().enumerate() {
+            if bases[..num_terms].iter().any(GpuCurveAffine::is_identity) {
+                results[i] =
+                    self.multiexp(pool, bases.clone(), exponents.clone(), 0)?;
+            } else {
+                shared.push(i);
+            }
+        }
+        if shared.is_empty() || num_terms == 0 {
+            return Ok(results);
+        }
+
+        let chunks = self.device_chunks(num_terms, |kern, num_terms| {
+            kern.chunk_len(num_terms)
+        })?;
+        let shared = &shared;
+        let exponents = &exponents[..];
+        let partials = self.run_chunks(
+            pool,
+            chunks,
+            vec![G::Curve::zero(); shared.len()],
+            move |kern, chunk, partial| {
+                let sets = shared
+                    .iter()
+                    .map(|&i| &base_sets[i][chunk.clone()])
+                    .collect::<Vec<_>>();
+                let chunk_results =
+                    kern.multiexp_shared_scalars(&sets, &exponents[chunk])?;
+                for (acc, result) in partial.iter_mut().zip(chunk_results) {
+                    acc.add_assign(&result);
+                }
+                Ok(())
+            },
+        )?;
+        for partial in partials {
+            for (&i, result) in shared.iter().zip(partial) {
+                results[i].add_assign(&result);
+            }
+        }
+        Ok(results)
+    }
+
+    /// Calculates the multiexp of the terms whose `mask` entry is `true`.
+    ///
+    /// The result is the same as the one of [`MultiexpKernel::multiexp`] of
+    /// the filtered terms, but the same `bases` and `exponents` can be used
+    /// with different masks, without copying the selected terms. The terms
+    /// are split among the devices like for [`MultiexpKernel::multiexp`], see
+    /// [`SingleMultiexpKernel::multiexp_masked`]. The bases may be longer
+    /// than the exponents, the remaining ones are ignored. Bases at infinity
+    /// are handled according to the [`IdentityHandling`]. In order to keep
+    /// the bases in GPU memory between masks, see
+    /// [`MultiexpKernel::multiexp_masked_with_handle`].
+    pub fn multiexp_masked(
+        &mut self, pool: &Worker, bases: Arc<Vec<G>>,
+        exponents: Arc<Vec<<G::Scalar as PrimeField>::Repr>>, mask: &[bool],
+    ) -> EcResult<G::Curve> {
+        let num_terms = exponents.len();
+        if bases.len() < num_terms {
+            return Err(EcError::InvalidLength(format!(
+                "there are {} bases, but {} exponents",
+                bases.len(),
+                num_terms
+            )));
+        }
+        check_len(num_terms, mask.len())?;
+        let bases = &bases[..num_terms];
+
+        // Skipping a base at infinity is the same as masking it out.
+        let finite_mask: Vec<bool>;
+        let mask = if self.identity_handling.has_identity(bases)? {
+            finite_mask = bases
+                .iter()
+                .zip(mask)
+                .map(|(base, &selected)| selected && !base.is_identity())
+                .collect();
+            &finite_mask[..]
+        } else {
+            mask
+        };
+
+        let chunks = self.device_chunks(num_terms, |kern, num_terms| {
+            kern.chunk_len(num_terms)
+        })?;
+        let exponents = &exponents[..];
+        let results = self.run_chunks(
+            pool,
+            chunks,
+            G::Curve::zero(),
+            move |kern, chunk, acc| {
+                let partial = kern.multiexp_masked(
+                    &bases[chunk.clone()],
+                    &exponents[chunk.clone()],
+                    &mask[chunk],
+                )?;
+                acc.add_assign(&partial);
+                Ok(())
+            },
+        )?;
+        let mut acc = G::Curve::zero();
+        for result in results {
+            acc.add_assign(&result);
+        }
+        Ok(acc)
+    }
+
+    /// Calculates a multiexp, but returns the sums of its windows instead of
+    /// the result, see [`MultiexpPartial`].
+    ///
+    /// This is meant for aggregating a multiexp over many nodes: every node
+    /// computes the partial of its share of the terms and passes it on, the
+    /// partials are merged and only the root combines the windows with
+    /// [`MultiexpKernel::combine_partials`]. The terms are split among the
+    /// devices like for [`MultiexpKernel::multiexp`], whose arguments it
+    /// takes. Bases at infinity are handled according to the
+    /// [`IdentityHandling`].
+    pub fn multiexp_partial(
+        &mut self, pool: &Worker, bases: Arc<Vec<G>>,
+        exps: Arc<Vec<<G::Scalar as PrimeField>::Repr>>, skip: usize,
+    ) -> EcResult<MultiexpPartial<G>> {
+        if skip + exps.len() > bases.len() {
+            return Err(EcError::InvalidLength(format!(
+                "there are {} bases, but {} are skipped and there are {} \
+                 exponents",
+                bases.len(),
+                skip,
+                exps.len()
+            )));
+        }
+        let bases = &bases[skip..(skip + exps.len())];
+        let exps = &exps[..];
+
+        let (bases, exps) = self.identity_handling.finite_terms(bases, exps)?;
+        let (bases, exps) = (&bases[..], &exps[..]);
+
+        let chunks = self.device_chunks(exps.len(), |kern, num_terms| {
+            kern.chunk_len(num_terms)
+        })?;
+        let partials = self.run_chunks(
+            pool,
+            chunks,
+            MultiexpPartial::default(),
+            move |kern, chunk, partial| {
+                kern.multiexp_partial(
+                    &bases[chunk.clone()],
+                    &exps[chunk],
+                    partial,
+                )
+            },
+        )?;
+        let mut acc = MultiexpPartial::default();
+        for partial in &partials {
+            acc.merge(partial);
+        }
+        Ok(acc)
+    }
+
+    /// Merges the `partials` of [`MultiexpKernel::multiexp_partial`] and
+    /// combines their windows into the result.
+    ///
+    /// It's the result of a multiexp of the terms of all partials. The
+    /// windows are combined once per window size, no matter how many
+    /// partials there are. It runs on the host only, hence it needs no GPU.
+    pub fn combine_partials<I>(partials: I) -> G::Curve
+    where I: IntoIterator<Item = MultiexpPartial<G>> {
+        let mut acc = MultiexpPartial::default();
+        for partial in partials {
+            acc.merge(&partial);
+        }
+        acc.reduce()
+    }
+
+    /// Calculates a multiexp with projective `bases`, e.g. ones that are the
+    /// output of a previous computation.
+    ///
+    /// The bases are converted into affine form on the GPU instead of on the
+    /// host, see [`SingleMultiexpKernel::multiexp_projective`]. The result is
+    /// the same as the one of [`MultiexpKernel::multiexp`] with the
+    /// normalized bases. The terms are split among the devices like for
+    /// [`MultiexpKernel::multiexp`], in smaller chunks as projective bases
+    /// take more memory. The bases may be longer than the exponents, the
+    /// remaining ones are ignored. Bases at infinity are handled according to
+    /// the [`IdentityHandling`].
+    pub fn multiexp_projective(
+        &mut self, pool: &Worker, bases: Arc<Vec<G::Curve>>,
+        exponents: Arc<Vec<<G::Scalar as PrimeField>::Repr>>,
+    ) -> EcResult<G::Curve> {
+        let num_terms = exponents.len();
+        if bases.len() < num_terms {
+            return Err(EcError::InvalidLength(format!(
+                "there are {} bases, but {} exponents",
+                bases.len(),
+                num_terms
+            )));
+        }
+        let bases = &bases[..num_terms];
+        if self.identity_handling == IdentityHandling::Reject
+            && bases.iter().any(Zero::is_zero)
+        {
+            return Err(EcError::Simple(
+                "A base of the multiexp is the point at infinity",
+            ));
+        }
+
+        let chunks = self.device_chunks(num_terms, |kern, num_terms| {
+            kern.projective_chunk_len(num_terms)
+        })?;
+        let exponents = &exponents[..];
+        let results = self.run_chunks(
+            pool,
+            chunks,
+            G::Curve::zero(),
+            move |kern, chunk, acc| {
+                let partial = kern.multiexp_projective(
+                    &bases[chunk.clone()],
+                    &exponents[chunk],
+                )?;
+                acc.add_assign(&partial);
+                Ok(())
+            },
+        )?;
+        let mut acc = G::Curve::zero();
+        for result in results {
+            acc.add_assign(&result);
+        }
+        Ok(acc)
+    }
+
+    /// Calculate multiexp with exponents that are wider than the scalar field.
+    ///
+    /// The `wide_exponents` consist of `K` little-endian 64-bit limbs. They
+    /// are reduced modulo the field order on the first available GPU, before
+    /// the multiexp is calculated as with [`MultiexpKernel::multiexp`].
+    pub fn multiexp_reduce<const K: usize>(
+        &mut self, pool: &Worker, bases_arc: Arc<Vec<G>>,
+        wide_exponents: &[[u64; K]], skip: usize,
+    ) -> EcResult<G::Curve> {
+        let kern = &mut self.kernels[0];
+        let mut exps = Vec::with_capacity(wide_exponents.len());
+        for chunk in wide_exponents.chunks(kern.n) {
+            exps.extend(kern.reduce_wide(chunk)?);
+        }
+        self.multiexp(pool, bases_arc, Arc::new(exps), skip)
+    }
+
+    /// Commits to a polynomial, given by its `evaluations` over the subgroup
+    /// of the same size, with the first bases of `srs`.
+    ///
+    /// This fuses an inverse FFT with the multiexp of the resulting
+    /// coefficients, without a round trip to the host in between. See
+    /// [`SingleMultiexpKernel::commit_polynomial`] for the requirements on
+    /// the programs.
+    ///
+    /// Uses the first available GPU.
+    pub fn commit_polynomial(
+        &mut self, evaluations: &[G::Scalar], srs: &[G],
+    ) -> EcResult<G::Curve>
+    where G::Scalar: GpuName {
+        if srs.len() < evaluations.len() {
+            return Err(EcError::InvalidLength(format!(
+                "the SRS has {} bases, but the polynomial has {} evaluations",
+                srs.len(),
+                evaluations.len()
+            )));
+        }
+        let kern = &mut self.kernels[0];
+        if evaluations.len() > kern.n {
+            return Err(EcError::Simple(
+                "The polynomial is too large to be committed on a single GPU",
+            ));
+        }
+        kern.commit_polynomial(&srs[..evaluations.len()], evaluations)
     }
-}
 
-/// A struct that containts several multiexp kernels for different devices.
-pub struct MultiexpKernel<'a, G>
-where G: GpuCurveAffine
-{
-    kernels: Vec<SingleMultiexpKernel<'a, G>>,
-}
+    /// Commits to the evaluations of a polynomial, given by its
+    /// `coefficients`, in bit-reversed order with the first bases of `srs`.
+    ///
+    /// See [`SingleMultiexpKernel::commit_evaluations_bit_reversed`] for the
+    /// details.
+    ///
+    /// Uses the first available GPU.
+    pub fn commit_evaluations_bit_reversed(
+        &mut self, coefficients: &[G::Scalar], srs: &[G],
+    ) -> EcResult<G::Curve>
+    where G::Scalar: GpuName {
+        if srs.len() < coefficients.len() {
+            return Err(EcError::InvalidLength(format!(
+                "the SRS has {} bases, but the polynomial has {} coefficients",
+                srs.len(),
+                coefficients.len()
+            )));
+        }
+        let kern = &mut self.kernels[0];
+        if coefficients.len() > kern.n {
+            return Err(EcError::Simple(
+                "The polynomial is too large to be committed on a single GPU",
+            ));
+        }
+        kern.commit_evaluations_bit_reversed(
+            &srs[..coefficients.len()],
+            coefficients,
+        )
+    }
 
-impl<'a, G> MultiexpKernel<'a, G>
-where G: GpuCurveAffine + GpuName
-{
-    /// Create new kernels, one for each given device.
-    pub fn create(
-        programs: Vec<Program>, devices: &[&Device],
-    ) -> EcResult<Self> {
-        Self::create_optional_abort(programs, devices, None)
+    /// Commits to every polynomial, given by its coefficients, with the
+    /// first bases of `srs`.
+    ///
+    /// The SRS is uploaded once per chunk and shared by all polynomials, see
+    /// [`SingleMultiexpKernel::multiexp_shared_bases`], instead of being
+    /// uploaded for every commitment. The terms are split among the devices
+    /// like for [`MultiexpKernel::multiexp`]. The polynomials may have
+    /// different lengths, but none may be longer than the `srs`. If the used
+    /// part of the `srs` contains the point at infinity, the polynomials are
+    /// committed one by one with [`MultiexpKernel::multiexp`] instead, which
+    /// applies the [`IdentityHandling`]. The results are in the order of the
+    /// `poly_coeffs`.
+    pub fn commit_many(
+        &mut self, pool: &Worker, srs: &[G], poly_coeffs: &[&[G::Scalar]],
+    ) -> EcResult<Vec<G::Curve>> {
+        for coeffs in poly_coeffs {
+            if srs.len() < coeffs.len() {
+                return Err(EcError::InvalidLength(format!(
+                    "the SRS has {} bases, but a polynomial has {} \
+                     coefficients",
+                    srs.len(),
+                    coeffs.len()
+                )));
+            }
+        }
+        let num_terms = poly_coeffs
+            .iter()
+            .map(|coeffs| coeffs.len())
+            .max()
+            .unwrap_or(0);
+        if num_terms == 0 {
+            return Ok(vec![G::Curve::zero(); poly_coeffs.len()]);
+        }
+
+        let srs = &srs[..num_terms];
+        if srs.iter().any(GpuCurveAffine::is_identity) {
+            let bases = Arc::new(srs.to_vec());
+            return poly_coeffs
+                .iter()
+                .map(|coeffs| {
+                    let exps = coeffs.iter().map(PrimeField::to_repr).collect();
+                    self.multiexp(pool, bases.clone(), Arc::new(exps), 0)
+                })
+                .collect();
+        }
+
+        let chunks = self.device_chunks(num_terms, |kern, num_terms| {
+            kern.chunk_len(num_terms)
+        })?;
+        let partials = self.run_chunks(
+            pool,
+            chunks,
+            vec![G::Curve::zero(); poly_coeffs.len()],
+            move |kern, chunk, partial| {
+                // Shorter polynomials only cover the start of the chunk, if
+                // at all.
+                let exps = poly_coeffs
+                    .iter()
+                    .map(|coeffs| {
+                        let start = cmp::min(coeffs.len(), chunk.start);
+                        let end = cmp::min(coeffs.len(), chunk.end);
+                        coeffs[start..end]
+                            .iter()
+                            .map(PrimeField::to_repr)
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>();
+                let exps =
+                    exps.iter().map(|exps| &exps[..]).collect::<Vec<_>>();
+                let chunk_results =
+                    kern.multiexp_shared_bases(&srs[chunk], &exps)?;
+                for (acc, result) in partial.iter_mut().zip(chunk_results) {
+                    acc.add_assign(&result);
+                }
+                Ok(())
+            },
+        )?;
+        let mut results = vec![G::Curve::zero(); poly_coeffs.len()];
+        for partial in partials {
+            for (acc, result) in results.iter_mut().zip(partial) {
+                acc.add_assign(&result);
+            }
+        }
+        Ok(results)
     }
 
-    /// Create new kernels, one for each given device, with early abort hook.
+    /// Calculates a multiexp with the precomputed multiples of the bases from
+    /// `table`.
     ///
-    /// The `maybe_abort` function is called when it is possible to abort the
-    /// computation, without leaving the GPU in a weird state. If that
-    /// function returns `true`, execution is aborted.
-    pub fn create_with_abort(
-        programs: Vec<Program>, devices: &[&Device],
-        maybe_abort: &'a (dyn Fn() -> bool + Send + Sync),
-    ) -> EcResult<Self> {
-        Self::create_optional_abort(programs, devices, Some(maybe_abort))
+    /// Bases at infinity are handled according to the [`IdentityHandling`].
+    ///
+    /// Uses the first available GPU. See
+    /// [`SingleMultiexpKernel::multiexp_with_table`].
+    pub fn multiexp_with_table(
+        &mut self, table: &BaseTable<G>,
+        exponents: &[<G::Scalar as PrimeField>::Repr],
+    ) -> EcResult<G::Curve> {
+        if self.identity_handling == IdentityHandling::Reject
+            && table.identities.is_some()
+        {
+            return Err(EcError::Simple(
+                "A base of the multiexp is the point at infinity",
+            ));
+        }
+        self.kernels[0].multiexp_with_table(table, exponents)
     }
 
-    fn create_optional_abort(
-        programs: Vec<Program>, devices: &[&Device],
-        maybe_abort: Option<&'a (dyn Fn() -> bool + Send + Sync)>,
-    ) -> EcResult<Self> {
-        let kernels: Vec<_> = programs
-            .into_iter()
-            .zip(devices.iter())
-            .filter_map(|(program, device)| {
-                let device_name = program.device_name().to_string();
-                let kernel =
-                    SingleMultiexpKernel::create(program, device, maybe_abort);
-                if let Err(ref e) = kernel {
-                    error!(
-                        "Cannot initialize kernel for device '{}'! Error: {}",
-                        device_name, e
+    /// Runs an inverse FFT of the `evaluations` and keeps the resulting
+    /// coefficients on the GPU.
+    ///
+    /// The returned buffer can be passed on to
+    /// [`MultiexpKernel::multiexp_from_device`]. See
+    /// [`SingleMultiexpKernel::ifft_to_device`] for the details.
+    ///
+    /// Uses the first available GPU.
+    pub fn ifft_to_device(
+        &mut self, evaluations: &[G::Scalar],
+    ) -> EcResult<DeviceBuffer<G::Scalar>>
+    where G::Scalar: GpuName {
+        self.kernels[0].ifft_to_device(evaluations)
+    }
+
+    /// Calculates `sum_i x^i * bases[i]`.
+    ///
+    /// The bases are split among the devices, each generates the powers of
+    /// `x` for its share on the GPU, they are never materialized on the host.
+    /// The programs must contain the FFT kernels of the scalar field, see
+    /// [`SingleMultiexpKernel::commit_polynomial`]. Bases at infinity are
+    /// handled according to the [`IdentityHandling`].
+    pub fn multiexp_powers(
+        &mut self, pool: &Worker, bases: Arc<Vec<G>>, x: G::Scalar,
+    ) -> EcResult<G::Curve>
+    where G::Scalar: GpuName {
+        // The powers are generated for the position of every base, hence
+        // skipped bases are masked out on the GPU instead of being dropped.
+        self.identity_handling.has_identity(&bases)?;
+        let ranges = self.device_ranges(bases.len())?;
+        let bases = &bases[..];
+        let mut results: Vec<EcResult<G::Curve>> = Vec::new();
+        pool.scoped(|s| {
+            results =
+                self.kernels.iter().map(|_| Ok(G::Curve::zero())).collect();
+            for ((range, kern), result) in ranges
+                .into_iter()
+                .zip(self.kernels.iter_mut())
+                .zip(results.iter_mut())
+            {
+                s.execute(move || {
+                    *result = kern.multiexp_powers_from(
+                        &bases[range.clone()],
+                        x,
+                        range.start,
                     );
-                }
-                kernel.ok()
-            })
-            .collect();
+                });
+            }
+        });
 
-        if kernels.is_empty() {
-            return Err(EcError::Simple("No working GPUs found!"));
+        let mut acc = G::Curve::zero();
+        for result in results {
+            acc.add_assign(&result?);
         }
-        info!("Multiexp: {} working device(s) selected.", kernels.len());
-        for (i, k) in kernels.iter().enumerate() {
-            info!(
-                "Multiexp: Device {}: {} (Chunk-size: {})",
-                i,
-                k.program.device_name(),
-                k.n
-            );
+        Ok(acc)
+    }
+
+    /// Calculates a multiexp and returns how full the buckets got, see
+    /// [`SingleMultiexpKernel::multiexp_with_occupancy`].
+    ///
+    /// It is done in a single run of the kernel, hence the number of terms
+    /// must not exceed [`SingleMultiexpKernel::chunk_size`], otherwise an
+    /// error is returned. Bases at infinity are handled according to the
+    /// [`IdentityHandling`], skipped ones aren't counted.
+    ///
+    /// Uses the first available GPU.
+    pub fn multiexp_with_occupancy(
+        &mut self, bases: &[G], exponents: &[<G::Scalar as PrimeField>::Repr],
+        neg_is_cheap: bool,
+    ) -> EcResult<(G::Curve, BucketOccupancy)> {
+        check_len(bases.len(), exponents.len())?;
+        let (bases, exponents) =
+            self.identity_handling.finite_terms(bases, exponents)?;
+        let kern = &mut self.kernels[0];
+        if exponents.len() > kern.n {
+            return Err(EcError::Simple("Too many terms for a single run"));
         }
-        Ok(MultiexpKernel { kernels })
+        kern.multiexp_with_occupancy(&bases, &exponents, neg_is_cheap)
     }
 
-    /// Calculate multiexp on all available GPUs.
+    /// Calculates the multiexp of the first bases of `srs` with the
+    /// `coefficients` that are already on the GPU.
     ///
-    /// It needs to run within a [`yastl::Scope`]. This method usually isn't
-    /// called directly, use [`MultiexpKernel::multiexp`] instead.
-    pub fn parallel_multiexp<'s>(
-        &'s mut self, scope: &Scope<'s>, bases: &'s [G],
-        exps: &'s [<G::Scalar as PrimeField>::Repr],
-        results: &'s mut [G::Curve], error: Arc<RwLock<EcResult<()>>>,
-    ) {
-        let num_devices = self.kernels.len();
-        let num_exps = exps.len();
-        // The maximum number of exponentiations per device.
-        let chunk_size =
-            ((num_exps as f64) / (num_devices as f64)).ceil() as usize;
-
-        for (((bases, exps), kern), result) in bases
-            .chunks(chunk_size)
-            .zip(exps.chunks(chunk_size))
+    /// The buffer must have been created by this kernel, e.g. with
+    /// [`MultiexpKernel::ifft_to_device`]. See
+    /// [`SingleMultiexpKernel::multiexp_from_device`] for the details. Bases
+    /// at infinity are handled according to the [`IdentityHandling`].
+    ///
+    /// Uses the first available GPU.
+    pub fn multiexp_from_device(
+        &mut self, srs: &[G], coefficients: DeviceBuffer<G::Scalar>,
+    ) -> EcResult<G::Curve>
+    where G::Scalar: GpuName {
+        if srs.len() < coefficients.len() {
+            return Err(EcError::InvalidLength(format!(
+                "the SRS has {} bases, but there are {} coefficients",
+                srs.len(),
+                coefficients.len()
+            )));
+        }
+        let kern = &mut self.kernels[0];
+        if coefficients.len() > kern.n {
+            return Err(EcError::InvalidLength(format!(
+                "there are {} coefficients, but at most {} fit on a single GPU",
+                coefficients.len(),
+                kern.n
+            )));
+        }
+        let srs = &srs[..coefficients.len()];
+        // The coefficients are already on the GPU, hence skipped bases are
+        // masked out there instead of being dropped.
+        self.identity_handling.has_identity(srs)?;
+        kern.multiexp_from_device(srs, coefficients)
+    }
+
+    /// Commits to a polynomial, given by its `evals` over the subgroup of
+    /// order `domain_size`, with an SRS in Lagrange form.
+    ///
+    /// `lagrange_bases[i]` is the commitment to the `i`-th Lagrange
+    /// polynomial of the domain. Hence the evaluations are the exponents and
+    /// no inverse FFT is needed, the result is the same as the one of
+    /// [`MultiexpKernel::commit_polynomial`] with the corresponding monomial
+    /// SRS. Missing evaluations at the end are treated as zero.
+    pub fn multiexp_lagrange(
+        &mut self, pool: &Worker, lagrange_bases: Arc<Vec<G>>,
+        evals: &[G::Scalar], domain_size: usize,
+    ) -> EcResult<G::Curve> {
+        if !domain_size.is_power_of_two() {
+            return Err(EcError::InvalidLength(format!(
+                "the domain size {} is not a power of two",
+                domain_size
+            )));
+        }
+        if lagrange_bases.len() != domain_size {
+            return Err(EcError::InvalidLength(format!(
+                "there are {} Lagrange bases, but the domain has {} elements",
+                lagrange_bases.len(),
+                domain_size
+            )));
+        }
+        if evals.len() > domain_size {
+            return Err(EcError::InvalidLength(format!(
+                "there are {} evaluations, but the domain has {} elements",
+                evals.len(),
+                domain_size
+            )));
+        }
+        let exps = Arc::new(evals.iter().map(PrimeField::to_repr).collect());
+        self.multiexp(pool, lagrange_bases, exps, 0)
+    }
+
+    /// Converts the given projective `points` into affine form.
+    ///
+    /// The inversions are batched on the device, which is much faster than
+    /// converting the points one by one. The point at infinity is supported.
+    ///
+    /// Uses the first available GPU.
+    pub fn normalize_many(&mut self, points: &[G::Curve]) -> EcResult<Vec<G>> {
+        self.kernels[0].normalize_many(points)
+    }
+
+    /// Computes the multiexp like [`MultiexpKernel::multiexp`] and returns
+    /// the canonical compressed encoding of the result.
+    ///
+    /// The encoding is the one of arkworks' [`CanonicalSerialize`], e.g. the
+    /// zcash encoding for BLS12-381. The result is normalized and compressed
+    /// on the first available GPU, see
+    /// [`SingleMultiexpKernel::compress_many`].
+    pub fn multiexp_to_compressed(
+        &mut self, pool: &Worker, bases_arc: Arc<Vec<G>>,
+        exps: Arc<Vec<<G::Scalar as PrimeField>::Repr>>, skip: usize,
+    ) -> EcResult<Vec<u8>>
+    where
+        G::Base: CanonicalBytes,
+    {
+        let result = self.multiexp(pool, bases_arc, exps, skip)?;
+        self.kernels[0].compress_many(&[result])
+    }
+
+    /// Checks for each of the `points` whether it satisfies the curve
+    /// equation `y^2 = x^3 + b`.
+    ///
+    /// This is a cheap check for malformed input, e.g. of deserialized
+    /// points, before the more expensive subgroup check. The point at
+    /// infinity is on the curve.
+    ///
+    /// Uses the first available GPU.
+    pub fn check_on_curve(&mut self, points: &[G]) -> EcResult<Vec<bool>> {
+        self.kernels[0].check_on_curve(points)
+    }
+
+    /// Calculates `g * s^i` for all `i` in `0..n`.
+    ///
+    /// Uses the first available GPU. See
+    /// [`SingleMultiexpKernel::powers_of_tau`].
+    pub fn powers_of_tau(
+        &mut self, g: G, s: G::Scalar, n: usize,
+    ) -> EcResult<Vec<G::Curve>> {
+        self.kernels[0].powers_of_tau(g, s, n)
+    }
+
+    /// Shares the given GPU memory `budget` with the other kernels that use
+    /// it.
+    ///
+    /// Every allocation reserves its memory from the budget first. A
+    /// multiexp that doesn't fit is split into smaller chunks, other
+    /// operations fail with [`EcError::MemoryBudgetExceeded`] if they are
+    /// larger than the whole budget. Reservations wait while other kernels
+    /// hold too much of the budget.
+    pub fn with_budget(mut self, budget: Arc<MemoryBudget>) -> Self {
+        for kern in self.kernels.iter_mut() {
+            kern.set_budget(budget.clone());
+        }
+        self
+    }
+
+    /// Enables or disables NUMA aware allocation of host staging buffers.
+    ///
+    /// On multi-socket servers, transfers to a GPU are slower if the host
+    /// memory is on the node of another socket. When enabled, the staging
+    /// buffers for each device are allocated on the NUMA node the device is
+    /// attached to. It is off by default and has no effect on systems where
+    /// the node is unknown. Only Linux is supported.
+    pub fn set_numa_aware(&mut self, numa_aware: bool) {
+        for kern in self.kernels.iter_mut() {
+            kern.set_numa_aware(numa_aware);
+        }
+    }
+
+    /// Sets the allocator of the temporary host buffers of all devices.
+    ///
+    /// See [`SingleMultiexpKernel::set_host_allocator`].
+    pub fn set_host_allocator(&mut self, allocator: HostAllocator) {
+        for kern in self.kernels.iter_mut() {
+            kern.set_host_allocator(allocator.clone());
+        }
+    }
+
+    /// Overrides the window size of the multiexps on all devices, `None`
+    /// restores the heuristic based on the number of terms.
+    ///
+    /// An invalid window size results in an error and leaves the kernels
+    /// unchanged, see [`SingleMultiexpKernel::set_window_size`], which also
+    /// describes how the window size interacts with a memory budget.
+    pub fn set_window_size(
+        &mut self, window_size: Option<usize>,
+    ) -> EcResult<()> {
+        // All kernels are of the same curve, if one accepts the window size,
+        // all of them do.
+        for kern in self.kernels.iter_mut() {
+            kern.set_window_size(window_size)?;
+        }
+        Ok(())
+    }
+
+    /// Sets further programs for every device, which are used by
+    /// [`MultiexpKernel::multiexp_pipeline`], see
+    /// [`SingleMultiexpKernel::set_pipeline_streams`].
+    ///
+    /// There must be one vector of programs per device, in the order of the
+    /// devices of the kernel.
+    pub fn set_pipeline_streams(
+        &mut self, programs: Vec<Vec<Program>>,
+    ) -> EcResult<()> {
+        check_len(self.kernels.len(), programs.len())?;
+        for (kern, programs) in self.kernels.iter_mut().zip(programs) {
+            kern.set_pipeline_streams(programs)?;
+        }
+        Ok(())
+    }
+
+    /// Enables or disables counting of the elliptic curve operations.
+    ///
+    /// Counting is off by default, as the GPU needs atomic counters for it.
+    /// When enabled, [`MultiexpKernel::op_count`] returns the operations of
+    /// the last multiexp.
+    pub fn set_count_ops(&mut self, count_ops: bool) {
+        for kern in self.kernels.iter_mut() {
+            kern.set_count_ops(count_ops);
+        }
+        self.op_count = count_ops.then(OpCount::default);
+    }
+
+    /// Enables or disables the verification of uploads on all GPUs.
+    ///
+    /// This is meant for high-assurance deployments on hardware where
+    /// transfers may be corrupted, which would otherwise silently result in
+    /// wrong results. See [`SingleMultiexpKernel::set_verify_transfers`].
+    pub fn set_verify_transfers(&mut self, verify: bool) {
+        for kern in self.kernels.iter_mut() {
+            kern.set_verify_transfers(verify);
+        }
+    }
+
+    /// Simulates transfer errors on all GPUs, see
+    /// [`SingleMultiexpKernel::inject_upload_corruption`].
+    #[doc(hidden)]
+    pub fn inject_upload_corruption(&mut self, corrupt: bool) {
+        for kern in self.kernels.iter_mut() {
+            kern.inject_upload_corruption(corrupt);
+        }
+    }
+
+    /// Returns the operations of the last multiexp, or `None` if counting is
+    /// disabled.
+    pub fn op_count(&self) -> Option<OpCount> { self.op_count }
+
+    /// Returns the GPU memory (in bytes) a single multiexp chunk of
+    /// `num_terms` terms needs, on the device that needs the most.
+    ///
+    /// This helps to choose a [`MemoryBudget`].
+    pub fn required_memory(&self, num_terms: usize) -> usize {
+        self.kernels
+            .iter()
+            .map(|kern| kern.chunk_memory(num_terms))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns the number of kernels (one per device).
+    pub fn num_kernels(&self) -> usize { self.kernels.len() }
+
+    /// Sets how the terms of a multiexp are split among the devices. By
+    /// default they are split evenly.
+    pub fn set_work_splitter(&mut self, splitter: impl WorkSplitter + 'static) {
+        self.splitter = Arc::new(splitter);
+    }
+
+    /// Sets what [`MultiexpKernel::multiexp`],
+    /// [`MultiexpKernel::multiexp_progressive`] and
+    /// [`MultiexpKernel::multiexp_with_progress`] do with bases that are the
+    /// point at infinity, see [`IdentityHandling`]. By default their terms are
+    /// skipped.
+    pub fn set_identity_handling(&mut self, handling: IdentityHandling) {
+        self.identity_handling = handling;
+    }
+
+    /// Enables or disables the reproducible mode.
+    ///
+    /// By default the partial results of the devices are added up in the
+    /// order the devices finish, hence the projective coordinates of a result
+    /// may differ between runs, even though the point is the same. In the
+    /// reproducible mode they are added up in the order of the devices, the
+    /// terms are always split evenly, no matter which [`WorkSplitter`] is
+    /// set, and the time estimates aren't calibrated,
+    /// [`MultiexpKernel::calibrate`] does nothing. Given the same devices, the
+    /// results are then identical on every run and machine.
+    ///
+    /// The price is that faster devices don't get a larger share of the
+    /// terms, and that [`MultiexpKernel::multiexp_with_progress`] only calls
+    /// back once all devices are done, so that the host can't process a
+    /// partial result while other devices are still running.
+    pub fn set_reproducible(&mut self, reproducible: bool) {
+        self.reproducible = reproducible;
+        if reproducible {
+            for kern in self.kernels.iter_mut() {
+                kern.time_scale = 1.0;
+            }
+        }
+    }
+
+    /// Returns the information of the devices, in the order they get their
+    /// share of the terms.
+    pub fn device_info(&self) -> Vec<DeviceInfo> {
+        self.kernels
+            .iter()
+            .map(|kern| kern.device_info.clone())
+            .collect()
+    }
+
+    /// Returns the range of the `num_terms` terms each device computes.
+    fn device_ranges(&self, num_terms: usize) -> EcResult<Vec<Range<usize>>> {
+        // An even split only depends on the number of devices.
+        let splitter: &dyn WorkSplitter = if self.reproducible {
+            &EvenSplit
+        } else {
+            &*self.splitter
+        };
+        split_ranges(splitter, num_terms, &self.device_info())
+    }
+
+    /// Splits the share of the `num_terms` terms of every device, see
+    /// [`MultiexpKernel::device_ranges`], into consecutive chunks of at most
+    /// `chunk_len(kern, remaining)` terms.
+    fn device_chunks<L>(
+        &self, num_terms: usize, chunk_len: L,
+    ) -> EcResult<Vec<Vec<Range<usize>>>>
+    where L: Fn(&SingleMultiexpKernel<'a, G>, usize) -> EcResult<usize> {
+        self.device_ranges(num_terms)?
+            .into_iter()
+            .zip(&self.kernels)
+            .map(|(range, kern)| {
+                let mut chunks = Vec::new();
+                let mut offset = range.start;
+                while offset < range.end {
+                    let len = chunk_len(kern, range.end - offset)?;
+                    chunks.push(offset..offset + len);
+                    offset += len;
+                }
+                Ok(chunks)
+            })
+            .collect()
+    }
+
+    /// Runs `chunk` for every chunk of every device and returns the
+    /// accumulators of the devices, see [`MultiexpKernel::scoped_chunks`].
+    ///
+    /// Every accumulator starts as `init`.
+    fn run_chunks<K, T, C>(
+        &mut self, pool: &Worker, chunks: Vec<Vec<K>>, init: T, chunk: C,
+    ) -> EcResult<Vec<T>>
+    where
+        K: Send,
+        T: Clone + Send,
+        C: Fn(&mut SingleMultiexpKernel<'a, G>, K, &mut T) -> EcResult<()>
+            + Clone
+            + Send,
+    {
+        let mut accs = vec![init; self.kernels.len()];
+        let error = Arc::new(RwLock::new(Ok(())));
+        pool.scoped(|s| {
+            Self::scoped_chunks(
+                &mut self.kernels,
+                s,
+                chunks,
+                &mut accs,
+                &error,
+                chunk,
+                |_, _| {},
+            )
+        });
+
+        Arc::try_unwrap(error)
+            .expect("only one ref left")
+            .into_inner()
+            .unwrap()?;
+        Ok(accs)
+    }
+
+    /// Runs `chunk` for every chunk of every device within `scope`.
+    ///
+    /// `chunks` holds the chunks of every device, in the order of the
+    /// `kernels`. The devices run in parallel, each one passes its chunks one
+    /// after another to `chunk`, together with its kernel and its accumulator
+    /// in `accs`. The first error is stored in `error`, no further chunks
+    /// are started then. Once a device processed all its chunks without an
+    /// error, `done` is called with its index and accumulator. Devices
+    /// without chunks are left out.
+    fn scoped_chunks<'s, K, T, C, D>(
+        kernels: &'s mut [SingleMultiexpKernel<'a, G>], scope: &Scope<'s>,
+        chunks: Vec<Vec<K>>, accs: &'s mut [T],
+        error: &Arc<RwLock<EcResult<()>>>, chunk: C, done: D,
+    ) where
+        K: Send + 's,
+        T: Send,
+        C: Fn(&mut SingleMultiexpKernel<'a, G>, K, &mut T) -> EcResult<()>
+            + Clone
+            + Send
+            + 's,
+        D: Fn(usize, &T) + Clone + Send + 's,
+    {
+        for (device, ((device_chunks, kern), acc)) in chunks
+            .into_iter()
             // NOTE vmx 2021-11-17: This doesn't need to be a mutable iterator.
             // But when it isn't there will be errors that the
             // OpenCL CommandQueue cannot be shared between threads
             // safely.
-            .zip(self.kernels.iter_mut())
-            .zip(results.iter_mut())
+            .zip(kernels.iter_mut())
+            .zip(accs.iter_mut())
+            .enumerate()
         {
+            if device_chunks.is_empty() {
+                continue;
+            }
             let error = error.clone();
+            let chunk = chunk.clone();
+            let done = done.clone();
+            // The span is created here, so that its parent is the span of the
+            // calling thread.
+            #[cfg(feature = "tracing")]
+            let span = tracing::info_span!(
+                "multiexp_device",
+                device = kern.program.device_name(),
+                backend = kern.program.backend(),
+                num_chunks = device_chunks.len(),
+            );
             scope.execute(move || {
-                let mut acc = G::Curve::zero();
-                for (bases, exps) in
-                    bases.chunks(kern.n).zip(exps.chunks(kern.n))
-                {
+                #[cfg(feature = "tracing")]
+                let _entered = span.enter();
+                for device_chunk in device_chunks {
                     if error.read().unwrap().is_err() {
-                        break;
+                        return;
                     }
-                    match kern.multiexp(bases, exps) {
-                        Ok(result) => acc.add_assign(&result),
-                        Err(e) => {
-                            *error.write().unwrap() = Err(e);
-                            break;
-                        }
+                    if let Err(e) = chunk(kern, device_chunk, acc) {
+                        *error.write().unwrap() = Err(e);
+                        return;
                     }
                 }
                 if error.read().unwrap().is_ok() {
-                    *result = acc;
+                    done(device, acc);
                 }
             });
         }
     }
 
-    /// Calculate multiexp.
+    /// Returns how many of the `num_terms` terms of a multiexp each device
+    /// computes, in the order of the devices.
     ///
-    /// This is the main entry point.
-    pub fn multiexp(
-        &mut self, pool: &Worker, bases_arc: Arc<Vec<G>>,
-        exps: Arc<Vec<<G::Scalar as PrimeField>::Repr>>, skip: usize,
-    ) -> EcResult<G::Curve> {
-        // Bases are skipped by `self.1` elements, when converted from
-        // (Arc<Vec<G>>, usize) to Source https://github.com/zkcrypto/bellman/blob/10c5010fd9c2ca69442dc9775ea271e286e776d8/src/multiexp.rs#L38
-        let bases = &bases_arc[skip..(skip + exps.len())];
-        let exps = &exps[..];
+    /// The terms are split by the [`WorkSplitter`], see
+    /// [`MultiexpKernel::set_work_splitter`].
+    pub fn device_shares(&self, num_terms: usize) -> EcResult<Vec<usize>> {
+        Ok(self
+            .device_ranges(num_terms)?
+            .iter()
+            .map(|range| range.len())
+            .collect())
+    }
 
-        let mut results = Vec::new();
-        let error = Arc::new(RwLock::new(Ok(())));
+    /// Returns the window size each device uses for the first chunk of its
+    /// share of a multiexp of `num_terms` terms, see
+    /// [`MultiexpKernel::device_shares`].
+    pub fn window_size(&self, num_terms: usize) -> EcResult<Vec<usize>> {
+        Ok(self
+            .kernels
+            .iter()
+            .zip(self.device_shares(num_terms)?)
+            .map(|(kern, share)| kern.window_size(share))
+            .collect())
+    }
 
-        pool.scoped(|s| {
-            results = vec![G::Curve::zero(); self.kernels.len()];
-            self.parallel_multiexp(s, bases, exps, &mut results, error.clone());
+    /// Returns the names of the backends of the devices, each is either
+    /// `"cuda"` or `"opencl"`.
+    pub fn backend(&self) -> Vec<&'static str> {
+        self.kernels
+            .iter()
+            .map(SingleMultiexpKernel::backend)
+            .collect()
+    }
+
+    /// Returns a rough estimate of the time a multiexp of `num_terms` terms
+    /// takes.
+    ///
+    /// The devices run in parallel, hence it's the time of the slowest device
+    /// for its share, see [`SingleMultiexpKernel::estimate_time`].
+    pub fn estimate_time(&self, num_terms: usize) -> EcResult<Duration> {
+        Ok(self
+            .kernels
+            .iter()
+            .zip(self.device_shares(num_terms)?)
+            .map(|(kern, share)| kern.estimate_time(share))
+            .max()
+            .unwrap_or_default())
+    }
+
+    /// Calibrates the time estimates of all devices, see
+    /// [`SingleMultiexpKernel::calibrate`].
+    ///
+    /// It does nothing in the reproducible mode, see
+    /// [`MultiexpKernel::set_reproducible`].
+    pub fn calibrate(&mut self) -> EcResult<()> {
+        if self.reproducible {
+            return Ok(());
+        }
+        self.kernels
+            .iter_mut()
+            .try_for_each(SingleMultiexpKernel::calibrate)
+    }
+}
+
+impl<G> MultiexpKernel<'static, G>
+where G: GpuCurveAffine + GpuName
+{
+    /// Calculates a multiexp and yields the partial result of each device.
+    ///
+    /// The items are the index of the device and its contribution, in the
+    /// order the devices finish, each one is yielded as soon as its device
+    /// is done. Their sum is the result of [`MultiexpKernel::multiexp`]. The
+    /// bases may be longer than the exponents, the remaining ones are
+    /// ignored. In the reproducible mode, see
+    /// [`MultiexpKernel::set_reproducible`], the items are yielded in the
+    /// order of the devices once all of them are done.
+    ///
+    /// The devices run on a separate thread, which borrows the kernels until
+    /// the returned iterator is exhausted or dropped. If the multiexp fails,
+    /// the last item is the error.
+    pub fn multiexp_progressive(
+        &mut self, bases: Arc<Vec<G>>,
+        exps: Arc<Vec<<G::Scalar as PrimeField>::Repr>>,
+    ) -> MultiexpPartials<'_, G> {
+        let mut worker = MultiexpKernel {
+            kernels: std::mem::take(&mut self.kernels),
+            op_count: self.op_count,
+            splitter: self.splitter.clone(),
+            identity_handling: self.identity_handling,
+            reproducible: self.reproducible,
+        };
+        let (sender, receiver) = unbounded();
+        let thread = thread::spawn(move || {
+            let result = worker.multiexp_with_progress(
+                &Worker::new(),
+                bases,
+                exps,
+                0,
+                |device, partial| {
+                    // The receiver only goes away if the caller isn't
+                    // interested in the results anymore.
+                    let _ = sender.send((device, partial));
+                },
+            );
+            (worker, result)
         });
+        MultiexpPartials {
+            kernel: self,
+            receiver,
+            thread: Some(thread),
+        }
+    }
+}
 
-        Arc::try_unwrap(error)
-            .expect("only one ref left")
-            .into_inner()
-            .unwrap()?;
+/// The partial results of [`MultiexpKernel::multiexp_progressive`], each is
+/// yielded as soon as its device is done.
+///
+/// Once the devices are done, the kernels are given back to the
+/// [`MultiexpKernel`]. Dropping the iterator early waits for the devices.
+pub struct MultiexpPartials<'k, G>
+where G: GpuCurveAffine + GpuName
+{
+    kernel: &'k mut MultiexpKernel<'static, G>,
+    receiver: Receiver<(usize, G::Curve)>,
+    /// The thread that runs the devices, it's `None` once it was joined.
+    thread: Option<JoinHandle<(MultiexpKernel<'static, G>, EcResult<()>)>>,
+}
 
-        let mut acc = G::Curve::zero();
-        for r in results {
-            acc.add_assign(&r);
+impl<G> MultiexpPartials<'_, G>
+where G: GpuCurveAffine + GpuName
+{
+    /// Waits for the devices and gives the kernels back, returns the result
+    /// of the multiexp, or `Ok(())` if that already happened.
+    fn finish(&mut self) -> EcResult<()> {
+        let thread = match self.thread.take() {
+            Some(thread) => thread,
+            None => return Ok(()),
+        };
+        let (worker, result) = thread
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+        self.kernel.kernels = worker.kernels;
+        self.kernel.op_count = worker.op_count;
+        result
+    }
+}
+
+impl<G> Iterator for MultiexpPartials<'_, G>
+where G: GpuCurveAffine + GpuName
+{
+    type Item = EcResult<(usize, G::Curve)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The channel is closed once the thread is done.
+        match self.receiver.recv() {
+            Ok(partial) => Some(Ok(partial)),
+            Err(_) => self.finish().err().map(Err),
         }
+    }
+}
 
-        Ok(acc)
+impl<G> Drop for MultiexpPartials<'_, G>
+where G: GpuCurveAffine + GpuName
+{
+    fn drop(&mut self) {
+        // The error is dropped, like the results that weren't consumed.
+        let _ = self.finish();
     }
+}
 
-    /// Returns the number of kernels (one per device).
-    pub fn num_kernels(&self) -> usize { self.kernels.len() }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chosen_ark_suite::G1Affine;
+
+    #[test]
+    fn window_size_boundaries() {
+        let work_units = work_units(68, Some((AMPERE, 6)));
+        assert_eq!(window_size(0, work_units), 2);
+        assert_eq!(window_size(1, work_units), 2);
+        for log_terms in 1..=MAX_WINDOW_SIZE {
+            let terms = work_units << log_terms;
+            let expected = cmp::min(log_terms + 2, MAX_WINDOW_SIZE);
+            assert_eq!(window_size(terms, work_units), expected);
+            assert_eq!(window_size(terms + 1, work_units), expected);
+            assert_eq!(
+                window_size(terms - work_units, work_units),
+                cmp::min(log_terms + 1, MAX_WINDOW_SIZE)
+            );
+            assert_eq!(
+                window_size(terms - work_units + 1, work_units),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn chunk_size_boundaries() {
+        type Curve = <G1Affine as GpuCurveAffine>::Curve;
+        type Scalar = <G1Affine as GpuCurveAffine>::Scalar;
+
+        let work_units = work_units(80, None);
+        let term_size = std::mem::size_of::<G1Affine>() + exp_size::<Scalar>();
+        let fixed_size = work_units
+            * ((1 << MAX_WINDOW_SIZE) + 1)
+            * std::mem::size_of::<Curve>();
+        for log_mem in 30..=40 {
+            let mem = 1u64 << log_mem;
+            let expected = ((mem * 4 / 5) as usize - fixed_size) / term_size;
+            assert_eq!(calc_chunk_size::<G1Affine>(mem, work_units), expected);
+            assert!(
+                calc_chunk_size::<G1Affine>(mem - 1, work_units) <= expected
+            );
+            assert!(
+                calc_chunk_size::<G1Affine>(mem + 1, work_units) >= expected
+            );
+        }
+    }
 }
@@ -9,9 +9,32 @@ use rayon::iter::{
     IndexedParallelIterator, IntoParallelIterator, ParallelIterator,
 };
 
-use crate::threadpool::{Waiter, Worker};
+use crate::{
+    log2_floor,
+    threadpool::{Waiter, Worker},
+};
 use ec_gpu_program::EcError;
 
+/// On the GPU, the exponents are split into windows, this is the maximum
+/// number of bits of such a window.
+pub const MAX_WINDOW_SIZE: usize = 10;
+
+/// Calculates the window size of a multiexp of `num_terms` terms that is split
+/// into `work_units` units, like the GPU multiexp does.
+///
+/// Only integer arithmetic is used, so that the choice doesn't depend on the
+/// float implementation of the platform. Use it with
+/// [`multiexp_with_window`] to get a CPU multiexp that mirrors the GPU one.
+pub fn window_size(num_terms: usize, work_units: usize) -> usize {
+    // The window size was determined by running the
+    // `gpu_multiexp_consistency` test and looking at the resulting
+    // numbers.
+    let terms_per_unit =
+        std::cmp::max((num_terms + work_units - 1) / work_units, 1);
+    let window_size = log2_floor(terms_per_unit) as usize + 2;
+    std::cmp::min(window_size, MAX_WINDOW_SIZE)
+}
+
 /// An object that builds a source of bases.
 pub trait SourceBuilder<G: GpuCurveAffine>:
     Send + Sync + 'static + Clone
@@ -338,6 +361,25 @@ where
     )
 }
 
+/// `floor(e^k)` for `k` in `0..23`, `e^23` doesn't fit into a `u32`.
+const FLOOR_EXP: [u32; 23] = [
+    1, 2, 7, 20, 54, 148, 403, 1096, 2980, 8103, 22026, 59874, 162754, 442413,
+    1202604, 3269017, 8886110, 24154952, 65659969, 178482300, 485165195,
+    1318815734, 3584912846,
+];
+
+/// Returns `ceil(ln(n))` for a non-zero `n`.
+///
+/// It uses integers only, so that the result is the same on all platforms.
+fn ln_ceil(n: u32) -> u32 {
+    // As `e^k` is irrational for `k > 0`, `n <= e^k` is the same as
+    // `n <= floor(e^k)`.
+    FLOOR_EXP
+        .iter()
+        .position(|&bound| n <= bound)
+        .unwrap_or(FLOOR_EXP.len()) as u32
+}
+
 /// Perform multi-exponentiation. The caller is responsible for ensuring the
 /// query size is the same as the number of exponents.
 pub fn multiexp_cpu<'b, Q, D, G, S>(
@@ -353,7 +395,7 @@ where
     let c = if exponents.len() < 32 {
         3u32
     } else {
-        (f64::from(exponents.len() as u32)).ln().ceil() as u32
+        ln_ceil(exponents.len() as u32)
     };
     dbg!(exponents.len(), c); // log_e instead of log_2 ?
 
@@ -366,6 +408,24 @@ where
     pool.compute(move || multiexp_inner(bases, density_map, exponents, c))
 }
 
+/// Perform multi-exponentiation with a window of `c` bits, e.g. the one the
+/// GPU uses, see [`window_size`].
+///
+/// # Panics
+///
+/// Panics if `c` is not within `1..=32`.
+pub fn multiexp_with_window<G, S>(
+    pool: &Worker, bases: S,
+    exponents: Arc<Vec<<G::Scalar as PrimeFieldRepr>::Repr>>, c: u32,
+) -> Waiter<Result<<G as GpuCurveAffine>::Curve, EcError>>
+where
+    G: GpuCurveAffine,
+    S: SourceBuilder<G>,
+{
+    assert!((1..=32).contains(&c), "The window must have 1 to 32 bits");
+    pool.compute(move || multiexp_inner(bases, FullDensity, exponents, c))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -419,6 +479,45 @@ mod tests {
         assert_eq!(naive, fast);
     }
 
+    #[test]
+    fn test_multiexp_with_window() {
+        const SAMPLES: usize = 1 << 10;
+
+        let rng = &mut rand::thread_rng();
+        let g = Arc::new(
+            (0..SAMPLES)
+                .map(|_| G1Affine::rand(&mut *rng))
+                .collect::<Vec<_>>(),
+        );
+        let v = Arc::new(
+            (0..SAMPLES)
+                .map(|_| Scalar::rand(&mut *rng).to_repr())
+                .collect::<Vec<_>>(),
+        );
+        let pool = Worker::new();
+
+        let expected =
+            multiexp_cpu(&pool, (g.clone(), 0), FullDensity, v.clone())
+                .wait()
+                .unwrap();
+        for c in [1, 2, 5, MAX_WINDOW_SIZE as u32, 16] {
+            let result =
+                multiexp_with_window(&pool, (g.clone(), 0), v.clone(), c)
+                    .wait()
+                    .unwrap();
+            assert_eq!(expected, result, "c = {}", c);
+        }
+    }
+
+    #[test]
+    fn test_window_size() {
+        assert_eq!(window_size(0, 1024), 2);
+        assert_eq!(window_size(1024, 1024), 2);
+        assert_eq!(window_size(1025, 1024), 3);
+        assert_eq!(window_size(4 * 1024, 1024), 4);
+        assert_eq!(window_size(usize::MAX / 2, 1024), MAX_WINDOW_SIZE);
+    }
+
     #[test]
     fn test_extend_density_regular() {
         let mut rng = XorShiftRng::from_seed([
@@ -576,4 +675,21 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_ln_ceil() {
+        for n in 1..100_000u32 {
+            assert_eq!(
+                ln_ceil(n),
+                f64::from(n).ln().ceil() as u32,
+                "n = {}",
+                n
+            );
+        }
+        for (k, &bound) in FLOOR_EXP.iter().enumerate().skip(1) {
+            assert_eq!(ln_ceil(bound), k as u32);
+            assert_eq!(ln_ceil(bound + 1), k as u32 + 1);
+        }
+        assert_eq!(ln_ceil(u32::MAX), 23);
+    }
 }
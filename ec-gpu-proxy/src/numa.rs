@@ -0,0 +1,113 @@
+//! Helpers to allocate host memory close to a GPU on NUMA systems.
+//!
+//! Linux allocates memory on the NUMA node of the CPU that touches it first.
+//! Binding the allocating thread to the CPUs of the node a GPU is attached to
+//! therefore places staging buffers next to that GPU.
+
+use rust_gpu_tools::Device;
+
+/// Returns the NUMA node the device is attached to, if it is known.
+#[cfg(target_os = "linux")]
+pub(crate) fn device_numa_node(device: &Device) -> Option<usize> {
+    // The PCI id consists of the bus and the device number, the domain and
+    // function are assumed to be zero.
+    let pci_id: u16 = device.pci_id().into();
+    let path = format!(
+        "/sys/bus/pci/devices/0000:{:02x}:{:02x}.0/numa_node",
+        pci_id >> 8,
+        pci_id & 0xff
+    );
+    let node = std::fs::read_to_string(path).ok()?;
+    // The kernel reports `-1` if the node is unknown.
+    node.trim().parse().ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn device_numa_node(_device: &Device) -> Option<usize> { None }
+
+/// Binds the current thread to the CPUs of a NUMA node, the previous binding
+/// is restored when this guard is dropped.
+pub(crate) struct NodeAffinity {
+    #[cfg(target_os = "linux")]
+    previous: libc::cpu_set_t,
+}
+
+#[cfg(target_os = "linux")]
+impl NodeAffinity {
+    /// Binds the current thread to the CPUs of the given NUMA node.
+    ///
+    /// Returns `None` if the CPUs of the node cannot be determined or if the
+    /// binding fails, the thread is not bound then.
+    pub(crate) fn bind(node: usize) -> Option<Self> {
+        let path = format!("/sys/devices/system/node/node{}/cpulist", node);
+        let cpus = parse_cpu_list(&std::fs::read_to_string(path).ok()?)?;
+
+        // It is safe as `cpu_set_t` is a plain bit set, for which all zeros
+        // is a valid value, and the pointers point to such sets.
+        unsafe {
+            let mut previous: libc::cpu_set_t = std::mem::zeroed();
+            let size = std::mem::size_of::<libc::cpu_set_t>();
+            if libc::sched_getaffinity(0, size, &mut previous) != 0 {
+                return None;
+            }
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            for cpu in cpus {
+                libc::CPU_SET(cpu, &mut set);
+            }
+            if libc::sched_setaffinity(0, size, &set) != 0 {
+                return None;
+            }
+            Some(Self { previous })
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl NodeAffinity {
+    pub(crate) fn bind(_node: usize) -> Option<Self> { None }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for NodeAffinity {
+    fn drop(&mut self) {
+        // It is safe as `previous` was filled by `sched_getaffinity`.
+        unsafe {
+            libc::sched_setaffinity(
+                0,
+                std::mem::size_of::<libc::cpu_set_t>(),
+                &self.previous,
+            );
+        }
+    }
+}
+
+/// Parses a CPU list like `0-3,8-11,16`.
+#[cfg(any(target_os = "linux", test))]
+fn parse_cpu_list(list: &str) -> Option<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        match range.split_once('-') {
+            Some((start, end)) => {
+                cpus.extend(start.parse::<usize>().ok()?..=end.parse().ok()?)
+            }
+            None => cpus.push(range.parse().ok()?),
+        }
+    }
+    Some(cpus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cpu_list() {
+        assert_eq!(
+            parse_cpu_list("0-3,8-9,16\n"),
+            Some(vec![0, 1, 2, 3, 8, 9, 16])
+        );
+        assert_eq!(parse_cpu_list("5"), Some(vec![5]));
+        assert_eq!(parse_cpu_list(""), Some(vec![]));
+        assert_eq!(parse_cpu_list("a-b"), None);
+    }
+}
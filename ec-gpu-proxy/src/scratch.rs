@@ -0,0 +1,119 @@
+//! Temporary host buffers, which can be allocated with a custom allocator.
+
+use std::{
+    alloc::{handle_alloc_error, GlobalAlloc, Layout},
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    sync::Arc,
+};
+
+/// An allocator for the temporary host buffers of the kernels, like the
+/// staging buffers of transfers and the GPU representations of points.
+///
+/// Any [`GlobalAlloc`] can be used, e.g. an arena or pool allocator, so that
+/// large jobs don't put pressure on the global allocator.
+pub type HostAllocator = Arc<dyn GlobalAlloc + Send + Sync>;
+
+/// A fixed size buffer of `T`s, allocated with a [`HostAllocator`] if one is
+/// given, with the global allocator otherwise.
+pub(crate) enum ScratchVec<T: Copy> {
+    Global(Vec<T>),
+    Custom {
+        ptr: NonNull<T>,
+        /// The number of initialized elements.
+        len: usize,
+        capacity: usize,
+        allocator: HostAllocator,
+    },
+}
+
+// It is safe as the buffer exclusively owns its elements, like a `Vec`.
+unsafe impl<T: Copy + Send> Send for ScratchVec<T> {}
+unsafe impl<T: Copy + Sync> Sync for ScratchVec<T> {}
+
+impl<T: Copy> ScratchVec<T> {
+    /// Collects the elements of `iter` into a new buffer.
+    pub(crate) fn from_iter<I>(
+        allocator: Option<&HostAllocator>, iter: I,
+    ) -> Self
+    where I: ExactSizeIterator<Item = T> {
+        let capacity = iter.len();
+        let allocator = match allocator {
+            Some(allocator) if capacity > 0 && std::mem::size_of::<T>() > 0 => {
+                allocator.clone()
+            }
+            _ => return Self::Global(iter.collect()),
+        };
+        let layout = Layout::array::<T>(capacity).expect("capacity overflow");
+        // It is safe as the layout has a non-zero size.
+        let ptr = unsafe { allocator.alloc(layout) } as *mut T;
+        let ptr =
+            NonNull::new(ptr).unwrap_or_else(|| handle_alloc_error(layout));
+        let mut scratch = Self::Custom {
+            ptr,
+            len: 0,
+            capacity,
+            allocator,
+        };
+        if let Self::Custom { ptr, len, .. } = &mut scratch {
+            for elem in iter.take(capacity) {
+                // It is safe as `len` is smaller than the capacity.
+                unsafe { ptr.as_ptr().add(*len).write(elem) };
+                *len += 1;
+            }
+            assert_eq!(*len, capacity, "The iterator reported a wrong length");
+        }
+        scratch
+    }
+
+    /// Returns a new buffer of `len` copies of `elem`.
+    pub(crate) fn from_elem(
+        allocator: Option<&HostAllocator>, elem: T, len: usize,
+    ) -> Self {
+        Self::from_iter(allocator, std::iter::repeat(elem).take(len))
+    }
+}
+
+impl<T: Copy> Deref for ScratchVec<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match self {
+            Self::Global(vec) => vec,
+            // It is safe as the first `len` elements are initialized.
+            Self::Custom { ptr, len, .. } => unsafe {
+                std::slice::from_raw_parts(ptr.as_ptr(), *len)
+            },
+        }
+    }
+}
+
+impl<T: Copy> DerefMut for ScratchVec<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        match self {
+            Self::Global(vec) => vec,
+            // It is safe as the first `len` elements are initialized.
+            Self::Custom { ptr, len, .. } => unsafe {
+                std::slice::from_raw_parts_mut(ptr.as_ptr(), *len)
+            },
+        }
+    }
+}
+
+impl<T: Copy> Drop for ScratchVec<T> {
+    fn drop(&mut self) {
+        if let Self::Custom {
+            ptr,
+            capacity,
+            allocator,
+            ..
+        } = self
+        {
+            let layout =
+                Layout::array::<T>(*capacity).expect("capacity overflow");
+            // It is safe as the memory was allocated with the same allocator
+            // and layout. The elements are `Copy`, hence need no drop.
+            unsafe { allocator.dealloc(ptr.as_ptr() as *mut u8, layout) };
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::ops::Range;
+
+use ec_gpu_program::{DeviceInfo, EcError, EcResult};
+
+use crate::fft::div_ceil;
+
+/// Decides how much of some work each device gets, e.g. how many terms of a
+/// multiexp or how many FFTs of a batch.
+pub trait WorkSplitter: Send + Sync {
+    /// Splits `total` items among the `devices`.
+    ///
+    /// It must return one count per device, in the same order, which sum up
+    /// to `total`. Each device gets a contiguous range of the items, in the
+    /// order of the devices.
+    fn split(&self, total: usize, devices: &[DeviceInfo]) -> Vec<usize>;
+}
+
+/// Splits the work into equally sized parts, the last devices may get fewer
+/// items or none.
+///
+/// This is the default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EvenSplit;
+
+impl WorkSplitter for EvenSplit {
+    fn split(&self, total: usize, devices: &[DeviceInfo]) -> Vec<usize> {
+        if devices.is_empty() {
+            return Vec::new();
+        }
+        let chunk_size = std::cmp::max(div_ceil(total, devices.len()), 1);
+        (0..devices.len())
+            .map(|i| {
+                std::cmp::min(total.saturating_sub(i * chunk_size), chunk_size)
+            })
+            .collect()
+    }
+}
+
+/// Splits the work proportionally to the number of compute units of the
+/// devices.
+///
+/// Items that are left over due to rounding go to the devices with the
+/// largest remainders. If no device reports any compute units, the work is
+/// split evenly.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ComputeUnitSplit;
+
+impl WorkSplitter for ComputeUnitSplit {
+    fn split(&self, total: usize, devices: &[DeviceInfo]) -> Vec<usize> {
+        let weights = devices
+            .iter()
+            .map(|info| u128::from(info.compute_units))
+            .collect::<Vec<_>>();
+        split_proportionally(total, devices, &weights)
+    }
+}
+
+/// Splits the work proportionally to the global memory of the devices, so
+/// that a device with less memory gets a smaller part.
+///
+/// Like [`ComputeUnitSplit`], items that are left over due to rounding go to
+/// the devices with the largest remainders. If no device reports its memory,
+/// the work is split evenly.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MemorySplit;
+
+impl WorkSplitter for MemorySplit {
+    fn split(&self, total: usize, devices: &[DeviceInfo]) -> Vec<usize> {
+        let weights = devices
+            .iter()
+            .map(|info| u128::from(info.memory))
+            .collect::<Vec<_>>();
+        split_proportionally(total, devices, &weights)
+    }
+}
+
+/// Splits `total` items proportionally to the `weights` of the `devices`, or
+/// evenly if all weights are zero.
+fn split_proportionally(
+    total: usize, devices: &[DeviceInfo], weights: &[u128],
+) -> Vec<usize> {
+    let total_weight: u128 = weights.iter().sum();
+    if total_weight == 0 {
+        return EvenSplit.split(total, devices);
+    }
+    // Integer arithmetic only, so that the split is the same on all
+    // platforms.
+    let mut shares = Vec::with_capacity(weights.len());
+    let mut remainders = Vec::with_capacity(weights.len());
+    for (i, weight) in weights.iter().enumerate() {
+        let scaled = total as u128 * weight;
+        shares.push((scaled / total_weight) as usize);
+        remainders.push((scaled % total_weight, i));
+    }
+    let left = total - shares.iter().sum::<usize>();
+    // Larger remainders first, ties go to the earlier device.
+    remainders.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    for &(_, i) in remainders.iter().take(left) {
+        shares[i] += 1;
+    }
+    shares
+}
+
+/// Splits `total` items with `splitter` and returns the range of items of
+/// each device.
+///
+/// An error is returned if the splitter doesn't return one count per device,
+/// or if they don't sum up to `total`.
+pub(crate) fn split_ranges(
+    splitter: &dyn WorkSplitter, total: usize, devices: &[DeviceInfo],
+) -> EcResult<Vec<Range<usize>>> {
+    let shares = splitter.split(total, devices);
+    if shares.len() != devices.len() {
+        return Err(EcError::Simple(
+            "The work splitter must return one count per device",
+        ));
+    }
+    if shares.iter().sum::<usize>() != total {
+        return Err(EcError::Simple(
+            "The counts of the work splitter must sum up to the total",
+        ));
+    }
+    let mut start = 0;
+    Ok(shares
+        .into_iter()
+        .map(|share| {
+            let range = start..start + share;
+            start += share;
+            range
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(compute_units: u32) -> DeviceInfo {
+        DeviceInfo {
+            name: format!("GPU with {} units", compute_units),
+            vendor: String::new(),
+            memory: 1 << 30,
+            compute_units,
+            compute_capability: None,
+            cuda: false,
+            opencl: true,
+            atomics_64: false,
+        }
+    }
+
+    #[test]
+    fn test_even_split() {
+        let devices = [device(10), device(20), device(30)];
+        assert_eq!(EvenSplit.split(10, &devices), vec![4, 4, 2]);
+        assert_eq!(EvenSplit.split(2, &devices), vec![1, 1, 0]);
+        assert_eq!(EvenSplit.split(0, &devices), vec![0, 0, 0]);
+        assert!(EvenSplit.split(10, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_compute_unit_split() {
+        let devices = [device(10), device(20), device(30)];
+        assert_eq!(ComputeUnitSplit.split(60, &devices), vec![10, 20, 30]);
+        // 10 * (1/6, 2/6, 3/6) rounds down to (1, 3, 5), the first device
+        // has the largest remainder.
+        assert_eq!(ComputeUnitSplit.split(10, &devices), vec![2, 3, 5]);
+        assert_eq!(
+            ComputeUnitSplit.split(7, &[device(0), device(0)]),
+            vec![4, 3]
+        );
+    }
+
+    #[test]
+    fn test_memory_split() {
+        let mut small = device(10);
+        small.memory = 4 << 30;
+        let mut large = device(10);
+        large.memory = 12 << 30;
+        assert_eq!(MemorySplit.split(8, &[small.clone(), large]), vec![2, 6]);
+        small.memory = 0;
+        assert_eq!(MemorySplit.split(7, &[small.clone(), small]), vec![4, 3]);
+    }
+
+    #[test]
+    fn test_split_ranges() {
+        let devices = [device(10), device(30)];
+        assert_eq!(
+            split_ranges(&ComputeUnitSplit, 8, &devices).unwrap(),
+            vec![0..2, 2..8]
+        );
+    }
+
+    #[test]
+    fn test_split_ranges_wrong_total() {
+        struct Broken;
+        impl WorkSplitter for Broken {
+            fn split(&self, _: usize, devices: &[DeviceInfo]) -> Vec<usize> {
+                vec![1; devices.len()]
+            }
+        }
+        assert!(matches!(
+            split_ranges(&Broken, 8, &[device(10)]),
+            Err(EcError::Simple(msg)) if msg.contains("must sum up to the total")
+        ));
+    }
+}
@@ -0,0 +1,218 @@
+/// The default number of bytes that are uploaded at once by
+/// [`create_buffer_chunked!`], the abort hook is checked in between.
+pub(crate) const TRANSFER_CHUNK_BYTES: usize = 64 << 20;
+
+/// The number of 32-bit words a single checksum of [`verify_upload!`]
+/// covers.
+pub(crate) const CHECKSUM_CHUNK_WORDS: usize = 1024;
+
+/// The parameters of the FNV-1a hash that is used as checksum.
+const FNV_OFFSET: u32 = 2166136261;
+const FNV_PRIME: u32 = 16777619;
+
+/// How an upload with [`create_buffer_checked!`] is done and what is done
+/// after it.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct UploadCheck {
+    /// The number of bytes that are uploaded at once.
+    pub(crate) chunk_bytes: usize,
+    /// Whether the data on the GPU is compared with the host data.
+    pub(crate) verify: bool,
+    /// Whether the first word on the GPU is flipped, to simulate a transfer
+    /// error.
+    pub(crate) corrupt: bool,
+}
+
+impl Default for UploadCheck {
+    fn default() -> Self {
+        Self {
+            chunk_bytes: TRANSFER_CHUNK_BYTES,
+            verify: false,
+            corrupt: false,
+        }
+    }
+}
+
+/// Returns how many elements of `data` are uploaded at once, if every chunk
+/// has `chunk_bytes` bytes.
+pub(crate) fn chunk_elements<T>(_data: &[T], chunk_bytes: usize) -> usize {
+    std::cmp::max(chunk_bytes / std::mem::size_of::<T>(), 1)
+}
+
+/// Returns the number of 32-bit words of an element of `data`.
+pub(crate) fn element_words<T>(_data: &[T]) -> usize {
+    assert_eq!(
+        std::mem::size_of::<T>() % 4,
+        0,
+        "Only elements that consist of 32-bit words can be uploaded in chunks"
+    );
+    std::mem::size_of::<T>() / 4
+}
+
+/// Returns the 32-bit words `data` consists of.
+pub(crate) fn as_words<T>(data: &[T]) -> &[u32] {
+    let words = element_words(data);
+    assert!(
+        std::mem::align_of::<T>() >= 4,
+        "The elements are not aligned"
+    );
+    // SAFETY: The elements consist of whole, aligned 32-bit words, which is
+    // also how they are copied on the GPU.
+    unsafe {
+        std::slice::from_raw_parts(
+            data.as_ptr() as *const u32,
+            data.len() * words,
+        )
+    }
+}
+
+/// Returns the checksums of the chunks of [`CHECKSUM_CHUNK_WORDS`] words of
+/// `data`, like the `checksum_words` kernel calculates them.
+pub(crate) fn checksums<T>(data: &[T]) -> Vec<u32> {
+    as_words(data)
+        .chunks(CHECKSUM_CHUNK_WORDS)
+        .map(|chunk| {
+            chunk.iter().fold(FNV_OFFSET, |hash, word| {
+                (hash ^ word).wrapping_mul(FNV_PRIME)
+            })
+        })
+        .collect()
+}
+
+/// Creates a buffer from `data`, which are elements of type `$t`, like
+/// `create_buffer_from_slice`, but uploads it in chunks of `$chunk_bytes`
+/// bytes.
+///
+/// The `maybe_abort` hook (an `&Option<&dyn Fn() -> bool>`) is called before
+/// every chunk, so that a large upload can be aborted with
+/// [`EcError::Aborted`](ec_gpu_program::EcError::Aborted). As buffers can
+/// only be written as a whole, the chunks go through a staging buffer and
+/// are copied into place on the GPU. It can only be used within the bodies
+/// of `program_closures!` that return an `EcResult`.
+macro_rules! create_buffer_chunked {
+    (
+        $program:expr, $data:expr, $t:ty, $maybe_abort:expr, $chunk_bytes:expr
+    ) => {{
+        let program = $program;
+        let data = $data;
+        let chunk_len = $crate::transfer::chunk_elements(data, $chunk_bytes);
+        if data.len() <= chunk_len {
+            program.create_buffer_from_slice(data)?
+        } else {
+            let words = $crate::transfer::element_words(data);
+            // It is safe as the chunks initialize the whole buffer.
+            let buffer = unsafe { program.create_buffer::<$t>(data.len())? };
+            // It is safe as every chunk is written before it is read.
+            let mut staging =
+                unsafe { program.create_buffer::<$t>(chunk_len)? };
+            // The staging buffer is always written as a whole, hence the last
+            // chunk needs a host buffer of the full size.
+            let mut host = data[..chunk_len].to_vec();
+            for (i, chunk) in data.chunks(chunk_len).enumerate() {
+                if let Some(maybe_abort) = $maybe_abort {
+                    if maybe_abort() {
+                        return Err(ec_gpu_program::EcError::Aborted);
+                    }
+                }
+                host[..chunk.len()].clone_from_slice(chunk);
+                program.write_from_buffer(&mut staging, &host)?;
+                let (global_work_size, local_work_size) =
+                    $crate::fft::elementwise_work_size(chunk.len() * words);
+                let kernel = program.create_kernel(
+                    "copy_words_to_offset",
+                    global_work_size,
+                    local_work_size,
+                )?;
+                kernel
+                    .arg(&staging)
+                    .arg(&buffer)
+                    .arg(&((i * chunk_len * words) as u32))
+                    .arg(&((chunk.len() * words) as u32))
+                    .run()?;
+            }
+            buffer
+        }
+    }};
+}
+
+/// Checks that `$buffer` contains the same elements as `$data`, i.e. that
+/// the upload wasn't corrupted.
+///
+/// Instead of reading the whole buffer back, a checksum of every chunk of
+/// [`CHECKSUM_CHUNK_WORDS`] words is calculated on the GPU and compared with
+/// the one of the host data. A mismatch results in an
+/// [`EcError::TransferCorrupted`](ec_gpu_program::EcError::TransferCorrupted).
+/// It can only be used within the bodies of `program_closures!` that return
+/// an `EcResult`.
+macro_rules! verify_upload {
+    ($program:expr, $buffer:expr, $data:expr) => {{
+        let program = $program;
+        let expected = $crate::transfer::checksums($data);
+        if !expected.is_empty() {
+            let num_words = $crate::transfer::as_words($data).len();
+            let num_chunks = expected.len();
+            // It is safe as the GPU will initialize that buffer
+            let results_buffer =
+                unsafe { program.create_buffer::<u32>(num_chunks)? };
+            let (global_work_size, local_work_size) =
+                $crate::fft::elementwise_work_size(num_chunks);
+            let kernel = program.create_kernel(
+                "checksum_words",
+                global_work_size,
+                local_work_size,
+            )?;
+            kernel
+                .arg($buffer)
+                .arg(&(num_words as u32))
+                .arg(&($crate::transfer::CHECKSUM_CHUNK_WORDS as u32))
+                .arg(&results_buffer)
+                .arg(&(num_chunks as u32))
+                .run()?;
+            let mut actual = vec![0u32; num_chunks];
+            program.read_into_buffer(&results_buffer, &mut actual)?;
+            if let Some(chunk) =
+                expected.iter().zip(&actual).position(|(e, a)| e != a)
+            {
+                return Err(ec_gpu_program::EcError::TransferCorrupted {
+                    offset: chunk * $crate::transfer::CHECKSUM_CHUNK_WORDS * 4,
+                });
+            }
+        }
+    }};
+}
+
+/// Like [`create_buffer_chunked!`] with the chunk size of the
+/// [`UploadCheck`] `$check`, but afterwards applies its checks.
+macro_rules! create_buffer_checked {
+    ($program:expr, $data:expr, $t:ty, $maybe_abort:expr, $check:expr) => {{
+        let program = $program;
+        let data = $data;
+        let check: $crate::transfer::UploadCheck = $check;
+        let buffer = $crate::transfer::create_buffer_chunked!(
+            program,
+            data,
+            $t,
+            $maybe_abort,
+            check.chunk_bytes
+        );
+        if check.corrupt && !data.is_empty() {
+            let flipped = [!$crate::transfer::as_words(data)[0]];
+            let flipped_buffer = program.create_buffer_from_slice(&flipped)?;
+            let kernel = program.create_kernel("copy_words_to_offset", 1, 1)?;
+            kernel
+                .arg(&flipped_buffer)
+                .arg(&buffer)
+                .arg(&0u32)
+                .arg(&1u32)
+                .run()?;
+        }
+        if check.verify {
+            $crate::transfer::verify_upload!(program, &buffer, data);
+        }
+        buffer
+    }};
+}
+
+pub(crate) use create_buffer_checked;
+pub(crate) use create_buffer_chunked;
+pub(crate) use verify_upload;
@@ -0,0 +1,52 @@
+#![cfg(any(feature = "cuda", feature = "opencl"))]
+
+use ag_build::{self, generate};
+use ark_bls12_381::{Fr, G1Affine};
+use ec_gpu_proxy::{
+    bench::run_comparison, fft::FftKernel, multiexp::MultiexpKernel,
+    threadpool::Worker,
+};
+use rust_gpu_tools::Device;
+
+#[test]
+fn gpu_run_comparison() {
+    fil_logger::maybe_init();
+    generate(
+        &ag_build::SourceBuilder::new()
+            .add_fft::<Fr>()
+            .add_multiexp::<G1Affine>(),
+    );
+    let devices = Device::all();
+    let load_programs = || {
+        devices
+            .iter()
+            .map(|device| ec_gpu_program::load_program!(device))
+            .collect::<Result<Vec<_>, _>>()
+            .expect("Cannot create programs!")
+    };
+    let mut fft_kern = FftKernel::<Fr>::create(load_programs())
+        .expect("Cannot initialize kernel!");
+    let mut multiexp_kern =
+        MultiexpKernel::<G1Affine>::create(load_programs(), &devices)
+            .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+
+    let log_sizes = [4, 6, 8];
+    let report =
+        run_comparison(&mut fft_kern, &mut multiexp_kern, &pool, &log_sizes)
+            .expect("The comparison failed!");
+
+    assert_eq!(report.multiexp_devices.len(), multiexp_kern.num_kernels());
+    assert!(!report.fft_device.name.is_empty());
+    for timings in [&report.fft, &report.multiexp] {
+        assert_eq!(
+            timings.iter().map(|t| t.log_n).collect::<Vec<_>>(),
+            log_sizes
+        );
+        for timing in timings {
+            assert!(timing.cpu > std::time::Duration::ZERO);
+            assert!(timing.gpu > std::time::Duration::ZERO);
+            assert!(timing.speedup() > 0.0);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+#![cfg(any(feature = "cuda", feature = "opencl"))]
+
+use std::sync::Arc;
+
+use ag_build::{self, generate};
+use ag_types::{GpuCurveAffine, PrimeFieldRepr};
+use ark_bn254::{Fq, Fr, G1Affine};
+use ark_ec::CurveGroup;
+use ark_ff::{BigInteger, FftField, PrimeField};
+use ark_std::UniformRand;
+use ec_gpu_proxy::{
+    bytes::FieldSpec, fft::FftKernel, multiexp::MultiexpKernel,
+    threadpool::Worker,
+};
+use rust_gpu_tools::{Device, Program};
+
+fn load_programs() -> Vec<Program> {
+    generate(
+        &ag_build::SourceBuilder::new()
+            .add_fft::<Fr>()
+            .add_multiexp::<G1Affine>(),
+    );
+    let devices = Device::all();
+    devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!")
+}
+
+fn encode<F: PrimeField>(values: &[F]) -> Vec<u8> {
+    let len = FieldSpec::of::<F>().bytes_per_element();
+    values
+        .iter()
+        .flat_map(|value| value.into_bigint().to_bytes_le()[..len].to_vec())
+        .collect()
+}
+
+#[test]
+fn gpu_multiexp_bytes_consistency() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    let mut kern =
+        MultiexpKernel::<G1Affine>::create(load_programs(), &devices)
+            .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    const NUM_TERMS: usize = 1 << 10;
+    let mut bases = (0..NUM_TERMS)
+        .map(|_| G1Affine::rand(&mut rng))
+        .collect::<Vec<_>>();
+    bases[3] = G1Affine::identity();
+    let scalars = (0..NUM_TERMS)
+        .map(|_| Fr::rand(&mut rng))
+        .collect::<Vec<_>>();
+
+    let typed = kern
+        .multiexp(
+            &pool,
+            Arc::new(bases.clone()),
+            Arc::new(scalars.iter().map(PrimeFieldRepr::to_repr).collect()),
+            0,
+        )
+        .expect("GPU multiexp failed!")
+        .into_affine();
+
+    let base_bytes = encode(
+        &bases
+            .iter()
+            .flat_map(|base| {
+                let (x, y) = base.to_xy();
+                [x, y]
+            })
+            .collect::<Vec<_>>(),
+    );
+    let base_field = FieldSpec::of::<Fq>();
+    let scalar_field = FieldSpec::of::<Fr>();
+    let bytes = kern
+        .multiexp_bytes(
+            &pool,
+            &base_field,
+            &scalar_field,
+            &base_bytes,
+            &encode(&scalars),
+        )
+        .expect("GPU multiexp failed!");
+    let (x, y) = typed.to_xy();
+    assert_eq!(bytes, encode(&[x, y]));
+
+    // The fields must match the kernel.
+    assert!(kern
+        .multiexp_bytes(
+            &pool,
+            &scalar_field,
+            &scalar_field,
+            &base_bytes,
+            &encode(&scalars),
+        )
+        .is_err());
+}
+
+#[test]
+fn gpu_fft_bytes_consistency() {
+    fil_logger::maybe_init();
+    let mut kern = FftKernel::<Fr>::create(load_programs())
+        .expect("Cannot initialize kernel!");
+    let mut rng = rand::thread_rng();
+
+    let log_n = 10;
+    let omega = Fr::get_root_of_unity(1 << log_n).unwrap();
+    let mut values = (0..1 << log_n)
+        .map(|_| Fr::rand(&mut rng))
+        .collect::<Vec<_>>();
+    let mut bytes = encode(&values);
+
+    kern.radix_fft(&mut values, &omega, log_n)
+        .expect("GPU FFT failed!");
+    kern.radix_fft_bytes(
+        &FieldSpec::of::<Fr>(),
+        &mut bytes,
+        &encode(&[omega]),
+        log_n,
+    )
+    .expect("GPU FFT failed!");
+    assert_eq!(bytes, encode(&values));
+}
@@ -1,23 +1,32 @@
 #![cfg(any(feature = "cuda", feature = "opencl"))]
 
-use std::time::Instant;
+use std::{sync::Arc, time::Instant};
 
 use ag_build::generate;
-use ark_bls12_381::{Fr, G1Affine};
-use ark_ec::AffineRepr;
-use ark_ff::FftField;
+use ag_types::PrimeFieldRepr;
+use ark_bls12_381::{Fq, Fr, G1Affine, G1Projective};
+use ark_ec::{AffineRepr, CurveGroup};
+use ark_ff::{FftField, Field};
 use ark_poly::{EvaluationDomain, Radix2EvaluationDomain};
 use ark_std::UniformRand;
+use ec_gpu_program::EcError;
 use ec_gpu_proxy::{
     ec_fft::EcFftKernel,
-    ec_fft_cpu::{parallel_ec_fft, serial_ec_fft},
+    ec_fft_cpu::{
+        parallel_ec_fft, serial_ec_coset_fft, serial_ec_coset_ifft,
+        serial_ec_fft,
+    },
+    fft::FftKernel,
+    multiexp::MultiexpKernel,
+    multiexp_cpu::{multiexp_cpu, FullDensity},
     threadpool::Worker,
+    SharedProgram,
 };
 use rust_gpu_tools::Device;
 
 fn omega<F: FftField>(num_coeffs: usize) -> F {
     // Compute omega, the 2^exp primitive root of unity
-    let exp = (num_coeffs as f32).log2().floor() as u32;
+    let exp = ec_gpu_proxy::log2_strict(num_coeffs);
     let mut omega = F::TWO_ADIC_ROOT_OF_UNITY;
     for _ in exp..F::TWO_ADICITY {
         omega = omega.square();
@@ -120,6 +129,9 @@ pub fn gpu_ec_fft_many_consistency() {
         let v22_omega = v12_omega;
         let v23_omega = v13_omega;
 
+        let original =
+            [v11_coeffs.clone(), v12_coeffs.clone(), v13_coeffs.clone()];
+
         println!("Testing FFTg3 for {} elements...", d);
 
         let mut now = Instant::now();
@@ -171,6 +183,330 @@ pub fn gpu_ec_fft_many_consistency() {
         assert!(v12_coeffs == v22_coeffs);
         assert!(v13_coeffs == v23_coeffs);
 
+        // The inverse FFT restores the original points exactly.
+        kern.radix_ec_ifft_many(
+            &mut [&mut v11_coeffs, &mut v12_coeffs, &mut v13_coeffs],
+            &[v11_omega, v12_omega, v13_omega],
+            &[log_d, log_d, log_d],
+        )
+        .expect("GPU IFFTg3 failed!");
+        assert!(v11_coeffs == original[0]);
+        assert!(v12_coeffs == original[1]);
+        assert!(v13_coeffs == original[2]);
+
         println!("============================");
     }
 }
+
+#[test]
+pub fn gpu_ec_fft_projective_output() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_ec_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = EcFftKernel::<G1Affine>::create(programs)
+        .expect("Cannot initialize kernel!");
+
+    let log_d = 10;
+    let d = 1 << log_d;
+    let mut v1_coeffs = (0..d)
+        .map(|_| G1Affine::rand(&mut rng).into_group())
+        .collect::<Vec<_>>();
+    let mut v2_coeffs = v1_coeffs.clone();
+    let v1_omega = Fr::get_root_of_unity(d as u64).unwrap();
+
+    kern.radix_ec_fft_many(&mut [&mut v1_coeffs], &[v1_omega], &[log_d])
+        .expect("GPU FFTg failed!");
+    // The kernel must not normalize the points.
+    assert!(v1_coeffs.iter().any(|point| point.z != Fq::ONE));
+
+    let fft_domain = Radix2EvaluationDomain::<Fr>::new(d).unwrap();
+    fft_domain.fft_in_place(&mut v2_coeffs);
+
+    assert_eq!(
+        G1Projective::normalize_batch(&v1_coeffs),
+        G1Projective::normalize_batch(&v2_coeffs)
+    );
+}
+
+#[test]
+pub fn gpu_shared_program() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+    generate(
+        &ag_build::SourceBuilder::new()
+            .add_fft::<Fr>()
+            .add_ec_fft::<G1Affine>()
+            .add_multiexp::<G1Affine>(),
+    );
+    let devices = Device::all();
+    let programs: Vec<SharedProgram> = devices
+        .iter()
+        .map(|device| {
+            ec_gpu_program::load_program!(device).map(SharedProgram::new)
+        })
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+
+    let mut fft_kern = FftKernel::<Fr>::create_shared(programs.clone())
+        .expect("Cannot initialize FFT kernel!");
+    let mut ec_fft_kern =
+        EcFftKernel::<G1Affine>::create_shared(programs.clone())
+            .expect("Cannot initialize FFTg kernel!");
+    let mut multiexp_kern =
+        MultiexpKernel::<G1Affine>::create_shared(programs, &devices)
+            .expect("Cannot initialize multiexp kernel!");
+
+    let log_d = 10;
+    let d = 1 << log_d;
+    let domain = Radix2EvaluationDomain::<Fr>::new(d).unwrap();
+    let omega = Fr::get_root_of_unity(d as u64).unwrap();
+
+    let coeffs = (0..d).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+    let mut gpu = coeffs.clone();
+    fft_kern.radix_fft(&mut gpu, &omega, log_d).unwrap();
+    assert_eq!(domain.fft(&coeffs), gpu);
+
+    let points = (0..d)
+        .map(|_| G1Affine::rand(&mut rng).into_group())
+        .collect::<Vec<_>>();
+    let mut gpu = points.clone();
+    ec_fft_kern
+        .radix_ec_fft_many(&mut [&mut gpu], &[omega], &[log_d])
+        .unwrap();
+    let mut cpu = points;
+    domain.fft_in_place(&mut cpu);
+    assert_eq!(cpu, gpu);
+
+    let pool = Worker::new();
+    let bases =
+        Arc::new((0..d).map(|_| G1Affine::rand(&mut rng)).collect::<Vec<_>>());
+    let exps = Arc::new(
+        (0..d)
+            .map(|_| Fr::rand(&mut rng).to_repr())
+            .collect::<Vec<_>>(),
+    );
+    let gpu = multiexp_kern
+        .multiexp(&pool, bases.clone(), exps.clone(), 0)
+        .unwrap();
+    let cpu = multiexp_cpu(&pool, (bases, 0), FullDensity, exps)
+        .wait()
+        .unwrap();
+    assert_eq!(cpu.into_affine(), gpu.into_affine());
+}
+
+#[test]
+pub fn gpu_ec_fft_on_device() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+    build_ec_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = EcFftKernel::<G1Affine>::create(programs)
+        .expect("Cannot initialize kernel!");
+
+    for log_d in [1, 4, 10] {
+        let d = 1 << log_d;
+        let omega = omega::<Fr>(d);
+        let points = (0..d)
+            .map(|_| G1Affine::rand(&mut rng).into_group())
+            .collect::<Vec<_>>();
+
+        let mut expected = points.clone();
+        for _ in 0..2 {
+            kern.radix_ec_fft(&mut expected, &omega, log_d)
+                .expect("GPU FFTg failed!");
+        }
+
+        let mut buffer = kern.upload(&points).unwrap();
+        for _ in 0..2 {
+            buffer =
+                kern.radix_ec_fft_on_device(buffer, &omega, log_d).unwrap();
+        }
+        assert_eq!(kern.download(buffer).unwrap(), expected);
+    }
+}
+
+#[test]
+pub fn gpu_point_add_many_consistency() {
+    use ark_ff::Zero;
+
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+    build_ec_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = EcFftKernel::<G1Affine>::create(programs)
+        .expect("Cannot initialize kernel!");
+
+    let n = 1000;
+    let mut a = (0..n)
+        .map(|_| G1Projective::rand(&mut rng))
+        .collect::<Vec<_>>();
+    let mut b = (0..n)
+        .map(|_| G1Projective::rand(&mut rng))
+        .collect::<Vec<_>>();
+    // The point at infinity on either side, as well as a doubling and a sum
+    // that is the point at infinity.
+    a[0] = G1Projective::zero();
+    b[1] = G1Projective::zero();
+    a[2] = G1Projective::zero();
+    b[2] = G1Projective::zero();
+    b[3] = a[3];
+    b[4] = -a[4];
+
+    let expected = a.iter().zip(&b).map(|(a, b)| *a + b).collect::<Vec<_>>();
+    kern.point_add_many(&mut a, &b)
+        .expect("GPU addition failed!");
+    assert_eq!(a, expected);
+    assert!(a[4].is_zero());
+
+    assert!(kern.point_add_many(&mut a, &b[1..]).is_err());
+}
+
+#[test]
+pub fn gpu_ec_fft_coset_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_ec_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = EcFftKernel::<G1Affine>::create(programs)
+        .expect("Cannot initialize kernel!");
+
+    for log_d in [1, 4, 10] {
+        let d = 1 << log_d;
+        let num_inputs = 3;
+        let original = (0..num_inputs)
+            .map(|_| {
+                (0..d)
+                    .map(|_| G1Affine::rand(&mut rng).into_group())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let omegas = vec![omega::<Fr>(d); num_inputs];
+        let log_ns = vec![log_d; num_inputs];
+        let shifts = [Fr::rand(&mut rng), Fr::rand(&mut rng), Fr::ONE];
+
+        let mut values = original.clone();
+        let mut inputs =
+            values.iter_mut().map(|v| &mut v[..]).collect::<Vec<_>>();
+        kern.radix_ec_fft_coset_many(&mut inputs, &omegas, &log_ns, &shifts)
+            .expect("GPU FFTg failed!");
+        for ((points, evals), shift) in
+            original.iter().zip(&values).zip(&shifts)
+        {
+            let mut expected = points.clone();
+            serial_ec_coset_fft::<G1Affine>(
+                &mut expected,
+                &omegas[0],
+                shift,
+                log_d,
+            );
+            assert_eq!(*evals, expected, "mismatch for 2^{}", log_d);
+        }
+
+        // A shift of one is the same as the FFT over the subgroup.
+        let mut plain = original[num_inputs - 1].clone();
+        kern.radix_ec_fft_many(&mut [&mut plain], &omegas[..1], &log_ns[..1])
+            .expect("GPU FFTg failed!");
+        assert_eq!(plain, values[num_inputs - 1]);
+
+        let mut expected = values.clone();
+        for (points, shift) in expected.iter_mut().zip(&shifts) {
+            serial_ec_coset_ifft::<G1Affine>(points, &omegas[0], shift, log_d);
+        }
+        assert_eq!(expected, original);
+        let mut inputs =
+            values.iter_mut().map(|v| &mut v[..]).collect::<Vec<_>>();
+        kern.radix_ec_ifft_coset_many(&mut inputs, &omegas, &log_ns, &shifts)
+            .expect("GPU FFTg failed!");
+        assert_eq!(values, expected, "mismatch for 2^{}", log_d);
+    }
+
+    let mut points = vec![G1Projective::default(); 4];
+    let result = kern.radix_ec_fft_coset_many(
+        &mut [&mut points],
+        &[omega::<Fr>(4)],
+        &[2],
+        &[Fr::ONE, Fr::ONE],
+    );
+    assert!(matches!(
+        result,
+        Err(EcError::DimensionMismatch {
+            expected: 1,
+            actual: 2
+        })
+    ));
+}
+
+#[test]
+pub fn gpu_ec_fft_distributed_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_ec_fft();
+    let devices = Device::all();
+    let load = |devices: &[&Device]| {
+        let programs = devices
+            .iter()
+            .map(|&device| ec_gpu_program::load_program!(device))
+            .collect::<Result<_, _>>()
+            .expect("Cannot create programs!");
+        EcFftKernel::<G1Affine>::create(programs)
+            .expect("Cannot initialize kernel!")
+    };
+    let mut single = load(&[&devices[0]]);
+    // Load the first device several times, so that the work is split even if
+    // there is only a single GPU.
+    let mut many = load(&[&devices[0], &devices[0], &devices[0]]);
+    many.set_work_splitter(ec_gpu_proxy::split::MemorySplit);
+    let mut all = load(&devices.iter().collect::<Vec<_>>());
+
+    for log_d in [1, 2, 5, 8, 11] {
+        let d = 1 << log_d;
+        let omega = omega::<Fr>(d);
+        let coeffs = (0..d)
+            .map(|_| G1Affine::rand(&mut rng).into_group())
+            .collect::<Vec<_>>();
+
+        let mut expected = coeffs.clone();
+        single
+            .radix_ec_fft(&mut expected, &omega, log_d)
+            .expect("GPU FFTg failed!");
+        let mut cpu = coeffs.clone();
+        serial_ec_fft::<G1Affine>(&mut cpu, &omega, log_d);
+        assert!(cpu == expected, "single GPU mismatch for 2^{}", log_d);
+
+        for kern in [&mut single, &mut many, &mut all] {
+            let mut distributed = coeffs.clone();
+            kern.radix_ec_fft_distributed(&mut distributed, &omega, log_d)
+                .expect("GPU FFTg failed!");
+            assert!(
+                distributed == expected,
+                "mismatch for 2^{} on {} devices",
+                log_d,
+                kern.device_info().len()
+            );
+        }
+    }
+}
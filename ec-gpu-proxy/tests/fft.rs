@@ -1,21 +1,24 @@
 #![cfg(any(feature = "cuda", feature = "opencl"))]
 
-use std::time::Instant;
+use std::{sync::Arc, time::Instant};
 
 use ag_build::{self, generate};
 use ark_bls12_381::Fr;
-use ark_ff::FftField;
+use ark_ff::{FftField, Field, PrimeField};
 use ark_std::UniformRand;
+use ec_gpu_program::EcError;
 use ec_gpu_proxy::{
-    fft::FftKernel,
-    fft_cpu::{parallel_fft, serial_fft},
+    budget::MemoryBudget,
+    fft::{FftKernel, FftPostMap, InputForm, QuotientDomain},
+    fft_cpu::{parallel_fft, serial_coset_fft, serial_coset_ifft, serial_fft},
     threadpool::Worker,
+    SharedProgram,
 };
 use rust_gpu_tools::Device;
 
 fn omega<F: FftField>(num_coeffs: usize) -> F {
     // Compute omega, the 2^exp primitive root of unity
-    let exp = (num_coeffs as f32).log2().floor() as u32;
+    let exp = ec_gpu_proxy::log2_strict(num_coeffs);
     let mut omega = F::TWO_ADIC_ROOT_OF_UNITY;
     for _ in exp..F::TWO_ADICITY {
         omega = omega.square();
@@ -120,6 +123,9 @@ pub fn gpu_fft_many_consistency() {
         let v22_omega = v12_omega;
         let v23_omega = v13_omega;
 
+        let original =
+            [v11_coeffs.clone(), v12_coeffs.clone(), v13_coeffs.clone()];
+
         println!("Testing FFT3 for {} elements...", d);
 
         let mut now = Instant::now();
@@ -171,6 +177,1276 @@ pub fn gpu_fft_many_consistency() {
         assert!(v12_coeffs == v22_coeffs);
         assert!(v13_coeffs == v23_coeffs);
 
+        // The inverse FFT restores the original coefficients exactly.
+        kern.radix_ifft_many(
+            &mut [&mut v11_coeffs, &mut v12_coeffs, &mut v13_coeffs],
+            &[v11_omega, v12_omega, v13_omega],
+            &[log_d, log_d, log_d],
+        )
+        .expect("GPU IFFT failed!");
+        assert!(v11_coeffs == original[0]);
+        assert!(v12_coeffs == original[1]);
+        assert!(v13_coeffs == original[2]);
+
         println!("============================");
     }
 }
+
+#[test]
+pub fn gpu_fft_many_mixed_sizes() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+
+    // Sizes that are done in local memory and with radix rounds, some of
+    // them repeated, so that they are batched.
+    let log_ns = [3, 12, 3, 17, 12, 1, 17, 12, 9, 3];
+    let mut omegas = log_ns
+        .iter()
+        .map(|log_n| omega::<Fr>(1 << log_n))
+        .collect::<Vec<_>>();
+    // The same size with a different `omega` is in a separate batch.
+    omegas[4] = omegas[4].inverse().unwrap();
+    let original = log_ns
+        .iter()
+        .map(|log_n| {
+            (0..1 << log_n)
+                .map(|_| Fr::rand(&mut rng))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut expected = original.clone();
+    for ((values, omega), log_n) in
+        expected.iter_mut().zip(&omegas).zip(&log_ns)
+    {
+        kern.radix_fft(values, omega, *log_n)
+            .expect("GPU FFT failed!");
+    }
+
+    let mut values = original.clone();
+    let mut inputs = values.iter_mut().map(|v| &mut v[..]).collect::<Vec<_>>();
+    kern.radix_fft_many(&mut inputs, &omegas, &log_ns)
+        .expect("GPU FFT failed!");
+    assert!(values == expected);
+
+    let mut inputs = values.iter_mut().map(|v| &mut v[..]).collect::<Vec<_>>();
+    kern.radix_ifft_many(&mut inputs, &omegas, &log_ns)
+        .expect("GPU IFFT failed!");
+    assert!(values == original);
+
+    // A budget for a single FFT of the largest size splits its batch.
+    let largest = 2 * (1 << 17) * std::mem::size_of::<Fr>();
+    let budget = Arc::new(MemoryBudget::new(largest + (1 << 20)));
+    let mut kern = kern.with_budget(budget.clone());
+    let mut inputs = values.iter_mut().map(|v| &mut v[..]).collect::<Vec<_>>();
+    kern.radix_fft_many(&mut inputs, &omegas, &log_ns)
+        .expect("GPU FFT failed!");
+    assert!(values == expected);
+    assert!(budget.peak() <= budget.limit());
+    assert_eq!(budget.used(), 0);
+}
+
+#[test]
+pub fn gpu_fft_dimension_errors() {
+    fil_logger::maybe_init();
+
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+
+    let omega = omega::<Fr>(8);
+    let mut values = vec![Fr::ONE; 6];
+    let result = kern.radix_fft(&mut values, &omega, 3);
+    assert!(matches!(
+        result,
+        Err(EcError::DimensionMismatch {
+            expected: 8,
+            actual: 6
+        })
+    ));
+
+    let mut values = vec![Fr::ONE; 8];
+    let result = kern.radix_fft_many(&mut [&mut values], &[omega, omega], &[3]);
+    assert!(matches!(
+        result,
+        Err(EcError::DimensionMismatch {
+            expected: 1,
+            actual: 2
+        })
+    ));
+
+    let mut other = vec![Fr::ONE; 4];
+    let result = kern.radix_fft_many(
+        &mut [&mut values, &mut other],
+        &[omega, omega],
+        &[3, 3],
+    );
+    assert!(matches!(result, Err(EcError::InvalidLength(_))));
+    // Nothing was transformed.
+    assert!(values.iter().all(|x| *x == Fr::ONE));
+}
+
+#[test]
+pub fn gpu_fft_normal_form_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+
+    for log_d in 1..=12 {
+        let d = 1 << log_d;
+
+        let mut mont_coeffs =
+            (0..d).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+        // Store the canonical integers directly, without converting them into
+        // Montgomery form.
+        let mut normal_coeffs = mont_coeffs
+            .iter()
+            .map(|x| Fr::new_unchecked(x.into_bigint()))
+            .collect::<Vec<_>>();
+        let omega = omega::<Fr>(d);
+
+        kern.radix_fft_many(&mut [&mut mont_coeffs], &[omega], &[log_d])
+            .expect("GPU FFT failed!");
+        kern.radix_fft_many_with_form(
+            &mut [&mut normal_coeffs],
+            &[omega],
+            &[log_d],
+            InputForm::Normal,
+        )
+        .expect("GPU FFT failed!");
+
+        for (mont, normal) in mont_coeffs.iter().zip(normal_coeffs.iter()) {
+            assert_eq!(mont.into_bigint(), normal.0);
+        }
+    }
+}
+
+#[test]
+pub fn gpu_fft_pinned_framework() {
+    use ec_gpu_program::Framework;
+    use rust_gpu_tools::Program;
+
+    fil_logger::maybe_init();
+    build_fft();
+    let frameworks = [
+        #[cfg(feature = "cuda")]
+        Framework::Cuda,
+        #[cfg(feature = "opencl")]
+        Framework::Opencl,
+    ];
+    for device in Device::all() {
+        for framework in frameworks {
+            let supported = match framework {
+                #[cfg(feature = "cuda")]
+                Framework::Cuda => device.cuda_device().is_some(),
+                #[cfg(feature = "opencl")]
+                Framework::Opencl => device.opencl_device().is_some(),
+            };
+            match ec_gpu_program::load_program!(device, framework) {
+                Ok(program) => {
+                    assert!(supported);
+                    let framework_used = match program {
+                        #[cfg(feature = "cuda")]
+                        Program::Cuda(_) => Framework::Cuda,
+                        #[cfg(feature = "opencl")]
+                        Program::Opencl(_) => Framework::Opencl,
+                    };
+                    assert_eq!(framework_used, framework);
+                }
+                Err(_) => assert!(!supported),
+            }
+        }
+    }
+}
+
+#[test]
+pub fn gpu_fft_prepared_domains() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+
+    kern.prepare_domains(&[8, 10]).unwrap();
+    let generations = kern.twiddle_generations();
+
+    for log_d in [8, 10] {
+        let d = 1 << log_d;
+        let mut coeffs = (0..d).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+        let omega = Fr::get_root_of_unity(d as u64).unwrap();
+        kern.radix_fft(&mut coeffs, &omega, log_d).unwrap();
+    }
+    assert_eq!(kern.twiddle_generations(), generations);
+
+    // An unprepared size needs new twiddles.
+    let mut coeffs = (0..512).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+    kern.radix_fft(&mut coeffs, &omega::<Fr>(512), 9).unwrap();
+    assert_eq!(kern.twiddle_generations(), generations + 1);
+}
+
+#[test]
+pub fn gpu_batch_evaluate_at_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+
+    let polys = [0, 1, 255, 256, 257, 1000, 4096]
+        .iter()
+        .map(|&len| (0..len).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let poly_refs = polys.iter().map(|p| &p[..]).collect::<Vec<_>>();
+    let z = Fr::rand(&mut rng);
+
+    let gpu = kern.batch_evaluate_at(&poly_refs, z).unwrap();
+
+    let cpu = polys
+        .iter()
+        .map(|p| p.iter().rev().fold(Fr::from(0u64), |acc, c| acc * z + c))
+        .collect::<Vec<_>>();
+    assert_eq!(cpu, gpu);
+}
+
+#[test]
+pub fn gpu_fft_shared_mem_threshold() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+
+    for threshold in [0, 4, 8, 10] {
+        kern.set_shared_mem_threshold(Some(threshold));
+        // Sizes right below, at and above the threshold.
+        for log_d in threshold.saturating_sub(1)..=threshold + 1 {
+            let d = 1 << log_d;
+            let mut v1_coeffs =
+                (0..d).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+            let mut v2_coeffs = v1_coeffs.clone();
+            let omega = omega::<Fr>(d);
+
+            kern.radix_fft(&mut v1_coeffs, &omega, log_d)
+                .expect("GPU FFT failed!");
+            serial_fft::<Fr>(&mut v2_coeffs, &omega, log_d);
+
+            assert!(
+                v1_coeffs == v2_coeffs,
+                "mismatch for 2^{} elements with threshold {}",
+                log_d,
+                threshold
+            );
+        }
+    }
+}
+
+#[test]
+pub fn gpu_set_performance_mode() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    for device in devices.iter() {
+        for on in [true, false] {
+            match ec_gpu_program::set_performance_mode(device, on) {
+                Ok(())
+                | Err(EcError::PerformanceModeUnsupported(_))
+                | Err(EcError::PerformanceModeFailed { .. }) => {}
+                Err(err) => panic!("Unexpected error: {}", err),
+            }
+        }
+    }
+}
+
+#[test]
+pub fn gpu_list_devices() {
+    let devices = ec_gpu_program::list_devices();
+    assert!(!devices.is_empty(), "No GPU found");
+    assert_eq!(devices.len(), Device::all().len());
+    for info in devices {
+        assert!(info.memory > 0);
+        assert!(info.compute_units > 0);
+        assert!(info.cuda || info.opencl);
+    }
+}
+
+#[test]
+pub fn gpu_fft_strided_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+
+    // Covers the single round in local memory as well as several rounds.
+    for log_d in [1, 5, 8, 9, 12] {
+        let d = 1 << log_d;
+        let omega = omega::<Fr>(d);
+        for (offset, stride) in [(0, 1), (1, 3), (2, 4)] {
+            let mut interleaved = (0..offset + d * stride)
+                .map(|_| Fr::rand(&mut rng))
+                .collect::<Vec<_>>();
+            let mut packed = interleaved
+                .iter()
+                .skip(offset)
+                .step_by(stride)
+                .copied()
+                .collect::<Vec<_>>();
+            let untouched = interleaved.clone();
+
+            kern.radix_fft_strided(
+                &mut interleaved,
+                offset,
+                stride,
+                &omega,
+                log_d,
+            )
+            .expect("GPU FFT failed!");
+            kern.radix_fft(&mut packed, &omega, log_d)
+                .expect("GPU FFT failed!");
+
+            for (i, (actual, original)) in
+                interleaved.iter().zip(&untouched).enumerate()
+            {
+                if i >= offset && (i - offset) % stride == 0 {
+                    assert_eq!(*actual, packed[(i - offset) / stride]);
+                } else {
+                    assert_eq!(actual, original);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+pub fn gpu_fft_segmented_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+
+    // The larger sizes need several transfers, whose boundaries don't match
+    // the ones of the segments.
+    for log_d in [3, 10, 17] {
+        let d = 1 << log_d;
+        let omega = omega::<Fr>(d);
+        let mut concatenated =
+            (0..d).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+        let split = [1, d / 2 - 1, d / 2 + 3];
+        let mut first = concatenated[..split[0]].to_vec();
+        let mut second = concatenated[split[0]..split[1]].to_vec();
+        let mut empty = Vec::new();
+        let mut third = concatenated[split[1]..split[2]].to_vec();
+        let mut fourth = concatenated[split[2]..].to_vec();
+
+        kern.radix_fft_segmented(
+            &mut [
+                &mut first[..],
+                &mut second[..],
+                &mut empty[..],
+                &mut third[..],
+                &mut fourth[..],
+            ],
+            &omega,
+            log_d,
+        )
+        .expect("GPU FFT failed!");
+        kern.radix_fft(&mut concatenated, &omega, log_d)
+            .expect("GPU FFT failed!");
+
+        let segmented = [first, second, third, fourth].concat();
+        assert_eq!(segmented, concatenated);
+    }
+}
+
+#[test]
+pub fn gpu_fft_post_map_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+
+    let maps = [
+        FftPostMap::None,
+        FftPostMap::Square,
+        FftPostMap::Negate,
+        FftPostMap::AddConst(Fr::rand(&mut rng)),
+        FftPostMap::MulConst(Fr::rand(&mut rng)),
+    ];
+    // Small FFTs are done in local memory, larger ones in several rounds.
+    for log_d in [4, 12] {
+        let d = 1 << log_d;
+        let coeffs = (0..d).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+        let omega = omega::<Fr>(d);
+        let mut expected = coeffs.clone();
+        serial_fft::<Fr>(&mut expected, &omega, log_d);
+
+        for map in maps {
+            let mut gpu = coeffs.clone();
+            kern.radix_fft_with_map(&mut gpu, &omega, log_d, map)
+                .expect("GPU FFT failed!");
+            let cpu =
+                expected.iter().map(|x| map.apply(*x)).collect::<Vec<_>>();
+            assert_eq!(cpu, gpu, "{:?} with {} elements", map, d);
+        }
+    }
+}
+
+#[test]
+pub fn gpu_update_fft_consistency() {
+    use rand::Rng;
+
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+
+    let log_d = 12;
+    let d = 1 << log_d;
+    let omega = omega::<Fr>(d);
+    let mut coeffs = (0..d).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+    let mut evals = coeffs.clone();
+    serial_fft::<Fr>(&mut evals, &omega, log_d);
+
+    // Few changes are applied directly, many fall back to full FFTs. The
+    // index 0 and the last one are always changed.
+    for num_changes in [1, 3, 5, 50] {
+        let changes = (0..num_changes)
+            .map(|i| {
+                let index = match i {
+                    0 => 0,
+                    1 => d - 1,
+                    _ => rng.gen_range(0..d),
+                };
+                let old = coeffs[index];
+                let new = Fr::rand(&mut rng);
+                coeffs[index] = new;
+                (index, old, new)
+            })
+            .collect::<Vec<_>>();
+        kern.update_fft(&mut evals, &changes, &omega, log_d)
+            .expect("GPU FFT update failed!");
+
+        let mut expected = coeffs.clone();
+        serial_fft::<Fr>(&mut expected, &omega, log_d);
+        assert_eq!(expected, evals, "{} changes", num_changes);
+    }
+}
+
+#[test]
+pub fn gpu_fft_estimate_time() {
+    fil_logger::maybe_init();
+
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+
+    let check_estimates = |kern: &FftKernel<Fr>| {
+        let estimates = (1..=24)
+            .map(|log_d| kern.estimate_time(log_d))
+            .collect::<Vec<_>>();
+        assert!(estimates.iter().all(|estimate| !estimate.is_zero()));
+        assert!(estimates.windows(2).all(|pair| pair[0] <= pair[1]));
+    };
+    check_estimates(&kern);
+    kern.calibrate().expect("Calibration failed!");
+    check_estimates(&kern);
+}
+
+#[test]
+pub fn gpu_fft_verify_twiddles() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+    kern.set_verify_twiddles(true);
+
+    // Both the local memory and the radix kernel path.
+    for log_d in [4, 12] {
+        let d = 1 << log_d;
+        let coeffs = (0..d).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+
+        let mut v = coeffs.clone();
+        kern.radix_fft(&mut v, &omega::<Fr>(d), log_d)
+            .expect("GPU FFT failed!");
+
+        // A root of unity of twice the order is no primitive `d`-th root.
+        let wrong_omega = omega::<Fr>(2 * d);
+        let mut v = coeffs.clone();
+        assert!(matches!(
+            kern.radix_fft(&mut v, &wrong_omega, log_d),
+            Err(EcError::Simple(_))
+        ));
+
+        kern.set_verify_twiddles(false);
+        let mut v = coeffs.clone();
+        kern.radix_fft(&mut v, &wrong_omega, log_d)
+            .expect("GPU FFT failed!");
+        kern.set_verify_twiddles(true);
+    }
+}
+
+#[test]
+pub fn gpu_fft_2d_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+
+    // Square and non-square matrices, with dimensions below and above the
+    // threshold of the local memory FFT.
+    for (log_rows, log_cols) in [(4, 4), (3, 10), (10, 2), (0, 5)] {
+        let (rows, cols) = (1 << log_rows, 1 << log_cols);
+        let omega_row = omega::<Fr>(cols);
+        let omega_col = omega::<Fr>(rows);
+        let matrix = (0..rows * cols)
+            .map(|_| Fr::rand(&mut rng))
+            .collect::<Vec<_>>();
+
+        let mut gpu = matrix.clone();
+        kern.fft_2d(&mut gpu, rows, cols, &omega_row, &omega_col)
+            .expect("GPU FFT failed!");
+
+        let mut cpu = matrix;
+        for row in cpu.chunks_mut(cols) {
+            serial_fft::<Fr>(row, &omega_row, log_cols);
+        }
+        for col in 0..cols {
+            let mut column = (0..rows)
+                .map(|row| cpu[row * cols + col])
+                .collect::<Vec<_>>();
+            serial_fft::<Fr>(&mut column, &omega_col, log_rows);
+            for (row, value) in column.into_iter().enumerate() {
+                cpu[row * cols + col] = value;
+            }
+        }
+
+        assert!(cpu == gpu, "mismatch for {}x{}", rows, cols);
+    }
+
+    let mut matrix = vec![Fr::from(1u64); 3 * 4];
+    assert!(kern
+        .fft_2d(&mut matrix, 3, 4, &omega::<Fr>(4), &omega::<Fr>(4))
+        .is_err());
+}
+
+#[test]
+pub fn gpu_fft_distributed_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_fft();
+    let devices = Device::all();
+    let load = |devices: &[&Device]| {
+        let programs = devices
+            .iter()
+            .map(|&device| ec_gpu_program::load_program!(device))
+            .collect::<Result<_, _>>()
+            .expect("Cannot create programs!");
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!")
+    };
+    let mut single = load(&[&devices[0]]);
+    // Load the first device several times, so that the work is split even if
+    // there is only a single GPU.
+    let mut many = load(&[&devices[0], &devices[0], &devices[0]]);
+    let mut all = load(&devices.iter().collect::<Vec<_>>());
+
+    for log_d in [1, 2, 5, 8, 11, 14] {
+        let d = 1 << log_d;
+        let omega = omega::<Fr>(d);
+        let coeffs = (0..d).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+
+        let mut expected = coeffs.clone();
+        single
+            .radix_fft(&mut expected, &omega, log_d)
+            .expect("GPU FFT failed!");
+        let mut cpu = coeffs.clone();
+        serial_fft::<Fr>(&mut cpu, &omega, log_d);
+        assert!(cpu == expected, "single GPU mismatch for 2^{}", log_d);
+
+        for kern in [&mut single, &mut many, &mut all] {
+            let mut distributed = coeffs.clone();
+            kern.radix_fft_distributed(&mut distributed, &omega, log_d)
+                .expect("GPU FFT failed!");
+            assert!(
+                distributed == expected,
+                "mismatch for 2^{} on {} devices",
+                log_d,
+                kern.device_info().len()
+            );
+        }
+    }
+}
+
+#[test]
+pub fn gpu_fft_difference_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+
+    for log_d in [1, 4, 8, 12] {
+        let d = 1 << log_d;
+        let omega = omega::<Fr>(d);
+        let a = (0..d).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+        let b = (0..d).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+
+        let gpu = kern
+            .fft_difference(&a, &b, &omega, log_d)
+            .expect("GPU FFT failed!");
+
+        let mut fft_a = a.clone();
+        serial_fft::<Fr>(&mut fft_a, &omega, log_d);
+        let mut fft_b = b.clone();
+        serial_fft::<Fr>(&mut fft_b, &omega, log_d);
+        let cpu = fft_a
+            .iter()
+            .zip(fft_b.iter())
+            .map(|(a, b)| *a - b)
+            .collect::<Vec<_>>();
+        assert!(cpu == gpu, "mismatch for 2^{}", log_d);
+
+        assert!(!kern
+            .fft_equal_on_domain(&a, &b, &omega, log_d)
+            .expect("GPU FFT failed!"));
+        assert!(kern
+            .fft_equal_on_domain(&a, &a, &omega, log_d)
+            .expect("GPU FFT failed!"));
+    }
+}
+
+#[test]
+pub fn gpu_compute_quotient_consistency() {
+    use ark_ff::Field;
+
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+    let coset_gen = Fr::GENERATOR;
+
+    for (log_n, log_h) in [(2, 1), (4, 2), (8, 6), (12, 10), (12, 12)] {
+        let n = 1 << log_n;
+        let h = 1 << log_h;
+        let domain = QuotientDomain {
+            omega: omega::<Fr>(n),
+            log_n,
+            log_h,
+        };
+
+        // The numerator `q * Z_H` is divisible by `Z_H = X^|H| - 1`.
+        let quotient =
+            (0..n - h).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+        let mut numerator = vec![Fr::from(0u64); n];
+        for (i, q) in quotient.iter().enumerate() {
+            numerator[i + h] += q;
+            numerator[i] -= q;
+        }
+
+        let gpu = kern
+            .compute_quotient(&numerator, &domain, &coset_gen)
+            .expect("GPU quotient failed!");
+
+        let mut cpu = numerator.clone();
+        serial_coset_fft(&mut cpu, &domain.omega, &coset_gen, log_n);
+        let mut point = coset_gen;
+        for value in cpu.iter_mut() {
+            *value *= (point.pow([h as u64]) - Fr::ONE).inverse().unwrap();
+            point *= domain.omega;
+        }
+        serial_coset_ifft(&mut cpu, &domain.omega, &coset_gen, log_n);
+        assert!(cpu == gpu, "mismatch for 2^{} / 2^{}", log_n, log_h);
+        assert_eq!(&gpu[..n - h], &quotient[..]);
+        assert!(gpu[n - h..].iter().all(|c| *c == Fr::from(0u64)));
+    }
+
+    // The vanishing polynomial is zero on the subgroup itself.
+    let domain = QuotientDomain {
+        omega: omega::<Fr>(16),
+        log_n: 4,
+        log_h: 2,
+    };
+    assert!(kern
+        .compute_quotient(&[Fr::from(1u64)], &domain, &Fr::ONE)
+        .is_err());
+}
+
+#[test]
+pub fn gpu_fft_arkworks_consistency() {
+    use ark_poly::{
+        univariate::DensePolynomial, DenseUVPolynomial, EvaluationDomain,
+        Radix2EvaluationDomain,
+    };
+
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+
+    for log_d in [1, 4, 8, 12] {
+        let domain = Radix2EvaluationDomain::<Fr>::new(1 << log_d).unwrap();
+        // A polynomial may have fewer coefficients than the domain.
+        let polys = [1 << log_d, (1 << log_d) - 1, 1]
+            .iter()
+            .map(|&len| DensePolynomial::<Fr>::rand(len - 1, &mut rng))
+            .collect::<Vec<_>>();
+
+        let evals = kern
+            .fft_polynomials(&polys, domain)
+            .expect("GPU FFT failed!");
+        for (poly, evals) in polys.iter().zip(evals.iter()) {
+            assert_eq!(evals.domain(), domain);
+            assert_eq!(evals.evals, domain.fft(&poly.coeffs));
+        }
+
+        let interpolated =
+            kern.ifft_evaluations(&evals).expect("GPU IFFT failed!");
+        for ((poly, evals), gpu) in
+            polys.iter().zip(evals.iter()).zip(interpolated.iter())
+        {
+            assert_eq!(gpu, poly);
+            assert_eq!(gpu, &evals.clone().interpolate());
+        }
+    }
+
+    let coset = Radix2EvaluationDomain::<Fr>::new(16)
+        .unwrap()
+        .get_coset(Fr::GENERATOR)
+        .unwrap();
+    assert!(kern.fft_polynomials(&[], coset).is_err());
+}
+
+#[test]
+pub fn gpu_partial_fft_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+
+    for log_d in [1, 4, 10, 13] {
+        let d = 1 << log_d;
+        let omega = omega::<Fr>(d);
+        let coeffs = (0..d).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+        let mut full = coeffs.clone();
+        serial_fft::<Fr>(&mut full, &omega, log_d);
+
+        // Few indices are evaluated directly, many with a full FFT.
+        let few = [d - 1, 0, d / 2, d - 1];
+        let many = (0..2 * log_d as usize)
+            .map(|i| (i * 7919) % d)
+            .collect::<Vec<_>>();
+        for indices in [&few[..], &many[..], &[]] {
+            let gpu = kern
+                .partial_fft(&coeffs, &omega, log_d, indices)
+                .expect("GPU FFT failed!");
+            let expected = indices.iter().map(|i| full[*i]).collect::<Vec<_>>();
+            assert!(gpu == expected, "mismatch for 2^{}", log_d);
+        }
+    }
+}
+
+#[test]
+pub fn fft_ifft_shared_source() {
+    let fft = ag_build::SourceBuilder::new().add_fft::<Fr>();
+    let both = ag_build::SourceBuilder::new()
+        .add_fft::<Fr>()
+        .add_ifft::<Fr>();
+    let source = both.build_32_bit_limbs();
+    assert_eq!(source.matches("_radix_fft(GLOBAL").count(), 1);
+    assert_eq!(source, fft.build_32_bit_limbs());
+}
+
+#[test]
+pub fn gpu_ifft_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    generate(
+        &ag_build::SourceBuilder::new()
+            .add_fft::<Fr>()
+            .add_ifft::<Fr>(),
+    );
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+
+    for log_d in [1, 5, 12] {
+        let d = 1 << log_d;
+        let omega = omega::<Fr>(d);
+        let coeffs = (0..d).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+
+        let mut evals = coeffs.clone();
+        kern.radix_fft(&mut evals, &omega, log_d)
+            .expect("GPU FFT failed!");
+        let mut expected = coeffs.clone();
+        serial_fft::<Fr>(&mut expected, &omega, log_d);
+        assert!(evals == expected, "FFT mismatch for 2^{}", log_d);
+
+        kern.radix_ifft(&mut evals, &omega, log_d)
+            .expect("GPU inverse FFT failed!");
+        assert!(evals == coeffs, "inverse FFT mismatch for 2^{}", log_d);
+    }
+}
+
+#[test]
+pub fn gpu_fft_with_root_table() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+
+    let max_log_n = 12;
+    let roots = kern.precompute_roots(max_log_n).unwrap();
+    assert_eq!(roots.max_log_n(), max_log_n);
+    for log_n in 0..=max_log_n {
+        let omega = omega::<Fr>(1 << log_n);
+        assert_eq!(roots.omega(log_n), Some(omega));
+        assert_eq!(roots.omega_inv(log_n), omega.inverse());
+        assert_eq!(roots.size_inv(log_n), Fr::from(1u64 << log_n).inverse());
+    }
+    assert_eq!(roots.omega(max_log_n + 1), None);
+
+    for log_n in [1, 7, max_log_n] {
+        let d = 1 << log_n;
+        let coeffs = (0..d).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+
+        let mut evals = coeffs.clone();
+        kern.radix_fft_with_roots(&mut evals, &roots, log_n)
+            .expect("GPU FFT failed!");
+        let mut expected = coeffs.clone();
+        serial_fft::<Fr>(&mut expected, &omega::<Fr>(d), log_n);
+        assert!(evals == expected, "FFT mismatch for 2^{}", log_n);
+
+        kern.radix_ifft_with_roots(&mut evals, &roots, log_n)
+            .expect("GPU inverse FFT failed!");
+        assert!(evals == coeffs, "inverse FFT mismatch for 2^{}", log_n);
+    }
+
+    let mut too_large = vec![Fr::ONE; 1 << (max_log_n + 1)];
+    assert!(kern
+        .radix_fft_with_roots(&mut too_large, &roots, max_log_n + 1)
+        .is_err());
+    assert!(kern.precompute_roots(Fr::TWO_ADICITY + 1).is_err());
+}
+
+#[test]
+pub fn gpu_fft_cpu_threshold() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+    kern.set_cpu_threshold(6);
+
+    for (log_d, cpu_ffts) in [(3, 1), (6, 1), (10, 1)] {
+        let d = 1 << log_d;
+        let omega = omega::<Fr>(d);
+        let coeffs = (0..d).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+
+        let mut evals = coeffs.clone();
+        kern.radix_fft(&mut evals, &omega, log_d)
+            .expect("FFT failed!");
+        let mut expected = coeffs.clone();
+        serial_fft::<Fr>(&mut expected, &omega, log_d);
+        assert!(evals == expected, "FFT mismatch for 2^{}", log_d);
+        assert_eq!(kern.cpu_ffts(), cpu_ffts);
+    }
+
+    // The small FFT of a batch is done on the CPU as well, the inverse FFT
+    // of the small size too.
+    let mut small = (0..16).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+    let mut large = (0..1024).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+    let (small_coeffs, large_coeffs) = (small.clone(), large.clone());
+    kern.radix_fft_many(
+        &mut [&mut small, &mut large],
+        &[omega::<Fr>(16), omega::<Fr>(1024)],
+        &[4, 10],
+    )
+    .expect("FFT failed!");
+    assert_eq!(kern.cpu_ffts(), 2);
+    kern.radix_ifft(&mut small, &omega::<Fr>(16), 4)
+        .expect("inverse FFT failed!");
+    kern.radix_ifft(&mut large, &omega::<Fr>(1024), 10)
+        .expect("inverse FFT failed!");
+    assert_eq!(kern.cpu_ffts(), 3);
+    assert!(small == small_coeffs);
+    assert!(large == large_coeffs);
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+pub fn gpu_fft_metrics() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+
+    let count = |metrics: &str| -> u64 {
+        let prefix = format!(
+            "ec_gpu_operation_duration_seconds_count{{operation=\"fft\",\
+             device=\"{}\"}} ",
+            devices[0].name()
+        );
+        metrics
+            .lines()
+            .find_map(|line| line.strip_prefix(&prefix))
+            .map_or(0, |count| count.parse().unwrap())
+    };
+    let before = count(&ec_gpu_proxy::gather_metrics());
+
+    let log_d = 10;
+    let mut coeffs = (0..1 << log_d)
+        .map(|_| Fr::rand(&mut rng))
+        .collect::<Vec<_>>();
+    kern.radix_fft(&mut coeffs, &omega::<Fr>(1 << log_d), log_d)
+        .expect("GPU FFT failed!");
+
+    let metrics = ec_gpu_proxy::gather_metrics();
+    // Other tests may run FFTs at the same time.
+    assert!(count(&metrics) > before);
+    assert!(metrics.contains("# TYPE ec_gpu_operation_size histogram\n"));
+}
+
+#[test]
+pub fn gpu_reset_device() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_fft();
+    let devices = Device::all();
+    let programs: Vec<SharedProgram> = devices
+        .iter()
+        .map(|device| {
+            ec_gpu_program::load_program!(device).map(SharedProgram::new)
+        })
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = FftKernel::<Fr>::create_shared(programs.clone())
+        .expect("Cannot initialize kernel!");
+
+    // A lost device can't be provoked reliably, but resetting a healthy one
+    // takes the same path.
+    for (program, device) in programs.iter().zip(&devices) {
+        assert!(!program.is_device_lost());
+        let rebuilt = ec_gpu_program::load_program!(device).unwrap();
+        program.reset_device(rebuilt).unwrap();
+        assert!(!program.is_device_lost());
+    }
+
+    let log_d = 10;
+    let omega = omega::<Fr>(1 << log_d);
+    let coeffs = (0..1 << log_d)
+        .map(|_| Fr::rand(&mut rng))
+        .collect::<Vec<_>>();
+    let mut gpu = coeffs.clone();
+    kern.radix_fft(&mut gpu, &omega, log_d)
+        .expect("GPU FFT failed!");
+    let mut cpu = coeffs;
+    serial_fft::<Fr>(&mut cpu, &omega, log_d);
+    assert!(gpu == cpu);
+}
+
+/// Creates a kernel, the programs it is created from go out of scope.
+fn create_fft_kernel() -> FftKernel<'static, Fr> {
+    let programs = Device::all()
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Cannot create programs!");
+    FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!")
+}
+
+#[test]
+pub fn gpu_fft_kernel_owns_programs() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_fft();
+    let mut kern = create_fft_kernel();
+
+    let log_d = 10;
+    let mut coeffs = (0..1 << log_d)
+        .map(|_| Fr::rand(&mut rng))
+        .collect::<Vec<_>>();
+    let mut expected = coeffs.clone();
+    let omega = omega::<Fr>(coeffs.len());
+
+    kern.radix_fft_many(&mut [&mut coeffs], &[omega], &[log_d])
+        .expect("GPU FFT failed!");
+    serial_fft::<Fr>(&mut expected, &omega, log_d);
+    assert_eq!(coeffs, expected);
+}
+
+#[test]
+pub fn gpu_fft_coset_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+
+    // Small FFTs are done in local memory, larger ones in several rounds.
+    for log_d in [1, 5, 10, 16] {
+        let d = 1 << log_d;
+        let num_polys = 4;
+        let original = (0..num_polys)
+            .map(|_| (0..d).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let omegas = vec![omega::<Fr>(d); num_polys];
+        let log_ns = vec![log_d; num_polys];
+        let mut shifts = (0..num_polys - 1)
+            .map(|_| Fr::rand(&mut rng))
+            .collect::<Vec<_>>();
+        shifts.push(Fr::ONE);
+
+        let mut values = original.clone();
+        let mut inputs =
+            values.iter_mut().map(|v| &mut v[..]).collect::<Vec<_>>();
+        kern.radix_fft_coset_many(&mut inputs, &omegas, &log_ns, &shifts)
+            .expect("GPU FFT failed!");
+        for ((coeffs, evals), shift) in
+            original.iter().zip(&values).zip(&shifts)
+        {
+            let mut expected = coeffs.clone();
+            serial_coset_fft(&mut expected, &omegas[0], shift, log_d);
+            assert!(*evals == expected, "mismatch for 2^{}", log_d);
+        }
+
+        // A shift of one is the same as the FFT over the subgroup.
+        let mut plain = original[num_polys - 1].clone();
+        kern.radix_fft_many(&mut [&mut plain], &omegas[..1], &log_ns[..1])
+            .expect("GPU FFT failed!");
+        assert!(plain == values[num_polys - 1]);
+
+        let mut inputs =
+            values.iter_mut().map(|v| &mut v[..]).collect::<Vec<_>>();
+        kern.radix_ifft_coset_many(&mut inputs, &omegas, &log_ns, &shifts)
+            .expect("GPU FFT failed!");
+        assert!(values == original, "mismatch for 2^{}", log_d);
+    }
+
+    let mut values = vec![Fr::ONE; 4];
+    let result = kern.radix_fft_coset_many(
+        &mut [&mut values],
+        &[omega::<Fr>(4)],
+        &[2],
+        &[],
+    );
+    assert!(matches!(
+        result,
+        Err(EcError::DimensionMismatch {
+            expected: 1,
+            actual: 0
+        })
+    ));
+
+    // A zero root of unity has no inverse.
+    let result = kern.radix_ifft_coset_many(
+        &mut [&mut values],
+        &[Fr::ZERO],
+        &[2],
+        &[Fr::GENERATOR],
+    );
+    assert!(matches!(result, Err(EcError::Simple(_))));
+}
+
+#[test]
+pub fn gpu_fold_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    build_fft();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+
+    for n in [1, 1000, 1 << 16] {
+        let mut a = (0..n).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+        let b = (0..n).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+        let r = Fr::rand(&mut rng);
+        let expected = a
+            .iter()
+            .zip(&b)
+            .map(|(a, b)| *a + r * b)
+            .collect::<Vec<_>>();
+        kern.fold(&mut a, &b, r).expect("GPU fold failed!");
+        assert!(a == expected, "mismatch for {} elements", n);
+    }
+
+    let mut a = vec![Fr::ONE; 4];
+    let result = kern.fold(&mut a, &[Fr::ONE; 3], Fr::ONE);
+    assert!(matches!(
+        result,
+        Err(EcError::DimensionMismatch {
+            expected: 4,
+            actual: 3
+        })
+    ));
+}
@@ -0,0 +1,46 @@
+#![cfg(any(feature = "cuda", feature = "opencl"))]
+
+use ag_build::{self, generate};
+use ark_bls12_381::Fr;
+use ark_ff::FftField;
+use ark_std::UniformRand;
+use ec_gpu_proxy::{fft::FftKernel, fft_cpu::serial_fft};
+use rust_gpu_tools::Device;
+
+#[test]
+pub fn gpu_fft_shared_twiddles_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    generate(
+        &ag_build::SourceBuilder::new()
+            .add_fft::<Fr>()
+            .fft_shared_twiddles(true),
+    );
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern =
+        FftKernel::<Fr>::create(programs).expect("Cannot initialize kernel!");
+    // Always use the iterative FFT, which is the one that caches twiddles.
+    kern.set_shared_mem_threshold(None);
+
+    for log_d in 1..=16 {
+        let d = 1 << log_d;
+        let omega = Fr::get_root_of_unity(d).unwrap();
+        let coeffs = (0..d).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+        let mut expected = coeffs.clone();
+        serial_fft::<Fr>(&mut expected, &omega, log_d);
+
+        for shared_twiddles in [false, true] {
+            kern.set_shared_twiddles(shared_twiddles);
+            let mut v = coeffs.clone();
+            kern.radix_fft(&mut v, &omega, log_d)
+                .expect("GPU FFT failed!");
+            assert!(v == expected, "shared_twiddles: {}", shared_twiddles);
+        }
+    }
+}
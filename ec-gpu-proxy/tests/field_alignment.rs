@@ -0,0 +1,77 @@
+#![cfg(any(feature = "cuda", feature = "opencl"))]
+
+use std::sync::Arc;
+
+use ag_build::{self, generate};
+use ag_types::PrimeFieldRepr;
+use ark_bls12_381::{Fr, G1Affine};
+use ark_ec::CurveGroup;
+use ark_ff::FftField;
+use ark_std::UniformRand;
+use ec_gpu_proxy::{
+    fft::FftKernel,
+    fft_cpu::serial_fft,
+    multiexp::MultiexpKernel,
+    multiexp_cpu::{multiexp_cpu, FullDensity},
+    threadpool::Worker,
+};
+use rust_gpu_tools::Device;
+
+#[test]
+fn gpu_field_alignment_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+
+    // 16 bytes divide the 32 bytes of `Fr` as well as the 48 bytes of `Fq`.
+    generate(
+        &ag_build::SourceBuilder::new()
+            .add_fft::<Fr>()
+            .add_multiexp::<G1Affine>()
+            .field_alignment(16),
+    );
+    let devices = Device::all();
+    let load_programs = || {
+        devices
+            .iter()
+            .map(|device| ec_gpu_program::load_program!(device))
+            .collect::<Result<Vec<_>, _>>()
+            .expect("Cannot create programs!")
+    };
+
+    let mut fft_kern = FftKernel::<Fr>::create(load_programs())
+        .expect("Cannot initialize kernel!");
+    for log_d in [1, 8, 12] {
+        let d = 1 << log_d;
+        let omega = Fr::get_root_of_unity(d).unwrap();
+        let mut gpu = (0..d).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+        let mut cpu = gpu.clone();
+        fft_kern
+            .radix_fft(&mut gpu, &omega, log_d)
+            .expect("GPU FFT failed!");
+        serial_fft::<Fr>(&mut cpu, &omega, log_d);
+        assert!(gpu == cpu, "log_d = {}", log_d);
+    }
+
+    let mut multiexp_kern =
+        MultiexpKernel::<G1Affine>::create(load_programs(), &devices)
+            .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let num_terms = 1000;
+    let bases = Arc::new(
+        (0..num_terms)
+            .map(|_| G1Affine::rand(&mut rng))
+            .collect::<Vec<_>>(),
+    );
+    let exps = Arc::new(
+        (0..num_terms)
+            .map(|_| Fr::rand(&mut rng).to_repr())
+            .collect::<Vec<_>>(),
+    );
+    let gpu = multiexp_kern
+        .multiexp(&pool, bases.clone(), exps.clone(), 0)
+        .unwrap();
+    let cpu = multiexp_cpu(&pool, (bases, 0), FullDensity, exps)
+        .wait()
+        .unwrap();
+    assert_eq!(cpu.into_affine(), gpu.into_affine());
+}
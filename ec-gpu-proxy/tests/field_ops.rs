@@ -0,0 +1,318 @@
+#![cfg(any(feature = "cuda", feature = "opencl"))]
+
+use std::collections::HashMap;
+
+use ag_build::{self, generate};
+use ark_bls12_381::Fr;
+use ark_ff::{BigInt, Field, PrimeField};
+use ark_serialize::CanonicalSerialize;
+use ark_std::UniformRand;
+use ec_gpu_program::EcError;
+use ec_gpu_proxy::{
+    field_ops::{FieldCodec, FieldOps, GateExpr, SingleFieldOpsKernel},
+    SharedProgram,
+};
+use rand::Rng;
+use rust_gpu_tools::Device;
+
+fn build_field_ops() {
+    generate(
+        &ag_build::SourceBuilder::new()
+            .add_field_ops::<Fr>()
+            .add_field_bytes::<Fr>(),
+    )
+}
+
+fn create_field_ops() -> FieldOps<'static, Fr> {
+    build_field_ops();
+    let devices = Device::all();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    FieldOps::<Fr>::create(programs).expect("Cannot initialize kernel!")
+}
+
+#[test]
+pub fn gpu_pow_many_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+    let mut kern = create_field_ops();
+
+    let bases = (0..1000).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+    let exp = Fr::rand(&mut rng).into_bigint();
+
+    let gpu = kern.pow_many(&bases, exp).unwrap();
+    let cpu = bases.iter().map(|b| b.pow(exp)).collect::<Vec<_>>();
+    assert_eq!(cpu, gpu);
+
+    let gpu = kern.pow_many(&bases, BigInt::zero()).unwrap();
+    assert!(gpu.iter().all(|x| *x == Fr::ONE));
+}
+
+#[test]
+pub fn gpu_segmented_sum_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+    let mut kern = create_field_ops();
+
+    let num_keys = 100;
+    let n = 10_000;
+    let values = (0..n).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+    // Key 0 gets many values, so that it is split into several chunks. The
+    // last key never gets any.
+    let keys = (0..n)
+        .map(|i| {
+            if i % 2 == 0 {
+                0
+            } else {
+                rng.gen_range(0..num_keys - 1)
+            }
+        })
+        .collect::<Vec<u32>>();
+
+    let gpu = kern
+        .segmented_sum(&values, &keys, num_keys as usize)
+        .unwrap();
+
+    let mut grouped = HashMap::new();
+    for (value, key) in values.iter().zip(&keys) {
+        *grouped.entry(*key).or_insert(Fr::ZERO) += value;
+    }
+    let cpu = (0..num_keys)
+        .map(|key| grouped.get(&key).copied().unwrap_or(Fr::ZERO))
+        .collect::<Vec<_>>();
+    assert_eq!(cpu, gpu);
+
+    assert!(kern.segmented_sum(&values, &keys, 10).is_err());
+}
+
+#[test]
+pub fn gpu_eval_gate_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+    let mut kern = create_field_ops();
+
+    let col = |i| Box::new(GateExpr::Column(i));
+    let coeff = |i| Box::new(GateExpr::Coeff(i));
+    let mul = |a, b| Box::new(GateExpr::Mul(a, b));
+    let add = |a, b| Box::new(GateExpr::Add(a, b));
+    // A standard PLONK gate: q_l * a + q_r * b + q_m * a * b - q_o * c + q_c
+    let plonk = GateExpr::Add(
+        add(
+            add(mul(coeff(0), col(0)), mul(coeff(1), col(1))),
+            mul(mul(coeff(2), col(0)), col(1)),
+        ),
+        Box::new(GateExpr::Sub(coeff(4), mul(coeff(3), col(2)))),
+    );
+    // A range check of a column to the values 0..4, as a product of
+    // differences.
+    let range = GateExpr::Mul(
+        mul(col(0), Box::new(GateExpr::Sub(col(0), coeff(0)))),
+        mul(
+            Box::new(GateExpr::Sub(col(0), coeff(1))),
+            Box::new(GateExpr::Sub(col(0), coeff(2))),
+        ),
+    );
+    let small = (1..=3).map(|i| Fr::from(i as u64)).collect::<Vec<_>>();
+
+    let n = 1000;
+    let columns = (0..3)
+        .map(|_| (0..n).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let columns = columns.iter().map(Vec::as_slice).collect::<Vec<_>>();
+    let coeffs = (0..5).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+
+    for (gate, coeffs) in [(&plonk, &coeffs), (&range, &small)] {
+        let gpu = kern.eval_gate(gate, &columns, coeffs).unwrap();
+        let cpu = (0..n)
+            .map(|row| gate.evaluate(&columns, coeffs, row))
+            .collect::<Vec<_>>();
+        assert_eq!(cpu, gpu);
+    }
+
+    // The range check is zero within the range.
+    let in_range = (0..n).map(|i| Fr::from(i as u64 % 4)).collect::<Vec<_>>();
+    let gpu = kern.eval_gate(&range, &[&in_range[..]], &small).unwrap();
+    assert!(gpu.iter().all(|x| *x == Fr::ZERO));
+
+    assert!(kern.eval_gate(&plonk, &columns[..2], &coeffs).is_err());
+    assert!(kern.eval_gate(&plonk, &columns, &coeffs[..4]).is_err());
+    let uneven = [columns[0], columns[1], &columns[2][1..]];
+    assert!(kern.eval_gate(&plonk, &uneven, &coeffs).is_err());
+}
+
+#[test]
+pub fn gpu_degree_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+    let mut kern = create_field_ops();
+
+    // The number of trailing zeros, from none to all, across several chunks.
+    let n = 1000;
+    for zeros in [0, 1, 255, 256, 257, 700, 999, 1000] {
+        let mut coeffs = (0..n).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+        for coeff in coeffs[n - zeros..].iter_mut() {
+            *coeff = Fr::ZERO;
+        }
+        // Zeros in the middle don't affect the degree.
+        if zeros < n - 10 {
+            coeffs[n - zeros - 5] = Fr::ZERO;
+        }
+
+        let cpu = coeffs.iter().rposition(|coeff| *coeff != Fr::ZERO);
+        assert_eq!(kern.degree(&coeffs).unwrap(), cpu, "zeros = {}", zeros);
+    }
+
+    assert_eq!(kern.degree(&[]).unwrap(), None);
+    assert_eq!(kern.degree(&[Fr::ONE]).unwrap(), Some(0));
+}
+
+#[test]
+pub fn gpu_batch_invert_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+    let mut kern = create_field_ops();
+
+    // More than one chunk, with a zero in the middle of one.
+    let mut values = (0..1000).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+    values[300] = Fr::ZERO;
+    let original = values.clone();
+
+    kern.batch_invert(&mut values).unwrap();
+    assert_eq!(values[300], Fr::ZERO);
+    for (i, (inverse, value)) in values.iter().zip(&original).enumerate() {
+        if i != 300 {
+            assert_eq!(*inverse * value, Fr::ONE, "mismatch at {}", i);
+        }
+    }
+}
+
+#[test]
+pub fn gpu_field_bytes_round_trip() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+    build_field_ops();
+    let device = *Device::all().first().expect("Cannot get a default device");
+    let program =
+        ec_gpu_program::load_program!(device).expect("Cannot create program!");
+    let mut codec =
+        FieldCodec::<Fr>::create(program, None).expect("Cannot create codec!");
+
+    let mut values = (0..1000).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+    values.extend([Fr::ZERO, Fr::ONE, -Fr::ONE]);
+    let mut expected = Vec::new();
+    for value in &values {
+        value.serialize_compressed(&mut expected).unwrap();
+    }
+    assert_eq!(
+        expected.len(),
+        values.len() * FieldCodec::<Fr>::bytes_per_element()
+    );
+
+    let values_gpu = codec.upload(&values).unwrap();
+    let bytes_gpu = codec.to_bytes(values_gpu).unwrap();
+    let bytes = codec.download(bytes_gpu).unwrap();
+    assert_eq!(expected, bytes);
+
+    let bytes_gpu = codec.upload(&bytes).unwrap();
+    let values_gpu = codec.from_bytes(bytes_gpu).unwrap();
+    assert_eq!(values, codec.download(values_gpu).unwrap());
+
+    // Encodings of integers that are not smaller than the modulus are
+    // rejected.
+    let invalid = vec![0xff; FieldCodec::<Fr>::bytes_per_element()];
+    let invalid_gpu = codec.upload(&invalid).unwrap();
+    assert!(codec.from_bytes(invalid_gpu).is_err());
+}
+
+#[test]
+pub fn gpu_transpose_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+    let mut kern = create_field_ops();
+
+    // Square and non-square matrices, with dimensions that are not multiples
+    // of the tile size.
+    for (rows, cols) in [(1, 1), (1, 37), (16, 16), (3, 5), (17, 33), (64, 48)]
+    {
+        let matrix = (0..rows * cols)
+            .map(|_| Fr::rand(&mut rng))
+            .collect::<Vec<_>>();
+        let mut expected = vec![Fr::ZERO; rows * cols];
+        for row in 0..rows {
+            for col in 0..cols {
+                expected[col * rows + row] = matrix[row * cols + col];
+            }
+        }
+
+        let matrix_gpu = kern.upload(&matrix).unwrap();
+        let transposed_gpu = kern.transpose(matrix_gpu, rows, cols).unwrap();
+        let transposed = kern.download(transposed_gpu).unwrap();
+        assert!(transposed == expected, "mismatch for {}x{}", rows, cols);
+    }
+
+    let matrix_gpu = kern.upload(&[Fr::ONE; 6]).unwrap();
+    assert!(kern.transpose(matrix_gpu, 2, 4).is_err());
+}
+
+#[test]
+pub fn gpu_constants_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+    let mut kern = create_field_ops();
+
+    let constants = kern.constants().unwrap();
+    // The host representation of an element `x` is `x * R`, with `R = 2^256`.
+    let r = Fr::from(2u64).pow([256u64]);
+    assert_eq!(constants.zero, Fr::ZERO);
+    assert_eq!(constants.one, Fr::ONE);
+    assert_eq!(constants.one_normal, r.inverse().unwrap());
+    assert_eq!(constants.r2, r);
+
+    // The GPU's one is the identity of its multiplication, the normal one
+    // converts out of Montgomery form instead.
+    let values = (0..1000).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+    let gate = GateExpr::Mul(
+        Box::new(GateExpr::Coeff(0)),
+        Box::new(GateExpr::Column(0)),
+    );
+    let times_one = kern.eval_gate(&gate, &[&values[..]], &[constants.one]);
+    assert_eq!(times_one.unwrap(), values);
+    let times_one_normal = kern
+        .eval_gate(&gate, &[&values[..]], &[constants.one_normal])
+        .unwrap();
+    for (value, unmont) in values.iter().zip(times_one_normal) {
+        assert_eq!(unmont, *value * constants.one_normal);
+        // The bits of the result are the integer in normal form.
+        assert_eq!(unmont.0, value.into_bigint());
+    }
+}
+
+#[test]
+pub fn gpu_stale_buffer_after_reset() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+    build_field_ops();
+    let device = *Device::all().first().expect("No GPU found!");
+    let program = SharedProgram::new(
+        ec_gpu_program::load_program!(device).expect("Cannot create program!"),
+    );
+    let mut kern = SingleFieldOpsKernel::<Fr>::create(program.clone(), None)
+        .expect("Cannot initialize kernel!");
+
+    let data = (0..100).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+    let buffer = kern.upload(&data).unwrap();
+    assert_eq!(kern.download(buffer).unwrap(), data);
+
+    // The buffer belongs to the old context, it must not be used after the
+    // reset, new buffers work as before.
+    let stale = kern.upload(&data).unwrap();
+    program
+        .reset_device(ec_gpu_program::load_program!(device).unwrap())
+        .unwrap();
+    assert!(matches!(kern.download(stale), Err(EcError::Simple(_))));
+    let buffer = kern.upload(&data).unwrap();
+    assert_eq!(kern.download(buffer).unwrap(), data);
+}
@@ -0,0 +1,85 @@
+#![cfg(any(feature = "cuda", feature = "opencl"))]
+
+use ag_build::{self, generate};
+use ark_ff::{
+    fields::{Fp64, MontBackend, MontConfig},
+    FftField, Field, PrimeField,
+};
+use ark_std::UniformRand;
+use ec_gpu_proxy::{fft::FftKernel, fft_cpu::serial_fft, field_ops::FieldOps};
+use rust_gpu_tools::{Device, Program};
+
+#[derive(MontConfig)]
+#[modulus = "18446744069414584321"]
+#[generator = "7"]
+pub struct GoldilocksConfig;
+/// The Goldilocks field `2^64 - 2^32 + 1`.
+pub type Goldilocks = Fp64<MontBackend<GoldilocksConfig, 1>>;
+
+fn load_programs() -> Vec<Program> {
+    generate(
+        &ag_build::SourceBuilder::new()
+            .add_fft::<Goldilocks>()
+            .add_field_ops::<Goldilocks>(),
+    );
+    let devices = Device::all();
+    devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!")
+}
+
+#[test]
+pub fn gpu_goldilocks_fft_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+    let mut kern = FftKernel::<Goldilocks>::create(load_programs())
+        .expect("Cannot initialize kernel!");
+
+    // Covers the local memory as well as the iterative FFT.
+    for log_d in 1..=14 {
+        let d = 1 << log_d;
+        let omega = Goldilocks::get_root_of_unity(d).unwrap();
+        let mut v1_coeffs = (0..d)
+            .map(|_| Goldilocks::rand(&mut rng))
+            .collect::<Vec<_>>();
+        let mut v2_coeffs = v1_coeffs.clone();
+
+        kern.radix_fft(&mut v1_coeffs, &omega, log_d)
+            .expect("GPU FFT failed!");
+        serial_fft::<Goldilocks>(&mut v2_coeffs, &omega, log_d);
+
+        assert!(v1_coeffs == v2_coeffs);
+    }
+}
+
+#[test]
+pub fn gpu_goldilocks_field_ops_consistency() {
+    fil_logger::maybe_init();
+    let mut rng = rand::thread_rng();
+    let mut kern = FieldOps::<Goldilocks>::create(load_programs())
+        .expect("Cannot initialize kernel!");
+
+    let mut bases = (0..1000)
+        .map(|_| Goldilocks::rand(&mut rng))
+        .collect::<Vec<_>>();
+    // Values at the edges of the reduction.
+    bases.extend([
+        Goldilocks::ZERO,
+        Goldilocks::ONE,
+        -Goldilocks::ONE,
+        Goldilocks::from(u32::MAX),
+        -Goldilocks::from(u32::MAX),
+    ]);
+
+    for exp in [
+        Goldilocks::rand(&mut rng).into_bigint(),
+        (-Goldilocks::ONE).into_bigint(),
+        Goldilocks::from(2u64).into_bigint(),
+    ] {
+        let gpu = kern.pow_many(&bases, exp).unwrap();
+        let cpu = bases.iter().map(|b| b.pow(exp)).collect::<Vec<_>>();
+        assert_eq!(cpu, gpu);
+    }
+}
@@ -0,0 +1,66 @@
+#![cfg(any(feature = "cuda", feature = "opencl"))]
+
+use ag_build::{self, generate};
+use ark_bls12_381::Fr;
+use ec_gpu_program::{
+    initialize_devices, list_devices, select_devices_by_index,
+    select_devices_by_name, select_devices_with_min_memory, DeviceSelection,
+    EcError,
+};
+use rust_gpu_tools::Device;
+
+#[test]
+fn gpu_initialize_devices() {
+    fil_logger::maybe_init();
+    generate(&ag_build::SourceBuilder::new().add_fft::<Fr>());
+
+    let report = initialize_devices(|device, framework| {
+        ec_gpu_program::load_program!(device, framework)
+    });
+    let failures = report.failures().collect::<Vec<_>>();
+    assert!(failures.is_empty(), "{:?}", failures);
+    assert!(report.is_ok());
+
+    // Every device is initialized with at least one backend.
+    for device in list_devices() {
+        assert!(report
+            .statuses
+            .iter()
+            .any(|status| status.device.name == device.name));
+    }
+}
+
+#[test]
+fn gpu_select_devices() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    let first = devices[0];
+
+    let selected = select_devices_by_name(&first.name()).unwrap();
+    assert!(selected.iter().any(|device| device.name() == first.name()));
+    assert!(selected
+        .iter()
+        .all(|device| device.name().contains(&first.name())));
+
+    let selected = select_devices_by_index(&[0]).unwrap();
+    assert_eq!(selected.len(), 1);
+    assert_eq!(selected[0].unique_id(), first.unique_id());
+
+    let selected = DeviceSelection::new()
+        .pci_ids(&[first.pci_id()])
+        .min_memory(first.memory())
+        .select()
+        .unwrap();
+    assert!(selected
+        .iter()
+        .any(|device| device.unique_id() == first.unique_id()));
+
+    // No match and invalid indices are errors instead of falling back to
+    // other devices.
+    let result = select_devices_by_name("no such device");
+    assert!(matches!(result, Err(EcError::Simple(_))));
+    let result = select_devices_with_min_memory(u64::MAX);
+    assert!(matches!(result, Err(EcError::Simple(_))));
+    let result = select_devices_by_index(&[devices.len()]);
+    assert!(matches!(result, Err(EcError::Simple(_))));
+}
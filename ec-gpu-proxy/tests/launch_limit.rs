@@ -0,0 +1,75 @@
+#![cfg(any(feature = "cuda", feature = "opencl"))]
+
+use ag_build::{self, generate};
+use ark_bls12_381::Fr;
+use ark_ff::{FftField, Field};
+use ark_std::UniformRand;
+use ec_gpu_proxy::{
+    fft::FftKernel, fft_cpu::serial_fft, max_concurrent_launches,
+    peak_concurrent_launches, set_max_concurrent_launches, threadpool::Worker,
+};
+use rust_gpu_tools::Device;
+
+const NUM_KERNELS: usize = 4;
+const LOG_D: u32 = 12;
+
+#[test]
+fn gpu_launches_are_limited() {
+    fil_logger::maybe_init();
+    generate(&ag_build::SourceBuilder::new().add_fft::<Fr>());
+    let devices = Device::all();
+
+    // Independent kernels, each with its own programs, like they would be
+    // created by different parts of an application.
+    let mut kernels = (0..NUM_KERNELS)
+        .map(|_| {
+            let programs = devices
+                .iter()
+                .map(|device| ec_gpu_program::load_program!(device))
+                .collect::<Result<_, _>>()
+                .expect("Cannot create programs!");
+            FftKernel::<Fr>::create(programs)
+                .expect("Cannot initialize kernel!")
+        })
+        .collect::<Vec<_>>();
+
+    let mut omega = Fr::TWO_ADIC_ROOT_OF_UNITY;
+    for _ in LOG_D..Fr::TWO_ADICITY {
+        omega = omega.square();
+    }
+    let mut rng = rand::thread_rng();
+    let inputs = (0..NUM_KERNELS)
+        .map(|_| (0..1 << LOG_D).map(|_| Fr::rand(&mut rng)).collect())
+        .collect::<Vec<Vec<_>>>();
+
+    assert_eq!(max_concurrent_launches(), usize::MAX);
+    set_max_concurrent_launches(1);
+    assert_eq!(max_concurrent_launches(), 1);
+
+    let mut outputs = inputs.clone();
+    Worker::new().scoped(|s| {
+        for ((kern, input), output) in kernels
+            .iter_mut()
+            .zip(inputs.iter())
+            .zip(outputs.iter_mut())
+        {
+            s.execute(move || {
+                for _ in 0..10 {
+                    output.copy_from_slice(input);
+                    kern.radix_fft(output, &omega, LOG_D)
+                        .expect("GPU FFT failed!");
+                }
+            });
+        }
+    });
+
+    // The launches were serialized, but still computed the right values.
+    assert_eq!(peak_concurrent_launches(), 1);
+    for (input, output) in inputs.iter().zip(outputs) {
+        let mut expected = input.clone();
+        serial_fft(&mut expected, &omega, LOG_D);
+        assert_eq!(expected, output);
+    }
+
+    set_max_concurrent_launches(usize::MAX);
+}
@@ -1,18 +1,37 @@
 #![cfg(any(feature = "cuda", feature = "opencl"))]
 
-use std::{sync::Arc, time::Instant};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
 
 use ag_build::{self, generate};
 use ag_types::{GpuCurveAffine, PrimeFieldRepr};
-use ark_bls12_381::{Fr, G1Affine};
-use ark_ec::CurveGroup;
-use ark_ff::UniformRand;
-use ec_gpu_program::EcError;
+use ark_bls12_381::{Fq, Fr, G1Affine, G1Projective};
+use ark_ec::{CurveGroup, Group};
+use ark_ff::{BigInteger, Field, PrimeField, UniformRand, Zero};
+use ark_poly::{EvaluationDomain, Radix2EvaluationDomain};
+use ec_gpu_program::{DeviceInfo, EcError};
 use ec_gpu_proxy::{
-    multiexp::MultiexpKernel,
-    multiexp_cpu::{multiexp_cpu, FullDensity, QueryDensity, SourceBuilder},
+    budget::MemoryBudget,
+    fft::FftKernel,
+    fft_cpu::{bit_reverse_permute, is_bit_reversed},
+    multiexp::{
+        BaseTable, BucketOccupancy, IdentityHandling, MultiexpKernel, OpCount,
+        SingleMultiexpKernel, WindowOccupancy,
+    },
+    multiexp_cpu::{
+        multiexp_cpu, multiexp_with_window, window_size, FullDensity,
+        QueryDensity, SourceBuilder, MAX_WINDOW_SIZE,
+    },
+    split::WorkSplitter,
     threadpool::Worker,
 };
+use rand::Rng;
 use rust_gpu_tools::Device;
 
 fn multiexp_gpu<Q, D, G, S>(
@@ -103,3 +122,2678 @@ fn gpu_multiexp_consistency() {
         bases = [bases.clone(), bases.clone()].concat();
     }
 }
+
+#[test]
+fn gpu_multiexp_op_count() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let g =
+        Arc::new((0..4).map(|_| G1Affine::rand(&mut rng)).collect::<Vec<_>>());
+    // Every exponent has at most one bit set, so each non-zero exponent
+    // results in exactly one mixed addition, whatever the window size is.
+    let v = Arc::new(
+        [1u64, 2, 4, 0]
+            .iter()
+            .map(|&x| Fr::from(x).to_repr())
+            .collect::<Vec<_>>(),
+    );
+
+    assert_eq!(kern.op_count(), None);
+    kern.set_count_ops(true);
+    let gpu =
+        multiexp_gpu(&pool, (g.clone(), 0), FullDensity, v.clone(), &mut kern)
+            .unwrap();
+    let cpu = multiexp_cpu(&pool, (g, 0), FullDensity, v).wait().unwrap();
+    assert_eq!(cpu.into_affine(), gpu.into_affine());
+
+    let op_count = kern.op_count().expect("counting is enabled");
+    assert_eq!(op_count.mixed_additions, 3);
+    // The buckets are summed up and the windows are combined on the GPU.
+    assert!(op_count.additions > 0);
+    assert!(op_count.doublings > 0);
+
+    kern.set_count_ops(false);
+    assert_eq!(kern.op_count(), None);
+}
+
+/// A CPU reference of the bucket method of the multiexp kernel, which counts
+/// the point operations it performs.
+///
+/// The terms are split into `num_chunks` chunks, each one is handled by one
+/// thread per window of `window_size` bits, the most significant window
+/// first. A thread adds the bases into the buckets of their digits and sums
+/// its buckets up by parts. Then the threads of a chunk are merged pairwise
+/// in a binary tree, the more significant side is doubled until it has the
+/// weight of the other one. The digits are signed if `signed` is set.
+fn reference_bucket_method(
+    bases: &[G1Affine], exps: &[<Fr as PrimeFieldRepr>::Repr],
+    window_size: usize, num_chunks: usize, signed: bool,
+) -> (G1Projective, OpCount) {
+    let exp_bits = 64 * exps[0].as_ref().len();
+    let num_windows = (exp_bits + window_size - 1) / window_size;
+    let signed = signed && window_size > 1;
+    let half = 1u64 << (window_size - 1);
+    let full = 1u64 << window_size;
+    let num_buckets = if signed { half } else { full - 1 } as usize;
+    // `len` bits of `exp`, starting at the `start`-th most significant bit.
+    let bits =
+        |exp: &<Fr as PrimeFieldRepr>::Repr, start: usize, len: usize| {
+            (start..start + len).fold(0u64, |acc, i| {
+                (acc << 1) | exp.get_bit(exp_bits - 1 - i) as u64
+            })
+        };
+    // The exponent of two of the least significant window of the threads
+    // from `thread` to `thread + num_threads`.
+    let weight = |thread: usize, num_threads: usize| {
+        exp_bits.saturating_sub((thread + num_threads) * window_size)
+    };
+
+    let mut ops = OpCount::default();
+    let mut result = G1Projective::zero();
+    let chunk_len = (exps.len() + num_chunks - 1) / num_chunks;
+    for chunk in 0..num_chunks {
+        let start = std::cmp::min(chunk * chunk_len, exps.len());
+        let end = std::cmp::min(start + chunk_len, exps.len());
+
+        let mut threads = Vec::with_capacity(num_windows);
+        for window in 0..num_windows {
+            let offset = window * window_size;
+            let len = std::cmp::min(window_size, exp_bits - offset);
+            let mut buckets = vec![G1Projective::zero(); num_buckets];
+            for i in start..end {
+                let digit = bits(&exps[i], offset, len);
+                // A negative digit of the next window borrows from this one.
+                let borrow = signed
+                    && offset + 2 * window_size <= exp_bits
+                    && bits(&exps[i], offset + window_size, window_size)
+                        >= half;
+                let value = digit + borrow as u64;
+                if signed && digit >= half {
+                    if value < full {
+                        buckets[(full - value - 1) as usize] += -bases[i];
+                        ops.mixed_additions += 1;
+                    }
+                } else if value > 0 {
+                    buckets[value as usize - 1] += bases[i];
+                    ops.mixed_additions += 1;
+                }
+            }
+
+            let mut acc = buckets[num_buckets - 1];
+            let mut sum = acc;
+            for bucket in buckets[..num_buckets - 1].iter().rev() {
+                acc += bucket;
+                sum += acc;
+                ops.additions += 2;
+            }
+            threads.push(sum);
+        }
+
+        let mut height = 1;
+        while height < num_windows {
+            for thread in (0..num_windows).step_by(2 * height) {
+                let sibling = thread + height;
+                if sibling >= num_windows {
+                    continue;
+                }
+                for _ in weight(sibling, height)..weight(thread, height) {
+                    threads[thread].double_in_place();
+                    ops.doublings += 1;
+                }
+                let sibling_sum = threads[sibling];
+                threads[thread] += sibling_sum;
+                ops.additions += 1;
+            }
+            height *= 2;
+        }
+        result += threads[0];
+    }
+    (result, ops)
+}
+
+#[test]
+fn gpu_multiexp_op_count_matches_cpu() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let device = devices[0];
+    let program =
+        ec_gpu_program::load_program!(device).expect("Cannot create program!");
+    let mut kern =
+        SingleMultiexpKernel::<G1Affine>::create(program, device, None)
+            .expect("Cannot initialize kernel!");
+    kern.set_count_ops(true);
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let samples = 1000;
+    let bases = (0..samples)
+        .map(|_| G1Affine::rand(&mut rng))
+        .collect::<Vec<_>>();
+    // Random exponents with some zero digits.
+    let mut exps = (0..samples)
+        .map(|_| Fr::rand(&mut rng).to_repr())
+        .collect::<Vec<_>>();
+    exps[0] = Fr::zero().to_repr();
+    exps[1] = Fr::from(1u64 << 40).to_repr();
+    let exp_bits = 64 * exps[0].as_ref().len();
+    let expected_result = multiexp_cpu(
+        &pool,
+        (Arc::new(bases.clone()), 0),
+        FullDensity,
+        Arc::new(exps.clone()),
+    )
+    .wait()
+    .unwrap();
+
+    for window_size in [1, 3, 5] {
+        kern.set_window_size(Some(window_size)).unwrap();
+        kern.reset_op_count();
+        let gpu = kern.multiexp(&bases, &exps).unwrap();
+        assert_eq!(gpu.into_affine(), expected_result.into_affine());
+
+        // Every window of a chunk is handled by its own thread.
+        let num_windows = (exp_bits + window_size - 1) / window_size;
+        let num_chunks = kern.work_units() / num_windows;
+        // The signed digits must give the same result, the host doesn't
+        // enable them though.
+        let (signed, _) = reference_bucket_method(
+            &bases,
+            &exps,
+            window_size,
+            num_chunks,
+            true,
+        );
+        assert_eq!(signed.into_affine(), expected_result.into_affine());
+        let (cpu, expected) = reference_bucket_method(
+            &bases,
+            &exps,
+            window_size,
+            num_chunks,
+            false,
+        );
+        assert_eq!(cpu.into_affine(), expected_result.into_affine());
+        assert_eq!(kern.op_count(), expected, "window size {}", window_size);
+    }
+}
+
+#[test]
+fn gpu_commit_polynomial_consistency() {
+    fil_logger::maybe_init();
+    const LOG_D: usize = 10;
+    let devices = Device::all();
+    generate(
+        &ag_build::SourceBuilder::new()
+            .add_fft::<Fr>()
+            .add_multiexp::<G1Affine>(),
+    );
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let samples = 1 << LOG_D;
+    // The SRS may be longer than the polynomial.
+    let srs = Arc::new(
+        (0..samples + 1)
+            .map(|_| G1Affine::rand(&mut rng))
+            .collect::<Vec<_>>(),
+    );
+    let evals = (0..samples).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+
+    let gpu = kern.commit_polynomial(&evals, &srs).unwrap();
+
+    let domain = Radix2EvaluationDomain::<Fr>::new(samples).unwrap();
+    let coeffs = Arc::new(
+        domain
+            .ifft(&evals)
+            .iter()
+            .map(|c| c.to_repr())
+            .collect::<Vec<_>>(),
+    );
+    let cpu = multiexp_cpu(&pool, (srs, 0), FullDensity, coeffs)
+        .wait()
+        .unwrap();
+
+    assert_eq!(cpu.into_affine(), gpu.into_affine());
+
+    assert!(matches!(
+        kern.commit_polynomial(&evals, &srs[..samples - 1]),
+        Err(EcError::InvalidLength(_))
+    ));
+    assert!(matches!(
+        kern.commit_polynomial(&evals[..samples - 1], &srs),
+        Err(EcError::InvalidLength(_))
+    ));
+}
+
+#[test]
+fn gpu_commit_evaluations_bit_reversed_consistency() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    generate(
+        &ag_build::SourceBuilder::new()
+            .add_fft::<Fr>()
+            .add_multiexp::<G1Affine>(),
+    );
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    for log_d in [0, 1, 5, 10] {
+        let samples = 1 << log_d;
+        let srs = Arc::new(
+            (0..samples)
+                .map(|_| G1Affine::rand(&mut rng))
+                .collect::<Vec<_>>(),
+        );
+        let coeffs =
+            (0..samples).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+
+        let gpu = kern.commit_evaluations_bit_reversed(&coeffs, &srs).unwrap();
+
+        // FFT, explicit bit-reversal permutation, then multiexp.
+        let domain = Radix2EvaluationDomain::<Fr>::new(samples).unwrap();
+        let evals = domain.fft(&coeffs);
+        let permuted = Arc::new(
+            (0..samples)
+                .map(|i: usize| {
+                    let rev = if log_d == 0 {
+                        0
+                    } else {
+                        i.reverse_bits() >> (usize::BITS - log_d)
+                    };
+                    evals[rev].to_repr()
+                })
+                .collect::<Vec<_>>(),
+        );
+        let cpu = multiexp_cpu(&pool, (srs, 0), FullDensity, permuted)
+            .wait()
+            .unwrap();
+
+        assert_eq!(cpu.into_affine(), gpu.into_affine(), "log_d = {}", log_d);
+    }
+
+    // The SRS must have a base for every coefficient.
+    let srs = vec![G1Affine::rand(&mut rng); 2];
+    let coeffs = (0..4).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+    assert!(matches!(
+        kern.commit_evaluations_bit_reversed(&coeffs, &srs),
+        Err(EcError::InvalidLength(_))
+    ));
+}
+
+#[test]
+fn gpu_bit_reverse_permute_consistency() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    generate(
+        &ag_build::SourceBuilder::new()
+            .add_fft::<Fr>()
+            .add_multiexp::<G1Affine>(),
+    );
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    for log_d in [0, 3, 10] {
+        let samples = 1 << log_d;
+        let srs = Arc::new(
+            (0..samples)
+                .map(|_| G1Affine::rand(&mut rng))
+                .collect::<Vec<_>>(),
+        );
+        let coeffs =
+            (0..samples).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+
+        // The GPU permutes the bases, the host permutes the evaluations.
+        let gpu = kern.commit_evaluations_bit_reversed(&coeffs, &srs).unwrap();
+        let domain = Radix2EvaluationDomain::<Fr>::new(samples).unwrap();
+        let evals = domain.fft(&coeffs);
+        let mut permuted = evals.clone();
+        bit_reverse_permute(&mut permuted, log_d);
+        assert!(is_bit_reversed(&evals, &permuted, log_d));
+        let exps = Arc::new(
+            permuted
+                .iter()
+                .map(PrimeFieldRepr::to_repr)
+                .collect::<Vec<_>>(),
+        );
+        let cpu = multiexp_cpu(&pool, (srs, 0), FullDensity, exps)
+            .wait()
+            .unwrap();
+        assert_eq!(cpu.into_affine(), gpu.into_affine(), "log_d = {}", log_d);
+
+        // Applying the permutation twice restores the natural order.
+        bit_reverse_permute(&mut permuted, log_d);
+        assert_eq!(permuted, evals);
+    }
+}
+
+#[test]
+fn gpu_normalize_many_consistency() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let mut rng = rand::thread_rng();
+
+    let mut points = (0..(1 << 12))
+        .map(|_| G1Projective::rand(&mut rng))
+        .collect::<Vec<_>>();
+    points[0] = G1Projective::zero();
+    points[1000] = G1Projective::zero();
+
+    let gpu = kern.normalize_many(&points).unwrap();
+    let cpu = G1Projective::normalize_batch(&points);
+    assert_eq!(cpu, gpu);
+}
+
+#[test]
+fn gpu_multiexp_reduce_consistency() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let samples = 1 << 10;
+    let g = Arc::new(
+        (0..samples)
+            .map(|_| G1Affine::rand(&mut rng))
+            .collect::<Vec<_>>(),
+    );
+    let wide = (0..samples)
+        .map(|_| {
+            let mut limbs = [0u64; 8];
+            rng.fill(&mut limbs);
+            limbs
+        })
+        .collect::<Vec<_>>();
+
+    let gpu = kern.multiexp_reduce(&pool, g.clone(), &wide, 0).unwrap();
+
+    let v = Arc::new(
+        wide.iter()
+            .map(|limbs| {
+                let bytes = limbs
+                    .iter()
+                    .flat_map(|limb| limb.to_le_bytes())
+                    .collect::<Vec<_>>();
+                Fr::from_le_bytes_mod_order(&bytes).to_repr()
+            })
+            .collect::<Vec<_>>(),
+    );
+    let cpu = multiexp_cpu(&pool, (g, 0), FullDensity, v).wait().unwrap();
+
+    assert_eq!(cpu.into_affine(), gpu.into_affine());
+}
+
+#[test]
+fn gpu_multiexp_numa_aware_consistency() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let samples = 1 << 12;
+    let g = Arc::new(
+        (0..samples)
+            .map(|_| G1Affine::rand(&mut rng))
+            .collect::<Vec<_>>(),
+    );
+    let v = Arc::new(
+        (0..samples)
+            .map(|_| Fr::rand(&mut rng).to_repr())
+            .collect::<Vec<_>>(),
+    );
+
+    kern.set_numa_aware(true);
+    let gpu =
+        multiexp_gpu(&pool, (g.clone(), 0), FullDensity, v.clone(), &mut kern)
+            .unwrap();
+    let cpu = multiexp_cpu(&pool, (g, 0), FullDensity, v).wait().unwrap();
+    assert_eq!(cpu.into_affine(), gpu.into_affine());
+}
+
+#[test]
+fn gpu_multiexp_shared_budget() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    generate(
+        &ag_build::SourceBuilder::new()
+            .add_fft::<Fr>()
+            .add_multiexp::<G1Affine>(),
+    );
+    let load_programs = || {
+        devices
+            .iter()
+            .map(|device| ec_gpu_program::load_program!(device))
+            .collect::<Result<Vec<_>, _>>()
+            .expect("Cannot create programs!")
+    };
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let samples = 1 << 12;
+    let multiexp_kern =
+        MultiexpKernel::<G1Affine>::create(load_programs(), &devices)
+            .expect("Cannot initialize kernel!");
+    // Too small for a multiexp of all terms at once.
+    let budget = Arc::new(MemoryBudget::new(
+        multiexp_kern.required_memory(samples / 4),
+    ));
+    let mut multiexp_kern = multiexp_kern.with_budget(budget.clone());
+    let mut fft_kern = FftKernel::<Fr>::create(load_programs())
+        .expect("Cannot initialize kernel!")
+        .with_budget(budget.clone());
+
+    let g = Arc::new(
+        (0..samples)
+            .map(|_| G1Affine::rand(&mut rng))
+            .collect::<Vec<_>>(),
+    );
+    let v = Arc::new(
+        (0..samples)
+            .map(|_| Fr::rand(&mut rng).to_repr())
+            .collect::<Vec<_>>(),
+    );
+    let gpu = multiexp_gpu(
+        &pool,
+        (g.clone(), 0),
+        FullDensity,
+        v.clone(),
+        &mut multiexp_kern,
+    )
+    .unwrap();
+    let cpu = multiexp_cpu(&pool, (g, 0), FullDensity, v).wait().unwrap();
+    assert_eq!(cpu.into_affine(), gpu.into_affine());
+
+    let log_d = 10;
+    let domain = Radix2EvaluationDomain::<Fr>::new(1 << log_d).unwrap();
+    let coeffs = (0..1 << log_d)
+        .map(|_| Fr::rand(&mut rng))
+        .collect::<Vec<_>>();
+    let mut gpu_evals = coeffs.clone();
+    fft_kern
+        .radix_fft(&mut gpu_evals, &domain.group_gen, log_d)
+        .unwrap();
+    assert_eq!(domain.fft(&coeffs), gpu_evals);
+
+    assert!(budget.peak() <= budget.limit());
+    assert_eq!(budget.used(), 0);
+}
+
+#[test]
+fn gpu_multiexp_shared_budget_concurrent() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    generate(
+        &ag_build::SourceBuilder::new()
+            .add_fft::<Fr>()
+            .add_multiexp::<G1Affine>(),
+    );
+    let load_programs = || {
+        devices
+            .iter()
+            .map(|device| ec_gpu_program::load_program!(device))
+            .collect::<Result<Vec<_>, _>>()
+            .expect("Cannot create programs!")
+    };
+    let mut rng = rand::thread_rng();
+
+    let samples = 1 << 12;
+    let kern = MultiexpKernel::<G1Affine>::create(load_programs(), &devices)
+        .expect("Cannot initialize kernel!");
+    // Enough for a single chunk of a quarter of the terms, the two kernels
+    // have to chunk and wait for each other.
+    let budget = Arc::new(MemoryBudget::new(kern.required_memory(samples / 4)));
+    let kerns = vec![
+        kern.with_budget(budget.clone()),
+        MultiexpKernel::<G1Affine>::create(load_programs(), &devices)
+            .expect("Cannot initialize kernel!")
+            .with_budget(budget.clone()),
+    ];
+
+    let handles = kerns
+        .into_iter()
+        .map(|mut kern| {
+            let g = Arc::new(
+                (0..samples)
+                    .map(|_| G1Affine::rand(&mut rng))
+                    .collect::<Vec<_>>(),
+            );
+            let v = Arc::new(
+                (0..samples)
+                    .map(|_| Fr::rand(&mut rng).to_repr())
+                    .collect::<Vec<_>>(),
+            );
+            std::thread::spawn(move || {
+                let pool = Worker::new();
+                let gpu = multiexp_gpu(
+                    &pool,
+                    (g.clone(), 0),
+                    FullDensity,
+                    v.clone(),
+                    &mut kern,
+                )
+                .unwrap();
+                let cpu =
+                    multiexp_cpu(&pool, (g, 0), FullDensity, v).wait().unwrap();
+                assert_eq!(cpu.into_affine(), gpu.into_affine());
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    // An FFT can't be split, one that exceeds the budget fails.
+    let mut fft_kern = FftKernel::<Fr>::create(load_programs())
+        .expect("Cannot initialize kernel!")
+        .with_budget(budget.clone());
+    let log_d = (budget.limit() / std::mem::size_of::<Fr>())
+        .next_power_of_two()
+        .trailing_zeros();
+    let domain = Radix2EvaluationDomain::<Fr>::new(1 << log_d).unwrap();
+    let mut values = vec![Fr::zero(); 1 << log_d];
+    assert!(matches!(
+        fft_kern.radix_fft(&mut values, &domain.group_gen, log_d),
+        Err(EcError::MemoryBudgetExceeded { .. })
+    ));
+
+    assert!(budget.peak() <= budget.limit());
+    assert_eq!(budget.used(), 0);
+}
+
+#[test]
+fn gpu_commit_polynomial_tight_budget() {
+    fil_logger::maybe_init();
+    const LOG_D: usize = 10;
+    let devices = Device::all();
+    generate(
+        &ag_build::SourceBuilder::new()
+            .add_fft::<Fr>()
+            .add_multiexp::<G1Affine>(),
+    );
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let samples = 1 << LOG_D;
+    // Just enough for a single chunk, the coefficients that stay on the GPU
+    // after the inverse FFT must not be reserved twice.
+    let budget = Arc::new(MemoryBudget::new(kern.required_memory(samples) + 1));
+    let mut kern = kern.with_budget(budget.clone());
+    let srs = Arc::new(
+        (0..samples)
+            .map(|_| G1Affine::rand(&mut rng))
+            .collect::<Vec<_>>(),
+    );
+    let evals = (0..samples).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+
+    let gpu = kern.commit_polynomial(&evals, &srs).unwrap();
+
+    let domain = Radix2EvaluationDomain::<Fr>::new(samples).unwrap();
+    let coeffs = Arc::new(
+        domain
+            .ifft(&evals)
+            .iter()
+            .map(|c| c.to_repr())
+            .collect::<Vec<_>>(),
+    );
+    let cpu = multiexp_cpu(&pool, (srs, 0), FullDensity, coeffs)
+        .wait()
+        .unwrap();
+    assert_eq!(cpu.into_affine(), gpu.into_affine());
+    assert!(budget.peak() <= budget.limit());
+    assert_eq!(budget.used(), 0);
+}
+
+#[test]
+fn gpu_multiexp_lagrange_consistency() {
+    fil_logger::maybe_init();
+    const LOG_D: usize = 10;
+    let devices = Device::all();
+    generate(
+        &ag_build::SourceBuilder::new()
+            .add_fft::<Fr>()
+            .add_multiexp::<G1Affine>(),
+    );
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let samples = 1 << LOG_D;
+    let domain = Radix2EvaluationDomain::<Fr>::new(samples).unwrap();
+    let g = G1Affine::rand(&mut rng);
+    let tau = Fr::rand(&mut rng);
+    let mut powers = Vec::with_capacity(samples);
+    let mut power = Fr::from(1u64);
+    for _ in 0..samples {
+        powers.push(g * power);
+        power *= tau;
+    }
+    let srs = Arc::new(G1Projective::normalize_batch(&powers));
+    let lagrange_bases = Arc::new(G1Projective::normalize_batch(
+        &domain
+            .evaluate_all_lagrange_coefficients(tau)
+            .into_iter()
+            .map(|l| g * l)
+            .collect::<Vec<_>>(),
+    ));
+    let evals = (0..samples).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+
+    let gpu = kern
+        .multiexp_lagrange(&pool, lagrange_bases.clone(), &evals, samples)
+        .unwrap();
+
+    let coeffs = Arc::new(
+        domain
+            .ifft(&evals)
+            .iter()
+            .map(|c| c.to_repr())
+            .collect::<Vec<_>>(),
+    );
+    let expected =
+        multiexp_gpu(&pool, (srs, 0), FullDensity, coeffs, &mut kern).unwrap();
+    assert_eq!(expected.into_affine(), gpu.into_affine());
+
+    // The domain size must be a power of two, match the bases and cover all
+    // evaluations.
+    let too_many = [evals.clone(), evals.clone()].concat();
+    let short_bases = Arc::new(lagrange_bases[..samples - 1].to_vec());
+    for (bases, evals, domain_size) in [
+        (&short_bases, &evals[..samples - 1], samples - 1),
+        (&short_bases, &evals[..], samples),
+        (&lagrange_bases, &too_many[..], samples),
+    ] {
+        assert!(matches!(
+            kern.multiexp_lagrange(&pool, bases.clone(), evals, domain_size),
+            Err(EcError::InvalidLength(_))
+        ));
+    }
+
+    // Missing evaluations are zero.
+    let gpu = kern
+        .multiexp_lagrange(
+            &pool,
+            lagrange_bases,
+            &evals[..samples / 2],
+            samples,
+        )
+        .unwrap();
+    let mut padded = evals[..samples / 2].to_vec();
+    padded.resize(samples, Fr::zero());
+    let mut expected = G1Projective::zero();
+    for (l, e) in domain
+        .evaluate_all_lagrange_coefficients(tau)
+        .into_iter()
+        .zip(padded)
+    {
+        expected += g * (l * e);
+    }
+    assert_eq!(expected.into_affine(), gpu.into_affine());
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn gpu_multiexp_tracing_spans() {
+    use std::sync::{atomic::AtomicU64, Mutex};
+    use tracing::{span, Event, Metadata, Subscriber};
+
+    /// Records the names and fields of all spans, and counts the events.
+    struct Recorder {
+        spans: Arc<Mutex<Vec<(&'static str, Vec<String>)>>>,
+        events: Arc<AtomicUsize>,
+        next_id: AtomicU64,
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool { true }
+
+        fn new_span(&self, attrs: &span::Attributes<'_>) -> span::Id {
+            let metadata = attrs.metadata();
+            let fields =
+                metadata.fields().iter().map(|f| f.to_string()).collect();
+            self.spans.lock().unwrap().push((metadata.name(), fields));
+            span::Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+        }
+
+        fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+        fn event(&self, _: &Event<'_>) {
+            self.events.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn enter(&self, _: &span::Id) {}
+
+        fn exit(&self, _: &span::Id) {}
+    }
+
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let samples = 1 << 10;
+    let g = Arc::new(
+        (0..samples)
+            .map(|_| G1Affine::rand(&mut rng))
+            .collect::<Vec<_>>(),
+    );
+    let v = Arc::new(
+        (0..samples)
+            .map(|_| Fr::rand(&mut rng).to_repr())
+            .collect::<Vec<_>>(),
+    );
+
+    let spans = Arc::new(Mutex::new(Vec::new()));
+    let events = Arc::new(AtomicUsize::new(0));
+    let recorder = Recorder {
+        spans: spans.clone(),
+        events: events.clone(),
+        next_id: AtomicU64::new(0),
+    };
+    tracing::subscriber::with_default(recorder, || {
+        let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+            .expect("Cannot initialize kernel!");
+        kern.multiexp(&pool, g, v, 0).unwrap();
+    });
+
+    let spans = spans.lock().unwrap();
+    let has_span = |name: &str, field: &str| {
+        spans.iter().any(|(span_name, fields)| {
+            *span_name == name && fields.iter().any(|f| f == field)
+        })
+    };
+    assert!(has_span("multiexp_create", "num_programs"));
+    assert!(has_span("multiexp", "num_terms"));
+    assert!(has_span("multiexp_device", "device"));
+    assert!(has_span("multiexp_device", "backend"));
+    assert!(events.load(Ordering::Relaxed) > 0);
+}
+
+#[test]
+fn gpu_multiexp_from_device_consistency() {
+    fil_logger::maybe_init();
+    const LOG_D: u32 = 10;
+    let devices = Device::all();
+    generate(
+        &ag_build::SourceBuilder::new()
+            .add_fft::<Fr>()
+            .add_multiexp::<G1Affine>(),
+    );
+    let load_programs = || {
+        devices
+            .iter()
+            .map(|device| ec_gpu_program::load_program!(device))
+            .collect::<Result<Vec<_>, _>>()
+            .expect("Cannot create programs!")
+    };
+    let mut kern =
+        MultiexpKernel::<G1Affine>::create(load_programs(), &devices)
+            .expect("Cannot initialize kernel!");
+    let mut fft_kern = FftKernel::<Fr>::create(load_programs())
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let samples = 1 << LOG_D;
+    let srs = Arc::new(
+        (0..samples)
+            .map(|_| G1Affine::rand(&mut rng))
+            .collect::<Vec<_>>(),
+    );
+    let evals = (0..samples).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+
+    let coeffs = kern.ifft_to_device(&evals).unwrap();
+    assert_eq!(coeffs.len(), samples);
+    let gpu = kern.multiexp_from_device(&srs, coeffs).unwrap();
+
+    // Every coefficient needs a base.
+    let coeffs = kern.ifft_to_device(&evals).unwrap();
+    assert!(matches!(
+        kern.multiexp_from_device(&srs[..samples - 1], coeffs),
+        Err(EcError::InvalidLength(_))
+    ));
+
+    // The same chain with a round trip to the host in between.
+    let domain = Radix2EvaluationDomain::<Fr>::new(samples).unwrap();
+    let mut coeffs = evals.clone();
+    fft_kern
+        .radix_fft(&mut coeffs, &domain.group_gen_inv, LOG_D)
+        .unwrap();
+    let exps = Arc::new(
+        coeffs
+            .iter()
+            .map(|c| (*c * domain.size_inv).to_repr())
+            .collect::<Vec<_>>(),
+    );
+    let host =
+        multiexp_gpu(&pool, (srs, 0), FullDensity, exps, &mut kern).unwrap();
+
+    assert_eq!(host.into_affine(), gpu.into_affine());
+}
+
+#[test]
+fn gpu_powers_of_tau_consistency() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let mut rng = rand::thread_rng();
+
+    let n = 1000;
+    let g = G1Affine::rand(&mut rng);
+    let s = Fr::rand(&mut rng);
+    let gpu = kern.powers_of_tau(g, s, n).unwrap();
+    assert_eq!(gpu.len(), n);
+
+    let mut power = Fr::from(1u64);
+    for point in gpu.iter().take(16) {
+        assert_eq!((g * power).into_affine(), point.into_affine());
+        power *= s;
+    }
+    let last = s.pow([n as u64 - 1]);
+    assert_eq!((g * last).into_affine(), gpu[n - 1].into_affine());
+
+    assert!(kern.powers_of_tau(g, s, 0).unwrap().is_empty());
+    assert!(matches!(
+        kern.powers_of_tau(g, s, (1 << 32) + 1),
+        Err(EcError::InvalidLength(_))
+    ));
+}
+
+#[test]
+fn gpu_multiexp_abort_during_upload() {
+    fil_logger::maybe_init();
+    let device = Device::all()[0];
+    build_multiexp();
+    let mut rng = rand::thread_rng();
+
+    // Small chunks, so that the bases are uploaded in many of them.
+    let chunk_bytes = 1 << 12;
+    let samples = 1 << 12;
+    let g = (0..samples)
+        .map(|_| G1Affine::rand(&mut rng))
+        .collect::<Vec<_>>();
+    let v = (0..samples)
+        .map(|_| Fr::rand(&mut rng).to_repr())
+        .collect::<Vec<_>>();
+
+    // The first call is before the launch, the second one before the first
+    // chunk of the bases. Aborting on the third call aborts the upload before
+    // its second chunk.
+    let calls = AtomicUsize::new(0);
+    let maybe_abort = || calls.fetch_add(1, Ordering::SeqCst) >= 2;
+    let program =
+        ec_gpu_program::load_program!(device).expect("Cannot create program!");
+    let mut kern = SingleMultiexpKernel::<G1Affine>::create(
+        program,
+        device,
+        Some(&maybe_abort),
+    )
+    .expect("Cannot initialize kernel!");
+    kern.set_transfer_chunk_bytes(chunk_bytes);
+    let result = kern.multiexp(&g, &v);
+    assert!(matches!(result, Err(EcError::Aborted)));
+    // No further chunk was uploaded after the abort.
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+    // Without an abort, the chunks add up to the whole upload.
+    let program =
+        ec_gpu_program::load_program!(device).expect("Cannot create program!");
+    let mut kern =
+        SingleMultiexpKernel::<G1Affine>::create(program, device, None)
+            .expect("Cannot initialize kernel!");
+    kern.set_transfer_chunk_bytes(chunk_bytes);
+    let gpu = kern.multiexp(&g, &v).unwrap();
+    let pool = Worker::new();
+    let cpu = multiexp_cpu(&pool, (Arc::new(g), 0), FullDensity, Arc::new(v))
+        .wait()
+        .unwrap();
+    assert_eq!(cpu.into_affine(), gpu.into_affine());
+}
+
+#[test]
+fn gpu_multiexp_pipeline_consistency() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    // Jobs of different sizes, including an empty one and one with more
+    // bases than exponents. Every ninth base of the second job is the point
+    // at infinity.
+    let jobs = [(1024, 1024), (1000, 1000), (0, 0), (4096, 3000), (1, 1)]
+        .iter()
+        .enumerate()
+        .map(|(job, &(num_bases, num_exps))| {
+            let bases = (0..num_bases)
+                .map(|i| {
+                    if job == 1 && i % 9 == 0 {
+                        G1Affine::identity()
+                    } else {
+                        G1Affine::rand(&mut rng)
+                    }
+                })
+                .collect::<Vec<_>>();
+            let exps = (0..num_exps)
+                .map(|_| Fr::rand(&mut rng).to_repr())
+                .collect::<Vec<_>>();
+            (Arc::new(bases), Arc::new(exps))
+        })
+        .collect::<Vec<_>>();
+
+    let pipelined = kern.multiexp_pipeline(&pool, &jobs).unwrap();
+    assert_eq!(pipelined.len(), jobs.len());
+    for ((bases, exps), result) in jobs.iter().zip(&pipelined) {
+        let cpu =
+            multiexp_cpu(&pool, (bases.clone(), 0), FullDensity, exps.clone())
+                .wait()
+                .unwrap();
+        assert_eq!(cpu.into_affine(), result.into_affine());
+    }
+
+    // Two further streams per device, so that three chunks are in flight.
+    let streams = devices
+        .iter()
+        .map(|device| {
+            (0..2)
+                .map(|_| ec_gpu_program::load_program!(device))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    kern.set_pipeline_streams(streams).unwrap();
+    let streamed = kern.multiexp_pipeline(&pool, &jobs).unwrap();
+    for (result, expected) in streamed.iter().zip(&pipelined) {
+        assert_eq!(result.into_affine(), expected.into_affine());
+    }
+
+    assert!(kern.multiexp_pipeline(&pool, &[]).unwrap().is_empty());
+
+    let (bases, exps) = &jobs[0];
+    let short = (Arc::new(bases[..10].to_vec()), exps.clone());
+    assert!(matches!(
+        kern.multiexp_pipeline(&pool, &[short]),
+        Err(EcError::InvalidLength(_))
+    ));
+    kern.set_identity_handling(IdentityHandling::Reject);
+    assert!(matches!(
+        kern.multiexp_pipeline(&pool, &jobs),
+        Err(EcError::Simple(_))
+    ));
+}
+
+#[test]
+fn gpu_multiexp_shared_scalars_consistency() {
+    use ark_ec::AffineRepr;
+
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let num_terms = 3000;
+    let exps = Arc::new(
+        (0..num_terms)
+            .map(|_| Fr::rand(&mut rng).to_repr())
+            .collect::<Vec<_>>(),
+    );
+    // One set is longer than the exponents, another one contains the point
+    // at infinity.
+    let mut base_sets = [num_terms, num_terms, num_terms + 100, num_terms]
+        .iter()
+        .map(|&num_bases| {
+            (0..num_bases)
+                .map(|_| G1Affine::rand(&mut rng))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    base_sets[3][10] = G1Affine::identity();
+    let base_sets = base_sets.into_iter().map(Arc::new).collect::<Vec<_>>();
+
+    let shared = kern
+        .multiexp_shared_scalars(&pool, &base_sets, exps.clone())
+        .unwrap();
+    assert_eq!(shared.len(), base_sets.len());
+    for (bases, result) in base_sets.iter().zip(shared) {
+        let expected = kern
+            .multiexp(&pool, bases.clone(), exps.clone(), 0)
+            .unwrap();
+        assert_eq!(expected.into_affine(), result.into_affine());
+    }
+
+    assert!(kern
+        .multiexp_shared_scalars(&pool, &[], exps.clone())
+        .unwrap()
+        .is_empty());
+    let short = Arc::new(base_sets[0][..exps.len() - 1].to_vec());
+    assert!(matches!(
+        kern.multiexp_shared_scalars(&pool, &[short], exps.clone()),
+        Err(EcError::InvalidLength(_))
+    ));
+    kern.set_identity_handling(IdentityHandling::Reject);
+    assert!(kern
+        .multiexp_shared_scalars(&pool, &base_sets, exps)
+        .is_err());
+}
+
+#[test]
+fn gpu_commit_many_consistency() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let srs_len = 3000;
+    let srs = Arc::new(
+        (0..srs_len)
+            .map(|_| G1Affine::rand(&mut rng))
+            .collect::<Vec<_>>(),
+    );
+    // The polynomials are shorter than the SRS, one is even empty.
+    let polys = [2500, 1000, 0, 1]
+        .iter()
+        .map(|&len| (0..len).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let poly_coeffs = polys.iter().map(|poly| &poly[..]).collect::<Vec<_>>();
+
+    let commitments = kern.commit_many(&pool, &srs, &poly_coeffs).unwrap();
+    assert_eq!(commitments.len(), polys.len());
+    for (poly, commitment) in polys.iter().zip(commitments) {
+        let exps = Arc::new(poly.iter().map(|c| c.to_repr()).collect());
+        let expected = kern.multiexp(&pool, srs.clone(), exps, 0).unwrap();
+        assert_eq!(expected.into_affine(), commitment.into_affine());
+    }
+
+    assert!(kern.commit_many(&pool, &srs, &[]).unwrap().is_empty());
+
+    // A small budget forces several chunks, most of them start after the end
+    // of the shorter polynomials.
+    let budget = Arc::new(MemoryBudget::new(kern.required_memory(500)));
+    let mut kern = kern.with_budget(budget.clone());
+    let chunked = kern.commit_many(&pool, &srs, &poly_coeffs).unwrap();
+    for (poly, commitment) in polys.iter().zip(chunked) {
+        let exps = Arc::new(poly.iter().map(|c| c.to_repr()).collect());
+        let expected = kern.multiexp(&pool, srs.clone(), exps, 0).unwrap();
+        assert_eq!(expected.into_affine(), commitment.into_affine());
+    }
+    assert_eq!(budget.used(), 0);
+
+    // No polynomial may be longer than the SRS.
+    assert!(matches!(
+        kern.commit_many(&pool, &srs[..10], &poly_coeffs),
+        Err(EcError::InvalidLength(_))
+    ));
+}
+
+#[test]
+fn gpu_multiexp_partial_combine() {
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+    use ec_gpu_proxy::multiexp::MultiexpPartial;
+
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let num_terms = 5000;
+    let bases = Arc::new(
+        (0..num_terms)
+            .map(|_| G1Affine::rand(&mut rng))
+            .collect::<Vec<_>>(),
+    );
+    let exps = (0..num_terms)
+        .map(|_| Fr::rand(&mut rng).to_repr())
+        .collect::<Vec<_>>();
+    let expected = kern
+        .multiexp(&pool, bases.clone(), Arc::new(exps.clone()), 0)
+        .unwrap();
+
+    // Two nodes with shares of different sizes, which hence use different
+    // window sizes.
+    let split = 1000;
+    let first = kern
+        .multiexp_partial(
+            &pool,
+            bases.clone(),
+            Arc::new(exps[..split].to_vec()),
+            0,
+        )
+        .unwrap();
+    let second = kern
+        .multiexp_partial(
+            &pool,
+            bases.clone(),
+            Arc::new(exps[split..].to_vec()),
+            split,
+        )
+        .unwrap();
+
+    // The partials are passed on to the root in serialized form.
+    let mut bytes = Vec::new();
+    second.serialize_compressed(&mut bytes).unwrap();
+    let second =
+        MultiexpPartial::<G1Affine>::deserialize_compressed(&bytes[..])
+            .unwrap();
+
+    let combined =
+        MultiexpKernel::<G1Affine>::combine_partials([first.clone(), second]);
+    assert_eq!(expected.into_affine(), combined.into_affine());
+    assert_eq!(
+        first.reduce().into_affine(),
+        kern.multiexp(
+            &pool,
+            bases.clone(),
+            Arc::new(exps[..split].to_vec()),
+            0
+        )
+        .unwrap()
+        .into_affine()
+    );
+    assert!(MultiexpKernel::<G1Affine>::combine_partials([]).is_zero());
+
+    // Skipping past the end of the bases is an error, not a panic.
+    assert!(matches!(
+        kern.multiexp_partial(
+            &pool,
+            bases,
+            Arc::new(exps[split..].to_vec()),
+            split + 1
+        ),
+        Err(EcError::InvalidLength(_))
+    ));
+}
+
+#[test]
+fn gpu_multiexp_to_compressed() {
+    use ark_serialize::CanonicalSerialize;
+
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let num_terms = 5000;
+    let bases = Arc::new(
+        (0..num_terms)
+            .map(|_| G1Affine::rand(&mut rng))
+            .collect::<Vec<_>>(),
+    );
+    // Several results, so that both signs of `y` are likely to be covered.
+    for _ in 0..8 {
+        let exps = Arc::new(
+            (0..num_terms)
+                .map(|_| Fr::rand(&mut rng).to_repr())
+                .collect::<Vec<_>>(),
+        );
+        let result = kern
+            .multiexp(&pool, bases.clone(), exps.clone(), 0)
+            .unwrap();
+        let mut expected = Vec::new();
+        result.serialize_compressed(&mut expected).unwrap();
+        let bytes = kern
+            .multiexp_to_compressed(&pool, bases.clone(), exps, 0)
+            .unwrap();
+        assert_eq!(expected, bytes);
+    }
+
+    // The point at infinity has its own encoding.
+    let zeros = Arc::new(vec![Fr::zero().to_repr(); num_terms]);
+    let mut expected = Vec::new();
+    G1Projective::zero()
+        .serialize_compressed(&mut expected)
+        .unwrap();
+    let bytes = kern.multiexp_to_compressed(&pool, bases, zeros, 0).unwrap();
+    assert_eq!(expected, bytes);
+}
+
+#[test]
+fn gpu_multiexp_verify_transfers() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let num_terms = 5000;
+    let bases = Arc::new(
+        (0..num_terms)
+            .map(|_| G1Affine::rand(&mut rng))
+            .collect::<Vec<_>>(),
+    );
+    let exps = Arc::new(
+        (0..num_terms)
+            .map(|_| Fr::rand(&mut rng).to_repr())
+            .collect::<Vec<_>>(),
+    );
+    let cpu =
+        multiexp_cpu(&pool, (bases.clone(), 0), FullDensity, exps.clone())
+            .wait()
+            .unwrap();
+
+    kern.set_verify_transfers(true);
+    let gpu = kern
+        .multiexp(&pool, bases.clone(), exps.clone(), 0)
+        .unwrap();
+    assert_eq!(cpu.into_affine(), gpu.into_affine());
+
+    // A simulated transfer error is detected.
+    kern.inject_upload_corruption(true);
+    let result = kern.multiexp(&pool, bases.clone(), exps.clone(), 0);
+    assert!(
+        matches!(result, Err(EcError::TransferCorrupted { offset: 0 })),
+        "{:?}",
+        result
+    );
+
+    // Without verification, it silently results in a wrong result.
+    kern.set_verify_transfers(false);
+    let gpu = kern.multiexp(&pool, bases, exps, 0).unwrap();
+    assert_ne!(cpu.into_affine(), gpu.into_affine());
+}
+
+#[test]
+fn gpu_multiexp_config_accessors() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let num_kernels = kern.num_kernels();
+
+    let backends = kern.backend();
+    assert_eq!(backends.len(), num_kernels);
+    assert!(backends
+        .iter()
+        .all(|backend| ["cuda", "opencl"].contains(backend)));
+
+    for num_terms in [0, 1, 1000, 1 << 20] {
+        let shares = kern.device_shares(num_terms).unwrap();
+        assert_eq!(shares.len(), num_kernels);
+        assert_eq!(shares.iter().sum::<usize>(), num_terms);
+
+        let window_sizes = kern.window_size(num_terms).unwrap();
+        assert_eq!(window_sizes.len(), num_kernels);
+        assert!(window_sizes.iter().all(|&size| (1..=10).contains(&size)));
+    }
+    // More terms never lead to smaller windows.
+    let small = kern.window_size(1000).unwrap();
+    let large = kern.window_size(1 << 20).unwrap();
+    assert!(small.iter().zip(&large).all(|(s, l)| s <= l));
+}
+
+#[test]
+fn gpu_check_on_curve() {
+    use ark_ec::AffineRepr;
+
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let mut rng = rand::thread_rng();
+
+    let mut points = Vec::new();
+    let mut expected = Vec::new();
+    for i in 0..1000 {
+        let point = G1Affine::rand(&mut rng);
+        let (x, y) = point.xy().unwrap();
+        match i % 4 {
+            // A tampered y coordinate.
+            1 => {
+                points.push(G1Affine::new_unchecked(*x, *y + Fq::ONE));
+                expected.push(false);
+            }
+            // A tampered x coordinate.
+            2 => {
+                points.push(G1Affine::new_unchecked(x.double(), *y));
+                expected.push(false);
+            }
+            _ => {
+                points.push(point);
+                expected.push(true);
+            }
+        }
+    }
+    points.push(G1Affine::identity());
+    expected.push(true);
+
+    let gpu = kern.check_on_curve(&points).unwrap();
+    assert_eq!(expected, gpu);
+    for (point, on_curve) in points.iter().zip(&gpu) {
+        assert_eq!(point.is_on_curve(), *on_curve);
+    }
+
+    assert!(kern.check_on_curve(&[]).unwrap().is_empty());
+}
+
+#[test]
+fn gpu_multiexp_sparse_consistency() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let samples = 1 << 12;
+    let bases = Arc::new(
+        (0..samples)
+            .map(|_| G1Affine::rand(&mut rng))
+            .collect::<Vec<_>>(),
+    );
+    // Only about one in a hundred exponents is non-zero, the last one is
+    // always set.
+    let dense = (0..samples)
+        .map(|i| {
+            if i == samples - 1 || rng.gen_range(0..100) == 0 {
+                Fr::rand(&mut rng)
+            } else {
+                Fr::zero()
+            }
+        })
+        .collect::<Vec<_>>();
+    let nonzero = dense
+        .iter()
+        .enumerate()
+        .filter(|(_, exp)| !exp.is_zero())
+        .map(|(index, exp)| (index, exp.to_repr()))
+        .collect::<Vec<_>>();
+
+    let sparse = kern
+        .multiexp_sparse(&pool, bases.clone(), &nonzero)
+        .unwrap();
+    let exps = Arc::new(dense.iter().map(|exp| exp.to_repr()).collect());
+    let dense = kern.multiexp(&pool, bases.clone(), exps, 0).unwrap();
+    assert_eq!(dense.into_affine(), sparse.into_affine());
+
+    assert!(kern
+        .multiexp_sparse(&pool, bases.clone(), &[])
+        .unwrap()
+        .is_zero());
+    let unsorted = [nonzero[1], nonzero[0]];
+    assert!(kern
+        .multiexp_sparse(&pool, bases.clone(), &unsorted)
+        .is_err());
+    let duplicate = [nonzero[0], nonzero[0]];
+    assert!(kern
+        .multiexp_sparse(&pool, bases.clone(), &duplicate)
+        .is_err());
+    let out_of_range = [(samples, nonzero[0].1)];
+    assert!(kern.multiexp_sparse(&pool, bases, &out_of_range).is_err());
+}
+
+#[test]
+fn gpu_multiexp_with_table_consistency() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let samples = 1 << 10;
+    let bases = (0..samples)
+        .map(|_| G1Affine::rand(&mut rng))
+        .collect::<Vec<_>>();
+    let mut exps = (0..samples)
+        .map(|_| Fr::rand(&mut rng).to_repr())
+        .collect::<Vec<_>>();
+    exps[0] = Fr::zero().to_repr();
+    exps[1] = (-Fr::from(1u64)).to_repr();
+    let cpu = multiexp_cpu(
+        &pool,
+        (Arc::new(bases.clone()), 0),
+        FullDensity,
+        Arc::new(exps.clone()),
+    )
+    .wait()
+    .unwrap();
+
+    for window_size in [1, 4, 7] {
+        let unsigned = BaseTable::precompute(&bases, window_size).unwrap();
+        let signed = BaseTable::precompute_signed(&bases, window_size).unwrap();
+        assert_eq!(unsigned.len(), samples);
+        assert!(!unsigned.is_signed());
+
+        let gpu = kern.multiexp_with_table(&unsigned, &exps).unwrap();
+        assert_eq!(gpu.into_affine(), cpu.into_affine());
+        let gpu = kern.multiexp_with_table(&signed, &exps).unwrap();
+        assert_eq!(gpu.into_affine(), cpu.into_affine());
+
+        // The signed table holds the multiples up to half of the window, the
+        // unsigned one all of them but zero.
+        let multiple_size = std::mem::size_of::<G1Affine>();
+        assert_eq!(
+            unsigned.memory_size(),
+            samples * ((1 << window_size) - 1) * multiple_size
+        );
+        if window_size == 1 {
+            assert!(!signed.is_signed());
+            assert_eq!(signed.memory_size(), unsigned.memory_size());
+        } else {
+            assert!(signed.is_signed());
+            assert_eq!(
+                signed.memory_size(),
+                samples * (1 << (window_size - 1)) * multiple_size
+            );
+            assert!(signed.memory_size() < unsigned.memory_size());
+        }
+    }
+
+    let table = BaseTable::precompute(&bases, 4).unwrap();
+    assert!(matches!(
+        kern.multiexp_with_table(&table, &exps[1..]),
+        Err(EcError::DimensionMismatch { .. })
+    ));
+    assert!(BaseTable::precompute(&bases, 0).is_err());
+    assert!(BaseTable::precompute_signed(&bases, MAX_WINDOW_SIZE + 1).is_err());
+}
+
+#[test]
+fn gpu_multiexp_with_table_identity_handling() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let samples = 1 << 10;
+    let bases = (0..samples)
+        .map(|i| {
+            if i % 7 == 0 {
+                G1Affine::identity()
+            } else {
+                G1Affine::rand(&mut rng)
+            }
+        })
+        .collect::<Vec<_>>();
+    let exps = (0..samples)
+        .map(|_| Fr::rand(&mut rng).to_repr())
+        .collect::<Vec<_>>();
+    let cpu = multiexp_cpu(
+        &pool,
+        (Arc::new(bases.clone()), 0),
+        FullDensity,
+        Arc::new(exps.clone()),
+    )
+    .wait()
+    .unwrap();
+
+    // The bases at infinity have no multiples.
+    let num_finite = bases.iter().filter(|base| !base.is_identity()).count();
+    let table = BaseTable::precompute(&bases, 4).unwrap();
+    assert_eq!(table.len(), samples);
+    assert_eq!(
+        table.memory_size(),
+        num_finite * 15 * std::mem::size_of::<G1Affine>()
+    );
+    let signed = BaseTable::precompute_signed(&bases, 4).unwrap();
+    for table in [&table, &signed] {
+        let gpu = kern.multiexp_with_table(table, &exps).unwrap();
+        assert_eq!(gpu.into_affine(), cpu.into_affine());
+    }
+
+    kern.set_identity_handling(IdentityHandling::Reject);
+    assert!(matches!(
+        kern.multiexp_with_table(&table, &exps),
+        Err(EcError::Simple(_))
+    ));
+}
+
+#[test]
+fn gpu_multiexp_estimate_time() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+
+    let check_estimates = |kern: &MultiexpKernel<G1Affine>| {
+        let estimates = (0..=24)
+            .map(|log_terms| kern.estimate_time(1 << log_terms).unwrap())
+            .collect::<Vec<_>>();
+        assert!(estimates.iter().all(|estimate| !estimate.is_zero()));
+        assert!(estimates.windows(2).all(|pair| pair[0] <= pair[1]));
+    };
+    check_estimates(&kern);
+    kern.calibrate().expect("Calibration failed!");
+    check_estimates(&kern);
+}
+
+/// An allocator that counts its allocations.
+#[derive(Default)]
+struct CountingAllocator {
+    allocations: AtomicUsize,
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.allocations.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[test]
+fn gpu_multiexp_host_allocator() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let allocator = Arc::new(CountingAllocator::default());
+    kern.set_host_allocator(allocator.clone());
+
+    let mut rng = rand::thread_rng();
+    let samples = 1 << 10;
+    let bases = Arc::new(
+        (0..samples)
+            .map(|_| G1Affine::rand(&mut rng))
+            .collect::<Vec<_>>(),
+    );
+    let exps = Arc::new(
+        (0..samples)
+            .map(|_| Fr::rand(&mut rng).to_repr())
+            .collect::<Vec<_>>(),
+    );
+
+    let gpu = multiexp_gpu(
+        &pool,
+        (bases.clone(), 0),
+        FullDensity,
+        exps.clone(),
+        &mut kern,
+    )
+    .unwrap();
+    let cpu = multiexp_cpu(&pool, (bases, 0), FullDensity, exps)
+        .wait()
+        .unwrap();
+    assert_eq!(cpu.into_affine(), gpu.into_affine());
+    // The bases were converted into their GPU representation in buffers of
+    // the custom allocator.
+    assert!(allocator.allocations.load(Ordering::SeqCst) > 0);
+}
+
+#[test]
+fn gpu_multiexp_progressive() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+
+    let mut rng = rand::thread_rng();
+    let samples = 1 << 12;
+    let bases = Arc::new(
+        (0..samples)
+            .map(|_| G1Affine::rand(&mut rng))
+            .collect::<Vec<_>>(),
+    );
+    let exps = Arc::new(
+        (0..samples)
+            .map(|_| Fr::rand(&mut rng).to_repr())
+            .collect::<Vec<_>>(),
+    );
+
+    let expected = kern
+        .multiexp(&pool, bases.clone(), exps.clone(), 0)
+        .unwrap();
+    let mut seen = vec![false; kern.num_kernels()];
+    let mut sum = G1Projective::zero();
+    for partial in kern.multiexp_progressive(bases, exps) {
+        let (device, partial) = partial.unwrap();
+        assert!(!seen[device], "device {} yielded twice", device);
+        seen[device] = true;
+        sum += partial;
+    }
+    assert_eq!(expected.into_affine(), sum.into_affine());
+}
+
+#[test]
+fn gpu_multiexp_powers_consistency() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    generate(
+        &ag_build::SourceBuilder::new()
+            .add_fft::<Fr>()
+            .add_multiexp::<G1Affine>(),
+    );
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    // Some bases are the point at infinity, they are skipped.
+    let random_bases = |samples: usize, rng: &mut rand::rngs::ThreadRng| {
+        let bases = (0..samples).map(|i| {
+            if i % 11 == 3 {
+                G1Affine::identity()
+            } else {
+                G1Affine::rand(rng)
+            }
+        });
+        Arc::new(bases.collect::<Vec<_>>())
+    };
+    for samples in [1, 1000, 1 << 12] {
+        let bases = random_bases(samples, &mut rng);
+        let x = Fr::rand(&mut rng);
+
+        let gpu = kern.multiexp_powers(&pool, bases.clone(), x).unwrap();
+
+        let powers = Arc::new(
+            std::iter::successors(Some(Fr::ONE), |power| Some(*power * x))
+                .take(samples)
+                .map(|power| power.to_repr())
+                .collect::<Vec<_>>(),
+        );
+        let cpu = multiexp_cpu(&pool, (bases, 0), FullDensity, powers)
+            .wait()
+            .unwrap();
+        assert_eq!(cpu.into_affine(), gpu.into_affine());
+    }
+
+    // A budget just above a single chunk forces several chunks, the powers
+    // that stay on the GPU must not be reserved twice.
+    let samples = 1 << 12;
+    let budget =
+        Arc::new(MemoryBudget::new(kern.required_memory(samples / 4) + 1));
+    let mut kern = kern.with_budget(budget.clone());
+    let bases = random_bases(samples, &mut rng);
+    let x = Fr::rand(&mut rng);
+    let gpu = kern.multiexp_powers(&pool, bases.clone(), x).unwrap();
+    let powers = Arc::new(
+        std::iter::successors(Some(Fr::ONE), |power| Some(*power * x))
+            .take(samples)
+            .map(|power| power.to_repr())
+            .collect::<Vec<_>>(),
+    );
+    let cpu = multiexp_cpu(&pool, (bases, 0), FullDensity, powers)
+        .wait()
+        .unwrap();
+    assert_eq!(cpu.into_affine(), gpu.into_affine());
+    assert!(budget.peak() <= budget.limit());
+    assert_eq!(budget.used(), 0);
+
+    kern.set_identity_handling(IdentityHandling::Reject);
+    assert!(matches!(
+        kern.multiexp_powers(&pool, bases, x),
+        Err(EcError::Simple(_))
+    ));
+}
+
+/// Gives all terms to the last device.
+struct LastDeviceSplit;
+
+impl WorkSplitter for LastDeviceSplit {
+    fn split(&self, total: usize, devices: &[DeviceInfo]) -> Vec<usize> {
+        let mut shares = vec![0; devices.len()];
+        *shares.last_mut().unwrap() = total;
+        shares
+    }
+}
+
+#[test]
+fn gpu_multiexp_work_splitter() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    kern.set_work_splitter(LastDeviceSplit);
+    let pool = Worker::new();
+
+    let mut rng = rand::thread_rng();
+    let samples = 1 << 12;
+    let bases = Arc::new(
+        (0..samples)
+            .map(|_| G1Affine::rand(&mut rng))
+            .collect::<Vec<_>>(),
+    );
+    let exps = Arc::new(
+        (0..samples)
+            .map(|_| Fr::rand(&mut rng).to_repr())
+            .collect::<Vec<_>>(),
+    );
+
+    let last = kern.num_kernels() - 1;
+    let mut expected_shares = vec![0; kern.num_kernels()];
+    expected_shares[last] = samples;
+    assert_eq!(kern.device_shares(samples).unwrap(), expected_shares);
+
+    let partials = kern
+        .multiexp_progressive(bases.clone(), exps.clone())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(partials.len(), 1);
+    assert_eq!(partials[0].0, last);
+
+    let cpu = multiexp_cpu(&pool, (bases, 0), FullDensity, exps)
+        .wait()
+        .unwrap();
+    assert_eq!(cpu.into_affine(), partials[0].1.into_affine());
+}
+
+#[test]
+fn gpu_multiexp_bucket_occupancy() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+
+    let mut rng = rand::thread_rng();
+    let samples = 1 << 16;
+    let bases = (0..samples)
+        .map(|_| G1Affine::rand(&mut rng))
+        .collect::<Vec<_>>();
+    let exps = (0..samples)
+        .map(|_| Fr::rand(&mut rng).to_repr())
+        .collect::<Vec<_>>();
+
+    let (gpu, occupancy) =
+        kern.multiexp_with_occupancy(&bases, &exps, false).unwrap();
+    let cpu =
+        multiexp_cpu(&pool, (Arc::new(bases), 0), FullDensity, Arc::new(exps))
+            .wait()
+            .unwrap();
+    assert_eq!(cpu.into_affine(), gpu.into_affine());
+
+    // The most significant window holds the top bits of the exponents, which
+    // are not uniformly distributed as the field order is not a power of two.
+    let (_, windows) = occupancy.windows.split_last().unwrap();
+    let buckets = (1 << occupancy.window_size) - 1;
+    for window in windows {
+        let expected = samples as f64 / (buckets + 1) as f64;
+        assert!((window.mean - expected).abs() < expected * 0.1);
+        assert!(f64::from(window.max) >= window.mean);
+        assert!(f64::from(window.max) < window.mean * 1.5);
+    }
+}
+
+#[test]
+fn gpu_multiexp_signed_bucket_occupancy() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let device = devices[0];
+    let program =
+        ec_gpu_program::load_program!(device).expect("Cannot create program!");
+    let mut kern =
+        SingleMultiexpKernel::<G1Affine>::create(program, device, None)
+            .expect("Cannot initialize kernel!");
+    let mut rng = rand::thread_rng();
+
+    let samples = 1000;
+    let bases = (0..samples)
+        .map(|_| G1Affine::rand(&mut rng))
+        .collect::<Vec<_>>();
+    let exps = (0..samples)
+        .map(|_| Fr::rand(&mut rng).to_repr())
+        .collect::<Vec<_>>();
+    let exp_bits = 64 * exps[0].as_ref().len();
+
+    for window_size in [1, 3, 8] {
+        kern.set_window_size(Some(window_size)).unwrap();
+        let num_windows = (exp_bits + window_size - 1) / window_size;
+        // The windows are numbered from the most significant bit, like on the
+        // GPU.
+        let digit = |exp: &<Fr as PrimeFieldRepr>::Repr, start: usize, len| {
+            (start..start + len).fold(0usize, |acc, bit| {
+                acc << 1 | exp.get_bit(exp_bits - 1 - bit) as usize
+            })
+        };
+        for neg_is_cheap in [false, true] {
+            let (_, occupancy) = kern
+                .multiexp_with_occupancy(&bases, &exps, neg_is_cheap)
+                .unwrap();
+
+            // The same signed recoding as the multiexp kernel.
+            let signed = neg_is_cheap && window_size > 1;
+            let half = 1 << (window_size - 1);
+            let full = 1 << window_size;
+            let mut counts = vec![vec![0u32; full]; num_windows];
+            for exp in &exps {
+                for (window, counts) in counts.iter_mut().enumerate() {
+                    let start = window * window_size;
+                    let len = std::cmp::min(window_size, exp_bits - start);
+                    let mut ind = digit(exp, start, len);
+                    let carry = ind >= half;
+                    if signed
+                        && start + 2 * window_size <= exp_bits
+                        && digit(exp, start + window_size, window_size) >= half
+                    {
+                        ind += 1;
+                    }
+                    if ind > 0 && !(carry && signed) {
+                        counts[ind] += 1;
+                    } else if full > ind && carry && signed {
+                        counts[full - ind] += 1;
+                    }
+                }
+            }
+            let windows = counts
+                .iter()
+                .enumerate()
+                .map(|(window, counts)| {
+                    let len = std::cmp::min(
+                        window_size,
+                        exp_bits - window * window_size,
+                    );
+                    let buckets = if signed && len == window_size {
+                        &counts[1..=half]
+                    } else {
+                        &counts[1..1 << len]
+                    };
+                    let total: u64 =
+                        buckets.iter().map(|&c| u64::from(c)).sum();
+                    WindowOccupancy {
+                        max: buckets.iter().copied().max().unwrap_or(0),
+                        mean: total as f64 / buckets.len() as f64,
+                    }
+                })
+                .collect();
+            let expected = BucketOccupancy {
+                window_size,
+                windows,
+            };
+            assert_eq!(
+                occupancy, expected,
+                "window size {}, neg_is_cheap {}",
+                window_size, neg_is_cheap
+            );
+        }
+    }
+}
+
+#[test]
+fn gpu_multiexp_cpu_same_window() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let device = devices[0];
+    let program =
+        ec_gpu_program::load_program!(device).expect("Cannot create program!");
+    let mut kern =
+        SingleMultiexpKernel::<G1Affine>::create(program, device, None)
+            .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+
+    let mut rng = rand::thread_rng();
+    for samples in [1 << 10, 1 << 14, 1 << 16] {
+        let bases = (0..samples)
+            .map(|_| G1Affine::rand(&mut rng))
+            .collect::<Vec<_>>();
+        let exps = (0..samples)
+            .map(|_| Fr::rand(&mut rng).to_repr())
+            .collect::<Vec<_>>();
+
+        let window = kern.window_size(samples);
+        assert_eq!(window, window_size(samples, kern.work_units()));
+
+        let gpu = kern.multiexp(&bases, &exps).unwrap();
+        let cpu = multiexp_with_window(
+            &pool,
+            (Arc::new(bases), 0),
+            Arc::new(exps),
+            window as u32,
+        )
+        .wait()
+        .unwrap();
+        assert_eq!(cpu.into_affine(), gpu.into_affine());
+    }
+}
+
+#[test]
+fn gpu_multiexp_identity_handling() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    const NUM_TERMS: usize = 1 << 10;
+    let bases = Arc::new(
+        (0..NUM_TERMS)
+            .map(|i| {
+                if i % 7 == 0 {
+                    G1Affine::identity()
+                } else {
+                    G1Affine::rand(&mut rng)
+                }
+            })
+            .collect::<Vec<_>>(),
+    );
+    let exps = Arc::new(
+        (0..NUM_TERMS)
+            .map(|_| Fr::rand(&mut rng).to_repr())
+            .collect::<Vec<_>>(),
+    );
+
+    let cpu =
+        multiexp_cpu(&pool, (bases.clone(), 0), FullDensity, exps.clone())
+            .wait()
+            .unwrap();
+    let gpu = kern
+        .multiexp(&pool, bases.clone(), exps.clone(), 0)
+        .expect("GPU multiexp failed!");
+    assert_eq!(cpu.into_affine(), gpu.into_affine());
+
+    kern.set_identity_handling(IdentityHandling::Reject);
+    assert!(matches!(
+        kern.multiexp(&pool, bases, exps.clone(), 0),
+        Err(EcError::Simple(_))
+    ));
+
+    // Without identity bases, rejecting them changes nothing.
+    let finite_bases = Arc::new(
+        (0..NUM_TERMS)
+            .map(|_| G1Affine::rand(&mut rng))
+            .collect::<Vec<_>>(),
+    );
+    let cpu = multiexp_cpu(
+        &pool,
+        (finite_bases.clone(), 0),
+        FullDensity,
+        exps.clone(),
+    )
+    .wait()
+    .unwrap();
+    let gpu = kern
+        .multiexp(&pool, finite_bases, exps, 0)
+        .expect("GPU multiexp failed!");
+    assert_eq!(cpu.into_affine(), gpu.into_affine());
+}
+
+#[test]
+fn gpu_multiexp_with_occupancy_identity_handling() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let samples = 1 << 10;
+    let bases = (0..samples)
+        .map(|i| {
+            if i % 7 == 0 {
+                G1Affine::identity()
+            } else {
+                G1Affine::rand(&mut rng)
+            }
+        })
+        .collect::<Vec<_>>();
+    let exps = (0..samples)
+        .map(|_| Fr::rand(&mut rng).to_repr())
+        .collect::<Vec<_>>();
+    let cpu = multiexp_cpu(
+        &pool,
+        (Arc::new(bases.clone()), 0),
+        FullDensity,
+        Arc::new(exps.clone()),
+    )
+    .wait()
+    .unwrap();
+
+    let (gpu, _) = kern.multiexp_with_occupancy(&bases, &exps, true).unwrap();
+    assert_eq!(cpu.into_affine(), gpu.into_affine());
+
+    kern.set_identity_handling(IdentityHandling::Reject);
+    assert!(matches!(
+        kern.multiexp_with_occupancy(&bases, &exps, true),
+        Err(EcError::Simple(_))
+    ));
+}
+
+#[test]
+fn gpu_multiexp_from_device_identity_handling() {
+    fil_logger::maybe_init();
+    const LOG_D: usize = 10;
+    let devices = Device::all();
+    generate(
+        &ag_build::SourceBuilder::new()
+            .add_fft::<Fr>()
+            .add_multiexp::<G1Affine>(),
+    );
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let samples = 1 << LOG_D;
+    let srs = (0..samples)
+        .map(|i| {
+            if i % 5 == 0 {
+                G1Affine::identity()
+            } else {
+                G1Affine::rand(&mut rng)
+            }
+        })
+        .collect::<Vec<_>>();
+    let evals = (0..samples).map(|_| Fr::rand(&mut rng)).collect::<Vec<_>>();
+    let domain = Radix2EvaluationDomain::<Fr>::new(samples).unwrap();
+    let exps = domain
+        .ifft(&evals)
+        .iter()
+        .map(PrimeFieldRepr::to_repr)
+        .collect::<Vec<_>>();
+    let cpu = multiexp_cpu(
+        &pool,
+        (Arc::new(srs.clone()), 0),
+        FullDensity,
+        Arc::new(exps),
+    )
+    .wait()
+    .unwrap();
+
+    let coeffs = kern.ifft_to_device(&evals).unwrap();
+    let gpu = kern.multiexp_from_device(&srs, coeffs).unwrap();
+    assert_eq!(cpu.into_affine(), gpu.into_affine());
+
+    kern.set_identity_handling(IdentityHandling::Reject);
+    let coeffs = kern.ifft_to_device(&evals).unwrap();
+    assert!(matches!(
+        kern.multiexp_from_device(&srs, coeffs),
+        Err(EcError::Simple(_))
+    ));
+}
+
+#[test]
+fn gpu_multiexp_masked_consistency() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let num_terms = 3000;
+    let bases = Arc::new(
+        (0..num_terms)
+            .map(|_| G1Affine::rand(&mut rng))
+            .collect::<Vec<_>>(),
+    );
+    let exps = Arc::new(
+        (0..num_terms)
+            .map(|_| Fr::rand(&mut rng).to_repr())
+            .collect::<Vec<_>>(),
+    );
+
+    // The same bases and exponents are used with different masks.
+    let masks = [
+        vec![true; num_terms],
+        vec![false; num_terms],
+        (0..num_terms).map(|i| i % 3 == 0).collect(),
+        (0..num_terms)
+            .map(|_| rng.gen::<bool>())
+            .collect::<Vec<_>>(),
+    ];
+    let handle = kern.upload_bases(bases.clone()).unwrap();
+    for mask in &masks {
+        let masked = kern
+            .multiexp_masked(&pool, bases.clone(), exps.clone(), mask)
+            .unwrap();
+        // With resident bases, only the exponents and the mask are uploaded.
+        let resident = kern
+            .multiexp_masked_with_handle(&pool, &handle, exps.clone(), 0, mask)
+            .unwrap();
+        assert_eq!(masked.into_affine(), resident.into_affine());
+
+        let (selected_bases, selected_exps): (Vec<_>, Vec<_>) = bases
+            .iter()
+            .zip(exps.iter())
+            .zip(mask)
+            .filter(|(_, &selected)| selected)
+            .map(|((base, exp), _)| (*base, *exp))
+            .unzip();
+        let expected = kern
+            .multiexp(
+                &pool,
+                Arc::new(selected_bases),
+                Arc::new(selected_exps),
+                0,
+            )
+            .unwrap();
+        assert_eq!(expected.into_affine(), masked.into_affine());
+    }
+
+    let result =
+        kern.multiexp_masked(&pool, bases, exps, &masks[0][..num_terms - 1]);
+    assert!(matches!(
+        result,
+        Err(EcError::DimensionMismatch {
+            expected,
+            actual
+        }) if expected == num_terms && actual == num_terms - 1
+    ));
+}
+
+#[test]
+fn gpu_multiexp_masked_uneven_chunks() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let device = devices[0];
+    let program =
+        ec_gpu_program::load_program!(device).expect("Cannot create program!");
+    let mut kern =
+        SingleMultiexpKernel::<G1Affine>::create(program, device, None)
+            .expect("Cannot initialize kernel!");
+    kern.set_count_ops(true);
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    // The terms aren't divisible into chunks of the same length, a chunk is
+    // shorter than the others and the ones after it are empty.
+    let window_size = 4;
+    kern.set_window_size(Some(window_size)).unwrap();
+    let num_windows = (256 + window_size - 1) / window_size;
+    let num_chunks = kern.work_units() / num_windows;
+    let num_terms = 3 * num_chunks + 1;
+    let chunk_len = (num_terms + num_chunks - 1) / num_chunks;
+    assert_ne!(num_terms % chunk_len, 0);
+
+    let bases = (0..num_terms)
+        .map(|_| G1Affine::rand(&mut rng))
+        .collect::<Vec<_>>();
+    // With an exponent of one, every selected term is a single mixed
+    // addition.
+    let exps = vec![Fr::from(1u64).to_repr(); num_terms];
+    // The first and the last term of every chunk are selected.
+    let mask = (0..num_terms)
+        .map(|i| i % chunk_len == 0 || i % chunk_len == chunk_len - 1)
+        .collect::<Vec<_>>();
+    let num_selected = mask.iter().filter(|&&selected| selected).count();
+
+    let gpu = kern.multiexp_masked(&bases, &exps, &mask).unwrap();
+    let (selected_bases, selected_exps): (Vec<_>, Vec<_>) = bases
+        .iter()
+        .zip(exps.iter())
+        .zip(&mask)
+        .filter(|(_, &selected)| selected)
+        .map(|((base, exp), _)| (*base, *exp))
+        .unzip();
+    let cpu = multiexp_cpu(
+        &pool,
+        (Arc::new(selected_bases), 0),
+        FullDensity,
+        Arc::new(selected_exps),
+    )
+    .wait()
+    .unwrap();
+    assert_eq!(gpu.into_affine(), cpu.into_affine());
+    assert_eq!(kern.op_count().mixed_additions, num_selected as u64);
+}
+
+#[test]
+fn gpu_multiexp_projective_consistency() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    // Random projective points have a `z` coordinate other than one, a few
+    // bases are at infinity.
+    let num_terms = 3000;
+    let mut projective = (0..num_terms)
+        .map(|_| G1Projective::rand(&mut rng))
+        .collect::<Vec<_>>();
+    projective[7] = G1Projective::zero();
+    projective[num_terms - 1] = G1Projective::zero();
+    let exps = Arc::new(
+        (0..num_terms)
+            .map(|_| Fr::rand(&mut rng).to_repr())
+            .collect::<Vec<_>>(),
+    );
+
+    let result = kern
+        .multiexp_projective(&pool, Arc::new(projective.clone()), exps.clone())
+        .unwrap();
+    let affine = G1Projective::normalize_batch(&projective);
+    let expected = kern
+        .multiexp(&pool, Arc::new(affine), exps.clone(), 0)
+        .unwrap();
+    assert_eq!(expected.into_affine(), result.into_affine());
+
+    // Fewer bases than exponents is an error, not a panic.
+    assert!(matches!(
+        kern.multiexp_projective(
+            &pool,
+            Arc::new(projective[..num_terms - 1].to_vec()),
+            exps.clone()
+        ),
+        Err(EcError::InvalidLength(_))
+    ));
+
+    kern.set_identity_handling(IdentityHandling::Reject);
+    assert!(kern
+        .multiexp_projective(&pool, Arc::new(projective), exps)
+        .is_err());
+}
+
+#[test]
+fn gpu_multiexp_with_handle_consistency() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let load = || {
+        let programs = devices
+            .iter()
+            .map(|device| ec_gpu_program::load_program!(device))
+            .collect::<Result<_, _>>()
+            .expect("Cannot create programs!");
+        MultiexpKernel::<G1Affine>::create(programs, &devices)
+            .expect("Cannot initialize kernel!")
+    };
+    let mut kern = load();
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let num_terms = 5000;
+    let mut bases = (0..num_terms)
+        .map(|_| G1Affine::rand(&mut rng))
+        .collect::<Vec<_>>();
+    bases[17] = G1Affine::identity();
+    let bases = Arc::new(bases);
+    let handle = kern.upload_bases(bases.clone()).unwrap();
+    assert_eq!(handle.len(), num_terms);
+
+    // The same bases are used with different exponents and skips.
+    for (skip, len) in [(0, num_terms), (0, 100), (1000, 3000)] {
+        let exps = Arc::new(
+            (0..len)
+                .map(|_| Fr::rand(&mut rng).to_repr())
+                .collect::<Vec<_>>(),
+        );
+        let expected = kern
+            .multiexp(&pool, bases.clone(), exps.clone(), skip)
+            .unwrap();
+        let result = kern
+            .multiexp_with_handle(&pool, &handle, exps, skip)
+            .unwrap();
+        assert_eq!(result, expected, "mismatch for skip {}", skip);
+    }
+
+    let too_many = Arc::new(vec![Fr::zero().to_repr(); num_terms]);
+    let result = kern.multiexp_with_handle(&pool, &handle, too_many, 1);
+    assert!(matches!(result, Err(EcError::Simple(_))));
+
+    // A handle can't be used with another kernel.
+    let mut other = load();
+    let exps = Arc::new(vec![Fr::zero().to_repr(); num_terms]);
+    let result = other.multiexp_with_handle(&pool, &handle, exps, 0);
+    assert!(matches!(result, Err(EcError::Simple(_))));
+
+    kern.set_identity_handling(IdentityHandling::Reject);
+    assert!(matches!(
+        kern.upload_bases(bases.clone()),
+        Err(EcError::Simple(_))
+    ));
+
+    // Resident bases that leave no room for a multiexp fail right away.
+    let kern = load();
+    let budget =
+        Arc::new(MemoryBudget::new(kern.required_memory(num_terms / 4)));
+    let mut kern = kern.with_budget(budget.clone());
+    assert!(matches!(
+        kern.upload_bases(bases.clone()),
+        Err(EcError::MemoryBudgetExceeded { .. })
+    ));
+    assert_eq!(budget.used(), 0);
+
+    // With enough room, the multiexp doesn't wait for the resident bases.
+    let kern = load();
+    let budget = Arc::new(MemoryBudget::new(
+        kern.num_kernels() * kern.required_memory(num_terms),
+    ));
+    let mut kern = kern.with_budget(budget.clone());
+    let exps = Arc::new(
+        (0..num_terms)
+            .map(|_| Fr::rand(&mut rng).to_repr())
+            .collect::<Vec<_>>(),
+    );
+    let expected = kern
+        .multiexp(&pool, bases.clone(), exps.clone(), 0)
+        .unwrap();
+    let handle = kern.upload_bases(bases).unwrap();
+    let result = kern.multiexp_with_handle(&pool, &handle, exps, 0).unwrap();
+    assert_eq!(result, expected);
+    drop(handle);
+    assert_eq!(budget.used(), 0);
+}
+
+#[test]
+fn gpu_multiexp_reproducible() {
+    use ark_serialize::CanonicalSerialize;
+
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    kern.set_reproducible(true);
+    kern.calibrate().unwrap();
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let num_terms = 1 << 16;
+    let bases = Arc::new(
+        (0..num_terms)
+            .map(|_| G1Affine::rand(&mut rng))
+            .collect::<Vec<_>>(),
+    );
+    let exps = Arc::new(
+        (0..num_terms)
+            .map(|_| Fr::rand(&mut rng).to_repr())
+            .collect::<Vec<_>>(),
+    );
+
+    // The projective coordinates are serialized, as serializing the point
+    // would normalize it.
+    let mut run = || {
+        let result = kern
+            .multiexp(&pool, bases.clone(), exps.clone(), 0)
+            .unwrap();
+        let mut bytes = Vec::new();
+        (result.x, result.y, result.z)
+            .serialize_uncompressed(&mut bytes)
+            .unwrap();
+        bytes
+    };
+    assert_eq!(run(), run());
+
+    // The terms are split evenly, whatever the splitter says.
+    kern.set_work_splitter(ec_gpu_proxy::split::MemorySplit);
+    let shares = kern.device_shares(num_terms).unwrap();
+    assert!(shares.iter().max().unwrap() - shares.iter().min().unwrap() <= 1);
+}
+
+#[test]
+fn gpu_multiexp_window_size_override() {
+    fil_logger::maybe_init();
+    let devices = Device::all();
+    build_multiexp();
+    let programs = devices
+        .iter()
+        .map(|device| ec_gpu_program::load_program!(device))
+        .collect::<Result<_, _>>()
+        .expect("Cannot create programs!");
+    let mut kern = MultiexpKernel::<G1Affine>::create(programs, &devices)
+        .expect("Cannot initialize kernel!");
+    let pool = Worker::new();
+    let mut rng = rand::thread_rng();
+
+    let num_terms = 1 << 16;
+    let bases = Arc::new(
+        (0..num_terms)
+            .map(|_| G1Affine::rand(&mut rng))
+            .collect::<Vec<_>>(),
+    );
+    let exps = Arc::new(
+        (0..num_terms)
+            .map(|_| Fr::rand(&mut rng).to_repr())
+            .collect::<Vec<_>>(),
+    );
+    let heuristic = kern.window_size(num_terms).unwrap();
+    let expected = kern
+        .multiexp(&pool, bases.clone(), exps.clone(), 0)
+        .unwrap();
+
+    for window in [1, 3, 7, MAX_WINDOW_SIZE] {
+        kern.set_window_size(Some(window)).unwrap();
+        assert!(kern
+            .window_size(num_terms)
+            .unwrap()
+            .iter()
+            .all(|&w| w == window));
+        let result = kern
+            .multiexp(&pool, bases.clone(), exps.clone(), 0)
+            .unwrap();
+        assert_eq!(result.into_affine(), expected.into_affine());
+    }
+
+    // Invalid window sizes are rejected and keep the previous one.
+    for window in [0, MAX_WINDOW_SIZE + 1, 1000] {
+        assert!(matches!(
+            kern.set_window_size(Some(window)),
+            Err(EcError::Simple(_))
+        ));
+        assert!(kern
+            .window_size(num_terms)
+            .unwrap()
+            .iter()
+            .all(|&w| w == MAX_WINDOW_SIZE));
+    }
+
+    kern.set_window_size(None).unwrap();
+    assert_eq!(kern.window_size(num_terms).unwrap(), heuristic);
+}